@@ -0,0 +1,36 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use storage::{BlobKey, BlobStorageError};
+use thiserror::Error;
+
+/// Errors that can occur archiving and rehydrating records.
+#[derive(Debug, Error, Diagnostic)]
+pub enum ArchiveError {
+  /// The database failed.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// The blob storage failed, or an archive blob is missing.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Storage(#[from] BlobStorageError),
+
+  /// An archive blob couldn't be read.
+  #[error("Failed to read archive blob {0}")]
+  Read(BlobKey, #[source] std::io::Error),
+
+  /// A record couldn't be serialized into, or deserialized from, an archive
+  /// blob.
+  #[error("Failed to (de)serialize archived record")]
+  Serialization(#[source] serde_json::Error),
+
+  /// A tombstone points at a blob which doesn't contain its record.
+  #[error("Archived record {id} is missing from blob {key}")]
+  MissingRecord {
+    /// The ID of the archived record
+    id:  String,
+    /// The blob the tombstone points at
+    key: BlobKey,
+  },
+}
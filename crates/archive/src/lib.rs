@@ -0,0 +1,410 @@
+//! Moving old database records to blob storage.
+//!
+//! [`Archive`] pairs a [`Database`] with a [`BlobStorage`] as a cold tier.
+//! [`Archive::archive`] moves records older than an [`ArchivePolicy`]
+//! threshold into newline-delimited JSON blobs under
+//! `{prefix}/{batch}.ndjson`, leaving a [`Tombstone`] row for each in place
+//! of the record. The prefix defaults to `archive/{table}`.
+//!
+//! Only reading through [`Archive::get`] rehydrates an archived record: it
+//! is read back from its blob, reinserted, and its tombstone marked as
+//! rehydrated. Other reads, e.g. [`Database::find_by_index`] or
+//! [`Database::list`], go straight to the database and don't see archived
+//! records at all. A rehydrated record isn't archived again until the
+//! policy's threshold has passed since it was rehydrated, so records read
+//! through the archive stay in the database while they are in use. Blobs are
+//! kept after their records are rehydrated, so they can be expired with a
+//! lifecycle rule on the bucket.
+//!
+//! Records are written as the database stores them: stamped with their
+//! [version](model::Model::VERSION), so they are upgraded when rehydrated
+//! after the model changes, and with encrypted fields encrypted by the
+//! [`FieldCipher`] given to [`Archive::with_field_cipher`].
+
+mod error;
+#[cfg(test)]
+mod tests;
+mod tombstone;
+
+use std::{collections::HashSet, fmt, ops::Bound, sync::Arc, time::Duration};
+
+use belt::Belt;
+use chrono::{DateTime, Utc};
+use db::{
+  Clock, Database, DatabaseError, FieldCipher, SystemClock, decrypt_fields,
+  encrypt_fields,
+};
+use model::{FromVersionedJson, IndexValue, Model, RecordId, stamp_version};
+use sha2::{Digest, Sha256};
+use storage::{BlobKey, BlobStorage, UploadOptions};
+use tracing::{debug, warn};
+
+pub use self::{error::ArchiveError, tombstone::Tombstone};
+
+/// Which records [`Archive::archive`] moves to blob storage.
+#[derive(Debug, Clone)]
+pub struct ArchivePolicy<M: Model> {
+  /// A [`Timestamp`](model::IndexKind::Timestamp) index giving each record's
+  /// age, e.g. when it was created or last active.
+  pub index:      M::IndexSelector,
+  /// Records whose index key is further than this in the past are archived.
+  pub older_than: Duration,
+  /// The most records written to a single blob.
+  pub batch_size: usize,
+}
+
+impl<M: Model> ArchivePolicy<M> {
+  /// Creates a new [`ArchivePolicy`] archiving records whose `index` key is
+  /// more than `older_than` ago, 1000 to a blob.
+  #[must_use]
+  pub const fn new(index: M::IndexSelector, older_than: Duration) -> Self {
+    Self {
+      index,
+      older_than,
+      batch_size: 1000,
+    }
+  }
+
+  /// Sets the most records written to a single blob.
+  #[must_use]
+  pub const fn with_batch_size(mut self, batch_size: usize) -> Self {
+    self.batch_size = batch_size;
+    self
+  }
+}
+
+/// Moves records of a model to blob storage and back.
+pub struct Archive<M: Model> {
+  db:           Database<M>,
+  tombstones:   Database<Tombstone>,
+  storage:      BlobStorage,
+  prefix:       String,
+  clock:        Arc<dyn Clock>,
+  field_cipher: Option<Arc<dyn FieldCipher>>,
+}
+
+impl<M: Model> Clone for Archive<M> {
+  fn clone(&self) -> Self {
+    Self {
+      db:           self.db.clone(),
+      tombstones:   self.tombstones.clone(),
+      storage:      self.storage.clone(),
+      prefix:       self.prefix.clone(),
+      clock:        self.clock.clone(),
+      field_cipher: self.field_cipher.clone(),
+    }
+  }
+}
+
+impl<M: Model> fmt::Debug for Archive<M> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Archive")
+      .field("prefix", &self.prefix)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<M: Model> Archive<M> {
+  /// Creates a new [`Archive`], keeping tombstones in `tombstones` and
+  /// storing blobs under `archive/{table}`.
+  #[must_use]
+  pub fn new(
+    db: Database<M>,
+    tombstones: Database<Tombstone>,
+    storage: BlobStorage,
+  ) -> Self {
+    Self {
+      db,
+      tombstones,
+      storage,
+      prefix: format!("archive/{}", M::TABLE_NAME),
+      clock: SystemClock::shared(),
+      field_cipher: None,
+    }
+  }
+
+  /// Sets the key prefix archive blobs are stored under.
+  #[must_use]
+  pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+    self.prefix = prefix.into();
+    self
+  }
+
+  /// Sets the clock used to decide which records are old enough to archive.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// Sets the cipher encrypting the model's encrypted fields in archive
+  /// blobs, which should be the one the database uses. Models with
+  /// encrypted fields can't be archived without one.
+  #[must_use]
+  pub fn with_field_cipher(
+    mut self,
+    field_cipher: Arc<dyn FieldCipher>,
+  ) -> Self {
+    self.field_cipher = Some(field_cipher);
+    self
+  }
+
+  /// Moves every record older than the `policy` threshold to blob storage,
+  /// returning how many were archived.
+  ///
+  /// Records rehydrated more recently than the threshold are skipped, even
+  /// if the policy's index says they are old enough, so they aren't moved
+  /// straight back out while in use.
+  ///
+  /// Records are fetched a batch at a time. Each batch is written to its
+  /// blob before any of its records are tombstoned, and each record is
+  /// tombstoned before it is deleted, so an interrupted run never loses a
+  /// record; it may leave a record both live and tombstoned, in which case
+  /// the live record wins.
+  pub async fn archive(
+    &self,
+    policy: &ArchivePolicy<M>,
+  ) -> Result<usize, ArchiveError> {
+    let now = self.clock.now();
+    let cutoff = clock::before(now, policy.older_than);
+    let upper = IndexValue::new_timestamp(&cutoff);
+    let limit = u32::try_from(policy.batch_size.max(1)).unwrap_or(u32::MAX);
+    debug!(%cutoff, "archiving records");
+
+    let mut archived = 0;
+    let mut lower = None;
+    loop {
+      // claimed rather than found, to fetch at most a batch; archived
+      // records are deleted, so the next claim starts after them
+      let batch = self
+        .db
+        .claim_by_index_range(
+          policy.index,
+          lower.as_ref().map_or(Bound::Unbounded, Bound::Excluded),
+          Bound::Excluded(&upper),
+          limit,
+          &|_| {},
+        )
+        .await?;
+      let Some(last) = batch.last() else {
+        break;
+      };
+
+      let rehydrated = self.recently_rehydrated(&batch, cutoff).await?;
+      let to_archive: Vec<M> = batch
+        .iter()
+        .filter(|model| !rehydrated.contains(&model.id()))
+        .cloned()
+        .collect();
+      if !rehydrated.is_empty() {
+        // skipped records stay in the database, so the next claim has to
+        // start after them
+        debug!(skipped = rehydrated.len(), "skipping rehydrated records");
+        lower = M::indices()
+          .get(policy.index)
+          .and_then(|index| index.extract(last).into_iter().next());
+        if lower.is_none() {
+          break;
+        }
+      }
+      if !to_archive.is_empty() {
+        archived += self.archive_batch(&to_archive, now).await?;
+      }
+      if batch.len() < limit as usize {
+        break;
+      }
+    }
+    Ok(archived)
+  }
+
+  /// Returns the IDs of the records in `batch` which were rehydrated after
+  /// `cutoff`.
+  async fn recently_rehydrated(
+    &self,
+    batch: &[M],
+    cutoff: DateTime<Utc>,
+  ) -> Result<HashSet<RecordId<M>>, ArchiveError> {
+    let ids: Vec<_> = batch.iter().map(|m| tombstone_id::<M>(m.id())).collect();
+    let tombstones = self.tombstones.get_many(&ids).await?;
+    Ok(
+      batch
+        .iter()
+        .zip(tombstones)
+        .filter(|(_, tombstone)| {
+          tombstone
+            .as_ref()
+            .and_then(|t| t.rehydrated_at)
+            .is_some_and(|at| at > cutoff)
+        })
+        .map(|(model, _)| model.id())
+        .collect(),
+    )
+  }
+
+  async fn archive_batch(
+    &self,
+    batch: &[M],
+    now: DateTime<Utc>,
+  ) -> Result<usize, ArchiveError> {
+    let key = BlobKey::new(format!(
+      "{}/{}.ndjson",
+      self.prefix,
+      RecordId::<Tombstone>::new()
+    ));
+    let mut lines = Vec::new();
+    for model in batch {
+      serde_json::to_writer(&mut lines, &self.encode(model)?)
+        .map_err(ArchiveError::Serialization)?;
+      lines.push(b'\n');
+    }
+
+    debug!(%key, count = batch.len(), "writing archive blob");
+    self
+      .storage
+      .put_stream(&key, Box::pin(Belt::from(lines)), UploadOptions::default())
+      .await?;
+
+    let mut archived = 0;
+    for model in batch {
+      let tombstone = Tombstone {
+        id:            tombstone_id::<M>(model.id()),
+        table:         M::TABLE_NAME.to_owned(),
+        blob:          key.as_str().to_owned(),
+        archived_at:   now,
+        rehydrated_at: None,
+      };
+      self.tombstones.upsert(&tombstone).await?;
+      match self.db.delete(model.id()).await {
+        Ok(()) => archived += 1,
+        // deleted concurrently, so it mustn't come back on rehydration
+        Err(DatabaseError::NotFound(_)) => {
+          self.tombstones.delete(tombstone.id).await?;
+        }
+        Err(e) => return Err(e.into()),
+      }
+    }
+    Ok(archived)
+  }
+
+  /// Retrieves a record by its ID, rehydrating it from blob storage if it
+  /// was archived.
+  pub async fn get(&self, id: RecordId<M>) -> Result<Option<M>, ArchiveError> {
+    if let Some(model) = self.db.get(id).await? {
+      return Ok(Some(model));
+    }
+    let Some(mut tombstone) =
+      self.tombstones.get(tombstone_id::<M>(id)).await?
+    else {
+      return Ok(None);
+    };
+    if tombstone.rehydrated_at.is_some() {
+      // rehydrated, then deleted
+      return Ok(None);
+    }
+
+    let key = BlobKey::new(tombstone.blob.clone());
+    debug!(%id, %key, "rehydrating archived record");
+    let model = self.read_archived(id, &key).await?;
+
+    if let Err(e) = self.db.insert(&model).await {
+      // rehydrated concurrently
+      if let Some(model) = self.db.get(id).await? {
+        return Ok(Some(model));
+      }
+      warn!(%id, error = %e, "failed to rehydrate archived record");
+      return Err(e.into());
+    }
+    tombstone.rehydrated_at = Some(self.clock.now());
+    self.tombstones.upsert(&tombstone).await?;
+    Ok(Some(model))
+  }
+
+  /// Retrieves a record by its ID, rehydrating it if it was archived, and
+  /// returning an error if not found.
+  pub async fn get_or_error(&self, id: RecordId<M>) -> Result<M, ArchiveError> {
+    self
+      .get(id)
+      .await?
+      .ok_or_else(|| DatabaseError::NotFound(id.to_string()).into())
+  }
+
+  /// Returns whether the record with the given ID is archived, without
+  /// rehydrating it.
+  pub async fn is_archived(
+    &self,
+    id: RecordId<M>,
+  ) -> Result<bool, ArchiveError> {
+    let tombstone = self.tombstones.get(tombstone_id::<M>(id)).await?;
+    Ok(tombstone.is_some_and(|t| t.rehydrated_at.is_none()))
+  }
+
+  async fn read_archived(
+    &self,
+    id: RecordId<M>,
+    key: &BlobKey,
+  ) -> Result<M, ArchiveError> {
+    let stream = self.storage.get_stream(key).await?;
+    let bytes = Belt::from_response_stream(stream)
+      .collect_bytes()
+      .await
+      .map_err(|e| ArchiveError::Read(key.clone(), e))?;
+
+    for line in bytes.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+      let model = self.decode(line)?;
+      if model.id() == id {
+        return Ok(model);
+      }
+    }
+    Err(ArchiveError::MissingRecord {
+      id:  id.to_string(),
+      key: key.clone(),
+    })
+  }
+
+  /// Serializes a model as the database stores it, stamped with its version
+  /// and with its encrypted fields encrypted.
+  fn encode(&self, model: &M) -> Result<serde_json::Value, ArchiveError> {
+    let mut data =
+      serde_json::to_value(model).map_err(ArchiveError::Serialization)?;
+    stamp_version::<M>(&mut data);
+    if let Some(cipher) = self.field_cipher()? {
+      encrypt_fields::<M>(cipher, &mut data)?;
+    }
+    Ok(data)
+  }
+
+  /// Deserializes a model written by [`encode`](Self::encode), decrypting
+  /// it and upgrading it from the version it was archived at.
+  fn decode(&self, line: &[u8]) -> Result<M, ArchiveError> {
+    let mut data: serde_json::Value =
+      serde_json::from_slice(line).map_err(ArchiveError::Serialization)?;
+    if let Some(cipher) = self.field_cipher()? {
+      decrypt_fields::<M>(cipher, &mut data)?;
+    }
+    M::from_versioned_json(data).map_err(ArchiveError::Serialization)
+  }
+
+  /// The cipher for the model's encrypted fields, if it has any.
+  fn field_cipher(&self) -> Result<Option<&dyn FieldCipher>, ArchiveError> {
+    if M::ENCRYPTED_FIELDS.is_empty() {
+      return Ok(None);
+    }
+    let cipher = self.field_cipher.as_deref().ok_or_else(|| {
+      DatabaseError::Serialization(miette::miette!(
+        "model {} has encrypted fields but no field cipher is configured",
+        M::TABLE_NAME
+      ))
+    })?;
+    Ok(Some(cipher))
+  }
+}
+
+/// The ID of the tombstone standing in for the record `id` of model `M`,
+/// derived from the model's table as well as the ID, so tombstones of
+/// different models never collide.
+fn tombstone_id<M: Model>(id: RecordId<M>) -> RecordId<Tombstone> {
+  let digest = Sha256::digest(format!("{}/{id}", M::TABLE_NAME).as_bytes());
+  let mut bytes = [0; 16];
+  bytes.copy_from_slice(&digest[..16]);
+  RecordId::from_ulid_u128(u128::from_be_bytes(bytes))
+}
@@ -0,0 +1,365 @@
+use std::{sync::Arc, time::Duration};
+
+use belt::Belt;
+use chrono::{DateTime, Utc};
+use db::{Clock, Database, DatabaseError, FieldCipher, ManualClock};
+use model::{IndexValue, Model, RecordId};
+use serde::{Deserialize, Serialize};
+use storage::{BlobKey, BlobStorage, UploadOptions};
+
+use crate::{Archive, ArchiveError, ArchivePolicy, Tombstone, tombstone_id};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "events",
+  index(name = "created_at", kind = timestamp, extract =
+    |m| vec![IndexValue::new_timestamp(&m.created_at)]
+  ),
+)]
+struct Event {
+  #[model(id)]
+  id:         RecordId<Event>,
+  name:       String,
+  created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "notes",
+  version = 1,
+  upgrades = [note_v0_to_v1],
+  index(name = "created_at", kind = timestamp, extract =
+    |m| vec![IndexValue::new_timestamp(&m.created_at)]
+  ),
+)]
+struct Note {
+  #[model(id)]
+  id:         RecordId<Note>,
+  #[model(encrypt)]
+  body:       String,
+  pinned:     bool,
+  created_at: DateTime<Utc>,
+}
+
+/// Version 1 added `pinned`.
+#[allow(clippy::unnecessary_wraps)]
+fn note_v0_to_v1(data: &mut serde_json::Value) -> serde_json::Result<()> {
+  data["pinned"] = serde_json::json!(false);
+  Ok(())
+}
+
+/// Reverses the bytes of each field, which is enough to tell ciphertext
+/// from plaintext.
+struct ReverseCipher;
+
+impl FieldCipher for ReverseCipher {
+  fn encrypt(
+    &self,
+    _table: &str,
+    _field: &str,
+    plaintext: &[u8],
+  ) -> Result<Vec<u8>, DatabaseError> {
+    Ok(plaintext.iter().rev().copied().collect())
+  }
+
+  fn decrypt(
+    &self,
+    _table: &str,
+    _field: &str,
+    ciphertext: &[u8],
+  ) -> Result<Vec<u8>, DatabaseError> {
+    Ok(ciphertext.iter().rev().copied().collect())
+  }
+}
+
+const DAY: Duration = Duration::from_hours(24);
+
+struct Setup {
+  archive:    Archive<Event>,
+  db:         Database<Event>,
+  tombstones: Database<Tombstone>,
+  storage:    BlobStorage,
+  clock:      ManualClock,
+}
+
+async fn setup(count: u128) -> Setup {
+  let clock = ManualClock::default();
+  let db = Database::new_mock();
+  let tombstones = Database::new_mock();
+  let storage = BlobStorage::new_memory();
+
+  // one event a day, oldest first
+  for i in 1..=count {
+    db.insert(&Event {
+      id:         RecordId::from_ulid_u128(i),
+      name:       format!("event {i}"),
+      created_at: clock.now(),
+    })
+    .await
+    .unwrap();
+    clock.advance(DAY);
+  }
+
+  let archive = Archive::new(db.clone(), tombstones.clone(), storage.clone())
+    .with_clock(Arc::new(clock.clone()));
+  Setup {
+    archive,
+    db,
+    tombstones,
+    storage,
+    clock,
+  }
+}
+
+fn policy(older_than_days: u32) -> ArchivePolicy<Event> {
+  ArchivePolicy::new(EventIndexSelector::CreatedAt, DAY * older_than_days)
+}
+
+#[tokio::test]
+async fn test_archive_moves_old_records() {
+  let s = setup(5).await;
+
+  // events 1 to 3 are 3 or more days old
+  let archived = s
+    .archive
+    .archive(&policy(2).with_batch_size(2))
+    .await
+    .unwrap();
+  assert_eq!(archived, 3);
+  assert_eq!(s.db.count().await.unwrap(), 2);
+  assert_eq!(s.tombstones.count().await.unwrap(), 3);
+
+  let stats = s.storage.stats("archive/events/").await.unwrap();
+  assert_eq!(stats.object_count, 2);
+
+  for i in 1..=3 {
+    assert!(
+      s.archive
+        .is_archived(RecordId::from_ulid_u128(i))
+        .await
+        .unwrap()
+    );
+  }
+  assert!(
+    !s.archive
+      .is_archived(RecordId::from_ulid_u128(4))
+      .await
+      .unwrap()
+  );
+
+  // nothing more to archive until time passes
+  assert_eq!(s.archive.archive(&policy(2)).await.unwrap(), 0);
+  s.clock.advance(DAY);
+  assert_eq!(s.archive.archive(&policy(2)).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_get_rehydrates_archived_record() {
+  let s = setup(3).await;
+  let id = RecordId::from_ulid_u128(2);
+  let original = s.db.get_or_error(id).await.unwrap();
+
+  s.archive.archive(&policy(0)).await.unwrap();
+  assert_eq!(s.db.get(id).await.unwrap(), None);
+
+  assert_eq!(s.archive.get(id).await.unwrap(), Some(original.clone()));
+  assert_eq!(s.db.get(id).await.unwrap(), Some(original));
+  assert!(!s.archive.is_archived(id).await.unwrap());
+
+  // the rest of the batch stays archived
+  assert_eq!(s.db.count().await.unwrap(), 1);
+  assert!(
+    s.archive
+      .is_archived(RecordId::from_ulid_u128(1))
+      .await
+      .unwrap()
+  );
+
+  assert_eq!(
+    s.archive.get(RecordId::from_ulid_u128(9)).await.unwrap(),
+    None
+  );
+}
+
+#[tokio::test]
+async fn test_rehydrated_records_arent_archived_again_straight_away() {
+  let s = setup(3).await;
+  let id = RecordId::from_ulid_u128(2);
+  assert_eq!(s.archive.archive(&policy(0)).await.unwrap(), 3);
+  s.archive.get_or_error(id).await.unwrap();
+  s.db
+    .insert(&Event {
+      id:         RecordId::from_ulid_u128(4),
+      name:       "event 4".to_owned(),
+      created_at: clock::before(s.clock.now(), Duration::from_hours(36)),
+    })
+    .await
+    .unwrap();
+
+  // skipped until the threshold has passed since it was rehydrated, without
+  // holding up the records after it
+  s.clock.advance(Duration::from_hours(12));
+  assert_eq!(
+    s.archive
+      .archive(&policy(1).with_batch_size(1))
+      .await
+      .unwrap(),
+    1
+  );
+  assert!(s.db.get(id).await.unwrap().is_some());
+  assert!(!s.archive.is_archived(id).await.unwrap());
+
+  s.clock.advance(DAY);
+  assert_eq!(s.archive.archive(&policy(1)).await.unwrap(), 1);
+  assert!(s.archive.is_archived(id).await.unwrap());
+  assert_eq!(s.archive.get(id).await.unwrap().unwrap().id, id);
+}
+
+#[tokio::test]
+async fn test_deleted_rehydrated_records_stay_deleted() {
+  let s = setup(1).await;
+  let id = RecordId::from_ulid_u128(1);
+  s.archive.archive(&policy(0)).await.unwrap();
+
+  s.archive.get_or_error(id).await.unwrap();
+  s.db.delete(id).await.unwrap();
+  assert_eq!(s.archive.get(id).await.unwrap(), None);
+  assert!(!s.archive.is_archived(id).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_get_reports_missing_blob() {
+  let s = setup(1).await;
+  let id = RecordId::from_ulid_u128(1);
+  s.archive.archive(&policy(0)).await.unwrap();
+
+  let tombstone = s
+    .tombstones
+    .get_or_error(tombstone_id::<Event>(id))
+    .await
+    .unwrap();
+  s.storage
+    .delete(&BlobKey::new(tombstone.blob))
+    .await
+    .unwrap();
+
+  assert!(matches!(
+    s.archive.get(id).await,
+    Err(ArchiveError::Storage(_))
+  ));
+  // the tombstone is kept, so the record isn't silently lost
+  assert!(s.archive.is_archived(id).await.unwrap());
+}
+
+async fn read_blob(storage: &BlobStorage, key: &str) -> String {
+  let stream = storage.get_stream(&BlobKey::new(key)).await.unwrap();
+  let bytes = Belt::from_response_stream(stream)
+    .collect_bytes()
+    .await
+    .unwrap();
+  String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_archive_stores_records_as_the_database_does() {
+  let db = Database::new_mock();
+  let tombstones = Database::new_mock();
+  let storage = BlobStorage::new_memory();
+  let note = Note {
+    id:         RecordId::from_ulid_u128(1),
+    body:       "top secret".to_owned(),
+    pinned:     true,
+    created_at: DateTime::UNIX_EPOCH,
+  };
+  db.insert(&note).await.unwrap();
+  let policy = ArchivePolicy::new(NoteIndexSelector::CreatedAt, DAY);
+
+  // encrypted fields can't be archived without a cipher
+  let archive = Archive::new(db.clone(), tombstones.clone(), storage.clone());
+  assert!(matches!(
+    archive.archive(&policy).await,
+    Err(ArchiveError::Database(DatabaseError::Serialization(_)))
+  ));
+
+  let archive = archive.with_field_cipher(Arc::new(ReverseCipher));
+  assert_eq!(archive.archive(&policy).await.unwrap(), 1);
+
+  let tombstone = tombstones
+    .get_or_error(tombstone_id::<Note>(note.id))
+    .await
+    .unwrap();
+  let blob = read_blob(&storage, &tombstone.blob).await;
+  assert!(!blob.contains("top secret"));
+  let line: serde_json::Value = serde_json::from_str(blob.trim()).unwrap();
+  assert_eq!(line["_version"], 1);
+
+  assert_eq!(archive.get(note.id).await.unwrap(), Some(note));
+}
+
+#[tokio::test]
+async fn test_get_upgrades_records_archived_at_older_versions() {
+  let db = Database::new_mock();
+  let tombstones = Database::new_mock();
+  let storage = BlobStorage::new_memory();
+  let archive = Archive::new(db.clone(), tombstones.clone(), storage.clone())
+    .with_field_cipher(Arc::new(ReverseCipher));
+
+  // a record archived before `pinned` was added, so without a version
+  let id = RecordId::<Note>::from_ulid_u128(1);
+  let mut v0 = serde_json::json!({
+    "id": id,
+    "body": "old",
+    "created_at": DateTime::<Utc>::UNIX_EPOCH,
+  });
+  db::encrypt_fields::<Note>(&ReverseCipher, &mut v0).unwrap();
+  let key = "archive/notes/old.ndjson";
+  let data = Belt::from(format!("{v0}\n").into_bytes());
+  storage
+    .put_stream(&BlobKey::new(key), Box::pin(data), UploadOptions::default())
+    .await
+    .unwrap();
+  tombstones
+    .insert(&Tombstone {
+      id:            tombstone_id::<Note>(id),
+      table:         Note::TABLE_NAME.to_owned(),
+      blob:          key.to_owned(),
+      archived_at:   DateTime::UNIX_EPOCH,
+      rehydrated_at: None,
+    })
+    .await
+    .unwrap();
+
+  let note = archive.get_or_error(id).await.unwrap();
+  assert_eq!(note.body, "old");
+  assert!(!note.pinned);
+}
+
+#[tokio::test]
+async fn test_tombstones_of_different_models_dont_collide() {
+  let s = setup(1).await;
+  let db = Database::new_mock();
+  let note = Note {
+    id:         RecordId::from_ulid_u128(1),
+    body:       "same id as event 1".to_owned(),
+    pinned:     false,
+    created_at: DateTime::UNIX_EPOCH,
+  };
+  db.insert(&note).await.unwrap();
+  let notes = Archive::new(db, s.tombstones.clone(), s.storage.clone())
+    .with_field_cipher(Arc::new(ReverseCipher))
+    .with_clock(Arc::new(s.clock.clone()));
+
+  s.archive.archive(&policy(0)).await.unwrap();
+  notes
+    .archive(&ArchivePolicy::new(
+      NoteIndexSelector::CreatedAt,
+      Duration::ZERO,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(s.tombstones.count().await.unwrap(), 2);
+
+  assert_eq!(notes.get(note.id).await.unwrap(), Some(note));
+  let event = s.archive.get(RecordId::from_ulid_u128(1)).await.unwrap();
+  assert_eq!(event.unwrap().name, "event 1");
+}
@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use model::{Model, RecordId};
+use serde::{Deserialize, Serialize};
+
+/// A record which was moved to an archive blob.
+///
+/// A tombstone's ID is derived from the table and ID of the record it
+/// stands in for, so the tombstones of every archived model can share one
+/// table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(table = "archive_tombstones")]
+pub struct Tombstone {
+  /// Derived from the archived record's table and ID.
+  #[model(id)]
+  pub id:            RecordId<Tombstone>,
+  /// The table the record was archived from.
+  pub table:         String,
+  /// The key of the blob holding the record.
+  pub blob:          String,
+  /// When the record was archived.
+  pub archived_at:   DateTime<Utc>,
+  /// When the record was rehydrated, if it has been since it was archived.
+  /// The tombstone is kept so the record isn't archived again straight
+  /// away; see [`Archive::archive`](crate::Archive::archive).
+  #[serde(default)]
+  pub rehydrated_at: Option<DateTime<Utc>>,
+}
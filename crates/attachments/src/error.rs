@@ -0,0 +1,26 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use storage::BlobStorageError;
+use thiserror::Error;
+
+/// Errors that can occur managing attachments.
+#[derive(Debug, Error, Diagnostic)]
+pub enum AttachmentError {
+  /// The attachment name is empty, or isn't a single key segment.
+  #[error("Invalid attachment name: {0:?}")]
+  InvalidName(String),
+
+  /// The record already has an attachment with the name.
+  #[error("Attachment already exists: {0:?}")]
+  AlreadyExists(String),
+
+  /// The database failed, or the record doesn't exist.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// The blob storage failed, or the attachment doesn't exist.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Storage(#[from] BlobStorageError),
+}
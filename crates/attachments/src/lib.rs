@@ -0,0 +1,216 @@
+//! Blobs attached to database records.
+//!
+//! [`Attachments`] pairs a [`Database`] with a [`BlobStorage`] and stores each
+//! record's attachments, such as an avatar or a build artifact, under
+//! `{prefix}/{id}/{name}`. The prefix defaults to the model's table name.
+//! Deleting a record with [`Attachments::delete_with_attachments`] deletes
+//! its attachments too.
+
+mod error;
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+
+use belt::Belt;
+use db::Database;
+use model::{Model, RecordId};
+use storage::{BlobKey, BlobStorage, BlobStorageError, UploadOptions};
+use tracing::{debug, warn};
+
+pub use self::error::AttachmentError;
+
+/// Manages the blobs attached to records of a model.
+pub struct Attachments<M: Model> {
+  db:      Database<M>,
+  storage: BlobStorage,
+  prefix:  String,
+}
+
+impl<M: Model> Clone for Attachments<M> {
+  fn clone(&self) -> Self {
+    Self {
+      db:      self.db.clone(),
+      storage: self.storage.clone(),
+      prefix:  self.prefix.clone(),
+    }
+  }
+}
+
+impl<M: Model> fmt::Debug for Attachments<M> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Attachments")
+      .field("prefix", &self.prefix)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<M: Model> Attachments<M> {
+  /// Creates a new [`Attachments`], storing blobs under the model's table
+  /// name.
+  #[must_use]
+  pub fn new(db: Database<M>, storage: BlobStorage) -> Self {
+    Self {
+      db,
+      storage,
+      prefix: M::TABLE_NAME.to_owned(),
+    }
+  }
+
+  /// Sets the key prefix attachments are stored under.
+  #[must_use]
+  pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+    self.prefix = prefix.into();
+    self
+  }
+
+  /// Returns the key the attachment `name` of record `id` is stored at.
+  ///
+  /// Names must be non-empty, must not contain `/`, and must not be `.` or
+  /// `..`.
+  pub fn key(
+    &self,
+    id: RecordId<M>,
+    name: &str,
+  ) -> Result<BlobKey, AttachmentError> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+      return Err(AttachmentError::InvalidName(name.to_owned()));
+    }
+    Ok(BlobKey::new(format!("{}{name}", self.record_prefix(id))))
+  }
+
+  fn record_prefix(&self, id: RecordId<M>) -> String {
+    format!("{}/{id}/", self.prefix)
+  }
+
+  /// Stores `data` as the attachment `name` of record `id`.
+  ///
+  /// Fails with [`DatabaseError::NotFound`](db::DatabaseError::NotFound) if
+  /// the record doesn't exist, and with [`AttachmentError::AlreadyExists`] if
+  /// it already has an attachment with that name;
+  /// [`detach`](Self::detach) it first to replace it.
+  pub async fn attach(
+    &self,
+    id: RecordId<M>,
+    name: &str,
+    data: Belt,
+  ) -> Result<(), AttachmentError> {
+    let key = self.key(id, name)?;
+    if !self.db.exists(id).await? {
+      return Err(db::DatabaseError::NotFound(id.to_string()).into());
+    }
+
+    debug!(%key, "storing attachment");
+    let result = self
+      .storage
+      .put_stream(&key, Box::pin(data), UploadOptions {
+        overwrite: false,
+        ..UploadOptions::default()
+      })
+      .await;
+    match result {
+      Ok(()) => Ok(()),
+      Err(BlobStorageError::AlreadyExists(_)) => {
+        Err(AttachmentError::AlreadyExists(name.to_owned()))
+      }
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  /// Deletes the attachment `name` of record `id`, if it exists.
+  pub async fn detach(
+    &self,
+    id: RecordId<M>,
+    name: &str,
+  ) -> Result<(), AttachmentError> {
+    let key = self.key(id, name)?;
+    debug!(%key, "deleting attachment");
+    match self.storage.delete(&key).await {
+      Ok(()) | Err(BlobStorageError::NotFound(_)) => Ok(()),
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  /// Opens the attachment `name` of record `id` for reading.
+  ///
+  /// Fails with [`BlobStorageError::NotFound`] if there is no such
+  /// attachment.
+  pub async fn open_attachment(
+    &self,
+    id: RecordId<M>,
+    name: &str,
+  ) -> Result<Belt, AttachmentError> {
+    let key = self.key(id, name)?;
+    let stream = self.storage.get_stream(&key).await?;
+    Ok(Belt::from_response_stream(stream))
+  }
+
+  /// Returns the names of the attachments of record `id`, in order.
+  pub async fn attachment_names(
+    &self,
+    id: RecordId<M>,
+  ) -> Result<Vec<String>, AttachmentError> {
+    Ok(
+      self
+        .attachment_keys(id)
+        .await?
+        .into_iter()
+        .filter_map(|key| key.as_str().rsplit('/').next().map(str::to_owned))
+        .collect(),
+    )
+  }
+
+  async fn attachment_keys(
+    &self,
+    id: RecordId<M>,
+  ) -> Result<Vec<BlobKey>, AttachmentError> {
+    let prefix = self.record_prefix(id);
+    let mut keys = Vec::new();
+    let mut continuation = None;
+    loop {
+      let page = self.storage.list_page(&prefix, continuation).await?;
+      keys.extend(page.entries.into_iter().map(|entry| entry.key));
+      continuation = page.continuation;
+      if continuation.is_none() {
+        return Ok(keys);
+      }
+    }
+  }
+
+  /// Deletes every attachment of record `id`, leaving the record in place.
+  pub async fn delete_attachments(
+    &self,
+    id: RecordId<M>,
+  ) -> Result<(), AttachmentError> {
+    let keys = self.attachment_keys(id).await?;
+    debug!(count = keys.len(), "deleting attachments");
+
+    for (key, result) in self.storage.delete_many(&keys).await {
+      match result {
+        // already gone, e.g. deleted concurrently
+        Ok(()) | Err(BlobStorageError::NotFound(_)) => (),
+        Err(e) => {
+          warn!(%key, error = %e, "failed to delete attachment");
+          return Err(e.into());
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Deletes record `id` and then its attachments, returning the deleted
+  /// record.
+  ///
+  /// The record is deleted first, so attaches started after it is gone fail.
+  /// An attach which checked the record before it was deleted may still land
+  /// after cleanup, as may any attachment if cleanup fails; call
+  /// [`delete_attachments`](Self::delete_attachments) to remove them.
+  pub async fn delete_with_attachments(
+    &self,
+    id: RecordId<M>,
+  ) -> Result<M, AttachmentError> {
+    let model = self.db.delete_and_return(id).await?;
+    self.delete_attachments(id).await?;
+    Ok(model)
+  }
+}
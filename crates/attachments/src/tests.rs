@@ -0,0 +1,131 @@
+use belt::Belt;
+use db::{Database, DatabaseError};
+use model::{Model, RecordId};
+use serde::{Deserialize, Serialize};
+use storage::{BlobStorage, BlobStorageError};
+
+use crate::{AttachmentError, Attachments};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(table = "artifacts")]
+struct Artifact {
+  #[model(id)]
+  id: RecordId<Artifact>,
+}
+
+async fn setup() -> (Attachments<Artifact>, Artifact) {
+  let db = Database::new_mock();
+  let artifact = Artifact {
+    id: RecordId::from_ulid_u128(1),
+  };
+  db.insert(&artifact).await.unwrap();
+  (Attachments::new(db, BlobStorage::new_memory()), artifact)
+}
+
+async fn read(attachments: &Attachments<Artifact>, name: &str) -> Vec<u8> {
+  attachments
+    .open_attachment(RecordId::from_ulid_u128(1), name)
+    .await
+    .unwrap()
+    .collect_bytes()
+    .await
+    .unwrap()
+    .to_vec()
+}
+
+#[tokio::test]
+async fn test_attach_and_open() {
+  let (attachments, artifact) = setup().await;
+
+  attachments
+    .attach(artifact.id, "build.log", Belt::from("first"))
+    .await
+    .unwrap();
+  attachments.detach(artifact.id, "build.log").await.unwrap();
+  attachments
+    .attach(artifact.id, "build.log", Belt::from("second"))
+    .await
+    .unwrap();
+  attachments
+    .attach(artifact.id, "bundle.tar", Belt::from("tar"))
+    .await
+    .unwrap();
+
+  assert_eq!(read(&attachments, "build.log").await, b"second");
+  assert_eq!(
+    attachments.key(artifact.id, "bundle.tar").unwrap().as_str(),
+    format!("artifacts/{}/bundle.tar", artifact.id)
+  );
+  assert_eq!(attachments.attachment_names(artifact.id).await.unwrap(), [
+    "build.log",
+    "bundle.tar"
+  ]);
+}
+
+#[tokio::test]
+async fn test_attach_does_not_replace() {
+  let (attachments, artifact) = setup().await;
+  attachments
+    .attach(artifact.id, "avatar", Belt::from("first"))
+    .await
+    .unwrap();
+
+  let result = attachments
+    .attach(artifact.id, "avatar", Belt::from("second"))
+    .await;
+  assert!(
+    matches!(result, Err(AttachmentError::AlreadyExists(name)) if name == "avatar")
+  );
+  assert_eq!(read(&attachments, "avatar").await, b"first");
+}
+
+#[tokio::test]
+async fn test_attach_requires_record() {
+  let (attachments, _) = setup().await;
+
+  let result = attachments
+    .attach(RecordId::from_ulid_u128(2), "avatar", Belt::from("x"))
+    .await;
+  assert!(matches!(
+    result,
+    Err(AttachmentError::Database(DatabaseError::NotFound(_)))
+  ));
+}
+
+#[tokio::test]
+async fn test_invalid_names() {
+  let (attachments, artifact) = setup().await;
+
+  for name in ["", "a/b", ".", ".."] {
+    assert!(matches!(
+      attachments.key(artifact.id, name),
+      Err(AttachmentError::InvalidName(_))
+    ));
+  }
+}
+
+#[tokio::test]
+async fn test_delete_with_attachments() {
+  let (attachments, artifact) = setup().await;
+  attachments
+    .attach(artifact.id, "avatar", Belt::from("png"))
+    .await
+    .unwrap();
+
+  let deleted = attachments
+    .delete_with_attachments(artifact.id)
+    .await
+    .unwrap();
+  assert_eq!(deleted, artifact);
+  assert!(
+    attachments
+      .attachment_names(artifact.id)
+      .await
+      .unwrap()
+      .is_empty()
+  );
+  assert!(matches!(
+    attachments.open_attachment(artifact.id, "avatar").await,
+    Err(AttachmentError::Storage(BlobStorageError::NotFound(_)))
+  ));
+}
@@ -0,0 +1,78 @@
+//! Cleanup hooks fired when a [`Belt`] finishes or is cancelled.
+
+use std::{
+  io,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::stream::Stream;
+
+use crate::Belt;
+
+/// When a hook fires.
+#[derive(Clone, Copy)]
+pub(crate) enum Trigger {
+  /// Once the stream yields its end.
+  Complete,
+  /// If the stream is dropped before yielding its end.
+  Cancel,
+}
+
+/// A [`Belt`] with a hook attached.
+pub(crate) struct Hooked {
+  inner:    Belt,
+  hook:     Option<Box<dyn FnOnce() + Send>>,
+  trigger:  Trigger,
+  finished: bool,
+}
+
+impl Hooked {
+  pub(crate) fn new(
+    inner: Belt,
+    trigger: Trigger,
+    hook: impl FnOnce() + Send + 'static,
+  ) -> Self {
+    Self {
+      inner,
+      hook: Some(Box::new(hook)),
+      trigger,
+      finished: false,
+    }
+  }
+
+  fn fire(&mut self) {
+    if let Some(hook) = self.hook.take() {
+      hook();
+    }
+  }
+}
+
+impl Stream for Hooked {
+  type Item = Result<Bytes, io::Error>;
+
+  fn poll_next(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let result = Pin::new(&mut self.inner).poll_next(cx);
+
+    if matches!(result, Poll::Ready(None)) {
+      self.finished = true;
+      if matches!(self.trigger, Trigger::Complete) {
+        self.fire();
+      }
+    }
+
+    result
+  }
+}
+
+impl Drop for Hooked {
+  fn drop(&mut self) {
+    if !self.finished && matches!(self.trigger, Trigger::Cancel) {
+      self.fire();
+    }
+  }
+}
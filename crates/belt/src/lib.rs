@@ -1,27 +1,40 @@
 //! Provides a streaming bytes container.
 
+mod hooks;
+mod tee;
 #[cfg(test)]
 mod tests;
 
 use std::{
+  error::Error,
   fmt, io,
+  path::{Path, PathBuf},
   pin::Pin,
   sync::{
     Arc,
     atomic::{AtomicU64, Ordering},
   },
   task::{Context, Poll},
+  time::Duration,
 };
 
 use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use clock::Clock;
 use futures::{TryStreamExt, stream::Stream};
+#[cfg(feature = "tokio")]
 use tokio::io::AsyncBufRead;
+#[cfg(feature = "tokio")]
 use tokio_util::io::{ReaderStream, StreamReader};
 
+use self::hooks::{Hooked, Trigger};
+pub use self::tee::TEE_BUFFER_CHUNKS;
+
 /// An opaque container for streaming bytes data.
 pub struct Belt {
   inner: Inner,
   count: Arc<AtomicU64>,
+  path:  Option<PathBuf>,
 }
 
 impl fmt::Debug for Belt {
@@ -45,15 +58,47 @@ impl Belt {
     Self {
       inner: Inner::Dynamic(Box::pin(stream)),
       count: Arc::new(AtomicU64::new(0)),
+      path:  None,
     }
   }
 
+  /// Create from a stream of [`Bytes`] with another error type, converting
+  /// its errors with `map_err`.
+  #[must_use]
+  pub fn new_mapped<S, E, F>(stream: S, map_err: F) -> Self
+  where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    F: FnMut(E) -> io::Error + Send + 'static,
+  {
+    Self::new(stream.map_err(map_err))
+  }
+
+  /// Create from a stream of [`Bytes`] with any boxable error type, wrapping
+  /// its errors in [`io::Error::other`].
+  #[must_use]
+  pub fn from_futures_stream<S, E>(stream: S) -> Self
+  where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Into<Box<dyn Error + Send + Sync>> + 'static,
+  {
+    Self::new_mapped(stream, io::Error::other)
+  }
+
+  /// Create from a storage [`ResponseStream`](storage_core::ResponseStream),
+  /// keeping the [`io::ErrorKind`] of its errors.
+  #[cfg(feature = "storage")]
+  #[must_use]
+  pub fn from_response_stream(stream: storage_core::ResponseStream) -> Self {
+    Self::new_mapped(stream, storage_core::BlobStorageError::into_io_error)
+  }
+
   /// Create from a [`Bytes`].
   #[must_use]
   pub fn new_from_bytes(input: Bytes) -> Self {
     Self {
       inner: Inner::Static(Some(input)),
       count: Arc::new(AtomicU64::new(0)),
+      path:  None,
     }
   }
 
@@ -71,6 +116,7 @@ impl Belt {
 
   /// Create a stream from an [`AsyncBufRead`](tokio::io::AsyncBufRead)
   /// implementer.
+  #[cfg(feature = "tokio")]
   #[must_use]
   pub fn new_from_async_buf_read<R>(reader: R) -> Self
   where
@@ -79,12 +125,39 @@ impl Belt {
     Self::new(ReaderStream::new(reader))
   }
 
+  /// Create from the contents of the file at `path`, which is opened when
+  /// the [`Belt`] is first polled.
+  ///
+  /// The path is kept as a [`path_hint`](Self::path_hint), so consumers
+  /// which can copy files directly, like filesystem storage, can skip
+  /// streaming the contents. Consumers using the hint don't poll the
+  /// [`Belt`], so its counter isn't advanced.
+  #[cfg(feature = "tokio")]
+  #[must_use]
+  pub fn from_file(path: impl Into<PathBuf>) -> Self {
+    let path = path.into();
+    let stream = futures::stream::once(tokio::fs::File::open(path.clone()))
+      .map_ok(ReaderStream::new)
+      .try_flatten();
+    Self {
+      path: Some(path),
+      ..Self::new(stream)
+    }
+  }
+
+  /// The path of the file this [`Belt`] streams, if it was created with
+  /// [`from_file`](Self::from_file). Wrapping it, e.g. with
+  /// [`tee`](Self::tee), drops the hint.
+  #[must_use]
+  pub fn path_hint(&self) -> Option<&Path> { self.path.as_deref() }
+
   /// Create an empty stream.
   #[must_use]
   pub fn empty() -> Self {
     Self {
       inner: Inner::Static(None),
       count: Arc::new(AtomicU64::new(0)),
+      path:  None,
     }
   }
 
@@ -92,6 +165,41 @@ impl Belt {
   /// its lifetime.
   pub fn counter(&self) -> Counter { Counter::new(self.count.clone()) }
 
+  /// Get a [`Progress`] tracking the bytes traversing this [`Belt`] over
+  /// time, as measured by `clock`, starting now.
+  #[must_use]
+  pub fn progress(&self, clock: Arc<dyn Clock>) -> Progress {
+    Progress {
+      counter: self.counter(),
+      started: clock.now(),
+      clock,
+    }
+  }
+
+  /// Split into two [`Belt`]s which both yield every chunk of this one,
+  /// without collecting it in memory.
+  ///
+  /// Up to [`TEE_BUFFER_CHUNKS`] chunks are buffered for the slower of the
+  /// two; beyond that, the faster one waits for it to catch up, so both must
+  /// be consumed concurrently. Dropping one lets the other continue alone.
+  #[must_use]
+  pub fn tee(self) -> (Belt, Belt) { tee::tee(self) }
+
+  /// Run `f` once this [`Belt`] yields its end. It never runs if the
+  /// [`Belt`] is dropped first.
+  #[must_use]
+  pub fn on_complete(self, f: impl FnOnce() + Send + 'static) -> Self {
+    Self::new(Hooked::new(self, Trigger::Complete, f))
+  }
+
+  /// Run `f` if this [`Belt`] is dropped before yielding its end, e.g. to
+  /// clean up after a cancelled transfer. It never runs if the [`Belt`]
+  /// completes, even if it yielded an error along the way.
+  #[must_use]
+  pub fn on_drop(self, f: impl FnOnce() + Send + 'static) -> Self {
+    Self::new(Hooked::new(self, Trigger::Cancel, f))
+  }
+
   /// Collect a [`Belt`] into a single [`Bytes`].
   pub async fn collect_bytes(self) -> Result<Bytes, io::Error> {
     self
@@ -104,6 +212,7 @@ impl Belt {
   }
 
   /// Convert into an [`AsyncRead`](tokio::io::AsyncRead) implementer.
+  #[cfg(feature = "tokio")]
   pub fn into_async_read(self) -> StreamReader<Belt, Bytes> {
     StreamReader::new(self)
   }
@@ -157,3 +266,42 @@ impl Counter {
   #[must_use]
   pub fn get(&self) -> u64 { self.0.load(Ordering::Relaxed) }
 }
+
+/// Tracks the bytes traversing a [`Belt`] over time.
+pub struct Progress {
+  counter: Counter,
+  clock:   Arc<dyn Clock>,
+  started: DateTime<Utc>,
+}
+
+impl fmt::Debug for Progress {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Progress")
+      .field("bytes", &self.bytes())
+      .field("started", &self.started)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Progress {
+  /// Gets the number of bytes that have traversed the [`Belt`].
+  #[must_use]
+  pub fn bytes(&self) -> u64 { self.counter.get() }
+
+  /// Gets the time elapsed since tracking started.
+  #[must_use]
+  pub fn elapsed(&self) -> Duration {
+    (self.clock.now() - self.started)
+      .to_std()
+      .unwrap_or_default()
+  }
+
+  /// Gets the average throughput since tracking started, in bytes per
+  /// second, or `None` if no time has elapsed.
+  #[must_use]
+  #[allow(clippy::cast_precision_loss)]
+  pub fn bytes_per_second(&self) -> Option<f64> {
+    let elapsed = self.elapsed().as_secs_f64();
+    (elapsed > 0.0).then(|| self.bytes() as f64 / elapsed)
+  }
+}
@@ -0,0 +1,163 @@
+//! Splitting a [`Belt`] between two consumers.
+
+use std::{
+  collections::VecDeque,
+  io,
+  pin::Pin,
+  sync::{Arc, Mutex},
+  task::{Context, Poll, Waker},
+};
+
+use bytes::Bytes;
+use futures::{
+  stream::Stream,
+  task::{self, ArcWake},
+};
+
+use crate::Belt;
+
+/// The number of chunks buffered for the slower consumer of a
+/// [`Belt::tee`] before the faster one waits for it.
+pub const TEE_BUFFER_CHUNKS: usize = 16;
+
+type Item = Result<Bytes, io::Error>;
+
+/// The wakers of both consumers.
+#[derive(Default)]
+struct Wakers(Mutex<[Option<Waker>; 2]>);
+
+impl Wakers {
+  fn register(&self, side: usize, waker: &Waker) {
+    self.0.lock().unwrap()[side] = Some(waker.clone());
+  }
+
+  fn wake_side(&self, side: usize) {
+    let waker = self.0.lock().unwrap()[side].take();
+    if let Some(waker) = waker {
+      waker.wake();
+    }
+  }
+}
+
+// the source is polled with a waker which wakes both consumers, as either
+// one may be the next to poll it
+impl ArcWake for Wakers {
+  fn wake_by_ref(arc_self: &Arc<Self>) {
+    let wakers = std::mem::take(&mut *arc_self.0.lock().unwrap());
+    for waker in wakers.into_iter().flatten() {
+      waker.wake();
+    }
+  }
+}
+
+struct State {
+  source:   Belt,
+  /// Chunks read from the source but not yet yielded, for each consumer.
+  buffers:  [VecDeque<Item>; 2],
+  finished: bool,
+  dropped:  [bool; 2],
+}
+
+struct Shared {
+  state:        Mutex<State>,
+  wakers:       Arc<Wakers>,
+  source_waker: Waker,
+}
+
+/// One consumer of a teed [`Belt`].
+struct TeeHalf {
+  shared: Arc<Shared>,
+  side:   usize,
+}
+
+/// Splits `source` into two [`Belt`]s yielding the same chunks.
+pub(crate) fn tee(source: Belt) -> (Belt, Belt) {
+  let wakers = Arc::new(Wakers::default());
+  let shared = Arc::new(Shared {
+    state: Mutex::new(State {
+      source,
+      buffers: [VecDeque::new(), VecDeque::new()],
+      finished: false,
+      dropped: [false; 2],
+    }),
+    source_waker: task::waker(wakers.clone()),
+    wakers,
+  });
+
+  (
+    Belt::new(TeeHalf {
+      shared: shared.clone(),
+      side:   0,
+    }),
+    Belt::new(TeeHalf { shared, side: 1 }),
+  )
+}
+
+/// Copies a chunk for the other consumer. [`io::Error`] isn't [`Clone`], so
+/// errors are copied by kind and message.
+fn duplicate(item: &Item) -> Item {
+  match item {
+    Ok(bytes) => Ok(bytes.clone()),
+    Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+  }
+}
+
+impl Stream for TeeHalf {
+  type Item = Item;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let side = self.side;
+    let other = 1 - side;
+    let shared = &self.shared;
+    let mut state = shared.state.lock().unwrap();
+
+    // drain our buffer first, making room for the other consumer
+    if let Some(item) = state.buffers[side].pop_front() {
+      shared.wakers.wake_side(other);
+      return Poll::Ready(Some(item));
+    }
+    if state.finished {
+      return Poll::Ready(None);
+    }
+
+    shared.wakers.register(side, cx.waker());
+
+    // wait for the other consumer to catch up before reading further
+    if !state.dropped[other] && state.buffers[other].len() >= TEE_BUFFER_CHUNKS
+    {
+      return Poll::Pending;
+    }
+
+    let mut source_cx = Context::from_waker(&shared.source_waker);
+    match Pin::new(&mut state.source).poll_next(&mut source_cx) {
+      Poll::Ready(Some(item)) => {
+        if !state.dropped[other] {
+          state.buffers[other].push_back(duplicate(&item));
+          shared.wakers.wake_side(other);
+        }
+        Poll::Ready(Some(item))
+      }
+      Poll::Ready(None) => {
+        state.finished = true;
+        shared.wakers.wake_side(other);
+        Poll::Ready(None)
+      }
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+impl Drop for TeeHalf {
+  fn drop(&mut self) {
+    let mut state = self.shared.state.lock().unwrap();
+    state.dropped[self.side] = true;
+    state.buffers[self.side].clear();
+    drop(state);
+
+    // the other consumer may be waiting for us to catch up
+    self.shared.wakers.wake_side(1 - self.side);
+  }
+}
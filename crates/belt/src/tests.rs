@@ -1,7 +1,10 @@
-use std::io;
+use std::{io, sync::atomic::AtomicBool};
 
 use bytes::Bytes;
-use futures::stream::{self, StreamExt};
+use futures::{
+  FutureExt,
+  stream::{self, StreamExt},
+};
 use tokio::io::AsyncReadExt;
 
 use super::*;
@@ -38,6 +41,29 @@ async fn test_from_static_slice() {
   assert_eq!(result, Bytes::from_static(data));
 }
 
+#[tokio::test]
+async fn test_from_file() {
+  let dir = tempfile::tempdir().unwrap();
+  let path = dir.path().join("data.bin");
+  std::fs::write(&path, b"file data").unwrap();
+
+  let belt = Belt::from_file(&path);
+  assert_eq!(belt.path_hint(), Some(path.as_path()));
+  let counter = belt.counter();
+  assert_eq!(
+    belt.collect_bytes().await.unwrap(),
+    Bytes::from_static(b"file data")
+  );
+  assert_eq!(counter.get(), 9);
+
+  // wrapping drops the hint
+  assert_eq!(Belt::from_file(&path).on_drop(|| ()).path_hint(), None);
+
+  let missing = Belt::from_file(dir.path().join("missing"));
+  let error = missing.collect_bytes().await.unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::NotFound);
+}
+
 #[tokio::test]
 async fn test_from_static_str() {
   let data: &'static str = "hello rust";
@@ -269,3 +295,112 @@ async fn test_counter_multiple_references() {
   assert_eq!(counter1.get(), 6);
   assert_eq!(counter2.get(), 6);
 }
+
+#[tokio::test]
+async fn test_progress_with_manual_clock() {
+  let clock = clock::ManualClock::default();
+  let belt = Belt::from(vec![0u8; 1000]);
+  let progress = belt.progress(Arc::new(clock.clone()));
+  assert_eq!(progress.bytes_per_second(), None);
+
+  belt.collect_bytes().await.unwrap();
+  clock.advance(Duration::from_secs(4));
+
+  assert_eq!(progress.bytes(), 1000);
+  assert_eq!(progress.elapsed(), Duration::from_secs(4));
+  assert_eq!(progress.bytes_per_second(), Some(250.0));
+}
+
+fn numbered_chunks(count: u8) -> Belt {
+  Belt::new(stream::iter((0..count).map(|i| Ok(Bytes::from(vec![i])))))
+}
+
+#[tokio::test]
+async fn test_tee_yields_identical_bytes() {
+  let (a, b) = numbered_chunks(100).tee();
+  let (a, b) = tokio::join!(a.collect_bytes(), b.collect_bytes());
+
+  let expected: Vec<u8> = (0..100).collect();
+  assert_eq!(a.unwrap(), expected);
+  assert_eq!(b.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn test_tee_applies_backpressure() {
+  let (mut a, mut b) = numbered_chunks(100).tee();
+
+  // the faster consumer stops once the slower one's buffer is full
+  let mut read = 0;
+  while let Some(Some(chunk)) = a.next().now_or_never() {
+    chunk.unwrap();
+    read += 1;
+  }
+  assert_eq!(read, TEE_BUFFER_CHUNKS);
+
+  // and resumes once it catches up
+  b.next().await.unwrap().unwrap();
+  assert!(a.next().now_or_never().is_some());
+}
+
+#[tokio::test]
+async fn test_tee_continues_after_drop() {
+  let (a, b) = numbered_chunks(100).tee();
+  drop(b);
+
+  let expected: Vec<u8> = (0..100).collect();
+  assert_eq!(a.collect_bytes().await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn test_new_mapped() {
+  let chunks = vec![Ok(Bytes::from("ok")), Err("broken")];
+  let belt = Belt::new_mapped(stream::iter(chunks), |e| {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+  });
+
+  let error = belt.collect_bytes().await.unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+  assert_eq!(error.to_string(), "broken");
+}
+
+#[tokio::test]
+async fn test_from_futures_stream() {
+  let chunks: Vec<Result<Bytes, String>> =
+    vec![Ok(Bytes::from("hello ")), Ok(Bytes::from("world"))];
+  let belt = Belt::from_futures_stream(stream::iter(chunks));
+  assert_eq!(
+    belt.collect_bytes().await.unwrap(),
+    Bytes::from("hello world")
+  );
+}
+
+fn flag() -> (Arc<AtomicBool>, impl FnOnce() + Send + 'static) {
+  let flag = Arc::new(AtomicBool::new(false));
+  let set = flag.clone();
+  (flag, move || set.store(true, Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn test_hooks_on_completion() {
+  let (completed, on_complete) = flag();
+  let (dropped, on_drop) = flag();
+  let belt = numbered_chunks(3).on_complete(on_complete).on_drop(on_drop);
+
+  belt.collect_bytes().await.unwrap();
+
+  assert!(completed.load(Ordering::SeqCst));
+  assert!(!dropped.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_hooks_on_cancellation() {
+  let (completed, on_complete) = flag();
+  let (dropped, on_drop) = flag();
+  let mut belt = numbered_chunks(3).on_complete(on_complete).on_drop(on_drop);
+
+  belt.next().await.unwrap().unwrap();
+  drop(belt);
+
+  assert!(!completed.load(Ordering::SeqCst));
+  assert!(dropped.load(Ordering::SeqCst));
+}
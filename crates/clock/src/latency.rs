@@ -0,0 +1,108 @@
+use std::{collections::HashMap, hash::Hash, time::Duration};
+
+use rand::Rng;
+
+/// A distribution of simulated operation latencies.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Latency {
+  /// Operations complete immediately.
+  #[default]
+  None,
+  /// Every operation takes the same time.
+  Fixed(Duration),
+  /// Operations take between `min` and `max`, uniformly.
+  Uniform {
+    /// The shortest latency.
+    min: Duration,
+    /// The longest latency.
+    max: Duration,
+  },
+  /// Operations usually take about `scale`, with a heavy tail of slow
+  /// outliers, like a real network.
+  ///
+  /// Lower `shape`s give heavier tails; a `shape` of about 1.16 has a fifth
+  /// of operations take four fifths of the time.
+  Pareto {
+    /// The shortest, and most common, latency.
+    scale: Duration,
+    /// How quickly the tail thins out. Must be positive.
+    shape: f64,
+    /// The longest latency, capping the tail.
+    max:   Duration,
+  },
+}
+
+impl Latency {
+  /// Draws a latency from the distribution.
+  #[must_use]
+  pub fn sample(&self) -> Duration { self.sample_with(&mut rand::rng()) }
+
+  /// Draws a latency from the distribution with `rng`.
+  #[must_use]
+  pub fn sample_with(&self, rng: &mut impl Rng) -> Duration {
+    match *self {
+      Self::None => Duration::ZERO,
+      Self::Fixed(latency) => latency,
+      Self::Uniform { min, max } if min >= max => min,
+      Self::Uniform { min, max } => rng.random_range(min..=max),
+      Self::Pareto { scale, shape, max } => {
+        // inverse transform sampling, with `u` in (0, 1]
+        let u = 1.0 - rng.random::<f64>();
+        let factor = u.powf(-shape.recip());
+        Duration::try_from_secs_f64(scale.as_secs_f64() * factor)
+          .map_or(max, |latency| latency.min(max))
+      }
+    }
+  }
+}
+
+/// The [`Latency`] of each kind of operation `Op` of a backend.
+///
+/// Operations without their own latency use the default, which is
+/// [`Latency::None`] unless set.
+#[derive(Clone, Debug)]
+pub struct LatencyProfile<Op> {
+  default:    Latency,
+  operations: HashMap<Op, Latency>,
+}
+
+impl<Op> Default for LatencyProfile<Op> {
+  fn default() -> Self {
+    Self {
+      default:    Latency::None,
+      operations: HashMap::new(),
+    }
+  }
+}
+
+impl<Op: Eq + Hash> LatencyProfile<Op> {
+  /// Creates a profile where every operation completes immediately.
+  #[must_use]
+  pub fn new() -> Self { Self::default() }
+
+  /// Sets the latency of operations without their own.
+  #[must_use]
+  pub const fn with_default(mut self, latency: Latency) -> Self {
+    self.default = latency;
+    self
+  }
+
+  /// Sets the latency of `operation`.
+  #[must_use]
+  pub fn with(mut self, operation: Op, latency: Latency) -> Self {
+    self.operations.insert(operation, latency);
+    self
+  }
+
+  /// Returns the latency of `operation`.
+  #[must_use]
+  pub fn latency(&self, operation: &Op) -> &Latency {
+    self.operations.get(operation).unwrap_or(&self.default)
+  }
+
+  /// Draws how long `operation` should take.
+  #[must_use]
+  pub fn sample(&self, operation: &Op) -> Duration {
+    self.latency(operation).sample()
+  }
+}
@@ -0,0 +1,115 @@
+//! Provides a pluggable source of the current time.
+//!
+//! Components that record or compare timestamps take an `Arc<dyn Clock>`,
+//! defaulting to [`SystemClock`]. Tests substitute a [`ManualClock`] and
+//! advance it explicitly to exercise ordering and expiry deterministically.
+//!
+//! In-memory backends can also simulate the time operations take, with a
+//! [`LatencyProfile`] drawing a [`Latency`] per operation.
+//!
+//! Deadlines and cutoffs are computed with [`after`] and [`before`], which
+//! cap durations so huge ones still give sensible times.
+
+mod latency;
+#[cfg(test)]
+mod tests;
+
+use std::{
+  fmt,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+pub use self::latency::{Latency, LatencyProfile};
+
+/// The furthest [`after`] and [`before`] move a time, roughly a thousand
+/// years. Times stay within four-digit years, so their RFC 3339 strings sort
+/// in time order, as timestamp indices rely on.
+pub const MAX_OFFSET: Duration = Duration::from_hours(1000 * 365 * 24);
+
+/// Returns the time `duration` after `time`, with `duration` capped at
+/// [`MAX_OFFSET`], so a huge timeout or ttl means "a long time from now"
+/// rather than a time sorting before the present.
+///
+/// # Panics
+/// Panics if the result is out of range, which it can't be for a time
+/// within a thousand years of the present.
+#[must_use]
+pub fn after(time: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+  time + capped(duration)
+}
+
+/// Returns the time `duration` before `time`, with `duration` capped at
+/// [`MAX_OFFSET`].
+///
+/// # Panics
+/// Panics if the result is out of range, which it can't be for a time
+/// within a thousand years of the present.
+#[must_use]
+pub fn before(time: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+  time - capped(duration)
+}
+
+/// Converts `duration` to a [`chrono::Duration`], capped at [`MAX_OFFSET`]
+fn capped(duration: Duration) -> chrono::Duration {
+  chrono::Duration::from_std(duration.min(MAX_OFFSET))
+    .expect("MAX_OFFSET is in range")
+}
+
+/// A source of the current time.
+pub trait Clock: fmt::Debug + Send + Sync {
+  /// Returns the current time.
+  fn now(&self) -> DateTime<Utc>;
+}
+
+/// A [`Clock`] reading the system time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+  /// Returns a shared [`SystemClock`].
+  #[must_use]
+  pub fn shared() -> Arc<dyn Clock> { Arc::new(SystemClock) }
+}
+
+impl Clock for SystemClock {
+  fn now(&self) -> DateTime<Utc> { Utc::now() }
+}
+
+/// A [`Clock`] which only moves when told to.
+///
+/// Clones share the same time, so a test can keep a clone and advance the
+/// clock it handed to the component under test.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+  now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl Default for ManualClock {
+  fn default() -> Self { Self::new(DateTime::UNIX_EPOCH) }
+}
+
+impl ManualClock {
+  /// Creates a new [`ManualClock`] reading `start`.
+  #[must_use]
+  pub fn new(start: DateTime<Utc>) -> Self {
+    Self {
+      now: Arc::new(Mutex::new(start)),
+    }
+  }
+
+  /// Moves the clock forward by `by`.
+  pub fn advance(&self, by: std::time::Duration) {
+    let by = chrono::Duration::from_std(by).expect("duration out of range");
+    *self.now.lock().unwrap() += by;
+  }
+
+  /// Sets the clock to `now`, which may be in the past.
+  pub fn set(&self, now: DateTime<Utc>) { *self.now.lock().unwrap() = now; }
+}
+
+impl Clock for ManualClock {
+  fn now(&self) -> DateTime<Utc> { *self.now.lock().unwrap() }
+}
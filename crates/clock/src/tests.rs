@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use chrono::DateTime;
+
+use super::*;
+
+#[test]
+fn test_manual_clock_advances() {
+  let clock = ManualClock::default();
+  let shared: Arc<dyn Clock> = Arc::new(clock.clone());
+  assert_eq!(shared.now(), DateTime::UNIX_EPOCH);
+
+  clock.advance(Duration::from_secs(90));
+  assert_eq!(shared.now().timestamp(), 90);
+
+  clock.set(DateTime::from_timestamp(10, 0).unwrap());
+  assert_eq!(shared.now().timestamp(), 10);
+}
+
+#[test]
+fn test_system_clock_moves() {
+  let clock = SystemClock::shared();
+  let before = Utc::now();
+  assert!(clock.now() >= before);
+}
+
+#[test]
+fn test_latency_samples_within_bounds() {
+  let min = Duration::from_millis(5);
+  let max = Duration::from_millis(50);
+
+  assert_eq!(Latency::None.sample(), Duration::ZERO);
+  assert_eq!(Latency::Fixed(min).sample(), min);
+  for _ in 0..100 {
+    let uniform = Latency::Uniform { min, max }.sample();
+    assert!((min..=max).contains(&uniform));
+
+    let pareto = Latency::Pareto {
+      scale: min,
+      shape: 1.16,
+      max,
+    }
+    .sample();
+    assert!((min..=max).contains(&pareto));
+  }
+}
+
+#[test]
+fn test_latency_profile_falls_back_to_default() {
+  let slow = Latency::Fixed(Duration::from_secs(1));
+  let profile = LatencyProfile::new()
+    .with_default(Latency::Fixed(Duration::from_millis(1)))
+    .with("write", slow);
+
+  assert_eq!(profile.latency(&"write"), &slow);
+  assert_eq!(profile.sample(&"read"), Duration::from_millis(1));
+  assert_eq!(LatencyProfile::new().sample(&"read"), Duration::ZERO);
+}
+
+#[test]
+fn test_offsets_are_capped() {
+  let now = DateTime::parse_from_rfc3339("2025-06-01T00:00:00Z")
+    .unwrap()
+    .to_utc();
+  let minute = Duration::from_mins(1);
+  assert_eq!(after(now, minute).timestamp(), now.timestamp() + 60);
+  assert_eq!(before(now, minute).timestamp(), now.timestamp() - 60);
+
+  // huge durations still give times which sort correctly as strings
+  let latest = after(now, Duration::MAX);
+  assert_eq!(latest, now + chrono::Duration::from_std(MAX_OFFSET).unwrap());
+  assert!(latest.to_rfc3339() > now.to_rfc3339());
+  assert!(before(now, Duration::MAX).to_rfc3339() < now.to_rfc3339());
+}
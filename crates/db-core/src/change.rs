@@ -0,0 +1,50 @@
+use model::{Model, RecordId};
+
+/// A committed change to a record of a model, as delivered to subscribers
+/// such as change data capture consumers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeEvent<M: Model> {
+  /// The record was inserted.
+  Inserted(M),
+  /// The record was updated, and is now this.
+  Updated(M),
+  /// The record with this ID was deleted.
+  Deleted(RecordId<M>),
+}
+
+impl<M: Model> ChangeEvent<M> {
+  /// The ID of the changed record.
+  #[must_use]
+  pub fn id(&self) -> RecordId<M> {
+    match self {
+      ChangeEvent::Inserted(model) | ChangeEvent::Updated(model) => model.id(),
+      ChangeEvent::Deleted(id) => *id,
+    }
+  }
+
+  /// What happened to the record: `inserted`, `updated` or `deleted`.
+  #[must_use]
+  pub const fn action(&self) -> &'static str {
+    match self {
+      ChangeEvent::Inserted(_) => "inserted",
+      ChangeEvent::Updated(_) => "updated",
+      ChangeEvent::Deleted(_) => "deleted",
+    }
+  }
+
+  /// The record as it is after the change, or `None` if it was deleted.
+  #[must_use]
+  pub const fn model(&self) -> Option<&M> {
+    match self {
+      ChangeEvent::Inserted(model) | ChangeEvent::Updated(model) => Some(model),
+      ChangeEvent::Deleted(_) => None,
+    }
+  }
+
+  /// The [display label](Model::display_label) of the record after the
+  /// change, or `None` if it was deleted.
+  #[must_use]
+  pub fn label(&self) -> Option<String> {
+    self.model().map(Model::display_label)
+  }
+}
@@ -0,0 +1,93 @@
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use miette::{Context, IntoDiagnostic};
+use model::Model;
+
+use crate::{DatabaseError, DatabaseResult};
+
+/// Encrypts and decrypts the fields of a model marked `#[model(encrypt)]`.
+///
+/// Each field's serialized JSON value is encrypted as a whole, and stored as
+/// a base64 string. The table and field names are passed along so that
+/// implementations can bind them as associated data, preventing ciphertext
+/// from being moved between fields.
+///
+/// Only backends which persist models encrypt them; the mock backend keeps
+/// models in memory as they are.
+pub trait FieldCipher: Send + Sync {
+  /// Encrypt the serialized value of `field` in `table`.
+  fn encrypt(
+    &self,
+    table: &str,
+    field: &str,
+    plaintext: &[u8],
+  ) -> DatabaseResult<Vec<u8>>;
+
+  /// Decrypt the serialized value of `field` in `table`.
+  fn decrypt(
+    &self,
+    table: &str,
+    field: &str,
+    ciphertext: &[u8],
+  ) -> DatabaseResult<Vec<u8>>;
+}
+
+/// Encrypts the [`ENCRYPTED_FIELDS`](Model::ENCRYPTED_FIELDS) of a serialized
+/// model in place.
+///
+/// Missing and null fields are left as they are.
+pub fn encrypt_fields<M: Model>(
+  cipher: &dyn FieldCipher,
+  data: &mut serde_json::Value,
+) -> DatabaseResult<()> {
+  for field in M::ENCRYPTED_FIELDS {
+    let Some(value) = data.get_mut(*field) else {
+      continue;
+    };
+    if value.is_null() {
+      continue;
+    }
+
+    let plaintext = serde_json::to_vec(value)
+      .into_diagnostic()
+      .map_err(DatabaseError::Serialization)?;
+    let ciphertext = cipher.encrypt(M::TABLE_NAME, field, &plaintext)?;
+    *value = serde_json::Value::String(BASE64.encode(ciphertext));
+  }
+  Ok(())
+}
+
+/// Decrypts the [`ENCRYPTED_FIELDS`](Model::ENCRYPTED_FIELDS) of a serialized
+/// model in place.
+///
+/// Missing and null fields are left as they are.
+pub fn decrypt_fields<M: Model>(
+  cipher: &dyn FieldCipher,
+  data: &mut serde_json::Value,
+) -> DatabaseResult<()> {
+  for field in M::ENCRYPTED_FIELDS {
+    let Some(value) = data.get_mut(*field) else {
+      continue;
+    };
+    let encoded = match value {
+      serde_json::Value::Null => continue,
+      serde_json::Value::String(encoded) => encoded,
+      _ => {
+        return Err(DatabaseError::Serialization(miette::miette!(
+          "encrypted field {field} is not a string"
+        )));
+      }
+    };
+
+    let ciphertext = BASE64
+      .decode(encoded.as_bytes())
+      .into_diagnostic()
+      .with_context(|| format!("failed to decode encrypted field {field}"))
+      .map_err(DatabaseError::Serialization)?;
+    let plaintext = cipher.decrypt(M::TABLE_NAME, field, &ciphertext)?;
+    *value = serde_json::from_slice(&plaintext)
+      .into_diagnostic()
+      .with_context(|| format!("failed to parse decrypted field {field}"))
+      .map_err(DatabaseError::Serialization)?;
+  }
+  Ok(())
+}
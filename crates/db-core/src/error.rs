@@ -1,4 +1,5 @@
 use miette::Diagnostic;
+use serde::{Serialize, Serializer, ser::SerializeStruct};
 use thiserror::Error;
 
 /// Errors that can occur during storage operations.
@@ -16,6 +17,19 @@ pub enum DatabaseError {
   #[error("Index {0} is not unique")]
   IndexNotUnique(String),
 
+  /// Index value invalid for the kind of index
+  #[error("Invalid value for index {index}: {value}")]
+  InvalidIndexValue {
+    /// The index the value was supplied for
+    index: String,
+    /// The invalid value
+    value: String,
+  },
+
+  /// Search not enabled for the model
+  #[error("Search is not enabled for table: {0}")]
+  SearchNotEnabled(String),
+
   /// Uniqueness violation
   #[error("Unique constraint violation on index {index}: {value}")]
   UniqueViolation {
@@ -25,10 +39,40 @@ pub enum DatabaseError {
     value: String,
   },
 
+  /// A conditional write found the record changed since it was read
+  #[error("Record {0} was modified since it was read")]
+  Conflict(String),
+
   /// Serialization error
   #[error("Serialization error: {0}")]
   Serialization(#[diagnostic_source] miette::Report),
 
+  /// A stored record couldn't be deserialized into its model
+  ///
+  /// The payload sample is kept out of the message, as it may contain
+  /// sensitive fields.
+  #[error(
+    "Failed to deserialize record {} in table {table}",
+    .id.as_deref().unwrap_or("<unknown>")
+  )]
+  Deserialization {
+    /// The table the record is stored in
+    table:   String,
+    /// The ID of the record, if it was read alongside the payload
+    id:      Option<String>,
+    /// The start of the stored payload, truncated to
+    /// [`PAYLOAD_SAMPLE_LEN`](DatabaseError::PAYLOAD_SAMPLE_LEN) characters
+    payload: String,
+    /// Why deserialization failed
+    #[diagnostic_source]
+    error:   miette::Report,
+  },
+
+  /// An operation which destroys data wholesale was attempted without
+  /// unsafe operations being allowed
+  #[error("Unsafe operation {0} is disabled")]
+  UnsafeOpsDisabled(String),
+
   /// Database error
   #[error("Database error: {0}")]
   Database(#[diagnostic_source] miette::Report),
@@ -37,3 +81,61 @@ pub enum DatabaseError {
   #[error("{0}")]
   Other(#[diagnostic_source] miette::Report),
 }
+
+impl DatabaseError {
+  /// The most characters of a payload kept in
+  /// [`DatabaseError::Deserialization`].
+  pub const PAYLOAD_SAMPLE_LEN: usize = 256;
+
+  /// A [`DatabaseError::Deserialization`] for the record `id` in `table`,
+  /// keeping a sample of `payload`.
+  #[must_use]
+  pub fn deserialization(
+    table: impl Into<String>,
+    id: Option<String>,
+    payload: &str,
+    error: miette::Report,
+  ) -> Self {
+    let payload = match payload.char_indices().nth(Self::PAYLOAD_SAMPLE_LEN) {
+      Some((end, _)) => format!("{}…", &payload[..end]),
+      None => payload.to_owned(),
+    };
+    DatabaseError::Deserialization {
+      table: table.into(),
+      id,
+      payload,
+      error,
+    }
+  }
+
+  /// A stable, machine-readable code identifying the kind of error.
+  #[must_use]
+  pub const fn error_code(&self) -> &'static str {
+    match self {
+      DatabaseError::NotFound(_) => "not_found",
+      DatabaseError::IndexNotFound(_) => "index_not_found",
+      DatabaseError::IndexNotUnique(_) => "index_not_unique",
+      DatabaseError::InvalidIndexValue { .. } => "invalid_index_value",
+      DatabaseError::SearchNotEnabled(_) => "search_not_enabled",
+      DatabaseError::UniqueViolation { .. } => "unique_violation",
+      DatabaseError::Conflict(_) => "conflict",
+      DatabaseError::Serialization(_) => "serialization",
+      DatabaseError::Deserialization { .. } => "deserialization",
+      DatabaseError::UnsafeOpsDisabled(_) => "unsafe_ops_disabled",
+      DatabaseError::Database(_) => "database",
+      DatabaseError::Other(_) => "other",
+    }
+  }
+}
+
+/// Serializes as `{ "code": "...", "message": "..." }`, where `code` is
+/// [`DatabaseError::error_code`] and `message` is the display form of the
+/// error.
+impl Serialize for DatabaseError {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("DatabaseError", 2)?;
+    state.serialize_field("code", self.error_code())?;
+    state.serialize_field("message", &self.to_string())?;
+    state.end()
+  }
+}
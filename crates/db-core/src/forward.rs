@@ -0,0 +1,276 @@
+//! Forwarding implementations of [`DatabaseLike`] for pointer types, so
+//! decorator stacks can be shared and composed without newtypes.
+
+use std::{ops::Bound, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use model::{IndexValue, Model, RecordId};
+
+use crate::{DatabaseLike, DatabaseResult, Page, SchemaDescription};
+
+/// Implements [`DatabaseLike`] for each pointer type, with its generics in
+/// brackets, by forwarding every method, including those with default
+/// implementations, to the pointee.
+macro_rules! forward_database_like {
+  ($([$($generics:tt)*] $ptr:ty),* $(,)?) => {$(
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl<M: Model, $($generics)*> DatabaseLike<M> for $ptr {
+      async fn initialize_schema(&self) -> DatabaseResult<()> {
+        (**self).initialize_schema().await
+      }
+
+      async fn rebuild_indices(&self) -> DatabaseResult<()> {
+        (**self).rebuild_indices().await
+      }
+
+      async fn insert(&self, model: &M) -> DatabaseResult<()> {
+        (**self).insert(model).await
+      }
+
+      async fn update(&self, model: &M) -> DatabaseResult<()> {
+        (**self).update(model).await
+      }
+
+      async fn update_if_unchanged(
+        &self,
+        model: &M,
+        expected_updated_at: DateTime<Utc>,
+      ) -> DatabaseResult<()> {
+        (**self).update_if_unchanged(model, expected_updated_at).await
+      }
+
+      async fn upsert(&self, model: &M) -> DatabaseResult<bool> {
+        (**self).upsert(model).await
+      }
+
+      async fn insert_and_return(&self, model: &M) -> DatabaseResult<M> {
+        (**self).insert_and_return(model).await
+      }
+
+      async fn upsert_and_return(&self, model: &M) -> DatabaseResult<M> {
+        (**self).upsert_and_return(model).await
+      }
+
+      async fn upsert_with(
+        &self,
+        id: RecordId<M>,
+        update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+      ) -> DatabaseResult<M> {
+        (**self).upsert_with(id, update).await
+      }
+
+      async fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
+        (**self).delete(id).await
+      }
+
+      async fn delete_and_return(&self, id: RecordId<M>) -> DatabaseResult<M> {
+        (**self).delete_and_return(id).await
+      }
+
+      async fn updated_at(
+        &self,
+        id: RecordId<M>,
+      ) -> DatabaseResult<Option<DateTime<Utc>>> {
+        (**self).updated_at(id).await
+      }
+
+      async fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
+        (**self).get(id).await
+      }
+
+      async fn get_or_error(&self, id: RecordId<M>) -> DatabaseResult<M> {
+        (**self).get_or_error(id).await
+      }
+
+      async fn get_many(
+        &self,
+        ids: &[RecordId<M>],
+      ) -> DatabaseResult<Vec<Option<M>>> {
+        (**self).get_many(ids).await
+      }
+
+      async fn get_raw(
+        &self,
+        id: RecordId<M>,
+      ) -> DatabaseResult<Option<serde_json::Value>> {
+        (**self).get_raw(id).await
+      }
+
+      async fn get_field(
+        &self,
+        id: RecordId<M>,
+        field_path: &[&str],
+      ) -> DatabaseResult<Option<serde_json::Value>> {
+        (**self).get_field(id, field_path).await
+      }
+
+      async fn get_projected(
+        &self,
+        id: RecordId<M>,
+        fields: &[&str],
+      ) -> DatabaseResult<Option<serde_json::Value>> {
+        (**self).get_projected(id, fields).await
+      }
+
+      async fn find_by_unique_index(
+        &self,
+        selector: M::IndexSelector,
+        key: &IndexValue,
+      ) -> DatabaseResult<Option<M>> {
+        (**self).find_by_unique_index(selector, key).await
+      }
+
+      async fn find_by_unique_index_or_error(
+        &self,
+        selector: M::IndexSelector,
+        key: &IndexValue,
+      ) -> DatabaseResult<M> {
+        (**self).find_by_unique_index_or_error(selector, key).await
+      }
+
+      async fn find_by_index(
+        &self,
+        selector: M::IndexSelector,
+        key: &IndexValue,
+      ) -> DatabaseResult<Vec<M>> {
+        (**self).find_by_index(selector, key).await
+      }
+
+      async fn find_one_by_index(
+        &self,
+        selector: M::IndexSelector,
+        key: &IndexValue,
+      ) -> DatabaseResult<Option<M>> {
+        (**self).find_one_by_index(selector, key).await
+      }
+
+      async fn find_by_index_raw(
+        &self,
+        selector: M::IndexSelector,
+        key: &IndexValue,
+      ) -> DatabaseResult<Vec<serde_json::Value>> {
+        (**self).find_by_index_raw(selector, key).await
+      }
+
+      async fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
+        (**self).list(limit, offset).await
+      }
+
+      async fn list_after(
+        &self,
+        after: Option<RecordId<M>>,
+        limit: u32,
+      ) -> DatabaseResult<Vec<M>> {
+        (**self).list_after(after, limit).await
+      }
+
+      async fn search(
+        &self,
+        query: &str,
+        limit: u32,
+      ) -> DatabaseResult<Vec<M>> {
+        (**self).search(query, limit).await
+      }
+
+      async fn list_page(
+        &self,
+        limit: u32,
+        offset: u32,
+      ) -> DatabaseResult<Page<M>> {
+        (**self).list_page(limit, offset).await
+      }
+
+      async fn list_projected(
+        &self,
+        limit: u32,
+        offset: u32,
+        fields: &[&str],
+      ) -> DatabaseResult<Vec<serde_json::Value>> {
+        (**self).list_projected(limit, offset, fields).await
+      }
+
+      async fn list_all(&self) -> DatabaseResult<Vec<M>> {
+        (**self).list_all().await
+      }
+
+      async fn count(&self) -> DatabaseResult<u64> { (**self).count().await }
+
+      async fn truncate(&self) -> DatabaseResult<()> {
+        (**self).truncate().await
+      }
+
+      async fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+        (**self).sample(n).await
+      }
+
+      async fn estimate_count(&self) -> DatabaseResult<u64> {
+        (**self).estimate_count().await
+      }
+
+      async fn describe_schema(&self) -> DatabaseResult<SchemaDescription> {
+        (**self).describe_schema().await
+      }
+
+      async fn find_by_index_range(
+        &self,
+        selector: M::IndexSelector,
+        lower: Bound<&IndexValue>,
+        upper: Bound<&IndexValue>,
+      ) -> DatabaseResult<Vec<M>> {
+        (**self).find_by_index_range(selector, lower, upper).await
+      }
+
+      async fn claim_by_index_range(
+        &self,
+        selector: M::IndexSelector,
+        lower: Bound<&IndexValue>,
+        upper: Bound<&IndexValue>,
+        limit: u32,
+        claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+      ) -> DatabaseResult<Vec<M>> {
+        (**self)
+          .claim_by_index_range(selector, lower, upper, limit, claim)
+          .await
+      }
+
+      async fn count_by_index(
+        &self,
+        selector: M::IndexSelector,
+        key: &IndexValue,
+      ) -> DatabaseResult<u64> {
+        (**self).count_by_index(selector, key).await
+      }
+
+      async fn increment(
+        &self,
+        id: RecordId<M>,
+        field_path: &[&str],
+        delta: i64,
+      ) -> DatabaseResult<i64> {
+        (**self).increment(id, field_path, delta).await
+      }
+
+      async fn exists(&self, id: RecordId<M>) -> DatabaseResult<bool> {
+        (**self).exists(id).await
+      }
+
+      async fn exists_by_unique_index(
+        &self,
+        selector: M::IndexSelector,
+        key: &IndexValue,
+      ) -> DatabaseResult<bool> {
+        (**self).exists_by_unique_index(selector, key).await
+      }
+    }
+  )*};
+}
+
+// owning pointers only forward trait objects: a blanket impl for `Arc<T>`
+// would shadow the inherent methods of `T`, like the mock's synchronous
+// helpers, on an `Arc<T>`
+forward_database_like!(
+  [T: DatabaseLike<M> + ?Sized] &T,
+  [] Arc<dyn DatabaseLike<M>>,
+  [] Box<dyn DatabaseLike<M>>,
+);
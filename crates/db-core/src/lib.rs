@@ -1,26 +1,112 @@
 //! Trait for a database-like interface for storing domain models.
 
+mod change;
+mod cipher;
 mod error;
+mod forward;
+mod page;
+mod pipeline;
+mod schema;
 
-use model::{IndexValue, Model, RecordId};
+use std::ops::Bound;
 
-pub use self::error::DatabaseError;
+use chrono::{DateTime, Utc};
+pub use maybe_send::MaybeSendSync;
+use model::{IndexDefinition, IndexKey, IndexValue, Model, RecordId};
+
+pub use self::{
+  change::ChangeEvent,
+  cipher::{FieldCipher, decrypt_fields, encrypt_fields},
+  error::DatabaseError,
+  page::Page,
+  pipeline::{IndexPipeline, IndexTransform},
+  schema::{
+    ColumnDescription, IndexTableDescription, SchemaDescription,
+    TableDescription,
+  },
+};
 
 /// The specialized [`DatabaseLike`] result type.
 pub type DatabaseResult<T> = Result<T, DatabaseError>;
 
+/// Parses an index value according to the [`IndexKind`](model::IndexKind) of
+/// its index definition.
+pub fn parse_index_key<M>(
+  def: &IndexDefinition<M>,
+  value: &IndexValue,
+) -> DatabaseResult<IndexKey> {
+  def
+    .kind
+    .parse(value)
+    .ok_or_else(|| DatabaseError::InvalidIndexValue {
+      index: def.name.to_string(),
+      value: value.to_string(),
+    })
+}
+
+/// Derives the keys a record is stored under in an index from its extracted
+/// values.
+///
+/// A record with no values isn't indexed. A
+/// [multi-value](model::IndexKind::MultiValue) index stores one key per
+/// distinct value; any other index stores a single key made from the
+/// segments of all values, so every backend stores the same keys.
+pub fn index_keys<M>(
+  def: &IndexDefinition<M>,
+  values: &[IndexValue],
+) -> DatabaseResult<Vec<IndexKey>> {
+  if values.is_empty() {
+    return Ok(Vec::new());
+  }
+  if !def.kind.is_multi_value() {
+    let value = IndexValue::new(values.iter().flat_map(IndexValue::segments));
+    return Ok(vec![parse_index_key(def, &value)?]);
+  }
+
+  let mut keys = Vec::with_capacity(values.len());
+  for value in values {
+    let key = parse_index_key(def, value)?;
+    if !keys.contains(&key) {
+      keys.push(key);
+    }
+  }
+  Ok(keys)
+}
+
 /// A generic storage interface for models implementing the [`Model`] trait.
-#[async_trait::async_trait]
-pub trait DatabaseLike<M: Model>: Send + Sync {
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait DatabaseLike<M: Model>: MaybeSendSync {
   /// Initialize the storage schema for this model.
   async fn initialize_schema(&self) -> DatabaseResult<()>;
 
+  /// Recompute every index entry from the stored models.
+  ///
+  /// Run this after the way index keys are derived changes, e.g. after
+  /// upgrading to a release that changes the [key
+  /// encoding](model::IndexValue::encode), so existing records can be found
+  /// by their new keys.
+  async fn rebuild_indices(&self) -> DatabaseResult<()>;
+
   /// Insert a new model into storage.
   async fn insert(&self, model: &M) -> DatabaseResult<()>;
 
   /// Update an existing model in storage.
   async fn update(&self, model: &M) -> DatabaseResult<()>;
 
+  /// Update an existing model in storage, unless it was written after
+  /// `expected_updated_at`, as returned by
+  /// [`updated_at`](Self::updated_at) when it was read.
+  ///
+  /// Returns [`DatabaseError::Conflict`] if the stored `updated_at` differs,
+  /// giving optimistic concurrency without a version field. Writes within
+  /// the same tick of the backend's clock can't be told apart.
+  async fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()>;
+
   /// Insert a model if it doesn't exist, or update it if it does.
   ///
   /// Returns `true` if inserted, `false` if updated.
@@ -34,6 +120,32 @@ pub trait DatabaseLike<M: Model>: Send + Sync {
     }
   }
 
+  /// Insert a new model into storage, returning the model as stored.
+  async fn insert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    self.insert(model).await?;
+    self.get_or_error(model.id()).await
+  }
+
+  /// Insert a model if it doesn't exist, or update it if it does, returning
+  /// the model as stored.
+  async fn upsert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    self.upsert(model).await?;
+    self.get_or_error(model.id()).await
+  }
+
+  /// Atomically read, modify and write the model with the given ID,
+  /// returning the model written.
+  ///
+  /// `update` receives the current model, or `None` if there is none, and
+  /// returns the model to store, which must have the same ID. Concurrent
+  /// calls for the same ID are applied one after another, so this is the
+  /// building block for counters and other shared state.
+  async fn upsert_with(
+    &self,
+    id: RecordId<M>,
+    update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+  ) -> DatabaseResult<M>;
+
   /// Delete a model from storage by ID.
   async fn delete(&self, id: RecordId<M>) -> DatabaseResult<()>;
 
@@ -47,6 +159,13 @@ pub trait DatabaseLike<M: Model>: Send + Sync {
   /// Retrieve a model by its ID.
   async fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>>;
 
+  /// When the model with the given ID was last inserted or updated, or
+  /// `None` if it doesn't exist.
+  async fn updated_at(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<DateTime<Utc>>>;
+
   /// Retrieve a model by its ID, returning an error if not found.
   async fn get_or_error(&self, id: RecordId<M>) -> DatabaseResult<M> {
     self
@@ -67,6 +186,51 @@ pub trait DatabaseLike<M: Model>: Send + Sync {
     Ok(results)
   }
 
+  /// Retrieve the serialized form of a model by its ID, without
+  /// deserializing it.
+  ///
+  /// Useful when only part of a large model is needed, or to inspect a
+  /// record which no longer deserializes. Encrypted fields are decrypted.
+  async fn get_raw(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    self.get(id).await?.as_ref().map(to_raw).transpose()
+  }
+
+  /// Retrieve the field at `field_path` of the serialized form of a model by
+  /// its ID, or `None` if the model or the field is missing.
+  ///
+  /// `field_path` names the field as for [`increment`](Self::increment),
+  /// with array elements named by their index, e.g. `&["tags", "0"]`.
+  async fn get_field(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    Ok(
+      self
+        .get_raw(id)
+        .await?
+        .and_then(|data| json_field(data, field_path)),
+    )
+  }
+
+  /// Retrieve only the given top-level `fields` of the serialized form of a
+  /// model by its ID, e.g. to deserialize a [`Projection`](model::Projection).
+  async fn get_projected(
+    &self,
+    id: RecordId<M>,
+    fields: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    Ok(
+      self
+        .get_raw(id)
+        .await?
+        .map(|data| project_fields(data, fields)),
+    )
+  }
+
   /// Find a single model by a unique index.
   async fn find_by_unique_index(
     &self,
@@ -105,9 +269,70 @@ pub trait DatabaseLike<M: Model>: Send + Sync {
     Ok(results.drain(..).next())
   }
 
+  /// Find the serialized forms of all models matching a non-unique index,
+  /// without deserializing them.
+  async fn find_by_index_raw(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    self
+      .find_by_index(selector, key)
+      .await?
+      .iter()
+      .map(to_raw)
+      .collect()
+  }
+
   /// List all models with pagination.
   async fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>>;
 
+  /// List up to `limit` models ordered by ID, starting after the model with
+  /// ID `after`, or from the first if `None`.
+  ///
+  /// Paging with the last ID of each page never skips or repeats a record
+  /// that exists throughout, unlike paging [`list`](Self::list) by offset
+  /// while records are updated.
+  async fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>>;
+
+  /// Search models by the text of their search fields, returning at most
+  /// `limit` results ordered by relevance where supported.
+  ///
+  /// Returns [`DatabaseError::SearchNotEnabled`] if the model has no
+  /// [`SEARCH_FIELDS`](Model::SEARCH_FIELDS).
+  async fn search(&self, query: &str, limit: u32) -> DatabaseResult<Vec<M>>;
+
+  /// List models with pagination, along with the total record count.
+  async fn list_page(
+    &self,
+    limit: u32,
+    offset: u32,
+  ) -> DatabaseResult<Page<M>> {
+    let total = self.count().await?;
+    let items = self.list(limit, offset).await?;
+    Ok(Page::new(items, offset, total))
+  }
+
+  /// List only the given top-level `fields` of the serialized forms of
+  /// models with pagination, in the same order as [`list`](Self::list).
+  async fn list_projected(
+    &self,
+    limit: u32,
+    offset: u32,
+    fields: &[&str],
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    self
+      .list(limit, offset)
+      .await?
+      .iter()
+      .map(|model| Ok(project_fields(to_raw(model)?, fields)))
+      .collect()
+  }
+
   /// List all models without pagination.
   async fn list_all(&self) -> DatabaseResult<Vec<M>> {
     self.list(u32::MAX, 0).await
@@ -116,6 +341,60 @@ pub trait DatabaseLike<M: Model>: Send + Sync {
   /// Count the total number of records in storage.
   async fn count(&self) -> DatabaseResult<u64>;
 
+  /// Delete every record and index entry.
+  ///
+  /// Nothing guards against calling this on a production database; go
+  /// through `Database::truncate`, which refuses unless unsafe operations
+  /// were allowed.
+  async fn truncate(&self) -> DatabaseResult<()>;
+
+  /// Return up to `n` records chosen at random, in no particular order, e.g.
+  /// for spot-checking data.
+  async fn sample(&self, n: u32) -> DatabaseResult<Vec<M>>;
+
+  /// Estimate the total number of records in storage.
+  ///
+  /// Unlike [`count`](Self::count), which may have to scan every record,
+  /// this is cheap on any table size, at the cost of accuracy: backends may
+  /// return a figure from their statistics, which can be stale by however
+  /// many records were written since they were last refreshed. Use it for
+  /// dashboards and pagination hints, not for logic that needs an exact
+  /// count. Defaults to the exact count.
+  async fn estimate_count(&self) -> DatabaseResult<u64> { self.count().await }
+
+  /// Describe the tables storing this model as they are found in the
+  /// database, including their columns, uniqueness constraints and row
+  /// count estimates.
+  async fn describe_schema(&self) -> DatabaseResult<SchemaDescription>;
+
+  /// Find all models whose index key falls within the given bounds, ordered
+  /// by key ascending.
+  ///
+  /// Keys are compared according to the [`IndexKind`](model::IndexKind) of
+  /// the index, so numeric and timestamp indices have correct range
+  /// semantics.
+  async fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>>;
+
+  /// Atomically claim up to `limit` models whose index key falls within the
+  /// given bounds, in key order, applying `claim` to each and saving it.
+  ///
+  /// Concurrent claimers never receive the same model. This is the building
+  /// block for work queues: `claim` should move the model out of the range,
+  /// e.g. by pushing back the timestamp it is indexed by.
+  async fn claim_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+    limit: u32,
+    claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+  ) -> DatabaseResult<Vec<M>>;
+
   /// Count records matching a non-unique index.
   async fn count_by_index(
     &self,
@@ -132,6 +411,19 @@ pub trait DatabaseLike<M: Model>: Send + Sync {
     )
   }
 
+  /// Atomically add `delta` to the integer field at `field_path` of the
+  /// model with the given ID, returning the new value.
+  ///
+  /// `field_path` names the field in the model's serialized form, e.g.
+  /// `&["stats", "views"]`. A missing final field counts as zero; a missing
+  /// parent is an error.
+  async fn increment(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64>;
+
   /// Check if a record exists by ID.
   async fn exists(&self, id: RecordId<M>) -> DatabaseResult<bool>;
 
@@ -144,3 +436,49 @@ pub trait DatabaseLike<M: Model>: Send + Sync {
     Ok(self.find_by_unique_index(selector, key).await?.is_some())
   }
 }
+
+/// Serializes a model into the form returned by
+/// [`DatabaseLike::get_raw`].
+fn to_raw<M: Model>(model: &M) -> DatabaseResult<serde_json::Value> {
+  serde_json::to_value(model)
+    .map_err(|e| DatabaseError::Serialization(miette::Report::from_err(e)))
+}
+
+/// Keeps only the top-level `fields` of serialized model `data`, as for
+/// [`DatabaseLike::get_projected`].
+#[must_use]
+pub fn project_fields(
+  data: serde_json::Value,
+  fields: &[&str],
+) -> serde_json::Value {
+  match data {
+    serde_json::Value::Object(mut all) => fields
+      .iter()
+      .filter_map(|field| Some(((*field).to_owned(), all.remove(*field)?)))
+      .collect(),
+    data => data,
+  }
+}
+
+/// Takes the field at `field_path` out of serialized model `data`, as for
+/// [`DatabaseLike::get_field`].
+#[must_use]
+pub fn json_field(
+  mut data: serde_json::Value,
+  field_path: &[&str],
+) -> Option<serde_json::Value> {
+  for field in field_path {
+    data = match data {
+      serde_json::Value::Object(mut fields) => fields.remove(*field)?,
+      serde_json::Value::Array(mut items) => {
+        let index = field.parse::<usize>().ok()?;
+        if index >= items.len() {
+          return None;
+        }
+        items.swap_remove(index)
+      }
+      _ => return None,
+    };
+  }
+  Some(data)
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// A page of models along with pagination metadata.
+///
+/// Serializes as `{ "items": [...], "total": 42, "has_more": true }`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<M> {
+  /// The models on this page.
+  pub items:    Vec<M>,
+  /// The total number of records across all pages.
+  pub total:    u64,
+  /// Whether there are more records after this page.
+  pub has_more: bool,
+}
+
+impl<M> Page<M> {
+  /// Create a new [`Page`] from the items found at `offset` and the total
+  /// record count.
+  #[must_use]
+  pub fn new(items: Vec<M>, offset: u32, total: u64) -> Self {
+    let has_more = u64::from(offset) + (items.len() as u64) < total;
+    Self {
+      items,
+      total,
+      has_more,
+    }
+  }
+}
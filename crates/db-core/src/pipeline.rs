@@ -0,0 +1,137 @@
+use std::{fmt, ops::Bound, sync::Arc};
+
+use model::{IndexDefinition, IndexValue, Model};
+
+use crate::DatabaseResult;
+
+/// A transform applied to index values before they are stored or queried.
+///
+/// Transforms are applied symmetrically: the same pipeline runs over the
+/// values extracted from a model on write and over the key supplied to an
+/// index query on read, so both sides agree on the normalized form.
+#[async_trait::async_trait]
+pub trait IndexTransform: Send + Sync {
+  /// Transform a single value belonging to the index named `index`.
+  async fn transform(
+    &self,
+    index: &str,
+    value: IndexValue,
+  ) -> DatabaseResult<IndexValue>;
+}
+
+/// Adapts a synchronous function into an [`IndexTransform`].
+struct FnTransform<F>(F);
+
+#[async_trait::async_trait]
+impl<F> IndexTransform for FnTransform<F>
+where
+  F: Fn(&str, IndexValue) -> DatabaseResult<IndexValue> + Send + Sync,
+{
+  async fn transform(
+    &self,
+    index: &str,
+    value: IndexValue,
+  ) -> DatabaseResult<IndexValue> {
+    (self.0)(index, value)
+  }
+}
+
+/// An ordered pipeline of [`IndexTransform`]s.
+#[derive(Clone, Default)]
+pub struct IndexPipeline {
+  transforms: Vec<Arc<dyn IndexTransform>>,
+}
+
+impl fmt::Debug for IndexPipeline {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("IndexPipeline")
+      .field("transforms", &self.transforms.len())
+      .finish()
+  }
+}
+
+impl IndexPipeline {
+  /// Create an empty pipeline, which leaves index values untouched.
+  #[must_use]
+  pub const fn new() -> Self {
+    Self {
+      transforms: Vec::new(),
+    }
+  }
+
+  /// Append a transform to the end of the pipeline.
+  #[must_use]
+  pub fn with_transform<T: IndexTransform + 'static>(
+    mut self,
+    transform: T,
+  ) -> Self {
+    self.transforms.push(Arc::new(transform));
+    self
+  }
+
+  /// Append a synchronous transform function to the end of the pipeline.
+  #[must_use]
+  pub fn with_fn<F>(self, f: F) -> Self
+  where
+    F: Fn(&str, IndexValue) -> DatabaseResult<IndexValue>
+      + Send
+      + Sync
+      + 'static,
+  {
+    self.with_transform(FnTransform(f))
+  }
+
+  /// Whether the pipeline contains no transforms.
+  #[must_use]
+  pub fn is_empty(&self) -> bool { self.transforms.is_empty() }
+
+  /// Run a single value for the index named `index` through the pipeline.
+  pub async fn apply(
+    &self,
+    index: &str,
+    value: IndexValue,
+  ) -> DatabaseResult<IndexValue> {
+    let mut value = value;
+    for transform in &self.transforms {
+      value = transform.transform(index, value).await?;
+    }
+    Ok(value)
+  }
+
+  /// Run the value of a range bound for the index named `index` through the
+  /// pipeline.
+  pub async fn apply_bound(
+    &self,
+    index: &str,
+    bound: Bound<&IndexValue>,
+  ) -> DatabaseResult<Bound<IndexValue>> {
+    Ok(match bound {
+      Bound::Included(value) => {
+        Bound::Included(self.apply(index, value.clone()).await?)
+      }
+      Bound::Excluded(value) => {
+        Bound::Excluded(self.apply(index, value.clone()).await?)
+      }
+      Bound::Unbounded => Bound::Unbounded,
+    })
+  }
+
+  /// Extract the index values for `def` from `model` and run each of them
+  /// through the pipeline.
+  pub async fn extract<M: Model>(
+    &self,
+    def: &IndexDefinition<M>,
+    model: &M,
+  ) -> DatabaseResult<Vec<IndexValue>> {
+    let values = def.extract(model);
+    if self.is_empty() {
+      return Ok(values);
+    }
+
+    let mut results = Vec::with_capacity(values.len());
+    for value in values {
+      results.push(self.apply(def.name, value).await?);
+    }
+    Ok(results)
+  }
+}
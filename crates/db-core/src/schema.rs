@@ -0,0 +1,56 @@
+use model::IndexKind;
+use serde::Serialize;
+
+/// A description of the tables storing a model, as found in the database.
+///
+/// Useful for admin UIs, and for debugging drift between the model's
+/// definition and the tables it is stored in.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SchemaDescription {
+  /// The main table, holding the serialized models.
+  pub main_table:   TableDescription,
+  /// The tables of the model's indices, in definition order.
+  pub index_tables: Vec<IndexTableDescription>,
+}
+
+/// A description of a single table.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TableDescription {
+  /// The qualified name of the table.
+  pub name:           String,
+  /// Whether the table exists.
+  pub exists:         bool,
+  /// The columns of the table, in order. Empty for backends which don't
+  /// store models in tables.
+  pub columns:        Vec<ColumnDescription>,
+  /// An estimate of the number of rows in the table, if known.
+  pub estimated_rows: Option<u64>,
+}
+
+/// A description of a single column of a table.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ColumnDescription {
+  /// The name of the column.
+  pub name:      String,
+  /// The backend's name for the type of the column.
+  pub data_type: String,
+  /// Whether the column may be null.
+  pub nullable:  bool,
+}
+
+/// A description of the table storing an index.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct IndexTableDescription {
+  /// The name of the index.
+  pub index:           String,
+  /// The kind of key stored in the index.
+  pub kind:            IndexKind,
+  /// Whether the model defines the index as unique.
+  pub unique:          bool,
+  /// Whether the database enforces uniqueness of the index keys. Differs
+  /// from [`unique`](Self::unique) when the table has drifted from the
+  /// model's definition.
+  pub enforced_unique: bool,
+  /// The table itself.
+  pub table:           TableDescription,
+}
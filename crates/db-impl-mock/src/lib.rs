@@ -1,24 +1,73 @@
 //! Mock storage implementation for testing.
 
 use std::{
+  cmp::Ordering,
   collections::HashMap,
+  hash::{BuildHasher, RandomState},
   marker::PhantomData,
-  sync::{Arc, RwLock},
+  ops::{Bound, RangeBounds},
+  sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
 
-use db_core::{DatabaseError, DatabaseLike, DatabaseResult};
-use model::{IndexValue, Model, RecordId};
+use chrono::{DateTime, Utc};
+use clock::{Clock, LatencyProfile, SystemClock};
+use db_core::{
+  DatabaseError, DatabaseLike, DatabaseResult, IndexPipeline,
+  IndexTableDescription, SchemaDescription, TableDescription, index_keys,
+  parse_index_key,
+};
+use model::{IndexDefinition, IndexValue, Model, RecordId};
 
 /// In-memory mock database for testing models implementing the [`Model`] trait.
+///
+/// The synchronous inherent methods operate on raw index values; an
+/// [`IndexPipeline`] is only applied when going through [`DatabaseLike`].
 #[derive(Clone)]
 pub struct MockDatabase<M: Model> {
-  inner:    Arc<RwLock<MockDatabaseInner<M>>>,
-  _phantom: PhantomData<M>,
+  inner:          Arc<RwLock<MockDatabaseInner<M>>>,
+  index_pipeline: IndexPipeline,
+  clock:          Arc<dyn Clock>,
+  latency:        LatencyProfile<MockOperation>,
+  /// Serializes [`DatabaseLike::claim_by_index_range`],
+  /// [`DatabaseLike::upsert_with`] and [`DatabaseLike::increment`] calls
+  atomic_lock:    Arc<futures::lock::Mutex<()>>,
+  _phantom:       PhantomData<M>,
+}
+
+/// The kinds of operation a [`MockDatabase`] can be given a latency for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MockOperation {
+  /// Initializing and describing the schema, and rebuilding indices.
+  Schema,
+  /// Inserting a record.
+  Insert,
+  /// Updating a record.
+  Update,
+  /// Deleting a record.
+  Delete,
+  /// Fetching a record by ID, or checking that it exists.
+  Get,
+  /// Finding records by index, listing, searching, sampling and counting.
+  Query,
 }
 
+/// When a record was created and last updated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordTimestamps {
+  /// When the record was inserted.
+  pub created_at: DateTime<Utc>,
+  /// When the record was last inserted or updated.
+  pub updated_at: DateTime<Utc>,
+}
+
+/// Index entries for a single model: (`index_name`, `unique`, `index_key`)
+type IndexEntries = Vec<(&'static str, bool, String)>;
+
 struct MockDatabaseInner<M: Model> {
   /// Main data storage: id -> model
   data:        HashMap<RecordId<M>, M>,
+  /// Record timestamps: id -> timestamps
+  timestamps:  HashMap<RecordId<M>, RecordTimestamps>,
   /// Index storage: (`index_name`, `index_key`) -> `Vec<record_id>`
   indices:     HashMap<(String, String), Vec<RecordId<M>>>,
   /// Tracks whether schema has been initialized
@@ -34,25 +83,72 @@ impl<M: Model> MockDatabase<M> {
   #[must_use]
   pub fn new() -> Self {
     Self {
-      inner:    Arc::new(RwLock::new(MockDatabaseInner {
+      inner:          Arc::new(RwLock::new(MockDatabaseInner {
         data:        HashMap::new(),
+        timestamps:  HashMap::new(),
         indices:     HashMap::new(),
         initialized: false,
       })),
-      _phantom: PhantomData,
+      index_pipeline: IndexPipeline::new(),
+      clock:          SystemClock::shared(),
+      latency:        LatencyProfile::new(),
+      atomic_lock:    Arc::new(futures::lock::Mutex::new(())),
+      _phantom:       PhantomData,
     }
   }
 
+  /// Sets the [`IndexPipeline`] applied to index values on write and to index
+  /// keys on query.
+  #[must_use]
+  pub fn with_index_pipeline(mut self, index_pipeline: IndexPipeline) -> Self {
+    self.index_pipeline = index_pipeline;
+    self
+  }
+
+  /// Sets the [`Clock`] used to timestamp records.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// Sets the [`LatencyProfile`] simulating how long operations take.
+  ///
+  /// Only operations through [`DatabaseLike`] are delayed. The delay comes
+  /// before the operation takes effect, so an operation abandoned by a
+  /// timeout has no effect. Latency isn't simulated on wasm32, which has no
+  /// timer without a runtime.
+  #[must_use]
+  pub fn with_latency(
+    mut self,
+    latency: LatencyProfile<MockOperation>,
+  ) -> Self {
+    self.latency = latency;
+    self
+  }
+
   /// Initialize the mock schema (marks as initialized).
   pub fn initialize_schema(&self) -> DatabaseResult<()> {
-    let mut inner = self.inner.write().unwrap();
+    let mut inner = self.write();
     inner.initialized = true;
     Ok(())
   }
 
   /// Insert a new model into the mock database.
   pub fn insert(&self, model: &M) -> DatabaseResult<()> {
-    let mut inner = self.inner.write().unwrap();
+    self.insert_with_entries(model, &Self::extract_entries(model)?)
+  }
+
+  fn insert_with_entries(
+    &self,
+    model: &M,
+    entries: &IndexEntries,
+  ) -> DatabaseResult<()> {
+    // prepare everything that runs user code before taking the lock, so a
+    // panic can't leave the store half-updated
+    let stored = model.clone();
+    let now = self.clock.now();
+    let mut inner = self.write();
 
     // Check if record already exists
     if inner.data.contains_key(&model.id()) {
@@ -63,44 +159,81 @@ impl<M: Model> MockDatabase<M> {
     }
 
     // Check unique index violations before inserting
-    Self::check_unique_violations(&inner, model, None)?;
+    Self::check_unique_violations(&inner, entries, None)?;
 
     // Insert the model
-    inner.data.insert(model.id(), model.clone());
+    inner.data.insert(model.id(), stored);
+    inner.timestamps.insert(model.id(), RecordTimestamps {
+      created_at: now,
+      updated_at: now,
+    });
 
     // Insert index entries
-    Self::insert_indices_inner(&mut inner, model);
+    Self::insert_indices_inner(&mut inner, model.id(), entries);
 
     Ok(())
   }
 
   /// Update an existing model in the mock database.
   pub fn update(&self, model: &M) -> DatabaseResult<()> {
-    let mut inner = self.inner.write().unwrap();
+    self.update_with_entries(model, &Self::extract_entries(model)?, None)
+  }
+
+  /// Update an existing model in the mock database, unless it was written
+  /// after `expected_updated_at`.
+  pub fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()> {
+    self.update_with_entries(
+      model,
+      &Self::extract_entries(model)?,
+      Some(expected_updated_at),
+    )
+  }
+
+  fn update_with_entries(
+    &self,
+    model: &M,
+    entries: &IndexEntries,
+    expected_updated_at: Option<DateTime<Utc>>,
+  ) -> DatabaseResult<()> {
+    let stored = model.clone();
+    let now = self.clock.now();
+    let mut inner = self.write();
 
     // Check if record exists
-    if !inner.data.contains_key(&model.id()) {
+    let Some(timestamps) = inner.timestamps.get(&model.id()) else {
       return Err(DatabaseError::NotFound(model.id().to_string()));
+    };
+
+    // Check the record hasn't been written since it was read
+    if expected_updated_at.is_some_and(|e| e != timestamps.updated_at) {
+      return Err(DatabaseError::Conflict(model.id().to_string()));
     }
 
     // Check unique index violations (excluding current record)
-    Self::check_unique_violations(&inner, model, Some(model.id()))?;
+    Self::check_unique_violations(&inner, entries, Some(model.id()))?;
 
     // Delete old index entries
     Self::delete_indices_inner(&mut inner, model.id());
 
     // Update the model
-    inner.data.insert(model.id(), model.clone());
+    inner.data.insert(model.id(), stored);
+    if let Some(timestamps) = inner.timestamps.get_mut(&model.id()) {
+      timestamps.updated_at = now;
+    }
 
     // Insert new index entries
-    Self::insert_indices_inner(&mut inner, model);
+    Self::insert_indices_inner(&mut inner, model.id(), entries);
 
     Ok(())
   }
 
   /// Delete a model from the mock database by ID.
   pub fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
-    let mut inner = self.inner.write().unwrap();
+    let mut inner = self.write();
 
     // Check if record exists
     if !inner.data.contains_key(&id) {
@@ -109,6 +242,7 @@ impl<M: Model> MockDatabase<M> {
 
     // Remove from main storage
     inner.data.remove(&id);
+    inner.timestamps.remove(&id);
 
     // Remove from indices
     Self::delete_indices_inner(&mut inner, id);
@@ -118,10 +252,17 @@ impl<M: Model> MockDatabase<M> {
 
   /// Get a model by ID.
   pub fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
-    let inner = self.inner.read().unwrap();
+    let inner = self.read();
     Ok(inner.data.get(&id).cloned())
   }
 
+  /// Get the timestamps of a record by ID.
+  #[must_use]
+  pub fn timestamps(&self, id: RecordId<M>) -> Option<RecordTimestamps> {
+    let inner = self.read();
+    inner.timestamps.get(&id).copied()
+  }
+
   /// Get a model by ID, returning an error if not found.
   pub fn get_or_error(&self, id: RecordId<M>) -> DatabaseResult<M> {
     self
@@ -135,7 +276,7 @@ impl<M: Model> MockDatabase<M> {
     selector: M::IndexSelector,
     key: &IndexValue,
   ) -> DatabaseResult<Option<M>> {
-    let inner = self.inner.read().unwrap();
+    let inner = self.read();
 
     let indices = M::indices();
     let index_def = indices
@@ -146,7 +287,10 @@ impl<M: Model> MockDatabase<M> {
       return Err(DatabaseError::IndexNotUnique(selector.to_string()));
     }
 
-    let index_key = (index_def.name.to_string(), key.to_string());
+    let index_key = (
+      index_def.name.to_string(),
+      parse_index_key(index_def, key)?.to_string(),
+    );
 
     if let Some(record_ids) = inner.indices.get(&index_key)
       && let Some(record_id) = record_ids.first()
@@ -174,14 +318,17 @@ impl<M: Model> MockDatabase<M> {
     selector: M::IndexSelector,
     key: &IndexValue,
   ) -> DatabaseResult<Vec<M>> {
-    let inner = self.inner.read().unwrap();
+    let inner = self.read();
 
     let indices = M::indices();
     let index_def = indices
       .get(selector)
       .ok_or_else(|| DatabaseError::IndexNotFound(selector.to_string()))?;
 
-    let index_key = (index_def.name.to_string(), key.to_string());
+    let index_key = (
+      index_def.name.to_string(),
+      parse_index_key(index_def, key)?.to_string(),
+    );
 
     let mut results = Vec::new();
 
@@ -196,44 +343,209 @@ impl<M: Model> MockDatabase<M> {
     Ok(results)
   }
 
-  /// List all models (no specific ordering in mock).
+  /// Find all models whose index key falls within the given bounds, ordered
+  /// by key ascending.
+  pub fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>> {
+    let inner = self.read();
+
+    let indices = M::indices();
+    let index_def = indices
+      .get(selector)
+      .ok_or_else(|| DatabaseError::IndexNotFound(selector.to_string()))?;
+
+    let parse_bound = |bound: Bound<&IndexValue>| {
+      Ok::<_, DatabaseError>(match bound {
+        Bound::Included(value) => {
+          Bound::Included(parse_index_key(index_def, value)?)
+        }
+        Bound::Excluded(value) => {
+          Bound::Excluded(parse_index_key(index_def, value)?)
+        }
+        Bound::Unbounded => Bound::Unbounded,
+      })
+    };
+    let range = (parse_bound(lower)?, parse_bound(upper)?);
+
+    let mut matches = inner
+      .indices
+      .iter()
+      .filter(|((name, _), _)| name == index_def.name)
+      .filter_map(|((_, key), record_ids)| {
+        let key = index_def.kind.parse_str(key)?;
+        range.contains(&key).then_some((key, record_ids))
+      })
+      .collect::<Vec<_>>();
+    matches
+      .sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    Ok(
+      matches
+        .into_iter()
+        .flat_map(|(_, record_ids)| record_ids)
+        .filter_map(|record_id| inner.data.get(record_id).cloned())
+        .collect(),
+    )
+  }
+
+  /// List all models, ordered by last update descending.
   pub fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
-    let inner = self.inner.read().unwrap();
+    let inner = self.read();
 
-    let results: Vec<M> = inner
-      .data
-      .values()
+    let mut records: Vec<_> = inner.timestamps.iter().collect();
+    // break ties by ID so that the order is stable
+    records.sort_by(|(a_id, a), (b_id, b)| {
+      b.updated_at.cmp(&a.updated_at).then_with(|| b_id.cmp(a_id))
+    });
+
+    let results: Vec<M> = records
+      .into_iter()
       .skip(offset as usize)
       .take(limit as usize)
-      .cloned()
+      .filter_map(|(id, _)| inner.data.get(id).cloned())
       .collect();
 
     Ok(results)
   }
 
+  /// List up to `limit` models ordered by ID, starting after the model with
+  /// ID `after`, or from the first if `None`.
+  pub fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    let inner = self.read();
+
+    let mut ids: Vec<_> = inner
+      .data
+      .keys()
+      .filter(|id| after.is_none_or(|after| **id > after))
+      .collect();
+    ids.sort();
+
+    Ok(
+      ids
+        .into_iter()
+        .take(limit as usize)
+        .filter_map(|id| inner.data.get(id).cloned())
+        .collect(),
+    )
+  }
+
+  /// Return up to `n` records chosen at random.
+  pub fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+    let inner = self.read();
+
+    // order records by a randomly keyed hash of their IDs
+    let state = RandomState::new();
+    let mut records: Vec<_> = inner.data.values().collect();
+    records.sort_by_cached_key(|model| state.hash_one(model.id()));
+
+    Ok(records.into_iter().take(n as usize).cloned().collect())
+  }
+
+  /// Search models with a naive, case-insensitive substring match.
+  ///
+  /// A model matches if every whitespace-separated term in `query` appears in
+  /// at least one of its search fields.
+  pub fn search(&self, query: &str, limit: u32) -> DatabaseResult<Vec<M>> {
+    if M::SEARCH_FIELDS.is_empty() {
+      return Err(DatabaseError::SearchNotEnabled(M::TABLE_NAME.to_string()));
+    }
+
+    let terms: Vec<String> =
+      query.split_whitespace().map(str::to_lowercase).collect();
+
+    let inner = self.read();
+
+    let mut results = Vec::new();
+    for model in inner.data.values() {
+      if results.len() >= limit as usize {
+        break;
+      }
+
+      let haystacks = Self::search_haystacks(model)?;
+      let matches = terms
+        .iter()
+        .all(|term| haystacks.iter().any(|h| h.contains(term.as_str())));
+      if matches {
+        results.push(model.clone());
+      }
+    }
+
+    Ok(results)
+  }
+
   /// Count total number of records.
   pub fn count(&self) -> DatabaseResult<u64> {
-    let inner = self.inner.read().unwrap();
+    let inner = self.read();
     Ok(inner.data.len().try_into().unwrap())
   }
 
+  /// Describe the tables this mock emulates. They have no columns, and their
+  /// row counts are exact.
+  #[must_use]
+  pub fn describe_schema(&self) -> SchemaDescription {
+    let inner = self.read();
+    let table = |name: String, rows: usize| TableDescription {
+      name,
+      exists: true,
+      columns: Vec::new(),
+      estimated_rows: Some(rows as u64),
+    };
+
+    let index_tables = M::indices()
+      .definitions
+      .iter()
+      .map(|def| {
+        let rows = inner
+          .indices
+          .iter()
+          .filter(|((index, _), _)| index == def.name)
+          .map(|(_, record_ids)| record_ids.len())
+          .sum();
+        IndexTableDescription {
+          index:           def.name.to_owned(),
+          kind:            def.kind,
+          unique:          def.unique,
+          enforced_unique: def.unique,
+          table:           table(
+            format!("{}__idx_{}", M::TABLE_NAME, def.name),
+            rows,
+          ),
+        }
+      })
+      .collect();
+
+    SchemaDescription {
+      main_table: table(M::TABLE_NAME.to_owned(), inner.data.len()),
+      index_tables,
+    }
+  }
+
   /// Check if a record exists by ID.
   pub fn exists(&self, id: RecordId<M>) -> DatabaseResult<bool> {
-    let inner = self.inner.read().unwrap();
+    let inner = self.read();
     Ok(inner.data.contains_key(&id))
   }
 
   /// Clear all data (useful for test cleanup).
   pub fn clear(&self) {
-    let mut inner = self.inner.write().unwrap();
+    let mut inner = self.write();
     inner.data.clear();
+    inner.timestamps.clear();
     inner.indices.clear();
   }
 
   /// Get the number of records (synchronous version for testing).
   #[must_use]
   pub fn len(&self) -> usize {
-    let inner = self.inner.read().unwrap();
+    let inner = self.read();
     inner.data.len()
   }
 
@@ -243,29 +555,39 @@ impl<M: Model> MockDatabase<M> {
 
   // Helper methods
 
+  /// Lock the store for reading.
+  ///
+  /// Poisoning is ignored: writes run no user code while holding the lock,
+  /// so a panic elsewhere can't leave the store half-updated, and one
+  /// panicking test thread shouldn't fail every other.
+  fn read(&self) -> RwLockReadGuard<'_, MockDatabaseInner<M>> {
+    self.inner.read().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  /// Lock the store for writing. See [`read`](Self::read) on poisoning.
+  fn write(&self) -> RwLockWriteGuard<'_, MockDatabaseInner<M>> {
+    self.inner.write().unwrap_or_else(PoisonError::into_inner)
+  }
+
   fn check_unique_violations(
     inner: &MockDatabaseInner<M>,
-    model: &M,
+    entries: &IndexEntries,
     exclude_id: Option<RecordId<M>>,
   ) -> DatabaseResult<()> {
-    let indices = M::indices();
-
-    for def in indices.definitions {
-      if !def.unique {
+    for (name, unique, key) in entries {
+      if !unique {
         continue;
       }
 
-      let values = def.extract(model);
-      let index_key_str = Self::format_index_key(&values);
-      let index_key = (def.name.to_string(), index_key_str.clone());
+      let index_key = ((*name).to_string(), key.clone());
 
       if let Some(existing_ids) = inner.indices.get(&index_key) {
         // Check if any existing ID is different from the one we're updating
         for existing_id in existing_ids {
           if Some(existing_id) != exclude_id.as_ref() {
             return Err(DatabaseError::UniqueViolation {
-              index: def.name.to_string(),
-              value: index_key_str,
+              index: (*name).to_string(),
+              value: key.clone(),
             });
           }
         }
@@ -275,16 +597,61 @@ impl<M: Model> MockDatabase<M> {
     Ok(())
   }
 
-  fn insert_indices_inner(inner: &mut MockDatabaseInner<M>, model: &M) {
-    let indices = M::indices();
+  fn insert_indices_inner(
+    inner: &mut MockDatabaseInner<M>,
+    id: RecordId<M>,
+    entries: &IndexEntries,
+  ) {
+    for (name, _, key) in entries {
+      let index_key = ((*name).to_string(), key.clone());
+      inner.indices.entry(index_key).or_default().push(id);
+    }
+  }
 
-    for def in indices.definitions {
-      let values = def.extract(model);
-      let index_key_str = Self::format_index_key(&values);
-      let index_key = (def.name.to_string(), index_key_str);
+  fn extract_entries(model: &M) -> DatabaseResult<IndexEntries> {
+    let mut entries = Vec::with_capacity(M::indices().definitions.len());
+    for def in M::indices().definitions {
+      Self::push_entries(&mut entries, def, &def.extract(model))?;
+    }
+    Ok(entries)
+  }
 
-      inner.indices.entry(index_key).or_default().push(model.id());
+  /// Wait out the simulated latency of `operation`.
+  #[cfg_attr(target_arch = "wasm32", allow(clippy::unused_async))]
+  async fn simulate_latency(&self, operation: MockOperation) {
+    let latency = self.latency.sample(&operation);
+    // wasm32 has no timer without a runtime, so latency isn't simulated there
+    #[cfg(not(target_arch = "wasm32"))]
+    if !latency.is_zero() {
+      tokio::time::sleep(latency).await;
     }
+    #[cfg(target_arch = "wasm32")]
+    let _ = latency;
+  }
+
+  async fn extract_entries_with_pipeline(
+    &self,
+    model: &M,
+  ) -> DatabaseResult<IndexEntries> {
+    let mut entries = Vec::with_capacity(M::indices().definitions.len());
+    for def in M::indices().definitions {
+      let values = self.index_pipeline.extract(def, model).await?;
+      Self::push_entries(&mut entries, def, &values)?;
+    }
+    Ok(entries)
+  }
+
+  /// Push the index entries for a model's extracted values, as derived by
+  /// [`index_keys`].
+  fn push_entries(
+    entries: &mut IndexEntries,
+    def: &IndexDefinition<M>,
+    values: &[IndexValue],
+  ) -> DatabaseResult<()> {
+    for key in index_keys(def, values)? {
+      entries.push((def.name, def.unique, key.to_string()));
+    }
+    Ok(())
   }
 
   fn delete_indices_inner(inner: &mut MockDatabaseInner<M>, id: RecordId<M>) {
@@ -295,36 +662,173 @@ impl<M: Model> MockDatabase<M> {
     });
   }
 
-  fn format_index_key(values: &[IndexValue]) -> String {
-    values
-      .iter()
-      .map(ToString::to_string)
-      .collect::<Vec<_>>()
-      .join("\0")
+  /// Add `delta` to the integer at `field_path` in a serialized model.
+  fn increment_field(
+    data: &mut serde_json::Value,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64> {
+    let missing = || {
+      DatabaseError::Other(miette::miette!(
+        "field path {} not found",
+        field_path.join(".")
+      ))
+    };
+    let (field, parents) = field_path.split_last().ok_or_else(missing)?;
+
+    let mut target = data;
+    for parent in parents {
+      target = target.get_mut(*parent).ok_or_else(missing)?;
+    }
+    let object = target.as_object_mut().ok_or_else(missing)?;
+
+    let current = match object.get(*field) {
+      None | Some(serde_json::Value::Null) => 0,
+      Some(value) => value.as_i64().ok_or_else(|| {
+        DatabaseError::Other(miette::miette!(
+          "field {} is not an integer: {value}",
+          field_path.join(".")
+        ))
+      })?,
+    };
+    let value = current.checked_add(delta).ok_or_else(|| {
+      DatabaseError::Other(miette::miette!(
+        "incrementing field {} overflowed",
+        field_path.join(".")
+      ))
+    })?;
+    object.insert((*field).to_owned(), value.into());
+    Ok(value)
+  }
+
+  fn search_haystacks(model: &M) -> DatabaseResult<Vec<String>> {
+    let value = serde_json::to_value(model)
+      .map_err(|e| DatabaseError::Serialization(miette::Report::from_err(e)))?;
+
+    Ok(
+      M::SEARCH_FIELDS
+        .iter()
+        .filter_map(|field| match value.get(field)? {
+          serde_json::Value::Null => None,
+          serde_json::Value::String(s) => Some(s.to_lowercase()),
+          other => Some(other.to_string().to_lowercase()),
+        })
+        .collect(),
+    )
   }
 }
 
-#[async_trait::async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
 impl<M: Model> DatabaseLike<M> for MockDatabase<M> {
   async fn initialize_schema(&self) -> DatabaseResult<()> {
+    self.simulate_latency(MockOperation::Schema).await;
     self.initialize_schema()
   }
 
-  async fn insert(&self, model: &M) -> DatabaseResult<()> { self.insert(model) }
+  async fn rebuild_indices(&self) -> DatabaseResult<()> {
+    self.simulate_latency(MockOperation::Schema).await;
+    let models: Vec<M> = self.read().data.values().cloned().collect();
+    let mut rebuilt = Vec::with_capacity(models.len());
+    for model in &models {
+      rebuilt
+        .push((model.id(), self.extract_entries_with_pipeline(model).await?));
+    }
 
-  async fn update(&self, model: &M) -> DatabaseResult<()> { self.update(model) }
+    // swap in the rebuilt indices, keeping the old ones if keys now collide
+    let mut inner = self.write();
+    let previous = std::mem::take(&mut inner.indices);
+    for (id, entries) in &rebuilt {
+      if let Err(e) = Self::check_unique_violations(&inner, entries, None) {
+        inner.indices = previous;
+        return Err(e);
+      }
+      Self::insert_indices_inner(&mut inner, *id, entries);
+    }
+    Ok(())
+  }
+
+  async fn insert(&self, model: &M) -> DatabaseResult<()> {
+    self.simulate_latency(MockOperation::Insert).await;
+    let entries = self.extract_entries_with_pipeline(model).await?;
+    self.insert_with_entries(model, &entries)
+  }
+
+  async fn update(&self, model: &M) -> DatabaseResult<()> {
+    self.simulate_latency(MockOperation::Update).await;
+    let entries = self.extract_entries_with_pipeline(model).await?;
+    self.update_with_entries(model, &entries, None)
+  }
+
+  async fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()> {
+    self.simulate_latency(MockOperation::Update).await;
+    let entries = self.extract_entries_with_pipeline(model).await?;
+    self.update_with_entries(model, &entries, Some(expected_updated_at))
+  }
+
+  async fn upsert_with(
+    &self,
+    id: RecordId<M>,
+    update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+  ) -> DatabaseResult<M> {
+    let _guard = self.atomic_lock.lock().await;
+
+    let existing = DatabaseLike::get(self, id).await?;
+    let exists = existing.is_some();
+    let model = update(existing);
+    if exists {
+      DatabaseLike::update(self, &model).await?;
+    } else {
+      DatabaseLike::insert(self, &model).await?;
+    }
+    Ok(model)
+  }
+
+  async fn increment(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64> {
+    let _guard = self.atomic_lock.lock().await;
+
+    let model = self.get_or_error(id)?;
+    let mut data = serde_json::to_value(&model)
+      .map_err(|e| DatabaseError::Serialization(miette::Report::from_err(e)))?;
+    let value = Self::increment_field(&mut data, field_path, delta)?;
+    let model: M = serde_json::from_value(data)
+      .map_err(|e| DatabaseError::Serialization(miette::Report::from_err(e)))?;
+
+    DatabaseLike::update(self, &model).await?;
+    Ok(value)
+  }
 
   async fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
+    self.simulate_latency(MockOperation::Delete).await;
     self.delete(id)
   }
 
   async fn delete_and_return(&self, id: RecordId<M>) -> DatabaseResult<M> {
+    self.simulate_latency(MockOperation::Delete).await;
     let model = self.get_or_error(id)?;
     self.delete(id)?;
     Ok(model)
   }
 
+  async fn updated_at(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<DateTime<Utc>>> {
+    self.simulate_latency(MockOperation::Get).await;
+    Ok(self.timestamps(id).map(|t| t.updated_at))
+  }
+
   async fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
+    self.simulate_latency(MockOperation::Get).await;
     self.get(id)
   }
 
@@ -333,7 +837,12 @@ impl<M: Model> DatabaseLike<M> for MockDatabase<M> {
     selector: M::IndexSelector,
     key: &IndexValue,
   ) -> DatabaseResult<Option<M>> {
-    self.find_by_unique_index(selector, key)
+    self.simulate_latency(MockOperation::Query).await;
+    let key = self
+      .index_pipeline
+      .apply(&selector.to_string(), key.clone())
+      .await?;
+    self.find_by_unique_index(selector, &key)
   }
 
   async fn find_by_index(
@@ -341,16 +850,98 @@ impl<M: Model> DatabaseLike<M> for MockDatabase<M> {
     selector: M::IndexSelector,
     key: &IndexValue,
   ) -> DatabaseResult<Vec<M>> {
-    self.find_by_index(selector, key)
+    self.simulate_latency(MockOperation::Query).await;
+    let key = self
+      .index_pipeline
+      .apply(&selector.to_string(), key.clone())
+      .await?;
+    self.find_by_index(selector, &key)
+  }
+
+  async fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>> {
+    self.simulate_latency(MockOperation::Query).await;
+    let index = selector.to_string();
+    let lower = self.index_pipeline.apply_bound(&index, lower).await?;
+    let upper = self.index_pipeline.apply_bound(&index, upper).await?;
+    self.find_by_index_range(selector, lower.as_ref(), upper.as_ref())
+  }
+
+  async fn claim_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+    limit: u32,
+    claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+  ) -> DatabaseResult<Vec<M>> {
+    // claims are serialized, so each sees the previous claim's updates
+    let _guard = self.atomic_lock.lock().await;
+
+    let candidates =
+      DatabaseLike::find_by_index_range(self, selector, lower, upper).await?;
+    let mut claimed: Vec<M> = Vec::new();
+    for mut model in candidates {
+      if claimed.len() >= limit as usize {
+        break;
+      }
+      // a model may match the range through several index values
+      if claimed.iter().any(|c| c.id() == model.id()) {
+        continue;
+      }
+      claim(&mut model);
+      DatabaseLike::update(self, &model).await?;
+      claimed.push(model);
+    }
+    Ok(claimed)
   }
 
   async fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
+    self.simulate_latency(MockOperation::Query).await;
     self.list(limit, offset)
   }
 
-  async fn count(&self) -> DatabaseResult<u64> { self.count() }
+  async fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    self.simulate_latency(MockOperation::Query).await;
+    self.list_after(after, limit)
+  }
+
+  async fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+    self.simulate_latency(MockOperation::Query).await;
+    self.sample(n)
+  }
+
+  async fn search(&self, query: &str, limit: u32) -> DatabaseResult<Vec<M>> {
+    self.simulate_latency(MockOperation::Query).await;
+    self.search(query, limit)
+  }
+
+  async fn count(&self) -> DatabaseResult<u64> {
+    self.simulate_latency(MockOperation::Query).await;
+    self.count()
+  }
+
+  async fn truncate(&self) -> DatabaseResult<()> {
+    self.simulate_latency(MockOperation::Delete).await;
+    self.clear();
+    Ok(())
+  }
+
+  async fn describe_schema(&self) -> DatabaseResult<SchemaDescription> {
+    self.simulate_latency(MockOperation::Schema).await;
+    Ok(self.describe_schema())
+  }
 
   async fn exists(&self, id: RecordId<M>) -> DatabaseResult<bool> {
+    self.simulate_latency(MockOperation::Get).await;
     self.exists(id)
   }
 }
@@ -0,0 +1,198 @@
+use std::{fmt::Write, pin::pin};
+
+use db_core::{DatabaseError, DatabaseResult, index_keys};
+use futures::{Stream, StreamExt};
+use miette::{Context, IntoDiagnostic, Report};
+use model::Model;
+use sqlx::{Postgres, postgres::PgDatabaseError};
+use tracing::{debug, instrument};
+
+use crate::{PostgresDatabase, with_transaction};
+
+/// How many models are copied and indexed at a time by
+/// [`PostgresDatabase::bulk_load`].
+const BULK_LOAD_BATCH_SIZE: usize = 1000;
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Load many new models at once, returning how many were loaded.
+  ///
+  /// Models are written to the main table with the `COPY` protocol, and
+  /// their index entries are inserted in batches, which is far faster than
+  /// inserting models one by one. Everything is loaded in one transaction, so
+  /// if any model conflicts with an existing record or violates a unique
+  /// index, nothing is loaded.
+  #[instrument(skip(self, models), fields(model = M::TABLE_NAME))]
+  pub async fn bulk_load(
+    &self,
+    models: impl Stream<Item = M> + Send,
+  ) -> DatabaseResult<u64> {
+    self
+      .timed("bulk_load", async {
+        with_transaction!(self, tx, {
+          let mut batches = pin!(models.chunks(BULK_LOAD_BATCH_SIZE));
+          let mut loaded = 0;
+          while let Some(batch) = batches.next().await {
+            loaded += self.copy_batch(&mut tx, &batch).await?;
+            self.insert_batch_indices(&mut tx, &batch).await?;
+            debug!(loaded, "Loaded batch of models");
+          }
+          Ok(loaded)
+        })
+      })
+      .await
+  }
+
+  /// Copy a batch of models into the main table.
+  async fn copy_batch(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    batch: &[M],
+  ) -> DatabaseResult<u64> {
+    // rows in COPY's text format: tab-separated, with escapes
+    let mut rows = String::new();
+    for model in batch {
+      let data = self.serialize(model)?.to_string();
+      writeln!(
+        rows,
+        "{}\t{}",
+        copy_escape(&model.id().to_string()),
+        copy_escape(&data)
+      )
+      .expect("writing to a string can't fail");
+    }
+
+    let statement = format!(
+      "COPY {} (id, data) FROM STDIN (FORMAT text)",
+      self.table_name()
+    );
+    let mut copy = tx
+      .copy_in_raw(&statement)
+      .await
+      .into_diagnostic()
+      .context("failed to start copy")
+      .map_err(DatabaseError::Database)?;
+    copy
+      .send(rows.into_bytes())
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+    copy
+      .finish()
+      .await
+      .into_diagnostic()
+      .context("failed to copy into main table")
+      .map_err(DatabaseError::Database)
+  }
+
+  /// Insert the index entries for a batch of models, one statement per
+  /// index.
+  async fn insert_batch_indices(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    batch: &[M],
+  ) -> DatabaseResult<()> {
+    for def in M::indices().definitions {
+      let mut keys = Vec::new();
+      let mut record_ids = Vec::new();
+      for model in batch {
+        let values = self.index_pipeline.extract(def, model).await?;
+        for key in index_keys(def, &values)? {
+          keys.push(key.to_string());
+          record_ids.push(model.id().to_string());
+        }
+      }
+      if keys.is_empty() {
+        continue;
+      }
+
+      let query = format!(
+        "INSERT INTO {index_table} (index_key, record_id) SELECT \
+         key::{key_type}, record_id FROM UNNEST($1::text[], $2::text[]) AS \
+         entry(key, record_id)",
+        index_table = self.calculate_index_table_name(def),
+        key_type = Self::index_sql_type(def.kind),
+      );
+      match sqlx::query(&query)
+        .bind(&keys)
+        .bind(&record_ids)
+        .execute(&mut **tx)
+        .await
+      {
+        Ok(_) => {}
+        Err(e) if Self::is_unique_violation(&e) => {
+          return Err(DatabaseError::UniqueViolation {
+            index: def.name.to_string(),
+            value: violating_key(&e),
+          });
+        }
+        Err(e) => return Err(DatabaseError::Database(Report::from_err(e))),
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// Escape a value for `COPY`'s text format.
+fn copy_escape(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for c in value.chars() {
+    match c {
+      '\\' => escaped.push_str("\\\\"),
+      '\t' => escaped.push_str("\\t"),
+      '\n' => escaped.push_str("\\n"),
+      '\r' => escaped.push_str("\\r"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+/// The index key a unique violation was for, which Postgres reports in the
+/// error's detail, or the violated constraint if it doesn't.
+fn violating_key(error: &sqlx::Error) -> String {
+  let sqlx::Error::Database(error) = error else {
+    return String::new();
+  };
+  error
+    .try_downcast_ref::<PgDatabaseError>()
+    .and_then(PgDatabaseError::detail)
+    .and_then(parse_violating_key)
+    .or_else(|| error.constraint().map(ToOwned::to_owned))
+    .unwrap_or_default()
+}
+
+/// Parse the key out of a unique violation's detail, e.g. `Key
+/// (index_key)=(ann@example.com) already exists.`
+fn parse_violating_key(detail: &str) -> Option<String> {
+  let (_, key) = detail.split_once(")=(")?;
+  key.strip_suffix(") already exists.").map(ToOwned::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{copy_escape, parse_violating_key};
+
+  #[test]
+  fn test_copy_escape() {
+    assert_eq!(copy_escape("plain"), "plain");
+    assert_eq!(copy_escape(r#"{"a":"b\\c"}"#), r#"{"a":"b\\\\c"}"#);
+    assert_eq!(copy_escape("a\tb\nc\rd"), "a\\tb\\nc\\rd");
+    // unescaped, `\N` would be read as NULL
+    assert_eq!(copy_escape("\\N"), "\\\\N");
+    assert_eq!(copy_escape("naïve ✓"), "naïve ✓");
+  }
+
+  #[test]
+  fn test_parse_violating_key() {
+    assert_eq!(
+      parse_violating_key("Key (index_key)=(ann@example.com) already exists."),
+      Some("ann@example.com".to_owned())
+    );
+    assert_eq!(
+      parse_violating_key("Key (index_key)=(a)=(b) already exists."),
+      Some("a)=(b".to_owned())
+    );
+    assert_eq!(parse_violating_key("something else"), None);
+  }
+}
@@ -0,0 +1,655 @@
+use std::{collections::VecDeque, fmt, time::Duration};
+
+use db_core::{ChangeEvent, DatabaseError, DatabaseResult, decrypt_fields};
+use miette::{Context, IntoDiagnostic};
+use model::{Model, RecordId, upgrade_json};
+use serde_json::Value;
+use sqlx::Row;
+use tracing::{debug, instrument, warn};
+
+use crate::{Lsn, PostgresDatabase};
+
+/// The logical decoding output plugin change streams read with.
+const OUTPUT_PLUGIN: &str = "wal2json";
+
+/// A change read from a [`ChangeStream`], with the position to
+/// [acknowledge](ChangeStream::ack) it at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CdcEvent<M: Model> {
+  /// The position of the change in the slot.
+  pub position: CdcPosition,
+  /// The change.
+  pub change:   ChangeEvent<M>,
+}
+
+/// The position of a change in a replication slot: the commit of its
+/// transaction, then its place in the transaction.
+///
+/// Transactions are read in commit order, but their changes are written to
+/// the write-ahead log interleaved with those of concurrent transactions, so
+/// a change's own LSN doesn't order it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CdcPosition {
+  /// The end of the commit record of the change's transaction.
+  pub commit: Lsn,
+  /// The index of the change in its transaction.
+  pub index:  u32,
+}
+
+/// Options for a [`ChangeStream`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdcOptions {
+  /// The logical replication slot to read changes from.
+  pub slot:          String,
+  /// The most new changes read per poll.
+  pub batch_size:    u32,
+  /// How long to wait before polling again when there are no new changes.
+  pub poll_interval: Duration,
+}
+
+impl CdcOptions {
+  /// Creates new [`CdcOptions`] reading from `slot`, 100 changes at a time,
+  /// polling every second.
+  #[must_use]
+  pub fn new(slot: impl Into<String>) -> Self {
+    Self {
+      slot:          slot.into(),
+      batch_size:    100,
+      poll_interval: Duration::from_secs(1),
+    }
+  }
+
+  /// Sets the most new changes read per poll.
+  #[must_use]
+  pub const fn with_batch_size(mut self, batch_size: u32) -> Self {
+    self.batch_size = batch_size;
+    self
+  }
+
+  /// Sets how long to wait before polling again when there are no new
+  /// changes.
+  #[must_use]
+  pub const fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+    self.poll_interval = poll_interval;
+    self
+  }
+}
+
+/// Committed changes to a model's main table, read from a logical
+/// replication slot, as returned by
+/// [`PostgresDatabase::change_stream`].
+///
+/// Changes are read with `pg_logical_slot_peek_changes`, so the slot only
+/// moves past them once they're [acknowledged](Self::ack). Changes which
+/// aren't delivered, like updates leaving the data unchanged, are moved past
+/// once everything before them is acknowledged. The slot only moves past a
+/// transaction once all of its changes are. Delivery is at least once:
+/// after a restart, every change since the last acknowledgement is delivered
+/// again, as are the earlier changes of a partly acknowledged transaction.
+/// Unacknowledged changes are re-read on every poll, so acknowledge
+/// regularly.
+pub struct ChangeStream<M: Model> {
+  db:        PostgresDatabase<M>,
+  options:   CdcOptions,
+  unacked:   Unacknowledged,
+  /// The last change read, so changes read again are skipped
+  last_seen: Option<CdcPosition>,
+  buffered:  VecDeque<CdcEvent<M>>,
+}
+
+impl<M: Model> fmt::Debug for ChangeStream<M> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ChangeStream")
+      .field("options", &self.options)
+      .field("unacknowledged", &self.unacked.len())
+      .finish_non_exhaustive()
+  }
+}
+
+/// Changes read from a slot which it hasn't moved past yet, oldest first.
+#[derive(Debug, Default)]
+struct Unacknowledged(VecDeque<Unacked>);
+
+#[derive(Debug, Clone, Copy)]
+struct Unacked {
+  position: CdcPosition,
+  skipped:  bool,
+  last:     bool,
+}
+
+impl Unacknowledged {
+  fn len(&self) -> usize { self.0.len() }
+
+  /// Track a change read from the slot, and whether it was skipped rather
+  /// than delivered.
+  fn push(&mut self, read: &ReadChange, skipped: bool) {
+    self.0.push_back(Unacked {
+      position: read.position,
+      skipped,
+      last: read.last,
+    });
+  }
+
+  /// Forget the changes up to and including `position`, returning the
+  /// commit of the last transaction this finishes for the slot to move past.
+  fn ack(&mut self, position: CdcPosition) -> Option<Lsn> {
+    self.pop_while(|read| read.position <= position)
+  }
+
+  /// Forget the skipped changes with nothing unacknowledged before them,
+  /// returning the commit of the last transaction this finishes for the
+  /// slot to move past.
+  fn pop_skipped(&mut self) -> Option<Lsn> {
+    self.pop_while(|read| read.skipped)
+  }
+
+  fn pop_while(&mut self, forget: impl Fn(&Unacked) -> bool) -> Option<Lsn> {
+    let mut commit = None;
+    while let Some(read) = self.0.front().copied().filter(|read| forget(read)) {
+      self.0.pop_front();
+      if read.last {
+        commit = Some(read.position.commit);
+      }
+    }
+    commit
+  }
+}
+
+/// A change read from a slot.
+#[derive(Debug, Clone, PartialEq)]
+struct ReadChange {
+  position: CdcPosition,
+  /// The change, or `None` for a transaction with no changes.
+  change:   Option<Value>,
+  /// Whether this is the last change of its transaction.
+  last:     bool,
+}
+
+/// Group wal2json (format version 2) rows, with their transactions'
+/// begin and commit records, into the changes of each transaction,
+/// positioned by its commit. A transaction without its commit is left to be
+/// read again.
+fn read_transactions(
+  rows: Vec<(Lsn, String)>,
+) -> DatabaseResult<Vec<ReadChange>> {
+  let mut read = Vec::new();
+  let mut transaction = Vec::new();
+  for (lsn, data) in rows {
+    let change: Value = serde_json::from_str(&data)
+      .into_diagnostic()
+      .context("failed to parse wal2json change")
+      .map_err(DatabaseError::Serialization)?;
+    match change["action"].as_str() {
+      Some("B") => transaction.clear(),
+      // a commit's LSN is the end of its record, which moving the slot to
+      // moves it past the whole transaction
+      Some("C") if transaction.is_empty() => read.push(ReadChange {
+        position: CdcPosition {
+          commit: lsn,
+          index:  0,
+        },
+        change:   None,
+        last:     true,
+      }),
+      Some("C") => {
+        let count = transaction.len();
+        read.extend(transaction.drain(..).enumerate().map(
+          |(index, change)| ReadChange {
+            position: CdcPosition {
+              commit: lsn,
+              index:  u32::try_from(index).unwrap_or(u32::MAX),
+            },
+            change:   Some(change),
+            last:     index + 1 == count,
+          },
+        ));
+      }
+      _ => transaction.push(change),
+    }
+  }
+  Ok(read)
+}
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Create the logical replication slot `slot` for
+  /// [`change_stream`](Self::change_stream)s to read from, if it doesn't
+  /// exist.
+  ///
+  /// Requires the `wal2json` output plugin on the server, `wal_level =
+  /// logical`, and the `REPLICATION` attribute. A slot retains the
+  /// write-ahead log until its changes are acknowledged, so drop slots which
+  /// are no longer read.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME))]
+  pub async fn create_replication_slot(
+    &self,
+    slot: &str,
+  ) -> DatabaseResult<()> {
+    sqlx::query(
+      "SELECT pg_create_logical_replication_slot($1, $2) WHERE NOT EXISTS \
+       (SELECT 1 FROM pg_replication_slots WHERE slot_name = $1)",
+    )
+    .bind(slot)
+    .bind(OUTPUT_PLUGIN)
+    .execute(&self.pool)
+    .await
+    .into_diagnostic()
+    .context("failed to create replication slot")
+    .map_err(DatabaseError::Database)?;
+
+    debug!("Replication slot created");
+    Ok(())
+  }
+
+  /// Drop the logical replication slot `slot`, if it exists.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME))]
+  pub async fn drop_replication_slot(&self, slot: &str) -> DatabaseResult<()> {
+    sqlx::query(
+      "SELECT pg_drop_replication_slot(slot_name) FROM pg_replication_slots \
+       WHERE slot_name = $1",
+    )
+    .bind(slot)
+    .execute(&self.pool)
+    .await
+    .into_diagnostic()
+    .context("failed to drop replication slot")
+    .map_err(DatabaseError::Database)?;
+    Ok(())
+  }
+
+  /// Read committed changes to this model's main table from a logical
+  /// replication slot created with
+  /// [`create_replication_slot`](Self::create_replication_slot).
+  ///
+  /// Changes to other tables in the slot are skipped, so one slot per model
+  /// is best.
+  #[must_use]
+  pub fn change_stream(&self, options: CdcOptions) -> ChangeStream<M> {
+    ChangeStream {
+      db: self.clone(),
+      options,
+      unacked: Unacknowledged::default(),
+      last_seen: None,
+      buffered: VecDeque::new(),
+    }
+  }
+
+  /// The `add-tables` filter matching this model's main table.
+  fn replicated_table(&self) -> String {
+    let table = self.namespace.local(M::TABLE_NAME);
+    match self.namespace.schema() {
+      Some(schema) => format!("{schema}.{table}"),
+      None => format!("*.{table}"),
+    }
+  }
+
+  /// Parse a wal2json (format version 2) change to the main table, or
+  /// `None` if change streams skip it.
+  fn parse_change(
+    &self,
+    change: &Value,
+  ) -> DatabaseResult<Option<ChangeEvent<M>>> {
+    let Some(action) = Action::of(change) else {
+      return Ok(None);
+    };
+    let data = || column(change, "columns", "data").unwrap_or(&Value::Null);
+    Ok(Some(match action {
+      Action::Insert => ChangeEvent::Inserted(
+        self.model_from_change(&change_id(change, "columns")?, data())?,
+      ),
+      Action::Update => ChangeEvent::Updated(
+        self.model_from_change(&change_id(change, "columns")?, data())?,
+      ),
+      Action::Delete => ChangeEvent::Deleted(change_id(change, "identity")?),
+    }))
+  }
+
+  fn model_from_change(
+    &self,
+    id: &RecordId<M>,
+    data: &Value,
+  ) -> DatabaseResult<M> {
+    // wal2json writes jsonb values as JSON, but tolerate them as text
+    let stored = match data {
+      Value::String(text) => text.clone(),
+      data => data.to_string(),
+    };
+    let invalid = |error: miette::Report| {
+      DatabaseError::deserialization(
+        self.table_name(),
+        Some(id.to_string()),
+        &stored,
+        error,
+      )
+    };
+
+    let mut value: Value = serde_json::from_str(&stored)
+      .into_diagnostic()
+      .context("failed to parse data as JSON")
+      .map_err(invalid)?;
+    if let Some(cipher) = self.field_cipher()? {
+      decrypt_fields::<M>(cipher, &mut value)?;
+    }
+    upgrade_json::<M>(&mut value)
+      .into_diagnostic()
+      .context("failed to upgrade data to the model's version")
+      .map_err(invalid)?;
+    serde_json::from_str(&value.to_string())
+      .into_diagnostic()
+      .context("failed to deserialize data as model")
+      .map_err(invalid)
+  }
+}
+
+/// What a wal2json (format version 2) change to the main table does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+  Insert,
+  Update,
+  Delete,
+}
+
+impl Action {
+  /// The action of `change`, or `None` if change streams skip it: updates
+  /// which left the data unchanged, and unsupported actions like truncates.
+  fn of(change: &Value) -> Option<Self> {
+    match change["action"].as_str() {
+      // unchanged TOASTed values are left out of updates
+      Some("I" | "U") if column(change, "columns", "data").is_none() => {
+        debug!("Skipping update which left data unchanged");
+        None
+      }
+      Some("I") => Some(Self::Insert),
+      Some("U") => Some(Self::Update),
+      Some("D") => Some(Self::Delete),
+      action => {
+        warn!(?action, "Skipping unsupported change");
+        None
+      }
+    }
+  }
+}
+
+/// The value of the column `name` in the `key` list of `change`.
+fn column<'a>(change: &'a Value, key: &str, name: &str) -> Option<&'a Value> {
+  change[key]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .find(|column| column["name"] == name)
+    .map(|column| &column["value"])
+}
+
+/// The record id in the `key` list of `change`.
+fn change_id<M: Model>(
+  change: &Value,
+  key: &str,
+) -> DatabaseResult<RecordId<M>> {
+  column(change, key, "id")
+    .and_then(Value::as_str)
+    .ok_or_else(|| {
+      DatabaseError::Serialization(miette::miette!("change has no id"))
+    })?
+    .parse()
+    .into_diagnostic()
+    .context("invalid id in change")
+    .map_err(DatabaseError::Serialization)
+}
+
+impl<M: Model> ChangeStream<M> {
+  /// Wait for the next change, polling the slot until there is one.
+  pub async fn recv(&mut self) -> DatabaseResult<CdcEvent<M>> {
+    loop {
+      if let Some(event) = self.buffered.pop_front() {
+        return Ok(event);
+      }
+      if !self.poll().await? {
+        tokio::time::sleep(self.options.poll_interval).await;
+      }
+    }
+  }
+
+  /// Read the changes after those already read, returning whether any are
+  /// to be delivered.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, slot = %self.options.slot))]
+  async fn poll(&mut self) -> DatabaseResult<bool> {
+    // unacknowledged changes are read again, so make room for them
+    let mut limit = u32::try_from(self.unacked.len())
+      .unwrap_or(u32::MAX)
+      .saturating_add(self.options.batch_size.max(1));
+
+    let changes = loop {
+      let rows = self.peek(limit).await?;
+      let exhausted = rows.len() < limit as usize || limit == u32::MAX;
+      let changes = read_transactions(rows)?;
+      // whole transactions are read, so changes already read can fill the
+      // window, e.g. from a partly acknowledged transaction; look further
+      let stale = changes
+        .iter()
+        .all(|read| self.last_seen.is_some_and(|seen| read.position <= seen));
+      if !stale || exhausted {
+        break changes;
+      }
+      limit = limit.saturating_mul(2);
+    };
+
+    let mut found = false;
+    for read in changes {
+      if self.last_seen.is_some_and(|seen| read.position <= seen) {
+        continue;
+      }
+      self.last_seen = Some(read.position);
+      let change = match &read.change {
+        Some(change) => self.db.parse_change(change)?,
+        None => None,
+      };
+      if let Some(change) = change {
+        self.unacked.push(&read, false);
+        self.buffered.push_back(CdcEvent {
+          position: read.position,
+          change,
+        });
+        found = true;
+      } else {
+        self.unacked.push(&read, true);
+      }
+    }
+
+    self.advance_past_skipped().await?;
+    Ok(found)
+  }
+
+  /// Read up to `limit` rows from the slot, ending at a transaction
+  /// boundary.
+  async fn peek(&self, limit: u32) -> DatabaseResult<Vec<(Lsn, String)>> {
+    let rows = sqlx::query(
+      "SELECT lsn::text AS lsn, data FROM pg_logical_slot_peek_changes($1, \
+       NULL, $2, 'format-version', '2', 'include-transaction', 'true', \
+       'add-tables', $3)",
+    )
+    .bind(&self.options.slot)
+    .bind(i32::try_from(limit).unwrap_or(i32::MAX))
+    .bind(self.db.replicated_table())
+    .fetch_all(&self.db.pool)
+    .await
+    .into_diagnostic()
+    .context("failed to read changes from replication slot")
+    .map_err(DatabaseError::Database)?;
+
+    rows
+      .into_iter()
+      .map(|row| {
+        let lsn = row
+          .try_get::<String, _>("lsn")
+          .into_diagnostic()
+          .map_err(DatabaseError::Database)?
+          .parse()?;
+        let data = row
+          .try_get("data")
+          .into_diagnostic()
+          .map_err(DatabaseError::Database)?;
+        Ok((lsn, data))
+      })
+      .collect()
+  }
+
+  /// Acknowledge every change up to and including the one at `position`,
+  /// so the slot moves past the transactions this finishes and they aren't
+  /// delivered again.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, slot = %self.options.slot))]
+  pub async fn ack(&mut self, position: CdcPosition) -> DatabaseResult<()> {
+    if let Some(commit) = self.unacked.ack(position) {
+      self.advance(commit).await?;
+    }
+    self.advance_past_skipped().await
+  }
+
+  /// Move the slot past skipped changes with nothing unacknowledged before
+  /// them, so they don't hold it back.
+  async fn advance_past_skipped(&mut self) -> DatabaseResult<()> {
+    match self.unacked.pop_skipped() {
+      Some(commit) => {
+        debug!(%commit, "Moving slot past skipped changes");
+        self.advance(commit).await
+      }
+      None => Ok(()),
+    }
+  }
+
+  /// Move the slot past every transaction up to and including the one
+  /// committed at `lsn`.
+  async fn advance(&self, lsn: Lsn) -> DatabaseResult<()> {
+    sqlx::query("SELECT pg_replication_slot_advance($1, $2::pg_lsn)")
+      .bind(&self.options.slot)
+      .bind(lsn.to_string())
+      .execute(&self.db.pool)
+      .await
+      .into_diagnostic()
+      .context("failed to advance replication slot")
+      .map_err(DatabaseError::Database)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::{Action, CdcPosition, Unacknowledged, column, read_transactions};
+  use crate::Lsn;
+
+  fn position(commit: u64, index: u32) -> CdcPosition {
+    CdcPosition {
+      commit: Lsn(commit),
+      index,
+    }
+  }
+
+  #[test]
+  fn test_change_actions() {
+    let data = json!([{ "name": "id", "value": "x" }, {
+      "name": "data",
+      "value": { "a": 1 }
+    }]);
+    let insert = json!({ "action": "I", "columns": data });
+    let update = json!({ "action": "U", "columns": data });
+    let delete = json!({
+      "action": "D",
+      "identity": [{ "name": "id", "value": "x" }]
+    });
+    assert_eq!(Action::of(&insert), Some(Action::Insert));
+    assert_eq!(Action::of(&update), Some(Action::Update));
+    assert_eq!(Action::of(&delete), Some(Action::Delete));
+    assert_eq!(column(&insert, "columns", "data"), Some(&json!({ "a": 1 })));
+    assert_eq!(column(&delete, "identity", "id"), Some(&json!("x")));
+  }
+
+  #[test]
+  fn test_skipped_changes() {
+    // an update which left TOASTed data unchanged
+    let unchanged = json!({
+      "action": "U",
+      "columns": [{ "name": "id", "value": "x" }]
+    });
+    assert_eq!(Action::of(&unchanged), None);
+    assert_eq!(Action::of(&json!({ "action": "T" })), None);
+    assert_eq!(Action::of(&json!({})), None);
+  }
+
+  #[test]
+  fn test_skipped_changes_are_moved_past() {
+    let mut unacked = Unacknowledged::default();
+    let rows = [
+      (10, r#"{"action":"B"}"#),
+      (11, r#"{"action":"I"}"#),
+      (12, r#"{"action":"I"}"#),
+      (13, r#"{"action":"C"}"#),
+      (20, r#"{"action":"B"}"#),
+      (21, r#"{"action":"C"}"#),
+      (30, r#"{"action":"B"}"#),
+      (31, r#"{"action":"I"}"#),
+      (32, r#"{"action":"I"}"#),
+      (33, r#"{"action":"C"}"#),
+    ];
+    let read = read_transactions(
+      rows.map(|(lsn, data)| (Lsn(lsn), data.to_owned())).to_vec(),
+    )
+    .unwrap();
+    // the first transaction and the empty one are skipped, as is the last
+    // change of the third
+    for (read, skipped) in read.iter().zip([true, true, true, false, true]) {
+      unacked.push(read, skipped);
+    }
+    assert_eq!(unacked.pop_skipped(), Some(Lsn(21)));
+    // skipped changes after unacknowledged ones wait for them
+    assert_eq!(unacked.pop_skipped(), None);
+    assert_eq!(unacked.len(), 2);
+
+    assert_eq!(unacked.ack(position(33, 0)), None);
+    assert_eq!(unacked.pop_skipped(), Some(Lsn(33)));
+    assert_eq!(unacked.len(), 0);
+  }
+
+  #[test]
+  fn test_overlapping_transactions_are_ordered_by_commit() {
+    // the second transaction writes before the first, but commits after it
+    let rows = [
+      (10, r#"{"action":"B"}"#),
+      (30, r#"{"action":"I","n":1}"#),
+      (40, r#"{"action":"C"}"#),
+      (15, r#"{"action":"B"}"#),
+      (20, r#"{"action":"I","n":2}"#),
+      (50, r#"{"action":"U","n":3}"#),
+      (60, r#"{"action":"C"}"#),
+      // a transaction cut off before its commit is read again later
+      (70, r#"{"action":"B"}"#),
+      (71, r#"{"action":"I","n":4}"#),
+    ];
+    let read = read_transactions(
+      rows.map(|(lsn, data)| (Lsn(lsn), data.to_owned())).to_vec(),
+    )
+    .unwrap();
+
+    let positions: Vec<_> = read.iter().map(|read| read.position).collect();
+    assert_eq!(positions, [
+      position(40, 0),
+      position(60, 0),
+      position(60, 1)
+    ]);
+    assert!(positions.is_sorted());
+    let changes: Vec<_> = read
+      .iter()
+      .map(|read| read.change.as_ref().unwrap()["n"].clone())
+      .collect();
+    assert_eq!(changes, [1, 2, 3]);
+
+    let mut unacked = Unacknowledged::default();
+    for read in &read {
+      unacked.push(read, false);
+    }
+    assert_eq!(unacked.ack(position(40, 0)), Some(Lsn(40)));
+    // the slot only moves past a transaction once all of it is acknowledged
+    assert_eq!(unacked.ack(position(60, 0)), None);
+    assert_eq!(unacked.ack(position(60, 1)), Some(Lsn(60)));
+    assert_eq!(unacked.len(), 0);
+  }
+}
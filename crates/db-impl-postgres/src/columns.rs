@@ -0,0 +1,149 @@
+use std::fmt::Write;
+
+use db_core::{DatabaseError, DatabaseResult};
+use miette::{Context, IntoDiagnostic};
+use model::{ColumnDefinition, ColumnKind, Model};
+use sqlx::Postgres;
+use tracing::{debug, instrument};
+
+use crate::PostgresDatabase;
+
+/// The SQL type of a column of the given kind.
+const fn sql_type(kind: ColumnKind) -> &'static str {
+  match kind {
+    ColumnKind::String => "TEXT",
+    ColumnKind::I64 => "BIGINT",
+    ColumnKind::F64 => "DOUBLE PRECISION",
+    ColumnKind::Bool => "BOOLEAN",
+    ColumnKind::Timestamp => "TIMESTAMPTZ",
+    ColumnKind::Json => "JSONB",
+  }
+}
+
+/// Quotes a serialized field name for use as a column name.
+fn column_name(column: &ColumnDefinition) -> String {
+  format!("\"{}\"", column.name.replace('"', "\"\""))
+}
+
+/// The expression computing a column's value from the model JSON in `data`.
+/// JSON nulls and missing fields become SQL `NULL`.
+fn column_value(column: &ColumnDefinition, data: &str) -> String {
+  let field = column.name.replace('\'', "''");
+  match column.kind {
+    ColumnKind::Json => format!("NULLIF({data}->'{field}', 'null'::jsonb)"),
+    ColumnKind::String => format!("({data}->>'{field}')"),
+    kind => format!("({data}->>'{field}')::{}", sql_type(kind)),
+  }
+}
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Create the columns promoted with `#[model(column)]`, and the trigger
+  /// keeping them in sync with the `data` column.
+  ///
+  /// The trigger covers every write, including bulk loads and raw SQL. Rows
+  /// whose columns are out of sync, e.g. because they were written before
+  /// the column was added, are backfilled.
+  #[instrument(skip(self, tx), fields(model = M::TABLE_NAME))]
+  pub(crate) async fn create_columns(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+  ) -> DatabaseResult<()> {
+    if M::COLUMNS.is_empty() {
+      return Ok(());
+    }
+
+    let table_name = self.table_name();
+    let function = format!("{table_name}_sync_columns");
+    let trigger =
+      format!("{}_sync_columns", self.namespace.local(M::TABLE_NAME));
+
+    let mut queries: Vec<String> = M::COLUMNS
+      .iter()
+      .map(|column| {
+        format!(
+          "ALTER TABLE {table_name} ADD COLUMN IF NOT EXISTS {name} {ty}",
+          name = column_name(column),
+          ty = sql_type(column.kind),
+        )
+      })
+      .collect();
+
+    let assignments = M::COLUMNS.iter().fold(String::new(), |mut sql, column| {
+      let _ = writeln!(
+        sql,
+        "NEW.{} := {};",
+        column_name(column),
+        column_value(column, "NEW.data")
+      );
+      sql
+    });
+    queries.push(format!(
+      "CREATE OR REPLACE FUNCTION {function}() RETURNS trigger AS $$
+      BEGIN
+        {assignments}
+        RETURN NEW;
+      END;
+      $$ LANGUAGE plpgsql"
+    ));
+    queries.push(format!("DROP TRIGGER IF EXISTS {trigger} ON {table_name}"));
+    queries.push(format!(
+      "CREATE TRIGGER {trigger} BEFORE INSERT OR UPDATE OF data ON \
+       {table_name} FOR EACH ROW EXECUTE FUNCTION {function}()"
+    ));
+
+    let out_of_sync = M::COLUMNS
+      .iter()
+      .map(|column| {
+        format!(
+          "{} IS DISTINCT FROM {}",
+          column_name(column),
+          column_value(column, "data")
+        )
+      })
+      .collect::<Vec<_>>()
+      .join(" OR ");
+    queries.push(format!(
+      "UPDATE {table_name} SET data = data WHERE {out_of_sync}"
+    ));
+
+    for query in &queries {
+      sqlx::query(query)
+        .execute(&mut **tx)
+        .await
+        .into_diagnostic()
+        .context("failed to create promoted columns")
+        .map_err(DatabaseError::Other)?;
+    }
+
+    debug!(count = M::COLUMNS.len(), "Promoted columns created");
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use model::{ColumnDefinition, ColumnKind};
+
+  use super::{column_name, column_value};
+
+  #[test]
+  fn test_column_value() {
+    let column = |name, kind| ColumnDefinition { name, kind };
+    assert_eq!(
+      column_value(&column("signedUpAt", ColumnKind::Timestamp), "data"),
+      "(data->>'signedUpAt')::TIMESTAMPTZ"
+    );
+    assert_eq!(
+      column_value(&column("o'brien", ColumnKind::String), "NEW.data"),
+      "(NEW.data->>'o''brien')"
+    );
+    assert_eq!(
+      column_value(&column("tags", ColumnKind::Json), "data"),
+      "NULLIF(data->'tags', 'null'::jsonb)"
+    );
+    assert_eq!(
+      column_name(&column("signedUpAt", ColumnKind::Timestamp)),
+      "\"signedUpAt\""
+    );
+  }
+}
@@ -0,0 +1,118 @@
+use std::{fmt, path::PathBuf};
+
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+
+/// How TLS is negotiated with the server.
+///
+/// Mirrors the `sslmode` connection parameter understood by `libpq`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PostgresSslMode {
+  /// Never use TLS.
+  Disable,
+  /// Try a plaintext connection first, then TLS.
+  Allow,
+  /// Try TLS first, then a plaintext connection.
+  #[default]
+  Prefer,
+  /// Require TLS, but don't verify the server certificate.
+  Require,
+  /// Require TLS and verify the server certificate against the root CA.
+  VerifyCa,
+  /// Require TLS, verify the server certificate against the root CA, and
+  /// verify that the server hostname matches the certificate.
+  VerifyFull,
+}
+
+impl From<PostgresSslMode> for PgSslMode {
+  fn from(mode: PostgresSslMode) -> Self {
+    match mode {
+      PostgresSslMode::Disable => PgSslMode::Disable,
+      PostgresSslMode::Allow => PgSslMode::Allow,
+      PostgresSslMode::Prefer => PgSslMode::Prefer,
+      PostgresSslMode::Require => PgSslMode::Require,
+      PostgresSslMode::VerifyCa => PgSslMode::VerifyCa,
+      PostgresSslMode::VerifyFull => PgSslMode::VerifyFull,
+    }
+  }
+}
+
+/// Typed options for connecting to a Postgres server, as an alternative to
+/// assembling a connection URL by hand.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PostgresConnectOptions {
+  /// The server hostname.
+  pub host:          String,
+  /// The server port.
+  pub port:          u16,
+  /// The database name. Defaults to the username if unset.
+  pub database:      Option<String>,
+  /// The user to connect as.
+  pub username:      Option<String>,
+  /// The user's password.
+  pub password:      Option<String>,
+  /// How TLS is negotiated with the server.
+  pub ssl_mode:      PostgresSslMode,
+  /// A PEM file containing the root CA certificate(s) used to verify the
+  /// server, in place of the system roots.
+  pub ssl_root_cert: Option<PathBuf>,
+}
+
+impl Default for PostgresConnectOptions {
+  fn default() -> Self {
+    Self {
+      host:          "localhost".to_owned(),
+      port:          5432,
+      database:      None,
+      username:      None,
+      password:      None,
+      ssl_mode:      PostgresSslMode::default(),
+      ssl_root_cert: None,
+    }
+  }
+}
+
+impl fmt::Debug for PostgresConnectOptions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PostgresConnectOptions")
+      .field("host", &self.host)
+      .field("port", &self.port)
+      .field("database", &self.database)
+      .field("username", &self.username)
+      .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+      .field("ssl_mode", &self.ssl_mode)
+      .field("ssl_root_cert", &self.ssl_root_cert)
+      .finish()
+  }
+}
+
+impl PostgresConnectOptions {
+  /// Converts into the [`PgConnectOptions`] used by the connection pool.
+  #[must_use]
+  pub fn to_pg_connect_options(&self) -> PgConnectOptions {
+    let mut options = PgConnectOptions::new_without_pgpass()
+      .host(&self.host)
+      .port(self.port)
+      .ssl_mode(self.ssl_mode.into());
+
+    if let Some(database) = &self.database {
+      options = options.database(database);
+    }
+    if let Some(username) = &self.username {
+      options = options.username(username);
+    }
+    if let Some(password) = &self.password {
+      options = options.password(password);
+    }
+    if let Some(ssl_root_cert) = &self.ssl_root_cert {
+      options = options.ssl_root_cert(ssl_root_cert);
+    }
+
+    options
+  }
+}
+
+impl From<&PostgresConnectOptions> for PgConnectOptions {
+  fn from(options: &PostgresConnectOptions) -> Self {
+    options.to_pg_connect_options()
+  }
+}
@@ -0,0 +1,107 @@
+//! Read-your-writes consistency for reads from replicas.
+//!
+//! After a write, the primary hands out a [`ConsistencyToken`] marking its
+//! position in the write-ahead log. A read carrying the token may go to a
+//! replica once [`wait_for_replica`] sees it has replayed that far, and
+//! should go to the primary otherwise.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use db_core::{DatabaseError, DatabaseResult};
+use miette::{Context, IntoDiagnostic};
+use model::Model;
+use sqlx::{PgPool, Row};
+use tracing::{debug, instrument};
+
+use crate::{Lsn, PostgresDatabase};
+
+/// How often [`wait_for_replica`] checks a replica's progress.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A point in the primary's write-ahead log which a read must observe.
+///
+/// Displays as, and parses from, the LSN, so it can be handed to clients,
+/// e.g. in a response header, and sent back with their next request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConsistencyToken(pub Lsn);
+
+impl fmt::Display for ConsistencyToken {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.0.fmt(f) }
+}
+
+impl FromStr for ConsistencyToken {
+  type Err = DatabaseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> { s.parse().map(Self) }
+}
+
+impl<M: Model> PostgresDatabase<M> {
+  /// A [`ConsistencyToken`] covering every write committed to this database
+  /// so far. Must be called on the primary.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME))]
+  pub async fn consistency_token(&self) -> DatabaseResult<ConsistencyToken> {
+    let row = sqlx::query("SELECT pg_current_wal_lsn()::text AS lsn")
+      .fetch_one(&self.pool)
+      .await
+      .into_diagnostic()
+      .context("failed to read the current WAL position")
+      .map_err(DatabaseError::Database)?;
+    let lsn: String = row
+      .try_get("lsn")
+      .into_diagnostic()
+      .map_err(DatabaseError::Serialization)?;
+    lsn.parse().map(ConsistencyToken)
+  }
+}
+
+/// Waits up to `timeout` for the replica behind `replica` to replay the
+/// write-ahead log up to `token`, returning whether it did. If it didn't,
+/// the read should go to the primary instead.
+///
+/// A pool connected to a primary has nothing to replay, so is always caught
+/// up.
+#[instrument(skip(replica))]
+pub async fn wait_for_replica(
+  replica: &PgPool,
+  token: ConsistencyToken,
+  timeout: Duration,
+) -> DatabaseResult<bool> {
+  let deadline = tokio::time::Instant::now() + timeout;
+  loop {
+    let row = sqlx::query("SELECT pg_last_wal_replay_lsn()::text AS lsn")
+      .fetch_one(replica)
+      .await
+      .into_diagnostic()
+      .context("failed to read the replica's WAL replay position")
+      .map_err(DatabaseError::Database)?;
+    let replayed: Option<String> = row
+      .try_get("lsn")
+      .into_diagnostic()
+      .map_err(DatabaseError::Serialization)?;
+    let Some(replayed) = replayed else {
+      return Ok(true);
+    };
+    if replayed.parse::<Lsn>()? >= token.0 {
+      return Ok(true);
+    }
+
+    if tokio::time::Instant::now() + POLL_INTERVAL > deadline {
+      debug!(%replayed, "replica didn't catch up in time");
+      return Ok(false);
+    }
+    tokio::time::sleep(POLL_INTERVAL).await;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ConsistencyToken, Lsn};
+
+  #[test]
+  fn test_token_round_trip() {
+    let token: ConsistencyToken = "16/B374D848".parse().unwrap();
+    assert_eq!(token, ConsistencyToken(Lsn(0x16_B374_D848)));
+    assert_eq!(token.to_string(), "16/B374D848");
+    assert!("16B374D848".parse::<ConsistencyToken>().is_err());
+  }
+}
@@ -1,4 +1,7 @@
-use db_core::{DatabaseLike, DatabaseResult};
+use std::ops::Bound;
+
+use chrono::{DateTime, Utc};
+use db_core::{DatabaseLike, DatabaseResult, Page, SchemaDescription};
 use model::{IndexValue, Model, RecordId};
 
 use crate::PostgresDatabase;
@@ -6,29 +9,119 @@ use crate::PostgresDatabase;
 #[async_trait::async_trait]
 impl<M: Model> DatabaseLike<M> for PostgresDatabase<M> {
   async fn initialize_schema(&self) -> DatabaseResult<()> {
-    self.initialize_schema().await
+    self
+      .timed("initialize_schema", self.initialize_schema())
+      .await
+  }
+
+  async fn rebuild_indices(&self) -> DatabaseResult<()> {
+    self.timed("rebuild_indices", self.rebuild_indices()).await
   }
 
   async fn insert(&self, model: &M) -> DatabaseResult<()> {
-    self.insert(model).await
+    self.timed("insert", self.insert(model)).await
   }
 
   async fn update(&self, model: &M) -> DatabaseResult<()> {
-    self.update(model).await
+    self.timed("update", self.update(model)).await
+  }
+
+  async fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()> {
+    self
+      .timed(
+        "update_if_unchanged",
+        self.update_if_unchanged(model, expected_updated_at),
+      )
+      .await
+  }
+
+  async fn insert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    self
+      .timed("insert_and_return", self.insert_and_return(model))
+      .await
+  }
+
+  async fn upsert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    self
+      .timed("upsert_and_return", self.upsert_and_return(model))
+      .await
+  }
+
+  async fn upsert_with(
+    &self,
+    id: RecordId<M>,
+    update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+  ) -> DatabaseResult<M> {
+    self
+      .timed("upsert_with", self.upsert_with(id, update))
+      .await
+  }
+
+  async fn increment(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64> {
+    self
+      .timed("increment", self.increment(id, field_path, delta))
+      .await
   }
 
   async fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
-    self.delete(id).await
+    self.timed("delete", self.delete(id)).await
   }
 
   async fn delete_and_return(&self, id: RecordId<M>) -> DatabaseResult<M> {
-    let model = self.get_or_error(id).await?;
-    self.delete(id).await?;
-    Ok(model)
+    self
+      .timed("delete_and_return", async {
+        let model = self.get_or_error(id).await?;
+        self.delete(id).await?;
+        Ok(model)
+      })
+      .await
+  }
+
+  async fn updated_at(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<DateTime<Utc>>> {
+    self.timed("updated_at", self.updated_at(id)).await
   }
 
   async fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
-    self.get(id).await
+    self.timed("get", self.get(id)).await
+  }
+
+  async fn get_raw(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    self.timed("get_raw", self.get_raw(id)).await
+  }
+
+  async fn get_field(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    self
+      .timed("get_field", self.get_field(id, field_path))
+      .await
+  }
+
+  async fn get_projected(
+    &self,
+    id: RecordId<M>,
+    fields: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    self
+      .timed("get_projected", self.get_projected(id, fields))
+      .await
   }
 
   async fn find_by_unique_index(
@@ -36,7 +129,12 @@ impl<M: Model> DatabaseLike<M> for PostgresDatabase<M> {
     selector: M::IndexSelector,
     key: &IndexValue,
   ) -> DatabaseResult<Option<M>> {
-    self.find_by_unique_index(selector, key).await
+    self
+      .timed(
+        "find_by_unique_index",
+        self.find_by_unique_index(selector, key),
+      )
+      .await
   }
 
   async fn find_by_index(
@@ -44,16 +142,111 @@ impl<M: Model> DatabaseLike<M> for PostgresDatabase<M> {
     selector: M::IndexSelector,
     key: &IndexValue,
   ) -> DatabaseResult<Vec<M>> {
-    self.find_by_index(selector, key).await
+    self
+      .timed("find_by_index", self.find_by_index(selector, key))
+      .await
+  }
+
+  async fn find_by_index_raw(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    self
+      .timed("find_by_index_raw", self.find_by_index_raw(selector, key))
+      .await
+  }
+
+  async fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>> {
+    self
+      .timed(
+        "find_by_index_range",
+        self.find_by_index_range(selector, lower, upper),
+      )
+      .await
+  }
+
+  async fn claim_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+    limit: u32,
+    claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+  ) -> DatabaseResult<Vec<M>> {
+    self
+      .timed(
+        "claim_by_index_range",
+        self.claim_by_index_range(selector, lower, upper, limit, claim),
+      )
+      .await
   }
 
   async fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
-    self.list(limit, offset).await
+    self.timed("list", self.list(limit, offset)).await
+  }
+
+  async fn list_projected(
+    &self,
+    limit: u32,
+    offset: u32,
+    fields: &[&str],
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    self
+      .timed("list_projected", self.list_projected(limit, offset, fields))
+      .await
+  }
+
+  async fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    self
+      .timed("list_after", self.list_after(after, limit))
+      .await
+  }
+
+  async fn search(&self, query: &str, limit: u32) -> DatabaseResult<Vec<M>> {
+    self.timed("search", self.search(query, limit)).await
+  }
+
+  async fn list_page(
+    &self,
+    limit: u32,
+    offset: u32,
+  ) -> DatabaseResult<Page<M>> {
+    self.timed("list_page", self.list_page(limit, offset)).await
+  }
+
+  async fn count(&self) -> DatabaseResult<u64> {
+    self.timed("count", self.count()).await
+  }
+
+  async fn truncate(&self) -> DatabaseResult<()> {
+    self.timed("truncate", self.truncate()).await
+  }
+
+  async fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+    self.timed("sample", self.sample(n)).await
+  }
+
+  async fn estimate_count(&self) -> DatabaseResult<u64> {
+    self.timed("estimate_count", self.estimate_count()).await
   }
 
-  async fn count(&self) -> DatabaseResult<u64> { self.count().await }
+  async fn describe_schema(&self) -> DatabaseResult<SchemaDescription> {
+    self.timed("describe_schema", self.describe_schema()).await
+  }
 
   async fn exists(&self, id: RecordId<M>) -> DatabaseResult<bool> {
-    Ok(self.get(id).await?.is_some())
+    self
+      .timed("exists", async { Ok(self.get(id).await?.is_some()) })
+      .await
   }
 }
@@ -1,6 +1,8 @@
-use db_core::{DatabaseError, DatabaseResult};
+use std::ops::Bound;
+
+use db_core::{DatabaseError, DatabaseResult, index_keys, parse_index_key};
 use miette::{Context, IntoDiagnostic, Report};
-use model::{IndexDefinition, Model, RecordId};
+use model::{IndexDefinition, IndexKind, IndexValue, Model, RecordId};
 use sqlx::Postgres;
 use tracing::{debug, instrument};
 
@@ -8,8 +10,9 @@ use crate::PostgresDatabase;
 
 impl<M: Model> PostgresDatabase<M> {
   /// Create index tables for all indices defined in the model.
-  #[instrument(skip(tx), fields(model = M::TABLE_NAME))]
+  #[instrument(skip(self, tx), fields(model = M::TABLE_NAME))]
   pub(crate) async fn create_index_tables(
+    &self,
     tx: &mut sqlx::Transaction<'_, Postgres>,
   ) -> DatabaseResult<()> {
     let indices = M::indices();
@@ -18,15 +21,18 @@ impl<M: Model> PostgresDatabase<M> {
     debug!("Creating {} index tables", index_count);
 
     for def in indices.definitions {
-      let index_table = Self::calculate_index_table_name(def);
+      let index_table = self.calculate_index_table_name(def);
+      let local_name = self.namespace.local(&Self::index_table_suffix(def));
       let query = format!(
         "CREATE TABLE IF NOT EXISTS {index_table} (
-            index_key TEXT NOT NULL,
+            index_key {key_type}{collation} NOT NULL,
             record_id TEXT NOT NULL REFERENCES {table_name}(id) ON DELETE \
          CASCADE
             {unique_constraint}
         )",
-        table_name = M::TABLE_NAME,
+        table_name = self.table_name(),
+        key_type = Self::index_sql_type(def.kind),
+        collation = Self::index_collation(def.kind),
         unique_constraint = if def.unique {
           ", UNIQUE (index_key)"
         } else {
@@ -43,7 +49,7 @@ impl<M: Model> PostgresDatabase<M> {
 
       // Create index on index_key for efficient lookups
       let btree_index = format!(
-        "CREATE INDEX IF NOT EXISTS idx_{index_table}_key ON \
+        "CREATE INDEX IF NOT EXISTS idx_{local_name}_key ON \
          {index_table}(index_key)"
       );
       sqlx::query(&btree_index)
@@ -58,7 +64,7 @@ impl<M: Model> PostgresDatabase<M> {
       // For non-unique indices, also index by record_id for efficient deletion
       if !def.unique {
         let record_id_index = format!(
-          "CREATE INDEX IF NOT EXISTS idx_{index_table}_record ON \
+          "CREATE INDEX IF NOT EXISTS idx_{local_name}_record ON \
            {index_table}(record_id)"
         );
         sqlx::query(&record_id_index)
@@ -89,17 +95,16 @@ impl<M: Model> PostgresDatabase<M> {
     let indices = M::indices();
 
     for def in indices.definitions {
-      let index_table = Self::calculate_index_table_name(def);
-      let values = def.extract(model);
+      let query = &self.queries.insert_index_entry[def.name];
+      let values = self.index_pipeline.extract(def, model).await?;
 
-      for value in values {
-        let index_key = value.to_string();
+      let keys = index_keys(def, &values)?
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
 
-        let query = format!(
-          "INSERT INTO {index_table} (index_key, record_id) VALUES ($1, $2)"
-        );
-
-        match sqlx::query(&query)
+      for index_key in keys {
+        match sqlx::query(query)
           .bind(&index_key)
           .bind(&id)
           .execute(&mut **tx)
@@ -132,10 +137,7 @@ impl<M: Model> PostgresDatabase<M> {
     let indices = M::indices();
 
     for def in indices.definitions {
-      let index_table = Self::calculate_index_table_name(def);
-      let query = format!("DELETE FROM {index_table} WHERE record_id = $1");
-
-      sqlx::query(&query)
+      sqlx::query(&self.queries.delete_index_entries[def.name])
         .bind(id.to_string())
         .execute(&mut **tx)
         .await
@@ -146,10 +148,134 @@ impl<M: Model> PostgresDatabase<M> {
     Ok(())
   }
 
-  /// Calculate table name for a given index.
+  /// Run a queried index value through the pipeline and render it in the
+  /// canonical text form for its index kind, ready to be cast in SQL.
+  pub(crate) async fn index_key_text(
+    &self,
+    index_def: &IndexDefinition<M>,
+    key: &IndexValue,
+  ) -> DatabaseResult<String> {
+    let key = self
+      .index_pipeline
+      .apply(index_def.name, key.clone())
+      .await?;
+    Ok(parse_index_key(index_def, &key)?.to_string())
+  }
+
+  /// Build a `WHERE` clause restricting `i.index_key` to the given bounds,
+  /// along with the text binds for its `$n` parameters.
+  pub(crate) async fn index_range_clause(
+    &self,
+    index_def: &IndexDefinition<M>,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<(String, Vec<String>)> {
+    let key_type = Self::index_sql_type(index_def.kind);
+
+    // build up the conditions and binds for each bounded side
+    let mut conditions = Vec::new();
+    let mut binds = Vec::new();
+    for (bound, inclusive_op, exclusive_op) in
+      [(lower, ">=", ">"), (upper, "<=", "<")]
+    {
+      let (value, op) = match bound {
+        Bound::Included(value) => (value, inclusive_op),
+        Bound::Excluded(value) => (value, exclusive_op),
+        Bound::Unbounded => continue,
+      };
+      binds.push(self.index_key_text(index_def, value).await?);
+      conditions.push(format!(
+        "i.index_key {op} ${n}::{key_type}",
+        n = binds.len()
+      ));
+    }
+    let where_clause = if conditions.is_empty() {
+      String::new()
+    } else {
+      format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    Ok((where_clause, binds))
+  }
+
+  /// The SQL column type used to store keys of the given kind.
+  pub(crate) const fn index_sql_type(kind: IndexKind) -> &'static str {
+    match kind {
+      IndexKind::String | IndexKind::MultiValue => "TEXT",
+      IndexKind::I64 => "BIGINT",
+      IndexKind::F64 => "DOUBLE PRECISION",
+      IndexKind::Timestamp => "TIMESTAMPTZ",
+    }
+  }
+
+  /// The collation clause for the key column of an index of the given kind.
+  /// String keys are compared bytewise, matching the order of
+  /// [encoded](model::IndexValue::encode) values.
+  pub(crate) const fn index_collation(kind: IndexKind) -> &'static str {
+    match kind {
+      IndexKind::String | IndexKind::MultiValue => " COLLATE \"C\"",
+      IndexKind::I64 | IndexKind::F64 | IndexKind::Timestamp => "",
+    }
+  }
+
+  /// Recompute the entries of every index table from the main table, moving
+  /// string key columns to bytewise collation on the way.
+  #[instrument(skip(self, tx), fields(model = M::TABLE_NAME))]
+  pub(crate) async fn rebuild_index_tables(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+  ) -> DatabaseResult<()> {
+    for def in M::indices().definitions {
+      let index_table = self.calculate_index_table_name(def);
+      let collation = Self::index_collation(def.kind);
+      if !collation.is_empty() {
+        let query = format!(
+          "ALTER TABLE {index_table} ALTER COLUMN index_key TYPE \
+           {key_type}{collation}",
+          key_type = Self::index_sql_type(def.kind),
+        );
+        sqlx::query(&query)
+          .execute(&mut **tx)
+          .await
+          .into_diagnostic()
+          .with_context(|| {
+            format!("Failed to alter index table: {index_table}")
+          })
+          .map_err(DatabaseError::Other)?;
+      }
+
+      sqlx::query(&format!("DELETE FROM {index_table}"))
+        .execute(&mut **tx)
+        .await
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?;
+    }
+
+    let query = format!("SELECT id, data FROM {}", self.table_name());
+    let rows = sqlx::query(&query)
+      .fetch_all(&mut **tx)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+    for row in &rows {
+      let model = self.deserialize_from_row(row)?;
+      self.insert_indices(tx, &model).await?;
+    }
+
+    debug!(count = rows.len(), "Rebuilt index tables");
+    Ok(())
+  }
+
+  /// Calculate the qualified table name for a given index.
   pub(crate) fn calculate_index_table_name(
+    &self,
     index_def: &IndexDefinition<M>,
   ) -> String {
+    self.namespace.qualify(&Self::index_table_suffix(index_def))
+  }
+
+  /// The name of an index's table before namespacing.
+  pub(crate) fn index_table_suffix(index_def: &IndexDefinition<M>) -> String {
     format!("{}__idx_{}", M::TABLE_NAME, index_def.name)
   }
 }
@@ -0,0 +1,227 @@
+use std::pin::Pin;
+
+use db_core::{DatabaseError, DatabaseResult};
+use futures::{Stream, stream};
+use miette::{Context, IntoDiagnostic};
+use model::{Model, RecordId};
+use sqlx::{Postgres, postgres::PgListener};
+use tracing::{debug, instrument, warn};
+
+use crate::PostgresDatabase;
+
+/// A notification that cached copies of a model's records are stale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalidation<M> {
+  /// The record with this ID was inserted, updated or deleted.
+  Record(RecordId<M>),
+  /// Notifications may have been missed, so every cached record is stale.
+  All,
+}
+
+/// A stream of [`Invalidation`]s, as returned by
+/// [`PostgresDatabase::subscribe_invalidations`].
+pub type InvalidationStream<M> =
+  Pin<Box<dyn Stream<Item = DatabaseResult<Invalidation<M>>> + Send>>;
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Publish an invalidation on every write to the main table, for caches in
+  /// other processes to subscribe to with
+  /// [`subscribe_invalidations`](Self::subscribe_invalidations).
+  ///
+  /// Writes are published by a trigger created with the schema, so every
+  /// write is covered, including bulk loads and raw SQL. Notifications are
+  /// only delivered once the writing transaction commits. Initializing the
+  /// schema without notifications drops the trigger again.
+  #[must_use]
+  pub const fn with_invalidation_notifications(mut self) -> Self {
+    self.notify_invalidations = true;
+    self
+  }
+
+  /// The `LISTEN`/`NOTIFY` channel invalidations of this model's table are
+  /// published on.
+  #[must_use]
+  pub fn invalidation_channel(&self) -> String {
+    format!("{}_invalidations", self.table_name())
+  }
+
+  /// Create the trigger publishing invalidations if they are enabled, or
+  /// drop it if they aren't, so a table stops publishing once notifications
+  /// are turned off.
+  pub(crate) async fn create_invalidation_trigger(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+  ) -> DatabaseResult<()> {
+    let queries = invalidation_trigger_queries(
+      &self.table_name(),
+      &format!(
+        "{}_notify_invalidation",
+        self.namespace.local(M::TABLE_NAME)
+      ),
+      &self.invalidation_channel(),
+      self.notify_invalidations,
+    );
+    for query in &queries {
+      sqlx::query(query)
+        .execute(&mut **tx)
+        .await
+        .into_diagnostic()
+        .context("failed to create invalidation trigger")
+        .map_err(DatabaseError::Other)?;
+    }
+
+    debug!(
+      enabled = self.notify_invalidations,
+      "Invalidation trigger synced"
+    );
+    Ok(())
+  }
+
+  /// Subscribe to the invalidations published by writers with
+  /// [`with_invalidation_notifications`](Self::with_invalidation_notifications)
+  /// enabled.
+  ///
+  /// The subscription holds a dedicated connection. If it drops, it's
+  /// re-established and [`Invalidation::All`] is yielded, as notifications
+  /// sent in the meantime are lost.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME))]
+  pub async fn subscribe_invalidations(
+    &self,
+  ) -> DatabaseResult<InvalidationStream<M>> {
+    let mut listener = PgListener::connect_with(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+    listener
+      .listen(&self.invalidation_channel())
+      .await
+      .into_diagnostic()
+      .context("failed to listen for invalidations")
+      .map_err(DatabaseError::Database)?;
+
+    debug!("Subscribed to invalidations");
+    Ok(Box::pin(stream::unfold(
+      listener,
+      |mut listener| async move {
+        let invalidation = match listener.try_recv().await {
+          Ok(Some(notification)) => parse_payload(notification.payload()),
+          Ok(None) => {
+            warn!("Invalidation connection lost, reconnecting");
+            Ok(Invalidation::All)
+          }
+          Err(e) => Err(e)
+            .into_diagnostic()
+            .context("failed to receive invalidation")
+            .map_err(DatabaseError::Database),
+        };
+        Some((invalidation, listener))
+      },
+    )))
+  }
+}
+
+/// The statements creating the trigger `trigger` on `table`, which publishes
+/// the ID of every written row on `channel`, or dropping it if not
+/// `enabled`.
+fn invalidation_trigger_queries(
+  table: &str,
+  trigger: &str,
+  channel: &str,
+  enabled: bool,
+) -> Vec<String> {
+  let function = format!("{table}_notify_invalidation");
+  let drop = format!("DROP TRIGGER IF EXISTS {trigger} ON {table}");
+  if !enabled {
+    return vec![drop, format!("DROP FUNCTION IF EXISTS {function}()")];
+  }
+
+  vec![
+    format!(
+      "CREATE OR REPLACE FUNCTION {function}() RETURNS trigger AS $$
+      BEGIN
+        PERFORM pg_notify(
+          '{channel}',
+          CASE WHEN TG_OP = 'DELETE' THEN OLD.id ELSE NEW.id END
+        );
+        RETURN NULL;
+      END;
+      $$ LANGUAGE plpgsql",
+    ),
+    drop,
+    format!(
+      "CREATE TRIGGER {trigger} AFTER INSERT OR UPDATE OR DELETE ON {table} \
+       FOR EACH ROW EXECUTE FUNCTION {function}()",
+    ),
+  ]
+}
+
+/// Parse the payload of an invalidation notification: the written row's ID.
+fn parse_payload<M>(payload: &str) -> DatabaseResult<Invalidation<M>> {
+  payload
+    .parse()
+    .into_diagnostic()
+    .context("invalid invalidation payload")
+    .map(Invalidation::Record)
+    .map_err(DatabaseError::Serialization)
+}
+
+#[cfg(test)]
+mod tests {
+  use model::RecordId;
+
+  use super::{Invalidation, invalidation_trigger_queries, parse_payload};
+
+  #[test]
+  fn test_invalidation_trigger_queries() {
+    let queries = invalidation_trigger_queries(
+      "app.users",
+      "users_notify_invalidation",
+      "app.users_invalidations",
+      true,
+    );
+    assert_eq!(queries.len(), 3);
+    assert!(queries[0].starts_with(
+      "CREATE OR REPLACE FUNCTION app.users_notify_invalidation()"
+    ));
+    assert!(queries[0].contains("pg_notify("));
+    assert!(queries[0].contains("'app.users_invalidations'"));
+    assert!(
+      queries[0]
+        .contains("CASE WHEN TG_OP = 'DELETE' THEN OLD.id ELSE NEW.id END")
+    );
+    assert_eq!(
+      queries[1],
+      "DROP TRIGGER IF EXISTS users_notify_invalidation ON app.users"
+    );
+    assert_eq!(
+      queries[2],
+      "CREATE TRIGGER users_notify_invalidation AFTER INSERT OR UPDATE OR \
+       DELETE ON app.users FOR EACH ROW EXECUTE FUNCTION \
+       app.users_notify_invalidation()"
+    );
+  }
+
+  #[test]
+  fn test_disabled_invalidation_trigger_is_dropped() {
+    let queries = invalidation_trigger_queries(
+      "users",
+      "users_notify_invalidation",
+      "users_invalidations",
+      false,
+    );
+    assert_eq!(queries, [
+      "DROP TRIGGER IF EXISTS users_notify_invalidation ON users",
+      "DROP FUNCTION IF EXISTS users_notify_invalidation()",
+    ]);
+  }
+
+  #[test]
+  fn test_parse_payload() {
+    let id = RecordId::<()>::from_ulid_u128(1);
+    assert_eq!(
+      parse_payload(&id.to_string()).unwrap(),
+      Invalidation::Record(id)
+    );
+    assert!(parse_payload::<()>("not an id").is_err());
+  }
+}
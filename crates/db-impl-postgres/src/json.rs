@@ -0,0 +1,198 @@
+use db_core::{DatabaseError, DatabaseResult, json_field, project_fields};
+use miette::IntoDiagnostic;
+use model::{IndexValue, Model, RecordId};
+use sqlx::{Row, postgres::PgRow};
+use tracing::{debug, instrument};
+
+use crate::PostgresDatabase;
+
+/// Selects the `data` column of the main table with only the top-level
+/// fields bound as `$1`.
+///
+/// Only for unversioned models: this drops the version field, and older
+/// rows of versioned models must be upgraded before their fields can be
+/// picked by their current names.
+const PROJECTED_DATA: &str = "COALESCE((SELECT jsonb_object_agg(key, value) \
+                              FROM jsonb_each(data) WHERE key = \
+                              ANY($1::text[])), '{}'::jsonb) AS data";
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Retrieve the stored JSON of a model by ID, without deserializing it.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, id = %id))]
+  pub(crate) async fn get_raw(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    debug!("Getting raw model by ID");
+
+    let row: Option<PgRow> = sqlx::query(&self.queries.get)
+      .bind(id.to_string())
+      .fetch_optional(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    row.map(|row| self.raw_from_row(&row)).transpose()
+  }
+
+  /// Retrieve one field of the stored JSON of a model by ID.
+  ///
+  /// The field is extracted by Postgres, so only it is transferred, unless
+  /// it's within an encrypted field, which can only be decrypted here, or the
+  /// model is versioned, as older rows must be upgraded here.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, id = %id))]
+  pub(crate) async fn get_field(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    let encrypted = field_path
+      .first()
+      .map_or(!M::ENCRYPTED_FIELDS.is_empty(), |field| {
+        M::ENCRYPTED_FIELDS.contains(field)
+      });
+    if encrypted || M::VERSION > 0 {
+      debug!("Getting field through the whole model");
+      let data = self.get_raw(id).await?;
+      return Ok(data.and_then(|data| json_field(data, field_path)));
+    }
+
+    debug!("Getting field");
+    let query = format!(
+      "SELECT data #> $1::text[] AS field FROM {table_name} WHERE id = $2",
+      table_name = self.table_name()
+    );
+    let row: Option<PgRow> = sqlx::query(&query)
+      .bind(field_path)
+      .bind(id.to_string())
+      .fetch_optional(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    let Some(row) = row else {
+      return Ok(None);
+    };
+    row
+      .try_get("field")
+      .into_diagnostic()
+      .map_err(DatabaseError::Serialization)
+  }
+
+  /// Find the stored JSON of all models matching a non-unique index, without
+  /// deserializing them.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, selector = %selector, key = %key))]
+  pub(crate) async fn find_by_index_raw(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    debug!("Finding raw models by index");
+
+    let indices = M::indices();
+    let index_def = indices
+      .get(selector)
+      .ok_or_else(|| DatabaseError::IndexNotFound(selector.to_string()))?;
+    let index_key = self.index_key_text(index_def, key).await?;
+
+    let rows: Vec<PgRow> =
+      sqlx::query(&self.queries.find_by_index[index_def.name])
+        .bind(index_key)
+        .fetch_all(&self.pool)
+        .await
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?;
+
+    debug!(count = rows.len(), "Found raw models by index");
+    rows.iter().map(|row| self.raw_from_row(row)).collect()
+  }
+
+  /// Retrieve only the given top-level fields of the stored JSON of a model
+  /// by ID.
+  ///
+  /// The fields are picked by Postgres, unless the model is versioned, as
+  /// older rows must be upgraded here first.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, id = %id))]
+  pub(crate) async fn get_projected(
+    &self,
+    id: RecordId<M>,
+    fields: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    if M::VERSION > 0 {
+      debug!("Getting projected model through the whole model");
+      let data = self.get_raw(id).await?;
+      return Ok(data.map(|data| project_fields(data, fields)));
+    }
+
+    debug!("Getting projected model by ID");
+
+    let query = format!(
+      "SELECT id, {PROJECTED_DATA} FROM {table_name} WHERE id = $2",
+      table_name = self.table_name()
+    );
+    let row: Option<PgRow> = sqlx::query(&query)
+      .bind(fields)
+      .bind(id.to_string())
+      .fetch_optional(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    row.map(|row| self.raw_from_row(&row)).transpose()
+  }
+
+  /// List only the given top-level fields of the stored JSON of models,
+  /// ordered by `updated_at` descending like
+  /// [`list`](db_core::DatabaseLike::list).
+  ///
+  /// The fields are picked by Postgres, unless the model is versioned, as
+  /// older rows must be upgraded here first.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, limit = limit, offset = offset))]
+  pub(crate) async fn list_projected(
+    &self,
+    limit: u32,
+    offset: u32,
+    fields: &[&str],
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    let versioned = M::VERSION > 0;
+    let query = if versioned {
+      debug!("Listing projected models through the whole models");
+      format!(
+        "SELECT id, data FROM {table_name} ORDER BY updated_at DESC LIMIT $1 \
+         OFFSET $2",
+        table_name = self.table_name()
+      )
+    } else {
+      debug!("Listing projected models");
+      format!(
+        "SELECT id, {PROJECTED_DATA} FROM {table_name} ORDER BY updated_at \
+         DESC LIMIT $2 OFFSET $3",
+        table_name = self.table_name()
+      )
+    };
+    let mut sql_query = sqlx::query(&query);
+    if !versioned {
+      sql_query = sql_query.bind(fields);
+    }
+    let rows: Vec<PgRow> = sql_query
+      .bind(i64::from(limit))
+      .bind(i64::from(offset))
+      .fetch_all(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    debug!(count = rows.len(), "Listed projected models");
+    rows
+      .iter()
+      .map(|row| {
+        let data = self.raw_from_row(row)?;
+        Ok(if versioned {
+          project_fields(data, fields)
+        } else {
+          data
+        })
+      })
+      .collect()
+  }
+}
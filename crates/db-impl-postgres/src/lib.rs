@@ -1,22 +1,68 @@
 //! Postgres storage implementation for models.
 
+mod bulk;
+#[cfg(feature = "cdc")]
+mod cdc;
+mod columns;
+mod connect;
+#[cfg(feature = "read-your-writes")]
+mod consistency;
 mod db_impl;
 mod indices;
-
-use std::marker::PhantomData;
-
-use db_core::{DatabaseError, DatabaseResult};
+mod invalidation;
+mod json;
+mod lsn;
+mod namespace;
+mod queries;
+#[cfg(feature = "raw-sql")]
+mod raw;
+mod schema;
+mod scope;
+mod search;
+mod slow_query;
+#[cfg(feature = "otel")]
+mod telemetry;
+
+use std::{marker::PhantomData, ops::Bound, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use db_core::{
+  DatabaseError, DatabaseResult, FieldCipher, IndexPipeline, Page,
+  decrypt_fields, encrypt_fields,
+};
 use miette::{Context, IntoDiagnostic};
-use model::{IndexValue, Model, RecordId};
-pub use sqlx::PgPool;
-use sqlx::{Postgres, Row, postgres::PgRow};
+use model::{IndexValue, Model, RecordId, stamp_version, upgrade_json};
+use sqlx::{PgExecutor, Postgres, Row, ValueRef, postgres::PgRow};
+pub use sqlx::{PgPool, postgres::PgPoolOptions};
 use tracing::{debug, instrument, warn};
 
+#[cfg(feature = "cdc")]
+pub use self::cdc::{CdcEvent, CdcOptions, CdcPosition, ChangeStream};
+#[cfg(feature = "read-your-writes")]
+pub use self::consistency::{ConsistencyToken, wait_for_replica};
+use self::queries::Queries;
+#[cfg(feature = "raw-sql")]
+pub use self::raw::RawBind;
+pub use self::{
+  connect::{PostgresConnectOptions, PostgresSslMode},
+  invalidation::{Invalidation, InvalidationStream},
+  lsn::Lsn,
+  namespace::TableNamespace,
+  scope::PgTransactionScope,
+  slow_query::{SLOW_QUERY_TABLE, SlowQueryLog},
+};
+
 /// Postgres-backed storage for models implementing the [`Model`] trait.
 #[derive(Clone)]
 pub struct PostgresDatabase<M: Model> {
-  pool:     PgPool,
-  _phantom: PhantomData<M>,
+  pool:                 PgPool,
+  index_pipeline:       IndexPipeline,
+  field_cipher:         Option<Arc<dyn FieldCipher>>,
+  slow_query_log:       Option<SlowQueryLog>,
+  notify_invalidations: bool,
+  namespace:            TableNamespace,
+  queries:              Arc<Queries>,
+  _phantom:             PhantomData<M>,
 }
 
 macro_rules! with_transaction {
@@ -43,6 +89,7 @@ macro_rules! with_transaction {
     }
   }};
 }
+pub(crate) use with_transaction;
 
 impl<M: Model> PostgresDatabase<M> {
   /// Create a new [`PostgresDatabase`] with the given connection URL.
@@ -56,16 +103,74 @@ impl<M: Model> PostgresDatabase<M> {
     ))
   }
 
+  /// Create a new [`PostgresDatabase`] with the given connection options.
+  #[instrument(fields(model = M::TABLE_NAME))]
+  pub async fn new_with_options(
+    options: &PostgresConnectOptions,
+  ) -> miette::Result<Self> {
+    Ok(Self::new_from_pool(
+      PgPool::connect_with(options.to_pg_connect_options())
+        .await
+        .into_diagnostic()
+        .context("failed to connect to database")?,
+    ))
+  }
+
   /// Create a new [`PostgresDatabase`] from the given postgres pool.
   #[must_use]
   pub fn new_from_pool(pool: PgPool) -> Self {
     debug!("Creating PostgresDatabase for model");
+    let namespace = TableNamespace::default();
     Self {
       pool,
+      index_pipeline: IndexPipeline::new(),
+      field_cipher: None,
+      slow_query_log: None,
+      notify_invalidations: false,
+      queries: Arc::new(Self::generate_queries(&namespace)),
+      namespace,
       _phantom: PhantomData,
     }
   }
 
+  /// Sets the [`IndexPipeline`] applied to index values on write and to index
+  /// keys on query.
+  #[must_use]
+  pub fn with_index_pipeline(mut self, index_pipeline: IndexPipeline) -> Self {
+    self.index_pipeline = index_pipeline;
+    self
+  }
+
+  /// Sets the [`FieldCipher`] used to encrypt the model's
+  /// [`ENCRYPTED_FIELDS`](Model::ENCRYPTED_FIELDS) before they are written.
+  ///
+  /// Models with encrypted fields can't be written or read without one.
+  #[must_use]
+  pub fn with_field_cipher(mut self, cipher: Arc<dyn FieldCipher>) -> Self {
+    self.field_cipher = Some(cipher);
+    self
+  }
+
+  /// Reports operations slower than the [`SlowQueryLog`] threshold.
+  #[must_use]
+  pub const fn with_slow_query_log(mut self, log: SlowQueryLog) -> Self {
+    self.slow_query_log = Some(log);
+    self
+  }
+
+  /// Stores this model's tables in the given [`TableNamespace`].
+  #[must_use]
+  pub fn with_namespace(mut self, namespace: TableNamespace) -> Self {
+    self.queries = Arc::new(Self::generate_queries(&namespace));
+    self.namespace = namespace;
+    self
+  }
+
+  /// The qualified name of the main table.
+  pub(crate) fn table_name(&self) -> String {
+    self.namespace.qualify(M::TABLE_NAME)
+  }
+
   /// Initialize the database schema for this model.
   /// Creates the main table and all index tables.
   #[instrument(skip(self), fields(model = M::TABLE_NAME))]
@@ -73,20 +178,63 @@ impl<M: Model> PostgresDatabase<M> {
     with_transaction!(self, tx, {
       debug!("Initializing database schema...");
 
-      Self::create_main_table(&mut tx).await?;
-      Self::create_index_tables(&mut tx).await?;
+      self.create_schema(&mut tx).await?;
+      self.create_main_table(&mut tx).await?;
+      self.create_columns(&mut tx).await?;
+      self.create_search_column(&mut tx).await?;
+      self.create_index_tables(&mut tx).await?;
+      self.create_slow_query_table(&mut tx).await?;
+      self.create_invalidation_trigger(&mut tx).await?;
 
       debug!("Schema initialization complete");
       Ok(())
     })
   }
 
+  /// Recompute every index entry from the stored models.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME))]
+  async fn rebuild_indices(&self) -> DatabaseResult<()> {
+    with_transaction!(self, tx, {
+      // keep writers out until the rebuilt indices are committed
+      let lock = format!("LOCK TABLE {} IN SHARE MODE", self.table_name());
+      sqlx::query(&lock)
+        .execute(&mut *tx)
+        .await
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?;
+
+      self.rebuild_index_tables(&mut tx).await
+    })
+  }
+
+  /// Create the namespace's schema, if it has one.
+  #[instrument(skip(self, tx), fields(model = M::TABLE_NAME))]
+  async fn create_schema(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+  ) -> DatabaseResult<()> {
+    let Some(schema) = self.namespace.schema() else {
+      return Ok(());
+    };
+    debug!("Creating schema: {schema}");
+
+    sqlx::query(&format!("CREATE SCHEMA IF NOT EXISTS {schema}"))
+      .execute(&mut **tx)
+      .await
+      .into_diagnostic()
+      .context("failed to create schema")
+      .map_err(DatabaseError::Other)?;
+    Ok(())
+  }
+
   /// Create the main data table.
-  #[instrument(skip(tx), fields(model = M::TABLE_NAME))]
+  #[instrument(skip(self, tx), fields(model = M::TABLE_NAME))]
   async fn create_main_table(
+    &self,
     tx: &mut sqlx::Transaction<'_, Postgres>,
   ) -> DatabaseResult<()> {
-    debug!("Creating main table: {}", M::TABLE_NAME);
+    let table_name = self.table_name();
+    debug!("Creating main table: {table_name}");
 
     let query = format!(
       "CREATE TABLE IF NOT EXISTS {table_name} (
@@ -94,8 +242,7 @@ impl<M: Model> PostgresDatabase<M> {
           data JSONB NOT NULL,
           created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
           updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-      )",
-      table_name = M::TABLE_NAME
+      )"
     );
 
     sqlx::query(&query)
@@ -107,9 +254,9 @@ impl<M: Model> PostgresDatabase<M> {
 
     // Create index on updated_at for efficient queries
     let index_query = format!(
-      "CREATE INDEX IF NOT EXISTS idx_{table_name}_updated_at ON \
+      "CREATE INDEX IF NOT EXISTS idx_{local_name}_updated_at ON \
        {table_name}(updated_at)",
-      table_name = M::TABLE_NAME
+      local_name = self.namespace.local(M::TABLE_NAME),
     );
     sqlx::query(&index_query)
       .execute(&mut **tx)
@@ -125,68 +272,174 @@ impl<M: Model> PostgresDatabase<M> {
   /// Insert a new model into the database.
   #[instrument(skip(self, model), fields(model = M::TABLE_NAME, id = %model.id()))]
   async fn insert(&self, model: &M) -> DatabaseResult<()> {
-    with_transaction!(self, tx, {
-      debug!("Inserting model");
+    with_transaction!(self, tx, { self.insert_in_tx(&mut tx, model).await })
+  }
 
-      let id = model.id().to_string();
-      let data = Self::serialize(model)?;
+  /// Insert a new model within an open transaction.
+  pub(crate) async fn insert_in_tx(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    model: &M,
+  ) -> DatabaseResult<()> {
+    debug!("Inserting model");
 
-      // Insert into main table
-      let table_name = M::TABLE_NAME;
-      let query =
-        format!("INSERT INTO {table_name} (id, data) VALUES ($1, $2)");
+    let id = model.id().to_string();
+    let data = self.serialize(model)?;
 
-      sqlx::query(&query)
-        .bind(&id)
-        .bind(&data)
-        .execute(&mut *tx)
+    // Insert into main table
+    sqlx::query(&self.queries.insert)
+      .bind(&id)
+      .bind(&data)
+      .execute(&mut **tx)
+      .await
+      .into_diagnostic()
+      .with_context(|| {
+        format!("failed to insert into main table: {}", self.table_name())
+      })
+      .map_err(DatabaseError::Database)?;
+
+    self.insert_indices(tx, model).await?;
+
+    debug!("Model inserted successfully");
+
+    Ok(())
+  }
+
+  /// Insert a new model, returning it as stored by the database.
+  #[instrument(skip(self, model), fields(model = M::TABLE_NAME, id = %model.id()))]
+  async fn insert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    with_transaction!(self, tx, {
+      debug!("Inserting model");
+
+      let row: PgRow = sqlx::query(&self.queries.insert_returning)
+        .bind(model.id().to_string())
+        .bind(self.serialize(model)?)
+        .fetch_one(&mut *tx)
         .await
         .into_diagnostic()
-        .with_context(|| {
-          format!("failed to insert into main table: {table_name}")
-        })
         .map_err(DatabaseError::Database)?;
+      let stored = self.deserialize_from_row(&row)?;
 
-      self.insert_indices(&mut tx, model).await?;
+      self.insert_indices(&mut tx, &stored).await?;
 
       debug!("Model inserted successfully");
+      Ok(stored)
+    })
+  }
 
-      Ok(())
+  /// Insert or update a model in a single statement, returning it as stored
+  /// by the database.
+  #[instrument(skip(self, model), fields(model = M::TABLE_NAME, id = %model.id()))]
+  async fn upsert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    with_transaction!(self, tx, {
+      debug!("Upserting model");
+
+      let id = model.id();
+      let row: PgRow = sqlx::query(&self.queries.upsert_returning)
+        .bind(id.to_string())
+        .bind(self.serialize(model)?)
+        .fetch_one(&mut *tx)
+        .await
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?;
+      let stored = self.deserialize_from_row(&row)?;
+
+      self.delete_indices(&mut tx, id).await?;
+      self.insert_indices(&mut tx, &stored).await?;
+
+      debug!("Model upserted successfully");
+      Ok(stored)
     })
   }
 
   /// Update an existing model in the database.
   #[instrument(skip(self, model), fields(model = M::TABLE_NAME, id = %model.id()))]
   async fn update(&self, model: &M) -> DatabaseResult<()> {
-    with_transaction!(self, tx, {
-      debug!("Updating model");
+    with_transaction!(self, tx, { self.update_in_tx(&mut tx, model).await })
+  }
 
-      let id = model.id();
-      let data = Self::serialize(model)?;
+  /// Update an existing model within an open transaction.
+  pub(crate) async fn update_in_tx(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    model: &M,
+  ) -> DatabaseResult<()> {
+    debug!("Updating model");
 
-      // Update main table
-      let table_name = M::TABLE_NAME;
-      let query = format!(
-        "UPDATE {table_name} SET data = $1, updated_at = NOW() WHERE id = $2"
-      );
+    let id = model.id();
+    let data = self.serialize(model)?;
+
+    // Update main table
+    let result = sqlx::query(&self.queries.update)
+      .bind(&data)
+      .bind(id.to_string())
+      .execute(&mut **tx)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    if result.rows_affected() == 0 {
+      warn!("Update failed: record not found");
+      return Err(DatabaseError::NotFound(id.to_string()));
+    }
 
-      let result = sqlx::query(&query)
+    // Delete old index entries
+    self.delete_indices(tx, id).await?;
+
+    // Insert new index entries
+    self.insert_indices(tx, model).await?;
+
+    debug!("Model updated successfully");
+    Ok(())
+  }
+
+  /// Update an existing model, unless its row was written after
+  /// `expected_updated_at`.
+  #[instrument(skip(self, model), fields(model = M::TABLE_NAME, id = %model.id()))]
+  async fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()> {
+    debug!("Updating model if unchanged");
+
+    let table_name = self.table_name();
+    let update_query = format!(
+      "UPDATE {table_name} SET data = $1, updated_at = NOW() WHERE id = $2 \
+       AND updated_at = $3"
+    );
+    let exists_query = format!("SELECT 1 FROM {table_name} WHERE id = $1");
+
+    let id = model.id();
+    let data = self.serialize(model)?;
+
+    with_transaction!(self, tx, {
+      let result = sqlx::query(&update_query)
         .bind(&data)
         .bind(id.to_string())
+        .bind(expected_updated_at)
         .execute(&mut *tx)
         .await
         .into_diagnostic()
         .map_err(DatabaseError::Database)?;
 
       if result.rows_affected() == 0 {
+        let exists = sqlx::query(&exists_query)
+          .bind(id.to_string())
+          .fetch_optional(&mut *tx)
+          .await
+          .into_diagnostic()
+          .map_err(DatabaseError::Database)?
+          .is_some();
+        if exists {
+          warn!("Update failed: record modified since it was read");
+          return Err(DatabaseError::Conflict(id.to_string()));
+        }
         warn!("Update failed: record not found");
         return Err(DatabaseError::NotFound(id.to_string()));
       }
 
-      // Delete old index entries
       self.delete_indices(&mut tx, id).await?;
-
-      // Insert new index entries
       self.insert_indices(&mut tx, model).await?;
 
       debug!("Model updated successfully");
@@ -194,17 +447,148 @@ impl<M: Model> PostgresDatabase<M> {
     })
   }
 
+  /// Atomically read, modify and write a model, creating it if absent.
+  ///
+  /// The row is locked with `FOR UPDATE`, so concurrent calls for the same
+  /// ID are applied one after another.
+  #[instrument(skip(self, update), fields(model = M::TABLE_NAME, id = %id))]
+  async fn upsert_with(
+    &self,
+    id: RecordId<M>,
+    update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+  ) -> DatabaseResult<M> {
+    let table_name = self.table_name();
+    let select_query =
+      format!("SELECT id, data FROM {table_name} WHERE id = $1 FOR UPDATE");
+    let update_query = format!(
+      "UPDATE {table_name} SET data = $1, updated_at = NOW() WHERE id = $2"
+    );
+    let insert_query = format!(
+      "INSERT INTO {table_name} (id, data) VALUES ($1, $2) ON CONFLICT (id) \
+       DO NOTHING"
+    );
+
+    with_transaction!(self, tx, {
+      loop {
+        let row: Option<PgRow> = sqlx::query(&select_query)
+          .bind(id.to_string())
+          .fetch_optional(&mut *tx)
+          .await
+          .into_diagnostic()
+          .map_err(DatabaseError::Database)?;
+        let existing = row
+          .as_ref()
+          .map(|row| self.deserialize_from_row(row))
+          .transpose()?;
+        let exists = existing.is_some();
+
+        let model = update(existing);
+        let data = self.serialize(&model)?;
+
+        if exists {
+          sqlx::query(&update_query)
+            .bind(&data)
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await
+            .into_diagnostic()
+            .map_err(DatabaseError::Database)?;
+          self.delete_indices(&mut tx, id).await?;
+          self.insert_indices(&mut tx, &model).await?;
+          debug!("Model updated atomically");
+          return Ok(model);
+        }
+
+        let result = sqlx::query(&insert_query)
+          .bind(id.to_string())
+          .bind(&data)
+          .execute(&mut *tx)
+          .await
+          .into_diagnostic()
+          .map_err(DatabaseError::Database)?;
+        if result.rows_affected() == 1 {
+          self.insert_indices(&mut tx, &model).await?;
+          debug!("Model inserted atomically");
+          return Ok(model);
+        }
+
+        // inserted concurrently, so lock the new row and apply to that
+        debug!("Model inserted concurrently, retrying");
+      }
+    })
+  }
+
+  /// Atomically add `delta` to the integer at `field_path` in a model's
+  /// data with `jsonb_set`, returning the new value.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, id = %id))]
+  async fn increment(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64> {
+    with_transaction!(self, tx, {
+      debug!("Incrementing field");
+
+      // `jsonb_set` leaves the data unchanged if a parent is missing, in
+      // which case the returned value is null
+      let table_name = self.table_name();
+      let query = format!(
+        "UPDATE {table_name} SET data = jsonb_set(data, $1::text[], \
+         to_jsonb(COALESCE((data #>> $1::text[])::bigint, 0) + $2), true), \
+         updated_at = NOW() WHERE id = $3 RETURNING data, (data #>> \
+         $1::text[])::bigint AS value"
+      );
+
+      let row: PgRow = sqlx::query(&query)
+        .bind(field_path)
+        .bind(delta)
+        .bind(id.to_string())
+        .fetch_optional(&mut *tx)
+        .await
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?
+        .ok_or_else(|| DatabaseError::NotFound(id.to_string()))?;
+
+      let value: Option<i64> = row
+        .try_get("value")
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?;
+      let value = value.ok_or_else(|| {
+        DatabaseError::Other(miette::miette!(
+          "field path {} not found",
+          field_path.join(".")
+        ))
+      })?;
+
+      // the field may be indexed
+      let model = self.deserialize_from_row(&row)?;
+      self.delete_indices(&mut tx, id).await?;
+      self.insert_indices(&mut tx, &model).await?;
+
+      debug!(value, "Field incremented successfully");
+      Ok(value)
+    })
+  }
+
   /// Delete a model from the database by ID.
   #[instrument(skip(self), fields(model = M::TABLE_NAME, id = %id))]
   async fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
-    debug!("Deleting model");
+    self.delete_with(&self.pool, id).await
+  }
 
-    let table_name = M::TABLE_NAME;
-    let query = format!("DELETE FROM {table_name} WHERE id = $1");
+  /// Delete a model by ID with the given executor, e.g. the pool or an open
+  /// transaction.
+  pub(crate) async fn delete_with<'e>(
+    &self,
+    executor: impl PgExecutor<'e>,
+    id: RecordId<M>,
+  ) -> DatabaseResult<()> {
+    debug!("Deleting model");
 
-    let result = sqlx::query(&query)
+    let result = sqlx::query(&self.queries.delete)
       .bind(id.to_string())
-      .execute(&self.pool)
+      .execute(executor)
       .await
       .into_diagnostic()
       .map_err(DatabaseError::Database)?;
@@ -227,10 +611,7 @@ impl<M: Model> PostgresDatabase<M> {
   async fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
     debug!("Getting model by ID");
 
-    let table_name = M::TABLE_NAME;
-    let query = format!("SELECT data FROM {table_name} WHERE id = $1");
-
-    let row: Option<PgRow> = sqlx::query(&query)
+    let row: Option<PgRow> = sqlx::query(&self.queries.get)
       .bind(id.to_string())
       .fetch_optional(&self.pool)
       .await
@@ -238,7 +619,7 @@ impl<M: Model> PostgresDatabase<M> {
       .map_err(DatabaseError::Database)?;
 
     if let Some(row) = row {
-      let model = Self::deserialize_from_row(&row)?;
+      let model = self.deserialize_from_row(&row)?;
 
       debug!("Model found");
       Ok(Some(model))
@@ -248,6 +629,22 @@ impl<M: Model> PostgresDatabase<M> {
     }
   }
 
+  /// When the model with the given ID was last inserted or updated.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, id = %id))]
+  async fn updated_at(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<DateTime<Utc>>> {
+    let query =
+      format!("SELECT updated_at FROM {} WHERE id = $1", self.table_name());
+    sqlx::query_scalar(&query)
+      .bind(id.to_string())
+      .fetch_optional(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)
+  }
+
   /// Find a model by a unique index.
   #[instrument(skip(self), fields(model = M::TABLE_NAME, selector = %selector, key = %key))]
   async fn find_by_unique_index(
@@ -266,17 +663,10 @@ impl<M: Model> PostgresDatabase<M> {
       return Err(DatabaseError::IndexNotUnique(selector.to_string()));
     }
 
-    let table_name = M::TABLE_NAME;
-    let index_table = Self::calculate_index_table_name(index_def);
-    let index_key = key.to_string();
+    let index_key = self.index_key_text(index_def, key).await?;
+    let query = &self.queries.find_by_unique_index[index_def.name];
 
-    let query = format!(
-      "SELECT m.data FROM {table_name} m 
-             INNER JOIN {index_table} i ON m.id = i.record_id 
-             WHERE i.index_key = $1"
-    );
-
-    let row: Option<PgRow> = sqlx::query(&query)
+    let row: Option<PgRow> = sqlx::query(query)
       .bind(index_key)
       .fetch_optional(&self.pool)
       .await
@@ -284,7 +674,7 @@ impl<M: Model> PostgresDatabase<M> {
       .map_err(DatabaseError::Database)?;
 
     if let Some(row) = row {
-      let model = Self::deserialize_from_row(&row)?;
+      let model = self.deserialize_from_row(&row)?;
 
       debug!("Model found by unique index");
       Ok(Some(model))
@@ -307,18 +697,11 @@ impl<M: Model> PostgresDatabase<M> {
     let index_def = indices
       .get(selector)
       .ok_or_else(|| DatabaseError::IndexNotFound(selector.to_string()))?;
-    let index_key = key.to_string();
+    let index_key = self.index_key_text(index_def, key).await?;
 
-    let query = format!(
-      "SELECT m.data FROM {table_name} m 
-             INNER JOIN {index_table} i ON m.id = i.record_id 
-             WHERE i.index_key = $1
-             ORDER BY m.updated_at DESC",
-      table_name = M::TABLE_NAME,
-      index_table = Self::calculate_index_table_name(index_def)
-    );
+    let query = &self.queries.find_by_index[index_def.name];
 
-    let rows: Vec<PgRow> = sqlx::query(&query)
+    let rows: Vec<PgRow> = sqlx::query(query)
       .bind(index_key)
       .fetch_all(&self.pool)
       .await
@@ -329,7 +712,7 @@ impl<M: Model> PostgresDatabase<M> {
     let mut results = Vec::with_capacity(count);
 
     for row in rows {
-      let model = Self::deserialize_from_row(&row)?;
+      let model = self.deserialize_from_row(&row)?;
 
       results.push(model);
     }
@@ -338,15 +721,145 @@ impl<M: Model> PostgresDatabase<M> {
     Ok(results)
   }
 
+  /// Find all models whose index key falls within the given bounds, ordered
+  /// by key ascending.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, selector = %selector))]
+  async fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>> {
+    debug!("Finding by index range");
+
+    let indices = M::indices();
+    let index_def = indices
+      .get(selector)
+      .ok_or_else(|| DatabaseError::IndexNotFound(selector.to_string()))?;
+    let (where_clause, binds) =
+      self.index_range_clause(index_def, lower, upper).await?;
+
+    let query = format!(
+      "SELECT m.id, m.data FROM {table_name} m 
+             INNER JOIN {index_table} i ON m.id = i.record_id 
+             {where_clause}
+             ORDER BY i.index_key ASC",
+      table_name = self.table_name(),
+      index_table = self.calculate_index_table_name(index_def),
+    );
+
+    let mut sql_query = sqlx::query(&query);
+    for bind in binds {
+      sql_query = sql_query.bind(bind);
+    }
+
+    let rows: Vec<PgRow> = sql_query
+      .fetch_all(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    let count = rows.len();
+    let mut results = Vec::with_capacity(count);
+
+    for row in rows {
+      results.push(self.deserialize_from_row(&row)?);
+    }
+
+    debug!(count = count, "Found models by index range");
+    Ok(results)
+  }
+
+  /// Atomically claim up to `limit` models within an index range, applying
+  /// `claim` to each and saving it.
+  ///
+  /// Matching rows are locked with `FOR UPDATE SKIP LOCKED`, so concurrent
+  /// claimers skip each other's rows instead of blocking on them. Their
+  /// index rows are locked too: a claim which re-keys a model replaces them,
+  /// so a model re-keyed after this query's snapshot is skipped rather than
+  /// claimed a second time.
+  #[instrument(skip(self, claim), fields(model = M::TABLE_NAME, index = %selector, limit = limit))]
+  async fn claim_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+    limit: u32,
+    claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+  ) -> DatabaseResult<Vec<M>> {
+    let indices = M::indices();
+    let index_def = indices
+      .get(selector)
+      .ok_or_else(|| DatabaseError::IndexNotFound(selector.to_string()))?;
+    let (where_clause, binds) =
+      self.index_range_clause(index_def, lower, upper).await?;
+
+    with_transaction!(self, tx, {
+      debug!("Claiming by index range");
+
+      let query = format!(
+        "SELECT m.id, m.data FROM {table_name} m
+             INNER JOIN {index_table} i ON m.id = i.record_id
+             {where_clause}
+             ORDER BY i.index_key ASC
+             LIMIT {limit}
+             FOR UPDATE OF m, i SKIP LOCKED",
+        table_name = self.table_name(),
+        index_table = self.calculate_index_table_name(index_def),
+      );
+
+      let mut sql_query = sqlx::query(&query);
+      for bind in &binds {
+        sql_query = sql_query.bind(bind);
+      }
+
+      let rows: Vec<PgRow> = sql_query
+        .fetch_all(&mut *tx)
+        .await
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?;
+
+      let update_query = format!(
+        "UPDATE {table_name} SET data = $1, updated_at = NOW() WHERE id = $2",
+        table_name = self.table_name(),
+      );
+
+      let mut claimed: Vec<M> = Vec::with_capacity(rows.len());
+      for row in rows {
+        let mut model = self.deserialize_from_row(&row)?;
+        // a model may match the range through several index values
+        if claimed.iter().any(|c| c.id() == model.id()) {
+          continue;
+        }
+        claim(&mut model);
+
+        sqlx::query(&update_query)
+          .bind(self.serialize(&model)?)
+          .bind(model.id().to_string())
+          .execute(&mut *tx)
+          .await
+          .into_diagnostic()
+          .map_err(DatabaseError::Database)?;
+        self.delete_indices(&mut tx, model.id()).await?;
+        self.insert_indices(&mut tx, &model).await?;
+
+        claimed.push(model);
+      }
+
+      debug!(count = claimed.len(), "Claimed models by index range");
+      Ok(claimed)
+    })
+  }
+
   /// List all models, ordered by `updated_at` descending.
   #[instrument(skip(self), fields(model = M::TABLE_NAME, limit = limit, offset = offset))]
   async fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
     debug!("Listing models");
 
-    let table_name = M::TABLE_NAME;
+    let table_name = self.table_name();
     let query = format!(
-      "SELECT data FROM {table_name} ORDER BY updated_at DESC LIMIT $1 OFFSET \
-       $2"
+      "SELECT id, data FROM {table_name} ORDER BY updated_at DESC LIMIT $1 \
+       OFFSET $2"
     );
 
     let rows: Vec<PgRow> = sqlx::query(&query)
@@ -361,7 +874,7 @@ impl<M: Model> PostgresDatabase<M> {
     let mut results = Vec::with_capacity(count);
 
     for row in rows {
-      let model = Self::deserialize_from_row(&row)?;
+      let model = self.deserialize_from_row(&row)?;
 
       results.push(model);
     }
@@ -370,15 +883,102 @@ impl<M: Model> PostgresDatabase<M> {
     Ok(results)
   }
 
+  /// List models ordered by ID, starting after the model with ID `after`.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, limit = limit))]
+  async fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    debug!("Listing models after ID");
+
+    let table_name = self.table_name();
+    // IDs are never empty, and the primary key index serves both the
+    // filter and the order
+    let query = format!(
+      "SELECT id, data FROM {table_name} WHERE id > COALESCE($1, '') ORDER BY \
+       id LIMIT $2"
+    );
+
+    let rows: Vec<PgRow> = sqlx::query(&query)
+      .bind(after.map(|id| id.to_string()))
+      .bind(i64::from(limit))
+      .fetch_all(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    let results = rows
+      .iter()
+      .map(|row| self.deserialize_from_row(row))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    debug!(count = results.len(), "Listed models after ID");
+    Ok(results)
+  }
+
+  /// List a page of models, ordered by `updated_at` descending, along with
+  /// the total record count in a single round trip.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, limit = limit, offset = offset))]
+  async fn list_page(
+    &self,
+    limit: u32,
+    offset: u32,
+  ) -> DatabaseResult<Page<M>> {
+    debug!("Listing page of models");
+
+    // the page is left-joined onto the count so that we still get the total
+    // when the page itself is empty
+    let table_name = self.table_name();
+    let query = format!(
+      "WITH total AS (SELECT COUNT(*) AS count FROM {table_name}),
+            page AS (
+              SELECT id, data, updated_at FROM {table_name}
+              ORDER BY updated_at DESC LIMIT $1 OFFSET $2
+            )
+       SELECT page.id, page.data, total.count FROM total
+       LEFT JOIN page ON TRUE
+       ORDER BY page.updated_at DESC"
+    );
+
+    let rows: Vec<PgRow> = sqlx::query(&query)
+      .bind(i64::from(limit))
+      .bind(i64::from(offset))
+      .fetch_all(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    let mut total = 0;
+    let mut items = Vec::with_capacity(rows.len());
+
+    for row in rows {
+      let count: i64 = row
+        .try_get("count")
+        .into_diagnostic()
+        .map_err(DatabaseError::Serialization)?;
+      total = count.unsigned_abs();
+
+      let is_empty_page = row
+        .try_get_raw("data")
+        .into_diagnostic()
+        .map_err(DatabaseError::Serialization)?
+        .is_null();
+      if !is_empty_page {
+        items.push(self.deserialize_from_row(&row)?);
+      }
+    }
+
+    debug!(count = items.len(), total = total, "Listed page of models");
+    Ok(Page::new(items, offset, total))
+  }
+
   /// Count total number of records.
   #[instrument(skip(self), fields(model = M::TABLE_NAME))]
   async fn count(&self) -> DatabaseResult<u64> {
     debug!("Counting models");
 
-    let table_name = M::TABLE_NAME;
-    let query = format!("SELECT COUNT(*) as count FROM {table_name}");
-
-    let row: PgRow = sqlx::query(&query)
+    let row: PgRow = sqlx::query(&self.queries.count)
       .fetch_one(&self.pool)
       .await
       .into_diagnostic()
@@ -394,6 +994,110 @@ impl<M: Model> PostgresDatabase<M> {
     Ok(count as u64)
   }
 
+  /// Delete every record with `TRUNCATE`, along with the index tables
+  /// referencing them.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME))]
+  async fn truncate(&self) -> DatabaseResult<()> {
+    let tables = std::iter::once(self.table_name())
+      .chain(
+        M::indices()
+          .definitions
+          .iter()
+          .map(|def| self.calculate_index_table_name(def)),
+      )
+      .collect::<Vec<_>>()
+      .join(", ");
+    warn!("Truncating {tables}");
+
+    sqlx::query(&format!("TRUNCATE {tables} CASCADE"))
+      .execute(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+    Ok(())
+  }
+
+  /// Return up to `n` random records.
+  ///
+  /// On large tables, a `BERNOULLI` table sample sized from the estimated
+  /// row count is drawn first, so that only a fraction of the table is
+  /// read. If it comes up short, random records are picked from the whole
+  /// table with `ORDER BY random()`.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, n = n))]
+  async fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+    debug!("Sampling models");
+
+    let table_name = self.table_name();
+    let estimate = self.estimate_count().await?;
+    // oversample, so that the sample is rarely short
+    #[allow(clippy::cast_precision_loss)]
+    let percent = f64::from(n) * 2.0 / estimate.max(1) as f64 * 100.0;
+
+    let mut rows: Vec<PgRow> = Vec::new();
+    if percent < 50.0 {
+      let query = format!(
+        "SELECT id, data FROM {table_name} TABLESAMPLE BERNOULLI ($1::REAL) \
+         ORDER BY random() LIMIT $2"
+      );
+      rows = sqlx::query(&query)
+        .bind(percent)
+        .bind(i64::from(n))
+        .fetch_all(&self.pool)
+        .await
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?;
+    }
+    if rows.len() < n as usize {
+      let query =
+        format!("SELECT id, data FROM {table_name} ORDER BY random() LIMIT $1");
+      rows = sqlx::query(&query)
+        .bind(i64::from(n))
+        .fetch_all(&self.pool)
+        .await
+        .into_diagnostic()
+        .map_err(DatabaseError::Database)?;
+    }
+
+    let results = rows
+      .iter()
+      .map(|row| self.deserialize_from_row(row))
+      .collect::<DatabaseResult<Vec<_>>>()?;
+    debug!(count = results.len(), "Sampled models");
+    Ok(results)
+  }
+
+  /// Estimate the number of records from the planner's statistics, falling
+  /// back to an exact count if the table has never been analyzed.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME))]
+  async fn estimate_count(&self) -> DatabaseResult<u64> {
+    debug!("Estimating model count");
+
+    let row: Option<PgRow> = sqlx::query(
+      "SELECT reltuples::BIGINT AS estimate FROM pg_class WHERE oid = \
+       to_regclass($1)",
+    )
+    .bind(self.table_name())
+    .fetch_optional(&self.pool)
+    .await
+    .into_diagnostic()
+    .map_err(DatabaseError::Database)?;
+
+    let estimate: Option<i64> = row
+      .map(|row| row.try_get("estimate"))
+      .transpose()
+      .into_diagnostic()
+      .map_err(DatabaseError::Serialization)?;
+
+    // `reltuples` is negative until the table is first analyzed
+    match estimate.and_then(|estimate| u64::try_from(estimate).ok()) {
+      Some(estimate) => {
+        debug!(estimate, "Model count estimated");
+        Ok(estimate)
+      }
+      None => self.count().await,
+    }
+  }
+
   /// Check if an error is a unique constraint violation.
   fn is_unique_violation(error: &sqlx::Error) -> bool {
     if let sqlx::Error::Database(db_err) = error {
@@ -405,7 +1109,22 @@ impl<M: Model> PostgresDatabase<M> {
     false
   }
 
-  fn deserialize_from_row(row: &PgRow) -> Result<M, DatabaseError> {
+  /// The [`FieldCipher`] for the model's encrypted fields, or `None` if it
+  /// has none.
+  fn field_cipher(&self) -> DatabaseResult<Option<&dyn FieldCipher>> {
+    if M::ENCRYPTED_FIELDS.is_empty() {
+      return Ok(None);
+    }
+    self.field_cipher.as_deref().map(Some).ok_or_else(|| {
+      DatabaseError::Serialization(miette::miette!(
+        "model {} has encrypted fields but no field cipher is configured",
+        M::TABLE_NAME
+      ))
+    })
+  }
+
+  /// The text of the `data` column of a row.
+  fn data_column(row: &PgRow) -> DatabaseResult<&str> {
     // get pg column as a &str
     let data = row
       .try_get_raw("data")
@@ -418,22 +1137,92 @@ impl<M: Model> PostgresDatabase<M> {
       .map_err(DatabaseError::Serialization)?;
 
     // trim ascii control characters from postgres JSONB
-    let data = data.trim_start_matches(|c: char| c.is_ascii_control());
+    Ok(data.trim_start_matches(|c: char| c.is_ascii_control()))
+  }
+
+  /// The `data` column of a row as JSON, with encrypted fields decrypted.
+  fn raw_from_row(&self, row: &PgRow) -> DatabaseResult<serde_json::Value> {
+    let data = Self::data_column(row)?;
+    let mut value: serde_json::Value = serde_json::from_str(data)
+      .into_diagnostic()
+      .context("failed to parse data as JSON")
+      .map_err(|error| {
+        DatabaseError::deserialization(
+          self.table_name(),
+          row.try_get("id").ok(),
+          data,
+          error,
+        )
+      })?;
+    if let Some(cipher) = self.field_cipher()? {
+      decrypt_fields::<M>(cipher, &mut value)?;
+    }
+    upgrade_json::<M>(&mut value)
+      .into_diagnostic()
+      .context("failed to upgrade data to the model's version")
+      .map_err(|error| {
+        DatabaseError::deserialization(
+          self.table_name(),
+          row.try_get("id").ok(),
+          data,
+          error,
+        )
+      })?;
+    Ok(value)
+  }
+
+  fn deserialize_from_row(&self, row: &PgRow) -> Result<M, DatabaseError> {
+    let data = Self::data_column(row)?;
+
+    // the sample is of the stored payload, so encrypted fields stay encrypted
+    let invalid = |error: miette::Report| {
+      DatabaseError::deserialization(
+        self.table_name(),
+        row.try_get("id").ok(),
+        data,
+        error,
+      )
+    };
 
     // we have to use `from_str` and not anything using `DeserializedOwned`
     // because `StorePath<String>` still uses borrowed data in its deserializer
     // and will fail on owned data
-    let model: M = serde_json::from_str(data)
+    let cipher = self.field_cipher()?;
+    if cipher.is_none() && M::VERSION == 0 {
+      return serde_json::from_str(data)
+        .into_diagnostic()
+        .context("failed to deserialize data as model")
+        .map_err(invalid);
+    }
+
+    // decrypt the encrypted fields and upgrade older versions, then
+    // deserialize from the resulting text
+    let mut value: serde_json::Value = serde_json::from_str(data)
+      .into_diagnostic()
+      .context("failed to parse data as JSON")
+      .map_err(invalid)?;
+    if let Some(cipher) = cipher {
+      decrypt_fields::<M>(cipher, &mut value)?;
+    }
+    upgrade_json::<M>(&mut value)
+      .into_diagnostic()
+      .context("failed to upgrade data to the model's version")
+      .map_err(invalid)?;
+    let decrypted = value.to_string();
+    serde_json::from_str(&decrypted)
       .into_diagnostic()
       .context("failed to deserialize data as model")
-      .map_err(DatabaseError::Serialization)?;
-
-    Ok(model)
+      .map_err(invalid)
   }
 
-  fn serialize(model: &M) -> Result<serde_json::Value, DatabaseError> {
-    serde_json::to_value(model)
+  fn serialize(&self, model: &M) -> Result<serde_json::Value, DatabaseError> {
+    let mut data = serde_json::to_value(model)
       .into_diagnostic()
-      .map_err(DatabaseError::Serialization)
+      .map_err(DatabaseError::Serialization)?;
+    stamp_version::<M>(&mut data);
+    if let Some(cipher) = self.field_cipher()? {
+      encrypt_fields::<M>(cipher, &mut data)?;
+    }
+    Ok(data)
   }
 }
@@ -0,0 +1,46 @@
+use std::{fmt, str::FromStr};
+
+use db_core::DatabaseError;
+
+/// A position in the Postgres write-ahead log, displayed as e.g.
+/// `16/B374D848`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lsn(pub u64);
+
+impl fmt::Display for Lsn {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:X}/{:X}", self.0 >> 32, self.0 & 0xFFFF_FFFF)
+  }
+}
+
+impl FromStr for Lsn {
+  type Err = DatabaseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let parse = |half: &str| u64::from_str_radix(half, 16).ok();
+    s.split_once('/')
+      .and_then(|(high, low)| parse(high).zip(parse(low)))
+      .filter(|(high, low)| *high <= 0xFFFF_FFFF && *low <= 0xFFFF_FFFF)
+      .map(|(high, low)| Lsn((high << 32) | low))
+      .ok_or_else(|| {
+        DatabaseError::Serialization(miette::miette!("invalid LSN: {s}"))
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Lsn;
+
+  #[test]
+  fn test_lsn_round_trip() {
+    let lsn: Lsn = "16/B374D848".parse().unwrap();
+    assert_eq!(lsn, Lsn(0x16_B374_D848));
+    assert_eq!(lsn.to_string(), "16/B374D848");
+    assert_eq!("0/0".parse::<Lsn>().unwrap(), Lsn(0));
+
+    assert!("16B374D848".parse::<Lsn>().is_err());
+    assert!("0/100000000".parse::<Lsn>().is_err());
+    assert!("x/1".parse::<Lsn>().is_err());
+  }
+}
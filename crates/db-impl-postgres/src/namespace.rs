@@ -0,0 +1,61 @@
+use miette::bail;
+
+/// Where a model's tables live: an optional Postgres schema, and a prefix
+/// for table names, e.g. to keep environments sharing a database apart.
+///
+/// With the schema `app` and the prefix `staging_`, the `users` table is
+/// stored as `app.staging_users`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableNamespace {
+  schema: Option<String>,
+  prefix: String,
+}
+
+impl TableNamespace {
+  /// Creates a new [`TableNamespace`].
+  ///
+  /// Both parts are used in SQL unquoted, so they may only contain ASCII
+  /// letters, digits and underscores, and the schema must not be empty or
+  /// start with a digit.
+  pub fn new(schema: Option<&str>, prefix: &str) -> miette::Result<Self> {
+    let is_identifier_part =
+      |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if let Some(schema) = schema
+      && (!is_identifier_part(schema)
+        || schema.is_empty()
+        || schema.starts_with(|c: char| c.is_ascii_digit()))
+    {
+      bail!("invalid schema name `{schema}`");
+    }
+    if !is_identifier_part(prefix) {
+      bail!("invalid table prefix `{prefix}`");
+    }
+
+    Ok(Self {
+      schema: schema.map(ToOwned::to_owned),
+      prefix: prefix.to_owned(),
+    })
+  }
+
+  /// Returns the schema, if any.
+  #[must_use]
+  pub fn schema(&self) -> Option<&str> { self.schema.as_deref() }
+
+  /// Returns the table name prefix.
+  #[must_use]
+  pub fn prefix(&self) -> &str { &self.prefix }
+
+  /// The prefixed name of a table, without the schema. Suitable for naming
+  /// indices, which always live in their table's schema.
+  pub(crate) fn local(&self, name: &str) -> String {
+    format!("{}{name}", self.prefix)
+  }
+
+  /// The fully qualified name of a table.
+  pub(crate) fn qualify(&self, name: &str) -> String {
+    match &self.schema {
+      Some(schema) => format!("{schema}.{}{name}", self.prefix),
+      None => self.local(name),
+    }
+  }
+}
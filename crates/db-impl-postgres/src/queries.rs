@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use model::Model;
+
+use crate::{PostgresDatabase, TableNamespace};
+
+/// SQL for the hot paths, generated once per database rather than on every
+/// call.
+///
+/// Reusing the same strings also lets sqlx reuse prepared statements: queries
+/// are persistent by default, so each connection prepares a statement once
+/// per distinct SQL text and caches it.
+#[derive(Debug)]
+pub(crate) struct Queries {
+  pub(crate) get:                  String,
+  pub(crate) insert:               String,
+  pub(crate) insert_returning:     String,
+  pub(crate) upsert_returning:     String,
+  pub(crate) update:               String,
+  pub(crate) delete:               String,
+  pub(crate) count:                String,
+  /// Keyed by index name.
+  pub(crate) find_by_unique_index: HashMap<&'static str, String>,
+  /// Keyed by index name.
+  pub(crate) find_by_index:        HashMap<&'static str, String>,
+  /// Keyed by index name.
+  pub(crate) insert_index_entry:   HashMap<&'static str, String>,
+  /// Keyed by index name.
+  pub(crate) delete_index_entries: HashMap<&'static str, String>,
+}
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Generate the cached [`Queries`] for tables in `namespace`.
+  pub(crate) fn generate_queries(namespace: &TableNamespace) -> Queries {
+    let table_name = namespace.qualify(M::TABLE_NAME);
+    let definitions = M::indices().definitions;
+    let per_index = |f: &dyn Fn(String, &str) -> String| {
+      definitions
+        .iter()
+        .map(|def| {
+          let index_table = namespace.qualify(&Self::index_table_suffix(def));
+          (def.name, f(index_table, Self::index_sql_type(def.kind)))
+        })
+        .collect()
+    };
+
+    Queries {
+      get:                  format!(
+        "SELECT id, data FROM {table_name} WHERE id = $1"
+      ),
+      insert:               format!(
+        "INSERT INTO {table_name} (id, data) VALUES ($1, $2)"
+      ),
+      insert_returning:     format!(
+        "INSERT INTO {table_name} (id, data) VALUES ($1, $2) RETURNING data"
+      ),
+      upsert_returning:     format!(
+        "INSERT INTO {table_name} (id, data) VALUES ($1, $2) ON CONFLICT (id) \
+         DO UPDATE SET data = EXCLUDED.data, updated_at = NOW() RETURNING data"
+      ),
+      update:               format!(
+        "UPDATE {table_name} SET data = $1, updated_at = NOW() WHERE id = $2"
+      ),
+      delete:               format!("DELETE FROM {table_name} WHERE id = $1"),
+      count:                format!(
+        "SELECT COUNT(*) as count FROM {table_name}"
+      ),
+      find_by_unique_index: per_index(&|index_table, key_type| {
+        format!(
+          "SELECT m.id, m.data FROM {table_name} m INNER JOIN {index_table} i \
+           ON m.id = i.record_id WHERE i.index_key = $1::{key_type}"
+        )
+      }),
+      find_by_index:        per_index(&|index_table, key_type| {
+        format!(
+          "SELECT m.id, m.data FROM {table_name} m INNER JOIN {index_table} i \
+           ON m.id = i.record_id WHERE i.index_key = $1::{key_type} ORDER BY \
+           m.updated_at DESC"
+        )
+      }),
+      insert_index_entry:   per_index(&|index_table, key_type| {
+        format!(
+          "INSERT INTO {index_table} (index_key, record_id) VALUES \
+           ($1::{key_type}, $2)"
+        )
+      }),
+      delete_index_entries: per_index(&|index_table, _| {
+        format!("DELETE FROM {index_table} WHERE record_id = $1")
+      }),
+    }
+  }
+}
@@ -0,0 +1,128 @@
+//! Raw SQL escape hatch.
+//!
+//! These methods bypass the model abstraction entirely: no index maintenance,
+//! no index pipeline, and no validation of the statement. Prefer the
+//! [`DatabaseLike`](db_core::DatabaseLike) interface wherever it can express
+//! the query.
+
+use db_core::{DatabaseError, DatabaseResult};
+use miette::{Context, IntoDiagnostic};
+use model::Model;
+use sqlx::{
+  Postgres,
+  postgres::{PgArguments, PgRow},
+  query::Query,
+};
+use tracing::{debug, instrument};
+
+use crate::PostgresDatabase;
+
+/// A value bound to a placeholder in a raw SQL statement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RawBind {
+  /// A `TEXT` value.
+  Text(String),
+  /// A `BIGINT` value.
+  I64(i64),
+  /// A `DOUBLE PRECISION` value.
+  F64(f64),
+  /// A `BOOLEAN` value.
+  Bool(bool),
+  /// A `JSONB` value.
+  Json(serde_json::Value),
+  /// A `NULL` value.
+  Null,
+}
+
+impl From<String> for RawBind {
+  fn from(value: String) -> Self { Self::Text(value) }
+}
+impl From<&str> for RawBind {
+  fn from(value: &str) -> Self { Self::Text(value.to_owned()) }
+}
+impl From<i64> for RawBind {
+  fn from(value: i64) -> Self { Self::I64(value) }
+}
+impl From<f64> for RawBind {
+  fn from(value: f64) -> Self { Self::F64(value) }
+}
+impl From<bool> for RawBind {
+  fn from(value: bool) -> Self { Self::Bool(value) }
+}
+impl From<serde_json::Value> for RawBind {
+  fn from(value: serde_json::Value) -> Self { Self::Json(value) }
+}
+
+fn bind_all<'q>(
+  mut query: Query<'q, Postgres, PgArguments>,
+  binds: &'q [RawBind],
+) -> Query<'q, Postgres, PgArguments> {
+  for bind in binds {
+    query = match bind {
+      RawBind::Text(value) => query.bind(value),
+      RawBind::I64(value) => query.bind(value),
+      RawBind::F64(value) => query.bind(value),
+      RawBind::Bool(value) => query.bind(value),
+      RawBind::Json(value) => query.bind(value),
+      RawBind::Null => query.bind(None::<String>),
+    };
+  }
+  query
+}
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Run a raw query and deserialize the `data` column of each returned row
+  /// as a model.
+  ///
+  /// The statement must select a `data` column containing the model JSON,
+  /// e.g. `SELECT data FROM users WHERE data->>'name' = $1`. Also selecting
+  /// the `id` column identifies the record in deserialization errors.
+  #[instrument(skip(self, binds), fields(model = M::TABLE_NAME))]
+  pub async fn query_raw(
+    &self,
+    sql: &str,
+    binds: &[RawBind],
+  ) -> DatabaseResult<Vec<M>> {
+    debug!("Running raw query");
+
+    let rows: Vec<PgRow> = bind_all(sqlx::query(sql), binds)
+      .fetch_all(&self.pool)
+      .await
+      .into_diagnostic()
+      .context("failed to run raw query")
+      .map_err(DatabaseError::Database)?;
+
+    let count = rows.len();
+    let mut results = Vec::with_capacity(count);
+
+    for row in rows {
+      results.push(self.deserialize_from_row(&row)?);
+    }
+
+    debug!(count = count, "Raw query complete");
+    Ok(results)
+  }
+
+  /// Execute a raw statement, returning the number of rows affected.
+  #[instrument(skip(self, binds), fields(model = M::TABLE_NAME))]
+  pub async fn execute_raw(
+    &self,
+    sql: &str,
+    binds: &[RawBind],
+  ) -> DatabaseResult<u64> {
+    debug!("Executing raw statement");
+
+    let result = bind_all(sqlx::query(sql), binds)
+      .execute(&self.pool)
+      .await
+      .into_diagnostic()
+      .context("failed to execute raw statement")
+      .map_err(DatabaseError::Database)?;
+
+    debug!(
+      rows_affected = result.rows_affected(),
+      "Raw statement executed"
+    );
+    Ok(result.rows_affected())
+  }
+}
@@ -0,0 +1,119 @@
+use db_core::{
+  ColumnDescription, DatabaseError, DatabaseResult, IndexTableDescription,
+  SchemaDescription, TableDescription,
+};
+use miette::IntoDiagnostic;
+use model::Model;
+use sqlx::{Row, postgres::PgRow};
+use tracing::{debug, instrument};
+
+use crate::PostgresDatabase;
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Describe the model's tables from the Postgres catalogs.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME))]
+  pub(crate) async fn describe_schema(
+    &self,
+  ) -> DatabaseResult<SchemaDescription> {
+    debug!("Describing schema");
+
+    let main_table = self.describe_table(self.table_name()).await?;
+    let mut index_tables = Vec::new();
+    for def in M::indices().definitions {
+      let name = self.calculate_index_table_name(def);
+      index_tables.push(IndexTableDescription {
+        index:           def.name.to_owned(),
+        kind:            def.kind,
+        unique:          def.unique,
+        enforced_unique: self.has_unique_index_key(&name).await?,
+        table:           self.describe_table(name).await?,
+      });
+    }
+
+    debug!(index_tables = index_tables.len(), "Schema described");
+    Ok(SchemaDescription {
+      main_table,
+      index_tables,
+    })
+  }
+
+  /// Describe a table from `pg_class` and `pg_attribute`.
+  async fn describe_table(
+    &self,
+    name: String,
+  ) -> DatabaseResult<TableDescription> {
+    let row: Option<PgRow> = sqlx::query(
+      "SELECT reltuples::BIGINT AS estimate FROM pg_class WHERE oid = \
+       to_regclass($1)",
+    )
+    .bind(&name)
+    .fetch_optional(&self.pool)
+    .await
+    .into_diagnostic()
+    .map_err(DatabaseError::Database)?;
+    let Some(row) = row else {
+      return Ok(TableDescription {
+        name,
+        exists: false,
+        columns: Vec::new(),
+        estimated_rows: None,
+      });
+    };
+
+    // `reltuples` is negative until the table is first analyzed
+    let estimate: i64 = row
+      .try_get("estimate")
+      .into_diagnostic()
+      .map_err(DatabaseError::Serialization)?;
+
+    let rows: Vec<PgRow> = sqlx::query(
+      "SELECT attname::TEXT AS name, format_type(atttypid, atttypmod) AS \
+       data_type, NOT attnotnull AS nullable FROM pg_attribute WHERE attrelid \
+       = to_regclass($1) AND attnum > 0 AND NOT attisdropped ORDER BY attnum",
+    )
+    .bind(&name)
+    .fetch_all(&self.pool)
+    .await
+    .into_diagnostic()
+    .map_err(DatabaseError::Database)?;
+    let columns = rows
+      .iter()
+      .map(|row| {
+        Ok(ColumnDescription {
+          name:      row.try_get("name")?,
+          data_type: row.try_get("data_type")?,
+          nullable:  row.try_get("nullable")?,
+        })
+      })
+      .collect::<Result<_, sqlx::Error>>()
+      .into_diagnostic()
+      .map_err(DatabaseError::Serialization)?;
+
+    Ok(TableDescription {
+      name,
+      exists: true,
+      columns,
+      estimated_rows: u64::try_from(estimate).ok(),
+    })
+  }
+
+  /// Whether a table has a unique index on `index_key` alone.
+  async fn has_unique_index_key(&self, table: &str) -> DatabaseResult<bool> {
+    let row: PgRow = sqlx::query(
+      "SELECT EXISTS (SELECT 1 FROM pg_index i JOIN pg_attribute a ON \
+       a.attrelid = i.indrelid AND a.attnum = i.indkey[0] WHERE i.indrelid = \
+       to_regclass($1) AND i.indisunique AND i.indnatts = 1 AND a.attname = \
+       'index_key') AS is_unique",
+    )
+    .bind(table)
+    .fetch_one(&self.pool)
+    .await
+    .into_diagnostic()
+    .map_err(DatabaseError::Database)?;
+
+    row
+      .try_get("is_unique")
+      .into_diagnostic()
+      .map_err(DatabaseError::Serialization)
+  }
+}
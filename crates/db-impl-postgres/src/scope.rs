@@ -0,0 +1,102 @@
+use std::fmt;
+
+use db_core::{DatabaseError, DatabaseResult};
+use miette::IntoDiagnostic;
+use model::{Model, RecordId};
+use sqlx::{PgPool, Postgres};
+use tracing::instrument;
+
+use crate::PostgresDatabase;
+
+/// A transaction that writes to several [`PostgresDatabase`]s, of any
+/// models, so that their changes are committed together or not at all.
+///
+/// Begin a scope from the pool the databases share, pass it to their
+/// `*_in` methods such as [`PostgresDatabase::insert_in`], then
+/// [`commit`](Self::commit) it. A scope that is dropped without being
+/// committed is rolled back. After any operation in the scope fails,
+/// Postgres rejects further statements in it, so it should be rolled back.
+pub struct PgTransactionScope {
+  tx: sqlx::Transaction<'static, Postgres>,
+}
+
+impl fmt::Debug for PgTransactionScope {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PgTransactionScope").finish_non_exhaustive()
+  }
+}
+
+impl PgTransactionScope {
+  /// Begin a new scope on a connection from `pool`.
+  pub async fn begin(pool: &PgPool) -> DatabaseResult<Self> {
+    let tx = pool
+      .begin()
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+    Ok(Self { tx })
+  }
+
+  /// Commit every change made in this scope.
+  pub async fn commit(self) -> DatabaseResult<()> {
+    self
+      .tx
+      .commit()
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)
+  }
+
+  /// Discard every change made in this scope.
+  pub async fn rollback(self) -> DatabaseResult<()> {
+    self
+      .tx
+      .rollback()
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)
+  }
+}
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Begin a [`PgTransactionScope`] on this database's pool.
+  pub async fn begin(&self) -> DatabaseResult<PgTransactionScope> {
+    PgTransactionScope::begin(&self.pool).await
+  }
+
+  /// Insert a new model as part of `scope`.
+  #[instrument(skip(self, scope, model), fields(model = M::TABLE_NAME, id = %model.id()))]
+  pub async fn insert_in(
+    &self,
+    scope: &mut PgTransactionScope,
+    model: &M,
+  ) -> DatabaseResult<()> {
+    self
+      .timed("insert_in", self.insert_in_tx(&mut scope.tx, model))
+      .await
+  }
+
+  /// Update an existing model as part of `scope`.
+  #[instrument(skip(self, scope, model), fields(model = M::TABLE_NAME, id = %model.id()))]
+  pub async fn update_in(
+    &self,
+    scope: &mut PgTransactionScope,
+    model: &M,
+  ) -> DatabaseResult<()> {
+    self
+      .timed("update_in", self.update_in_tx(&mut scope.tx, model))
+      .await
+  }
+
+  /// Delete a model by ID as part of `scope`.
+  #[instrument(skip(self, scope), fields(model = M::TABLE_NAME, id = %id))]
+  pub async fn delete_in(
+    &self,
+    scope: &mut PgTransactionScope,
+    id: RecordId<M>,
+  ) -> DatabaseResult<()> {
+    self
+      .timed("delete_in", self.delete_with(&mut *scope.tx, id))
+      .await
+  }
+}
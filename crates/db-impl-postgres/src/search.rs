@@ -0,0 +1,102 @@
+use db_core::{DatabaseError, DatabaseResult};
+use miette::{Context, IntoDiagnostic};
+use model::Model;
+use sqlx::{Postgres, postgres::PgRow};
+use tracing::{debug, instrument};
+
+use crate::PostgresDatabase;
+
+/// The text search configuration used for search vectors and queries.
+const SEARCH_CONFIG: &str = "simple";
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Create the generated `search_vector` column and its GIN index, if the
+  /// model has any search fields.
+  #[instrument(skip(self, tx), fields(model = M::TABLE_NAME))]
+  pub(crate) async fn create_search_column(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+  ) -> DatabaseResult<()> {
+    if M::SEARCH_FIELDS.is_empty() {
+      return Ok(());
+    }
+
+    debug!(fields = ?M::SEARCH_FIELDS, "Creating search column");
+
+    let document = M::SEARCH_FIELDS
+      .iter()
+      .map(|field| format!("coalesce(data->>'{field}', '')"))
+      .collect::<Vec<_>>()
+      .join(" || ' ' || ");
+
+    let query = format!(
+      "ALTER TABLE {table_name} ADD COLUMN IF NOT EXISTS search_vector \
+       tsvector GENERATED ALWAYS AS (to_tsvector('{SEARCH_CONFIG}', \
+       {document})) STORED",
+      table_name = self.table_name()
+    );
+    sqlx::query(&query)
+      .execute(&mut **tx)
+      .await
+      .into_diagnostic()
+      .context("failed to create search column")
+      .map_err(DatabaseError::Other)?;
+
+    let index_query = format!(
+      "CREATE INDEX IF NOT EXISTS idx_{local_name}_search ON {table_name} \
+       USING GIN (search_vector)",
+      local_name = self.namespace.local(M::TABLE_NAME),
+      table_name = self.table_name()
+    );
+    sqlx::query(&index_query)
+      .execute(&mut **tx)
+      .await
+      .into_diagnostic()
+      .context("failed to create search index")
+      .map_err(DatabaseError::Other)?;
+
+    debug!("Search column created successfully");
+    Ok(())
+  }
+
+  /// Search models by their search fields, ordered by rank.
+  #[instrument(skip(self), fields(model = M::TABLE_NAME, limit = limit))]
+  pub(crate) async fn search(
+    &self,
+    query: &str,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    debug!("Searching models");
+
+    if M::SEARCH_FIELDS.is_empty() {
+      return Err(DatabaseError::SearchNotEnabled(M::TABLE_NAME.to_string()));
+    }
+
+    let sql = format!(
+      "SELECT id, data FROM {table_name},
+              websearch_to_tsquery('{SEARCH_CONFIG}', $1) query
+       WHERE search_vector @@ query
+       ORDER BY ts_rank(search_vector, query) DESC
+       LIMIT $2",
+      table_name = self.table_name()
+    );
+
+    let rows: Vec<PgRow> = sqlx::query(&sql)
+      .bind(query)
+      .bind(i64::from(limit))
+      .fetch_all(&self.pool)
+      .await
+      .into_diagnostic()
+      .map_err(DatabaseError::Database)?;
+
+    let count = rows.len();
+    let mut results = Vec::with_capacity(count);
+
+    for row in rows {
+      results.push(self.deserialize_from_row(&row)?);
+    }
+
+    debug!(count = count, "Searched models");
+    Ok(results)
+  }
+}
@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+use db_core::{DatabaseError, DatabaseResult};
+use miette::{Context, IntoDiagnostic};
+use model::Model;
+use sqlx::Postgres;
+use tracing::{debug, warn};
+
+use crate::PostgresDatabase;
+
+/// The table slow operations are recorded into, shared by all models in a
+/// [`TableNamespace`](crate::TableNamespace).
+pub const SLOW_QUERY_TABLE: &str = "palin_slow_queries";
+
+/// Settings for reporting database operations that take too long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlowQueryLog {
+  /// Operations taking at least this long are logged as warnings.
+  pub threshold: Duration,
+  /// Whether to also record slow operations into [`SLOW_QUERY_TABLE`].
+  pub record:    bool,
+}
+
+impl SlowQueryLog {
+  /// Log operations taking at least `threshold`, without recording them.
+  #[must_use]
+  pub const fn new(threshold: Duration) -> Self {
+    Self {
+      threshold,
+      record: false,
+    }
+  }
+
+  /// Also record slow operations into [`SLOW_QUERY_TABLE`].
+  #[must_use]
+  pub const fn with_recording(mut self) -> Self {
+    self.record = true;
+    self
+  }
+}
+
+impl<M: Model> PostgresDatabase<M> {
+  /// Create the slow query table, if slow operations are recorded.
+  pub(crate) async fn create_slow_query_table(
+    &self,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+  ) -> DatabaseResult<()> {
+    if !self.slow_query_log.is_some_and(|log| log.record) {
+      return Ok(());
+    }
+
+    let query = format!(
+      "CREATE TABLE IF NOT EXISTS {table} (
+          operation TEXT NOT NULL,
+          table_name TEXT NOT NULL,
+          duration_ms DOUBLE PRECISION NOT NULL,
+          recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+      )",
+      table = self.namespace.qualify(SLOW_QUERY_TABLE),
+    );
+    sqlx::query(&query)
+      .execute(&mut **tx)
+      .await
+      .into_diagnostic()
+      .context("failed to create slow query table")
+      .map_err(DatabaseError::Other)?;
+
+    debug!("Slow query table created");
+    Ok(())
+  }
+
+  /// Run an operation, reporting it if it exceeds the slow query threshold.
+  ///
+  /// With the `otel` feature, the operation also runs in a client span.
+  pub(crate) async fn timed<T>(
+    &self,
+    operation: &'static str,
+    future: impl Future<Output = DatabaseResult<T>>,
+  ) -> DatabaseResult<T> {
+    #[cfg(feature = "otel")]
+    let future =
+      tracing::Instrument::instrument(future, self.operation_span(operation));
+
+    let Some(log) = self.slow_query_log else {
+      return future.await;
+    };
+
+    let start = Instant::now();
+    let result = future.await;
+    let elapsed = start.elapsed();
+    if elapsed < log.threshold {
+      return result;
+    }
+
+    let duration_ms = elapsed.as_secs_f64() * 1000.0;
+    warn!(
+      operation,
+      table = M::TABLE_NAME,
+      duration_ms,
+      "slow database operation"
+    );
+    if log.record {
+      self.record_slow_query(operation, duration_ms).await;
+    }
+    result
+  }
+
+  /// Record a slow operation. Failures are logged rather than returned, so
+  /// that they don't fail the operation itself.
+  async fn record_slow_query(&self, operation: &str, duration_ms: f64) {
+    let query = format!(
+      "INSERT INTO {table} (operation, table_name, duration_ms) VALUES ($1, \
+       $2, $3)",
+      table = self.namespace.qualify(SLOW_QUERY_TABLE),
+    );
+    if let Err(e) = sqlx::query(&query)
+      .bind(operation)
+      .bind(M::TABLE_NAME)
+      .bind(duration_ms)
+      .execute(&self.pool)
+      .await
+    {
+      warn!(operation, error = %e, "failed to record slow database operation");
+    }
+  }
+}
@@ -0,0 +1,106 @@
+use model::Model;
+use tracing::{Span, info_span};
+
+use crate::{PostgresDatabase, queries::Queries};
+
+impl Queries {
+  /// The cached statement an operation runs, if it runs a single one.
+  pub(crate) fn statement(&self, operation: &str) -> Option<&str> {
+    match operation {
+      "get" | "get_raw" | "exists" => Some(&self.get),
+      "insert" => Some(&self.insert),
+      "update" => Some(&self.update),
+      "delete" => Some(&self.delete),
+      "count" => Some(&self.count),
+      _ => None,
+    }
+  }
+}
+
+impl<M: Model> PostgresDatabase<M> {
+  /// A client span for an operation, carrying the OpenTelemetry database
+  /// semantic convention attributes.
+  ///
+  /// The statement text is only recorded for operations that run a single
+  /// cached statement, and is [sanitized](sanitize_statement) first.
+  pub(crate) fn operation_span(&self, operation: &'static str) -> Span {
+    let statement = self.queries.statement(operation).map(sanitize_statement);
+    info_span!(
+      "db.operation",
+      otel.name = format!("{operation} {}", M::TABLE_NAME),
+      otel.kind = "client",
+      db.system.name = "postgresql",
+      db.namespace = self.namespace.schema(),
+      db.collection.name = M::TABLE_NAME,
+      db.operation.name = operation,
+      db.query.text = statement.as_deref(),
+    )
+  }
+}
+
+/// Replace the literals in a SQL statement with `?`, and collapse its
+/// whitespace, so that it can be recorded without leaking values.
+///
+/// Bind placeholders like `$1` are kept as they are.
+pub(crate) fn sanitize_statement(sql: &str) -> String {
+  let mut out = String::with_capacity(sql.len());
+  let mut chars = sql.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    let prev = out.chars().next_back();
+    match c {
+      '\'' => {
+        // skip to the closing quote, where `''` is an escaped quote
+        while let Some(c) = chars.next() {
+          if c == '\'' {
+            if chars.peek() == Some(&'\'') {
+              chars.next();
+            } else {
+              break;
+            }
+          }
+        }
+        out.push('?');
+      }
+      c if c.is_ascii_digit()
+        && !prev
+          .is_some_and(|p| p.is_alphanumeric() || p == '_' || p == '$') =>
+      {
+        while chars.next_if(|c| c.is_ascii_digit() || *c == '.').is_some() {}
+        out.push('?');
+      }
+      c if c.is_whitespace() => {
+        if prev.is_some_and(|p| p != ' ') {
+          out.push(' ');
+        }
+      }
+      c => out.push(c),
+    }
+  }
+
+  out.truncate(out.trim_end().len());
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::sanitize_statement;
+
+  #[test]
+  fn test_sanitize_statement() {
+    assert_eq!(
+      sanitize_statement("SELECT data FROM t1 WHERE id = $1"),
+      "SELECT data FROM t1 WHERE id = $1"
+    );
+    assert_eq!(
+      sanitize_statement(
+        "SELECT *\n    FROM users\n    WHERE name = 'o''brien' AND age > 42.5"
+      ),
+      "SELECT * FROM users WHERE name = ? AND age > ?"
+    );
+    assert_eq!(
+      sanitize_statement("SELECT data FROM t LIMIT 10 OFFSET $12 "),
+      "SELECT data FROM t LIMIT ? OFFSET $12"
+    );
+  }
+}
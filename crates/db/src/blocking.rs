@@ -0,0 +1,357 @@
+//! A blocking interface to [`Database`](crate::Database), for use outside of
+//! an async runtime.
+//!
+//! Each [`Database`] drives the async interface on an internal
+//! current-thread runtime. Its methods must not be called from within an
+//! async runtime, as blocking on a runtime from inside another panics.
+
+use std::{collections::HashMap, ops::Bound, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use db_core::DatabaseResult;
+use miette::{Context, IntoDiagnostic};
+use model::{IndexValue, Model, Projection, RecordId};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{DbConfig, Page, SchemaDescription};
+
+/// A blocking domain model database.
+///
+/// Clones share the same underlying store and runtime.
+#[derive(Clone, Debug)]
+pub struct Database<M> {
+  inner:   crate::Database<M>,
+  runtime: Arc<Runtime>,
+}
+
+/// Builds the runtime a blocking wrapper drives its futures on.
+fn build_runtime() -> miette::Result<Arc<Runtime>> {
+  Builder::new_current_thread()
+    .enable_all()
+    .build()
+    .map(Arc::new)
+    .into_diagnostic()
+    .context("failed to build runtime for blocking database")
+}
+
+impl<M: Model> Database<M> {
+  /// Wrap an async [`Database`](crate::Database) in a blocking interface.
+  pub fn from_async(inner: crate::Database<M>) -> miette::Result<Self> {
+    Ok(Self {
+      inner,
+      runtime: build_runtime()?,
+    })
+  }
+
+  /// Create a new database backed by a mock store.
+  pub fn new_mock() -> miette::Result<Self> {
+    Self::from_async(crate::Database::new_mock())
+  }
+
+  /// Create a new database backed by a `PostgreSQL` store.
+  pub fn new_postgres(url: &str) -> miette::Result<Self> {
+    let runtime = build_runtime()?;
+    let inner = runtime.block_on(crate::Database::new_postgres(url))?;
+    Ok(Self { inner, runtime })
+  }
+
+  /// Create a new database from a [`DbConfig`].
+  pub fn from_config(config: DbConfig) -> miette::Result<Self> {
+    let runtime = build_runtime()?;
+    let inner = runtime.block_on(crate::Database::from_config(config))?;
+    Ok(Self { inner, runtime })
+  }
+
+  /// The async [`Database`](crate::Database) this wraps.
+  #[must_use]
+  pub const fn as_async(&self) -> &crate::Database<M> { &self.inner }
+
+  /// Initialize the storage schema for this model.
+  pub fn initialize_schema(&self) -> DatabaseResult<()> {
+    self.runtime.block_on(self.inner.initialize_schema())
+  }
+  /// Recompute every index entry from the stored models.
+  pub fn rebuild_indices(&self) -> DatabaseResult<()> {
+    self.runtime.block_on(self.inner.rebuild_indices())
+  }
+  /// Insert a new model into storage.
+  pub fn insert(&self, model: &M) -> DatabaseResult<()> {
+    self.runtime.block_on(self.inner.insert(model))
+  }
+  /// Update an existing model in storage.
+  pub fn update(&self, model: &M) -> DatabaseResult<()> {
+    self.runtime.block_on(self.inner.update(model))
+  }
+  /// Update an existing model in storage, unless it was written after
+  /// `expected_updated_at`, returning
+  /// [`DatabaseError::Conflict`](crate::DatabaseError::Conflict) if it was.
+  pub fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()> {
+    self
+      .runtime
+      .block_on(self.inner.update_if_unchanged(model, expected_updated_at))
+  }
+  /// Insert a model if it doesn't exist, or update it if it does.
+  ///
+  /// Returns `true` if inserted, `false` if updated.
+  pub fn upsert(&self, model: &M) -> DatabaseResult<bool> {
+    self.runtime.block_on(self.inner.upsert(model))
+  }
+  /// Insert a new model into storage, returning the model as stored.
+  pub fn insert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    self.runtime.block_on(self.inner.insert_and_return(model))
+  }
+  /// Insert a model if it doesn't exist, or update it if it does, returning
+  /// the model as stored.
+  pub fn upsert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    self.runtime.block_on(self.inner.upsert_and_return(model))
+  }
+  /// Atomically read, modify and write the model with the given ID. `update`
+  /// receives the current model, if any, and returns the model to store.
+  pub fn upsert_with(
+    &self,
+    id: RecordId<M>,
+    update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+  ) -> DatabaseResult<M> {
+    self.runtime.block_on(self.inner.upsert_with(id, update))
+  }
+  /// Atomically add `delta` to the integer field at `field_path` of the
+  /// model with the given ID, returning the new value.
+  pub fn increment(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64> {
+    self
+      .runtime
+      .block_on(self.inner.increment(id, field_path, delta))
+  }
+  /// Delete a model from storage by ID.
+  pub fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
+    self.runtime.block_on(self.inner.delete(id))
+  }
+  /// Delete a model from storage by ID, returning the deleted model.
+  pub fn delete_and_return(&self, id: RecordId<M>) -> DatabaseResult<M> {
+    self.runtime.block_on(self.inner.delete_and_return(id))
+  }
+  /// When the model with the given ID was last inserted or updated.
+  pub fn updated_at(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<DateTime<Utc>>> {
+    self.runtime.block_on(self.inner.updated_at(id))
+  }
+  /// Retrieve a model by its ID.
+  pub fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
+    self.runtime.block_on(self.inner.get(id))
+  }
+  /// Retrieve a model by its ID, returning an error if not found.
+  pub fn get_or_error(&self, id: RecordId<M>) -> DatabaseResult<M> {
+    self.runtime.block_on(self.inner.get_or_error(id))
+  }
+  /// Retrieve multiple models by their IDs in a single operation.
+  pub fn get_many(
+    &self,
+    ids: &[RecordId<M>],
+  ) -> DatabaseResult<Vec<Option<M>>> {
+    self.runtime.block_on(self.inner.get_many(ids))
+  }
+  /// Retrieve the serialized form of a model by its ID, without
+  /// deserializing it.
+  pub fn get_raw(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    self.runtime.block_on(self.inner.get_raw(id))
+  }
+  /// Retrieve the field at `field_path` of the serialized form of a model by
+  /// its ID, or `None` if the model or the field is missing.
+  pub fn get_field(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    self.runtime.block_on(self.inner.get_field(id, field_path))
+  }
+  /// Retrieve a [`Projection`] of a model by its ID, fetching only the
+  /// fields it needs.
+  pub fn get_as<P: Projection<M>>(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<P>> {
+    self.runtime.block_on(self.inner.get_as(id))
+  }
+  /// Fetch the records of this database referenced by a batch of other
+  /// models, keyed by ID, with a single [`get_many`](Self::get_many) call.
+  pub fn join<T, I>(
+    &self,
+    models: &[T],
+    foreign_ids: impl Fn(&T) -> I,
+  ) -> DatabaseResult<HashMap<RecordId<M>, M>>
+  where
+    I: IntoIterator<Item = RecordId<M>>,
+  {
+    self.runtime.block_on(self.inner.join(models, foreign_ids))
+  }
+  /// Find a single model by a unique index.
+  pub fn find_by_unique_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Option<M>> {
+    self
+      .runtime
+      .block_on(self.inner.find_by_unique_index(selector, key))
+  }
+  /// Find a model by a unique index, returning an error if not found.
+  pub fn find_by_unique_index_or_error(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<M> {
+    self
+      .runtime
+      .block_on(self.inner.find_by_unique_index_or_error(selector, key))
+  }
+  /// Find all models matching a non-unique index.
+  pub fn find_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<M>> {
+    self
+      .runtime
+      .block_on(self.inner.find_by_index(selector, key))
+  }
+  /// Find the first model matching a non-unique index.
+  pub fn find_one_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Option<M>> {
+    self
+      .runtime
+      .block_on(self.inner.find_one_by_index(selector, key))
+  }
+  /// Find the serialized forms of all models matching a non-unique index,
+  /// without deserializing them.
+  pub fn find_by_index_raw(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    self
+      .runtime
+      .block_on(self.inner.find_by_index_raw(selector, key))
+  }
+  /// Find all models whose index key falls within the given bounds, ordered
+  /// by key ascending.
+  pub fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>> {
+    self
+      .runtime
+      .block_on(self.inner.find_by_index_range(selector, lower, upper))
+  }
+  /// Atomically claim up to `limit` models whose index key falls within the
+  /// given bounds, in key order, applying `claim` to each and saving it.
+  /// Concurrent claimers never receive the same model.
+  pub fn claim_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+    limit: u32,
+    claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+  ) -> DatabaseResult<Vec<M>> {
+    self.runtime.block_on(
+      self
+        .inner
+        .claim_by_index_range(selector, lower, upper, limit, claim),
+    )
+  }
+  /// List all models with pagination.
+  pub fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
+    self.runtime.block_on(self.inner.list(limit, offset))
+  }
+  /// List up to `limit` models ordered by ID, starting after the model with
+  /// ID `after`, or from the first if `None`.
+  pub fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    self.runtime.block_on(self.inner.list_after(after, limit))
+  }
+  /// Search models by the text of their search fields, returning at most
+  /// `limit` results ordered by relevance where supported.
+  pub fn search(&self, query: &str, limit: u32) -> DatabaseResult<Vec<M>> {
+    self.runtime.block_on(self.inner.search(query, limit))
+  }
+  /// List models with pagination, along with the total record count.
+  pub fn list_page(&self, limit: u32, offset: u32) -> DatabaseResult<Page<M>> {
+    self.runtime.block_on(self.inner.list_page(limit, offset))
+  }
+  /// List [`Projection`]s of models with pagination, fetching only the
+  /// fields they need.
+  pub fn list_as<P: Projection<M>>(
+    &self,
+    limit: u32,
+    offset: u32,
+  ) -> DatabaseResult<Vec<P>> {
+    self.runtime.block_on(self.inner.list_as(limit, offset))
+  }
+  /// List all models without pagination.
+  pub fn list_all(&self) -> DatabaseResult<Vec<M>> {
+    self.runtime.block_on(self.inner.list_all())
+  }
+  /// Count the total number of records in storage.
+  pub fn count(&self) -> DatabaseResult<u64> {
+    self.runtime.block_on(self.inner.count())
+  }
+  /// Return up to `n` records chosen at random.
+  pub fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+    self.runtime.block_on(self.inner.sample(n))
+  }
+  /// Estimate the total number of records in storage. Cheap on large tables,
+  /// but possibly stale.
+  pub fn estimate_count(&self) -> DatabaseResult<u64> {
+    self.runtime.block_on(self.inner.estimate_count())
+  }
+  /// Describe the tables storing this model as they are found in the
+  /// database.
+  pub fn describe_schema(&self) -> DatabaseResult<SchemaDescription> {
+    self.runtime.block_on(self.inner.describe_schema())
+  }
+  /// Count records matching a non-unique index.
+  pub fn count_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<u64> {
+    self
+      .runtime
+      .block_on(self.inner.count_by_index(selector, key))
+  }
+  /// Check if a record exists by ID.
+  pub fn exists(&self, id: RecordId<M>) -> DatabaseResult<bool> {
+    self.runtime.block_on(self.inner.exists(id))
+  }
+  /// Check if any records match a unique index key.
+  pub fn exists_by_unique_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<bool> {
+    self
+      .runtime
+      .block_on(self.inner.exists_by_unique_index(selector, key))
+  }
+}
@@ -0,0 +1,304 @@
+//! Configuration for selecting and building a database backend.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use db_impl_postgres::TableNamespace;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+
+/// The environment variable suffixes read by [`DbConfig::from_env`].
+mod vars {
+  pub const BACKEND: &str = "BACKEND";
+  pub const URL: &str = "URL";
+  pub const MAX_CONNECTIONS: &str = "MAX_CONNECTIONS";
+  pub const MIN_CONNECTIONS: &str = "MIN_CONNECTIONS";
+  pub const ACQUIRE_TIMEOUT_SECS: &str = "ACQUIRE_TIMEOUT_SECS";
+  pub const IDLE_TIMEOUT_SECS: &str = "IDLE_TIMEOUT_SECS";
+  pub const SCHEMA_INIT: &str = "SCHEMA_INIT";
+  pub const SCHEMA_NAME: &str = "SCHEMA_NAME";
+  pub const TABLE_PREFIX: &str = "TABLE_PREFIX";
+}
+
+/// Parses the value of an optional environment variable.
+fn parse_var<T: FromStr>(
+  (var, value): (String, Option<String>),
+) -> Result<Option<T>, DbConfigError> {
+  value
+    .map(|value| {
+      value
+        .parse()
+        .map_err(|_| DbConfigError::InvalidVar { var, value })
+    })
+    .transpose()
+}
+
+/// Errors produced while loading or validating a [`DbConfig`].
+#[derive(Debug, thiserror::Error, Diagnostic)]
+pub enum DbConfigError {
+  /// A required environment variable is not set.
+  #[error("missing environment variable `{0}`")]
+  #[diagnostic(help("set the variable or choose a different backend"))]
+  MissingVar(String),
+
+  /// An environment variable is set but not valid unicode.
+  #[error("environment variable `{0}` is not valid unicode")]
+  NotUnicode(String),
+
+  /// An environment variable could not be parsed.
+  #[error("environment variable `{var}` has invalid value `{value}`")]
+  InvalidVar {
+    /// The variable name.
+    var:   String,
+    /// The value that failed to parse.
+    value: String,
+  },
+
+  /// The backend name is not recognized.
+  #[error("unknown database backend `{0}`")]
+  #[diagnostic(help("expected one of `postgres` or `mock`"))]
+  UnknownBackend(String),
+
+  /// A required field is empty.
+  #[error("database config field `{0}` must not be empty")]
+  EmptyField(&'static str),
+
+  /// The schema name or table prefix is not a valid identifier.
+  #[error("invalid table namespace: {0}")]
+  #[diagnostic(help(
+    "use only ASCII letters, digits and underscores, and don't start the \
+     schema name with a digit"
+  ))]
+  InvalidNamespace(String),
+
+  /// The pool settings are inconsistent.
+  #[error("min_connections ({min}) must not exceed max_connections ({max})")]
+  InvalidPoolSize {
+    /// The configured minimum.
+    min: u32,
+    /// The configured maximum.
+    max: u32,
+  },
+}
+
+/// Connection pool settings. Unset fields use the driver's defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolSettings {
+  /// The maximum number of connections in the pool.
+  pub max_connections:      Option<u32>,
+  /// The minimum number of idle connections kept in the pool.
+  pub min_connections:      Option<u32>,
+  /// How long to wait for a connection before failing, in seconds.
+  pub acquire_timeout_secs: Option<u64>,
+  /// How long a connection may sit idle before being closed, in seconds.
+  pub idle_timeout_secs:    Option<u64>,
+}
+
+impl PoolSettings {
+  /// The connection acquire timeout.
+  #[must_use]
+  pub fn acquire_timeout(&self) -> Option<Duration> {
+    self.acquire_timeout_secs.map(Duration::from_secs)
+  }
+
+  /// The idle connection timeout.
+  #[must_use]
+  pub fn idle_timeout(&self) -> Option<Duration> {
+    self.idle_timeout_secs.map(Duration::from_secs)
+  }
+}
+
+/// Whether to initialize the schema when building a database from config.
+#[derive(
+  Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaInitPolicy {
+  /// Run [`initialize_schema`](crate::Database::initialize_schema) before
+  /// returning the database.
+  #[default]
+  Initialize,
+  /// Leave schema management to the caller, e.g. a migration tool.
+  Skip,
+}
+
+impl FromStr for SchemaInitPolicy {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "initialize" => Ok(SchemaInitPolicy::Initialize),
+      "skip" => Ok(SchemaInitPolicy::Skip),
+      _ => Err(()),
+    }
+  }
+}
+
+/// The database backend and its settings.
+///
+/// When deserialized, the backend is selected by a `backend` field.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum DbBackendConfig {
+  /// A `PostgreSQL` database.
+  Postgres {
+    /// The connection URL.
+    url:          String,
+    /// Connection pool settings.
+    #[serde(default)]
+    pool:         PoolSettings,
+    /// The Postgres schema to store tables in, instead of the default
+    /// search path. Created if missing.
+    #[serde(default)]
+    schema:       Option<String>,
+    /// A prefix for every table name, e.g. `staging_`.
+    #[serde(default)]
+    table_prefix: Option<String>,
+  },
+  /// An in-memory mock store, discarded when dropped.
+  Mock,
+}
+
+impl DbBackendConfig {
+  /// The [`TableNamespace`] for a Postgres backend's tables.
+  pub(crate) fn table_namespace(
+    &self,
+  ) -> Result<TableNamespace, DbConfigError> {
+    match self {
+      DbBackendConfig::Postgres {
+        schema,
+        table_prefix,
+        ..
+      } => TableNamespace::new(
+        schema.as_deref(),
+        table_prefix.as_deref().unwrap_or_default(),
+      )
+      .map_err(|e| DbConfigError::InvalidNamespace(e.to_string())),
+      DbBackendConfig::Mock => Ok(TableNamespace::default()),
+    }
+  }
+}
+
+impl fmt::Debug for DbBackendConfig {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DbBackendConfig::Postgres {
+        pool,
+        schema,
+        table_prefix,
+        ..
+      } => f
+        .debug_struct("Postgres")
+        .field("url", &"<redacted>")
+        .field("pool", pool)
+        .field("schema", schema)
+        .field("table_prefix", table_prefix)
+        .finish(),
+      DbBackendConfig::Mock => f.write_str("Mock"),
+    }
+  }
+}
+
+/// Configuration selecting a database backend and how to set it up.
+///
+/// For example, `{ "backend": "postgres", "url": "postgres://...", "pool": {
+/// "max_connections": 10 }, "schema_init": "skip" }`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbConfig {
+  /// The backend and its settings.
+  #[serde(flatten)]
+  pub backend:     DbBackendConfig,
+  /// Whether to initialize the schema on startup.
+  #[serde(default)]
+  pub schema_init: SchemaInitPolicy,
+}
+
+impl DbConfig {
+  /// Loads a [`DbConfig`] from environment variables.
+  ///
+  /// Variables are named `{prefix}_{NAME}`, e.g. with the prefix `DB`:
+  /// - `DB_BACKEND`: one of `postgres` or `mock`.
+  /// - `postgres`: `DB_URL`, and optionally `DB_MAX_CONNECTIONS`,
+  ///   `DB_MIN_CONNECTIONS`, `DB_ACQUIRE_TIMEOUT_SECS`, `DB_IDLE_TIMEOUT_SECS`,
+  ///   `DB_SCHEMA_NAME`, and `DB_TABLE_PREFIX`.
+  /// - `DB_SCHEMA_INIT`: optionally one of `initialize` (the default) or
+  ///   `skip`.
+  pub fn from_env(prefix: &str) -> Result<Self, DbConfigError> {
+    Self::from_lookup(prefix, |name| match std::env::var(name) {
+      Ok(value) => Ok(Some(value)),
+      Err(std::env::VarError::NotPresent) => Ok(None),
+      Err(std::env::VarError::NotUnicode(_)) => {
+        Err(DbConfigError::NotUnicode(name.to_owned()))
+      }
+    })
+  }
+
+  /// Loads a [`DbConfig`] using `lookup` to read variables by name.
+  pub(crate) fn from_lookup<F>(
+    prefix: &str,
+    lookup: F,
+  ) -> Result<Self, DbConfigError>
+  where
+    F: Fn(&str) -> Result<Option<String>, DbConfigError>,
+  {
+    let name = |suffix: &str| {
+      if prefix.is_empty() {
+        suffix.to_owned()
+      } else {
+        format!("{prefix}_{suffix}")
+      }
+    };
+    let optional = |suffix: &str| lookup(&name(suffix));
+    let required = |suffix: &str| {
+      optional(suffix)?.ok_or_else(|| DbConfigError::MissingVar(name(suffix)))
+    };
+    let parsed = |suffix: &str| -> Result<_, DbConfigError> {
+      Ok((name(suffix), optional(suffix)?))
+    };
+
+    let backend = match required(vars::BACKEND)?.to_lowercase().as_str() {
+      "postgres" => DbBackendConfig::Postgres {
+        url:          required(vars::URL)?,
+        pool:         PoolSettings {
+          max_connections:      parse_var(parsed(vars::MAX_CONNECTIONS)?)?,
+          min_connections:      parse_var(parsed(vars::MIN_CONNECTIONS)?)?,
+          acquire_timeout_secs: parse_var(parsed(vars::ACQUIRE_TIMEOUT_SECS)?)?,
+          idle_timeout_secs:    parse_var(parsed(vars::IDLE_TIMEOUT_SECS)?)?,
+        },
+        schema:       optional(vars::SCHEMA_NAME)?,
+        table_prefix: optional(vars::TABLE_PREFIX)?,
+      },
+      "mock" => DbBackendConfig::Mock,
+      other => return Err(DbConfigError::UnknownBackend(other.to_owned())),
+    };
+    let schema_init =
+      parse_var(parsed(vars::SCHEMA_INIT)?)?.unwrap_or_default();
+
+    let config = DbConfig {
+      backend,
+      schema_init,
+    };
+    config.validate()?;
+    Ok(config)
+  }
+
+  /// Checks that all required fields are set and consistent.
+  pub fn validate(&self) -> Result<(), DbConfigError> {
+    match &self.backend {
+      DbBackendConfig::Postgres { url, pool, .. } => {
+        if url.trim().is_empty() {
+          return Err(DbConfigError::EmptyField("url"));
+        }
+        self.backend.table_namespace()?;
+        if let (Some(min), Some(max)) =
+          (pool.min_connections, pool.max_connections)
+          && min > max
+        {
+          return Err(DbConfigError::InvalidPoolSize { min, max });
+        }
+        Ok(())
+      }
+      DbBackendConfig::Mock => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,444 @@
+//! Timing and error instrumentation for any database backend.
+
+use std::{future::Future, ops::Bound, sync::Arc, time::Instant};
+
+use chrono::{DateTime, Utc};
+use db_core::{DatabaseLike, DatabaseResult, Page, SchemaDescription};
+use model::{IndexValue, Model, RecordId};
+use tracing::{debug, warn};
+
+/// A [`DatabaseLike`] decorator which reports the duration and outcome of
+/// every operation as `tracing` events, for backends without their own
+/// instrumentation.
+///
+/// Each operation emits an event with target `db::metrics` carrying the
+/// table, the operation name and a `histogram.db.operation.duration` field in
+/// seconds. Failed operations are reported at `WARN` with a
+/// `monotonic_counter.db.operation.errors` field and the error's code. The
+/// field names follow the conventions of `tracing-opentelemetry`'s metrics
+/// layer, so installing it turns the events into histograms and counters.
+pub struct InstrumentedDatabase<D: ?Sized> {
+  inner: Arc<D>,
+}
+
+impl<D: ?Sized> InstrumentedDatabase<D> {
+  /// Wraps `inner`, instrumenting its operations.
+  #[must_use]
+  pub const fn new(inner: Arc<D>) -> Self { Self { inner } }
+}
+
+impl<D: ?Sized> Clone for InstrumentedDatabase<D> {
+  fn clone(&self) -> Self {
+    Self {
+      inner: self.inner.clone(),
+    }
+  }
+}
+
+impl<D: ?Sized> std::fmt::Debug for InstrumentedDatabase<D> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("InstrumentedDatabase")
+      .finish_non_exhaustive()
+  }
+}
+
+/// Runs `operation` on `table`, reporting its duration and outcome.
+async fn timed<T>(
+  table: &'static str,
+  operation: &'static str,
+  future: impl Future<Output = DatabaseResult<T>>,
+) -> DatabaseResult<T> {
+  let start = Instant::now();
+  let result = future.await;
+  let duration = start.elapsed().as_secs_f64();
+  match &result {
+    Ok(_) => debug!(
+      target: "db::metrics",
+      {
+        histogram.db.operation.duration = duration,
+        db.table = table,
+        db.operation = operation,
+      },
+      "database operation completed"
+    ),
+    Err(error) => warn!(
+      target: "db::metrics",
+      {
+        histogram.db.operation.duration = duration,
+        monotonic_counter.db.operation.errors = 1_u64,
+        db.table = table,
+        db.operation = operation,
+        error.code = error.error_code(),
+        %error,
+      },
+      "database operation failed"
+    ),
+  }
+  result
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl<M: Model, D: DatabaseLike<M> + ?Sized> DatabaseLike<M>
+  for InstrumentedDatabase<D>
+{
+  async fn initialize_schema(&self) -> DatabaseResult<()> {
+    timed(
+      M::TABLE_NAME,
+      "initialize_schema",
+      self.inner.initialize_schema(),
+    )
+    .await
+  }
+
+  async fn rebuild_indices(&self) -> DatabaseResult<()> {
+    timed(
+      M::TABLE_NAME,
+      "rebuild_indices",
+      self.inner.rebuild_indices(),
+    )
+    .await
+  }
+
+  async fn insert(&self, model: &M) -> DatabaseResult<()> {
+    timed(M::TABLE_NAME, "insert", self.inner.insert(model)).await
+  }
+
+  async fn update(&self, model: &M) -> DatabaseResult<()> {
+    timed(M::TABLE_NAME, "update", self.inner.update(model)).await
+  }
+
+  async fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()> {
+    timed(
+      M::TABLE_NAME,
+      "update_if_unchanged",
+      self.inner.update_if_unchanged(model, expected_updated_at),
+    )
+    .await
+  }
+
+  async fn upsert(&self, model: &M) -> DatabaseResult<bool> {
+    timed(M::TABLE_NAME, "upsert", self.inner.upsert(model)).await
+  }
+
+  async fn insert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    timed(
+      M::TABLE_NAME,
+      "insert_and_return",
+      self.inner.insert_and_return(model),
+    )
+    .await
+  }
+
+  async fn upsert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    timed(
+      M::TABLE_NAME,
+      "upsert_and_return",
+      self.inner.upsert_and_return(model),
+    )
+    .await
+  }
+
+  async fn upsert_with(
+    &self,
+    id: RecordId<M>,
+    update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+  ) -> DatabaseResult<M> {
+    timed(
+      M::TABLE_NAME,
+      "upsert_with",
+      self.inner.upsert_with(id, update),
+    )
+    .await
+  }
+
+  async fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
+    timed(M::TABLE_NAME, "delete", self.inner.delete(id)).await
+  }
+
+  async fn delete_and_return(&self, id: RecordId<M>) -> DatabaseResult<M> {
+    timed(
+      M::TABLE_NAME,
+      "delete_and_return",
+      self.inner.delete_and_return(id),
+    )
+    .await
+  }
+
+  async fn updated_at(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<DateTime<Utc>>> {
+    timed(M::TABLE_NAME, "updated_at", self.inner.updated_at(id)).await
+  }
+
+  async fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
+    timed(M::TABLE_NAME, "get", self.inner.get(id)).await
+  }
+
+  async fn get_or_error(&self, id: RecordId<M>) -> DatabaseResult<M> {
+    timed(M::TABLE_NAME, "get_or_error", self.inner.get_or_error(id)).await
+  }
+
+  async fn get_many(
+    &self,
+    ids: &[RecordId<M>],
+  ) -> DatabaseResult<Vec<Option<M>>> {
+    timed(M::TABLE_NAME, "get_many", self.inner.get_many(ids)).await
+  }
+
+  async fn get_raw(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    timed(M::TABLE_NAME, "get_raw", self.inner.get_raw(id)).await
+  }
+
+  async fn get_field(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    timed(
+      M::TABLE_NAME,
+      "get_field",
+      self.inner.get_field(id, field_path),
+    )
+    .await
+  }
+
+  async fn get_projected(
+    &self,
+    id: RecordId<M>,
+    fields: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    timed(
+      M::TABLE_NAME,
+      "get_projected",
+      self.inner.get_projected(id, fields),
+    )
+    .await
+  }
+
+  async fn find_by_unique_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Option<M>> {
+    timed(
+      M::TABLE_NAME,
+      "find_by_unique_index",
+      self.inner.find_by_unique_index(selector, key),
+    )
+    .await
+  }
+
+  async fn find_by_unique_index_or_error(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<M> {
+    timed(
+      M::TABLE_NAME,
+      "find_by_unique_index_or_error",
+      self.inner.find_by_unique_index_or_error(selector, key),
+    )
+    .await
+  }
+
+  async fn find_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<M>> {
+    timed(
+      M::TABLE_NAME,
+      "find_by_index",
+      self.inner.find_by_index(selector, key),
+    )
+    .await
+  }
+
+  async fn find_one_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Option<M>> {
+    timed(
+      M::TABLE_NAME,
+      "find_one_by_index",
+      self.inner.find_one_by_index(selector, key),
+    )
+    .await
+  }
+
+  async fn find_by_index_raw(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    timed(
+      M::TABLE_NAME,
+      "find_by_index_raw",
+      self.inner.find_by_index_raw(selector, key),
+    )
+    .await
+  }
+
+  async fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
+    timed(M::TABLE_NAME, "list", self.inner.list(limit, offset)).await
+  }
+
+  async fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    timed(
+      M::TABLE_NAME,
+      "list_after",
+      self.inner.list_after(after, limit),
+    )
+    .await
+  }
+
+  async fn search(&self, query: &str, limit: u32) -> DatabaseResult<Vec<M>> {
+    timed(M::TABLE_NAME, "search", self.inner.search(query, limit)).await
+  }
+
+  async fn list_page(
+    &self,
+    limit: u32,
+    offset: u32,
+  ) -> DatabaseResult<Page<M>> {
+    timed(
+      M::TABLE_NAME,
+      "list_page",
+      self.inner.list_page(limit, offset),
+    )
+    .await
+  }
+
+  async fn list_projected(
+    &self,
+    limit: u32,
+    offset: u32,
+    fields: &[&str],
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    timed(
+      M::TABLE_NAME,
+      "list_projected",
+      self.inner.list_projected(limit, offset, fields),
+    )
+    .await
+  }
+
+  async fn list_all(&self) -> DatabaseResult<Vec<M>> {
+    timed(M::TABLE_NAME, "list_all", self.inner.list_all()).await
+  }
+
+  async fn count(&self) -> DatabaseResult<u64> {
+    timed(M::TABLE_NAME, "count", self.inner.count()).await
+  }
+
+  async fn truncate(&self) -> DatabaseResult<()> {
+    timed(M::TABLE_NAME, "truncate", self.inner.truncate()).await
+  }
+
+  async fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+    timed(M::TABLE_NAME, "sample", self.inner.sample(n)).await
+  }
+
+  async fn estimate_count(&self) -> DatabaseResult<u64> {
+    timed(M::TABLE_NAME, "estimate_count", self.inner.estimate_count()).await
+  }
+
+  async fn describe_schema(&self) -> DatabaseResult<SchemaDescription> {
+    timed(
+      M::TABLE_NAME,
+      "describe_schema",
+      self.inner.describe_schema(),
+    )
+    .await
+  }
+
+  async fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>> {
+    timed(
+      M::TABLE_NAME,
+      "find_by_index_range",
+      self.inner.find_by_index_range(selector, lower, upper),
+    )
+    .await
+  }
+
+  async fn claim_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+    limit: u32,
+    claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+  ) -> DatabaseResult<Vec<M>> {
+    timed(
+      M::TABLE_NAME,
+      "claim_by_index_range",
+      self
+        .inner
+        .claim_by_index_range(selector, lower, upper, limit, claim),
+    )
+    .await
+  }
+
+  async fn count_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<u64> {
+    timed(
+      M::TABLE_NAME,
+      "count_by_index",
+      self.inner.count_by_index(selector, key),
+    )
+    .await
+  }
+
+  async fn increment(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64> {
+    timed(
+      M::TABLE_NAME,
+      "increment",
+      self.inner.increment(id, field_path, delta),
+    )
+    .await
+  }
+
+  async fn exists(&self, id: RecordId<M>) -> DatabaseResult<bool> {
+    timed(M::TABLE_NAME, "exists", self.inner.exists(id)).await
+  }
+
+  async fn exists_by_unique_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<bool> {
+    timed(
+      M::TABLE_NAME,
+      "exists_by_unique_index",
+      self.inner.exists_by_unique_index(selector, key),
+    )
+    .await
+  }
+}
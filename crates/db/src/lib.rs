@@ -1,22 +1,60 @@
 //! Provides a model database interface and implementers.
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod config;
+mod instrument;
+mod limit;
 #[cfg(test)]
 mod tests;
 
 use core::fmt;
-use std::sync::Arc;
+use std::{collections::HashMap, ops::Bound, sync::Arc};
 
-pub use db_core::DatabaseError;
+use chrono::{DateTime, Utc};
+pub use clock::{Clock, Latency, LatencyProfile, ManualClock, SystemClock};
+pub use db_core::{
+  ChangeEvent, ColumnDescription, DatabaseError, FieldCipher, IndexPipeline,
+  IndexTableDescription, IndexTransform, Page, SchemaDescription,
+  TableDescription, decrypt_fields, encrypt_fields,
+};
 use db_core::{DatabaseLike, DatabaseResult};
-use db_impl_mock::MockDatabase;
-pub use db_impl_postgres::PgPool;
-use db_impl_postgres::PostgresDatabase;
-use model::{IndexValue, Model, RecordId};
+pub use db_impl_mock::{MockDatabase, MockOperation};
+use db_impl_postgres::PgPoolOptions;
+#[cfg(feature = "raw-sql")]
+pub use db_impl_postgres::RawBind;
+#[cfg(feature = "cdc")]
+pub use db_impl_postgres::{CdcEvent, CdcOptions, CdcPosition, ChangeStream};
+#[cfg(feature = "read-your-writes")]
+pub use db_impl_postgres::{ConsistencyToken, wait_for_replica};
+pub use db_impl_postgres::{
+  Invalidation, InvalidationStream, Lsn, PgPool, PgTransactionScope,
+  PostgresConnectOptions, PostgresDatabase, PostgresSslMode, SLOW_QUERY_TABLE,
+  SlowQueryLog, TableNamespace,
+};
+use miette::{Context, IntoDiagnostic};
+pub use model::{IndexKey, IndexKind};
+use model::{IndexValue, Model, Projection, RecordId};
+
+use self::limit::LimitedDatabase;
+pub use self::{
+  config::{
+    DbBackendConfig, DbConfig, DbConfigError, PoolSettings, SchemaInitPolicy,
+  },
+  instrument::InstrumentedDatabase,
+  limit::{DatabaseLimiter, DatabaseLimits, Lane},
+};
+
+/// How many times [`Database::seed_fake`] generates a model before giving up
+/// on unique index collisions.
+#[cfg(feature = "fake-data")]
+pub const SEED_FAKE_ATTEMPTS: u32 = 10;
 
 /// A domain model database.
 #[derive(Clone)]
 pub struct Database<M> {
-  inner: Arc<dyn DatabaseLike<M>>,
+  inner:      Arc<dyn DatabaseLike<M>>,
+  unsafe_ops: bool,
 }
 
 impl<M> fmt::Debug for Database<M> {
@@ -32,14 +70,27 @@ impl<M: Model> Database<M> {
   #[must_use]
   pub fn new_mock() -> Self {
     Self {
-      inner: Arc::new(MockDatabase::new()),
+      inner:      Arc::new(MockDatabase::new()),
+      unsafe_ops: false,
     }
   }
 
   /// Create a new database backed by a `PostgreSQL` store.
   pub async fn new_postgres(url: &str) -> miette::Result<Self> {
     Ok(Self {
-      inner: Arc::new(PostgresDatabase::new(url).await?),
+      inner:      Arc::new(PostgresDatabase::new(url).await?),
+      unsafe_ops: false,
+    })
+  }
+
+  /// Create a new database backed by a `PostgreSQL` store with the given
+  /// connection options.
+  pub async fn new_postgres_with_options(
+    options: &PostgresConnectOptions,
+  ) -> miette::Result<Self> {
+    Ok(Self {
+      inner:      Arc::new(PostgresDatabase::new_with_options(options).await?),
+      unsafe_ops: false,
     })
   }
 
@@ -47,14 +98,110 @@ impl<M: Model> Database<M> {
   #[must_use]
   pub fn new_postgres_from_pool(pool: PgPool) -> Self {
     Self {
-      inner: Arc::new(PostgresDatabase::new_from_pool(pool)),
+      inner:      Arc::new(PostgresDatabase::new_from_pool(pool)),
+      unsafe_ops: false,
+    }
+  }
+
+  /// Create a new database backed by the given store, for stores configured
+  /// with their builder methods, e.g.
+  /// [`MockDatabase::with_index_pipeline`] or
+  /// [`PostgresDatabase::with_field_cipher`].
+  #[must_use]
+  pub fn from_backend(backend: impl DatabaseLike<M> + 'static) -> Self {
+    Self {
+      inner:      Arc::new(backend),
+      unsafe_ops: false,
+    }
+  }
+
+  /// Create a new database from a [`DbConfig`], initializing the schema if
+  /// the config's [`SchemaInitPolicy`] requires it.
+  pub async fn from_config(config: DbConfig) -> miette::Result<Self> {
+    config.validate()?;
+
+    let namespace = config.backend.table_namespace()?;
+    let db = match config.backend {
+      DbBackendConfig::Postgres { url, pool, .. } => {
+        let mut options = PgPoolOptions::new();
+        if let Some(max_connections) = pool.max_connections {
+          options = options.max_connections(max_connections);
+        }
+        if let Some(min_connections) = pool.min_connections {
+          options = options.min_connections(min_connections);
+        }
+        if let Some(acquire_timeout) = pool.acquire_timeout() {
+          options = options.acquire_timeout(acquire_timeout);
+        }
+        if let Some(idle_timeout) = pool.idle_timeout() {
+          options = options.idle_timeout(idle_timeout);
+        }
+
+        let pool = options
+          .connect(&url)
+          .await
+          .into_diagnostic()
+          .context("failed to connect to database")?;
+        Self {
+          inner:      Arc::new(
+            PostgresDatabase::new_from_pool(pool).with_namespace(namespace),
+          ),
+          unsafe_ops: false,
+        }
+      }
+      DbBackendConfig::Mock => Self::new_mock(),
+    };
+
+    if config.schema_init == SchemaInitPolicy::Initialize {
+      db.initialize_schema()
+        .await
+        .context("failed to initialize schema")?;
+    }
+
+    Ok(db)
+  }
+
+  /// Wrap this database so that its operations run in `lane` of `limiter`,
+  /// waiting for a slot when the limiter is saturated.
+  ///
+  /// Attach the same limiter to clones of a database in different lanes so
+  /// that, e.g., bulk exports can't take every pooled connection from
+  /// request handlers.
+  #[must_use]
+  pub fn with_limiter(self, limiter: DatabaseLimiter, lane: Lane) -> Self {
+    Self {
+      inner:      Arc::new(LimitedDatabase::new(self.inner, limiter, lane)),
+      unsafe_ops: self.unsafe_ops,
     }
   }
 
+  /// Wrap this database so that the duration and outcome of each operation
+  /// are reported as `tracing` events. See [`InstrumentedDatabase`].
+  #[must_use]
+  pub fn with_instrumentation(self) -> Self {
+    Self {
+      inner:      Arc::new(InstrumentedDatabase::new(self.inner)),
+      unsafe_ops: self.unsafe_ops,
+    }
+  }
+
+  /// Allow operations which destroy data wholesale, such as
+  /// [`truncate`](Self::truncate). They're refused otherwise, so that test
+  /// cleanup can't wipe a production database by accident.
+  #[must_use]
+  pub const fn with_unsafe_ops(mut self) -> Self {
+    self.unsafe_ops = true;
+    self
+  }
+
   /// Initialize the storage schema for this model.
   pub async fn initialize_schema(&self) -> DatabaseResult<()> {
     self.inner.initialize_schema().await
   }
+  /// Recompute every index entry from the stored models.
+  pub async fn rebuild_indices(&self) -> DatabaseResult<()> {
+    self.inner.rebuild_indices().await
+  }
   /// Insert a new model into storage.
   pub async fn insert(&self, model: &M) -> DatabaseResult<()> {
     self.inner.insert(model).await
@@ -63,12 +210,52 @@ impl<M: Model> Database<M> {
   pub async fn update(&self, model: &M) -> DatabaseResult<()> {
     self.inner.update(model).await
   }
+  /// Update an existing model in storage, unless it was written after
+  /// `expected_updated_at`, returning [`DatabaseError::Conflict`] if it was.
+  pub async fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()> {
+    self
+      .inner
+      .update_if_unchanged(model, expected_updated_at)
+      .await
+  }
   /// Insert a model if it doesn't exist, or update it if it does.
   ///
   /// Returns `true` if inserted, `false` if updated.
   pub async fn upsert(&self, model: &M) -> DatabaseResult<bool> {
     self.inner.upsert(model).await
   }
+  /// Insert a new model into storage, returning the model as stored.
+  pub async fn insert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    self.inner.insert_and_return(model).await
+  }
+  /// Insert a model if it doesn't exist, or update it if it does, returning
+  /// the model as stored.
+  pub async fn upsert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    self.inner.upsert_and_return(model).await
+  }
+  /// Atomically read, modify and write the model with the given ID. `update`
+  /// receives the current model, if any, and returns the model to store.
+  pub async fn upsert_with(
+    &self,
+    id: RecordId<M>,
+    update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+  ) -> DatabaseResult<M> {
+    self.inner.upsert_with(id, update).await
+  }
+  /// Atomically add `delta` to the integer field at `field_path` of the
+  /// model with the given ID, returning the new value.
+  pub async fn increment(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64> {
+    self.inner.increment(id, field_path, delta).await
+  }
   /// Delete a model from storage by ID.
   pub async fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
     self.inner.delete(id).await
@@ -77,6 +264,13 @@ impl<M: Model> Database<M> {
   pub async fn delete_and_return(&self, id: RecordId<M>) -> DatabaseResult<M> {
     self.inner.delete_and_return(id).await
   }
+  /// When the model with the given ID was last inserted or updated.
+  pub async fn updated_at(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<DateTime<Utc>>> {
+    self.inner.updated_at(id).await
+  }
   /// Retrieve a model by its ID.
   pub async fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
     self.inner.get(id).await
@@ -92,6 +286,58 @@ impl<M: Model> Database<M> {
   ) -> DatabaseResult<Vec<Option<M>>> {
     self.inner.get_many(ids).await
   }
+  /// Retrieve the serialized form of a model by its ID, without
+  /// deserializing it.
+  pub async fn get_raw(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    self.inner.get_raw(id).await
+  }
+  /// Retrieve the field at `field_path` of the serialized form of a model by
+  /// its ID, or `None` if the model or the field is missing.
+  pub async fn get_field(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    self.inner.get_field(id, field_path).await
+  }
+  /// Retrieve a [`Projection`] of a model by its ID, fetching only the
+  /// fields it needs.
+  pub async fn get_as<P: Projection<M>>(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<P>> {
+    self
+      .inner
+      .get_projected(id, P::FIELDS)
+      .await?
+      .map(|data| Self::deserialize_projection(Some(id), &data))
+      .transpose()
+  }
+  /// Fetch the records of this database referenced by a batch of other
+  /// models, keyed by ID, with a single [`get_many`](Self::get_many) call
+  /// rather than one fetch per model.
+  ///
+  /// `foreign_ids` extracts the referenced IDs from each model, e.g.
+  /// `|post| Some(post.author)`. Referenced records that don't exist are
+  /// left out of the map.
+  pub async fn join<T, I>(
+    &self,
+    models: &[T],
+    foreign_ids: impl Fn(&T) -> I,
+  ) -> DatabaseResult<HashMap<RecordId<M>, M>>
+  where
+    I: IntoIterator<Item = RecordId<M>>,
+  {
+    let mut ids: Vec<_> = models.iter().flat_map(foreign_ids).collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let related = self.inner.get_many(&ids).await?;
+    Ok(related.into_iter().flatten().map(|m| (m.id(), m)).collect())
+  }
   /// Find a single model by a unique index.
   pub async fn find_by_unique_index(
     &self,
@@ -127,16 +373,121 @@ impl<M: Model> Database<M> {
   ) -> DatabaseResult<Option<M>> {
     self.inner.find_one_by_index(selector, key).await
   }
+  /// Find the serialized forms of all models matching a non-unique index,
+  /// without deserializing them.
+  pub async fn find_by_index_raw(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    self.inner.find_by_index_raw(selector, key).await
+  }
+
+  /// Find all models whose index key falls within the given bounds, ordered
+  /// by key ascending.
+  pub async fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>> {
+    self.inner.find_by_index_range(selector, lower, upper).await
+  }
+  /// Atomically claim up to `limit` models whose index key falls within the
+  /// given bounds, in key order, applying `claim` to each and saving it.
+  /// Concurrent claimers never receive the same model.
+  pub async fn claim_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+    limit: u32,
+    claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+  ) -> DatabaseResult<Vec<M>> {
+    self
+      .inner
+      .claim_by_index_range(selector, lower, upper, limit, claim)
+      .await
+  }
   /// List all models with pagination.
   pub async fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
     self.inner.list(limit, offset).await
   }
+  /// List up to `limit` models ordered by ID, starting after the model with
+  /// ID `after`, or from the first if `None`.
+  ///
+  /// Paging with the last ID of each page never skips or repeats a record
+  /// that exists throughout, unlike paging [`list`](Self::list) by offset
+  /// while records are updated.
+  pub async fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    self.inner.list_after(after, limit).await
+  }
+  /// Search models by the text of their search fields, returning at most
+  /// `limit` results ordered by relevance where supported.
+  pub async fn search(
+    &self,
+    query: &str,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    self.inner.search(query, limit).await
+  }
+  /// List models with pagination, along with the total record count.
+  pub async fn list_page(
+    &self,
+    limit: u32,
+    offset: u32,
+  ) -> DatabaseResult<Page<M>> {
+    self.inner.list_page(limit, offset).await
+  }
+  /// List [`Projection`]s of models with pagination, fetching only the
+  /// fields they need.
+  pub async fn list_as<P: Projection<M>>(
+    &self,
+    limit: u32,
+    offset: u32,
+  ) -> DatabaseResult<Vec<P>> {
+    self
+      .inner
+      .list_projected(limit, offset, P::FIELDS)
+      .await?
+      .into_iter()
+      .map(|data| Self::deserialize_projection(None, &data))
+      .collect()
+  }
   /// List all models without pagination.
   pub async fn list_all(&self) -> DatabaseResult<Vec<M>> {
     self.inner.list_all().await
   }
   /// Count the total number of records in storage.
   pub async fn count(&self) -> DatabaseResult<u64> { self.inner.count().await }
+  /// Delete every record and index entry, e.g. to clean up between tests.
+  ///
+  /// Returns [`DatabaseError::UnsafeOpsDisabled`] unless unsafe operations
+  /// were allowed with [`with_unsafe_ops`](Self::with_unsafe_ops).
+  pub async fn truncate(&self) -> DatabaseResult<()> {
+    if !self.unsafe_ops {
+      return Err(DatabaseError::UnsafeOpsDisabled("truncate".to_owned()));
+    }
+    self.inner.truncate().await
+  }
+  /// Return up to `n` records chosen at random.
+  pub async fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+    self.inner.sample(n).await
+  }
+  /// Estimate the total number of records in storage. Cheap on large tables,
+  /// but possibly stale; see [`DatabaseLike::estimate_count`].
+  pub async fn estimate_count(&self) -> DatabaseResult<u64> {
+    self.inner.estimate_count().await
+  }
+  /// Describe the tables storing this model as they are found in the
+  /// database, e.g. for admin UIs or to debug schema drift.
+  pub async fn describe_schema(&self) -> DatabaseResult<SchemaDescription> {
+    self.inner.describe_schema().await
+  }
   /// Count records matching a non-unique index.
   pub async fn count_by_index(
     &self,
@@ -157,4 +508,50 @@ impl<M: Model> Database<M> {
   ) -> DatabaseResult<bool> {
     self.inner.exists_by_unique_index(selector, key).await
   }
+  /// Insert `n` fake models generated by `#[model(fake)]`, returning them.
+  ///
+  /// A model colliding with a unique index, e.g. a taken email, is
+  /// regenerated, up to [`SEED_FAKE_ATTEMPTS`] times.
+  #[cfg(feature = "fake-data")]
+  pub async fn seed_fake(&self, n: usize) -> DatabaseResult<Vec<M>>
+  where
+    M: model::fake::Dummy<model::fake::Faker>,
+  {
+    use model::fake::{Fake, Faker};
+
+    let mut seeded = Vec::with_capacity(n);
+    for _ in 0..n {
+      let mut attempts = 1;
+      loop {
+        let model: M = Faker.fake();
+        match self.inner.insert(&model).await {
+          Ok(()) => {
+            seeded.push(model);
+            break;
+          }
+          Err(DatabaseError::UniqueViolation { .. })
+            if attempts < SEED_FAKE_ATTEMPTS =>
+          {
+            attempts += 1;
+          }
+          Err(e) => return Err(e),
+        }
+      }
+    }
+    Ok(seeded)
+  }
+  /// Deserialize a [`Projection`] from the projected fields of a model.
+  fn deserialize_projection<P: Projection<M>>(
+    id: Option<RecordId<M>>,
+    data: &serde_json::Value,
+  ) -> DatabaseResult<P> {
+    <P as serde::Deserialize>::deserialize(data).map_err(|e| {
+      DatabaseError::deserialization(
+        M::TABLE_NAME,
+        id.map(|id| id.to_string()),
+        &data.to_string(),
+        miette::Report::from_err(e),
+      )
+    })
+  }
 }
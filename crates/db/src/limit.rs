@@ -0,0 +1,418 @@
+//! Concurrency limits and priority lanes for database operations.
+
+use std::{fmt, ops::Bound, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use db_core::{DatabaseLike, DatabaseResult, Page, SchemaDescription};
+use model::{IndexValue, Model, RecordId};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// The priority lane an operation runs in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Lane {
+  /// Latency-sensitive operations, e.g. serving requests. May use every
+  /// slot.
+  #[default]
+  Interactive,
+  /// Bulk operations, e.g. exports and backfills. Limited to
+  /// [`max_batch`](DatabaseLimits::max_batch) slots.
+  Batch,
+}
+
+/// Limits on the database operations in flight at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DatabaseLimits {
+  /// The maximum number of operations in flight at once, across lanes.
+  pub max_concurrent: usize,
+  /// The maximum number of [`Batch`](Lane::Batch) operations in flight at
+  /// once. The remaining slots are reserved for the interactive lane.
+  pub max_batch:      usize,
+}
+
+impl DatabaseLimits {
+  /// Allow `max_concurrent` operations in flight at once, in either lane.
+  #[must_use]
+  pub const fn new(max_concurrent: usize) -> Self {
+    Self {
+      max_concurrent,
+      max_batch: max_concurrent,
+    }
+  }
+
+  /// Allow at most `max_batch` batch operations in flight at once.
+  #[must_use]
+  pub const fn with_max_batch(mut self, max_batch: usize) -> Self {
+    self.max_batch = max_batch;
+    self
+  }
+}
+
+/// Enforces [`DatabaseLimits`] across every [`Database`](crate::Database)
+/// it's attached to with
+/// [`with_limiter`](crate::Database::with_limiter).
+///
+/// Clones share the same limits, so a limiter sized to the connection pool
+/// can be shared by the databases of every model using the pool.
+#[derive(Clone)]
+pub struct DatabaseLimiter {
+  all:   Arc<Semaphore>,
+  batch: Arc<Semaphore>,
+}
+
+impl fmt::Debug for DatabaseLimiter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("DatabaseLimiter")
+      .field("available", &self.all.available_permits())
+      .field("batch_available", &self.batch.available_permits())
+      .finish()
+  }
+}
+
+impl DatabaseLimiter {
+  /// Create a limiter enforcing `limits`. Each lane gets at least one slot.
+  #[must_use]
+  pub fn new(limits: DatabaseLimits) -> Self {
+    let max_concurrent = limits.max_concurrent.max(1);
+    Self {
+      all:   Arc::new(Semaphore::new(max_concurrent)),
+      batch: Arc::new(Semaphore::new(
+        limits.max_batch.clamp(1, max_concurrent),
+      )),
+    }
+  }
+
+  /// Wait for a slot in `lane`.
+  ///
+  /// Batch operations take a batch slot before a shared one, so they never
+  /// hold more shared slots than the batch limit while waiting.
+  pub(crate) async fn acquire(
+    &self,
+    lane: Lane,
+  ) -> (Option<SemaphorePermit<'_>>, SemaphorePermit<'_>) {
+    // the semaphores are never closed
+    let batch = match lane {
+      Lane::Interactive => None,
+      Lane::Batch => Some(self.batch.acquire().await.unwrap()),
+    };
+    (batch, self.all.acquire().await.unwrap())
+  }
+}
+
+/// A [`DatabaseLike`] decorator which runs every operation in a slot of a
+/// [`DatabaseLimiter`].
+pub(crate) struct LimitedDatabase<M> {
+  inner:   Arc<dyn DatabaseLike<M>>,
+  limiter: DatabaseLimiter,
+  lane:    Lane,
+}
+
+impl<M> LimitedDatabase<M> {
+  /// Wraps `inner`, running its operations in `lane` of `limiter`.
+  pub(crate) const fn new(
+    inner: Arc<dyn DatabaseLike<M>>,
+    limiter: DatabaseLimiter,
+    lane: Lane,
+  ) -> Self {
+    Self {
+      inner,
+      limiter,
+      lane,
+    }
+  }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl<M: Model> DatabaseLike<M> for LimitedDatabase<M> {
+  async fn initialize_schema(&self) -> DatabaseResult<()> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.initialize_schema().await
+  }
+
+  async fn rebuild_indices(&self) -> DatabaseResult<()> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.rebuild_indices().await
+  }
+
+  async fn insert(&self, model: &M) -> DatabaseResult<()> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.insert(model).await
+  }
+
+  async fn update(&self, model: &M) -> DatabaseResult<()> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.update(model).await
+  }
+
+  async fn update_if_unchanged(
+    &self,
+    model: &M,
+    expected_updated_at: DateTime<Utc>,
+  ) -> DatabaseResult<()> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self
+      .inner
+      .update_if_unchanged(model, expected_updated_at)
+      .await
+  }
+
+  async fn upsert(&self, model: &M) -> DatabaseResult<bool> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.upsert(model).await
+  }
+
+  async fn insert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.insert_and_return(model).await
+  }
+
+  async fn upsert_and_return(&self, model: &M) -> DatabaseResult<M> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.upsert_and_return(model).await
+  }
+
+  async fn upsert_with(
+    &self,
+    id: RecordId<M>,
+    update: &(dyn Fn(Option<M>) -> M + Send + Sync),
+  ) -> DatabaseResult<M> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.upsert_with(id, update).await
+  }
+
+  async fn delete(&self, id: RecordId<M>) -> DatabaseResult<()> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.delete(id).await
+  }
+
+  async fn delete_and_return(&self, id: RecordId<M>) -> DatabaseResult<M> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.delete_and_return(id).await
+  }
+
+  async fn updated_at(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<DateTime<Utc>>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.updated_at(id).await
+  }
+
+  async fn get(&self, id: RecordId<M>) -> DatabaseResult<Option<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.get(id).await
+  }
+
+  async fn get_or_error(&self, id: RecordId<M>) -> DatabaseResult<M> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.get_or_error(id).await
+  }
+
+  async fn get_many(
+    &self,
+    ids: &[RecordId<M>],
+  ) -> DatabaseResult<Vec<Option<M>>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.get_many(ids).await
+  }
+
+  async fn get_raw(
+    &self,
+    id: RecordId<M>,
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.get_raw(id).await
+  }
+
+  async fn get_field(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.get_field(id, field_path).await
+  }
+
+  async fn get_projected(
+    &self,
+    id: RecordId<M>,
+    fields: &[&str],
+  ) -> DatabaseResult<Option<serde_json::Value>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.get_projected(id, fields).await
+  }
+
+  async fn find_by_unique_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Option<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.find_by_unique_index(selector, key).await
+  }
+
+  async fn find_by_unique_index_or_error(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<M> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self
+      .inner
+      .find_by_unique_index_or_error(selector, key)
+      .await
+  }
+
+  async fn find_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.find_by_index(selector, key).await
+  }
+
+  async fn find_one_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Option<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.find_one_by_index(selector, key).await
+  }
+
+  async fn find_by_index_raw(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.find_by_index_raw(selector, key).await
+  }
+
+  async fn list(&self, limit: u32, offset: u32) -> DatabaseResult<Vec<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.list(limit, offset).await
+  }
+
+  async fn list_after(
+    &self,
+    after: Option<RecordId<M>>,
+    limit: u32,
+  ) -> DatabaseResult<Vec<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.list_after(after, limit).await
+  }
+
+  async fn search(&self, query: &str, limit: u32) -> DatabaseResult<Vec<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.search(query, limit).await
+  }
+
+  async fn list_page(
+    &self,
+    limit: u32,
+    offset: u32,
+  ) -> DatabaseResult<Page<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.list_page(limit, offset).await
+  }
+
+  async fn list_projected(
+    &self,
+    limit: u32,
+    offset: u32,
+    fields: &[&str],
+  ) -> DatabaseResult<Vec<serde_json::Value>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.list_projected(limit, offset, fields).await
+  }
+
+  async fn list_all(&self) -> DatabaseResult<Vec<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.list_all().await
+  }
+
+  async fn count(&self) -> DatabaseResult<u64> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.count().await
+  }
+
+  async fn truncate(&self) -> DatabaseResult<()> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.truncate().await
+  }
+
+  async fn sample(&self, n: u32) -> DatabaseResult<Vec<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.sample(n).await
+  }
+
+  async fn estimate_count(&self) -> DatabaseResult<u64> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.estimate_count().await
+  }
+
+  async fn describe_schema(&self) -> DatabaseResult<SchemaDescription> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.describe_schema().await
+  }
+
+  async fn find_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+  ) -> DatabaseResult<Vec<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.find_by_index_range(selector, lower, upper).await
+  }
+
+  async fn claim_by_index_range(
+    &self,
+    selector: M::IndexSelector,
+    lower: Bound<&IndexValue>,
+    upper: Bound<&IndexValue>,
+    limit: u32,
+    claim: &(dyn for<'a> Fn(&'a mut M) + Send + Sync),
+  ) -> DatabaseResult<Vec<M>> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self
+      .inner
+      .claim_by_index_range(selector, lower, upper, limit, claim)
+      .await
+  }
+
+  async fn count_by_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<u64> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.count_by_index(selector, key).await
+  }
+
+  async fn increment(
+    &self,
+    id: RecordId<M>,
+    field_path: &[&str],
+    delta: i64,
+  ) -> DatabaseResult<i64> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.increment(id, field_path, delta).await
+  }
+
+  async fn exists(&self, id: RecordId<M>) -> DatabaseResult<bool> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.exists(id).await
+  }
+
+  async fn exists_by_unique_index(
+    &self,
+    selector: M::IndexSelector,
+    key: &IndexValue,
+  ) -> DatabaseResult<bool> {
+    let _permit = self.limiter.acquire(self.lane).await;
+    self.inner.exists_by_unique_index(selector, key).await
+  }
+}
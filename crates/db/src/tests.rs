@@ -1,4 +1,8 @@
-use model::{IndexValue, Model, RecordId};
+use std::{ops::Bound, sync::Arc, time::Duration};
+
+use model::{
+  ColumnDefinition, ColumnKind, IndexValue, Model, Projection, RecordId,
+};
 use serde::{Deserialize, Serialize};
 
 use super::*;
@@ -26,6 +30,12 @@ struct Unit {
   index(name = "name", extract =
     |m| vec![IndexValue::new_single(&m.name)]
   ),
+  index(name = "age", kind = i64, extract =
+    |m| vec![IndexValue::new_i64(i64::from(m.age))]
+  ),
+  search(fields = [name, email]),
+  label = "{name} <{email}>",
+  builder,
 )]
 struct User {
   #[model(id)]
@@ -36,6 +46,36 @@ struct User {
   age:      u32,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "articles",
+  index(name = "tags", kind = multi_value, extract =
+    |m| m.tags.iter().map(IndexValue::new_single).collect()
+  ),
+  index(name = "aliases", unique, kind = multi_value, extract =
+    |m| m.aliases.iter().map(IndexValue::new_single).collect()
+  ),
+  index(name = "external_id", unique, extract =
+    |m| m.external_id.iter().map(IndexValue::new_single).collect()
+  ),
+)]
+struct Article {
+  #[model(id)]
+  id:          RecordId<Article>,
+  tags:        Vec<String>,
+  aliases:     Vec<String>,
+  external_id: Option<String>,
+}
+
+fn create_article(id: u128, tags: &[&str], aliases: &[&str]) -> Article {
+  Article {
+    id:          RecordId::from_ulid_u128(id),
+    tags:        tags.iter().map(ToString::to_string).collect(),
+    aliases:     aliases.iter().map(ToString::to_string).collect(),
+    external_id: None,
+  }
+}
+
 // Helper to create test users
 fn create_user(id: u128, email: &str, name: &str, age: u32) -> User {
   User {
@@ -181,6 +221,88 @@ async fn test_upsert_update_path() {
   assert_eq!(retrieved.name, "Alice Updated");
 }
 
+#[tokio::test]
+async fn test_upsert_with_is_atomic() {
+  let db: Arc<dyn DatabaseLike<User>> = Arc::new(MockDatabase::new());
+  let id = RecordId::from_ulid_u128(1);
+
+  // concurrent increments must not lose updates
+  let mut handles = vec![];
+  for _ in 0..10 {
+    let db = db.clone();
+    handles.push(tokio::spawn(async move {
+      db.upsert_with(id, &|user: Option<User>| match user {
+        Some(user) => User {
+          age: user.age + 1,
+          ..user
+        },
+        None => create_user(1, "counter@example.com", "Counter", 1),
+      })
+      .await
+      .unwrap()
+    }));
+  }
+  for handle in handles {
+    handle.await.unwrap();
+  }
+
+  let stored = db.get(id).await.unwrap().unwrap();
+  assert_eq!(stored.age, 10);
+  // indices are kept up to date
+  let found = db
+    .find_by_index(UserIndexSelector::Age, &IndexValue::new_i64(10))
+    .await
+    .unwrap();
+  assert_eq!(found, vec![stored]);
+}
+
+#[tokio::test]
+async fn test_increment_is_atomic() {
+  let db: Arc<dyn DatabaseLike<User>> = Arc::new(MockDatabase::new());
+  let user = create_user(1, "alice@example.com", "Alice", 30);
+  db.insert(&user).await.unwrap();
+  let id = user.id;
+
+  let mut handles = vec![];
+  for _ in 0..10 {
+    let db = db.clone();
+    handles.push(tokio::spawn(async move {
+      db.increment(id, &["age"], 2).await.unwrap()
+    }));
+  }
+  for handle in handles {
+    handle.await.unwrap();
+  }
+
+  assert_eq!(db.increment(id, &["age"], -5).await.unwrap(), 45);
+  let found = db
+    .find_by_index(UserIndexSelector::Age, &IndexValue::new_i64(45))
+    .await
+    .unwrap();
+  assert_eq!(found.len(), 1);
+}
+
+#[tokio::test]
+async fn test_increment_errors() {
+  let db = MockDatabase::<User>::new();
+  let user = create_user(1, "alice@example.com", "Alice", 30);
+  db.insert(&user).unwrap();
+
+  let missing = db.increment(RecordId::from_ulid_u128(2), &["age"], 1).await;
+  assert!(matches!(missing, Err(DatabaseError::NotFound(_))));
+
+  let not_integer = db.increment(user.id, &["name"], 1).await;
+  assert!(matches!(not_integer, Err(DatabaseError::Other(_))));
+
+  let bad_path = db.increment(user.id, &["nested", "count"], 1).await;
+  assert!(matches!(bad_path, Err(DatabaseError::Other(_))));
+
+  // the result must still deserialize as the model
+  let negative = db.increment(user.id, &["age"], -31).await;
+  assert!(matches!(negative, Err(DatabaseError::Serialization(_))));
+  assert_eq!(db.get(user.id).unwrap().unwrap().age, 30);
+}
+
 // --- Batch Operations ---
 
 #[tokio::test]
@@ -217,6 +339,36 @@ async fn test_get_many_empty() {
   assert_eq!(results.len(), 0);
 }
 
+#[tokio::test]
+async fn test_join() {
+  let unit_db = Database::<Unit>::new_mock();
+  let unit1 = Unit {
+    id: RecordId::from_ulid_u128(1),
+  };
+  let unit2 = Unit {
+    id: RecordId::from_ulid_u128(2),
+  };
+  unit_db.insert(&unit1).await.unwrap();
+  unit_db.insert(&unit2).await.unwrap();
+
+  let mut users = vec![
+    create_user(1, "a@example.com", "A", 20),
+    create_user(2, "b@example.com", "B", 30),
+    create_user(3, "c@example.com", "C", 40),
+  ];
+  users[0].relation = unit1.id;
+  users[1].relation = unit1.id;
+  // users[2] references a unit that doesn't exist
+
+  let related = unit_db.join(&users, |u| Some(u.relation)).await.unwrap();
+  assert_eq!(related.len(), 1);
+  assert_eq!(related.get(&unit1.id), Some(&unit1));
+  assert!(!related.contains_key(&unit2.id));
+
+  let related = unit_db.join(&[] as &[User], |u| Some(u.relation)).await;
+  assert!(related.unwrap().is_empty());
+}
+
 #[tokio::test]
 async fn test_list_with_pagination() {
   let db = MockDatabase::<User>::new();
@@ -257,6 +409,77 @@ async fn test_list_all() {
   assert_eq!(all.len(), 5);
 }
 
+#[tokio::test]
+async fn test_list_page() {
+  let db = MockDatabase::<User>::new();
+  for i in 1..=5 {
+    let user = create_user(
+      i,
+      &format!("user{i}@example.com"),
+      &format!("User{i}"),
+      20 + u32::try_from(i).unwrap(),
+    );
+    db.insert(&user).unwrap();
+  }
+
+  let page1 = db.list_page(2, 0).await.unwrap();
+  assert_eq!(page1.items.len(), 2);
+  assert_eq!(page1.total, 5);
+  assert!(page1.has_more);
+
+  let page3 = db.list_page(2, 4).await.unwrap();
+  assert_eq!(page3.items.len(), 1);
+  assert_eq!(page3.total, 5);
+  assert!(!page3.has_more);
+}
+
+#[tokio::test]
+async fn test_list_page_past_end() {
+  let db = MockDatabase::<Unit>::new();
+  db.insert(&Unit {
+    id: RecordId::from_ulid_u128(1),
+  })
+  .unwrap();
+
+  let page = db.list_page(10, 10).await.unwrap();
+  assert!(page.items.is_empty());
+  assert_eq!(page.total, 1);
+  assert!(!page.has_more);
+}
+
+#[tokio::test]
+async fn test_list_after() {
+  let db = Database::<User>::new_mock();
+  for i in [3, 1, 5, 2, 4] {
+    db.insert(&create_user(i, &format!("user{i}@example.com"), "User", 20))
+      .await
+      .unwrap();
+  }
+  let ids = |users: Vec<User>| -> Vec<RecordId<User>> {
+    users.into_iter().map(|u| u.id).collect()
+  };
+
+  // ordered by ID, whatever the order of updates
+  let mut listed = Vec::new();
+  let mut after = None;
+  loop {
+    let page = ids(db.list_after(after, 2).await.unwrap());
+    // updating a listed record doesn't move it into a later page
+    if let Some(first) = page.first() {
+      db.update(&db.get_or_error(*first).await.unwrap())
+        .await
+        .unwrap();
+    }
+    after = page.last().copied();
+    listed.extend(page);
+    if after.is_none() {
+      break;
+    }
+  }
+  let expected: Vec<_> = (1..=5).map(RecordId::from_ulid_u128).collect();
+  assert_eq!(listed, expected);
+}
+
 #[tokio::test]
 async fn test_list_empty_db() {
   let db = MockDatabase::<Unit>::new();
@@ -365,6 +588,332 @@ async fn test_find_one_by_index_not_found() {
   assert_eq!(found, None);
 }
 
+// --- Multi-Value Indices ---
+
+#[tokio::test]
+async fn test_multi_value_index_matches_any_value() {
+  let db = MockDatabase::<Article>::new();
+  let rust = create_article(1, &["rust", "db", "rust"], &[]);
+  let go = create_article(2, &["go", "db"], &[]);
+  db.insert(&rust).unwrap();
+  db.insert(&go).unwrap();
+
+  let tag = |tag: &str| IndexValue::new_single(tag);
+  let found = db.find_by_index(ArticleIndexSelector::Tags, &tag("rust"));
+  assert_eq!(found.unwrap(), vec![rust.clone()]);
+  let count = db
+    .count_by_index(ArticleIndexSelector::Tags, &tag("db"))
+    .await;
+  assert_eq!(count.unwrap(), 2);
+
+  // updates replace every value
+  let retagged = create_article(1, &["zig"], &[]);
+  db.update(&retagged).unwrap();
+  let found = db.find_by_index(ArticleIndexSelector::Tags, &tag("rust"));
+  assert!(found.unwrap().is_empty());
+  let found = db.find_by_index(ArticleIndexSelector::Tags, &tag("zig"));
+  assert_eq!(found.unwrap(), vec![retagged]);
+}
+
+#[tokio::test]
+async fn test_unique_multi_value_index() {
+  let db = MockDatabase::<Article>::new();
+  let first = create_article(1, &[], &["intro", "welcome"]);
+  db.insert(&first).unwrap();
+
+  let found = db
+    .find_by_unique_index(
+      ArticleIndexSelector::Aliases,
+      &IndexValue::new_single("welcome"),
+    )
+    .unwrap();
+  assert_eq!(found, Some(first));
+
+  // a single shared value conflicts
+  let second = create_article(2, &[], &["hello", "intro"]);
+  assert!(matches!(
+    db.insert(&second),
+    Err(DatabaseError::UniqueViolation { .. })
+  ));
+  db.insert(&create_article(3, &[], &["hello"])).unwrap();
+}
+
+// --- Sparse Indices ---
+
+#[tokio::test]
+async fn test_sparse_unique_index_skips_unindexed_records() {
+  let db = MockDatabase::<Article>::new();
+  let external_id = |id: &str| IndexValue::new_single(id);
+
+  // records without a value never conflict with each other
+  db.insert(&create_article(1, &[], &[])).unwrap();
+  db.insert(&create_article(2, &[], &[])).unwrap();
+
+  let mut linked = create_article(3, &[], &[]);
+  linked.external_id = Some("ext-1".to_owned());
+  db.insert(&linked).unwrap();
+  let mut duplicate = create_article(4, &[], &[]);
+  duplicate.external_id = Some("ext-1".to_owned());
+  assert!(matches!(
+    db.insert(&duplicate),
+    Err(DatabaseError::UniqueViolation { .. })
+  ));
+
+  let found = db
+    .find_by_unique_index(
+      ArticleIndexSelector::ExternalId,
+      &external_id("ext-1"),
+    )
+    .unwrap();
+  assert_eq!(found, Some(linked.clone()));
+  let count = db
+    .count_by_index(ArticleIndexSelector::ExternalId, &external_id(""))
+    .await
+    .unwrap();
+  assert_eq!(count, 0);
+
+  // clearing the value removes the record from the index
+  linked.external_id = None;
+  db.update(&linked).unwrap();
+  db.insert(&duplicate).unwrap();
+}
+
+// --- Index Key Encoding ---
+
+#[test]
+fn test_index_value_encoding_round_trips() {
+  let values = [
+    IndexValue::new(["a", "b"]),
+    IndexValue::new(["a\u{1}b"]),
+    IndexValue::new(["a\0", "\u{2}b"]),
+    IndexValue::new(["[\"a\",\"b\"]"]),
+    IndexValue::new(["", ""]),
+  ];
+  for (i, value) in values.iter().enumerate() {
+    let encoded = value.encode();
+    assert!(!encoded.contains('\0'));
+    assert_eq!(IndexValue::decode(&encoded).as_ref(), Some(value));
+    for other in &values[i + 1..] {
+      assert_ne!(encoded, other.encode());
+    }
+  }
+  assert_eq!(IndexValue::decode("a\0b"), None);
+  assert_eq!(IndexValue::decode("a\u{2}"), None);
+}
+
+#[test]
+fn test_index_value_encoding_preserves_order() {
+  // sorted by segments, with prefixes first
+  let values = [
+    IndexValue::new(["a"]),
+    IndexValue::new(["a", ""]),
+    IndexValue::new(["a", "\0"]),
+    IndexValue::new(["a", "b"]),
+    IndexValue::new(["a\0"]),
+    IndexValue::new(["a!"]),
+    IndexValue::new(["a!", "a"]),
+    IndexValue::new(["b"]),
+  ];
+  for pair in values.windows(2) {
+    assert!(pair[0].encode() < pair[1].encode(), "{pair:?}");
+  }
+}
+
+#[tokio::test]
+async fn test_composite_keys_with_delimiters_do_not_collide() {
+  let db = MockDatabase::<User>::new();
+  db.insert(&create_user(1, "a@example.com", "Ann", 30))
+    .unwrap();
+  db.insert(&create_user(2, "b@example.com", "Ann\u{1}30", 30))
+    .unwrap();
+  db.insert(&create_user(3, "c@example.com", "Ann!", 30))
+    .unwrap();
+
+  let found = db
+    .find_by_index(UserIndexSelector::NameAge, &IndexValue::new(["Ann", "30"]))
+    .unwrap();
+  assert_eq!(found.len(), 1);
+  assert_eq!(found[0].id, RecordId::from_ulid_u128(1));
+
+  // every key starting with the name "Ann" sorts between these bounds
+  let lower = IndexValue::new(["Ann"]);
+  let upper = IndexValue::new(["Ann", "\u{10FFFF}"]);
+  let found = db
+    .find_by_index_range(
+      UserIndexSelector::NameAge,
+      Bound::Included(&lower),
+      Bound::Included(&upper),
+    )
+    .unwrap();
+  assert_eq!(found.len(), 1);
+  assert_eq!(found[0].id, RecordId::from_ulid_u128(1));
+}
+
+#[tokio::test]
+async fn test_rebuild_indices_applies_new_pipeline() {
+  // records written before a transform was added aren't found through it
+  let raw = MockDatabase::<User>::new();
+  raw
+    .insert(&create_user(1, "Ann@Example.com", "Ann", 30))
+    .unwrap();
+  let db: Arc<dyn DatabaseLike<User>> =
+    Arc::new(raw.with_index_pipeline(lowercase_pipeline()));
+  let key = IndexValue::new_single("ANN@example.com");
+  let found = db
+    .find_by_unique_index(UserIndexSelector::Email, &key)
+    .await
+    .unwrap();
+  assert!(found.is_none());
+
+  db.rebuild_indices().await.unwrap();
+  let found = db
+    .find_by_unique_index(UserIndexSelector::Email, &key)
+    .await
+    .unwrap();
+  assert_eq!(found.map(|user| user.id), Some(RecordId::from_ulid_u128(1)));
+}
+
+// --- Range Queries ---
+
+#[tokio::test]
+async fn test_find_by_index_range_numeric() {
+  let db = MockDatabase::<User>::new();
+  let user1 = create_user(1, "a@example.com", "A", 5);
+  let user2 = create_user(2, "b@example.com", "B", 30);
+  let user3 = create_user(3, "c@example.com", "C", 100);
+
+  db.insert(&user1).unwrap();
+  db.insert(&user2).unwrap();
+  db.insert(&user3).unwrap();
+
+  // lexicographically "100" < "30" < "5", so this checks numeric ordering
+  let found = db
+    .find_by_index_range(
+      UserIndexSelector::Age,
+      Bound::Included(&IndexValue::new_i64(10)),
+      Bound::Unbounded,
+    )
+    .unwrap();
+  assert_eq!(found, vec![user2.clone(), user3.clone()]);
+
+  let found = db
+    .find_by_index_range(
+      UserIndexSelector::Age,
+      Bound::Excluded(&IndexValue::new_i64(5)),
+      Bound::Excluded(&IndexValue::new_i64(100)),
+    )
+    .unwrap();
+  assert_eq!(found, vec![user2.clone()]);
+
+  let found = db
+    .find_by_index_range(
+      UserIndexSelector::Age,
+      Bound::Unbounded,
+      Bound::Unbounded,
+    )
+    .unwrap();
+  assert_eq!(found, vec![user1, user2, user3]);
+}
+
+#[tokio::test]
+async fn test_find_by_index_range_invalid_value() {
+  let db = MockDatabase::<User>::new();
+
+  let result = db.find_by_index_range(
+    UserIndexSelector::Age,
+    Bound::Included(&IndexValue::new_single("thirty")),
+    Bound::Unbounded,
+  );
+  assert!(matches!(
+    result,
+    Err(DatabaseError::InvalidIndexValue { .. })
+  ));
+}
+
+#[tokio::test]
+async fn test_claim_by_index_range_is_exclusive() {
+  let db: Arc<dyn DatabaseLike<User>> = Arc::new(MockDatabase::new());
+  for i in 0..10 {
+    let user = create_user(i, &format!("u{i}@example.com"), "U", 10);
+    db.insert(&user).await.unwrap();
+  }
+
+  // each claim moves the user out of the range, like a job lease
+  let mut handles = vec![];
+  for _ in 0..4 {
+    let db = db.clone();
+    handles.push(tokio::spawn(async move {
+      db.claim_by_index_range(
+        UserIndexSelector::Age,
+        Bound::Unbounded,
+        Bound::Excluded(&IndexValue::new_i64(50)),
+        3,
+        &|user: &mut User| user.age = 50,
+      )
+      .await
+      .unwrap()
+    }));
+  }
+
+  let mut claimed = vec![];
+  for handle in handles {
+    let batch = handle.await.unwrap();
+    assert!(batch.len() <= 3);
+    claimed.extend(batch.into_iter().map(|user| user.id));
+  }
+  claimed.sort();
+  claimed.dedup();
+  assert_eq!(claimed.len(), 10);
+
+  let stored = db.get(RecordId::from_ulid_u128(0)).await.unwrap().unwrap();
+  assert_eq!(stored.age, 50);
+}
+
+#[tokio::test]
+async fn test_find_by_typed_index_exact() {
+  let db = MockDatabase::<User>::new();
+  let user = create_user(1, "alice@example.com", "Alice", 30);
+  db.insert(&user).unwrap();
+
+  let found = db
+    .find_by_index(UserIndexSelector::Age, &IndexValue::new_i64(30))
+    .unwrap();
+  assert_eq!(found, vec![user]);
+}
+
+// --- Search ---
+
+#[tokio::test]
+async fn test_search_substring() {
+  let db = MockDatabase::<User>::new();
+  let user1 = create_user(1, "alice@example.com", "Alice Smith", 30);
+  let user2 = create_user(2, "bob@example.com", "Bob Smith", 25);
+  let user3 = create_user(3, "carol@example.org", "Carol Jones", 35);
+
+  db.insert(&user1).unwrap();
+  db.insert(&user2).unwrap();
+  db.insert(&user3).unwrap();
+
+  let found = db.search("smith", 10).unwrap();
+  assert_eq!(found.len(), 2);
+  assert!(found.contains(&user1));
+  assert!(found.contains(&user2));
+
+  let found = db.search("SMITH example.com alice", 10).unwrap();
+  assert_eq!(found, vec![user1]);
+
+  let found = db.search("smith", 1).unwrap();
+  assert_eq!(found.len(), 1);
+}
+
+#[tokio::test]
+async fn test_search_not_enabled() {
+  let db = MockDatabase::<Unit>::new();
+  let result = db.search("anything", 10);
+
+  assert!(matches!(result, Err(DatabaseError::SearchNotEnabled(_))));
+}
+
 // --- Counting ---
 
 #[tokio::test]
@@ -384,6 +933,24 @@ async fn test_count() {
   assert_eq!(count, 5);
 }
 
+#[tokio::test]
+async fn test_estimate_count() {
+  let db = MockDatabase::<User>::new();
+  for i in 1..=5 {
+    let user = create_user(
+      i,
+      &format!("user{i}@example.com"),
+      &format!("User{i}"),
+      20 + u32::try_from(i).unwrap(),
+    );
+    db.insert(&user).unwrap();
+  }
+
+  // the mock's estimate is exact
+  let estimate = DatabaseLike::estimate_count(&db).await.unwrap();
+  assert_eq!(estimate, 5);
+}
+
 #[tokio::test]
 async fn test_count_empty() {
   let db = MockDatabase::<Unit>::new();
@@ -551,38 +1118,122 @@ async fn test_find_by_unique_index_or_error_not_found() {
   assert!(matches!(result, Err(DatabaseError::NotFound(_))));
 }
 
-// --- Edge Cases & Complex Scenarios ---
-
-#[tokio::test]
-async fn test_update_changes_index() {
-  let db = MockDatabase::<User>::new();
-  let user = create_user(1, "alice@example.com", "Alice", 30);
-  db.insert(&user).unwrap();
-
-  // Update changes the indexed field
-  let updated = create_user(1, "alice@example.com", "Alicia", 30);
-  db.update(&updated).unwrap();
+// --- Builders ---
 
-  // Old index should not find it
-  let found_old = db
-    .find_by_index(UserIndexSelector::Name, &IndexValue::new_single("Alice"))
+#[test]
+fn test_model_builder() {
+  let user = User::builder()
+    .relation(RecordId::from_ulid_u128(0))
+    .email("builder@example.com")
+    .name("Builder")
+    .age(30_u32)
+    .build()
     .unwrap();
-  assert_eq!(found_old.len(), 0);
+  assert_eq!(user.email, "builder@example.com");
+
+  let builder = UserBuilder::new_with_id(RecordId::from_ulid_u128(7))
+    .relation(RecordId::from_ulid_u128(0))
+    .email("builder@example.com")
+    .name("Builder");
+  assert_eq!(builder.clone().build().unwrap_err(), model::BuildError {
+    model: "User",
+    field: "age",
+  });
+  assert_eq!(
+    builder.age(30_u32).build().unwrap(),
+    create_user(7, "builder@example.com", "Builder", 30)
+  );
+}
 
-  // New index should find it
-  let found_new = db
-    .find_by_index(UserIndexSelector::Name, &IndexValue::new_single("Alicia"))
-    .unwrap();
-  assert_eq!(found_new.len(), 1);
+// --- Index Pipelines ---
+
+fn lowercase_pipeline() -> IndexPipeline {
+  IndexPipeline::new().with_fn(|_, value| {
+    Ok(IndexValue::new(
+      value.segments().iter().map(|s| s.to_lowercase()),
+    ))
+  })
 }
 
 #[tokio::test]
-async fn test_delete_removes_from_indexes() {
-  let db = MockDatabase::<User>::new();
-  let user = create_user(1, "alice@example.com", "Alice", 30);
-  db.insert(&user).unwrap();
-
-  db.delete(user.id).unwrap();
+async fn test_index_pipeline_applies_on_write_and_query() {
+  let db = Database::<User>::from_backend(
+    MockDatabase::new().with_index_pipeline(lowercase_pipeline()),
+  );
+  let user = create_user(1, "Alice@Example.com", "Alice", 30);
+  db.insert(&user).await.unwrap();
+
+  let found = db
+    .find_by_unique_index(
+      UserIndexSelector::Email,
+      &IndexValue::new_single("ALICE@example.COM"),
+    )
+    .await
+    .unwrap();
+  assert_eq!(found, Some(user));
+}
+
+#[tokio::test]
+async fn test_index_pipeline_unique_violation_after_transform() {
+  let db = Database::<User>::from_backend(
+    MockDatabase::new().with_index_pipeline(lowercase_pipeline()),
+  );
+  let user1 = create_user(1, "alice@example.com", "Alice", 30);
+  let user2 = create_user(2, "ALICE@example.com", "Alice Clone", 25);
+
+  db.insert(&user1).await.unwrap();
+  let result = db.insert(&user2).await;
+
+  assert!(matches!(result, Err(DatabaseError::UniqueViolation { .. })));
+}
+
+#[tokio::test]
+async fn test_index_pipeline_error_propagates() {
+  let pipeline = IndexPipeline::new().with_fn(|index, _| {
+    Err(DatabaseError::Other(miette::miette!("rejected by {index}")))
+  });
+  let db = Database::<User>::from_backend(
+    MockDatabase::new().with_index_pipeline(pipeline),
+  );
+  let user = create_user(1, "alice@example.com", "Alice", 30);
+
+  let result = db.insert(&user).await;
+  assert!(matches!(result, Err(DatabaseError::Other(_))));
+  assert!(!db.exists(user.id).await.unwrap());
+}
+
+// --- Edge Cases & Complex Scenarios ---
+
+#[tokio::test]
+async fn test_update_changes_index() {
+  let db = MockDatabase::<User>::new();
+  let user = create_user(1, "alice@example.com", "Alice", 30);
+  db.insert(&user).unwrap();
+
+  // Update changes the indexed field
+  let updated = create_user(1, "alice@example.com", "Alicia", 30);
+  db.update(&updated).unwrap();
+
+  // Old index should not find it
+  let found_old = db
+    .find_by_index(UserIndexSelector::Name, &IndexValue::new_single("Alice"))
+    .unwrap();
+  assert_eq!(found_old.len(), 0);
+
+  // New index should find it
+  let found_new = db
+    .find_by_index(UserIndexSelector::Name, &IndexValue::new_single("Alicia"))
+    .unwrap();
+  assert_eq!(found_new.len(), 1);
+}
+
+#[tokio::test]
+async fn test_delete_removes_from_indexes() {
+  let db = MockDatabase::<User>::new();
+  let user = create_user(1, "alice@example.com", "Alice", 30);
+  db.insert(&user).unwrap();
+
+  db.delete(user.id).unwrap();
 
   let found_by_email = db
     .find_by_unique_index(
@@ -624,3 +1275,1251 @@ async fn test_concurrent_operations() {
   let count = db.count().unwrap();
   assert_eq!(count, 10);
 }
+
+// --- Shared Handles ---
+
+async fn insert_and_count<D: DatabaseLike<User>>(db: &D, user: &User) -> u64 {
+  db.insert(user).await.unwrap();
+  db.count().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_arc_implements_database_like() {
+  let mock = Arc::new(MockDatabase::<User>::new());
+  let shared: Arc<dyn DatabaseLike<User>> = mock.clone();
+
+  let user1 = create_user(1, "one@example.com", "One", 30);
+  let user2 = create_user(2, "two@example.com", "Two", 40);
+  assert_eq!(insert_and_count(&shared, &user1).await, 1);
+  assert_eq!(insert_and_count(&shared.clone(), &user2).await, 2);
+  // the backend's inherent methods aren't shadowed on an `Arc` of it
+  assert_eq!(mock.get(user2.id).unwrap(), Some(user2));
+}
+
+#[tokio::test]
+async fn test_box_and_reference_implement_database_like() {
+  let mock = MockDatabase::<User>::new();
+  let boxed: Box<dyn DatabaseLike<User>> = Box::new(mock.clone());
+
+  let user1 = create_user(1, "one@example.com", "One", 30);
+  let user2 = create_user(2, "two@example.com", "Two", 40);
+  assert_eq!(insert_and_count(&&mock, &user1).await, 1);
+  assert_eq!(insert_and_count(&boxed, &user2).await, 2);
+}
+
+// --- Concurrency ---
+
+#[test]
+fn test_mock_concurrent_unique_inserts() {
+  let db = MockDatabase::<User>::new();
+
+  let results: Vec<_> = std::thread::scope(|scope| {
+    let handles: Vec<_> = (1..=8)
+      .map(|i| {
+        let db = &db;
+        scope.spawn(move || {
+          db.insert(&create_user(i, "race@example.com", "Racer", 30))
+        })
+      })
+      .collect();
+    handles.into_iter().map(|h| h.join().unwrap()).collect()
+  });
+
+  assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+  assert!(
+    results
+      .iter()
+      .filter_map(|r| r.as_ref().err())
+      .all(|e| matches!(e, DatabaseError::UniqueViolation { .. }))
+  );
+  let schema = db.describe_schema();
+  assert!(
+    schema
+      .index_tables
+      .iter()
+      .all(|index| index.table.estimated_rows == Some(1))
+  );
+}
+
+#[test]
+fn test_mock_concurrent_writes_keep_indices_consistent() {
+  let db = MockDatabase::<User>::new();
+  for i in 0..32 {
+    db.insert(&create_user(i, &format!("{i}@example.com"), "User", 20))
+      .unwrap();
+  }
+
+  std::thread::scope(|scope| {
+    for thread in 0..4_u128 {
+      let db = &db;
+      scope.spawn(move || {
+        for i in (thread..32).step_by(4) {
+          if i % 2 == 0 {
+            db.delete(RecordId::from_ulid_u128(i)).unwrap();
+          } else {
+            let email = format!("{i}@example.com");
+            db.update(&create_user(i, &email, "Renamed", 21)).unwrap();
+          }
+        }
+      });
+    }
+  });
+
+  let schema = db.describe_schema();
+  assert_eq!(schema.main_table.estimated_rows, Some(16));
+  assert!(
+    schema
+      .index_tables
+      .iter()
+      .all(|index| index.table.estimated_rows == Some(16))
+  );
+  let key = IndexValue::new_single("Renamed");
+  assert_eq!(
+    db.find_by_index(UserIndexSelector::Name, &key)
+      .unwrap()
+      .len(),
+    16
+  );
+}
+
+#[test]
+fn test_mock_survives_panicking_writer() {
+  #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+  #[model(
+    table = "fragile",
+    index(name = "code", unique, extract = |m| {
+      assert_ne!(m.code, "boom", "extractor failure");
+      vec![IndexValue::new_single(&m.code)]
+    }),
+  )]
+  struct Fragile {
+    #[model(id)]
+    id:   RecordId<Fragile>,
+    code: String,
+  }
+
+  let db = MockDatabase::<Fragile>::new();
+  let fragile = |id, code: &str| Fragile {
+    id:   RecordId::from_ulid_u128(id),
+    code: code.to_owned(),
+  };
+
+  let db_ref = &db;
+  let panicked = std::thread::scope(|scope| {
+    scope
+      .spawn(move || db_ref.insert(&fragile(1, "boom")))
+      .join()
+      .is_err()
+  });
+  assert!(panicked);
+
+  db.insert(&fragile(2, "fine")).unwrap();
+  assert_eq!(db.len(), 1);
+  assert!(db.get(RecordId::from_ulid_u128(1)).unwrap().is_none());
+}
+
+// --- Raw Access ---
+
+#[tokio::test]
+async fn test_raw_access() {
+  let db = Database::<Article>::new_mock();
+  let article = create_article(1, &["rust", "db"], &[]);
+  db.insert(&article).await.unwrap();
+
+  let raw = db.get_raw(article.id).await.unwrap().unwrap();
+  assert_eq!(raw, serde_json::to_value(&article).unwrap());
+  assert_eq!(
+    db.get_field(article.id, &["tags", "1"]).await.unwrap(),
+    Some(serde_json::json!("db"))
+  );
+  assert_eq!(
+    db.get_field(article.id, &["external_id"]).await.unwrap(),
+    Some(serde_json::Value::Null)
+  );
+  assert_eq!(
+    db.get_field(article.id, &["tags", "2"]).await.unwrap(),
+    None
+  );
+  assert_eq!(db.get_field(article.id, &["missing"]).await.unwrap(), None);
+  assert_eq!(db.get_raw(RecordId::new()).await.unwrap(), None);
+
+  let found = db
+    .find_by_index_raw(
+      ArticleIndexSelector::Tags,
+      &IndexValue::new_single("rust"),
+    )
+    .await
+    .unwrap();
+  assert_eq!(found, vec![raw]);
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct UserSummary {
+  id:   RecordId<User>,
+  name: String,
+}
+
+impl Projection<User> for UserSummary {
+  const FIELDS: &'static [&'static str] = &["id", "name"];
+}
+
+#[tokio::test]
+async fn test_projections() {
+  let mock = MockDatabase::<User>::new();
+  let user = create_user(1, "pat@example.com", "Pat", 30);
+  mock.insert(&user).unwrap();
+
+  assert_eq!(
+    DatabaseLike::get_projected(&mock, user.id, UserSummary::FIELDS)
+      .await
+      .unwrap(),
+    Some(serde_json::json!({ "id": user.id, "name": "Pat" }))
+  );
+  let db = Database::<User> {
+    inner:      Arc::new(mock),
+    unsafe_ops: false,
+  };
+  let summary = UserSummary {
+    id:   user.id,
+    name: "Pat".to_owned(),
+  };
+  assert_eq!(
+    db.get_as::<UserSummary>(user.id).await.unwrap(),
+    Some(summary)
+  );
+  assert_eq!(
+    db.get_as::<UserSummary>(RecordId::new()).await.unwrap(),
+    None
+  );
+
+  let summaries = db.list_as::<UserSummary>(10, 0).await.unwrap();
+  assert_eq!(summaries.len(), 1);
+  assert_eq!(summaries[0].name, "Pat");
+}
+
+#[tokio::test]
+async fn test_projection_missing_field() {
+  #[derive(Debug, Deserialize)]
+  struct Misdeclared {
+    #[allow(dead_code)]
+    email: String,
+  }
+  impl Projection<User> for Misdeclared {
+    const FIELDS: &'static [&'static str] = &["name"];
+  }
+
+  let db = Database::<User>::new_mock();
+  let user = create_user(1, "pat@example.com", "Pat", 30);
+  db.insert(&user).await.unwrap();
+
+  let error = db.get_as::<Misdeclared>(user.id).await.unwrap_err();
+  assert_eq!(error.error_code(), "deserialization");
+}
+
+// --- Timestamps ---
+
+#[tokio::test]
+async fn test_mock_timestamps_follow_clock() {
+  let clock = ManualClock::default();
+  let db = MockDatabase::<Unit>::new().with_clock(Arc::new(clock.clone()));
+  let unit = Unit {
+    id: RecordId::from_ulid_u128(1),
+  };
+
+  clock.advance(Duration::from_secs(10));
+  db.insert(&unit).unwrap();
+  clock.advance(Duration::from_secs(5));
+  db.update(&unit).unwrap();
+
+  let timestamps = db.timestamps(unit.id).unwrap();
+  assert_eq!(timestamps.created_at.timestamp(), 10);
+  assert_eq!(timestamps.updated_at.timestamp(), 15);
+
+  db.delete(unit.id).unwrap();
+  assert_eq!(db.timestamps(unit.id), None);
+}
+
+#[tokio::test]
+async fn test_list_orders_by_last_update() {
+  let clock = ManualClock::default();
+  let db = Database::<Unit>::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let units: Vec<_> = (1..=3)
+    .map(|i| Unit {
+      id: RecordId::from_ulid_u128(i),
+    })
+    .collect();
+
+  for unit in &units {
+    clock.advance(Duration::from_secs(1));
+    db.insert(unit).await.unwrap();
+  }
+  clock.advance(Duration::from_secs(1));
+  db.update(&units[0]).await.unwrap();
+
+  let ids: Vec<_> = db
+    .list(10, 0)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|u| u.id)
+    .collect();
+  assert_eq!(ids, [units[0].id, units[2].id, units[1].id]);
+}
+
+#[tokio::test]
+async fn test_update_if_unchanged() {
+  let clock = ManualClock::default();
+  let db = Database::<User>::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let mut user = create_user(1, "a@example.com", "A", 20);
+  db.insert(&user).await.unwrap();
+
+  let read_at = db.updated_at(user.id).await.unwrap().unwrap();
+  clock.advance(Duration::from_secs(1));
+  user.age = 21;
+  db.update_if_unchanged(&user, read_at).await.unwrap();
+
+  // a second writer holding the stale timestamp loses
+  user.age = 22;
+  let err = db.update_if_unchanged(&user, read_at).await.unwrap_err();
+  assert!(matches!(err, DatabaseError::Conflict(_)));
+  assert_eq!(db.get_or_error(user.id).await.unwrap().age, 21);
+
+  let missing = create_user(2, "b@example.com", "B", 30);
+  let err = db.update_if_unchanged(&missing, read_at).await.unwrap_err();
+  assert!(matches!(err, DatabaseError::NotFound(_)));
+  assert_eq!(db.updated_at(missing.id).await.unwrap(), None);
+}
+
+// --- Returning Writes ---
+
+#[tokio::test]
+async fn test_insert_and_upsert_and_return() {
+  let db = Database::<User>::new_mock();
+  let mut user = create_user(1, "a@example.com", "A", 20);
+
+  assert_eq!(db.insert_and_return(&user).await.unwrap(), user);
+  assert!(db.insert_and_return(&user).await.is_err());
+
+  user.age = 21;
+  assert_eq!(db.upsert_and_return(&user).await.unwrap(), user);
+  let other = create_user(2, "b@example.com", "B", 30);
+  assert_eq!(db.upsert_and_return(&other).await.unwrap(), other);
+  assert_eq!(db.count().await.unwrap(), 2);
+}
+
+// --- Schema Description ---
+
+#[tokio::test]
+async fn test_describe_schema() {
+  let db = Database::<Article>::new_mock();
+  db.insert(&create_article(1, &["a", "b"], &["x"]))
+    .await
+    .unwrap();
+  db.insert(&create_article(2, &["a"], &[])).await.unwrap();
+
+  let schema = db.describe_schema().await.unwrap();
+  assert_eq!(schema.main_table.name, "articles");
+  assert!(schema.main_table.exists);
+  assert_eq!(schema.main_table.estimated_rows, Some(2));
+
+  let names: Vec<_> = schema
+    .index_tables
+    .iter()
+    .map(|t| t.index.as_str())
+    .collect();
+  assert_eq!(names, ["tags", "aliases", "external_id"]);
+
+  let tags = &schema.index_tables[0];
+  assert_eq!(tags.kind, IndexKind::MultiValue);
+  assert!(!tags.unique);
+  assert_eq!(tags.table.name, "articles__idx_tags");
+  assert_eq!(tags.table.estimated_rows, Some(3));
+
+  let aliases = &schema.index_tables[1];
+  assert!(aliases.unique && aliases.enforced_unique);
+  assert_eq!(aliases.table.estimated_rows, Some(1));
+
+  let json = serde_json::to_value(&schema).unwrap();
+  assert_eq!(json["index_tables"][0]["kind"], "multi_value");
+}
+
+#[test]
+fn test_promoted_columns() {
+  #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+  #[model(table = "orders")]
+  struct Order {
+    #[model(id)]
+    id:          RecordId<Order>,
+    #[model(column)]
+    #[serde(rename = "customerId")]
+    customer_id: RecordId<User>,
+    #[model(column)]
+    total_cents: Option<u32>,
+    #[model(column = timestamp)]
+    placed_at:   String,
+    #[model(column)]
+    lines:       Vec<String>,
+    note:        String,
+  }
+
+  let column = |name, kind| ColumnDefinition { name, kind };
+  assert_eq!(Order::COLUMNS, [
+    column("customerId", ColumnKind::String),
+    column("total_cents", ColumnKind::I64),
+    column("placed_at", ColumnKind::Timestamp),
+    column("lines", ColumnKind::Json),
+  ]);
+}
+
+// --- Sampling ---
+
+#[tokio::test]
+async fn test_sample() {
+  let db = Database::<Unit>::new_mock();
+  for i in 1..=10 {
+    db.insert(&Unit {
+      id: RecordId::from_ulid_u128(i),
+    })
+    .await
+    .unwrap();
+  }
+
+  let mut sample = db.sample(4).await.unwrap();
+  assert_eq!(sample.len(), 4);
+  sample.sort_by_key(|unit| unit.id);
+  sample.dedup();
+  assert_eq!(sample.len(), 4);
+
+  assert_eq!(db.sample(20).await.unwrap().len(), 10);
+  assert!(db.sample(0).await.unwrap().is_empty());
+}
+
+// --- Limits ---
+
+#[tokio::test]
+async fn test_limiter_reserves_interactive_slots() {
+  let limiter = DatabaseLimiter::new(DatabaseLimits::new(2).with_max_batch(1));
+  let _batch = limiter.acquire(Lane::Batch).await;
+
+  // the batch lane is full, but a slot is still free for interactive work
+  let wait = Duration::from_millis(50);
+  assert!(
+    tokio::time::timeout(wait, limiter.acquire(Lane::Batch))
+      .await
+      .is_err()
+  );
+  let _interactive =
+    tokio::time::timeout(wait, limiter.acquire(Lane::Interactive))
+      .await
+      .unwrap();
+}
+
+#[tokio::test]
+async fn test_limited_lanes_share_storage() {
+  let limiter = DatabaseLimiter::new(DatabaseLimits::new(1));
+  let db = Database::<User>::new_mock();
+  let interactive = db.clone().with_limiter(limiter.clone(), Lane::Interactive);
+  let batch = db.with_limiter(limiter, Lane::Batch);
+
+  let user = create_user(1, "lanes@example.com", "Lanes", 30);
+  interactive.insert(&user).await.unwrap();
+  assert_eq!(batch.get(user.id).await.unwrap(), Some(user));
+  assert_eq!(batch.count().await.unwrap(), 1);
+}
+
+// --- Instrumentation ---
+
+#[tokio::test]
+async fn test_instrumented_database_delegates() {
+  let db = Database::<User>::new_mock().with_instrumentation();
+  let user = create_user(1, "traced@example.com", "Traced", 30);
+
+  db.insert(&user).await.unwrap();
+  assert_eq!(db.get(user.id).await.unwrap(), Some(user.clone()));
+
+  // errors pass through unchanged
+  let clone = create_user(2, "traced@example.com", "Clone", 30);
+  let result = db.insert(&clone).await;
+  assert!(matches!(result, Err(DatabaseError::UniqueViolation { .. })));
+  assert_eq!(db.count().await.unwrap(), 1);
+}
+
+// --- Latency ---
+
+#[tokio::test]
+async fn test_mock_latency_delays_operations() {
+  let slow = Latency::Fixed(Duration::from_mins(1));
+  let db = Database::<User>::from_backend(
+    MockDatabase::new()
+      .with_latency(LatencyProfile::new().with(MockOperation::Insert, slow)),
+  );
+  let user = create_user(1, "slow@example.com", "Slow", 30);
+
+  // a timed out insert never takes effect
+  let wait = Duration::from_millis(50);
+  assert!(tokio::time::timeout(wait, db.insert(&user)).await.is_err());
+  assert_eq!(
+    tokio::time::timeout(wait, db.get(user.id))
+      .await
+      .unwrap()
+      .unwrap(),
+    None
+  );
+}
+
+// --- Config ---
+
+fn config_from_vars(vars: &[(&str, &str)]) -> Result<DbConfig, DbConfigError> {
+  let vars: std::collections::HashMap<_, _> = vars.iter().copied().collect();
+  DbConfig::from_lookup("DB", |name| {
+    Ok(vars.get(name).map(|v| (*v).to_owned()))
+  })
+}
+
+#[test]
+fn test_config_from_env_postgres() {
+  let config = config_from_vars(&[
+    ("DB_BACKEND", "postgres"),
+    ("DB_URL", "postgres://localhost/app"),
+    ("DB_MAX_CONNECTIONS", "8"),
+    ("DB_ACQUIRE_TIMEOUT_SECS", "5"),
+    ("DB_SCHEMA_INIT", "skip"),
+  ])
+  .unwrap();
+
+  assert_eq!(config, DbConfig {
+    backend:     DbBackendConfig::Postgres {
+      url:          "postgres://localhost/app".to_owned(),
+      pool:         PoolSettings {
+        max_connections: Some(8),
+        acquire_timeout_secs: Some(5),
+        ..Default::default()
+      },
+      schema:       None,
+      table_prefix: None,
+    },
+    schema_init: SchemaInitPolicy::Skip,
+  });
+}
+
+#[test]
+fn test_config_table_namespace() {
+  let config = config_from_vars(&[
+    ("DB_BACKEND", "postgres"),
+    ("DB_URL", "postgres://localhost/app"),
+    ("DB_SCHEMA_NAME", "app"),
+    ("DB_TABLE_PREFIX", "staging_"),
+  ])
+  .unwrap();
+  let namespace = config.backend.table_namespace().unwrap();
+  assert_eq!(namespace.schema(), Some("app"));
+  assert_eq!(namespace.prefix(), "staging_");
+
+  for (var, value) in [
+    ("DB_SCHEMA_NAME", "app; DROP TABLE users"),
+    ("DB_SCHEMA_NAME", "1app"),
+    ("DB_TABLE_PREFIX", "staging-"),
+  ] {
+    assert!(matches!(
+      config_from_vars(&[
+        ("DB_BACKEND", "postgres"),
+        ("DB_URL", "postgres://localhost/app"),
+        (var, value),
+      ]),
+      Err(DbConfigError::InvalidNamespace(_))
+    ));
+  }
+}
+
+#[test]
+fn test_config_from_env_errors() {
+  assert!(matches!(
+    config_from_vars(&[("DB_BACKEND", "sqlite")]),
+    Err(DbConfigError::UnknownBackend(_))
+  ));
+  assert!(matches!(
+    config_from_vars(&[("DB_BACKEND", "postgres")]),
+    Err(DbConfigError::MissingVar(var)) if var == "DB_URL"
+  ));
+  assert!(matches!(
+    config_from_vars(&[
+      ("DB_BACKEND", "postgres"),
+      ("DB_URL", "postgres://localhost/app"),
+      ("DB_MAX_CONNECTIONS", "many"),
+    ]),
+    Err(DbConfigError::InvalidVar { var, .. }) if var == "DB_MAX_CONNECTIONS"
+  ));
+  assert!(matches!(
+    config_from_vars(&[
+      ("DB_BACKEND", "postgres"),
+      ("DB_URL", "postgres://localhost/app"),
+      ("DB_MIN_CONNECTIONS", "4"),
+      ("DB_MAX_CONNECTIONS", "2"),
+    ]),
+    Err(DbConfigError::InvalidPoolSize { min: 4, max: 2 })
+  ));
+}
+
+#[tokio::test]
+async fn test_from_config_mock() {
+  let config: DbConfig =
+    serde_json::from_str(r#"{ "backend": "mock" }"#).unwrap();
+  assert_eq!(config.schema_init, SchemaInitPolicy::Initialize);
+
+  let db = Database::<User>::from_config(config).await.unwrap();
+  let user = create_user(1, "config@example.com", "Config", 30);
+  db.insert(&user).await.unwrap();
+  assert_eq!(db.get(user.id).await.unwrap(), Some(user));
+}
+
+// --- Serialization ---
+
+#[tokio::test]
+async fn test_page_serialization() {
+  let db = MockDatabase::<Unit>::new();
+  db.insert(&Unit {
+    id: RecordId::from_ulid_u128(1),
+  })
+  .unwrap();
+
+  let page = db.list_page(10, 0).await.unwrap();
+  let json = serde_json::to_value(&page).unwrap();
+  assert_eq!(json["total"], 1);
+  assert_eq!(json["has_more"], false);
+  assert_eq!(json["items"].as_array().unwrap().len(), 1);
+  assert_eq!(serde_json::from_value::<Page<Unit>>(json).unwrap(), page);
+}
+
+#[test]
+fn test_error_serialization() {
+  let error = DatabaseError::UniqueViolation {
+    index: "email".to_owned(),
+    value: "a@example.com".to_owned(),
+  };
+  assert_eq!(
+    serde_json::to_value(&error).unwrap(),
+    serde_json::json!({
+      "code": "unique_violation",
+      "message": "Unique constraint violation on index email: a@example.com",
+    })
+  );
+}
+
+#[test]
+fn test_deserialization_error_samples_payload() {
+  let payload = format!(r#"{{"secret": "{}"}}"#, "x".repeat(1000));
+  let error = DatabaseError::deserialization(
+    "users",
+    Some("01ARZ3NDEKTSV4RRFFQ69G5FAV".to_owned()),
+    &payload,
+    miette::miette!("missing field `email`"),
+  );
+
+  assert_eq!(error.error_code(), "deserialization");
+  assert_eq!(
+    error.to_string(),
+    "Failed to deserialize record 01ARZ3NDEKTSV4RRFFQ69G5FAV in table users"
+  );
+  let DatabaseError::Deserialization {
+    payload: sample, ..
+  } = error
+  else {
+    panic!("expected a deserialization error");
+  };
+  assert_eq!(
+    sample.chars().count(),
+    DatabaseError::PAYLOAD_SAMPLE_LEN + 1
+  );
+  assert!(payload.starts_with(sample.trim_end_matches('…')));
+}
+
+// --- Sensitive Fields ---
+
+#[derive(
+  Clone, PartialEq, Serialize, Deserialize, Model, model::RedactedDebug,
+)]
+#[model(
+  table = "patients",
+  index(name = "email", unique, extract =
+    |m| vec![IndexValue::new_single(&m.email)]
+  ),
+)]
+struct Patient {
+  #[model(id)]
+  id:        RecordId<Patient>,
+  #[model(encrypt, redact)]
+  email:     String,
+  #[model(encrypt, redact)]
+  #[serde(rename = "dob")]
+  birthdate: Option<String>,
+  ward:      u32,
+}
+
+/// XORs with a key derived from the field name, so ciphertext differs per
+/// field.
+struct XorCipher;
+
+impl XorCipher {
+  fn apply(field: &str, data: &[u8]) -> Vec<u8> {
+    let key = field.len().to_le_bytes()[0] ^ 0x5a;
+    data.iter().map(|b| b ^ key).collect()
+  }
+}
+
+impl FieldCipher for XorCipher {
+  fn encrypt(
+    &self,
+    _table: &str,
+    field: &str,
+    plaintext: &[u8],
+  ) -> DatabaseResult<Vec<u8>> {
+    Ok(Self::apply(field, plaintext))
+  }
+
+  fn decrypt(
+    &self,
+    _table: &str,
+    field: &str,
+    ciphertext: &[u8],
+  ) -> DatabaseResult<Vec<u8>> {
+    Ok(Self::apply(field, ciphertext))
+  }
+}
+
+#[test]
+fn test_encrypted_fields_use_serialized_names() {
+  assert_eq!(Patient::ENCRYPTED_FIELDS, ["email", "dob"]);
+  assert!(User::ENCRYPTED_FIELDS.is_empty());
+}
+
+#[test]
+fn test_table_auto() {
+  #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+  #[model(table_auto)]
+  struct HTTPRequestLog2 {
+    #[model(id)]
+    id: RecordId<HTTPRequestLog2>,
+  }
+
+  assert_eq!(HTTPRequestLog2::TABLE_NAME, "http_request_log2");
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+#[model(table = "contacts", version = 2, upgrades = [
+  contact_v0_to_v1,
+  contact_v1_to_v2,
+])]
+struct Contact {
+  #[model(id)]
+  id:        RecordId<Contact>,
+  full_name: String,
+  tags:      Vec<String>,
+}
+
+/// Version 1 renamed `name` to `full_name`.
+#[allow(clippy::unnecessary_wraps)]
+fn contact_v0_to_v1(data: &mut serde_json::Value) -> serde_json::Result<()> {
+  if let Some(name) = data.as_object_mut().and_then(|d| d.remove("name")) {
+    data["full_name"] = name;
+  }
+  Ok(())
+}
+
+/// Version 2 added `tags`.
+#[allow(clippy::unnecessary_wraps)]
+fn contact_v1_to_v2(data: &mut serde_json::Value) -> serde_json::Result<()> {
+  data["tags"] = serde_json::json!([]);
+  Ok(())
+}
+
+#[test]
+fn test_versioned_json_upgrades() {
+  use model::{FromVersionedJson, VERSION_FIELD, stamp_version};
+
+  let id = RecordId::<Contact>::from_ulid_u128(1);
+  let expected = Contact {
+    id,
+    full_name: "Ada".to_owned(),
+    tags: Vec::new(),
+  };
+
+  // unversioned and older data is upgraded
+  let v0 = serde_json::json!({ "id": id, "name": "Ada" });
+  assert_eq!(Contact::from_versioned_json(v0).unwrap(), expected);
+  let v1 = serde_json::json!({ "id": id, "full_name": "Ada", "_version": 1 });
+  assert_eq!(Contact::from_versioned_json(v1).unwrap(), expected);
+
+  // current data round trips
+  let mut current = serde_json::to_value(&expected).unwrap();
+  stamp_version::<Contact>(&mut current);
+  assert_eq!(current[VERSION_FIELD], 2);
+  assert_eq!(Contact::from_versioned_json(current).unwrap(), expected);
+
+  // data from a newer version of the model is refused
+  let newer = serde_json::json!({ "id": id, "full_name": "Ada", "tags": [], "_version": 3 });
+  assert!(Contact::from_versioned_json(newer).is_err());
+}
+
+#[test]
+fn test_display_label() {
+  let user = create_user(1, "label@example.com", "Label", 30);
+  assert_eq!(user.display_label(), "Label <label@example.com>");
+
+  // models without a label fall back to their ID
+  let unit = Unit {
+    id: RecordId::from_ulid_u128(1),
+  };
+  assert_eq!(unit.display_label(), unit.id.to_string());
+}
+
+#[test]
+fn test_field_encryption_round_trips() {
+  let patient = Patient {
+    id:        RecordId::from_ulid_u128(1),
+    email:     "pat@example.com".to_owned(),
+    birthdate: Some("1970-01-01".to_owned()),
+    ward:      4,
+  };
+  let plaintext = serde_json::to_value(&patient).unwrap();
+
+  let mut data = plaintext.clone();
+  db_core::encrypt_fields::<Patient>(&XorCipher, &mut data).unwrap();
+  assert!(data["email"].is_string());
+  assert_ne!(data["email"], plaintext["email"]);
+  assert_ne!(data["dob"], plaintext["dob"]);
+  assert_eq!(data["ward"], 4);
+  assert_eq!(data["id"], plaintext["id"]);
+
+  db_core::decrypt_fields::<Patient>(&XorCipher, &mut data).unwrap();
+  assert_eq!(data, plaintext);
+}
+
+#[test]
+fn test_field_encryption_skips_null_fields() {
+  let patient = Patient {
+    id:        RecordId::from_ulid_u128(1),
+    email:     "pat@example.com".to_owned(),
+    birthdate: None,
+    ward:      4,
+  };
+  let mut data = serde_json::to_value(&patient).unwrap();
+  db_core::encrypt_fields::<Patient>(&XorCipher, &mut data).unwrap();
+  assert!(data["dob"].is_null());
+
+  db_core::decrypt_fields::<Patient>(&XorCipher, &mut data).unwrap();
+  assert_eq!(serde_json::from_value::<Patient>(data).unwrap(), patient);
+}
+
+#[test]
+fn test_redacted_debug() {
+  let patient = Patient {
+    id:        RecordId::from_ulid_u128(1),
+    email:     "pat@example.com".to_owned(),
+    birthdate: None,
+    ward:      4,
+  };
+  let debug = format!("{patient:?}");
+  assert!(!debug.contains("pat@example.com"));
+  assert_eq!(
+    debug,
+    format!(
+      "Patient {{ id: {:?}, email: ***, birthdate: ***, ward: 4 }}",
+      patient.id
+    )
+  );
+}
+
+#[test]
+fn test_field_decryption_rejects_plaintext_values() {
+  let mut data = serde_json::json!({ "id": "x", "email": 42 });
+  let error =
+    db_core::decrypt_fields::<Patient>(&XorCipher, &mut data).unwrap_err();
+  assert_eq!(error.error_code(), "serialization");
+}
+
+// --- Fake Data ---
+
+#[cfg(feature = "fake-data")]
+#[tokio::test]
+async fn test_seed_fake() {
+  #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+  #[model(
+    table = "members",
+    index(name = "email", unique, extract =
+      |m| vec![IndexValue::new_single(&m.email)]
+    ),
+    fake,
+  )]
+  struct Member {
+    #[model(id)]
+    id:       RecordId<Member>,
+    #[model(fake = model::fake::faker::internet::en::SafeEmail())]
+    email:    String,
+    nickname: Option<String>,
+    #[serde(skip)]
+    cache:    Vec<u8>,
+  }
+
+  let db = Database::<Member>::new_mock();
+  let members = db.seed_fake(20).await.unwrap();
+  assert_eq!(members.len(), 20);
+  assert_eq!(db.count().await.unwrap(), 20);
+  assert!(
+    members
+      .iter()
+      .all(|m| m.email.contains('@') && m.cache.is_empty())
+  );
+}
+
+// --- JSON Schema ---
+
+#[cfg(feature = "json-schema")]
+#[test]
+fn test_model_json_schema() {
+  #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+  #[model(table = "documents", json_schema)]
+  struct Document {
+    #[model(id)]
+    id:      RecordId<Document>,
+    owner:   RecordId<User>,
+    #[serde(rename = "heading")]
+    title:   String,
+    summary: Option<String>,
+    #[serde(skip)]
+    cache:   Vec<u8>,
+  }
+
+  let schema = Document::json_schema().to_value();
+  let properties = schema["properties"].as_object().unwrap();
+  let mut names: Vec<_> = properties.keys().map(String::as_str).collect();
+  names.sort_unstable();
+  assert_eq!(names, ["heading", "id", "owner", "summary"]);
+  assert_eq!(properties["id"]["type"], "string");
+  assert_eq!(properties["id"]["format"], "ulid");
+  assert_eq!(properties["owner"], properties["id"]);
+  assert_eq!(
+    schema["required"],
+    serde_json::json!(["id", "owner", "heading"])
+  );
+}
+
+// --- Blocking ---
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_blocking_crud() {
+  let db = blocking::Database::<User>::new_mock().unwrap();
+  db.initialize_schema().unwrap();
+
+  let user = create_user(1, "blocking@example.com", "Blocking", 30);
+  db.insert(&user).unwrap();
+  assert_eq!(db.get(user.id).unwrap(), Some(user.clone()));
+  assert_eq!(db.count().unwrap(), 1);
+
+  db.delete(user.id).unwrap();
+  assert!(!db.exists(user.id).unwrap());
+}
+
+// --- Backend Battery ---
+
+trait DatabaseInstantiator {
+  type Guard;
+  async fn init<M: Model>() -> (Database<M>, Self::Guard);
+}
+
+struct MockInstatiator;
+#[cfg(feature = "integration")]
+struct PostgresInstatiator;
+
+impl DatabaseInstantiator for MockInstatiator {
+  type Guard = ();
+  async fn init<M: Model>() -> (Database<M>, Self::Guard) {
+    let db = Database::new_mock();
+    db.initialize_schema().await.unwrap();
+    (db, ())
+  }
+}
+#[cfg(feature = "integration")]
+impl DatabaseInstantiator for PostgresInstatiator {
+  type Guard = testcontainers_modules::testcontainers::ContainerAsync<
+    testcontainers_modules::postgres::Postgres,
+  >;
+  async fn init<M: Model>() -> (Database<M>, Self::Guard) {
+    use testcontainers_modules::testcontainers::runners::AsyncRunner;
+
+    let container = testcontainers_modules::postgres::Postgres::default()
+      .start()
+      .await
+      .unwrap();
+    let url = format!(
+      "postgres://postgres:postgres@{}:{}/postgres",
+      container.get_host().await.unwrap(),
+      container.get_host_port_ipv4(5432).await.unwrap()
+    );
+    let db = Database::new_postgres(&url).await.unwrap();
+    db.initialize_schema().await.unwrap();
+    (db, container)
+  }
+}
+
+/// Assertions every backend must pass, run through the [`Database`]
+/// frontend.
+#[generic_tests::define(attrs(tokio::test))]
+mod generic_testing {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_crud<I: DatabaseInstantiator>() {
+    let (db, _guard) = I::init::<User>().await;
+    let user = create_user(1, "alice@example.com", "Alice", 30);
+
+    db.insert(&user).await.unwrap();
+    assert_eq!(db.get(user.id).await.unwrap(), Some(user.clone()));
+
+    let updated = create_user(1, "alice@example.com", "Alice Updated", 31);
+    db.update(&updated).await.unwrap();
+    assert_eq!(db.get(user.id).await.unwrap(), Some(updated));
+
+    db.delete(user.id).await.unwrap();
+    assert_eq!(db.get(user.id).await.unwrap(), None);
+    assert!(matches!(
+      db.delete(user.id).await,
+      Err(DatabaseError::NotFound(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_unique_violation<I: DatabaseInstantiator>() {
+    let (db, _guard) = I::init::<User>().await;
+    let alice = create_user(1, "alice@example.com", "Alice", 30);
+    let clone = create_user(2, "alice@example.com", "Alice Clone", 25);
+    db.insert(&alice).await.unwrap();
+
+    assert!(matches!(
+      db.insert(&clone).await,
+      Err(DatabaseError::UniqueViolation { .. })
+    ));
+    // the failed insert leaves nothing behind
+    assert_eq!(db.get(clone.id).await.unwrap(), None);
+    assert_eq!(db.count().await.unwrap(), 1);
+
+    let bob = create_user(2, "bob@example.com", "Bob", 25);
+    db.insert(&bob).await.unwrap();
+    let stolen = create_user(2, "alice@example.com", "Bob", 25);
+    assert!(matches!(
+      db.update(&stolen).await,
+      Err(DatabaseError::UniqueViolation { .. })
+    ));
+    assert_eq!(db.get(bob.id).await.unwrap(), Some(bob));
+  }
+
+  #[tokio::test]
+  async fn test_truncate<I: DatabaseInstantiator>() {
+    let (db, _guard) = I::init::<User>().await;
+    let alice = create_user(1, "alice@example.com", "Alice", 30);
+    db.insert(&alice).await.unwrap();
+
+    // refused until unsafe operations are allowed
+    assert!(matches!(
+      db.truncate().await,
+      Err(DatabaseError::UnsafeOpsDisabled(_))
+    ));
+    assert_eq!(db.count().await.unwrap(), 1);
+
+    let db = db.with_unsafe_ops();
+    db.truncate().await.unwrap();
+    assert_eq!(db.count().await.unwrap(), 0);
+    // the unique index was emptied too
+    db.insert(&alice).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_delete_removes_index_rows<I: DatabaseInstantiator>() {
+    let (db, _guard) = I::init::<User>().await;
+    let alice = create_user(1, "alice@example.com", "Alice", 30);
+    db.insert(&alice).await.unwrap();
+    db.delete(alice.id).await.unwrap();
+
+    let email = IndexValue::new_single("alice@example.com");
+    assert_eq!(
+      db.find_by_unique_index(UserIndexSelector::Email, &email)
+        .await
+        .unwrap(),
+      None
+    );
+    assert!(
+      db.find_by_index(
+        UserIndexSelector::Name,
+        &IndexValue::new_single("Alice")
+      )
+      .await
+      .unwrap()
+      .is_empty()
+    );
+    // the unique value is free again
+    let other = create_user(2, "alice@example.com", "Other Alice", 40);
+    db.insert(&other).await.unwrap();
+    assert_eq!(
+      db.find_by_unique_index(UserIndexSelector::Email, &email)
+        .await
+        .unwrap(),
+      Some(other)
+    );
+  }
+
+  #[tokio::test]
+  async fn test_update_replaces_index_rows<I: DatabaseInstantiator>() {
+    let (db, _guard) = I::init::<Article>().await;
+    let article = create_article(1, &["rust", "db"], &["first"]);
+    db.insert(&article).await.unwrap();
+
+    let retagged = create_article(1, &["rust"], &["renamed"]);
+    db.update(&retagged).await.unwrap();
+    let tag = |tag| IndexValue::new_single(tag);
+    assert!(
+      db.find_by_index(ArticleIndexSelector::Tags, &tag("db"))
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    assert_eq!(
+      db.find_by_index(ArticleIndexSelector::Tags, &tag("rust"))
+        .await
+        .unwrap(),
+      vec![retagged.clone()]
+    );
+    // the old alias is free again
+    db.insert(&create_article(2, &[], &["first"]))
+      .await
+      .unwrap();
+
+    db.delete(retagged.id).await.unwrap();
+    assert!(
+      db.find_by_index(ArticleIndexSelector::Tags, &tag("rust"))
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
+
+  #[tokio::test]
+  async fn test_pagination_order<I: DatabaseInstantiator>() {
+    let (db, _guard) = I::init::<User>().await;
+    for i in 1..=5 {
+      db.insert(&create_user(i, &format!("user{i}@example.com"), "User", 20))
+        .await
+        .unwrap();
+      // keep update timestamps distinct
+      tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    let ids = |users: Vec<User>| -> Vec<RecordId<User>> {
+      users.into_iter().map(|u| u.id).collect()
+    };
+    let expected: Vec<_> =
+      (1..=5).rev().map(RecordId::from_ulid_u128).collect();
+
+    // most recently updated first, with pages neither overlapping nor
+    // skipping records
+    let mut listed = Vec::new();
+    for offset in [0, 2, 4] {
+      let page = db.list_page(2, offset).await.unwrap();
+      assert_eq!(page.total, 5);
+      assert_eq!(page.has_more, offset < 4);
+      listed.extend(ids(page.items));
+    }
+    assert_eq!(listed, expected);
+
+    // updating a record moves it to the front
+    db.update(&create_user(1, "user1@example.com", "User", 21))
+      .await
+      .unwrap();
+    assert_eq!(ids(db.list(1, 0).await.unwrap()), [
+      RecordId::from_ulid_u128(1)
+    ]);
+  }
+
+  #[tokio::test]
+  async fn test_projection_of_versioned_model<I: DatabaseInstantiator>() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct ContactTags {
+      id:   RecordId<Contact>,
+      tags: Vec<String>,
+    }
+    impl Projection<Contact> for ContactTags {
+      const FIELDS: &'static [&'static str] = &["id", "tags"];
+    }
+
+    let (db, _guard) = I::init::<Contact>().await;
+    let contact = Contact {
+      id:        RecordId::from_ulid_u128(1),
+      full_name: "Ada".to_owned(),
+      tags:      vec!["vip".to_owned()],
+    };
+    db.insert(&contact).await.unwrap();
+
+    // current rows aren't upgraded again, which would reset `tags`
+    let expected = ContactTags {
+      id:   contact.id,
+      tags: contact.tags.clone(),
+    };
+    assert_eq!(
+      db.get_as::<ContactTags>(contact.id).await.unwrap(),
+      Some(expected)
+    );
+    let listed = db.list_as::<ContactTags>(10, 0).await.unwrap();
+    assert_eq!(listed[0].tags, contact.tags);
+  }
+
+  #[tokio::test]
+  async fn test_concurrent_claims_never_share_models<
+    I: DatabaseInstantiator,
+  >() {
+    let (db, _guard) = I::init::<User>().await;
+    for i in 0..40 {
+      let user = create_user(i, &format!("u{i}@example.com"), "U", 10);
+      db.insert(&user).await.unwrap();
+    }
+
+    // two claimers race, each moving what it claims out of the range
+    let claim_all = |db: Database<User>| async move {
+      let mut claimed = Vec::new();
+      loop {
+        let batch = db
+          .claim_by_index_range(
+            UserIndexSelector::Age,
+            Bound::Unbounded,
+            Bound::Excluded(&IndexValue::new_i64(50)),
+            2,
+            &|user: &mut User| user.age = 50,
+          )
+          .await
+          .unwrap();
+        if batch.is_empty() {
+          return claimed;
+        }
+        claimed.extend(batch.into_iter().map(|user| user.id));
+      }
+    };
+    let (first, second) = tokio::join!(
+      tokio::spawn(claim_all(db.clone())),
+      tokio::spawn(claim_all(db.clone()))
+    );
+
+    let mut claimed = first.unwrap();
+    claimed.extend(second.unwrap());
+    assert_eq!(claimed.len(), 40);
+    claimed.sort();
+    claimed.dedup();
+    assert_eq!(claimed.len(), 40);
+  }
+
+  #[instantiate_tests(<MockInstatiator>)]
+  mod test_mock {}
+  #[cfg(feature = "integration")]
+  #[instantiate_tests(<PostgresInstatiator>)]
+  mod test_postgres {}
+}
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use model::{IndexValue, Model, RecordId};
+use serde::{Deserialize, Serialize};
+
+/// The state of a [`Derivation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DerivationState {
+  /// The transform is running, or was interrupted.
+  Pending,
+  /// The derived blob was written.
+  Complete,
+  /// The transform failed; see [`Derivation::error`].
+  Failed,
+}
+
+/// The status of one transform of one source blob.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "blob_derivations",
+  index(name = "source_transform", unique, extract =
+    |m| vec![IndexValue::new([m.source.as_str(), m.transform.as_str()])]
+  ),
+  index(name = "source", extract = |m| vec![IndexValue::new_single(&m.source)]),
+)]
+pub struct Derivation {
+  /// The derivation's ID.
+  #[model(id)]
+  pub id:         RecordId<Derivation>,
+  /// The key of the source blob.
+  pub source:     String,
+  /// The name of the transform.
+  pub transform:  String,
+  /// The key of the derived blob.
+  pub key:        String,
+  /// The derivation's state.
+  pub state:      DerivationState,
+  /// Why the transform failed, if it did.
+  pub error:      Option<String>,
+  /// When the state last changed.
+  pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,21 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use storage::BlobStorageError;
+use thiserror::Error;
+
+/// Errors that can occur deriving blobs.
+///
+/// A failing [`Transform`](crate::Transform) isn't an error; it's recorded
+/// as a [`Failed`](crate::DerivationState::Failed) derivation.
+#[derive(Debug, Error, Diagnostic)]
+pub enum DerivedError {
+  /// The database failed.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// The blob storage failed.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Storage(#[from] BlobStorageError),
+}
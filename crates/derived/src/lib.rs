@@ -0,0 +1,321 @@
+//! Blobs derived from uploads, e.g. thumbnails or extracted text.
+//!
+//! [`DerivedBlobs`] runs each [`Transform`] registered for a source blob's
+//! content type and writes its output to the deterministic key
+//! `{prefix}/{transform}/{source}`, where the prefix defaults to `derived`.
+//! The state of each transform of each source is tracked as a
+//! [`Derivation`] record.
+//!
+//! Derivation is driven by [`BlobEvent`]s: [`DerivedBlobs`] is a
+//! [`BlobEventSink`], so wrapping storage with
+//! [`BlobStorage::with_events`] derives blobs as part of every upload.
+//! To derive in the background instead, send events to a
+//! [`ChannelEventSink`](storage::events::ChannelEventSink) and pass them to
+//! [`DerivedBlobs::handle`].
+
+mod derivation;
+mod error;
+#[cfg(test)]
+mod tests;
+
+use std::{fmt, sync::Arc};
+
+use belt::Belt;
+use db::{Clock, Database, SystemClock};
+use model::{IndexValue, RecordId};
+use storage::{
+  BlobKey, BlobStorage, UploadOptions,
+  events::{BlobEvent, BlobEventKind, BlobEventSink},
+};
+use tracing::{debug, warn};
+
+pub use self::{
+  derivation::{Derivation, DerivationIndexSelector, DerivationState},
+  error::DerivedError,
+};
+
+/// The output of a [`Transform`].
+#[derive(Debug)]
+pub struct Derived {
+  /// The derived blob's contents.
+  pub data:         Belt,
+  /// The derived blob's media type, e.g. `image/webp`.
+  pub content_type: Option<String>,
+}
+
+/// Produces a derived blob from a source blob.
+#[async_trait::async_trait]
+pub trait Transform: Send + Sync {
+  /// The transform's name, e.g. `thumbnail-256`. It names the derived
+  /// blobs, so must be unique among the transforms of a [`DerivedBlobs`]
+  /// and stable across releases.
+  fn name(&self) -> &str;
+
+  /// Derives a blob from `source`, whose media type is `content_type`.
+  async fn transform(
+    &self,
+    source: Belt,
+    content_type: &str,
+  ) -> miette::Result<Derived>;
+}
+
+/// A transform, and the content types it applies to.
+#[derive(Clone)]
+struct Registration {
+  content_type: String,
+  transform:    Arc<dyn Transform>,
+}
+
+impl Registration {
+  /// Whether the transform applies to `content_type`. Patterns are a media
+  /// type, a type with any subtype like `image/*`, or `*/*`.
+  fn applies_to(&self, content_type: &str) -> bool {
+    // ignore parameters, e.g. `; charset=utf-8`
+    let content_type = content_type.split(';').next().unwrap_or_default();
+    let content_type = content_type.trim();
+    match self.content_type.split_once('/') {
+      Some(("*", "*")) => true,
+      Some((ty, "*")) => content_type
+        .split_once('/')
+        .is_some_and(|(other, _)| other.eq_ignore_ascii_case(ty)),
+      _ => self.content_type.eq_ignore_ascii_case(content_type),
+    }
+  }
+}
+
+/// Runs transforms on uploaded blobs and tracks their state.
+#[derive(Clone)]
+pub struct DerivedBlobs {
+  storage:    BlobStorage,
+  db:         Database<Derivation>,
+  transforms: Vec<Registration>,
+  prefix:     String,
+  clock:      Arc<dyn Clock>,
+}
+
+impl fmt::Debug for DerivedBlobs {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let transforms: Vec<_> = self
+      .transforms
+      .iter()
+      .map(|r| (r.content_type.as_str(), r.transform.name()))
+      .collect();
+    f.debug_struct("DerivedBlobs")
+      .field("transforms", &transforms)
+      .field("prefix", &self.prefix)
+      .finish_non_exhaustive()
+  }
+}
+
+impl DerivedBlobs {
+  /// Creates a new [`DerivedBlobs`] reading and writing blobs in `storage`
+  /// and tracking derivations in `db`, with no transforms.
+  ///
+  /// If `storage` sends its events to this [`DerivedBlobs`], derived blobs
+  /// are skipped rather than derived from again.
+  #[must_use]
+  pub fn new(storage: BlobStorage, db: Database<Derivation>) -> Self {
+    Self {
+      storage,
+      db,
+      transforms: Vec::new(),
+      prefix: "derived".to_owned(),
+      clock: SystemClock::shared(),
+    }
+  }
+
+  /// Registers `transform` for blobs of `content_type`, which may be a media
+  /// type like `image/png`, a type with any subtype like `image/*`, or
+  /// `*/*`.
+  #[must_use]
+  pub fn with_transform(
+    mut self,
+    content_type: impl Into<String>,
+    transform: Arc<dyn Transform>,
+  ) -> Self {
+    self.transforms.push(Registration {
+      content_type: content_type.into(),
+      transform,
+    });
+    self
+  }
+
+  /// Sets the prefix derived blobs are written under.
+  #[must_use]
+  pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+    self.prefix = prefix.into();
+    self
+  }
+
+  /// Sets the clock used to timestamp derivations.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// The key of the blob derived from `source` by the transform named
+  /// `transform`.
+  #[must_use]
+  pub fn derived_key(&self, source: &BlobKey, transform: &str) -> BlobKey {
+    BlobKey::new(format!("{}/{transform}/{source}", self.prefix))
+  }
+
+  fn is_derived(&self, key: &BlobKey) -> bool {
+    key
+      .as_str()
+      .strip_prefix(&self.prefix)
+      .is_some_and(|rest| rest.starts_with('/'))
+  }
+
+  /// Derives a blob or removes derived blobs, as `event` calls for.
+  pub async fn handle(&self, event: &BlobEvent) -> Result<(), DerivedError> {
+    match event.kind {
+      BlobEventKind::Created | BlobEventKind::Overwritten => {
+        self.derive(&event.key).await?;
+      }
+      BlobEventKind::Deleted => self.remove(&event.key).await?,
+    }
+    Ok(())
+  }
+
+  /// Runs every transform registered for the content type of `source`,
+  /// replacing any blobs derived from it before, and returns the
+  /// derivations.
+  ///
+  /// Sources without a content type, derived blobs, and sources which no
+  /// longer exist are skipped. A failing transform is recorded as a
+  /// [`Failed`](DerivationState::Failed) derivation and doesn't stop the
+  /// others.
+  pub async fn derive(
+    &self,
+    source: &BlobKey,
+  ) -> Result<Vec<Derivation>, DerivedError> {
+    if self.is_derived(source) {
+      return Ok(Vec::new());
+    }
+    let Some(content_type) = self
+      .storage
+      .head(source)
+      .await?
+      .and_then(|metadata| metadata.content_type)
+    else {
+      debug!(%source, "skipping blob without a content type");
+      return Ok(Vec::new());
+    };
+
+    let mut derivations = Vec::new();
+    for registration in self
+      .transforms
+      .iter()
+      .filter(|r| r.applies_to(&content_type))
+    {
+      let transform = registration.transform.as_ref();
+      let mut derivation = self.start(source, transform.name()).await?;
+
+      let data =
+        Belt::from_response_stream(self.storage.get_stream(source).await?);
+      match transform.transform(data, &content_type).await {
+        Ok(derived) => {
+          self
+            .storage
+            .put_stream(
+              &BlobKey::new(derivation.key.clone()),
+              Box::pin(derived.data),
+              UploadOptions {
+                overwrite: true,
+                content_type: derived.content_type,
+                ..UploadOptions::default()
+              },
+            )
+            .await?;
+          debug!(%source, key = %derivation.key, "derived blob");
+          derivation.state = DerivationState::Complete;
+          derivation.error = None;
+        }
+        Err(error) => {
+          warn!(%source, transform = transform.name(), %error, "transform failed");
+          derivation.state = DerivationState::Failed;
+          derivation.error = Some(error.to_string());
+        }
+      }
+      derivation.updated_at = self.clock.now();
+      self.db.update(&derivation).await?;
+      derivations.push(derivation);
+    }
+    Ok(derivations)
+  }
+
+  /// Records a derivation of `source` as pending, reusing its record if it
+  /// was derived before.
+  async fn start(
+    &self,
+    source: &BlobKey,
+    transform: &str,
+  ) -> Result<Derivation, DerivedError> {
+    let existing = self
+      .db
+      .find_by_unique_index(
+        DerivationIndexSelector::SourceTransform,
+        &IndexValue::new([source.as_str(), transform]),
+      )
+      .await?;
+    let derivation = Derivation {
+      id:         existing.map_or_else(RecordId::new, |d| d.id),
+      source:     source.to_string(),
+      transform:  transform.to_owned(),
+      key:        self.derived_key(source, transform).to_string(),
+      state:      DerivationState::Pending,
+      error:      None,
+      updated_at: self.clock.now(),
+    };
+    self.db.upsert(&derivation).await?;
+    Ok(derivation)
+  }
+
+  /// Deletes the blobs derived from `source` and their derivations.
+  pub async fn remove(&self, source: &BlobKey) -> Result<(), DerivedError> {
+    let derivations = self
+      .db
+      .find_by_index(
+        DerivationIndexSelector::Source,
+        &IndexValue::new_single(source.as_str()),
+      )
+      .await?;
+    for derivation in derivations {
+      self.storage.delete(&BlobKey::new(derivation.key)).await?;
+      self.db.delete(derivation.id).await?;
+    }
+    debug!(%source, "removed derived blobs");
+    Ok(())
+  }
+
+  /// Returns the derivation of `source` by the transform named `transform`,
+  /// if it was ever started.
+  pub async fn status(
+    &self,
+    source: &BlobKey,
+    transform: &str,
+  ) -> Result<Option<Derivation>, DerivedError> {
+    Ok(
+      self
+        .db
+        .find_by_unique_index(
+          DerivationIndexSelector::SourceTransform,
+          &IndexValue::new([source.as_str(), transform]),
+        )
+        .await?,
+    )
+  }
+}
+
+/// Derives blobs as their events arrive, logging failures, as blob event
+/// sinks can't fail the operation they're notified of.
+#[async_trait::async_trait]
+impl BlobEventSink for DerivedBlobs {
+  async fn notify(&self, event: BlobEvent) {
+    if let Err(error) = self.handle(&event).await {
+      warn!(key = %event.key, kind = %event.kind, %error, "failed to derive blobs");
+    }
+  }
+}
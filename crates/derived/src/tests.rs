@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use belt::Belt;
+use db::Database;
+use storage::{BlobKey, BlobStorage, UploadOptions};
+
+use crate::{DerivationState, Derived, DerivedBlobs, Transform};
+
+/// Uppercases text.
+struct Uppercase;
+
+#[async_trait::async_trait]
+impl Transform for Uppercase {
+  fn name(&self) -> &'static str { "uppercase" }
+
+  async fn transform(
+    &self,
+    source: Belt,
+    _content_type: &str,
+  ) -> miette::Result<Derived> {
+    let data = source.collect_bytes().await.map_err(miette::Report::msg)?;
+    Ok(Derived {
+      data:         Belt::new_from_slice(&data.to_ascii_uppercase()),
+      content_type: Some("text/plain".to_owned()),
+    })
+  }
+}
+
+/// Always fails.
+struct Broken;
+
+#[async_trait::async_trait]
+impl Transform for Broken {
+  fn name(&self) -> &'static str { "broken" }
+
+  async fn transform(
+    &self,
+    _source: Belt,
+    _content_type: &str,
+  ) -> miette::Result<Derived> {
+    Err(miette::miette!("unsupported encoding"))
+  }
+}
+
+async fn put(storage: &BlobStorage, key: &BlobKey, data: &str, ty: &str) {
+  storage
+    .put_stream(
+      key,
+      Box::pin(Belt::new_from_slice(data.as_bytes())),
+      UploadOptions {
+        overwrite: true,
+        content_type: Some(ty.to_owned()),
+        ..UploadOptions::default()
+      },
+    )
+    .await
+    .unwrap();
+}
+
+async fn read(storage: &BlobStorage, key: &BlobKey) -> String {
+  let data = Belt::from_response_stream(storage.get_stream(key).await.unwrap())
+    .collect_bytes()
+    .await
+    .unwrap();
+  String::from_utf8(data.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_derive_writes_derived_blob() {
+  let storage = BlobStorage::new_memory();
+  let derived = DerivedBlobs::new(storage.clone(), Database::new_mock())
+    .with_transform("text/*", Arc::new(Uppercase));
+  let source = BlobKey::new("notes/a.txt");
+  put(&storage, &source, "hello", "text/plain; charset=utf-8").await;
+
+  let derivations = derived.derive(&source).await.unwrap();
+  assert_eq!(derivations.len(), 1);
+  assert_eq!(derivations[0].state, DerivationState::Complete);
+
+  let key = derived.derived_key(&source, "uppercase");
+  assert_eq!(key.as_str(), "derived/uppercase/notes/a.txt");
+  assert_eq!(read(&storage, &key).await, "HELLO");
+  let metadata = storage.head(&key).await.unwrap().unwrap();
+  assert_eq!(metadata.content_type.as_deref(), Some("text/plain"));
+
+  // deriving again replaces the derived blob and reuses the record
+  put(&storage, &source, "bye", "text/plain").await;
+  let again = derived.derive(&source).await.unwrap();
+  assert_eq!(again[0].id, derivations[0].id);
+  assert_eq!(read(&storage, &key).await, "BYE");
+}
+
+#[tokio::test]
+async fn test_derive_only_matching_transforms() {
+  let storage = BlobStorage::new_memory();
+  let derived = DerivedBlobs::new(storage.clone(), Database::new_mock())
+    .with_transform("text/plain", Arc::new(Uppercase))
+    .with_transform("image/*", Arc::new(Broken));
+  let source = BlobKey::new("photo.png");
+  put(&storage, &source, "not really a png", "image/png").await;
+
+  let derivations = derived.derive(&source).await.unwrap();
+  assert_eq!(derivations.len(), 1);
+  assert_eq!(derivations[0].transform, "broken");
+  assert!(
+    derived
+      .status(&source, "uppercase")
+      .await
+      .unwrap()
+      .is_none()
+  );
+}
+
+#[tokio::test]
+async fn test_failed_transform_is_recorded() {
+  let storage = BlobStorage::new_memory();
+  let derived = DerivedBlobs::new(storage.clone(), Database::new_mock())
+    .with_transform("*/*", Arc::new(Broken))
+    .with_transform("*/*", Arc::new(Uppercase));
+  let source = BlobKey::new("a.txt");
+  put(&storage, &source, "hello", "text/plain").await;
+
+  derived.derive(&source).await.unwrap();
+  let failed = derived.status(&source, "broken").await.unwrap().unwrap();
+  assert_eq!(failed.state, DerivationState::Failed);
+  assert!(failed.error.unwrap().contains("unsupported encoding"));
+  assert!(!storage.exists(&BlobKey::new(failed.key)).await.unwrap());
+
+  // the other transform still ran
+  let complete = derived.status(&source, "uppercase").await.unwrap().unwrap();
+  assert_eq!(complete.state, DerivationState::Complete);
+}
+
+#[tokio::test]
+async fn test_events_derive_and_remove() {
+  let storage = BlobStorage::new_memory();
+  let derived = DerivedBlobs::new(storage.clone(), Database::new_mock())
+    .with_transform("text/plain", Arc::new(Uppercase));
+  let notifying = storage.clone().with_events(Arc::new(derived.clone()));
+  let source = BlobKey::new("a.txt");
+  let key = derived.derived_key(&source, "uppercase");
+
+  put(&notifying, &source, "hello", "text/plain").await;
+  assert_eq!(read(&storage, &key).await, "HELLO");
+  assert!(
+    derived
+      .status(&source, "uppercase")
+      .await
+      .unwrap()
+      .is_some()
+  );
+
+  notifying.delete(&source).await.unwrap();
+  assert!(!storage.exists(&key).await.unwrap());
+  assert!(
+    derived
+      .status(&source, "uppercase")
+      .await
+      .unwrap()
+      .is_none()
+  );
+}
+
+#[tokio::test]
+async fn test_derived_blobs_are_not_derived_from() {
+  let storage = BlobStorage::new_memory();
+  let derived = DerivedBlobs::new(storage.clone(), Database::new_mock())
+    .with_transform("text/plain", Arc::new(Uppercase));
+  let key = BlobKey::new("derived/uppercase/a.txt");
+  put(&storage, &key, "HELLO", "text/plain").await;
+
+  assert!(derived.derive(&key).await.unwrap().is_empty());
+}
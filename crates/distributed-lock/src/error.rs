@@ -0,0 +1,16 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// Errors that can occur using a distributed lock.
+#[derive(Debug, Error, Diagnostic)]
+pub enum LockError {
+  /// The database failed.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// The lock expired, and may have been acquired by someone else.
+  #[error("Lock {0:?} was lost")]
+  LockLost(String),
+}
@@ -0,0 +1,228 @@
+//! Named locks with expiry, stored in a database.
+//!
+//! [`DistributedLock`] coordinates processes sharing a database, e.g. to run
+//! a singleton background job or to elect a leader, without extra
+//! infrastructure. A lock is acquired with a time-to-live and must be
+//! [`renew`](DistributedLock::renew)ed before it expires; a holder that
+//! crashes loses the lock once its time-to-live passes.
+//!
+//! Each acquisition is given a fencing token, [`LockGuard::fence`], which
+//! increases every time the lock changes hands. Passing it along with writes
+//! lets other systems reject writes from a holder whose lock has expired.
+
+mod error;
+mod lock;
+#[cfg(test)]
+mod tests;
+
+use std::{fmt, ops::Bound, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use db::{Clock, Database, DatabaseError, SystemClock};
+use model::{IndexValue, RecordId};
+use tracing::debug;
+
+use self::lock::expiry_key;
+pub use self::{
+  error::LockError,
+  lock::{Lock, LockIndexSelector},
+};
+
+/// Acquires and releases named locks.
+#[derive(Clone)]
+pub struct DistributedLock {
+  db:    Database<Lock>,
+  clock: Arc<dyn Clock>,
+}
+
+impl fmt::Debug for DistributedLock {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("DistributedLock").finish_non_exhaustive()
+  }
+}
+
+/// A held lock, returned by [`DistributedLock::try_acquire`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use = "the lock is held until it is released or expires"]
+pub struct LockGuard {
+  name:       String,
+  fence:      u64,
+  expires_at: DateTime<Utc>,
+}
+
+impl LockGuard {
+  /// Returns the name of the lock.
+  #[must_use]
+  pub fn name(&self) -> &str { &self.name }
+
+  /// Returns the fencing token, which is greater than that of every previous
+  /// holder of the lock.
+  #[must_use]
+  pub const fn fence(&self) -> u64 { self.fence }
+
+  /// Returns when the lock expires unless renewed.
+  #[must_use]
+  pub const fn expires_at(&self) -> DateTime<Utc> { self.expires_at }
+}
+
+impl DistributedLock {
+  /// Creates a new [`DistributedLock`].
+  #[must_use]
+  pub fn new(db: Database<Lock>) -> Self {
+    Self {
+      db,
+      clock: SystemClock::shared(),
+    }
+  }
+
+  /// Sets the clock used to expire locks.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// Acquires the lock `name` for `ttl`, returning `None` if it is held by
+  /// someone else.
+  pub async fn try_acquire(
+    &self,
+    name: &str,
+    ttl: Duration,
+  ) -> Result<Option<LockGuard>, LockError> {
+    let now = self.clock.now();
+    let expires_at = clock::after(now, ttl);
+
+    // take over the lock if it was released or has expired; the range is
+    // matched against a snapshot, so check again on the locked row in case
+    // someone else took it over first
+    let lower = IndexValue::new([name, ""]);
+    let upper = expiry_key(name, &now);
+    let claimed = self
+      .db
+      .claim_by_index_range(
+        LockIndexSelector::Expiry,
+        Bound::Included(&lower),
+        Bound::Included(&upper),
+        1,
+        &|lock: &mut Lock| {
+          if !lock.held || lock.expires_at <= now {
+            lock.fence += 1;
+            lock.held = true;
+            lock.expires_at = expires_at;
+          }
+        },
+      )
+      .await?;
+    if let Some(lock) = claimed.into_iter().next() {
+      if !lock.held || lock.expires_at != expires_at {
+        return Ok(None);
+      }
+      debug!(name, fence = lock.fence, "acquired lock");
+      return Ok(Some(LockGuard {
+        name: lock.name,
+        fence: lock.fence,
+        expires_at,
+      }));
+    }
+
+    // otherwise the lock is held, or has never been acquired
+    let key = IndexValue::new_single(name);
+    if self
+      .db
+      .exists_by_unique_index(LockIndexSelector::Name, &key)
+      .await?
+    {
+      return Ok(None);
+    }
+    let lock = Lock {
+      id: RecordId::new(),
+      name: name.to_owned(),
+      fence: 1,
+      held: true,
+      expires_at,
+    };
+    match self.db.insert(&lock).await {
+      Ok(()) => {
+        debug!(name, fence = lock.fence, "acquired new lock");
+        Ok(Some(LockGuard {
+          name: lock.name,
+          fence: lock.fence,
+          expires_at,
+        }))
+      }
+      // someone else created it first
+      Err(DatabaseError::UniqueViolation { .. }) => Ok(None),
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  /// Extends a held lock to expire `ttl` from now.
+  ///
+  /// Fails with [`LockError::LockLost`] if the lock has already expired.
+  pub async fn renew(
+    &self,
+    guard: &mut LockGuard,
+    ttl: Duration,
+  ) -> Result<(), LockError> {
+    let now = self.clock.now();
+    let expires_at = clock::after(now, ttl);
+    let lock = self
+      .update_held(guard, &|lock: &mut Lock| {
+        if lock.held && lock.expires_at > now {
+          lock.expires_at = expires_at;
+        }
+      })
+      .await?;
+
+    if !lock.held || lock.expires_at != expires_at {
+      return Err(LockError::LockLost(guard.name.clone()));
+    }
+    debug!(name = %guard.name, "renewed lock");
+    guard.expires_at = expires_at;
+    Ok(())
+  }
+
+  /// Releases a held lock, so that it can be acquired immediately.
+  ///
+  /// Fails with [`LockError::LockLost`] if the lock has already expired.
+  pub async fn release(&self, guard: LockGuard) -> Result<(), LockError> {
+    let now = self.clock.now();
+    let lock = self
+      .update_held(&guard, &|lock: &mut Lock| {
+        if lock.held && lock.expires_at > now {
+          lock.held = false;
+          lock.expires_at = now;
+        }
+      })
+      .await?;
+
+    if lock.held {
+      return Err(LockError::LockLost(guard.name));
+    }
+    debug!(name = %guard.name, "released lock");
+    Ok(())
+  }
+
+  /// Atomically applies `update` to the lock held by `guard`. Fails with
+  /// [`LockError::LockLost`] if the lock has since changed hands.
+  async fn update_held(
+    &self,
+    guard: &LockGuard,
+    update: &(dyn Fn(&mut Lock) + Send + Sync),
+  ) -> Result<Lock, LockError> {
+    let key = IndexValue::new([guard.name.clone(), guard.fence.to_string()]);
+    self
+      .db
+      .claim_by_index_range(
+        LockIndexSelector::Fence,
+        Bound::Included(&key),
+        Bound::Included(&key),
+        1,
+        update,
+      )
+      .await?
+      .into_iter()
+      .next()
+      .ok_or_else(|| LockError::LockLost(guard.name.clone()))
+  }
+}
@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use model::{IndexValue, Model, RecordId};
+use serde::{Deserialize, Serialize};
+
+/// The stored state of a named lock.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "locks",
+  index(name = "name", unique, extract =
+    |m| vec![IndexValue::new_single(&m.name)]
+  ),
+  index(name = "expiry", extract =
+    |m| vec![expiry_key(&m.name, &m.expires_at)]
+  ),
+  index(name = "fence", extract =
+    |m| vec![IndexValue::new([m.name.clone(), m.fence.to_string()])]
+  ),
+)]
+pub struct Lock {
+  /// The lock's ID.
+  #[model(id)]
+  pub id:         RecordId<Lock>,
+  /// The lock's name.
+  pub name:       String,
+  /// Incremented on every acquisition, identifying the current holder.
+  pub fence:      u64,
+  /// Whether the lock is held, until `expires_at`.
+  pub held:       bool,
+  /// When the lock expires if held, or when it was released.
+  pub expires_at: DateTime<Utc>,
+}
+
+/// The `expiry` index key for a lock name and time. The timestamp is
+/// fixed-width so that keys order chronologically.
+pub(crate) fn expiry_key(name: &str, at: &DateTime<Utc>) -> IndexValue {
+  IndexValue::new([
+    name.to_owned(),
+    at.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string(),
+  ])
+}
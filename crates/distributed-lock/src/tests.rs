@@ -0,0 +1,118 @@
+use std::{sync::Arc, time::Duration};
+
+use db::{Clock, Database, ManualClock, MockDatabase};
+
+use crate::{DistributedLock, LockError};
+
+const TTL: Duration = Duration::from_secs(10);
+
+fn setup() -> (DistributedLock, ManualClock) {
+  let clock = ManualClock::default();
+  let db = Database::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let lock = DistributedLock::new(db).with_clock(Arc::new(clock.clone()));
+  (lock, clock)
+}
+
+#[tokio::test]
+async fn test_acquire_is_exclusive() {
+  let (lock, _) = setup();
+
+  let guard = lock.try_acquire("leader", TTL).await.unwrap().unwrap();
+  assert_eq!(guard.name(), "leader");
+  assert_eq!(guard.fence(), 1);
+  assert!(lock.try_acquire("leader", TTL).await.unwrap().is_none());
+
+  // other names are independent
+  assert!(lock.try_acquire("leader-2", TTL).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_huge_ttl_holds_lock() {
+  let (lock, _) = setup();
+
+  let _guard =
+    lock.try_acquire("leader", Duration::MAX).await.unwrap().unwrap();
+  assert!(lock.try_acquire("leader", TTL).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_release_allows_reacquire() {
+  let (lock, _) = setup();
+
+  let guard = lock.try_acquire("leader", TTL).await.unwrap().unwrap();
+  lock.release(guard).await.unwrap();
+
+  let guard = lock.try_acquire("leader", TTL).await.unwrap().unwrap();
+  assert_eq!(guard.fence(), 2);
+}
+
+#[tokio::test]
+async fn test_expired_lock_is_taken_over() {
+  let (lock, clock) = setup();
+
+  let stale = lock.try_acquire("leader", TTL).await.unwrap().unwrap();
+  clock.advance(Duration::from_secs(9));
+  assert!(lock.try_acquire("leader", TTL).await.unwrap().is_none());
+
+  clock.advance(Duration::from_secs(1));
+  let fresh = lock.try_acquire("leader", TTL).await.unwrap().unwrap();
+  assert!(fresh.fence() > stale.fence());
+
+  // the previous holder can no longer renew or release
+  let mut stale_renew = stale.clone();
+  assert!(matches!(
+    lock.renew(&mut stale_renew, TTL).await,
+    Err(LockError::LockLost(_))
+  ));
+  assert!(matches!(
+    lock.release(stale).await,
+    Err(LockError::LockLost(_))
+  ));
+  lock.release(fresh).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_renew_extends_expiry() {
+  let (lock, clock) = setup();
+
+  let mut guard = lock.try_acquire("leader", TTL).await.unwrap().unwrap();
+  clock.advance(Duration::from_secs(8));
+  lock.renew(&mut guard, TTL).await.unwrap();
+  assert_eq!(
+    guard.expires_at(),
+    clock.now() + chrono::Duration::seconds(10)
+  );
+
+  clock.advance(Duration::from_secs(8));
+  assert!(lock.try_acquire("leader", TTL).await.unwrap().is_none());
+
+  // an expired lock can't be renewed, even if no one else took it
+  clock.advance(Duration::from_secs(2));
+  assert!(matches!(
+    lock.renew(&mut guard, TTL).await,
+    Err(LockError::LockLost(_))
+  ));
+}
+
+#[tokio::test]
+async fn test_concurrent_acquirers_elect_one_leader() {
+  let (lock, _) = setup();
+
+  let mut handles = vec![];
+  for _ in 0..8 {
+    let lock = lock.clone();
+    handles.push(tokio::spawn(async move {
+      lock.try_acquire("leader", TTL).await.unwrap()
+    }));
+  }
+
+  let mut leaders = 0;
+  for handle in handles {
+    if handle.await.unwrap().is_some() {
+      leaders += 1;
+    }
+  }
+  assert_eq!(leaders, 1);
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use storage::events::{BlobEvent, BlobEventSink};
+use tracing::warn;
+
+use crate::{EventSink, SinkError, SinkMessage};
+
+/// A [`BlobEventSink`] publishing blob storage events to an [`EventSink`].
+///
+/// Each event is published to the `blobs` topic with the event's kind,
+/// keyed by the blob's key, with the event as its JSON payload. Publishing
+/// failures are logged and the event dropped, as storage operations never
+/// fail on account of their notifications.
+#[derive(Debug, Clone)]
+pub struct BlobEventForwarder {
+  sink: Arc<dyn EventSink>,
+}
+
+impl BlobEventForwarder {
+  /// Creates a new [`BlobEventForwarder`] publishing to `sink`.
+  #[must_use]
+  pub fn new(sink: Arc<dyn EventSink>) -> Self { Self { sink } }
+}
+
+impl SinkMessage {
+  /// Builds the message for a blob storage event.
+  pub fn from_blob_event(event: &BlobEvent) -> Result<Self, SinkError> {
+    Ok(Self {
+      topic:   "blobs".to_owned(),
+      kind:    event.kind.to_string(),
+      key:     event.key.to_string(),
+      payload: serde_json::to_vec(event).map_err(SinkError::Serialization)?,
+    })
+  }
+}
+
+#[async_trait::async_trait]
+impl BlobEventSink for BlobEventForwarder {
+  async fn notify(&self, event: BlobEvent) {
+    let result = match SinkMessage::from_blob_event(&event) {
+      Ok(message) => self.sink.publish(&message).await,
+      Err(e) => Err(e),
+    };
+    if let Err(error) = result {
+      warn!(key = %event.key, kind = %event.kind, %error, "dropping blob event");
+    }
+  }
+}
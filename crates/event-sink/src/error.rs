@@ -0,0 +1,26 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// Errors that can occur publishing events to a sink.
+#[derive(Debug, Error, Diagnostic)]
+pub enum SinkError {
+  /// The database failed while reading changes.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// The event couldn't be serialized.
+  #[error("Failed to serialize event")]
+  Serialization(#[source] serde_json::Error),
+
+  /// NATS JetStream didn't accept the event.
+  #[cfg(feature = "nats")]
+  #[error("Failed to publish event to NATS")]
+  Nats(#[from] async_nats::jetstream::context::PublishError),
+
+  /// Kafka didn't accept the event.
+  #[cfg(feature = "kafka")]
+  #[error("Failed to produce event to Kafka")]
+  Kafka(#[from] rdkafka::error::KafkaError),
+}
@@ -0,0 +1,81 @@
+use std::{fmt, time::Duration};
+
+use rdkafka::{
+  message::{Header, OwnedHeaders},
+  producer::{FutureProducer, FutureRecord},
+};
+
+use crate::{EventSink, SinkError, SinkMessage};
+
+/// An [`EventSink`] producing to Kafka.
+///
+/// Each message is produced to the topic named by [`SinkMessage::topic`],
+/// under the prefix if one is set, keyed by [`SinkMessage::key`] so Kafka's
+/// partitioner keeps each record's events on one partition, in order. The
+/// kind is sent in the `kind` header.
+#[derive(Clone)]
+pub struct KafkaSink {
+  producer:      FutureProducer,
+  prefix:        Option<String>,
+  queue_timeout: Duration,
+}
+
+impl fmt::Debug for KafkaSink {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("KafkaSink")
+      .field("prefix", &self.prefix)
+      .field("queue_timeout", &self.queue_timeout)
+      .finish_non_exhaustive()
+  }
+}
+
+impl KafkaSink {
+  /// Creates a new [`KafkaSink`] producing with `producer`, waiting up to 5
+  /// seconds for room in its queue.
+  #[must_use]
+  pub const fn new(producer: FutureProducer) -> Self {
+    Self {
+      producer,
+      prefix: None,
+      queue_timeout: Duration::from_secs(5),
+    }
+  }
+
+  /// Sets a prefix for the topics produced to, e.g. `events`, giving
+  /// `events.orders`.
+  #[must_use]
+  pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+    self.prefix = Some(prefix.into());
+    self
+  }
+
+  /// Sets how long to wait for room in the producer's queue.
+  #[must_use]
+  pub const fn with_queue_timeout(mut self, queue_timeout: Duration) -> Self {
+    self.queue_timeout = queue_timeout;
+    self
+  }
+}
+
+#[async_trait::async_trait]
+impl EventSink for KafkaSink {
+  async fn publish(&self, message: &SinkMessage) -> Result<(), SinkError> {
+    let topic = match &self.prefix {
+      Some(prefix) => format!("{prefix}.{}", message.topic),
+      None => message.topic.clone(),
+    };
+    let record = FutureRecord::to(&topic)
+      .key(&message.key)
+      .payload(&message.payload)
+      .headers(OwnedHeaders::new().insert(Header {
+        key:   "kind",
+        value: Some(message.kind.as_str()),
+      }));
+    self
+      .producer
+      .send(record, self.queue_timeout)
+      .await
+      .map_err(|(error, _)| SinkError::Kafka(error))?;
+    Ok(())
+  }
+}
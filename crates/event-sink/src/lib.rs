@@ -0,0 +1,105 @@
+//! Pluggable sinks publishing change events to message brokers.
+//!
+//! An [`EventSink`] publishes [`SinkMessage`]s, which [`forward`] builds
+//! from [`ChangeEvent`]s: the topic is the model's table, the kind is what
+//! happened, and the partition key is the record ID, so brokers keep each
+//! record's events in order. With the `nats` and `kafka` features,
+//! [`NatsSink`] and [`KafkaSink`] publish to NATS `JetStream` and Kafka.
+//!
+//! With the `cdc` feature, [`forward_stream`] publishes the changes read
+//! from a Postgres change data capture stream. With the `storage` feature,
+//! [`BlobEventForwarder`] publishes blob storage events.
+
+#[cfg(feature = "storage")]
+mod blob;
+mod error;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+
+use db::ChangeEvent;
+use model::Model;
+use tracing::debug;
+
+#[cfg(feature = "storage")]
+pub use self::blob::BlobEventForwarder;
+pub use self::error::SinkError;
+#[cfg(feature = "kafka")]
+pub use self::kafka::KafkaSink;
+#[cfg(feature = "nats")]
+pub use self::nats::NatsSink;
+
+/// An event to publish to a sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkMessage {
+  /// The stream the event belongs to, e.g. a model's table.
+  pub topic:   String,
+  /// What happened, e.g. `inserted`.
+  pub kind:    String,
+  /// The partition key. Brokers keep events with the same key in order.
+  pub key:     String,
+  /// The JSON payload.
+  pub payload: Vec<u8>,
+}
+
+impl SinkMessage {
+  /// Builds the message for a change: the topic is the model's table, the
+  /// kind is the change's [action](ChangeEvent::action), the key is the
+  /// record ID, and the payload is `{"type", "id", "label", "record"}`, with
+  /// a null label and record for deletions.
+  pub fn from_change<M: Model>(
+    event: &ChangeEvent<M>,
+  ) -> Result<Self, SinkError> {
+    let payload = serde_json::to_vec(&serde_json::json!({
+      "type": format!("{}.{}", M::TABLE_NAME, event.action()),
+      "id": event.id(),
+      "label": event.label(),
+      "record": event.model(),
+    }))
+    .map_err(SinkError::Serialization)?;
+
+    Ok(Self {
+      topic: M::TABLE_NAME.to_owned(),
+      kind: event.action().to_owned(),
+      key: event.id().to_string(),
+      payload,
+    })
+  }
+}
+
+/// A destination for events, such as a message broker.
+#[async_trait::async_trait]
+pub trait EventSink: fmt::Debug + Send + Sync {
+  /// Publishes `message`, returning once the sink has accepted it.
+  async fn publish(&self, message: &SinkMessage) -> Result<(), SinkError>;
+}
+
+/// Publishes a change to `sink`.
+pub async fn forward<M: Model>(
+  sink: &dyn EventSink,
+  event: &ChangeEvent<M>,
+) -> Result<(), SinkError> {
+  let message = SinkMessage::from_change(event)?;
+  sink.publish(&message).await?;
+  debug!(topic = %message.topic, kind = %message.kind, key = %message.key, "forwarded change");
+  Ok(())
+}
+
+/// Publishes every change read from `stream` to `sink`, acknowledging each
+/// change once the sink has accepted it. Only returns on error.
+#[cfg(feature = "cdc")]
+pub async fn forward_stream<M: Model>(
+  sink: &dyn EventSink,
+  stream: &mut db::ChangeStream<M>,
+) -> Result<(), SinkError> {
+  loop {
+    let event = stream.recv().await?;
+    forward(sink, &event.change).await?;
+    stream.ack(event.position).await?;
+  }
+}
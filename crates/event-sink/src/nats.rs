@@ -0,0 +1,63 @@
+use std::fmt;
+
+use async_nats::jetstream;
+
+use crate::{EventSink, SinkError, SinkMessage};
+
+/// An [`EventSink`] publishing to NATS JetStream.
+///
+/// Each message is published to the subject `{topic}.{kind}.{key}`, under
+/// the prefix if one is set, e.g. `orders.updated.01ARZ3NDEKTSV4RRFFQ69G5FAV`.
+/// Ending the subject with the key lets a stream's subject mapping
+/// partition by it, e.g. with `{{partition(8,3)}}`.
+#[derive(Clone)]
+pub struct NatsSink {
+  jetstream: jetstream::Context,
+  prefix:    Option<String>,
+}
+
+impl fmt::Debug for NatsSink {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("NatsSink")
+      .field("prefix", &self.prefix)
+      .finish_non_exhaustive()
+  }
+}
+
+impl NatsSink {
+  /// Creates a new [`NatsSink`] publishing with `jetstream`.
+  #[must_use]
+  pub const fn new(jetstream: jetstream::Context) -> Self {
+    Self {
+      jetstream,
+      prefix: None,
+    }
+  }
+
+  /// Sets a prefix for the subjects published to, e.g. `events`.
+  #[must_use]
+  pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+    self.prefix = Some(prefix.into());
+    self
+  }
+
+  fn subject(&self, message: &SinkMessage) -> String {
+    let subject = format!("{}.{}.{}", message.topic, message.kind, message.key);
+    match &self.prefix {
+      Some(prefix) => format!("{prefix}.{subject}"),
+      None => subject,
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl EventSink for NatsSink {
+  async fn publish(&self, message: &SinkMessage) -> Result<(), SinkError> {
+    self
+      .jetstream
+      .publish(self.subject(message), message.payload.clone().into())
+      .await?
+      .await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,122 @@
+use std::sync::Mutex;
+
+use db::ChangeEvent;
+use model::{Model, RecordId};
+use serde::{Deserialize, Serialize};
+
+use crate::{EventSink, SinkError, SinkMessage, forward};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(table = "orders")]
+struct Order {
+  #[model(id)]
+  id:    RecordId<Order>,
+  total: u32,
+}
+
+#[derive(Debug, Default)]
+struct RecordingSink {
+  messages: Mutex<Vec<SinkMessage>>,
+}
+
+#[async_trait::async_trait]
+impl EventSink for RecordingSink {
+  async fn publish(&self, message: &SinkMessage) -> Result<(), SinkError> {
+    self.messages.lock().unwrap().push(message.clone());
+    Ok(())
+  }
+}
+
+#[test]
+fn test_message_from_change() {
+  let order = Order {
+    id:    RecordId::from_ulid_u128(1),
+    total: 42,
+  };
+  let message =
+    SinkMessage::from_change(&ChangeEvent::Updated(order.clone())).unwrap();
+  assert_eq!(message.topic, "orders");
+  assert_eq!(message.kind, "updated");
+  // the record ID is the partition key
+  assert_eq!(message.key, order.id.to_string());
+
+  let payload: serde_json::Value =
+    serde_json::from_slice(&message.payload).unwrap();
+  assert_eq!(payload["type"], "orders.updated");
+  assert_eq!(payload["id"], order.id.to_string());
+  assert_eq!(payload["label"], order.id.to_string());
+  assert_eq!(payload["record"]["total"], 42);
+
+  let message =
+    SinkMessage::from_change(&ChangeEvent::Deleted(order.id)).unwrap();
+  assert_eq!(message.kind, "deleted");
+  assert_eq!(message.key, order.id.to_string());
+  let payload: serde_json::Value =
+    serde_json::from_slice(&message.payload).unwrap();
+  assert!(payload["label"].is_null());
+  assert!(payload["record"].is_null());
+}
+
+#[tokio::test]
+async fn test_forward_keeps_record_order() {
+  let sink = RecordingSink::default();
+  let a = Order {
+    id:    RecordId::from_ulid_u128(1),
+    total: 1,
+  };
+  let b = Order {
+    id:    RecordId::from_ulid_u128(2),
+    total: 2,
+  };
+
+  for event in [
+    ChangeEvent::Inserted(a.clone()),
+    ChangeEvent::Inserted(b.clone()),
+    ChangeEvent::Updated(a.clone()),
+    ChangeEvent::Deleted(a.id),
+  ] {
+    forward(&sink, &event).await.unwrap();
+  }
+
+  let messages = sink.messages.lock().unwrap();
+  let kinds: Vec<_> = messages
+    .iter()
+    .filter(|m| m.key == a.id.to_string())
+    .map(|m| m.kind.as_str())
+    .collect();
+  assert_eq!(kinds, ["inserted", "updated", "deleted"]);
+  assert_eq!(messages.len(), 4);
+}
+
+#[cfg(feature = "storage")]
+#[tokio::test]
+async fn test_blob_events_are_forwarded() {
+  use std::sync::Arc;
+
+  use storage::{BlobKey, BlobStorage, Bytes, UploadOptions};
+
+  use crate::BlobEventForwarder;
+
+  let sink = Arc::new(RecordingSink::default());
+  let storage = BlobStorage::new_memory()
+    .with_events(Arc::new(BlobEventForwarder::new(sink.clone())));
+  let key = BlobKey::new("photos/cat.png");
+
+  let data = Box::pin(futures::stream::once(async {
+    Ok(Bytes::from_static(b"meow"))
+  }));
+  storage
+    .put_stream(&key, data, UploadOptions::default())
+    .await
+    .unwrap();
+  storage.delete(&key).await.unwrap();
+
+  let messages = sink.messages.lock().unwrap();
+  let kinds: Vec<_> = messages.iter().map(|m| m.kind.as_str()).collect();
+  assert_eq!(kinds, ["created", "deleted"]);
+  assert!(
+    messages
+      .iter()
+      .all(|m| m.topic == "blobs" && m.key == key.to_string())
+  );
+}
@@ -0,0 +1,262 @@
+use std::sync::Arc;
+
+use arrow_json::{ArrayWriter, ReaderBuilder};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use belt::Belt;
+use db::Database;
+use futures::{TryFutureExt, try_join};
+use model::{ColumnKind, Model, schemars::JsonSchema};
+use parquet::arrow::{
+  AsyncArrowWriter, arrow_reader::ParquetRecordBatchReaderBuilder,
+};
+use serde_json::{Map, Value};
+use storage::{BlobKey, BlobStorage, UploadOptions};
+use tokio::io::BufReader;
+use tracing::debug;
+
+use crate::ExportError;
+
+/// How many bytes of an export are buffered between encoding and upload.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Options for [`export_parquet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParquetOptions {
+  /// How many records are read from the database at a time. Each page is
+  /// written as a row group.
+  pub batch_size: u32,
+}
+
+impl Default for ParquetOptions {
+  fn default() -> Self { Self { batch_size: 1024 } }
+}
+
+/// A top-level field of a model's serialized form, as a column.
+struct Column {
+  name:     String,
+  kind:     ColumnKind,
+  nullable: bool,
+}
+
+/// The columns of `M`, from the top-level properties of its JSON Schema.
+fn columns<M: Model + JsonSchema>() -> Vec<Column> {
+  let schema = M::json_schema().to_value();
+  let required: Vec<&str> = schema["required"]
+    .as_array()
+    .map(|names| names.iter().filter_map(Value::as_str).collect())
+    .unwrap_or_default();
+  let Some(properties) = schema["properties"].as_object() else {
+    return Vec::new();
+  };
+
+  properties
+    .iter()
+    .map(|(name, property)| {
+      let (kind, nullable) = column_kind(property);
+      Column {
+        name: name.clone(),
+        kind,
+        nullable: nullable || !required.contains(&name.as_str()),
+      }
+    })
+    .collect()
+}
+
+/// The kind of column for a property's schema, and whether it admits null.
+/// Anything but a single primitive type is stored as JSON.
+fn column_kind(property: &Value) -> (ColumnKind, bool) {
+  let types: Vec<&str> = match &property["type"] {
+    Value::String(ty) => vec![ty.as_str()],
+    Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+    _ => Vec::new(),
+  };
+  // `Option`s of non-primitive types are `anyOf` the type and null
+  let nullable = types.contains(&"null")
+    || property["anyOf"]
+      .as_array()
+      .is_some_and(|variants| variants.iter().any(|v| v["type"] == "null"));
+
+  let mut non_null = types.into_iter().filter(|ty| *ty != "null");
+  let kind = match (non_null.next(), non_null.next()) {
+    (Some("string"), None) if property["format"] == "date-time" => {
+      ColumnKind::Timestamp
+    }
+    (Some("string"), None) => ColumnKind::String,
+    (Some("integer"), None) => ColumnKind::I64,
+    (Some("number"), None) => ColumnKind::F64,
+    (Some("boolean"), None) => ColumnKind::Bool,
+    _ => ColumnKind::Json,
+  };
+  (kind, nullable)
+}
+
+fn data_type(kind: ColumnKind) -> DataType {
+  match kind {
+    ColumnKind::String | ColumnKind::Json => DataType::Utf8,
+    ColumnKind::I64 => DataType::Int64,
+    ColumnKind::F64 => DataType::Float64,
+    ColumnKind::Bool => DataType::Boolean,
+    ColumnKind::Timestamp => {
+      DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+    }
+  }
+}
+
+/// The Arrow schema of the Parquet files written for `M`, with a column for
+/// each top-level property of its JSON Schema.
+///
+/// Nested values are stored as JSON text, and timestamps to the
+/// microsecond.
+#[must_use]
+pub fn parquet_schema<M: Model + JsonSchema>() -> Schema {
+  Schema::new(
+    columns::<M>()
+      .into_iter()
+      .map(|c| Field::new(c.name, data_type(c.kind), c.nullable))
+      .collect::<Vec<_>>(),
+  )
+}
+
+/// The serialized form of `model`, restricted to `columns`, with nested
+/// values encoded as JSON text.
+fn to_row<M: Model>(
+  model: &M,
+  columns: &[Column],
+) -> Result<Value, ExportError> {
+  let mut fields =
+    match serde_json::to_value(model).map_err(ExportError::Serialization)? {
+      Value::Object(fields) => fields,
+      _ => Map::new(),
+    };
+
+  let row = columns
+    .iter()
+    .map(|column| {
+      let value = match (column.kind, fields.remove(&column.name)) {
+        (_, None | Some(Value::Null)) => Value::Null,
+        (ColumnKind::Json, Some(value)) => Value::String(value.to_string()),
+        (_, Some(value)) => value,
+      };
+      (column.name.clone(), value)
+    })
+    .collect();
+  Ok(Value::Object(row))
+}
+
+/// The model for a row read back from a file, decoding nested values.
+fn from_row<M: Model>(
+  mut row: Map<String, Value>,
+  columns: &[Column],
+) -> Result<M, ExportError> {
+  for column in columns.iter().filter(|c| c.kind == ColumnKind::Json) {
+    let decoded = match row.get(&column.name) {
+      Some(Value::String(json)) => {
+        serde_json::from_str(json).map_err(ExportError::Serialization)?
+      }
+      _ => continue,
+    };
+    row.insert(column.name.clone(), decoded);
+  }
+  serde_json::from_value(Value::Object(row)).map_err(ExportError::Serialization)
+}
+
+/// Writes every record of `M` to a Parquet file at `key`, replacing any
+/// existing file, and returns how many records were written.
+///
+/// Records are read a page at a time in ID order with
+/// [`Database::list_after`] and streamed into the upload, so the file is
+/// never held in memory. Every record which exists throughout the export is
+/// written once; records inserted or deleted meanwhile may be missed.
+pub async fn export_parquet<M: Model + JsonSchema>(
+  db: &Database<M>,
+  storage: &BlobStorage,
+  key: &BlobKey,
+  options: ParquetOptions,
+) -> Result<usize, ExportError> {
+  let schema = Arc::new(parquet_schema::<M>());
+  let columns = columns::<M>();
+  let (writer, reader) = tokio::io::duplex(PIPE_CAPACITY);
+
+  let upload = storage
+    .put_stream(
+      key,
+      Box::pin(Belt::new_from_async_buf_read(BufReader::new(reader))),
+      UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      },
+    )
+    .map_err(ExportError::from);
+
+  let encode = async {
+    let mut writer = AsyncArrowWriter::try_new(writer, schema.clone(), None)?;
+    let mut decoder = ReaderBuilder::new(schema).build_decoder()?;
+    let batch_size = options.batch_size.max(1);
+    let mut after = None;
+    let mut written = 0;
+    loop {
+      let page = db.list_after(after, batch_size).await?;
+      let rows = page
+        .iter()
+        .map(|model| to_row(model, &columns))
+        .collect::<Result<Vec<_>, _>>()?;
+      decoder.serialize(&rows)?;
+      if let Some(batch) = decoder.flush()? {
+        writer.write(&batch).await?;
+      }
+      written += page.len();
+      match page.last() {
+        Some(last) if page.len() == batch_size as usize => {
+          after = Some(last.id());
+        }
+        _ => break,
+      }
+    }
+    // closing the writer ends the upload
+    writer.close().await?;
+    Ok::<_, ExportError>(written)
+  };
+
+  let (written, ()) = try_join!(encode, upload)?;
+  debug!(%key, written, "exported records to parquet");
+  Ok(written)
+}
+
+/// Reads the records in the Parquet file at `key`, as written by
+/// [`export_parquet`], into the database, and returns how many were
+/// imported.
+///
+/// Records are upserted, so importing a file twice is harmless. The file is
+/// read into memory, as Parquet keeps its metadata at the end.
+pub async fn import_parquet<M: Model + JsonSchema>(
+  db: &Database<M>,
+  storage: &BlobStorage,
+  key: &BlobKey,
+) -> Result<usize, ExportError> {
+  let stream = storage.get_stream(key).await?;
+  let bytes = Belt::from_response_stream(stream)
+    .collect_bytes()
+    .await
+    .map_err(|e| ExportError::Read(key.clone(), e))?;
+  let columns = columns::<M>();
+  let batches = ParquetRecordBatchReaderBuilder::try_new(bytes)?
+    .build()?
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let mut imported = 0;
+  for batch in batches.iter().filter(|b| b.num_rows() > 0) {
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write(batch)?;
+    writer.finish()?;
+    let rows: Vec<Map<String, Value>> =
+      serde_json::from_slice(&writer.into_inner())
+        .map_err(ExportError::Serialization)?;
+
+    for row in rows {
+      db.upsert(&from_row::<M>(row, &columns)?).await?;
+      imported += 1;
+    }
+  }
+  debug!(%key, imported, "imported records from parquet");
+  Ok(imported)
+}
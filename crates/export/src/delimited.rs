@@ -0,0 +1,342 @@
+use std::{collections::BTreeMap, io};
+
+use db::Database;
+use model::Model;
+use serde::de::{
+  self, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, Visitor,
+};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::ExportError;
+
+/// How many records are read from the database at a time.
+const PAGE_SIZE: u32 = 1024;
+
+/// The [`export_csv`] column holding each record's
+/// [display label](Model::display_label). It's ignored on import.
+pub const LABEL_COLUMN: &str = "@label";
+
+/// A row [`import_csv`] skipped.
+#[derive(Debug)]
+pub struct CsvRowError {
+  /// The line the row starts on, counting the header as line 1.
+  pub line:  u64,
+  /// Why the row was skipped.
+  pub error: ExportError,
+}
+
+/// The outcome of [`import_csv`].
+#[derive(Debug, Default)]
+pub struct CsvImport {
+  /// How many rows were imported.
+  pub imported: usize,
+  /// The rows which couldn't be imported, in order.
+  pub failed:   Vec<CsvRowError>,
+}
+
+/// Writes the `columns` of every record of `M` to `writer` as CSV, with a
+/// header row, and returns how many records were written.
+///
+/// Each column names a field of the serialized model, with nested fields
+/// separated by `.`, e.g. `address.city`. Strings are written as they are,
+/// missing fields and nulls as empty cells, and nested objects and arrays as
+/// JSON. The [`LABEL_COLUMN`] column holds each record's display label.
+/// Records are read a page at a time in ID order with
+/// [`Database::list_after`], so they are never all held in memory, and each
+/// record which exists throughout the export is written once.
+pub async fn export_csv<M: Model, W: io::Write>(
+  db: &Database<M>,
+  writer: W,
+  columns: &[&str],
+) -> Result<usize, ExportError> {
+  let mut writer = csv::Writer::from_writer(writer);
+  writer.write_record(columns)?;
+
+  let mut after = None;
+  let mut written = 0;
+  loop {
+    let page = db.list_after(after, PAGE_SIZE).await?;
+    for model in &page {
+      let value =
+        serde_json::to_value(model).map_err(ExportError::Serialization)?;
+      writer.write_record(columns.iter().map(|column| {
+        if *column == LABEL_COLUMN {
+          model.display_label()
+        } else {
+          cell(field(&value, column))
+        }
+      }))?;
+    }
+    written += page.len();
+    match page.last() {
+      Some(last) if page.len() == PAGE_SIZE as usize => {
+        after = Some(last.id());
+      }
+      _ => break,
+    }
+  }
+  writer.flush().map_err(csv::Error::from)?;
+
+  debug!(written, "exported records to csv");
+  Ok(written)
+}
+
+/// The field at the `.`-separated `path` of a serialized model.
+fn field<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+  path
+    .split('.')
+    .try_fold(value, |value, segment| match value {
+      Value::Object(fields) => fields.get(segment),
+      Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+      _ => None,
+    })
+}
+
+fn cell(value: Option<&Value>) -> String {
+  match value {
+    None | Some(Value::Null) => String::new(),
+    Some(Value::String(s)) => s.clone(),
+    Some(value) => value.to_string(),
+  }
+}
+
+/// Reads CSV with a header row, as written by [`export_csv`], from `reader`
+/// and upserts a record of `M` for each row.
+///
+/// Each cell is read as the type of the field its column names, so a
+/// string field may hold digits; empty cells are `None` for optional
+/// fields. Nested fields are given either as `.`-separated columns or as
+/// JSON. Rows which can't be parsed or written are skipped and reported in
+/// [`CsvImport::failed`], rather than aborting the import.
+pub async fn import_csv<M: Model, R: io::Read>(
+  db: &Database<M>,
+  reader: R,
+) -> Result<CsvImport, ExportError> {
+  let mut reader = csv::Reader::from_reader(reader);
+  let headers = reader.headers()?.clone();
+  let mut report = CsvImport::default();
+
+  for record in reader.records() {
+    let record = match record {
+      Ok(record) => record,
+      Err(e) if e.is_io_error() => return Err(e.into()),
+      Err(e) => {
+        let line = e.position().map_or(0, csv::Position::line);
+        report.failed.push(CsvRowError {
+          line,
+          error: e.into(),
+        });
+        continue;
+      }
+    };
+    let line = record.position().map_or(0, csv::Position::line);
+
+    let result = match row_to_model::<M>(&headers, &record) {
+      Ok(model) => db.upsert(&model).await.map_err(ExportError::from),
+      Err(e) => Err(e),
+    };
+    match result {
+      Ok(_) => report.imported += 1,
+      Err(error) => report.failed.push(CsvRowError { line, error }),
+    }
+  }
+
+  debug!(
+    imported = report.imported,
+    failed = report.failed.len(),
+    "imported records from csv"
+  );
+  Ok(report)
+}
+
+fn row_to_model<M: Model>(
+  headers: &csv::StringRecord,
+  record: &csv::StringRecord,
+) -> Result<M, ExportError> {
+  let mut row = Cell::Object(BTreeMap::new());
+  for (header, value) in headers.iter().zip(record.iter()) {
+    if header != LABEL_COLUMN {
+      row.insert(header, value);
+    }
+  }
+  M::deserialize(&row).map_err(ExportError::Serialization)
+}
+
+/// A row of CSV cells, with `.`-separated columns nested into objects.
+enum Cell<'a> {
+  Text(&'a str),
+  Object(BTreeMap<&'a str, Cell<'a>>),
+}
+
+impl<'a> Cell<'a> {
+  fn insert(&mut self, path: &'a str, value: &'a str) {
+    let Cell::Object(fields) = self else { return };
+    match path.split_once('.') {
+      Some((head, rest)) => fields
+        .entry(head)
+        .or_insert_with(|| Cell::Object(BTreeMap::new()))
+        .insert(rest, value),
+      None => {
+        fields.insert(path, Cell::Text(value));
+      }
+    }
+  }
+
+  fn parse<T: std::str::FromStr>(&self) -> Result<T, serde_json::Error>
+  where
+    T::Err: std::fmt::Display,
+  {
+    match self {
+      Cell::Text(text) => text.trim().parse().map_err(de::Error::custom),
+      Cell::Object(_) => {
+        Err(de::Error::custom("expected a value, found nested columns"))
+      }
+    }
+  }
+}
+
+/// Implements `Deserializer` methods which parse a text cell as the type
+/// asked for.
+macro_rules! deserialize_parsed {
+  ($($method:ident => $visit:ident),* $(,)?) => {$(
+    fn $method<V: Visitor<'de>>(
+      self,
+      visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+      visitor.$visit(self.parse()?)
+    }
+  )*};
+}
+
+/// Deserializes cells as the types asked for, rather than the types they
+/// look like, as CSV is untyped.
+impl<'de> Deserializer<'de> for &Cell<'_> {
+  type Error = serde_json::Error;
+
+  fn deserialize_any<V: Visitor<'de>>(
+    self,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    match self {
+      Cell::Text(text) if text.starts_with(['{', '[']) => {
+        serde_json::from_str::<Value>(text)?.deserialize_any(visitor)
+      }
+      Cell::Text(text) => visitor.visit_str(text),
+      Cell::Object(fields) => visitor.visit_map(CellMap {
+        fields: fields.iter(),
+        value:  None,
+      }),
+    }
+  }
+
+  deserialize_parsed! {
+    deserialize_bool => visit_bool,
+    deserialize_i8 => visit_i8,
+    deserialize_i16 => visit_i16,
+    deserialize_i32 => visit_i32,
+    deserialize_i64 => visit_i64,
+    deserialize_i128 => visit_i128,
+    deserialize_u8 => visit_u8,
+    deserialize_u16 => visit_u16,
+    deserialize_u32 => visit_u32,
+    deserialize_u64 => visit_u64,
+    deserialize_u128 => visit_u128,
+    deserialize_f32 => visit_f32,
+    deserialize_f64 => visit_f64,
+    deserialize_char => visit_char,
+  }
+
+  fn deserialize_str<V: Visitor<'de>>(
+    self,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    match self {
+      Cell::Text(text) => visitor.visit_str(text),
+      Cell::Object(_) => self.deserialize_any(visitor),
+    }
+  }
+
+  fn deserialize_string<V: Visitor<'de>>(
+    self,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    self.deserialize_str(visitor)
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(
+    self,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    match self {
+      Cell::Text("") => visitor.visit_none(),
+      _ => visitor.visit_some(self),
+    }
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    match self {
+      Cell::Text(text) if !text.starts_with('{') => {
+        let text: &str = text;
+        text
+          .into_deserializer()
+          .deserialize_enum(name, variants, visitor)
+      }
+      Cell::Text(text) => serde_json::from_str::<Value>(text)?
+        .deserialize_enum(name, variants, visitor),
+      Cell::Object(_) => Err(de::Error::custom(format!(
+        "expected a variant of {name}, found nested columns"
+      ))),
+    }
+  }
+
+  serde::forward_to_deserialize_any! {
+    bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+    identifier ignored_any
+  }
+}
+
+struct CellMap<'a, 'b> {
+  fields: std::collections::btree_map::Iter<'b, &'a str, Cell<'a>>,
+  value:  Option<&'b Cell<'a>>,
+}
+
+impl<'de> MapAccess<'de> for CellMap<'_, '_> {
+  type Error = serde_json::Error;
+
+  fn next_key_seed<K: DeserializeSeed<'de>>(
+    &mut self,
+    seed: K,
+  ) -> Result<Option<K::Value>, Self::Error> {
+    let Some((key, value)) = self.fields.next() else {
+      return Ok(None);
+    };
+    self.value = Some(value);
+    let key: &str = key;
+    seed.deserialize(key.into_deserializer()).map(Some)
+  }
+
+  fn next_value_seed<V: DeserializeSeed<'de>>(
+    &mut self,
+    seed: V,
+  ) -> Result<V::Value, Self::Error> {
+    let value = self
+      .value
+      .take()
+      .ok_or_else(|| de::Error::custom("value requested before key"))?;
+    seed.deserialize(value)
+  }
+}
@@ -0,0 +1,40 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use storage::{BlobKey, BlobStorageError};
+use thiserror::Error;
+
+/// Errors that can occur exporting and importing records.
+#[derive(Debug, Error, Diagnostic)]
+pub enum ExportError {
+  /// The database failed.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// The blob storage failed, or the file to import doesn't exist.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Storage(#[from] BlobStorageError),
+
+  /// The file to import couldn't be read.
+  #[error("Failed to read {0}")]
+  Read(BlobKey, #[source] std::io::Error),
+
+  /// A record couldn't be converted to or from its serialized form.
+  #[error("Failed to (de)serialize record")]
+  Serialization(#[source] serde_json::Error),
+
+  /// CSV couldn't be written or read.
+  #[error("CSV encoding failed")]
+  Csv(#[from] csv::Error),
+
+  /// Records couldn't be converted to or from Arrow record batches.
+  #[cfg(feature = "parquet")]
+  #[error("Arrow conversion failed")]
+  Arrow(#[from] arrow_schema::ArrowError),
+
+  /// The Parquet file couldn't be written or read.
+  #[cfg(feature = "parquet")]
+  #[error("Parquet encoding failed")]
+  Parquet(#[from] parquet::errors::ParquetError),
+}
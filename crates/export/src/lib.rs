@@ -0,0 +1,27 @@
+//! Exporting model records to files in blob storage, and importing them
+//! back.
+//!
+//! With the `parquet` feature, [`export_parquet`] writes every record of a
+//! model to a Parquet file, with a column for each top-level field of the
+//! model's JSON Schema, and [`import_parquet`] reads such a file back into
+//! the database.
+//!
+//! For quick operational tasks, [`export_csv`] writes chosen fields of every
+//! record as CSV, and [`import_csv`] reads CSV back, reporting the rows it
+//! couldn't import rather than failing on the first.
+
+#[cfg(feature = "parquet")]
+mod columnar;
+mod delimited;
+mod error;
+#[cfg(test)]
+mod tests;
+
+#[cfg(feature = "parquet")]
+pub use self::columnar::{
+  ParquetOptions, export_parquet, import_parquet, parquet_schema,
+};
+pub use self::{
+  delimited::{CsvImport, CsvRowError, LABEL_COLUMN, export_csv, import_csv},
+  error::ExportError,
+};
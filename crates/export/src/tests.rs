@@ -0,0 +1,190 @@
+use chrono::{DateTime, Utc};
+use db::Database;
+use model::{Model, RecordId};
+use serde::{Deserialize, Serialize};
+
+use crate::{ExportError, LABEL_COLUMN, export_csv, import_csv};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[cfg_attr(feature = "parquet", model(json_schema))]
+#[model(table = "images", label = "{name} ({bytes} bytes)")]
+struct Image {
+  #[model(id)]
+  id:          RecordId<Image>,
+  name:        String,
+  bytes:       i64,
+  public:      bool,
+  uploaded_at: DateTime<Utc>,
+  caption:     Option<String>,
+  exif:        serde_json::Value,
+  tags:        Vec<String>,
+}
+
+fn image(i: u128) -> Image {
+  Image {
+    id:          RecordId::from_ulid_u128(i),
+    name:        format!("image {i}.png"),
+    bytes:       1000 * i64::try_from(i).unwrap(),
+    public:      i.is_multiple_of(2),
+    uploaded_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+    caption:     (i == 1).then(|| "a \"quoted\" caption".to_owned()),
+    exif:        serde_json::json!({ "width": 640, "height": 480 }),
+    tags:        vec!["a".to_owned(), "b".to_owned()],
+  }
+}
+
+async fn seeded(count: u128) -> (Database<Image>, Vec<Image>) {
+  let db = Database::new_mock();
+  let images: Vec<_> = (1..=count).map(image).collect();
+  for image in &images {
+    db.insert(image).await.unwrap();
+  }
+  (db, images)
+}
+
+// --- Parquet ---
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_parquet_schema() {
+  use arrow_schema::{DataType, TimeUnit};
+
+  let schema = crate::parquet_schema::<Image>();
+  let field = |name: &str| schema.field_with_name(name).unwrap().clone();
+
+  assert_eq!(field("id").data_type(), &DataType::Utf8);
+  assert_eq!(field("bytes").data_type(), &DataType::Int64);
+  assert_eq!(field("public").data_type(), &DataType::Boolean);
+  assert_eq!(
+    field("uploaded_at").data_type(),
+    &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+  );
+  // nested values are JSON text
+  assert_eq!(field("exif").data_type(), &DataType::Utf8);
+  assert_eq!(field("tags").data_type(), &DataType::Utf8);
+
+  assert!(field("caption").is_nullable());
+  assert!(!field("name").is_nullable());
+}
+
+#[cfg(feature = "parquet")]
+#[tokio::test]
+async fn test_parquet_round_trip() {
+  use storage::{BlobKey, BlobStorage};
+
+  use crate::{ParquetOptions, export_parquet, import_parquet};
+
+  let (source, images) = seeded(5).await;
+  let storage = BlobStorage::new_memory();
+  let key = BlobKey::new("exports/images.parquet");
+
+  let written =
+    export_parquet(&source, &storage, &key, ParquetOptions { batch_size: 2 })
+      .await
+      .unwrap();
+  assert_eq!(written, 5);
+
+  let target = Database::new_mock();
+  assert_eq!(import_parquet(&target, &storage, &key).await.unwrap(), 5);
+  for image in &images {
+    assert_eq!(&target.get_or_error(image.id).await.unwrap(), image);
+  }
+
+  // importing again upserts rather than failing
+  assert_eq!(import_parquet(&target, &storage, &key).await.unwrap(), 5);
+  assert_eq!(target.count().await.unwrap(), 5);
+}
+
+// --- CSV ---
+
+#[tokio::test]
+async fn test_csv_export_flattens_fields() {
+  let (db, _) = seeded(2).await;
+  let mut out = Vec::new();
+
+  let written = export_csv(&db, &mut out, &[
+    "name",
+    "caption",
+    "exif.width",
+    "tags",
+    "missing",
+  ])
+  .await
+  .unwrap();
+  assert_eq!(written, 2);
+
+  let mut lines: Vec<_> = std::str::from_utf8(&out).unwrap().lines().collect();
+  assert_eq!(lines.remove(0), "name,caption,exif.width,tags,missing");
+  lines.sort_unstable();
+  assert_eq!(lines, [
+    r#"image 1.png,"a ""quoted"" caption",640,"[""a"",""b""]","#,
+    r#"image 2.png,,640,"[""a"",""b""]","#,
+  ]);
+}
+
+#[tokio::test]
+async fn test_csv_export_labels() {
+  let (db, _) = seeded(2).await;
+  let mut out = Vec::new();
+  export_csv(&db, &mut out, &[LABEL_COLUMN, "public"])
+    .await
+    .unwrap();
+
+  let mut lines: Vec<_> = std::str::from_utf8(&out).unwrap().lines().collect();
+  assert_eq!(lines.remove(0), "@label,public");
+  lines.sort_unstable();
+  assert_eq!(lines, [
+    "image 1.png (1000 bytes),false",
+    "image 2.png (2000 bytes),true"
+  ]);
+}
+
+#[tokio::test]
+async fn test_csv_round_trip() {
+  let (source, images) = seeded(3).await;
+  let columns = [
+    LABEL_COLUMN,
+    "id",
+    "name",
+    "bytes",
+    "public",
+    "uploaded_at",
+    "caption",
+    "exif",
+    "tags",
+  ];
+  let mut out = Vec::new();
+  export_csv(&source, &mut out, &columns).await.unwrap();
+
+  let target = Database::new_mock();
+  let report = import_csv(&target, out.as_slice()).await.unwrap();
+  assert_eq!(report.imported, 3);
+  assert!(report.failed.is_empty());
+  for image in &images {
+    assert_eq!(&target.get_or_error(image.id).await.unwrap(), image);
+  }
+}
+
+#[tokio::test]
+async fn test_csv_import_reports_bad_rows() {
+  let db = Database::<Image>::new_mock();
+  let id = RecordId::<Image>::from_ulid_u128(1);
+  let csv = format!(
+    "id,name,bytes,public,uploaded_at,exif.width,tags\n{id},123,10,true,\
+     2024-01-01T00:00:00Z,1,[]\n{id},b,not a \
+     number,true,2024-01-01T00:00:00Z,1,[]\n{id},c,10\n"
+  );
+
+  let report = import_csv(&db, csv.as_bytes()).await.unwrap();
+  assert_eq!(report.imported, 1);
+  // digits are kept as text for string fields
+  assert_eq!(db.get_or_error(id).await.unwrap().name, "123");
+
+  let lines: Vec<_> = report.failed.iter().map(|f| f.line).collect();
+  assert_eq!(lines, [3, 4]);
+  assert!(matches!(
+    report.failed[0].error,
+    ExportError::Serialization(_)
+  ));
+  assert!(matches!(report.failed[1].error, ExportError::Csv(_)));
+}
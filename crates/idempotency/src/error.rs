@@ -0,0 +1,31 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// Errors that can occur executing an idempotent operation.
+#[derive(Debug, Error, Diagnostic)]
+pub enum IdempotencyError {
+  /// The database failed.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// Another attempt with the same key is still running.
+  #[error("Operation with idempotency key {0:?} is already in progress")]
+  InProgress(String),
+
+  /// The result couldn't be stored, or the stored result couldn't be read
+  /// back as the expected type.
+  #[error("Failed to (de)serialize the result for idempotency key {key:?}")]
+  Serialization {
+    /// The idempotency key.
+    key:    String,
+    /// Why (de)serialization failed.
+    #[source]
+    source: serde_json::Error,
+  },
+
+  /// The operation failed. Nothing was recorded, so it may be retried.
+  #[error("Operation failed: {0}")]
+  Operation(#[diagnostic_source] miette::Report),
+}
@@ -0,0 +1,278 @@
+//! Idempotency keys for operations retried by clients, stored in a database.
+//!
+//! External clients retry writes when a response is lost, so a write may
+//! arrive more than once. Clients send an idempotency key with each logical
+//! request, and [`Idempotency::execute_idempotent`] runs the operation for a
+//! key only once: the first successful result is stored in an
+//! [`IdempotencyRecord`], and retries with the same key get that result back
+//! instead of applying the operation again. Records expire after a
+//! time-to-live, after which the key may be reused.
+//!
+//! A failed operation records nothing, so it can be retried. While an
+//! operation runs, concurrent attempts with its key fail with
+//! [`IdempotencyError::InProgress`]. The running operation's claim on the key
+//! lasts for a shorter lease, so if the process running it crashes, another
+//! attempt takes the key over once the lease runs out. An operation which
+//! outlives its lease may run twice, so the lease should be longer than
+//! operations take.
+
+mod error;
+#[cfg(test)]
+mod tests;
+
+use std::{
+  fmt,
+  ops::Bound,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use db::{Clock, Database, DatabaseError, SystemClock};
+use model::{IndexValue, Model, RecordId};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+pub use self::error::IdempotencyError;
+
+/// How long results are kept by default.
+const DEFAULT_TTL: Duration = Duration::from_hours(24);
+/// How long a running operation holds its key by default.
+const DEFAULT_CLAIM_TTL: Duration = Duration::from_mins(5);
+
+/// The stored outcome of an operation run with an idempotency key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "idempotency_keys",
+  index(name = "expiry", kind = timestamp, extract =
+    |m| vec![IndexValue::new_timestamp(&m.expires_at)]
+  ),
+)]
+pub struct IdempotencyRecord {
+  /// The record's ID, derived from its key.
+  #[model(id)]
+  pub id:            RecordId<IdempotencyRecord>,
+  /// The idempotency key.
+  pub key:           String,
+  /// The serialized result, or `None` while the operation is running.
+  pub result:        Option<serde_json::Value>,
+  /// The hex SHA-256 digest of the serialized result, for comparing results
+  /// without deserializing them.
+  pub result_hash:   Option<String>,
+  /// When the record expires and the key may be reused.
+  pub expires_at:    DateTime<Utc>,
+  /// While the operation is running, when another attempt may take the key
+  /// over.
+  #[serde(default)]
+  pub claimed_until: Option<DateTime<Utc>>,
+}
+
+impl IdempotencyRecord {
+  /// Whether the key may be claimed at `now`: the record has expired, or
+  /// its operation's claim has run out without a result.
+  fn is_claimable(&self, now: DateTime<Utc>) -> bool {
+    self.expires_at <= now
+      || (self.result.is_none()
+        && self.claimed_until.is_some_and(|until| until <= now))
+  }
+}
+
+/// Runs operations at most once per idempotency key.
+#[derive(Clone)]
+pub struct Idempotency {
+  db:        Database<IdempotencyRecord>,
+  ttl:       Duration,
+  claim_ttl: Duration,
+  clock:     Arc<dyn Clock>,
+}
+
+impl fmt::Debug for Idempotency {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Idempotency")
+      .field("ttl", &self.ttl)
+      .field("claim_ttl", &self.claim_ttl)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Idempotency {
+  /// Creates a new [`Idempotency`], keeping results for a day and letting
+  /// running operations hold their keys for five minutes.
+  #[must_use]
+  pub fn new(db: Database<IdempotencyRecord>) -> Self {
+    Self {
+      db,
+      ttl: DEFAULT_TTL,
+      claim_ttl: DEFAULT_CLAIM_TTL,
+      clock: SystemClock::shared(),
+    }
+  }
+
+  /// Sets how long results are kept, and so how long a key blocks new
+  /// operations.
+  #[must_use]
+  pub const fn with_ttl(mut self, ttl: Duration) -> Self {
+    self.ttl = ttl;
+    self
+  }
+
+  /// Sets how long a running operation holds its key before another attempt
+  /// may take it over.
+  #[must_use]
+  pub const fn with_claim_ttl(mut self, claim_ttl: Duration) -> Self {
+    self.claim_ttl = claim_ttl;
+    self
+  }
+
+  /// Sets the clock used to expire records.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// Runs `op` unless an operation with `key` has already succeeded, in
+  /// which case its result is returned instead.
+  ///
+  /// Fails with [`IdempotencyError::InProgress`] if an operation with `key`
+  /// is still running, and with [`IdempotencyError::Operation`] if `op`
+  /// fails, in which case the key is freed for a retry. If the key is taken
+  /// over while `op` runs, its outcome isn't recorded.
+  pub async fn execute_idempotent<T, F, Fut>(
+    &self,
+    key: &str,
+    op: F,
+  ) -> Result<T, IdempotencyError>
+  where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, miette::Report>>,
+  {
+    let now = self.clock.now();
+    let id = record_id(key);
+    let expires_at = clock::after(now, self.ttl);
+    let claimed_until = clock::after(now, self.claim_ttl);
+
+    // claim the key, unless a live record or running operation holds it
+    let claimed = AtomicBool::new(false);
+    let record = self
+      .db
+      .upsert_with(id, &|record: Option<IdempotencyRecord>| match record {
+        Some(record) if !record.is_claimable(now) => {
+          claimed.store(false, Ordering::Relaxed);
+          record
+        }
+        _ => {
+          claimed.store(true, Ordering::Relaxed);
+          IdempotencyRecord {
+            id,
+            key: key.to_owned(),
+            result: None,
+            result_hash: None,
+            expires_at,
+            claimed_until: Some(claimed_until),
+          }
+        }
+      })
+      .await?;
+
+    if !claimed.load(Ordering::Relaxed) {
+      let Some(result) = record.result else {
+        return Err(IdempotencyError::InProgress(key.to_owned()));
+      };
+      debug!(key, "replaying stored result");
+      return serde_json::from_value(result).map_err(|source| {
+        IdempotencyError::Serialization {
+          key: key.to_owned(),
+          source,
+        }
+      });
+    }
+
+    // the outcome is only written while the claim is unchanged, so a claim
+    // taken over after its lease ran out isn't overwritten
+    let claimed_at = match self.db.updated_at(id).await? {
+      Some(at) if self.db.get(id).await?.as_ref() == Some(&record) => at,
+      _ => return Err(IdempotencyError::InProgress(key.to_owned())),
+    };
+
+    let value = match op().await {
+      Ok(value) => value,
+      Err(error) => {
+        // expire the claim, freeing the key for a retry
+        let released = IdempotencyRecord {
+          expires_at: self.clock.now(),
+          claimed_until: None,
+          ..record
+        };
+        match self.db.update_if_unchanged(&released, claimed_at).await {
+          Ok(()) | Err(DatabaseError::NotFound(_)) => {}
+          Err(DatabaseError::Conflict(_)) => {
+            warn!(key, "claim was taken over while the operation ran");
+          }
+          Err(e) => return Err(e.into()),
+        }
+        return Err(IdempotencyError::Operation(error));
+      }
+    };
+
+    let result = serde_json::to_value(&value).map_err(|source| {
+      IdempotencyError::Serialization {
+        key: key.to_owned(),
+        source,
+      }
+    })?;
+    let result_hash = format!("{:x}", Sha256::digest(result.to_string()));
+    let completed = IdempotencyRecord {
+      result: Some(result),
+      result_hash: Some(result_hash),
+      claimed_until: None,
+      ..record
+    };
+    match self.db.update_if_unchanged(&completed, claimed_at).await {
+      Ok(()) => debug!(key, "stored result"),
+      Err(DatabaseError::Conflict(_) | DatabaseError::NotFound(_)) => {
+        warn!(key, "claim was taken over while the operation ran");
+      }
+      Err(e) => return Err(e.into()),
+    }
+    Ok(value)
+  }
+
+  /// Deletes expired records, returning how many were deleted.
+  pub async fn purge_expired(&self) -> Result<usize, IdempotencyError> {
+    let now = IndexValue::new_timestamp(&self.clock.now());
+    let expired = self
+      .db
+      .find_by_index_range(
+        IdempotencyRecordIndexSelector::Expiry,
+        Bound::Unbounded,
+        Bound::Included(&now),
+      )
+      .await?;
+
+    let mut purged = 0;
+    for record in expired {
+      match self.db.delete(record.id).await {
+        Ok(()) => purged += 1,
+        Err(DatabaseError::NotFound(_)) => {}
+        Err(e) => return Err(e.into()),
+      }
+    }
+    debug!(purged, "purged expired idempotency records");
+    Ok(purged)
+  }
+}
+
+/// Derives a record's ID from its key, so every process finds the same
+/// record.
+fn record_id(key: &str) -> RecordId<IdempotencyRecord> {
+  let digest = Sha256::digest(key.as_bytes());
+  let mut bytes = [0; 16];
+  bytes.copy_from_slice(&digest[..16]);
+  RecordId::from_ulid_u128(u128::from_be_bytes(bytes))
+}
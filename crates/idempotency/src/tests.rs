@@ -0,0 +1,154 @@
+use std::{
+  sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+  },
+  time::Duration,
+};
+
+use db::{Database, ManualClock, MockDatabase};
+
+use crate::{Idempotency, IdempotencyError};
+
+const TTL: Duration = Duration::from_mins(1);
+
+fn setup() -> (Idempotency, ManualClock) {
+  let clock = ManualClock::default();
+  let db = Database::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let idempotency = Idempotency::new(db)
+    .with_ttl(TTL)
+    .with_clock(Arc::new(clock.clone()));
+  (idempotency, clock)
+}
+
+#[tokio::test]
+async fn test_retries_return_original_result() {
+  let (idempotency, _) = setup();
+  let runs = AtomicU32::new(0);
+  let op = || async { Ok(runs.fetch_add(1, Ordering::SeqCst) + 100) };
+
+  let first: u32 = idempotency
+    .execute_idempotent("charge-1", op)
+    .await
+    .unwrap();
+  let retry: u32 = idempotency
+    .execute_idempotent("charge-1", op)
+    .await
+    .unwrap();
+  assert_eq!((first, retry), (100, 100));
+  assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+  // other keys run their own operations
+  let other: u32 = idempotency
+    .execute_idempotent("charge-2", op)
+    .await
+    .unwrap();
+  assert_eq!(other, 101);
+}
+
+#[tokio::test]
+async fn test_failed_operation_can_be_retried() {
+  let (idempotency, _) = setup();
+
+  let result = idempotency
+    .execute_idempotent("charge", || async {
+      Err::<u32, _>(miette::miette!("card declined"))
+    })
+    .await;
+  assert!(matches!(result, Err(IdempotencyError::Operation(_))));
+
+  let value = idempotency
+    .execute_idempotent("charge", || async { Ok(7_u32) })
+    .await
+    .unwrap();
+  assert_eq!(value, 7);
+}
+
+#[tokio::test]
+async fn test_running_operation_blocks_key() {
+  let (idempotency, _) = setup();
+
+  let nested = idempotency
+    .execute_idempotent("charge", || async {
+      let retry = idempotency
+        .execute_idempotent("charge", || async { Ok(2_u32) })
+        .await;
+      assert!(matches!(retry, Err(IdempotencyError::InProgress(_))));
+      Ok(1_u32)
+    })
+    .await
+    .unwrap();
+  assert_eq!(nested, 1);
+}
+
+#[tokio::test]
+async fn test_expired_records_are_reused_and_purged() {
+  let (idempotency, clock) = setup();
+  let value = |v: u32| move || async move { Ok(v) };
+
+  idempotency.execute_idempotent("a", value(1)).await.unwrap();
+  clock.advance(TTL);
+  idempotency.execute_idempotent("b", value(2)).await.unwrap();
+
+  // the first key expired, so its operation runs again
+  let again: u32 = idempotency.execute_idempotent("a", value(3)).await.unwrap();
+  assert_eq!(again, 3);
+
+  clock.advance(TTL);
+  assert_eq!(idempotency.purge_expired().await.unwrap(), 2);
+  assert_eq!(idempotency.purge_expired().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_expired_claims_are_taken_over() {
+  let (idempotency, clock) = setup();
+  let idempotency = idempotency.with_claim_ttl(Duration::from_secs(5));
+
+  let first = idempotency
+    .execute_idempotent("charge", || async {
+      // the claim runs out while the operation is still going
+      clock.advance(Duration::from_secs(5));
+      let retry = idempotency
+        .execute_idempotent("charge", || async { Ok(2_u32) })
+        .await;
+      assert_eq!(retry.unwrap(), 2);
+      Ok(1_u32)
+    })
+    .await
+    .unwrap();
+  assert_eq!(first, 1);
+
+  // the slow operation doesn't overwrite the result of the one which took
+  // its key over
+  let replay: u32 = idempotency
+    .execute_idempotent("charge", || async { Ok(3_u32) })
+    .await
+    .unwrap();
+  assert_eq!(replay, 2);
+}
+
+#[tokio::test]
+async fn test_failed_operation_keeps_taken_over_result() {
+  let (idempotency, clock) = setup();
+  let idempotency = idempotency.with_claim_ttl(Duration::from_secs(5));
+
+  let result = idempotency
+    .execute_idempotent("charge", || async {
+      clock.advance(Duration::from_secs(5));
+      idempotency
+        .execute_idempotent("charge", || async { Ok(2_u32) })
+        .await
+        .unwrap();
+      Err::<u32, _>(miette::miette!("card declined"))
+    })
+    .await;
+  assert!(matches!(result, Err(IdempotencyError::Operation(_))));
+
+  let replay: u32 = idempotency
+    .execute_idempotent("charge", || async { Ok(3_u32) })
+    .await
+    .unwrap();
+  assert_eq!(replay, 2);
+}
@@ -0,0 +1,27 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use model::RecordId;
+use thiserror::Error;
+
+use crate::Job;
+
+/// Errors that can occur working with a job queue.
+#[derive(Debug, Error, Diagnostic)]
+pub enum JobError {
+  /// The database failed, or the job doesn't exist.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// The payload couldn't be serialized.
+  #[error("Failed to serialize job payload")]
+  Payload(#[source] serde_json::Error),
+
+  /// The lease expired and the job was leased again, or the job is gone.
+  #[error("Lease on job {0} was lost")]
+  LeaseLost(RecordId<Job>),
+
+  /// Only dead jobs can be requeued or discarded.
+  #[error("Job {0} is not dead")]
+  NotDead(RecordId<Job>),
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use model::{IndexValue, Model, RecordId};
+use serde::{Deserialize, Serialize};
+
+/// The lifecycle state of a [`Job`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+  /// Waiting to be leased once `available_at` passes.
+  Pending,
+  /// Leased by a worker until `available_at`, after which it can be leased
+  /// again.
+  Leased,
+  /// Failed too many times, or has an unreadable payload. Dead jobs are
+  /// never leased; see [`JobQueue::requeue`](crate::JobQueue::requeue).
+  Dead,
+}
+
+impl JobState {
+  /// Returns the name of the state, as stored.
+  #[must_use]
+  pub const fn as_str(self) -> &'static str {
+    match self {
+      JobState::Pending => "pending",
+      JobState::Leased => "leased",
+      JobState::Dead => "dead",
+    }
+  }
+}
+
+/// A job stored in a queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "jobs",
+  index(name = "ready", extract = Job::ready_index),
+  index(name = "queue_state", extract =
+    |m| vec![IndexValue::new([m.queue.as_str(), m.state.as_str()])]
+  ),
+)]
+pub struct Job {
+  /// The job's ID.
+  #[model(id)]
+  pub id:           RecordId<Job>,
+  /// The queue the job belongs to.
+  pub queue:        String,
+  /// The serialized payload.
+  pub payload:      serde_json::Value,
+  /// The job's state.
+  pub state:        JobState,
+  /// How many times the job has been leased.
+  pub attempts:     u32,
+  /// When the job can next be leased: when it becomes ready if pending, or
+  /// when its lease expires if leased.
+  pub available_at: DateTime<Utc>,
+  /// The error from the last failed attempt.
+  pub last_error:   Option<String>,
+  /// When the job was enqueued.
+  pub created_at:   DateTime<Utc>,
+}
+
+impl Job {
+  /// Leasable jobs are indexed by queue, then by when they're available, so
+  /// a range query finds the jobs which are ready in order.
+  fn ready_index(&self) -> Vec<IndexValue> {
+    if self.state == JobState::Dead {
+      return Vec::new();
+    }
+    vec![ready_key(&self.queue, &self.available_at)]
+  }
+}
+
+/// The `ready` index key for a queue and time. The timestamp is fixed-width
+/// so that keys order chronologically.
+pub(crate) fn ready_key(queue: &str, at: &DateTime<Utc>) -> IndexValue {
+  IndexValue::new([
+    queue.to_owned(),
+    at.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string(),
+  ])
+}
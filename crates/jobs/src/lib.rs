@@ -0,0 +1,357 @@
+//! A background job queue stored in a database.
+//!
+//! [`JobQueue`] stores jobs as [`Job`] records. Workers [`lease`] ready jobs,
+//! which hides them from other workers for a visibility timeout, and then
+//! either [`complete`] them or [`fail`] them. Failed jobs are retried with
+//! exponential backoff until they run out of attempts, at which point they
+//! are dead-lettered. A job whose lease expires without being completed or
+//! failed, e.g. because its worker crashed, is leased again.
+//!
+//! Leasing uses
+//! [`Database::claim_by_index_range`](db::Database::claim_by_index_range), so
+//! concurrent workers never lease the same job; in Postgres this is
+//! `SELECT ... FOR UPDATE SKIP LOCKED`.
+//!
+//! [`lease`]: JobQueue::lease
+//! [`complete`]: JobQueue::complete
+//! [`fail`]: JobQueue::fail
+
+mod error;
+mod job;
+#[cfg(test)]
+mod tests;
+
+use std::{fmt, marker::PhantomData, ops::Bound, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use db::{Clock, Database, DatabaseError, SystemClock};
+use model::{IndexValue, RecordId};
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::{debug, warn};
+
+use self::job::ready_key;
+pub use self::{
+  error::JobError,
+  job::{Job, JobIndexSelector, JobState},
+};
+
+/// Exponential backoff between attempts of a failed job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+  /// The delay after the first failed attempt, doubled for each subsequent
+  /// failure.
+  pub base: Duration,
+  /// The longest delay.
+  pub max:  Duration,
+}
+
+impl Backoff {
+  /// Returns the delay after failed attempt number `attempt`, counting from
+  /// 1.
+  #[must_use]
+  pub fn delay(&self, attempt: u32) -> Duration {
+    let factor = 2_u32.saturating_pow(attempt.saturating_sub(1));
+    self.base.saturating_mul(factor).min(self.max)
+  }
+}
+
+impl Default for Backoff {
+  fn default() -> Self {
+    Self {
+      base: Duration::from_secs(1),
+      max:  Duration::from_mins(5),
+    }
+  }
+}
+
+/// Options for a [`JobQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueOptions {
+  /// How long a leased job is hidden from other workers.
+  pub visibility_timeout: Duration,
+  /// How many times a job is leased before it is dead-lettered.
+  pub max_attempts:       u32,
+  /// The delay before retrying a failed job.
+  pub backoff:            Backoff,
+}
+
+impl Default for QueueOptions {
+  fn default() -> Self {
+    Self {
+      visibility_timeout: Duration::from_secs(30),
+      max_attempts:       5,
+      backoff:            Backoff::default(),
+    }
+  }
+}
+
+/// A leased job, held by one worker until it is completed, failed, or the
+/// visibility timeout passes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lease<P> {
+  /// The job's ID.
+  pub id:      RecordId<Job>,
+  /// Which attempt this is, counting from 1.
+  pub attempt: u32,
+  /// The job's payload.
+  pub payload: P,
+}
+
+/// A queue of jobs with payloads of type `P`.
+///
+/// Queues are identified by name; any number of [`JobQueue`]s, in any number
+/// of processes, can share a queue.
+pub struct JobQueue<P> {
+  db:       Database<Job>,
+  queue:    String,
+  clock:    Arc<dyn Clock>,
+  options:  QueueOptions,
+  _payload: PhantomData<fn() -> P>,
+}
+
+impl<P> Clone for JobQueue<P> {
+  fn clone(&self) -> Self {
+    Self {
+      db:       self.db.clone(),
+      queue:    self.queue.clone(),
+      clock:    self.clock.clone(),
+      options:  self.options,
+      _payload: PhantomData,
+    }
+  }
+}
+
+impl<P> fmt::Debug for JobQueue<P> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("JobQueue")
+      .field("queue", &self.queue)
+      .field("options", &self.options)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<P: Serialize + DeserializeOwned> JobQueue<P> {
+  /// Creates a new [`JobQueue`] for the queue named `queue`.
+  #[must_use]
+  pub fn new(db: Database<Job>, queue: impl Into<String>) -> Self {
+    Self {
+      db,
+      queue: queue.into(),
+      clock: SystemClock::shared(),
+      options: QueueOptions::default(),
+      _payload: PhantomData,
+    }
+  }
+
+  /// Sets the clock used to schedule jobs.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// Sets the queue options.
+  #[must_use]
+  pub const fn with_options(mut self, options: QueueOptions) -> Self {
+    self.options = options;
+    self
+  }
+
+  /// Adds a job to the queue, ready to be leased immediately.
+  pub async fn enqueue(&self, payload: &P) -> Result<RecordId<Job>, JobError> {
+    self.enqueue_at(payload, self.clock.now()).await
+  }
+
+  /// Adds a job to the queue, ready to be leased once `at` passes.
+  pub async fn enqueue_at(
+    &self,
+    payload: &P,
+    at: DateTime<Utc>,
+  ) -> Result<RecordId<Job>, JobError> {
+    let job = Job {
+      id:           RecordId::new(),
+      queue:        self.queue.clone(),
+      payload:      serde_json::to_value(payload).map_err(JobError::Payload)?,
+      state:        JobState::Pending,
+      attempts:     0,
+      available_at: at,
+      last_error:   None,
+      created_at:   self.clock.now(),
+    };
+    self.db.insert(&job).await?;
+    debug!(id = %job.id, queue = %self.queue, "enqueued job");
+    Ok(job.id)
+  }
+
+  /// Leases up to `limit` ready jobs, oldest first.
+  ///
+  /// Jobs whose payload can't be deserialized as `P` are dead-lettered
+  /// instead of being returned, as are jobs whose final attempt's lease
+  /// expired.
+  pub async fn lease(&self, limit: u32) -> Result<Vec<Lease<P>>, JobError> {
+    let now = self.clock.now();
+    let expires_at = clock::after(now, self.options.visibility_timeout);
+    let max_attempts = self.options.max_attempts;
+
+    let lower = IndexValue::new([self.queue.as_str(), ""]);
+    let upper = ready_key(&self.queue, &now);
+    let claimed = self
+      .db
+      .claim_by_index_range(
+        JobIndexSelector::Ready,
+        Bound::Included(&lower),
+        Bound::Included(&upper),
+        limit,
+        &|job: &mut Job| {
+          if job.state == JobState::Leased && job.attempts >= max_attempts {
+            job.state = JobState::Dead;
+            job.last_error = Some("lease expired on final attempt".to_owned());
+            return;
+          }
+          job.state = JobState::Leased;
+          job.attempts += 1;
+          job.available_at = expires_at;
+        },
+      )
+      .await?;
+
+    let mut leases = Vec::with_capacity(claimed.len());
+    for mut job in claimed {
+      if job.state == JobState::Dead {
+        warn!(id = %job.id, "dead-lettered job with expired lease");
+        continue;
+      }
+      match serde_json::from_value(job.payload.clone()) {
+        Ok(payload) => leases.push(Lease {
+          id: job.id,
+          attempt: job.attempts,
+          payload,
+        }),
+        Err(e) => {
+          warn!(id = %job.id, error = %e, "dead-lettering unreadable job");
+          job.state = JobState::Dead;
+          job.last_error = Some(format!("unreadable payload: {e}"));
+          self.db.update(&job).await?;
+        }
+      }
+    }
+
+    debug!(count = leases.len(), queue = %self.queue, "leased jobs");
+    Ok(leases)
+  }
+
+  /// Completes a leased job, removing it from the queue.
+  ///
+  /// Fails with [`JobError::LeaseLost`] if the lease expired and the job was
+  /// leased again.
+  pub async fn complete(&self, lease: &Lease<P>) -> Result<(), JobError> {
+    // renew the lease first, so the job can't be leased again before it's
+    // deleted
+    let (mut job, updated_at) = self.leased_job(lease).await?;
+    job.available_at =
+      clock::after(self.clock.now(), self.options.visibility_timeout);
+    self.write_leased(&job, updated_at).await?;
+    self.db.delete(lease.id).await?;
+    debug!(id = %lease.id, "completed job");
+    Ok(())
+  }
+
+  /// Fails a leased job, recording `error`. The job is retried after a
+  /// backoff, or dead-lettered if it has run out of attempts.
+  ///
+  /// Fails with [`JobError::LeaseLost`] if the lease expired and the job was
+  /// leased again.
+  pub async fn fail(
+    &self,
+    lease: &Lease<P>,
+    error: &str,
+  ) -> Result<(), JobError> {
+    let (mut job, updated_at) = self.leased_job(lease).await?;
+    job.last_error = Some(error.to_owned());
+    if job.attempts >= self.options.max_attempts {
+      warn!(id = %job.id, attempts = job.attempts, "dead-lettering job");
+      job.state = JobState::Dead;
+    } else {
+      let delay = self.options.backoff.delay(job.attempts);
+      debug!(id = %job.id, ?delay, "retrying job after backoff");
+      job.state = JobState::Pending;
+      job.available_at = clock::after(self.clock.now(), delay);
+    }
+    self.write_leased(&job, updated_at).await
+  }
+
+  /// Returns the dead-lettered jobs in the queue.
+  pub async fn dead_letters(&self) -> Result<Vec<Job>, JobError> {
+    let key = IndexValue::new([self.queue.as_str(), JobState::Dead.as_str()]);
+    Ok(
+      self
+        .db
+        .find_by_index(JobIndexSelector::QueueState, &key)
+        .await?,
+    )
+  }
+
+  /// Moves a dead-lettered job back into the queue, ready immediately and
+  /// with its attempts reset.
+  pub async fn requeue(&self, id: RecordId<Job>) -> Result<(), JobError> {
+    let mut job = self.db.get_or_error(id).await?;
+    if job.state != JobState::Dead {
+      return Err(JobError::NotDead(id));
+    }
+    job.state = JobState::Pending;
+    job.attempts = 0;
+    job.available_at = self.clock.now();
+    self.db.update(&job).await?;
+    debug!(%id, "requeued job");
+    Ok(())
+  }
+
+  /// Removes a dead-lettered job from the queue, e.g. once it has been moved
+  /// somewhere else for inspection.
+  pub async fn discard(&self, id: RecordId<Job>) -> Result<(), JobError> {
+    let job = self.db.get_or_error(id).await?;
+    if job.state != JobState::Dead {
+      return Err(JobError::NotDead(id));
+    }
+    self.db.delete(id).await?;
+    debug!(%id, "discarded job");
+    Ok(())
+  }
+
+  /// Fetches the job held by `lease`, checking the lease is still current,
+  /// and when it was last written, to write it back with
+  /// [`write_leased`](Self::write_leased).
+  async fn leased_job(
+    &self,
+    lease: &Lease<P>,
+  ) -> Result<(Job, DateTime<Utc>), JobError> {
+    // read the write time first, so a lease in between makes it stale
+    let Some(updated_at) = self.db.updated_at(lease.id).await? else {
+      return Err(JobError::LeaseLost(lease.id));
+    };
+    match self.db.get(lease.id).await? {
+      Some(job)
+        if job.state == JobState::Leased && job.attempts == lease.attempt =>
+      {
+        Ok((job, updated_at))
+      }
+      _ => Err(JobError::LeaseLost(lease.id)),
+    }
+  }
+
+  /// Writes back a job fetched with [`leased_job`](Self::leased_job), unless
+  /// it was written since, e.g. leased again by another worker after this
+  /// lease expired.
+  async fn write_leased(
+    &self,
+    job: &Job,
+    updated_at: DateTime<Utc>,
+  ) -> Result<(), JobError> {
+    match self.db.update_if_unchanged(job, updated_at).await {
+      Err(DatabaseError::Conflict(_) | DatabaseError::NotFound(_)) => {
+        Err(JobError::LeaseLost(job.id))
+      }
+      result => Ok(result?),
+    }
+  }
+}
@@ -0,0 +1,239 @@
+use std::{sync::Arc, time::Duration};
+
+use db::{Clock, Database, ManualClock, MockDatabase};
+
+use crate::{Backoff, JobError, JobQueue, JobState, QueueOptions};
+
+const OPTIONS: QueueOptions = QueueOptions {
+  visibility_timeout: Duration::from_secs(30),
+  max_attempts:       3,
+  backoff:            Backoff {
+    base: Duration::from_secs(10),
+    max:  Duration::from_secs(15),
+  },
+};
+
+fn setup() -> (JobQueue<String>, ManualClock) {
+  let clock = ManualClock::default();
+  let db = Database::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let queue = JobQueue::new(db, "emails")
+    .with_clock(Arc::new(clock.clone()))
+    .with_options(OPTIONS);
+  (queue, clock)
+}
+
+#[tokio::test]
+async fn test_enqueue_lease_complete() {
+  let (queue, _) = setup();
+  let id = queue.enqueue(&"hello".to_owned()).await.unwrap();
+
+  let leases = queue.lease(10).await.unwrap();
+  assert_eq!(leases.len(), 1);
+  assert_eq!(leases[0].id, id);
+  assert_eq!(leases[0].attempt, 1);
+  assert_eq!(leases[0].payload, "hello");
+
+  // leased jobs are hidden from other workers
+  assert!(queue.lease(10).await.unwrap().is_empty());
+
+  queue.complete(&leases[0]).await.unwrap();
+  assert!(queue.db.get(id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_lease_respects_limit_and_order() {
+  let (queue, clock) = setup();
+  for i in 0..5 {
+    queue.enqueue(&format!("job {i}")).await.unwrap();
+    clock.advance(Duration::from_secs(1));
+  }
+
+  let first: Vec<_> = queue.lease(3).await.unwrap();
+  let second: Vec<_> = queue.lease(3).await.unwrap();
+  let payloads: Vec<_> = first
+    .iter()
+    .chain(&second)
+    .map(|l| l.payload.as_str())
+    .collect();
+  assert_eq!(payloads, ["job 0", "job 1", "job 2", "job 3", "job 4"]);
+}
+
+#[tokio::test]
+async fn test_enqueue_at_delays_job() {
+  let (queue, clock) = setup();
+  let at = clock.now() + chrono::Duration::seconds(60);
+  queue.enqueue_at(&"later".to_owned(), at).await.unwrap();
+
+  assert!(queue.lease(1).await.unwrap().is_empty());
+  clock.advance(Duration::from_mins(1));
+  assert_eq!(queue.lease(1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_queues_are_isolated() {
+  let (queue, clock) = setup();
+  let other: JobQueue<String> = JobQueue::new(queue.db.clone(), "email")
+    .with_clock(Arc::new(clock.clone()));
+  queue.enqueue(&"a".to_owned()).await.unwrap();
+
+  assert!(other.lease(10).await.unwrap().is_empty());
+  assert_eq!(queue.lease(10).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_expired_lease_is_leased_again() {
+  let (queue, clock) = setup();
+  queue.enqueue(&"crash".to_owned()).await.unwrap();
+  let stale = queue.lease(1).await.unwrap().remove(0);
+
+  clock.advance(Duration::from_secs(30));
+  let fresh = queue.lease(1).await.unwrap().remove(0);
+  assert_eq!(fresh.id, stale.id);
+  assert_eq!(fresh.attempt, 2);
+
+  assert!(matches!(
+    queue.complete(&stale).await,
+    Err(JobError::LeaseLost(id)) if id == stale.id
+  ));
+  queue.complete(&fresh).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_lease_lost_between_read_and_write() {
+  let (queue, clock) = setup();
+  queue.enqueue(&"slow".to_owned()).await.unwrap();
+  let stale = queue.lease(1).await.unwrap().remove(0);
+
+  // the stale worker reads its job, which is then leased again before the
+  // worker writes it back
+  let (job, updated_at) = queue.leased_job(&stale).await.unwrap();
+  clock.advance(Duration::from_secs(30));
+  let fresh = queue.lease(1).await.unwrap().remove(0);
+
+  assert!(matches!(
+    queue.write_leased(&job, updated_at).await,
+    Err(JobError::LeaseLost(id)) if id == stale.id
+  ));
+  queue.fail(&fresh, "timeout").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_fail_retries_with_backoff() {
+  let (queue, clock) = setup();
+  queue.enqueue(&"flaky".to_owned()).await.unwrap();
+
+  let lease = queue.lease(1).await.unwrap().remove(0);
+  queue.fail(&lease, "timeout").await.unwrap();
+
+  clock.advance(Duration::from_secs(9));
+  assert!(queue.lease(1).await.unwrap().is_empty());
+  clock.advance(Duration::from_secs(1));
+  let lease = queue.lease(1).await.unwrap().remove(0);
+  assert_eq!(lease.attempt, 2);
+
+  // the second backoff is capped at the maximum
+  queue.fail(&lease, "timeout").await.unwrap();
+  clock.advance(Duration::from_secs(14));
+  assert!(queue.lease(1).await.unwrap().is_empty());
+  clock.advance(Duration::from_secs(1));
+  assert_eq!(queue.lease(1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_dead_letter_and_requeue() {
+  let (queue, clock) = setup();
+  let id = queue.enqueue(&"doomed".to_owned()).await.unwrap();
+
+  for _ in 0..3 {
+    clock.advance(Duration::from_mins(1));
+    let lease = queue.lease(1).await.unwrap().remove(0);
+    queue.fail(&lease, "boom").await.unwrap();
+  }
+
+  clock.advance(Duration::from_mins(1));
+  assert!(queue.lease(1).await.unwrap().is_empty());
+  let dead = queue.dead_letters().await.unwrap();
+  assert_eq!(dead.len(), 1);
+  assert_eq!(dead[0].state, JobState::Dead);
+  assert_eq!(dead[0].attempts, 3);
+  assert_eq!(dead[0].last_error.as_deref(), Some("boom"));
+
+  queue.requeue(id).await.unwrap();
+  assert!(matches!(queue.requeue(id).await, Err(JobError::NotDead(_))));
+  let lease = queue.lease(1).await.unwrap().remove(0);
+  assert_eq!(lease.attempt, 1);
+  assert!(queue.dead_letters().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_expired_final_lease_is_dead_lettered() {
+  let (queue, clock) = setup();
+  queue.enqueue(&"hangs".to_owned()).await.unwrap();
+
+  for _ in 0..3 {
+    assert_eq!(queue.lease(1).await.unwrap().len(), 1);
+    clock.advance(Duration::from_secs(30));
+  }
+
+  assert!(queue.lease(1).await.unwrap().is_empty());
+  let dead = queue.dead_letters().await.unwrap();
+  assert_eq!(dead.len(), 1);
+
+  queue.discard(dead[0].id).await.unwrap();
+  assert!(queue.dead_letters().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_only_dead_jobs_are_discarded() {
+  let (queue, _) = setup();
+  let id = queue.enqueue(&"alive".to_owned()).await.unwrap();
+
+  assert!(matches!(queue.discard(id).await, Err(JobError::NotDead(_))));
+  assert_eq!(queue.lease(1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_unreadable_payload_is_dead_lettered() {
+  let (queue, clock) = setup();
+  let numbers: JobQueue<u32> = JobQueue::new(queue.db.clone(), "emails")
+    .with_clock(Arc::new(clock.clone()));
+  queue.enqueue(&"not a number".to_owned()).await.unwrap();
+
+  assert!(numbers.lease(1).await.unwrap().is_empty());
+  let dead = numbers.dead_letters().await.unwrap();
+  assert_eq!(dead.len(), 1);
+  assert!(dead[0].last_error.as_ref().unwrap().contains("unreadable"));
+}
+
+#[tokio::test]
+async fn test_concurrent_workers_never_share_jobs() {
+  let (queue, _) = setup();
+  for i in 0..20 {
+    queue.enqueue(&format!("job {i}")).await.unwrap();
+  }
+
+  let mut handles = vec![];
+  for _ in 0..4 {
+    let queue = queue.clone();
+    handles.push(tokio::spawn(async move {
+      let mut ids = vec![];
+      loop {
+        let leases = queue.lease(2).await.unwrap();
+        if leases.is_empty() {
+          return ids;
+        }
+        ids.extend(leases.into_iter().map(|lease| lease.id));
+      }
+    }));
+  }
+
+  let mut ids = vec![];
+  for handle in handles {
+    ids.extend(handle.await.unwrap());
+  }
+  ids.sort();
+  ids.dedup();
+  assert_eq!(ids.len(), 20);
+}
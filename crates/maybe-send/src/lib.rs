@@ -0,0 +1,18 @@
+//! Provides thread-safety bounds which are relaxed on `wasm32`.
+//!
+//! The database and storage traits share [`MaybeSendSync`], so backends
+//! holding single-threaded JS handles can implement both in the browser.
+
+/// A bound which is `Send + Sync` on native targets, and empty on `wasm32`,
+/// where implementers may hold single-threaded JS handles.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSendSync: Send + Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + Sync + ?Sized> MaybeSendSync for T {}
+
+/// A bound which is `Send + Sync` on native targets, and empty on `wasm32`,
+/// where implementers may hold single-threaded JS handles.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSendSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSendSync for T {}
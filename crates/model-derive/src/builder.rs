@@ -0,0 +1,117 @@
+//! The builder generated by `#[model(builder)]`.
+
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields};
+
+use crate::{SchemaField, is_option};
+
+/// How a builder fills a field that was never set.
+enum Unset {
+  /// The field must be set.
+  Required,
+  /// The field is `None`.
+  None,
+  /// The field is skipped by serde, so takes its default.
+  Default,
+}
+
+pub(crate) fn expand_builder(
+  input: &DeriveInput,
+  id_field: &syn::Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+  let Data::Struct(data) = &input.data else {
+    return Err(syn::Error::new_spanned(input, "expected a struct"));
+  };
+  let Fields::Named(fields) = &data.fields else {
+    return Err(syn::Error::new_spanned(input, "expected named fields"));
+  };
+
+  let struct_name = &input.ident;
+  let vis = &input.vis;
+  let builder_name = format_ident!("{}Builder", struct_name);
+  let model_name = struct_name.to_string();
+  let struct_doc = format!("A builder for [`{struct_name}`].");
+
+  let mut builder_fields = Vec::new();
+  let mut setters = Vec::new();
+  let mut initializers = Vec::new();
+  let mut builds = Vec::new();
+
+  for field in fields
+    .named
+    .iter()
+    .filter(|f| f.ident.as_ref() != Some(id_field))
+  {
+    let name = field.ident.as_ref().unwrap();
+    let ty = &field.ty;
+    let unset = if SchemaField::parse(field)?.is_none() {
+      Unset::Default
+    } else if is_option(ty) {
+      Unset::None
+    } else {
+      Unset::Required
+    };
+
+    let setter_doc = format!("Sets `{name}`.");
+    builder_fields.push(quote! { #name: ::std::option::Option<#ty> });
+    setters.push(quote! {
+        #[doc = #setter_doc]
+        #[must_use]
+        pub fn #name(mut self, #name: impl ::std::convert::Into<#ty>) -> Self {
+            self.#name = ::std::option::Option::Some(#name.into());
+            self
+        }
+    });
+    initializers.push(quote! { #name: ::std::option::Option::None });
+
+    let field_name = name.to_string();
+    builds.push(match unset {
+      Unset::Required => quote! {
+          #name: self.#name.ok_or(model::BuildError {
+              model: #model_name,
+              field: #field_name,
+          })?
+      },
+      Unset::None => quote! { #name: self.#name.flatten() },
+      Unset::Default => quote! { #name: self.#name.unwrap_or_default() },
+    });
+  }
+
+  Ok(quote! {
+      #[doc = #struct_doc]
+      #[derive(Clone)]
+      #vis struct #builder_name {
+          #id_field: RecordId<#struct_name>,
+          #(#builder_fields,)*
+      }
+
+      impl #struct_name {
+          /// Starts building a model with a new ID.
+          #[must_use]
+          pub fn builder() -> #builder_name {
+              #builder_name::new_with_id(RecordId::new())
+          }
+      }
+
+      impl #builder_name {
+          /// Starts building a model with the given ID.
+          #[must_use]
+          pub const fn new_with_id(#id_field: RecordId<#struct_name>) -> Self {
+              Self {
+                  #id_field,
+                  #(#initializers,)*
+              }
+          }
+
+          #(#setters)*
+
+          /// Builds the model, failing if a required field was never set.
+          pub fn build(self) -> ::std::result::Result<#struct_name, model::BuildError> {
+              ::std::result::Result::Ok(#struct_name {
+                  #id_field: self.#id_field,
+                  #(#builds,)*
+              })
+          }
+      }
+  })
+}
@@ -0,0 +1,66 @@
+//! The `fake::Dummy` implementation generated by `#[model(fake)]`.
+
+use quote::quote;
+use syn::{Data, DeriveInput, Expr, Fields};
+
+use crate::SchemaField;
+
+/// The faker given to a field with `#[model(fake = ...)]`, if any.
+fn field_faker(field: &syn::Field) -> syn::Result<Option<Expr>> {
+  let mut faker = None;
+  for attr in &field.attrs {
+    if !attr.path().is_ident("model") {
+      continue;
+    }
+
+    attr.parse_nested_meta(|meta| {
+      if meta.path.is_ident("fake") {
+        faker = Some(meta.value()?.parse()?);
+      } else if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+      }
+      Ok(())
+    })?;
+  }
+  Ok(faker)
+}
+
+pub(crate) fn expand_fake(
+  input: &DeriveInput,
+) -> syn::Result<proc_macro2::TokenStream> {
+  let Data::Struct(data) = &input.data else {
+    return Err(syn::Error::new_spanned(input, "expected a struct"));
+  };
+  let Fields::Named(fields) = &data.fields else {
+    return Err(syn::Error::new_spanned(input, "expected named fields"));
+  };
+
+  let struct_name = &input.ident;
+  let mut initializers = Vec::new();
+  for field in &fields.named {
+    let name = field.ident.as_ref().unwrap();
+    let value = if SchemaField::parse(field)?.is_none() {
+      quote! { ::std::default::Default::default() }
+    } else {
+      let faker = field_faker(field)?.map_or_else(
+        || quote! { model::fake::Faker },
+        |faker| quote! { #faker },
+      );
+      quote! { model::fake::Fake::fake_with_rng(&(#faker), rng) }
+    };
+    initializers.push(quote! { #name: #value });
+  }
+
+  Ok(quote! {
+      impl model::fake::Dummy<model::fake::Faker> for #struct_name {
+          fn dummy_with_rng<R: model::fake::rand::Rng + ?Sized>(
+              _config: &model::fake::Faker,
+              rng: &mut R,
+          ) -> Self {
+              Self {
+                  #(#initializers,)*
+              }
+          }
+      }
+  })
+}
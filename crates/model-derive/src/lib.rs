@@ -1,4 +1,9 @@
-//! Provides the derive macro for the [`Model`] trait.
+//! Provides the derive macro for the [`Model`] trait, and the
+//! [`RedactedDebug`](macro@RedactedDebug) derive macro.
+
+mod builder;
+mod fake;
+mod redact;
 
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
@@ -20,15 +25,42 @@ pub fn derive_model(input: TokenStream) -> TokenStream {
   }
 }
 
+/// Derive macro for a [`Debug`] implementation which prints `***` in place of
+/// fields marked `#[model(redact)]`.
+#[proc_macro_derive(RedactedDebug, attributes(model))]
+pub fn derive_redacted_debug(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  match redact::expand_redacted_debug(&input) {
+    Ok(tokens) => tokens.into(),
+    Err(err) => err.to_compile_error().into(),
+  }
+}
+
 struct ModelAttrs {
-  table_name: String,
-  indices:    Vec<Index>,
+  table_name:    String,
+  indices:       Vec<Index>,
+  search_fields: Vec<syn::Ident>,
+  label:         Option<syn::LitStr>,
+  version:       Option<syn::LitInt>,
+  upgrades:      Option<syn::ExprArray>,
+  json_schema:   bool,
+  builder:       bool,
+  fake:          bool,
 }
 
 impl ModelAttrs {
   fn parse(input: &DeriveInput) -> syn::Result<Self> {
     let mut table_name = None;
+    let mut table_auto = false;
     let mut indices = Vec::new();
+    let mut search_fields = Vec::new();
+    let mut label = None;
+    let mut version = None;
+    let mut upgrades = None;
+    let mut json_schema = false;
+    let mut builder = false;
+    let mut fake = false;
 
     for attr in &input.attrs {
       if !attr.path().is_ident("model") {
@@ -39,12 +71,47 @@ impl ModelAttrs {
         if meta.path.is_ident("table") {
           let value: Lit = meta.value()?.parse()?;
           if let Lit::Str(s) = value {
+            if !is_safe_identifier(&s.value()) {
+              return Err(syn::Error::new_spanned(s, TABLE_NAME_RULES));
+            }
             table_name = Some(s.value());
           }
+        } else if meta.path.is_ident("table_auto") {
+          table_auto = true;
         } else if meta.path.is_ident("index") {
           let content;
           syn::parenthesized!(content in meta.input);
           indices.push(content.parse()?);
+        } else if meta.path.is_ident("search") {
+          meta.parse_nested_meta(|search| {
+            if search.path.is_ident("fields") {
+              let value: syn::ExprArray = search.value()?.parse()?;
+              for elem in value.elems {
+                let ident = match &elem {
+                  Expr::Path(path) => path.path.get_ident().cloned(),
+                  _ => None,
+                };
+                search_fields.push(ident.ok_or_else(|| {
+                  syn::Error::new_spanned(&elem, "expected a field name")
+                })?);
+              }
+            } else {
+              return Err(search.error("unrecognized search attribute"));
+            }
+            Ok(())
+          })?;
+        } else if meta.path.is_ident("label") {
+          label = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("version") {
+          version = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("upgrades") {
+          upgrades = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("json_schema") {
+          json_schema = true;
+        } else if meta.path.is_ident("builder") {
+          builder = true;
+        } else if meta.path.is_ident("fake") {
+          fake = true;
         } else {
           return Err(meta.error("unrecognized model attribute"));
         }
@@ -52,22 +119,157 @@ impl ModelAttrs {
       })?;
     }
 
-    let table_name = table_name.ok_or_else(|| {
-      syn::Error::new_spanned(
-        input,
-        "missing #[model(table = \"...\")] attribute",
-      )
-    })?;
+    let table_name = match (table_name, table_auto) {
+      (Some(_), true) => {
+        return Err(syn::Error::new_spanned(
+          input,
+          "#[model(table = \"...\")] and #[model(table_auto)] can't be \
+           combined",
+        ));
+      }
+      (Some(table_name), false) => table_name,
+      (None, true) => {
+        let table_name = auto_table_name(&input.ident);
+        if !is_safe_identifier(&table_name) {
+          return Err(syn::Error::new_spanned(&input.ident, TABLE_NAME_RULES));
+        }
+        table_name
+      }
+      (None, false) => {
+        return Err(syn::Error::new_spanned(
+          input,
+          "missing #[model(table = \"...\")] or #[model(table_auto)] attribute",
+        ));
+      }
+    };
 
     Ok(Self {
       table_name,
       indices,
+      search_fields,
+      label,
+      version,
+      upgrades,
+      json_schema,
+      builder,
+      fake,
     })
   }
 }
 
+/// The longest identifier Postgres keeps without truncating.
+const MAX_TABLE_NAME_LEN: usize = 63;
+
+const TABLE_NAME_RULES: &str = "table names must be 1 to 63 lowercase ASCII \
+                                letters, digits and underscores, not starting \
+                                with a digit";
+
+/// Whether a table name can be used in SQL without quoting or escaping.
+fn is_safe_identifier(name: &str) -> bool {
+  name.len() <= MAX_TABLE_NAME_LEN
+    && name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+    && name
+      .chars()
+      .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Derives a table name from a struct name by slugifying its words with
+/// underscores, e.g. `HTTPRequestLog` becomes `http_request_log`.
+fn auto_table_name(ident: &syn::Ident) -> String {
+  let name = ident.to_string();
+  let chars: Vec<char> = name.chars().collect();
+  let mut words = String::new();
+  for (i, &c) in chars.iter().enumerate() {
+    // a word starts at an uppercase letter after a lowercase letter or
+    // digit, or at the last uppercase letter of an acronym
+    let starts_word = c.is_uppercase()
+      && i > 0
+      && (!chars[i - 1].is_uppercase()
+        || chars.get(i + 1).is_some_and(|n| n.is_lowercase()));
+    if starts_word {
+      words.push(' ');
+    }
+    words.push(c);
+  }
+  let options = slug::SlugOptions::DEFAULT.with_separator('_');
+  slug::Slug::with_options(&words, &options)
+    .as_str()
+    .to_owned()
+}
+
 struct FieldAttrs {
-  id_field: syn::Ident,
+  id_field:         syn::Ident,
+  field_names:      Vec<syn::Ident>,
+  schema_fields:    Vec<SchemaField>,
+  /// The fields marked `#[model(encrypt)]`, with their serialized names.
+  encrypted_fields: Vec<(syn::Ident, String)>,
+  /// The fields marked `#[model(column)]`, with their serialized names and
+  /// `ColumnKind` variants.
+  columns:          Vec<(syn::Ident, String, syn::Ident)>,
+}
+
+/// A field as it appears in the serialized form of a model.
+struct SchemaField {
+  name:     String,
+  ty:       syn::Type,
+  required: bool,
+}
+
+impl SchemaField {
+  /// Reads the serialized name and presence of a field from its serde
+  /// attributes. Returns `None` for fields serde skips.
+  fn parse(field: &syn::Field) -> syn::Result<Option<Self>> {
+    let mut name = field.ident.as_ref().unwrap().to_string();
+    let mut required = !is_option(&field.ty);
+    let mut skipped = false;
+
+    for attr in &field.attrs {
+      if !attr.path().is_ident("serde") {
+        continue;
+      }
+
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("rename") && meta.input.peek(Token![=]) {
+          let value: syn::LitStr = meta.value()?.parse()?;
+          name = value.value();
+          return Ok(());
+        }
+        if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing")
+        {
+          skipped = true;
+        } else if meta.path.is_ident("default")
+          || meta.path.is_ident("skip_serializing_if")
+        {
+          required = false;
+        }
+        // consume the values of attributes that don't affect the schema
+        if meta.input.peek(Token![=]) {
+          meta.value()?.parse::<Expr>()?;
+        } else if meta.input.peek(syn::token::Paren) {
+          meta.input.parse::<proc_macro2::TokenTree>()?;
+        }
+        Ok(())
+      })?;
+    }
+
+    Ok((!skipped).then(|| SchemaField {
+      name,
+      ty: field.ty.clone(),
+      required,
+    }))
+  }
+}
+
+/// Whether a type is spelled as an `Option`, which serde treats as optional.
+fn is_option(ty: &syn::Type) -> bool {
+  match ty {
+    syn::Type::Path(path) => path
+      .path
+      .segments
+      .last()
+      .is_some_and(|segment| segment.ident == "Option"),
+    _ => false,
+  }
 }
 
 impl FieldAttrs {
@@ -91,9 +293,15 @@ impl FieldAttrs {
     };
 
     let mut id_field = None;
+    let mut field_names = Vec::new();
+    let mut schema_fields = Vec::new();
+    let mut encrypted_fields = Vec::new();
+    let mut columns = Vec::new();
 
     for field in fields {
       let field_name = field.ident.as_ref().unwrap();
+      field_names.push(field_name.clone());
+      let schema_field = SchemaField::parse(field)?;
 
       for attr in &field.attrs {
         if !attr.path().is_ident("model") {
@@ -103,19 +311,56 @@ impl FieldAttrs {
         attr.parse_nested_meta(|meta| {
           if meta.path.is_ident("id") {
             id_field = Some(field_name.clone());
+          } else if meta.path.is_ident("encrypt") {
+            let name = schema_field.as_ref().map(|f| f.name.clone());
+            encrypted_fields.push((
+              field_name.clone(),
+              name.ok_or_else(|| {
+                meta.error("fields skipped by serde can't be encrypted")
+              })?,
+            ));
+          } else if meta.path.is_ident("column") {
+            let name = schema_field.as_ref().map(|f| f.name.clone());
+            let name = name.ok_or_else(|| {
+              meta.error("fields skipped by serde can't be columns")
+            })?;
+            let kind = if meta.input.peek(Token![=]) {
+              parse_column_kind(&meta.value()?.parse()?)?
+            } else {
+              infer_column_kind(&field.ty).ok_or_else(|| {
+                meta.error(
+                  "can't infer the column kind of this type; give one with \
+                   #[model(column = ...)]",
+                )
+              })?
+            };
+            columns.push((field_name.clone(), name, kind));
+          } else if meta.path.is_ident("redact") {
+            // handled by the `RedactedDebug` derive
+          } else if meta.path.is_ident("fake") {
+            // handled by `fake::expand_fake`
+            meta.value()?.parse::<Expr>()?;
           } else {
             return Err(meta.error("unrecognized field attribute"));
           }
           Ok(())
         })?;
       }
+
+      schema_fields.extend(schema_field);
     }
 
     let id_field = id_field.ok_or_else(|| {
       syn::Error::new_spanned(input, "missing #[model(id)] field attribute")
     })?;
 
-    Ok(Self { id_field })
+    Ok(Self {
+      id_field,
+      field_names,
+      schema_fields,
+      encrypted_fields,
+      columns,
+    })
   }
 }
 
@@ -131,7 +376,7 @@ fn expand_model(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
   let field_attrs = FieldAttrs::parse(input)?;
 
   let index_selector_name = format_ident!("{}IndexSelector", struct_name);
-  let indices = collect_indices(&model_attrs);
+  let indices = collect_indices(struct_name, &model_attrs);
 
   let (enum_def, display_impl) = if indices.is_empty() {
     generate_empty_enum(&index_selector_name)
@@ -144,6 +389,67 @@ fn expand_model(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
   let table_name = &model_attrs.table_name;
   let id_field = &field_attrs.id_field;
 
+  for search_field in &model_attrs.search_fields {
+    if !field_attrs.field_names.contains(search_field) {
+      return Err(syn::Error::new_spanned(
+        search_field,
+        "unknown field in #[model(search(fields = [...]))] attribute",
+      ));
+    }
+  }
+  let search_fields = model_attrs.search_fields.iter().map(ToString::to_string);
+
+  for (field, _) in &field_attrs.encrypted_fields {
+    if *field == field_attrs.id_field {
+      return Err(syn::Error::new_spanned(
+        field,
+        "the #[model(id)] field can't be encrypted",
+      ));
+    }
+    if model_attrs.search_fields.contains(field) {
+      return Err(syn::Error::new_spanned(
+        field,
+        "encrypted fields can't be used in #[model(search(fields = [...]))]",
+      ));
+    }
+  }
+  let encrypted_fields =
+    field_attrs.encrypted_fields.iter().map(|(_, name)| name);
+
+  let columns = generate_columns(&field_attrs)?;
+
+  let label_impl = match &model_attrs.label {
+    Some(label) => {
+      let fields = label_fields(label, &field_attrs.field_names)?;
+      quote! {
+          fn display_label(&self) -> String {
+              format!(#label, #(#fields = self.#fields),*)
+          }
+      }
+    }
+    None => quote! {},
+  };
+
+  let version_impl = generate_version_impl(&model_attrs)?;
+
+  let json_schema_impl = if model_attrs.json_schema {
+    generate_json_schema(struct_name, &field_attrs.schema_fields)
+  } else {
+    quote! {}
+  };
+
+  let builder_impl = if model_attrs.builder {
+    builder::expand_builder(input, id_field)?
+  } else {
+    quote! {}
+  };
+
+  let fake_impl = if model_attrs.fake {
+    fake::expand_fake(input)?
+  } else {
+    quote! {}
+  };
+
   Ok(quote! {
       #enum_def
 
@@ -152,6 +458,14 @@ fn expand_model(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
       impl model::Model for #struct_name {
           const TABLE_NAME: &'static str = #table_name;
 
+          const SEARCH_FIELDS: &'static [&'static str] = &[#(#search_fields),*];
+
+          const ENCRYPTED_FIELDS: &'static [&'static str] = &[#(#encrypted_fields),*];
+
+          const COLUMNS: &'static [model::ColumnDefinition] = &[#(#columns),*];
+
+          #version_impl
+
           type IndexSelector = #index_selector_name;
 
           fn indices() -> &'static model::IndexRegistry<Self> {
@@ -170,22 +484,172 @@ fn expand_model(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
           fn id(&self) -> RecordId<Self> {
               self.#id_field
           }
+
+          #label_impl
       }
+
+      #json_schema_impl
+
+      #builder_impl
+
+      #fake_impl
   })
 }
 
-fn collect_indices(model_attrs: &ModelAttrs) -> Vec<IndexInfo> {
+/// Reads the fields named by the placeholders of a `#[model(label = "...")]`
+/// format string, each once.
+fn label_fields(
+  label: &syn::LitStr,
+  field_names: &[syn::Ident],
+) -> syn::Result<Vec<syn::Ident>> {
+  let value = label.value();
+  let mut fields: Vec<syn::Ident> = Vec::new();
+  let mut rest = value.as_str();
+  while let Some(start) = rest.find(['{', '}']) {
+    let brace = rest.as_bytes()[start];
+    rest = &rest[start + 1..];
+    // escaped braces
+    if rest.as_bytes().first() == Some(&brace) {
+      rest = &rest[1..];
+      continue;
+    }
+    if brace == b'}' {
+      return Err(syn::Error::new_spanned(label, "unmatched `}` in label"));
+    }
+    let end = rest.find('}').ok_or_else(|| {
+      syn::Error::new_spanned(label, "unmatched `{` in label")
+    })?;
+    let name = rest[..end].split(':').next().unwrap_or_default().trim();
+    rest = &rest[end + 1..];
+
+    let field = field_names.iter().find(|f| *f == name).ok_or_else(|| {
+      syn::Error::new_spanned(
+        label,
+        format!("label placeholders must name a field, found `{{{name}}}`"),
+      )
+    })?;
+    if !fields.contains(field) {
+      fields.push(field.clone());
+    }
+  }
+  Ok(fields)
+}
+
+fn generate_json_schema(
+  struct_name: &syn::Ident,
+  fields: &[SchemaField],
+) -> proc_macro2::TokenStream {
+  let schema_name = struct_name.to_string();
+  let names: Vec<_> = fields.iter().map(|f| &f.name).collect();
+  let types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+  let required = fields.iter().filter(|f| f.required).map(|f| &f.name);
+
+  quote! {
+      impl model::schemars::JsonSchema for #struct_name {
+          fn schema_name() -> ::std::borrow::Cow<'static, str> {
+              #schema_name.into()
+          }
+
+          fn json_schema(
+              generator: &mut model::schemars::SchemaGenerator,
+          ) -> model::schemars::Schema {
+              model::schemars::json_schema!({
+                  "type": "object",
+                  "properties": {
+                      #(#names: generator.subschema_for::<#types>()),*
+                  },
+                  "required": [#(#required),*]
+              })
+          }
+      }
+  }
+}
+
+/// Validates the promoted columns and generates their definitions.
+fn generate_columns(
+  field_attrs: &FieldAttrs,
+) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+  for (field, name, _) in &field_attrs.columns {
+    if *field == field_attrs.id_field
+      || field_attrs.encrypted_fields.iter().any(|(f, _)| f == field)
+    {
+      return Err(syn::Error::new_spanned(
+        field,
+        "the #[model(id)] field and encrypted fields can't be columns",
+      ));
+    }
+    if RESERVED_COLUMNS.contains(&name.as_str()) {
+      return Err(syn::Error::new_spanned(
+        field,
+        format!("`{name}` is reserved and can't be used as a column name"),
+      ));
+    }
+  }
+  Ok(
+    field_attrs
+      .columns
+      .iter()
+      .map(|(_, name, kind)| {
+        quote! {
+            model::ColumnDefinition {
+                name: #name,
+                kind: model::ColumnKind::#kind,
+            }
+        }
+      })
+      .collect(),
+  )
+}
+
+/// Generates the `VERSION` and `UPGRADES` constants, checking that there is
+/// one upgrade from each earlier version.
+fn generate_version_impl(
+  model_attrs: &ModelAttrs,
+) -> syn::Result<proc_macro2::TokenStream> {
+  match (&model_attrs.version, &model_attrs.upgrades) {
+    (Some(version), upgrades) => {
+      let count = upgrades.as_ref().map_or(0, |u| u.elems.len());
+      if version.base10_parse::<u32>()? as usize != count {
+        return Err(syn::Error::new_spanned(
+          version,
+          "#[model(version = N)] needs `upgrades = [...]` with one upgrade \
+           from each version before N",
+        ));
+      }
+      let upgrades = upgrades.iter().flat_map(|u| &u.elems);
+      Ok(quote! {
+          const VERSION: u32 = #version;
+
+          const UPGRADES: &'static [model::Upgrade] = &[#(#upgrades),*];
+      })
+    }
+    (None, Some(upgrades)) => Err(syn::Error::new_spanned(
+      upgrades,
+      "`upgrades` needs a #[model(version = N)] attribute",
+    )),
+    (None, None) => Ok(quote! {}),
+  }
+}
+
+fn collect_indices(
+  struct_name: &syn::Ident,
+  model_attrs: &ModelAttrs,
+) -> Vec<IndexInfo> {
   let mut indices = Vec::new();
 
   for composite in &model_attrs.indices {
     let name = &composite.name;
     let unique = &composite.unique;
+    let kind = &composite.kind;
     let extract_fn = &composite.extract;
     indices.push(IndexInfo {
       variant:    format_ident!("{}", to_pascal_case(name)),
       name:       name.clone(),
+      // the model type is spelled out so that the extractor closure's
+      // argument type is known before `with_kind` is resolved
       definition: quote! {
-          model::IndexDefinition::new(#name, #unique, #extract_fn)
+          model::IndexDefinition::<#struct_name>::new(#name, #unique, #extract_fn)
+            .with_kind(model::IndexKind::#kind)
       },
     });
   }
@@ -244,6 +708,7 @@ fn generate_enum(
 struct Index {
   name:    String,
   unique:  bool,
+  kind:    syn::Ident,
   extract: Expr,
 }
 
@@ -253,6 +718,7 @@ impl Parse for Index {
 
     let mut name = None;
     let mut unique = None;
+    let mut kind = None;
     let mut extract = None;
 
     for meta in meta_items {
@@ -267,6 +733,8 @@ impl Parse for Index {
             {
               name = Some(s.value());
             }
+          } else if pair.path.is_ident("kind") {
+            kind = Some(parse_index_kind(&pair.value)?);
           } else if pair.path.is_ident("extract") {
             extract = Some(pair.value.clone());
           }
@@ -281,12 +749,91 @@ impl Parse for Index {
       name:    name
         .ok_or_else(|| input.error("missing 'name' in composite index"))?,
       unique:  unique.unwrap_or(false),
+      kind:    kind.unwrap_or_else(|| format_ident!("String")),
       extract: extract
         .ok_or_else(|| input.error("missing 'extract' in composite index"))?,
     })
   }
 }
 
+fn parse_index_kind(value: &Expr) -> syn::Result<syn::Ident> {
+  let ident = match value {
+    Expr::Path(path) => path.path.get_ident(),
+    _ => None,
+  };
+  let variant = match ident.map(ToString::to_string).as_deref() {
+    Some("string") => "String",
+    Some("i64") => "I64",
+    Some("f64") => "F64",
+    Some("timestamp") => "Timestamp",
+    Some("multi_value") => "MultiValue",
+    _ => {
+      return Err(syn::Error::new_spanned(
+        value,
+        "expected one of `string`, `i64`, `f64`, `timestamp`, or \
+         `multi_value` for index kind",
+      ));
+    }
+  };
+  Ok(format_ident!("{}", variant))
+}
+
+/// The names of the columns backends already store alongside a model.
+const RESERVED_COLUMNS: &[&str] =
+  &["id", "data", "created_at", "updated_at", "search_vector"];
+
+fn parse_column_kind(value: &Expr) -> syn::Result<syn::Ident> {
+  let ident = match value {
+    Expr::Path(path) => path.path.get_ident(),
+    _ => None,
+  };
+  let variant = match ident.map(ToString::to_string).as_deref() {
+    Some("string") => "String",
+    Some("i64") => "I64",
+    Some("f64") => "F64",
+    Some("bool") => "Bool",
+    Some("timestamp") => "Timestamp",
+    Some("json") => "Json",
+    _ => {
+      return Err(syn::Error::new_spanned(
+        value,
+        "expected one of `string`, `i64`, `f64`, `bool`, `timestamp`, or \
+         `json` for column kind",
+      ));
+    }
+  };
+  Ok(format_ident!("{}", variant))
+}
+
+/// Infers the `ColumnKind` variant of a field from its type, looking through
+/// `Option`. Types which don't fit a 64-bit integer aren't inferred.
+fn infer_column_kind(ty: &syn::Type) -> Option<syn::Ident> {
+  let syn::Type::Path(path) = ty else {
+    return Some(format_ident!("Json"));
+  };
+  let segment = path.path.segments.last()?;
+  if segment.ident == "Option" {
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+      return None;
+    };
+    return match args.args.first()? {
+      syn::GenericArgument::Type(inner) => infer_column_kind(inner),
+      _ => None,
+    };
+  }
+
+  let variant = match segment.ident.to_string().as_str() {
+    "String" | "Cow" | "RecordId" => "String",
+    "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" => "I64",
+    "u64" | "usize" | "i128" | "u128" | "isize" => return None,
+    "f32" | "f64" => "F64",
+    "bool" => "Bool",
+    "DateTime" => "Timestamp",
+    _ => "Json",
+  };
+  Some(format_ident!("{}", variant))
+}
+
 fn to_pascal_case(s: &str) -> String {
   s.split('_')
     .map(|word| {
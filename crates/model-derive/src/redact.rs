@@ -0,0 +1,90 @@
+//! The `RedactedDebug` derive macro.
+
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, parse_quote};
+
+/// Whether a field is marked `#[model(redact)]`. Other field attributes are
+/// left to the `Model` derive.
+fn is_redacted(field: &syn::Field) -> syn::Result<bool> {
+  let mut redacted = false;
+  for attr in &field.attrs {
+    if !attr.path().is_ident("model") {
+      continue;
+    }
+
+    attr.parse_nested_meta(|meta| {
+      if meta.path.is_ident("redact") {
+        redacted = true;
+      } else if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+      }
+      Ok(())
+    })?;
+  }
+  Ok(redacted)
+}
+
+/// The expression to format a field with: the field itself, or a `***`
+/// placeholder if it's redacted.
+fn field_value(
+  field: &syn::Field,
+  access: &proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+  Ok(if is_redacted(field)? {
+    quote! { &::std::format_args!("***") }
+  } else {
+    quote! { &#access }
+  })
+}
+
+pub(crate) fn expand_redacted_debug(
+  input: &DeriveInput,
+) -> syn::Result<proc_macro2::TokenStream> {
+  let Data::Struct(data) = &input.data else {
+    return Err(syn::Error::new_spanned(
+      input,
+      "RedactedDebug can only be derived for structs",
+    ));
+  };
+
+  let name = &input.ident;
+  let name_str = name.to_string();
+
+  let body = match &data.fields {
+    Fields::Named(fields) => {
+      let mut entries = Vec::new();
+      for field in &fields.named {
+        let ident = field.ident.as_ref().unwrap();
+        let ident_str = ident.to_string();
+        let value = field_value(field, &quote! { self.#ident })?;
+        entries.push(quote! { .field(#ident_str, #value) });
+      }
+      quote! { f.debug_struct(#name_str) #(#entries)* .finish() }
+    }
+    Fields::Unnamed(fields) => {
+      let mut entries = Vec::new();
+      for (i, field) in fields.unnamed.iter().enumerate() {
+        let index = syn::Index::from(i);
+        let value = field_value(field, &quote! { self.#index })?;
+        entries.push(quote! { .field(#value) });
+      }
+      quote! { f.debug_tuple(#name_str) #(#entries)* .finish() }
+    }
+    Fields::Unit => quote! { f.write_str(#name_str) },
+  };
+
+  // like `#[derive(Debug)]`, require every type parameter to be `Debug`
+  let mut generics = input.generics.clone();
+  for param in generics.type_params_mut() {
+    param.bounds.push(parse_quote!(::std::fmt::Debug));
+  }
+  let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+  Ok(quote! {
+      impl #impl_generics ::std::fmt::Debug for #name #ty_generics #where_clause {
+          fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+              #body
+          }
+      }
+  })
+}
@@ -0,0 +1,10 @@
+/// An error building a model with a builder generated by
+/// `#[model(builder)]`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("missing required field `{field}` building `{model}`")]
+pub struct BuildError {
+  /// The name of the model being built.
+  pub model: &'static str,
+  /// The required field which was never set.
+  pub field: &'static str,
+}
@@ -0,0 +1,31 @@
+use serde::Serialize;
+
+/// The SQL type of a field promoted to a column with `#[model(column)]`.
+///
+/// Serializes as the name used in `#[model(column = ...)]`, e.g. `i64`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnKind {
+  /// The field is text.
+  String,
+  /// The field is a signed 64-bit integer.
+  I64,
+  /// The field is a 64-bit float.
+  F64,
+  /// The field is a boolean.
+  Bool,
+  /// The field is an RFC 3339 timestamp.
+  Timestamp,
+  /// The field is stored as JSON, e.g. for nested structures.
+  Json,
+}
+
+/// A field of a model maintained by backends as a column of its own, e.g.
+/// for reporting tools which can't query JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct ColumnDefinition {
+  /// The serialized name of the field, which is also the column's name.
+  pub name: &'static str,
+  /// The SQL type of the column.
+  pub kind: ColumnKind,
+}
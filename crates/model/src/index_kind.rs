@@ -0,0 +1,96 @@
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::IndexValue;
+
+/// The kind of key stored in an index, which determines how keys are
+/// compared.
+///
+/// Serializes as the name used in `#[model(index(kind = ...))]`, e.g.
+/// `multi_value`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexKind {
+  /// Keys are compared as strings. Values may have multiple segments, and
+  /// are stored in their [encoded](IndexValue::encode) form.
+  #[default]
+  String,
+  /// Keys are single-segment signed 64-bit integers.
+  I64,
+  /// Keys are single-segment 64-bit floats. `NaN` is not allowed.
+  F64,
+  /// Keys are single-segment RFC 3339 timestamps.
+  Timestamp,
+  /// Keys are compared as strings, and each extracted value is indexed
+  /// separately, so a record is found by any of its values, e.g. any one of
+  /// its tags. On a unique index, no two records may share a value.
+  MultiValue,
+}
+
+impl IndexKind {
+  /// Whether each extracted value is indexed separately, rather than
+  /// together as one key.
+  #[must_use]
+  pub const fn is_multi_value(self) -> bool { matches!(self, Self::MultiValue) }
+
+  /// Parses an [`IndexValue`] as a key of this kind.
+  ///
+  /// Returns `None` if the value is not valid for this kind.
+  #[must_use]
+  pub fn parse(self, value: &IndexValue) -> Option<IndexKey> {
+    match (self, value.segments()) {
+      (Self::String | Self::MultiValue, _) => {
+        Some(IndexKey::String(value.encode()))
+      }
+      (_, [segment]) => self.parse_str(segment),
+      _ => None,
+    }
+  }
+
+  /// Parses the canonical text form of a key of this kind, as produced by
+  /// the [`Display`](fmt::Display) implementation of [`IndexKey`].
+  #[must_use]
+  pub fn parse_str(self, s: &str) -> Option<IndexKey> {
+    match self {
+      Self::String | Self::MultiValue => Some(IndexKey::String(s.to_owned())),
+      Self::I64 => s.parse().ok().map(IndexKey::I64),
+      Self::F64 => s
+        .parse::<f64>()
+        .ok()
+        .filter(|f| !f.is_nan())
+        .map(IndexKey::F64),
+      Self::Timestamp => DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(IndexKey::Timestamp),
+    }
+  }
+}
+
+/// An index key parsed according to its [`IndexKind`].
+///
+/// Keys of the same kind are ordered by their typed value rather than
+/// lexicographically.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum IndexKey {
+  /// A string key.
+  String(String),
+  /// A signed 64-bit integer key.
+  I64(i64),
+  /// A 64-bit float key.
+  F64(f64),
+  /// A timestamp key.
+  Timestamp(DateTime<FixedOffset>),
+}
+
+impl fmt::Display for IndexKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::String(s) => f.write_str(s),
+      Self::I64(i) => write!(f, "{i}"),
+      Self::F64(x) => write!(f, "{x}"),
+      Self::Timestamp(t) => f.write_str(&t.to_rfc3339()),
+    }
+  }
+}
@@ -2,13 +2,71 @@
 //!
 //! The [`Model`] trait must be implemented for a type to be used as a domain
 //! data model. Use the `#[derive(Model)]` macro to automatically implement it.
+//!
+//! Table names are given with `#[model(table = "...")]`, and must be
+//! lowercase ASCII letters, digits and underscores so they're safe to use in
+//! SQL. `#[model(table_auto)]` derives the table name from the struct name
+//! instead, e.g. `HTTPRequestLog` is kept in `http_request_log`.
+//!
+//! With the `json-schema` feature, `#[model(json_schema)]` also implements
+//! `schemars::JsonSchema` for the model, describing [`RecordId`] fields as
+//! ULID strings.
+//!
+//! Derive [`RedactedDebug`] in place of [`Debug`] to keep sensitive fields out
+//! of logs: fields marked `#[model(redact)]` are printed as `***`.
+//!
+//! `#[model(builder)]` generates a builder for the model, e.g.
+//! `User::builder().email("..").build()`, which fills in a new ID (or takes
+//! one with `UserBuilder::new_with_id`) and returns a [`BuildError`] if a
+//! required field is missing. `Option` fields and fields skipped by serde may
+//! be left unset.
+//!
+//! With the `fake-data` feature, `#[model(fake)]` implements
+//! `fake::Dummy<fake::Faker>` for the model, so `Faker.fake::<User>()`
+//! generates a random user. Fields are generated with `fake::Faker` unless
+//! given a faker of their own, e.g.
+//! `#[model(fake = fake::faker::internet::en::SafeEmail())]`, and fields
+//! skipped by serde take their defaults.
+//!
+//! Fields marked `#[model(column)]` are kept in columns of their own by
+//! backends with tables, e.g. for BI tools, in addition to the serialized
+//! model. The [`ColumnKind`] is inferred from the field's type, or given
+//! with e.g. `#[model(column = timestamp)]`.
+//!
+//! `#[model(version = 2, upgrades = [v0_to_v1, v1_to_v2])]` versions the
+//! stored form of a model, so rows written before a field was added or
+//! renamed still deserialize: each [`Upgrade`] rewrites the serialized model
+//! of one version into the next, and [`FromVersionedJson`] applies them.
+//!
+//! A [`Projection`] is a smaller struct deserialized from a subset of a
+//! model's fields, so backends can fetch only those fields.
+
+mod builder;
+mod column;
+mod index_kind;
+mod projection;
+mod version;
 
 use std::fmt::{self, Debug, Display};
 
-pub use model_derive::Model;
+#[cfg(feature = "fake-data")]
+pub use fake;
+pub use model_derive::{Model, RedactedDebug};
 pub use record_id::*;
+#[cfg(feature = "json-schema")]
+pub use schemars;
 use serde::{Serialize, de::DeserializeOwned};
 
+pub use self::{
+  builder::BuildError,
+  column::{ColumnDefinition, ColumnKind},
+  index_kind::{IndexKey, IndexKind},
+  projection::Projection,
+  version::{
+    FromVersionedJson, Upgrade, VERSION_FIELD, stamp_version, upgrade_json,
+  },
+};
+
 /// Represents a model in the database.
 pub trait Model:
   Clone + Debug + PartialEq + Serialize + DeserializeOwned + Send + Sync + 'static
@@ -16,6 +74,39 @@ pub trait Model:
   /// The table name in the database.
   const TABLE_NAME: &'static str;
 
+  /// The serialized names of the fields included in full-text search.
+  ///
+  /// Set with `#[model(search(fields = [...]))]`. Fields renamed by serde
+  /// must not be used here, as the names are read from the serialized data.
+  const SEARCH_FIELDS: &'static [&'static str] = &[];
+
+  /// The serialized names of the fields encrypted at rest.
+  ///
+  /// Set with `#[model(encrypt)]` on a field. Backends which persist models
+  /// encrypt these fields with a `FieldCipher` before writing and decrypt
+  /// them after reading, so index extractors still see the plaintext.
+  /// Encrypted fields can't be searched or incremented in the database.
+  const ENCRYPTED_FIELDS: &'static [&'static str] = &[];
+
+  /// The fields kept in columns of their own.
+  ///
+  /// Set with `#[model(column)]` on a field. The columns are derived from
+  /// the serialized model on every write, and are never read back.
+  const COLUMNS: &'static [ColumnDefinition] = &[];
+
+  /// The version of the model's serialized form.
+  ///
+  /// Set with `#[model(version = N, upgrades = [...])]`. Backends which
+  /// persist models store it in the [`VERSION_FIELD`] of the serialized
+  /// model, and bring data of older versions up to date with
+  /// [`UPGRADES`](Self::UPGRADES) before deserializing it. Data without a
+  /// version is version 0.
+  const VERSION: u32 = 0;
+
+  /// The functions upgrading the serialized model from each older version:
+  /// the function at index `i` upgrades version `i` to version `i + 1`.
+  const UPGRADES: &'static [Upgrade] = &[];
+
   /// The index selector type for this model.
   type IndexSelector: Display + Debug + Clone + Copy + Send + Sync + 'static;
 
@@ -24,6 +115,25 @@ pub trait Model:
 
   /// Returns the model's ID.
   fn id(&self) -> RecordId<Self>;
+
+  /// Returns a human-readable label for the record, for admin tools, exports
+  /// and change events. Defaults to the ID.
+  ///
+  /// Set with `#[model(label = "...")]`, a format string whose placeholders
+  /// name fields, e.g. `#[model(label = "{name} <{email}>")]`.
+  fn display_label(&self) -> String { self.id().to_string() }
+
+  /// Returns the JSON Schema of the model's serialized form.
+  ///
+  /// Available for models deriving with `#[model(json_schema)]`.
+  #[cfg(feature = "json-schema")]
+  #[must_use]
+  fn json_schema() -> schemars::Schema
+  where
+    Self: schemars::JsonSchema,
+  {
+    schemars::schema_for!(Self)
+  }
 }
 
 /// Registry containing all index definitions for a model.
@@ -82,15 +192,107 @@ impl IndexValue {
   pub fn new_single<T: AsRef<str>>(input: T) -> Self {
     IndexValue(vec![input.as_ref().to_owned()])
   }
+
+  /// Creates a new single-segment [`IndexValue`] for an
+  /// [`I64`](IndexKind::I64) index.
+  #[must_use]
+  pub fn new_i64(input: i64) -> Self { IndexValue(vec![input.to_string()]) }
+
+  /// Creates a new single-segment [`IndexValue`] for an
+  /// [`F64`](IndexKind::F64) index.
+  #[must_use]
+  pub fn new_f64(input: f64) -> Self { IndexValue(vec![input.to_string()]) }
+
+  /// Creates a new single-segment [`IndexValue`] for a
+  /// [`Timestamp`](IndexKind::Timestamp) index.
+  #[must_use]
+  pub fn new_timestamp<Tz>(input: &chrono::DateTime<Tz>) -> Self
+  where
+    Tz: chrono::TimeZone,
+    Tz::Offset: Display,
+  {
+    IndexValue(vec![input.to_rfc3339()])
+  }
+
+  /// Returns the segments that make up this [`IndexValue`].
+  #[must_use]
+  pub fn segments(&self) -> &[String] { &self.0 }
+
+  /// Encodes this [`IndexValue`] as the single string key stored by every
+  /// backend for string indices.
+  ///
+  /// Segments are separated by `\u{1}`, and the characters `\u{0}` to
+  /// `\u{2}` within segments are escaped, so distinct values never share an
+  /// encoding. Encodings compare bytewise in the same order as their
+  /// segments do, so a value sorts directly after its prefixes. A value with
+  /// no segments encodes the same as one empty segment.
+  #[must_use]
+  pub fn encode(&self) -> String {
+    let mut encoded = String::new();
+    for (i, segment) in self.0.iter().enumerate() {
+      if i > 0 {
+        encoded.push(SEGMENT_SEPARATOR);
+      }
+      for c in segment.chars() {
+        let escaped = match c {
+          '\u{0}' => '\u{3}',
+          SEGMENT_SEPARATOR => '\u{4}',
+          SEGMENT_ESCAPE => '\u{5}',
+          c => {
+            encoded.push(c);
+            continue;
+          }
+        };
+        encoded.push(SEGMENT_ESCAPE);
+        encoded.push(escaped);
+      }
+    }
+    encoded
+  }
+
+  /// Decodes an [`IndexValue`] from its [encoding](Self::encode), returning
+  /// `None` if `encoded` isn't a valid encoding.
+  #[must_use]
+  pub fn decode(encoded: &str) -> Option<Self> {
+    let mut segments = vec![String::new()];
+    let mut chars = encoded.chars();
+    while let Some(c) = chars.next() {
+      match c {
+        SEGMENT_SEPARATOR => segments.push(String::new()),
+        SEGMENT_ESCAPE => {
+          let unescaped = match chars.next()? {
+            '\u{3}' => '\u{0}',
+            '\u{4}' => SEGMENT_SEPARATOR,
+            '\u{5}' => SEGMENT_ESCAPE,
+            _ => return None,
+          };
+          segments.last_mut()?.push(unescaped);
+        }
+        '\u{0}' => return None,
+        c => segments.last_mut()?.push(c),
+      }
+    }
+    Some(IndexValue(segments))
+  }
 }
 
+/// Separates the segments of an encoded [`IndexValue`].
+const SEGMENT_SEPARATOR: char = '\u{1}';
+/// Escapes the characters `\u{0}` to `\u{2}` in an encoded [`IndexValue`].
+const SEGMENT_ESCAPE: char = '\u{2}';
+
 /// Definition of a single index (can be simple or composite).
 pub struct IndexDefinition<M> {
   /// The name of the index (matches the selector variant name in snake case).
   pub name:      &'static str,
   /// Whether this is a unique index.
   pub unique:    bool,
-  /// Function to extract the index value(s) from a model instance.
+  /// The kind of key stored in this index.
+  pub kind:      IndexKind,
+  /// Function to extract the index value(s) from a model instance. The
+  /// values are indexed together as one key, unless the index is
+  /// [`MultiValue`](IndexKind::MultiValue). If there are no values, the
+  /// model isn't indexed, and so never conflicts on a unique index.
   pub extractor: fn(&M) -> Vec<IndexValue>,
 }
 
@@ -104,10 +306,18 @@ impl<M> IndexDefinition<M> {
     Self {
       name,
       unique,
+      kind: IndexKind::String,
       extractor,
     }
   }
 
+  /// Set the kind of key stored in this index.
+  #[must_use]
+  pub const fn with_kind(mut self, kind: IndexKind) -> Self {
+    self.kind = kind;
+    self
+  }
+
   /// Extract the index value from a model instance.
   pub fn extract(&self, model: &M) -> Vec<IndexValue> {
     (self.extractor)(model)
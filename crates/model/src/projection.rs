@@ -0,0 +1,23 @@
+use serde::de::DeserializeOwned;
+
+use crate::Model;
+
+/// A partial view of a model `M`, deserializable from a subset of its
+/// fields, for fetching only what a list view or lookup needs.
+///
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct UserSummary {
+///   id:   RecordId<User>,
+///   name: String,
+/// }
+///
+/// impl Projection<User> for UserSummary {
+///   const FIELDS: &'static [&'static str] = &["id", "name"];
+/// }
+/// ```
+pub trait Projection<M: Model>: DeserializeOwned {
+  /// The top-level fields of the model's serialized form the projection is
+  /// deserialized from.
+  const FIELDS: &'static [&'static str];
+}
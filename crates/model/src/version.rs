@@ -0,0 +1,77 @@
+use serde::de::Error as _;
+use serde_json::Value;
+
+use crate::Model;
+
+/// The field of a serialized model holding its
+/// [version](Model::VERSION).
+pub const VERSION_FIELD: &str = "_version";
+
+/// Upgrades a serialized model from one version to the next, in place.
+pub type Upgrade = fn(&mut Value) -> Result<(), serde_json::Error>;
+
+/// Adds the model's [version](Model::VERSION) to its serialized form, if it
+/// has one.
+pub fn stamp_version<M: Model>(data: &mut Value) {
+  if M::VERSION == 0 {
+    return;
+  }
+  if let Value::Object(fields) = data {
+    fields.insert(VERSION_FIELD.to_owned(), M::VERSION.into());
+  }
+}
+
+/// Brings a serialized model of any older version up to date with
+/// [`UPGRADES`](Model::UPGRADES), and removes its version field. Data without
+/// a version field is version 0.
+///
+/// # Errors
+/// Returns an error if the version is invalid or newer than the model's, or
+/// if an upgrade fails.
+pub fn upgrade_json<M: Model>(
+  data: &mut Value,
+) -> Result<(), serde_json::Error> {
+  let version = match data.get(VERSION_FIELD) {
+    None => 0,
+    Some(version) => version
+      .as_u64()
+      .and_then(|v| u32::try_from(v).ok())
+      .ok_or_else(|| {
+        serde_json::Error::custom(format!("invalid {VERSION_FIELD}: {version}"))
+      })?,
+  };
+  if version > M::VERSION {
+    return Err(serde_json::Error::custom(format!(
+      "{} data is version {version}, newer than the model's version {}",
+      M::TABLE_NAME,
+      M::VERSION
+    )));
+  }
+
+  if let Value::Object(fields) = data {
+    fields.remove(VERSION_FIELD);
+  }
+  let upgrades = M::UPGRADES.get(version as usize..).unwrap_or_default();
+  for upgrade in upgrades {
+    upgrade(data)?;
+  }
+  Ok(())
+}
+
+/// Deserializes models from serialized data of any version, upgrading it
+/// first. Implemented for every [`Model`].
+pub trait FromVersionedJson: Sized {
+  /// Upgrades `data` with [`upgrade_json`] and deserializes it.
+  ///
+  /// # Errors
+  /// Returns an error if the data can't be upgraded or deserialized.
+  fn from_versioned_json(data: Value) -> Result<Self, serde_json::Error>;
+}
+
+impl<M: Model> FromVersionedJson for M {
+  fn from_versioned_json(mut data: Value) -> Result<Self, serde_json::Error> {
+    upgrade_json::<M>(&mut data)?;
+    // through text, as some types only deserialize from borrowed data
+    serde_json::from_str(&data.to_string())
+  }
+}
@@ -0,0 +1,12 @@
+use db::DatabaseError;
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// Errors that can occur checking a rate limit.
+#[derive(Debug, Error, Diagnostic)]
+pub enum RateLimitError {
+  /// The database failed.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+}
@@ -0,0 +1,192 @@
+//! Token-bucket rate limiting with state shared through a database.
+//!
+//! [`RateLimiter`] keeps one [`Bucket`] record per key, e.g. per API key, so
+//! every process sharing the database enforces the same limit. Each check
+//! updates the bucket with
+//! [`Database::upsert_with`](db::Database::upsert_with), which is atomic, so
+//! concurrent checks never overspend a bucket.
+
+mod error;
+#[cfg(test)]
+mod tests;
+
+use std::{
+  fmt,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use db::{Clock, Database, SystemClock};
+use model::{Model, RecordId};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+pub use self::error::RateLimitError;
+
+/// The stored state of a token bucket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(table = "rate_limit_buckets")]
+pub struct Bucket {
+  /// The bucket's ID, derived from its key.
+  #[model(id)]
+  pub id:         RecordId<Bucket>,
+  /// The limiter name and key the bucket belongs to.
+  pub key:        String,
+  /// The tokens left as of `updated_at`.
+  pub tokens:     f64,
+  /// When the bucket was last checked.
+  pub updated_at: DateTime<Utc>,
+}
+
+/// A token-bucket limit: bursts of up to `capacity` requests, refilling one
+/// token every `refill_interval`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+  /// The most tokens a bucket holds.
+  pub capacity:        u32,
+  /// How long it takes to regain one token.
+  pub refill_interval: Duration,
+}
+
+impl RateLimit {
+  /// A limit of `count` requests per `period`, allowing bursts of the full
+  /// `count`.
+  #[must_use]
+  pub fn per(count: u32, period: Duration) -> Self {
+    Self {
+      capacity:        count,
+      refill_interval: period / count.max(1),
+    }
+  }
+}
+
+/// The outcome of a [`RateLimiter::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+  /// Whether the request is allowed.
+  pub allowed:     bool,
+  /// How many more requests would be allowed right now.
+  pub remaining:   u32,
+  /// If the request was denied, how long until a request would be allowed.
+  pub retry_after: Option<Duration>,
+}
+
+/// Enforces a [`RateLimit`] per key.
+///
+/// Limiters are identified by name, so limiters with different names keep
+/// separate buckets for the same key.
+#[derive(Clone)]
+pub struct RateLimiter {
+  db:    Database<Bucket>,
+  name:  String,
+  limit: RateLimit,
+  clock: Arc<dyn Clock>,
+}
+
+impl fmt::Debug for RateLimiter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("RateLimiter")
+      .field("name", &self.name)
+      .field("limit", &self.limit)
+      .finish_non_exhaustive()
+  }
+}
+
+impl RateLimiter {
+  /// Creates a new [`RateLimiter`] named `name`.
+  #[must_use]
+  pub fn new(
+    db: Database<Bucket>,
+    name: impl Into<String>,
+    limit: RateLimit,
+  ) -> Self {
+    Self {
+      db,
+      name: name.into(),
+      limit,
+      clock: SystemClock::shared(),
+    }
+  }
+
+  /// Sets the clock used to refill buckets.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// Takes a token from the bucket for `key`, if there is one.
+  pub async fn check(
+    &self,
+    key: &str,
+  ) -> Result<RateLimitDecision, RateLimitError> {
+    let now = self.clock.now();
+    let bucket_key = format!("{}:{key}", self.name);
+    let id = bucket_id(&bucket_key);
+    let capacity = f64::from(self.limit.capacity);
+    let refill_secs = self.limit.refill_interval.as_secs_f64();
+
+    let allowed = AtomicBool::new(false);
+    let bucket = self
+      .db
+      .upsert_with(id, &|bucket: Option<Bucket>| {
+        let tokens = match bucket {
+          Some(bucket) => {
+            let elapsed = (now - bucket.updated_at)
+              .to_std()
+              .unwrap_or_default()
+              .as_secs_f64();
+            let refilled = if refill_secs > 0.0 {
+              elapsed / refill_secs
+            } else {
+              capacity
+            };
+            (bucket.tokens + refilled).min(capacity)
+          }
+          None => capacity,
+        };
+        let take = tokens >= 1.0;
+        allowed.store(take, Ordering::Relaxed);
+        Bucket {
+          id,
+          key: bucket_key.clone(),
+          tokens: if take { tokens - 1.0 } else { tokens },
+          updated_at: now,
+        }
+      })
+      .await?;
+
+    let allowed = allowed.load(Ordering::Relaxed);
+    let retry_after = (!allowed).then(|| {
+      self
+        .limit
+        .refill_interval
+        .mul_f64((1.0 - bucket.tokens).max(0.0))
+    });
+    debug!(key = %bucket.key, allowed, "checked rate limit");
+
+    // tokens are between zero and the capacity
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let remaining = bucket.tokens.floor() as u32;
+
+    Ok(RateLimitDecision {
+      allowed,
+      remaining,
+      retry_after,
+    })
+  }
+}
+
+/// Derives a bucket's ID from its key, so every process finds the same
+/// record.
+fn bucket_id(key: &str) -> RecordId<Bucket> {
+  let digest = Sha256::digest(key.as_bytes());
+  let mut bytes = [0; 16];
+  bytes.copy_from_slice(&digest[..16]);
+  RecordId::from_ulid_u128(u128::from_be_bytes(bytes))
+}
@@ -0,0 +1,91 @@
+use std::{sync::Arc, time::Duration};
+
+use db::{Database, ManualClock, MockDatabase};
+
+use crate::{RateLimit, RateLimiter};
+
+const LIMIT: RateLimit = RateLimit {
+  capacity:        3,
+  refill_interval: Duration::from_secs(10),
+};
+
+fn setup() -> (RateLimiter, ManualClock) {
+  let clock = ManualClock::default();
+  let db = Database::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let limiter =
+    RateLimiter::new(db, "api", LIMIT).with_clock(Arc::new(clock.clone()));
+  (limiter, clock)
+}
+
+#[tokio::test]
+async fn test_allows_bursts_up_to_capacity() {
+  let (limiter, _) = setup();
+
+  for remaining in [2, 1, 0] {
+    let decision = limiter.check("key-a").await.unwrap();
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, remaining);
+    assert_eq!(decision.retry_after, None);
+  }
+
+  let decision = limiter.check("key-a").await.unwrap();
+  assert!(!decision.allowed);
+  assert_eq!(decision.retry_after, Some(Duration::from_secs(10)));
+}
+
+#[tokio::test]
+async fn test_tokens_refill_over_time() {
+  let (limiter, clock) = setup();
+  for _ in 0..3 {
+    assert!(limiter.check("key-a").await.unwrap().allowed);
+  }
+
+  clock.advance(Duration::from_secs(4));
+  let decision = limiter.check("key-a").await.unwrap();
+  assert!(!decision.allowed);
+  assert_eq!(decision.retry_after, Some(Duration::from_secs(6)));
+
+  clock.advance(Duration::from_secs(6));
+  assert!(limiter.check("key-a").await.unwrap().allowed);
+
+  // buckets never fill past capacity
+  clock.advance(Duration::from_hours(1));
+  assert_eq!(limiter.check("key-a").await.unwrap().remaining, 2);
+}
+
+#[tokio::test]
+async fn test_keys_and_limiters_are_independent() {
+  let (limiter, clock) = setup();
+  let other = RateLimiter::new(limiter.db.clone(), "uploads", LIMIT)
+    .with_clock(Arc::new(clock.clone()));
+  for _ in 0..3 {
+    assert!(limiter.check("key-a").await.unwrap().allowed);
+  }
+
+  assert!(!limiter.check("key-a").await.unwrap().allowed);
+  assert!(limiter.check("key-b").await.unwrap().allowed);
+  assert!(other.check("key-a").await.unwrap().allowed);
+}
+
+#[tokio::test]
+async fn test_concurrent_checks_never_overspend() {
+  let (limiter, _) = setup();
+
+  let mut handles = vec![];
+  for _ in 0..10 {
+    let limiter = limiter.clone();
+    handles.push(tokio::spawn(async move {
+      limiter.check("key-a").await.unwrap().allowed
+    }));
+  }
+
+  let mut allowed = 0;
+  for handle in handles {
+    if handle.await.unwrap() {
+      allowed += 1;
+    }
+  }
+  assert_eq!(allowed, 3);
+}
@@ -0,0 +1,290 @@
+use std::{
+  collections::{HashMap, HashSet, hash_map},
+  fmt,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::RecordId;
+
+// As with `RecordId`, these traits are implemented manually so they don't
+// require anything of `T`.
+
+/// A set of [`RecordId`]s.
+///
+/// Lookups are hash-based, but iteration and serialization are ordered by
+/// ULID, so the same set always serializes the same way. Serializes as a
+/// sequence of IDs.
+pub struct RecordIdSet<T>(HashSet<RecordId<T>>);
+
+impl<T> RecordIdSet<T> {
+  /// Creates an empty [`RecordIdSet`].
+  #[must_use]
+  pub fn new() -> Self { Self(HashSet::new()) }
+
+  /// Returns the number of IDs in the set.
+  #[must_use]
+  pub fn len(&self) -> usize { self.0.len() }
+
+  /// Returns `true` if the set holds no IDs.
+  #[must_use]
+  pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+  /// Returns `true` if the set holds `id`.
+  #[must_use]
+  pub fn contains(&self, id: &RecordId<T>) -> bool { self.0.contains(id) }
+
+  /// Adds `id` to the set, returning `false` if it was already present.
+  pub fn insert(&mut self, id: RecordId<T>) -> bool { self.0.insert(id) }
+
+  /// Removes `id` from the set, returning `false` if it wasn't present.
+  pub fn remove(&mut self, id: &RecordId<T>) -> bool { self.0.remove(id) }
+
+  /// Removes every ID from the set.
+  pub fn clear(&mut self) { self.0.clear(); }
+
+  /// Iterates over the IDs in ULID order.
+  pub fn iter(&self) -> std::vec::IntoIter<RecordId<T>> {
+    let mut ids: Vec<_> = self.0.iter().copied().collect();
+    ids.sort_unstable();
+    ids.into_iter()
+  }
+}
+
+impl<T> fmt::Debug for RecordIdSet<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_set().entries(self.iter()).finish()
+  }
+}
+
+impl<T> Clone for RecordIdSet<T> {
+  fn clone(&self) -> Self { Self(self.0.clone()) }
+}
+
+impl<T> PartialEq for RecordIdSet<T> {
+  fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+}
+
+impl<T> Eq for RecordIdSet<T> {}
+
+impl<T> Default for RecordIdSet<T> {
+  fn default() -> Self { Self::new() }
+}
+
+impl<T> FromIterator<RecordId<T>> for RecordIdSet<T> {
+  fn from_iter<I: IntoIterator<Item = RecordId<T>>>(iter: I) -> Self {
+    Self(iter.into_iter().collect())
+  }
+}
+
+impl<T> Extend<RecordId<T>> for RecordIdSet<T> {
+  fn extend<I: IntoIterator<Item = RecordId<T>>>(&mut self, iter: I) {
+    self.0.extend(iter);
+  }
+}
+
+impl<T> IntoIterator for RecordIdSet<T> {
+  type Item = RecordId<T>;
+  type IntoIter = std::vec::IntoIter<RecordId<T>>;
+
+  fn into_iter(self) -> Self::IntoIter { self.iter() }
+}
+
+impl<T> IntoIterator for &RecordIdSet<T> {
+  type Item = RecordId<T>;
+  type IntoIter = std::vec::IntoIter<RecordId<T>>;
+
+  fn into_iter(self) -> Self::IntoIter { self.iter() }
+}
+
+impl<T> Serialize for RecordIdSet<T>
+where
+  RecordId<T>: Serialize,
+{
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(self.iter())
+  }
+}
+
+impl<'de, T> Deserialize<'de> for RecordIdSet<T>
+where
+  RecordId<T>: Deserialize<'de>,
+{
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    HashSet::deserialize(deserializer).map(Self)
+  }
+}
+
+#[cfg(feature = "schemars")]
+impl<T> schemars::JsonSchema for RecordIdSet<T> {
+  fn inline_schema() -> bool { true }
+
+  fn schema_name() -> std::borrow::Cow<'static, str> {
+    <HashSet<RecordId<T>>>::schema_name()
+  }
+
+  fn json_schema(
+    generator: &mut schemars::SchemaGenerator,
+  ) -> schemars::Schema {
+    <HashSet<RecordId<T>>>::json_schema(generator)
+  }
+}
+
+/// A map keyed by [`RecordId`]s.
+///
+/// Lookups are hash-based, but iteration and serialization are ordered by
+/// ULID, so the same map always serializes the same way. Serializes as a
+/// map from IDs to values.
+pub struct RecordIdMap<T, V>(HashMap<RecordId<T>, V>);
+
+impl<T, V> RecordIdMap<T, V> {
+  /// Creates an empty [`RecordIdMap`].
+  #[must_use]
+  pub fn new() -> Self { Self(HashMap::new()) }
+
+  /// Returns the number of entries in the map.
+  #[must_use]
+  pub fn len(&self) -> usize { self.0.len() }
+
+  /// Returns `true` if the map holds no entries.
+  #[must_use]
+  pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+  /// Returns `true` if the map holds a value for `id`.
+  #[must_use]
+  pub fn contains_key(&self, id: &RecordId<T>) -> bool {
+    self.0.contains_key(id)
+  }
+
+  /// Returns the value for `id`.
+  #[must_use]
+  pub fn get(&self, id: &RecordId<T>) -> Option<&V> { self.0.get(id) }
+
+  /// Returns the value for `id`, mutably.
+  pub fn get_mut(&mut self, id: &RecordId<T>) -> Option<&mut V> {
+    self.0.get_mut(id)
+  }
+
+  /// Sets the value for `id`, returning the value it replaced.
+  pub fn insert(&mut self, id: RecordId<T>, value: V) -> Option<V> {
+    self.0.insert(id, value)
+  }
+
+  /// Removes the value for `id`, returning it.
+  pub fn remove(&mut self, id: &RecordId<T>) -> Option<V> { self.0.remove(id) }
+
+  /// Returns the entry for `id`, for in-place updates.
+  pub fn entry(
+    &mut self,
+    id: RecordId<T>,
+  ) -> hash_map::Entry<'_, RecordId<T>, V> {
+    self.0.entry(id)
+  }
+
+  /// Removes every entry from the map.
+  pub fn clear(&mut self) { self.0.clear(); }
+
+  /// Iterates over the entries in ULID order.
+  pub fn iter(&self) -> std::vec::IntoIter<(RecordId<T>, &V)> {
+    let mut entries: Vec<_> = self.0.iter().map(|(id, v)| (*id, v)).collect();
+    entries.sort_unstable_by_key(|(id, _)| *id);
+    entries.into_iter()
+  }
+
+  /// Iterates over the IDs in ULID order.
+  pub fn keys(&self) -> impl Iterator<Item = RecordId<T>> {
+    self.iter().map(|(id, _)| id)
+  }
+
+  /// Iterates over the values in the ULID order of their IDs.
+  pub fn values(&self) -> impl Iterator<Item = &V> {
+    self.iter().map(|(_, v)| v)
+  }
+}
+
+impl<T, V: fmt::Debug> fmt::Debug for RecordIdMap<T, V> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_map().entries(self.iter()).finish()
+  }
+}
+
+impl<T, V: Clone> Clone for RecordIdMap<T, V> {
+  fn clone(&self) -> Self { Self(self.0.clone()) }
+}
+
+impl<T, V: PartialEq> PartialEq for RecordIdMap<T, V> {
+  fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+}
+
+impl<T, V: Eq> Eq for RecordIdMap<T, V> {}
+
+impl<T, V> Default for RecordIdMap<T, V> {
+  fn default() -> Self { Self::new() }
+}
+
+impl<T, V> FromIterator<(RecordId<T>, V)> for RecordIdMap<T, V> {
+  fn from_iter<I: IntoIterator<Item = (RecordId<T>, V)>>(iter: I) -> Self {
+    Self(iter.into_iter().collect())
+  }
+}
+
+impl<T, V> Extend<(RecordId<T>, V)> for RecordIdMap<T, V> {
+  fn extend<I: IntoIterator<Item = (RecordId<T>, V)>>(&mut self, iter: I) {
+    self.0.extend(iter);
+  }
+}
+
+impl<T, V> IntoIterator for RecordIdMap<T, V> {
+  type Item = (RecordId<T>, V);
+  type IntoIter = std::vec::IntoIter<(RecordId<T>, V)>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    let mut entries: Vec<_> = self.0.into_iter().collect();
+    entries.sort_unstable_by_key(|(id, _)| *id);
+    entries.into_iter()
+  }
+}
+
+impl<'a, T, V> IntoIterator for &'a RecordIdMap<T, V> {
+  type Item = (RecordId<T>, &'a V);
+  type IntoIter = std::vec::IntoIter<(RecordId<T>, &'a V)>;
+
+  fn into_iter(self) -> Self::IntoIter { self.iter() }
+}
+
+impl<T, V: Serialize> Serialize for RecordIdMap<T, V>
+where
+  RecordId<T>: Serialize,
+{
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(self.iter())
+  }
+}
+
+impl<'de, T, V: Deserialize<'de>> Deserialize<'de> for RecordIdMap<T, V>
+where
+  RecordId<T>: Deserialize<'de>,
+{
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    HashMap::deserialize(deserializer).map(Self)
+  }
+}
+
+#[cfg(feature = "schemars")]
+impl<T, V: schemars::JsonSchema> schemars::JsonSchema for RecordIdMap<T, V> {
+  fn inline_schema() -> bool { true }
+
+  fn schema_name() -> std::borrow::Cow<'static, str> {
+    <HashMap<RecordId<T>, V>>::schema_name()
+  }
+
+  fn json_schema(
+    generator: &mut schemars::SchemaGenerator,
+  ) -> schemars::Schema {
+    <HashMap<RecordId<T>, V>>::json_schema(generator)
+  }
+}
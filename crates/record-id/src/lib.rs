@@ -1,3 +1,7 @@
+mod collections;
+#[cfg(test)]
+mod tests;
+
 use std::{
   array::TryFromSliceError, fmt, hash::Hash, marker::PhantomData, str::FromStr,
 };
@@ -5,6 +9,8 @@ use std::{
 use serde::{Deserialize, Serialize};
 pub use ulid::Ulid;
 
+pub use self::collections::{RecordIdMap, RecordIdSet};
+
 // Generally we have to implement these traits manually that we'd normally
 // derive because of the `PhantomData` field; the derives assume that the `T`
 // generic also has to implement the trait we're deriving.
@@ -104,3 +110,37 @@ impl<T> From<RecordId<T>> for Ulid {
 impl<T> Default for RecordId<T> {
   fn default() -> Self { Self::new() }
 }
+
+/// Record IDs are described as ULID strings, independent of `T`, matching
+/// their serialized form.
+#[cfg(feature = "schemars")]
+impl<T> schemars::JsonSchema for RecordId<T> {
+  fn inline_schema() -> bool { true }
+
+  fn schema_name() -> std::borrow::Cow<'static, str> { "RecordId".into() }
+
+  fn json_schema(
+    _generator: &mut schemars::SchemaGenerator,
+  ) -> schemars::Schema {
+    schemars::json_schema!({
+      "type": "string",
+      "format": "ulid",
+      "pattern": "^[0-9A-HJKMNP-TV-Za-hjkmnp-tv-z]{26}$"
+    })
+  }
+}
+
+/// Fake record IDs are stamped with the current time, with random bits drawn
+/// from the given RNG.
+#[cfg(feature = "fake")]
+impl<T> fake::Dummy<fake::Faker> for RecordId<T> {
+  fn dummy_with_rng<R: fake::rand::Rng + ?Sized>(
+    _config: &fake::Faker,
+    rng: &mut R,
+  ) -> Self {
+    Self(
+      Ulid::from_parts(Ulid::new().timestamp_ms(), rng.random()),
+      PhantomData,
+    )
+  }
+}
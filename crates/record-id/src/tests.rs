@@ -0,0 +1,39 @@
+use crate::{RecordId, RecordIdMap, RecordIdSet};
+
+fn id(value: u128) -> RecordId<()> { RecordId::from_ulid_u128(value) }
+
+#[test]
+fn test_set_iterates_in_ulid_order() {
+  let set: RecordIdSet<()> = [id(3), id(1), id(2), id(1)].into_iter().collect();
+  assert_eq!(set.len(), 3);
+  assert!(set.contains(&id(2)));
+  assert_eq!(set.iter().collect::<Vec<_>>(), [id(1), id(2), id(3)]);
+}
+
+#[test]
+fn test_set_serde_round_trip() {
+  let set: RecordIdSet<()> = [id(2), id(1)].into_iter().collect();
+  let json = serde_json::to_string(&set).unwrap();
+  assert_eq!(json, serde_json::to_string(&[id(1), id(2)]).unwrap());
+  assert_eq!(serde_json::from_str::<RecordIdSet<()>>(&json).unwrap(), set);
+}
+
+#[test]
+fn test_map_serde_round_trip() {
+  let mut map = RecordIdMap::<(), u32>::new();
+  map.insert(id(2), 20);
+  map.insert(id(1), 10);
+  *map.entry(id(3)).or_default() += 30;
+  assert_eq!(map.keys().collect::<Vec<_>>(), [id(1), id(2), id(3)]);
+  assert_eq!(map.values().copied().collect::<Vec<_>>(), [10, 20, 30]);
+
+  let json = serde_json::to_string(&map).unwrap();
+  assert_eq!(
+    json,
+    format!(r#"{{"{}":10,"{}":20,"{}":30}}"#, id(1), id(2), id(3))
+  );
+  assert_eq!(
+    serde_json::from_str::<RecordIdMap<(), u32>>(&json).unwrap(),
+    map
+  );
+}
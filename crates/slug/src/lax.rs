@@ -5,6 +5,7 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 
 use self::sanitize::lax_slugify;
+use crate::Transliteration;
 
 /// A laxer slug generated from arbitrary text.
 ///
@@ -33,22 +34,68 @@ pub struct LaxSlug(Box<str>);
 impl LaxSlug {
   /// Slugifies the input and creates a new slug.
   #[must_use]
-  pub fn new(input: &str) -> Self { Self(lax_slugify(input).into_boxed_str()) }
+  pub fn new(input: &str) -> Self {
+    Self(lax_slugify(input, None).into_boxed_str())
+  }
+  /// Slugifies the input, transliterating it with the given profile, and
+  /// creates a new slug.
+  #[must_use]
+  pub fn with_transliteration(
+    input: &str,
+    transliteration: &Transliteration,
+  ) -> Self {
+    Self(lax_slugify(input, Some(transliteration)).into_boxed_str())
+  }
   /// Creates a new slug without first slugifying the input.
   #[must_use]
   pub fn new_unchecked(input: &str) -> Self {
     Self(input.to_owned().into_boxed_str())
   }
 
+  /// Parses a string slice which is already a valid lax slug, without
+  /// re-slugifying it.
+  ///
+  /// # Errors
+  /// Fails with the first character outside of `[-.+_0-9a-zA-Z]`.
+  pub fn parse_strict(input: &str) -> Result<Self, InvalidLaxSlug> {
+    match input
+      .chars()
+      .enumerate()
+      .find(|(_, c)| !is_lax_slug_char(*c))
+    {
+      Some((position, character)) => Err(InvalidLaxSlug {
+        position,
+        character,
+      }),
+      None => Ok(Self::new_unchecked(input)),
+    }
+  }
+
   /// Validates that a string slice is already slugified.
   #[must_use]
-  pub fn validate(input: &str) -> bool { lax_slugify(input) == input }
+  pub fn validate(input: &str) -> bool { lax_slugify(input, None) == input }
 
   /// Returns a reference to the slug contents.
   #[must_use]
   pub fn as_str(&self) -> &str { &self.0 }
 }
 
+/// Whether a character may appear in a lax slug.
+const fn is_lax_slug_char(c: char) -> bool {
+  matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '+' | '.' | '_' | '-')
+}
+
+/// An error from [`LaxSlug::parse_strict`], for input which isn't already a
+/// valid lax slug.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid character {character:?} at position {position} in lax slug")]
+pub struct InvalidLaxSlug {
+  /// The position of the character, counted in characters from 0.
+  pub position:  usize,
+  /// The offending character.
+  pub character: char,
+}
+
 // formatting
 impl fmt::Display for LaxSlug {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -89,3 +136,27 @@ impl PartialEq<str> for LaxSlug {
 impl PartialEq<&str> for LaxSlug {
   fn eq(&self, other: &&str) -> bool { self.0.as_ref() == *other }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_strict() {
+    assert_eq!(
+      LaxSlug::parse_strict("foo_bar.baz+qux").unwrap(),
+      "foo_bar.baz+qux"
+    );
+    assert_eq!(
+      LaxSlug::parse_strict("hello wörld"),
+      Err(InvalidLaxSlug {
+        position:  5,
+        character: ' ',
+      })
+    );
+    assert_eq!(
+      LaxSlug::parse_strict("wörld").unwrap_err().to_string(),
+      "invalid character 'ö' at position 1 in lax slug"
+    );
+  }
+}
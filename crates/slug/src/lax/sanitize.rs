@@ -2,6 +2,8 @@
 
 use deunicode::AsciiChars;
 
+use crate::Transliteration;
+
 /// Convert any unicode string to "lax slug" (useful for nix store paths)
 ///
 /// "Lax slugs" can contain any combination of characters from the regex set
@@ -14,8 +16,16 @@ use deunicode::AsciiChars;
 /// - '_' (underscore)
 /// - '-' (dash)
 ///
-/// Any other characters will be replaced with a single dash.
-pub fn lax_slugify(s: &str) -> String {
+/// Any other characters will be replaced with a single dash. Characters
+/// mapped by `transliteration` are replaced before folding to ASCII.
+pub fn lax_slugify(
+  s: &str,
+  transliteration: Option<&Transliteration>,
+) -> String {
+  let s = match transliteration {
+    Some(transliteration) => transliteration.apply(s),
+    None => s.into(),
+  };
   let mut slug = String::with_capacity(s.len());
 
   // deunicode non-ascii and replace with ascii substrings, then iterate on
@@ -45,6 +55,8 @@ fn push_ascii_char(slug: &mut String, x: u8) {
 mod tests {
   use super::*;
 
+  fn lax_slugify(s: &str) -> String { super::lax_slugify(s, None) }
+
   #[test]
   fn test_lax_slugify_basic() {
     // Basic ASCII input
@@ -88,4 +100,17 @@ mod tests {
     assert_eq!(lax_slugify("a"), "a");
     assert_eq!(lax_slugify("-_-"), "-_-");
   }
+
+  #[test]
+  fn test_lax_slugify_transliteration() {
+    let german = Transliteration::german();
+    assert_eq!(lax_slugify("Schöne Grüße"), "Schone-Grusse");
+    assert_eq!(
+      super::lax_slugify("Schöne Grüße", Some(&german)),
+      "Schoene-Gruesse"
+    );
+
+    let custom = Transliteration::custom([('&', "and")]);
+    assert_eq!(super::lax_slugify("R&D", Some(&custom)), "RandD");
+  }
 }
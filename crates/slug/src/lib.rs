@@ -1,6 +1,13 @@
 //! Provides slug types.
 
 mod lax;
+mod store_path;
 mod strict;
+mod transliterate;
 
-pub use self::{lax::*, strict::*};
+pub use self::{
+  lax::*,
+  store_path::{InvalidStorePathName, StorePathName},
+  strict::*,
+  transliterate::Transliteration,
+};
@@ -0,0 +1,200 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::LaxSlug;
+
+/// The name component of a Nix store path, e.g. `hello-2.12` in
+/// `/nix/store/<hash>-hello-2.12`.
+///
+/// Names are non-empty, at most [`MAX_LENGTH`](Self::MAX_LENGTH) characters
+/// long, don't start with a dot, and consist of characters from the regex set
+/// `[-.+_?=0-9a-zA-Z]`.
+///
+/// # Examples
+/// ```
+/// # use slug::{LaxSlug, StorePathName};
+/// let name = StorePathName::from(LaxSlug::new(".hello world"));
+/// assert_eq!(name.as_str(), "_hello-world");
+/// assert!(StorePathName::parse(".hidden").is_err());
+/// ```
+#[derive(
+  Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct StorePathName(Box<str>);
+
+/// An error from parsing an invalid [`StorePathName`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidStorePathName {
+  /// The name is empty.
+  #[error("store path name is empty")]
+  Empty,
+  /// The name is longer than [`StorePathName::MAX_LENGTH`].
+  #[error(
+    "store path name is {0} characters long, more than the maximum of {max}",
+    max = StorePathName::MAX_LENGTH
+  )]
+  TooLong(usize),
+  /// The name starts with a dot.
+  #[error("store path name starts with a dot")]
+  LeadingDot,
+  /// The name contains a character outside of the allowed set.
+  #[error(
+    "invalid character {character:?} at position {position} in store path name"
+  )]
+  InvalidCharacter {
+    /// The position of the character, counted in characters from 0.
+    position:  usize,
+    /// The offending character.
+    character: char,
+  },
+}
+
+impl StorePathName {
+  /// The maximum length of a store path name, in characters.
+  pub const MAX_LENGTH: usize = 211;
+
+  /// Parses a valid store path name.
+  pub fn parse(input: &str) -> Result<Self, InvalidStorePathName> {
+    if let Some((position, character)) = input
+      .chars()
+      .enumerate()
+      .find(|(_, c)| !is_store_path_char(*c))
+    {
+      return Err(InvalidStorePathName::InvalidCharacter {
+        position,
+        character,
+      });
+    }
+    if input.is_empty() {
+      return Err(InvalidStorePathName::Empty);
+    }
+    if input.len() > Self::MAX_LENGTH {
+      return Err(InvalidStorePathName::TooLong(input.len()));
+    }
+    if input.starts_with('.') {
+      return Err(InvalidStorePathName::LeadingDot);
+    }
+
+    Ok(Self(input.into()))
+  }
+
+  /// Validates that a string slice is a valid store path name.
+  #[must_use]
+  pub fn validate(input: &str) -> bool { Self::parse(input).is_ok() }
+
+  /// Returns a reference to the name.
+  #[must_use]
+  pub fn as_str(&self) -> &str { &self.0 }
+}
+
+/// Whether a character may appear in a store path name.
+const fn is_store_path_char(c: char) -> bool {
+  matches!(
+    c,
+    'a'..='z' | 'A'..='Z' | '0'..='9' | '+' | '-' | '.' | '_' | '?' | '='
+  )
+}
+
+// formatting
+impl fmt::Display for StorePathName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+impl fmt::Debug for StorePathName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("StorePathName").field(&self.0).finish()
+  }
+}
+
+// referencing
+impl AsRef<str> for StorePathName {
+  fn as_ref(&self) -> &str { &self.0 }
+}
+impl std::ops::Deref for StorePathName {
+  type Target = str;
+  fn deref(&self) -> &Self::Target { &self.0 }
+}
+
+// conversions
+/// Lax slugs share the store path name character set, so conversion only
+/// truncates the slug to [`StorePathName::MAX_LENGTH`], replaces a leading
+/// dot with an underscore, and replaces an empty slug with `_`.
+impl From<LaxSlug> for StorePathName {
+  fn from(slug: LaxSlug) -> Self {
+    // lax slugs are ascii, so bytes are characters
+    let end = slug.len().min(Self::MAX_LENGTH);
+    let mut name = slug.as_str()[..end].to_owned();
+    if name.starts_with('.') {
+      name.replace_range(..1, "_");
+    }
+    if name.is_empty() {
+      name.push('_');
+    }
+    Self(name.into_boxed_str())
+  }
+}
+impl TryFrom<&str> for StorePathName {
+  type Error = InvalidStorePathName;
+  fn try_from(s: &str) -> Result<Self, Self::Error> { Self::parse(s) }
+}
+impl TryFrom<String> for StorePathName {
+  type Error = InvalidStorePathName;
+  fn try_from(s: String) -> Result<Self, Self::Error> { Self::parse(&s) }
+}
+impl From<StorePathName> for String {
+  fn from(name: StorePathName) -> Self { name.0.into() }
+}
+impl std::str::FromStr for StorePathName {
+  type Err = InvalidStorePathName;
+  fn from_str(s: &str) -> Result<Self, Self::Err> { Self::parse(s) }
+}
+
+// comparisons
+impl PartialEq<str> for StorePathName {
+  fn eq(&self, other: &str) -> bool { self.0.as_ref() == other }
+}
+impl PartialEq<&str> for StorePathName {
+  fn eq(&self, other: &&str) -> bool { self.0.as_ref() == *other }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(StorePathName::parse("hello-2.12").unwrap(), "hello-2.12");
+    assert_eq!(StorePathName::parse("a?b=c").unwrap(), "a?b=c");
+    assert_eq!(StorePathName::parse(""), Err(InvalidStorePathName::Empty));
+    assert_eq!(
+      StorePathName::parse(".hidden"),
+      Err(InvalidStorePathName::LeadingDot)
+    );
+    assert_eq!(
+      StorePathName::parse("a/b"),
+      Err(InvalidStorePathName::InvalidCharacter {
+        position:  1,
+        character: '/',
+      })
+    );
+    assert_eq!(
+      StorePathName::parse(&"a".repeat(212)),
+      Err(InvalidStorePathName::TooLong(212))
+    );
+  }
+
+  #[test]
+  fn test_from_lax_slug() {
+    let name = StorePathName::from(LaxSlug::new("..config"));
+    assert_eq!(name, "_.config");
+    assert!(StorePathName::validate(&name));
+
+    assert_eq!(StorePathName::from(LaxSlug::new("")), "_");
+
+    let long = StorePathName::from(LaxSlug::new(&"a".repeat(300)));
+    assert_eq!(long.len(), StorePathName::MAX_LENGTH);
+  }
+}
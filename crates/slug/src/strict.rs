@@ -5,6 +5,7 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 
 use self::sanitize::strict_slugify;
+use crate::Transliteration;
 
 /// A URL-safe slug generated from arbitrary text.
 ///
@@ -29,7 +30,12 @@ impl Slug {
   /// Slugifies the input and creates a new slug.
   #[must_use]
   pub fn new(input: &str) -> Self {
-    Self(strict_slugify(input).into_boxed_str())
+    Self::with_options(input, &SlugOptions::DEFAULT)
+  }
+  /// Slugifies the input with custom rules and creates a new slug.
+  #[must_use]
+  pub fn with_options(input: &str, options: &SlugOptions) -> Self {
+    Self(strict_slugify(input, options).into_boxed_str())
   }
   /// Creates a new slug without first slugifying the input.
   #[must_use]
@@ -39,13 +45,99 @@ impl Slug {
 
   /// Validates that a string slice is already slugified.
   #[must_use]
-  pub fn validate(input: &str) -> bool { strict_slugify(input) == input }
+  pub fn validate(input: &str) -> bool {
+    Self::validate_with_options(input, &SlugOptions::DEFAULT)
+  }
+  /// Validates that a string slice is already slugified with custom rules.
+  #[must_use]
+  pub fn validate_with_options(input: &str, options: &SlugOptions) -> bool {
+    strict_slugify(input, options) == input
+  }
 
   /// Returns a reference to the slug contents.
   #[must_use]
   pub fn as_str(&self) -> &str { &self.0 }
 }
 
+/// Rules for strict slugification.
+///
+/// The defaults lowercase the input and separate words with hyphens, with no
+/// maximum length.
+///
+/// # Examples
+/// ```
+/// # use slug::{Slug, SlugOptions};
+/// let options = SlugOptions::default()
+///   .with_separator('_')
+///   .with_max_length(8);
+/// let slug = Slug::with_options("Hello World!", &options);
+/// assert_eq!(slug.as_str(), "hello_wo");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlugOptions {
+  separator:         char,
+  preserve_case:     bool,
+  allow_underscores: bool,
+  max_length:        Option<usize>,
+  transliteration:   Option<Transliteration>,
+}
+
+impl SlugOptions {
+  /// The default rules.
+  pub const DEFAULT: Self = Self {
+    separator:         '-',
+    preserve_case:     false,
+    allow_underscores: false,
+    max_length:        None,
+    transliteration:   None,
+  };
+
+  /// Sets the character which replaces runs of disallowed characters.
+  #[must_use]
+  pub const fn with_separator(mut self, separator: char) -> Self {
+    self.separator = separator;
+    self
+  }
+
+  /// Sets whether uppercase letters are kept rather than lowercased.
+  #[must_use]
+  pub const fn with_preserve_case(mut self, preserve_case: bool) -> Self {
+    self.preserve_case = preserve_case;
+    self
+  }
+
+  /// Sets whether underscores are kept rather than replaced.
+  #[must_use]
+  pub const fn with_underscores(mut self, allow_underscores: bool) -> Self {
+    self.allow_underscores = allow_underscores;
+    self
+  }
+
+  /// Sets the maximum length of the slug, in characters.
+  #[must_use]
+  pub const fn with_max_length(mut self, max_length: usize) -> Self {
+    self.max_length = Some(max_length);
+    self
+  }
+}
+
+impl SlugOptions {
+  /// Sets the transliteration profile applied before folding non-ASCII
+  /// characters.
+  #[must_use]
+  pub fn with_transliteration(
+    mut self,
+    transliteration: Transliteration,
+  ) -> Self {
+    self.transliteration = Some(transliteration);
+    self
+  }
+}
+
+impl Default for SlugOptions {
+  fn default() -> Self { Self::DEFAULT }
+}
+
 // formatting
 impl fmt::Display for Slug {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
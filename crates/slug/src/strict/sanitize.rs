@@ -1,26 +1,39 @@
 use deunicode::AsciiChars;
 
+use super::SlugOptions;
+
 /// Convert any unicode string to a "strict slug" (useful for any user-defined
 /// name that might be used in a URL component)
 ///
-/// The returned "slug" will consist of a-z, 0-9, and '-'. Furthermore, a slug
-/// will never contain more than one '-' in a row and will never start or end
-/// with '-'.
-pub(super) fn strict_slugify(s: &str) -> String {
+/// With the default options, the returned "slug" will consist of a-z, 0-9,
+/// and '-'. Furthermore, a slug will never contain more than one separator in
+/// a row and will never start or end with one.
+pub(super) fn strict_slugify(s: &str, options: &SlugOptions) -> String {
+  let s = match &options.transliteration {
+    Some(transliteration) => transliteration.apply(s),
+    None => s.into(),
+  };
   let mut slug = String::with_capacity(s.len());
-  // starts with true to avoid leading `-`
-  let mut prev_is_dash = true;
+  // starts with true to avoid a leading separator
+  let mut prev_is_separator = true;
 
   // deunicode non-ascii and replace with ascii substrings, then iterate on
   // the characters in those substrings
   let iter = s.ascii_chars().flatten().flat_map(|s| s.chars());
 
   for c in iter {
-    push_ascii_char(&mut slug, c as u8, &mut prev_is_dash);
+    push_ascii_char(&mut slug, c as u8, options, &mut prev_is_separator);
+  }
+
+  // truncate to the maximum length, in characters
+  if let Some(max_length) = options.max_length
+    && let Some((end, _)) = slug.char_indices().nth(max_length)
+  {
+    slug.truncate(end);
   }
 
-  // remove trailing dash
-  if slug.ends_with('-') {
+  // remove trailing separator
+  if slug.ends_with(options.separator) {
     slug.pop();
   }
 
@@ -29,22 +42,35 @@ pub(super) fn strict_slugify(s: &str) -> String {
   slug
 }
 
-fn push_ascii_char(slug: &mut String, x: u8, prev_is_dash: &mut bool) {
+fn push_ascii_char(
+  slug: &mut String,
+  x: u8,
+  options: &SlugOptions,
+  prev_is_separator: &mut bool,
+) {
   match x {
     b'a'..=b'z' | b'0'..=b'9' => {
-      *prev_is_dash = false;
+      *prev_is_separator = false;
       slug.push(x.into());
     }
     b'A'..=b'Z' => {
-      *prev_is_dash = false;
-      // Manual lowercasing as Rust to_lowercase() is unicode
-      // aware and therefore much slower
-      slug.push((x - b'A' + b'a').into());
+      *prev_is_separator = false;
+      if options.preserve_case {
+        slug.push(x.into());
+      } else {
+        // Manual lowercasing as Rust to_lowercase() is unicode
+        // aware and therefore much slower
+        slug.push((x - b'A' + b'a').into());
+      }
+    }
+    b'_' if options.allow_underscores => {
+      *prev_is_separator = false;
+      slug.push('_');
     }
     _ => {
-      if !*prev_is_dash {
-        slug.push('-');
-        *prev_is_dash = true;
+      if !*prev_is_separator {
+        slug.push(options.separator);
+        *prev_is_separator = true;
       }
     }
   }
@@ -53,6 +79,11 @@ fn push_ascii_char(slug: &mut String, x: u8, prev_is_dash: &mut bool) {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::Transliteration;
+
+  fn strict_slugify(s: &str) -> String {
+    super::strict_slugify(s, &SlugOptions::default())
+  }
 
   #[test]
   fn test_strict_slugify_basic() {
@@ -95,4 +126,36 @@ mod tests {
     assert_eq!(strict_slugify("a"), "a");
     assert_eq!(strict_slugify("-_-"), "");
   }
+
+  #[test]
+  fn test_strict_slugify_options() {
+    let options = SlugOptions::default()
+      .with_separator('_')
+      .with_preserve_case(true);
+    assert_eq!(
+      super::strict_slugify("Hello World!", &options),
+      "Hello_World"
+    );
+
+    let options = SlugOptions::default().with_underscores(true);
+    assert_eq!(
+      super::strict_slugify("foo_bar baz", &options),
+      "foo_bar-baz"
+    );
+
+    // truncation never leaves a trailing separator
+    let options = SlugOptions::default().with_max_length(6);
+    assert_eq!(super::strict_slugify("hello world", &options), "hello");
+    assert_eq!(super::strict_slugify("helloworld", &options), "hellow");
+  }
+
+  #[test]
+  fn test_strict_slugify_transliteration() {
+    let options = SlugOptions::default()
+      .with_transliteration(Transliteration::danish().with('&', " and "));
+    assert_eq!(
+      super::strict_slugify("Blåbær & Ærø", &options),
+      "blaabaer-and-aeroe"
+    );
+  }
 }
@@ -0,0 +1,83 @@
+use std::{borrow::Cow, collections::HashMap};
+
+/// A transliteration profile, mapping characters to ASCII replacements before
+/// the default folding of non-ASCII characters.
+///
+/// The default folding is language-agnostic, and gets some languages wrong,
+/// e.g. folding German "ö" to "o" rather than "oe".
+///
+/// # Examples
+/// ```
+/// # use slug::{Slug, SlugOptions, Transliteration};
+/// let options =
+///   SlugOptions::default().with_transliteration(Transliteration::german());
+/// let slug = Slug::with_options("Schöne Grüße", &options);
+/// assert_eq!(slug.as_str(), "schoene-gruesse");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transliteration {
+  map: HashMap<char, Box<str>>,
+}
+
+impl Transliteration {
+  /// Creates a profile from a custom mapping table.
+  #[must_use]
+  pub fn custom<S: Into<Box<str>>>(
+    map: impl IntoIterator<Item = (char, S)>,
+  ) -> Self {
+    Self {
+      map: map.into_iter().map(|(c, s)| (c, s.into())).collect(),
+    }
+  }
+
+  /// The German profile, which expands umlauts and "ß".
+  #[must_use]
+  pub fn german() -> Self {
+    Self::custom([
+      ('ä', "ae"),
+      ('ö', "oe"),
+      ('ü', "ue"),
+      ('Ä', "Ae"),
+      ('Ö', "Oe"),
+      ('Ü', "Ue"),
+      ('ß', "ss"),
+      ('ẞ', "SS"),
+    ])
+  }
+
+  /// The Danish and Norwegian profile, which expands "æ", "ø" and "å".
+  #[must_use]
+  pub fn danish() -> Self {
+    Self::custom([
+      ('æ', "ae"),
+      ('ø', "oe"),
+      ('å', "aa"),
+      ('Æ', "Ae"),
+      ('Ø', "Oe"),
+      ('Å', "Aa"),
+    ])
+  }
+
+  /// Adds or replaces the mapping for a character.
+  #[must_use]
+  pub fn with(mut self, c: char, replacement: impl Into<Box<str>>) -> Self {
+    self.map.insert(c, replacement.into());
+    self
+  }
+
+  /// Replaces every mapped character in `s`.
+  pub(crate) fn apply<'a>(&self, s: &'a str) -> Cow<'a, str> {
+    if !s.chars().any(|c| self.map.contains_key(&c)) {
+      return Cow::Borrowed(s);
+    }
+
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+      match self.map.get(&c) {
+        Some(replacement) => result.push_str(replacement),
+        None => result.push(c),
+      }
+    }
+    Cow::Owned(result)
+  }
+}
@@ -0,0 +1,78 @@
+use axum::{
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use storage::BlobStorageError;
+use tracing::error;
+
+/// An error from serving or ingesting a blob, convertible into a response.
+#[derive(Debug)]
+pub enum BlobError {
+  /// The storage backend failed.
+  Storage(BlobStorageError),
+  /// The requested range lies outside the blob, which is `size` bytes long.
+  RangeNotSatisfiable {
+    /// The size of the blob in bytes.
+    size: u64,
+  },
+  /// A form upload was malformed.
+  InvalidForm(String),
+  /// A form upload's token was invalid or expired, or the upload didn't
+  /// meet its policy.
+  PolicyDenied(String),
+}
+
+impl From<BlobStorageError> for BlobError {
+  fn from(error: BlobStorageError) -> Self { BlobError::Storage(error) }
+}
+
+impl BlobError {
+  /// The status code this error is reported with.
+  #[must_use]
+  pub const fn status(&self) -> StatusCode {
+    match self {
+      BlobError::Storage(error) => match error {
+        BlobStorageError::NotFound(_) => StatusCode::NOT_FOUND,
+        BlobStorageError::AlreadyExists(_) => StatusCode::CONFLICT,
+        BlobStorageError::PermissionDenied { .. } => StatusCode::FORBIDDEN,
+        BlobStorageError::InvalidInput(_)
+        | BlobStorageError::StreamError(_) => StatusCode::BAD_REQUEST,
+        BlobStorageError::Rejected(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        BlobStorageError::Locked(_) => StatusCode::LOCKED,
+        BlobStorageError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+        BlobStorageError::NetworkError { .. } => StatusCode::BAD_GATEWAY,
+        BlobStorageError::InvalidConfig(_)
+        | BlobStorageError::IoError(_)
+        | BlobStorageError::SerializationError(_)
+        | BlobStorageError::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      },
+      BlobError::RangeNotSatisfiable { .. } => {
+        StatusCode::RANGE_NOT_SATISFIABLE
+      }
+      BlobError::InvalidForm(_) => StatusCode::BAD_REQUEST,
+      BlobError::PolicyDenied(_) => StatusCode::FORBIDDEN,
+    }
+  }
+}
+
+impl IntoResponse for BlobError {
+  fn into_response(self) -> Response {
+    let status = self.status();
+    match self {
+      BlobError::RangeNotSatisfiable { size } => (status, [(
+        axum::http::header::CONTENT_RANGE,
+        format!("bytes */{size}"),
+      )])
+        .into_response(),
+      // don't leak backend details to the client
+      BlobError::Storage(error) if status.is_server_error() => {
+        error!(%error, "blob storage operation failed");
+        status.into_response()
+      }
+      BlobError::Storage(error) => (status, error.to_string()).into_response(),
+      BlobError::InvalidForm(reason) | BlobError::PolicyDenied(reason) => {
+        (status, reason).into_response()
+      }
+    }
+  }
+}
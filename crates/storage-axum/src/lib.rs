@@ -0,0 +1,22 @@
+//! Axum integration for [`BlobStorage`](storage::BlobStorage).
+//!
+//! [`blob_response`] streams a blob as a [`Response`](axum::response::Response)
+//! with content headers, conditional requests, and single-range requests.
+//! [`BlobBody`] extracts a request body as a stream which can be passed to
+//! [`put_stream`](storage::BlobStorage::put_stream) without buffering.
+//! [`PostPolicyEmulator`] emulates pre-signed form uploads for backends
+//! without them, for development servers.
+
+mod error;
+mod post;
+mod response;
+#[cfg(test)]
+mod tests;
+mod upload;
+
+pub use self::{
+  error::BlobError,
+  post::PostPolicyEmulator,
+  response::{blob_response, content_type_for},
+  upload::{BlobBody, put_body},
+};
@@ -0,0 +1,184 @@
+use std::{collections::BTreeMap, fmt, sync::Arc, time::Duration};
+
+use axum::extract::Multipart;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD as BASE64};
+use chrono::Utc;
+use futures::stream;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use storage::{
+  BlobKey, BlobStorage, PostCondition, PresignedPost, UploadOptions,
+};
+use tracing::debug;
+
+use crate::BlobError;
+
+/// The form field carrying the signed policy.
+const TOKEN_FIELD: &str = "token";
+
+/// What a token permits: an upload to `key` meeting `conditions`, until
+/// `expires_at` milliseconds after the epoch.
+#[derive(Debug, Serialize, Deserialize)]
+struct Policy {
+  key:        BlobKey,
+  conditions: Vec<PostCondition>,
+  expires_at: i64,
+}
+
+/// Emulates pre-signed form uploads for backends without them, such as the
+/// memory and filesystem backends, so a development server can accept
+/// browser uploads the way S3 does.
+///
+/// [`presign`](Self::presign) issues a [`PresignedPost`] whose fields carry
+/// a token: the policy, signed with a secret only the server knows. The
+/// server routes the URL to a handler which passes the form to
+/// [`accept`](Self::accept), which checks the token and the policy's
+/// conditions before storing the upload.
+#[derive(Clone)]
+pub struct PostPolicyEmulator {
+  url:    String,
+  secret: Arc<[u8]>,
+}
+
+impl fmt::Debug for PostPolicyEmulator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PostPolicyEmulator")
+      .field("url", &self.url)
+      .finish_non_exhaustive()
+  }
+}
+
+impl PostPolicyEmulator {
+  /// Creates a new [`PostPolicyEmulator`] for uploads posted to `url`,
+  /// signing tokens with `secret`.
+  #[must_use]
+  pub fn new(url: impl Into<String>, secret: impl Into<Vec<u8>>) -> Self {
+    Self {
+      url:    url.into(),
+      secret: secret.into().into(),
+    }
+  }
+
+  fn mac(&self) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(&self.secret)
+      .expect("HMAC accepts keys of any length")
+  }
+
+  /// Issues a form upload to `key`, valid for `expiry`, whose uploads must
+  /// meet every one of `conditions`.
+  #[must_use]
+  pub fn presign(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: Duration,
+  ) -> PresignedPost {
+    let expiry = i64::try_from(expiry.as_millis()).unwrap_or(i64::MAX);
+    let policy = Policy {
+      key:        key.clone(),
+      conditions: conditions.to_vec(),
+      expires_at: Utc::now().timestamp_millis().saturating_add(expiry),
+    };
+    let policy = BASE64.encode(
+      serde_json::to_vec(&policy).expect("policies serialize infallibly"),
+    );
+    let mut mac = self.mac();
+    mac.update(policy.as_bytes());
+    let signature = BASE64.encode(mac.finalize().into_bytes());
+
+    let mut fields = BTreeMap::from([
+      ("key".to_owned(), key.to_string()),
+      (TOKEN_FIELD.to_owned(), format!("{policy}.{signature}")),
+    ]);
+    for condition in conditions {
+      if let PostCondition::ContentType(content_type) = condition {
+        fields.insert("Content-Type".to_owned(), content_type.clone());
+      }
+    }
+    PresignedPost {
+      url: self.url.clone(),
+      fields,
+    }
+  }
+
+  /// Verifies a token, returning the policy it carries.
+  fn verify(&self, token: &str) -> Result<Policy, BlobError> {
+    let denied = || BlobError::PolicyDenied("invalid token".to_owned());
+    let (policy, signature) = token.split_once('.').ok_or_else(denied)?;
+    let signature = BASE64.decode(signature).map_err(|_| denied())?;
+    let mut mac = self.mac();
+    mac.update(policy.as_bytes());
+    mac.verify_slice(&signature).map_err(|_| denied())?;
+
+    let policy = BASE64.decode(policy).map_err(|_| denied())?;
+    serde_json::from_slice(&policy).map_err(|_| denied())
+  }
+
+  /// Stores the upload in a form submitted with the fields of a
+  /// [`PresignedPost`] from [`presign`](Self::presign), returning its key.
+  ///
+  /// As with S3, the blob's contents are the `file` field, which must come
+  /// last, and it's stored with the form's `Content-Type` field as its
+  /// content type, overwriting any blob already at its key. The upload is
+  /// buffered to check its size before it's stored.
+  pub async fn accept(
+    &self,
+    storage: &BlobStorage,
+    mut form: Multipart,
+  ) -> Result<BlobKey, BlobError> {
+    let malformed = |e: axum::extract::multipart::MultipartError| {
+      BlobError::InvalidForm(e.body_text())
+    };
+
+    let mut fields = BTreeMap::new();
+    let data = loop {
+      let Some(field) = form.next_field().await.map_err(malformed)? else {
+        return Err(BlobError::InvalidForm("missing `file` field".to_owned()));
+      };
+      let name = field.name().unwrap_or_default().to_owned();
+      if name == "file" {
+        break field.bytes().await.map_err(malformed)?;
+      }
+      fields.insert(name, field.text().await.map_err(malformed)?);
+    };
+
+    let token = fields.get(TOKEN_FIELD).ok_or_else(|| {
+      BlobError::PolicyDenied(format!("missing `{TOKEN_FIELD}` field"))
+    })?;
+    let policy = self.verify(token)?;
+    if Utc::now().timestamp_millis() >= policy.expires_at {
+      return Err(BlobError::PolicyDenied("policy expired".to_owned()));
+    }
+    if fields.get("key").map(String::as_str) != Some(policy.key.as_str()) {
+      return Err(BlobError::PolicyDenied(
+        "`key` doesn't match the policy".to_owned(),
+      ));
+    }
+    let content_type = fields.remove("Content-Type");
+    let size = data.len() as u64;
+    if let Some(condition) = policy
+      .conditions
+      .iter()
+      .find(|c| !c.allows(content_type.as_deref(), size))
+    {
+      return Err(BlobError::PolicyDenied(format!(
+        "upload doesn't meet the condition {condition:?}"
+      )));
+    }
+
+    storage
+      .put_stream(
+        &policy.key,
+        Box::pin(stream::once(async move { Ok(data) })),
+        UploadOptions {
+          overwrite: true,
+          content_type,
+          ..UploadOptions::default()
+        },
+      )
+      .await?;
+    debug!(key = %policy.key, size, "accepted form upload");
+    Ok(policy.key)
+  }
+}
@@ -0,0 +1,185 @@
+use axum::{
+  body::Body,
+  http::{HeaderMap, HeaderValue, StatusCode, header},
+  response::Response,
+};
+use chrono::DateTime;
+use storage::{BlobKey, BlobMetadata, BlobStorage, BlobStorageError};
+
+use crate::BlobError;
+
+/// Guesses the content type of a blob from the extension of its key, falling
+/// back to `application/octet-stream`.
+#[must_use]
+pub fn content_type_for(key: &BlobKey) -> String {
+  mime_guess::from_path(key.as_str())
+    .first_or_octet_stream()
+    .to_string()
+}
+
+/// Streams the blob at `key` as a response to a request with `headers`.
+///
+/// The response carries `Content-Type`, `Content-Length`, `ETag`, and
+/// `Last-Modified` headers where known. A matching `If-None-Match` yields
+/// `304 Not Modified`, and a single `Range` of bytes yields
+/// `206 Partial Content`. Other ranges are ignored and the whole blob is
+/// served. The content type is the one stored with the blob, or else is
+/// guessed with [`content_type_for`].
+pub async fn blob_response(
+  storage: &BlobStorage,
+  key: &BlobKey,
+  headers: &HeaderMap,
+) -> Result<Response, BlobError> {
+  let metadata = storage
+    .head(key)
+    .await?
+    .ok_or_else(|| BlobStorageError::NotFound(key.clone()))?;
+  let etag = metadata.etag.as_deref().map(quote_etag);
+
+  let mut response = if let Some(etag) = &etag
+    && if_none_match(headers, etag)
+  {
+    Response::builder()
+      .status(StatusCode::NOT_MODIFIED)
+      .body(Body::empty())
+  } else {
+    let range = match parse_range(headers, metadata.size) {
+      Some(Ok(range)) => Some(range),
+      Some(Err(())) => {
+        return Err(BlobError::RangeNotSatisfiable {
+          size: metadata.size,
+        });
+      }
+      None => None,
+    };
+    let stream = match range {
+      Some((start, end)) => {
+        storage.get_stream_range(key, start..end + 1).await?
+      }
+      None => storage.get_stream(key).await?,
+    };
+
+    let builder = Response::builder()
+      .header(
+        header::CONTENT_TYPE,
+        metadata
+          .content_type
+          .clone()
+          .unwrap_or_else(|| content_type_for(key)),
+      )
+      .header(header::ACCEPT_RANGES, "bytes");
+    match range {
+      Some((start, end)) => builder
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_LENGTH, end - start + 1)
+        .header(
+          header::CONTENT_RANGE,
+          format!("bytes {start}-{end}/{}", metadata.size),
+        )
+        .body(Body::from_stream(stream)),
+      None => builder
+        .status(StatusCode::OK)
+        .header(header::CONTENT_LENGTH, metadata.size)
+        .body(Body::from_stream(stream)),
+    }
+  }
+  .expect("blob response headers are valid");
+
+  set_validators(response.headers_mut(), etag.as_deref(), &metadata);
+  Ok(response)
+}
+
+/// Sets the `ETag` and `Last-Modified` headers, where known.
+fn set_validators(
+  headers: &mut HeaderMap,
+  etag: Option<&str>,
+  metadata: &BlobMetadata,
+) {
+  if let Some(etag) = etag.and_then(|e| HeaderValue::from_str(e).ok()) {
+    headers.insert(header::ETAG, etag);
+  }
+  let last_modified = metadata.last_modified.as_deref().and_then(|value| {
+    DateTime::parse_from_rfc3339(value)
+      .or_else(|_| DateTime::parse_from_rfc2822(value))
+      .ok()
+  });
+  if let Some(last_modified) = last_modified {
+    let value = last_modified
+      .to_utc()
+      .format("%a, %d %b %Y %H:%M:%S GMT")
+      .to_string();
+    if let Ok(value) = HeaderValue::from_str(&value) {
+      headers.insert(header::LAST_MODIFIED, value);
+    }
+  }
+}
+
+/// Wraps a backend `ETag` in quotes, as HTTP requires.
+fn quote_etag(etag: &str) -> String {
+  if etag.starts_with('"') || etag.starts_with("W/\"") {
+    etag.to_owned()
+  } else {
+    format!("\"{etag}\"")
+  }
+}
+
+/// Whether the request's `If-None-Match` header matches `etag`.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+  let Some(value) = headers
+    .get(header::IF_NONE_MATCH)
+    .and_then(|v| v.to_str().ok())
+  else {
+    return false;
+  };
+  let strip_weak = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+  value.trim() == "*"
+    || value
+      .split(',')
+      .any(|candidate| strip_weak(candidate) == strip_weak(etag))
+}
+
+/// Parses a single-range `Range` header against a blob of `size` bytes.
+///
+/// Returns `None` if there is no usable range, in which case the whole blob
+/// should be served, and `Some(Err(()))` if the range is unsatisfiable.
+/// Otherwise, returns the inclusive start and end offsets.
+fn parse_range(
+  headers: &HeaderMap,
+  size: u64,
+) -> Option<Result<(u64, u64), ()>> {
+  let spec = headers
+    .get(header::RANGE)?
+    .to_str()
+    .ok()?
+    .trim()
+    .strip_prefix("bytes=")?;
+  if spec.contains(',') {
+    return None;
+  }
+  let (start, end) = spec.split_once('-')?;
+  let (start, end) = (start.trim(), end.trim());
+
+  let range = if start.is_empty() {
+    // a suffix range, e.g. `bytes=-500` for the last 500 bytes
+    let len: u64 = end.parse().ok()?;
+    if len == 0 || size == 0 {
+      return Some(Err(()));
+    }
+    (size.saturating_sub(len), size - 1)
+  } else {
+    let start: u64 = start.parse().ok()?;
+    let end: u64 = if end.is_empty() {
+      u64::MAX
+    } else {
+      end.parse().ok()?
+    };
+    if end < start {
+      return None;
+    }
+    if start >= size {
+      return Some(Err(()));
+    }
+    (start, end.min(size - 1))
+  };
+  Some(Ok(range))
+}
@@ -0,0 +1,245 @@
+use std::time::Duration;
+
+use axum::{
+  body::{Body, to_bytes},
+  extract::{FromRequest, Multipart},
+  http::{HeaderMap, HeaderValue, Request, StatusCode, header},
+  response::{IntoResponse, Response},
+};
+use storage::{
+  BlobKey, BlobStorage, PostCondition, PresignedPost, UploadOptions,
+};
+
+use crate::{BlobBody, BlobError, PostPolicyEmulator, blob_response, put_body};
+
+const DATA: &[u8] = b"0123456789abcdefghij";
+
+async fn storage_with_blob(key: &BlobKey) -> BlobStorage {
+  let storage = BlobStorage::new_memory();
+  let body = BlobBody::from_request(Request::new(Body::from(DATA)), &())
+    .await
+    .unwrap();
+  put_body(&storage, key, body, UploadOptions::default())
+    .await
+    .unwrap();
+  storage
+}
+
+fn headers(pairs: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+  pairs
+    .iter()
+    .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+    .collect()
+}
+
+async fn body_bytes(response: Response) -> Vec<u8> {
+  to_bytes(response.into_body(), usize::MAX)
+    .await
+    .unwrap()
+    .to_vec()
+}
+
+#[tokio::test]
+async fn test_full_response() {
+  let key = BlobKey::new("files/readme.txt");
+  let storage = storage_with_blob(&key).await;
+
+  let response = blob_response(&storage, &key, &HeaderMap::new())
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain");
+  assert_eq!(response.headers()[header::CONTENT_LENGTH], "20");
+  assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+  assert!(response.headers().contains_key(header::ETAG));
+  assert!(response.headers().contains_key(header::LAST_MODIFIED));
+  assert_eq!(body_bytes(response).await, DATA);
+}
+
+#[tokio::test]
+async fn test_range_responses() {
+  let key = BlobKey::new("blob.bin");
+  let storage = storage_with_blob(&key).await;
+
+  let cases: [(&str, &str, &[u8]); 3] = [
+    ("bytes=2-5", "bytes 2-5/20", b"2345"),
+    ("bytes=15-", "bytes 15-19/20", b"fghij"),
+    ("bytes=-3", "bytes 17-19/20", b"hij"),
+  ];
+  for (range, content_range, expected) in cases {
+    let response =
+      blob_response(&storage, &key, &headers(&[(header::RANGE, range)]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()[header::CONTENT_RANGE], content_range);
+    assert_eq!(
+      response.headers()[header::CONTENT_LENGTH],
+      expected.len().to_string().as_str()
+    );
+    assert_eq!(body_bytes(response).await, expected);
+  }
+}
+
+#[tokio::test]
+async fn test_unsatisfiable_range() {
+  let key = BlobKey::new("blob.bin");
+  let storage = storage_with_blob(&key).await;
+
+  let error =
+    blob_response(&storage, &key, &headers(&[(header::RANGE, "bytes=20-")]))
+      .await
+      .unwrap_err();
+  assert!(matches!(error, BlobError::RangeNotSatisfiable { size: 20 }));
+
+  let response = error.into_response();
+  assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+  assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */20");
+}
+
+#[tokio::test]
+async fn test_if_none_match() {
+  let key = BlobKey::new("blob.bin");
+  let storage = storage_with_blob(&key).await;
+
+  let response = blob_response(&storage, &key, &HeaderMap::new())
+    .await
+    .unwrap();
+  let etag = response.headers()[header::ETAG].clone();
+
+  let mut request_headers = HeaderMap::new();
+  request_headers.insert(header::IF_NONE_MATCH, etag.clone());
+  let response = blob_response(&storage, &key, &request_headers)
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+  assert_eq!(response.headers()[header::ETAG], etag);
+  assert!(body_bytes(response).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_missing_blob() {
+  let storage = BlobStorage::new_memory();
+  let error = blob_response(&storage, &"missing".into(), &HeaderMap::new())
+    .await
+    .unwrap_err();
+  assert_eq!(error.into_response().status(), StatusCode::NOT_FOUND);
+}
+
+const BOUNDARY: &str = "form-boundary";
+
+/// Builds the form a browser would submit for `post`, with `content_type`
+/// as its `Content-Type` field.
+async fn form(
+  post: &PresignedPost,
+  content_type: Option<&str>,
+  file: &[u8],
+) -> Multipart {
+  let mut fields = post.fields.clone();
+  if let Some(content_type) = content_type {
+    fields.insert("Content-Type".to_owned(), content_type.to_owned());
+  }
+  let mut body = Vec::new();
+  for (name, value) in &fields {
+    body.extend_from_slice(
+      format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; \
+         name=\"{name}\"\r\n\r\n{value}\r\n"
+      )
+      .as_bytes(),
+    );
+  }
+  body.extend_from_slice(
+    format!(
+      "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; \
+       filename=\"upload\"\r\n\r\n"
+    )
+    .as_bytes(),
+  );
+  body.extend_from_slice(file);
+  body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+  let request = Request::builder()
+    .method("POST")
+    .uri(&post.url)
+    .header(
+      header::CONTENT_TYPE,
+      format!("multipart/form-data; boundary={BOUNDARY}"),
+    )
+    .body(Body::from(body))
+    .unwrap();
+  Multipart::from_request(request, &()).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_emulated_post_upload() {
+  let storage = BlobStorage::new_memory();
+  let emulator = PostPolicyEmulator::new("http://localhost/uploads", "shh");
+  let key = BlobKey::new("avatars/1.png");
+  let post = emulator.presign(
+    &key,
+    &[
+      PostCondition::ContentTypePrefix("image/".to_owned()),
+      PostCondition::ContentLengthRange { min: 1, max: 64 },
+    ],
+    Duration::from_mins(1),
+  );
+  assert_eq!(post.fields["key"], "avatars/1.png");
+
+  let stored = emulator
+    .accept(&storage, form(&post, Some("image/png"), DATA).await)
+    .await
+    .unwrap();
+  assert_eq!(stored, key);
+  let metadata = storage.head(&key).await.unwrap().unwrap();
+  assert_eq!(metadata.size, DATA.len() as u64);
+  assert_eq!(metadata.content_type.as_deref(), Some("image/png"));
+}
+
+#[tokio::test]
+async fn test_emulated_post_rejects_policy_violations() {
+  let storage = BlobStorage::new_memory();
+  let emulator = PostPolicyEmulator::new("http://localhost/uploads", "shh");
+  let key = BlobKey::new("avatars/1.png");
+  let post = emulator.presign(
+    &key,
+    &[PostCondition::ContentLengthRange { min: 1, max: 8 }],
+    Duration::from_mins(1),
+  );
+
+  // too large
+  let error = emulator
+    .accept(&storage, form(&post, None, DATA).await)
+    .await
+    .unwrap_err();
+  assert!(matches!(error, BlobError::PolicyDenied(_)));
+  assert_eq!(error.into_response().status(), StatusCode::FORBIDDEN);
+
+  // a different key than was signed
+  let mut forged = post.clone();
+  forged.fields.insert("key".to_owned(), "other".to_owned());
+  let error = emulator
+    .accept(&storage, form(&forged, None, b"hi").await)
+    .await
+    .unwrap_err();
+  assert!(matches!(error, BlobError::PolicyDenied(_)));
+
+  // signed with another secret
+  let other = PostPolicyEmulator::new("http://localhost/uploads", "other")
+    .presign(&key, &[], Duration::from_mins(1));
+  let error = emulator
+    .accept(&storage, form(&other, None, b"hi").await)
+    .await
+    .unwrap_err();
+  assert!(matches!(error, BlobError::PolicyDenied(_)));
+
+  // expired
+  let expired = emulator.presign(&key, &[], Duration::ZERO);
+  let error = emulator
+    .accept(&storage, form(&expired, None, b"hi").await)
+    .await
+    .unwrap_err();
+  assert!(matches!(error, BlobError::PolicyDenied(_)));
+
+  assert!(!storage.exists(&key).await.unwrap());
+}
@@ -0,0 +1,52 @@
+use std::{fmt, io};
+
+use axum::{
+  body::Body,
+  extract::{FromRequest, Request},
+};
+use futures::TryStreamExt;
+use storage::{BlobKey, BlobStorage, RequestStream, UploadOptions};
+
+use crate::BlobError;
+
+/// An extractor for a request body as a stream of bytes.
+///
+/// The body is not buffered; pass [`into_stream`](Self::into_stream) to
+/// [`put_stream`](BlobStorage::put_stream) to store it as it arrives.
+pub struct BlobBody(Body);
+
+impl fmt::Debug for BlobBody {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("BlobBody").finish_non_exhaustive()
+  }
+}
+
+impl BlobBody {
+  /// Converts the body into a stream suitable for uploading.
+  #[must_use]
+  pub fn into_stream(self) -> RequestStream {
+    Box::pin(self.0.into_data_stream().map_err(io::Error::other))
+  }
+}
+
+impl<S: Send + Sync> FromRequest<S> for BlobBody {
+  type Rejection = std::convert::Infallible;
+
+  async fn from_request(
+    req: Request,
+    _state: &S,
+  ) -> Result<Self, Self::Rejection> {
+    Ok(BlobBody(req.into_body()))
+  }
+}
+
+/// Stores a request body at `key` as it arrives, without buffering.
+pub async fn put_body(
+  storage: &BlobStorage,
+  key: &BlobKey,
+  body: BlobBody,
+  options: UploadOptions,
+) -> Result<(), BlobError> {
+  storage.put_stream(key, body.into_stream(), options).await?;
+  Ok(())
+}
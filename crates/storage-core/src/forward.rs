@@ -0,0 +1,161 @@
+//! Forwarding implementations of [`BlobStorageLike`] for pointer types, so
+//! decorator stacks can be shared and composed without newtypes.
+
+use std::{ops::Range, path::Path, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+  BlobKey, BlobListPage, BlobMetadata, BlobStorageLike, BlobStorageResult,
+  Bytes, PostCondition, PresignedPost, RequestStream, ResponseStream,
+  StorageStats, UploadHandle, UploadOptions, UploadedPart,
+};
+
+/// Implements [`BlobStorageLike`] for each pointer type by forwarding every
+/// method, including those with default implementations, to the pointee.
+macro_rules! forward_blob_storage_like {
+  ($($ptr:ty),* $(,)?) => {$(
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl<T: BlobStorageLike + ?Sized> BlobStorageLike for $ptr {
+      async fn put_stream(
+        &self,
+        key: &BlobKey,
+        data: RequestStream,
+        options: UploadOptions,
+      ) -> BlobStorageResult<()> {
+        (**self).put_stream(key, data, options).await
+      }
+
+      async fn put_file(
+        &self,
+        key: &BlobKey,
+        path: &Path,
+        data: RequestStream,
+        options: UploadOptions,
+      ) -> BlobStorageResult<()> {
+        (**self).put_file(key, path, data, options).await
+      }
+
+      async fn get_stream(
+        &self,
+        key: &BlobKey,
+      ) -> BlobStorageResult<ResponseStream> {
+        (**self).get_stream(key).await
+      }
+
+      async fn get_stream_range(
+        &self,
+        key: &BlobKey,
+        range: Range<u64>,
+      ) -> BlobStorageResult<ResponseStream> {
+        (**self).get_stream_range(key, range).await
+      }
+
+      async fn head(
+        &self,
+        key: &BlobKey,
+      ) -> BlobStorageResult<Option<BlobMetadata>> {
+        (**self).head(key).await
+      }
+
+      async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+        (**self).delete(key).await
+      }
+
+      async fn delete_many(
+        &self,
+        keys: &[BlobKey],
+      ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+        (**self).delete_many(keys).await
+      }
+
+      async fn copy(
+        &self,
+        from: &BlobKey,
+        to: &BlobKey,
+      ) -> BlobStorageResult<()> {
+        (**self).copy(from, to).await
+      }
+
+      async fn list_page(
+        &self,
+        prefix: &str,
+        continuation: Option<String>,
+      ) -> BlobStorageResult<BlobListPage> {
+        (**self).list_page(prefix, continuation).await
+      }
+
+      async fn stats(&self, prefix: &str) -> BlobStorageResult<StorageStats> {
+        (**self).stats(prefix).await
+      }
+
+      async fn get_presigned_url(
+        &self,
+        key: &BlobKey,
+        expiry: Duration,
+      ) -> BlobStorageResult<String> {
+        (**self).get_presigned_url(key, expiry).await
+      }
+
+      async fn get_presigned_post(
+        &self,
+        key: &BlobKey,
+        conditions: &[PostCondition],
+        expiry: Duration,
+      ) -> BlobStorageResult<PresignedPost> {
+        (**self).get_presigned_post(key, conditions, expiry).await
+      }
+
+      async fn create_upload(
+        &self,
+        key: &BlobKey,
+        options: UploadOptions,
+      ) -> BlobStorageResult<UploadHandle> {
+        (**self).create_upload(key, options).await
+      }
+
+      async fn upload_part(
+        &self,
+        handle: &UploadHandle,
+        part_number: u32,
+        data: Bytes,
+      ) -> BlobStorageResult<UploadedPart> {
+        (**self).upload_part(handle, part_number, data).await
+      }
+
+      async fn complete_upload(
+        &self,
+        handle: &UploadHandle,
+        parts: &[UploadedPart],
+      ) -> BlobStorageResult<()> {
+        (**self).complete_upload(handle, parts).await
+      }
+
+      async fn abort_upload(
+        &self,
+        handle: &UploadHandle,
+      ) -> BlobStorageResult<()> {
+        (**self).abort_upload(handle).await
+      }
+
+      async fn set_retention(
+        &self,
+        key: &BlobKey,
+        until: DateTime<Utc>,
+      ) -> BlobStorageResult<()> {
+        (**self).set_retention(key, until).await
+      }
+
+      async fn set_legal_hold(
+        &self,
+        key: &BlobKey,
+        hold: bool,
+      ) -> BlobStorageResult<()> {
+        (**self).set_legal_hold(key, hold).await
+      }
+    }
+  )*};
+}
+
+forward_blob_storage_like!(Arc<T>, Box<T>, &T);
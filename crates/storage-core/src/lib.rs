@@ -1,20 +1,43 @@
 //! Trait for a cloud storage interface.
 
-use std::{io, pin::Pin};
+mod forward;
+
+use std::{
+  collections::BTreeMap, future::ready, io, ops::Range, path::Path, pin::Pin,
+  str::FromStr,
+};
 
 use async_trait::async_trait;
 pub use bytes::Bytes;
+use chrono::{DateTime, FixedOffset, Utc};
 pub use futures::stream::Stream;
+use futures::{StreamExt, TryStreamExt};
+pub use maybe_send::MaybeSendSync;
 use miette::Diagnostic;
-use serde::{Deserialize, Serialize};
-pub use storage_types::BlobKey;
+use serde::{
+  Deserialize, Serialize, Serializer, de::IntoDeserializer,
+  ser::SerializeStruct,
+};
+use sha2::{Digest, Sha256};
+pub use storage_types::{
+  BlobKey, KeyParts, KeyTemplate, KeyTemplateError, key_template,
+};
 
 /// Type alias for streaming request data
+#[cfg(not(target_arch = "wasm32"))]
 pub type RequestStream =
   Pin<Box<dyn Stream<Item = Result<Bytes, io::Error>> + Send>>;
 /// Type alias for streaming response data
+#[cfg(not(target_arch = "wasm32"))]
 pub type ResponseStream =
   Pin<Box<dyn Stream<Item = Result<Bytes, BlobStorageError>> + Send>>;
+/// Type alias for streaming request data
+#[cfg(target_arch = "wasm32")]
+pub type RequestStream = Pin<Box<dyn Stream<Item = Result<Bytes, io::Error>>>>;
+/// Type alias for streaming response data
+#[cfg(target_arch = "wasm32")]
+pub type ResponseStream =
+  Pin<Box<dyn Stream<Item = Result<Bytes, BlobStorageError>>>>;
 
 /// Metadata associated with a blob object
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +48,13 @@ pub struct BlobMetadata {
   pub etag:          Option<String>,
   /// Last modified timestamp
   pub last_modified: Option<String>,
+  /// The blob's media type, e.g. `image/png`, if it was given or detected
+  /// on upload
+  #[serde(default)]
+  pub content_type:  Option<String>,
+  /// The canned ACL the blob was uploaded with, if the backend records it
+  #[serde(default)]
+  pub acl:           Option<CannedAcl>,
 }
 
 /// Options for uploading blobs
@@ -32,9 +62,336 @@ pub struct BlobMetadata {
 pub struct UploadOptions {
   /// Whether to overwrite existing blob (if false, return error if blob
   /// exists)
+  pub overwrite:     bool,
+  /// A checksum of the blob's contents for the backend to verify the payload
+  /// against, rejecting the upload on a mismatch. Backends which can't verify
+  /// checksums ignore it.
+  pub checksum:      Option<Checksum>,
+  /// The storage class to store the blob in. Backends without storage
+  /// classes ignore it.
+  pub storage_class: Option<StorageClass>,
+  /// How the backend should encrypt the blob at rest. Backends without
+  /// server-side encryption ignore it.
+  pub sse:           Option<ServerSideEncryption>,
+  /// The blob's media type, e.g. `image/png`, stored with it and served
+  /// with downloads, including through pre-signed URLs.
+  pub content_type:  Option<String>,
+  /// Who may read the blob, as a canned ACL. Backends without ACLs record
+  /// it in the blob's metadata, if at all, without enforcing it.
+  pub acl:           Option<CannedAcl>,
+}
+
+/// A canned access control list, granting a predefined set of permissions
+/// on a blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CannedAcl {
+  /// Only the owner can access the blob.
+  Private,
+  /// Anyone can read the blob.
+  PublicRead,
+  /// Anyone can read and write the blob.
+  PublicReadWrite,
+  /// Any authenticated user can read the blob.
+  AuthenticatedRead,
+  /// The bucket owner can read the blob.
+  BucketOwnerRead,
+  /// The bucket owner has full control of the blob.
+  BucketOwnerFullControl,
+}
+
+impl CannedAcl {
+  /// The canned ACL's name, as used by S3.
+  #[must_use]
+  pub const fn as_str(self) -> &'static str {
+    match self {
+      Self::Private => "private",
+      Self::PublicRead => "public-read",
+      Self::PublicReadWrite => "public-read-write",
+      Self::AuthenticatedRead => "authenticated-read",
+      Self::BucketOwnerRead => "bucket-owner-read",
+      Self::BucketOwnerFullControl => "bucket-owner-full-control",
+    }
+  }
+
+  /// Whether the ACL lets anyone read the blob.
+  #[must_use]
+  pub const fn is_public(self) -> bool {
+    matches!(self, Self::PublicRead | Self::PublicReadWrite)
+  }
+}
+
+impl FromStr for CannedAcl {
+  type Err = ();
+
+  /// Parses a canned ACL from its [name](Self::as_str).
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::deserialize(s.into_deserializer())
+      .map_err(|_: serde::de::value::Error| ())
+  }
+}
+
+/// A storage class, trading retrieval cost and latency for storage cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StorageClass {
+  /// Frequently accessed data.
+  Standard,
+  /// Infrequently accessed data, stored redundantly across zones.
+  StandardIa,
+  /// Infrequently accessed data, stored in a single zone.
+  OnezoneIa,
+  /// Data with unknown or changing access patterns.
+  IntelligentTiering,
+  /// Archived data, retrievable in minutes to hours.
+  Glacier,
+  /// Archived data, retrievable in milliseconds.
+  GlacierIr,
+  /// Long-term archived data, retrievable within hours.
+  DeepArchive,
+}
+
+impl StorageClass {
+  /// The storage class's name, as used by S3.
+  #[must_use]
+  pub const fn as_str(self) -> &'static str {
+    match self {
+      Self::Standard => "STANDARD",
+      Self::StandardIa => "STANDARD_IA",
+      Self::OnezoneIa => "ONEZONE_IA",
+      Self::IntelligentTiering => "INTELLIGENT_TIERING",
+      Self::Glacier => "GLACIER",
+      Self::GlacierIr => "GLACIER_IR",
+      Self::DeepArchive => "DEEP_ARCHIVE",
+    }
+  }
+}
+
+/// A server-side encryption mode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServerSideEncryption {
+  /// Encryption with keys managed by the storage service (SSE-S3).
+  S3,
+  /// Encryption with a KMS key (SSE-KMS), using the service's default key if
+  /// no key ID is given.
+  Kms {
+    /// The ID or ARN of the KMS key.
+    key_id: Option<String>,
+  },
+}
+
+/// An integrity checksum of a blob's contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+  /// An MD5 digest, as sent in `Content-MD5`.
+  Md5([u8; 16]),
+  /// A SHA-256 digest, as sent in `x-amz-checksum-sha256`.
+  Sha256([u8; 32]),
+}
+
+impl Checksum {
+  /// Computes the MD5 checksum of `data`.
+  #[must_use]
+  pub fn md5(data: &[u8]) -> Self { Self::Md5(md5::compute(data).0) }
+
+  /// Computes the SHA-256 checksum of `data`.
+  #[must_use]
+  pub fn sha256(data: &[u8]) -> Self {
+    Self::Sha256(Sha256::digest(data).into())
+  }
+
+  /// The raw digest bytes.
+  #[must_use]
+  pub const fn digest(&self) -> &[u8] {
+    match self {
+      Self::Md5(digest) => digest,
+      Self::Sha256(digest) => digest,
+    }
+  }
+}
+
+/// The object lock state of a blob, for backends which emulate object lock.
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub struct ObjectLock {
+  /// The time until which the blob is retained.
+  pub retain_until: Option<DateTime<Utc>>,
+  /// Whether the blob is under a legal hold.
+  pub legal_hold:   bool,
+}
+
+impl ObjectLock {
+  /// Whether the blob can't be deleted or overwritten at `now`.
+  #[must_use]
+  pub fn is_locked(&self, now: DateTime<Utc>) -> bool {
+    self.legal_hold || self.retain_until.is_some_and(|until| until > now)
+  }
+
+  /// Retains the blob until `until`, failing with
+  /// [`BlobStorageError::Locked`] if that would shorten its retention.
+  pub fn retain(
+    &mut self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    if self.retain_until.is_some_and(|current| until < current) {
+      return Err(BlobStorageError::Locked(key.clone()));
+    }
+    self.retain_until = Some(until);
+    Ok(())
+  }
+}
+
+/// Identifies an in-progress resumable upload.
+///
+/// Persist this alongside the uploaded parts to resume the upload after a
+/// restart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadHandle {
+  /// The key the blob will be stored at once the upload completes.
+  pub key:       BlobKey,
+  /// The backend-specific upload identifier.
+  pub upload_id: String,
+  /// Whether to overwrite an existing blob when the upload completes.
   pub overwrite: bool,
 }
 
+/// A part uploaded as part of a resumable upload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadedPart {
+  /// The 1-based position of the part within the blob.
+  pub part_number: u32,
+  /// Size of the part in bytes.
+  pub size:        u64,
+  /// `ETag` of the part, used to verify it when completing the upload.
+  pub etag:        String,
+}
+
+/// A condition a browser upload made with a [`PresignedPost`] must meet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostCondition {
+  /// The upload must be between `min` and `max` bytes long, inclusive.
+  ContentLengthRange {
+    /// The smallest allowed size in bytes.
+    min: u64,
+    /// The largest allowed size in bytes.
+    max: u64,
+  },
+  /// The upload's `Content-Type` field must be exactly this.
+  ContentType(String),
+  /// The upload's `Content-Type` field must start with this, e.g. `image/`.
+  ContentTypePrefix(String),
+}
+
+impl PostCondition {
+  /// Whether an upload of `size` bytes with the `Content-Type` field
+  /// `content_type` meets the condition.
+  #[must_use]
+  pub fn allows(&self, content_type: Option<&str>, size: u64) -> bool {
+    match self {
+      PostCondition::ContentLengthRange { min, max } => {
+        (*min..=*max).contains(&size)
+      }
+      PostCondition::ContentType(expected) => {
+        content_type == Some(expected.as_str())
+      }
+      PostCondition::ContentTypePrefix(prefix) => {
+        content_type.is_some_and(|ty| ty.starts_with(prefix.as_str()))
+      }
+    }
+  }
+}
+
+/// A pre-signed form upload, for uploading a blob straight from a browser.
+///
+/// Submit a `multipart/form-data` POST to `url` with each of `fields`, any
+/// fields the conditions call for, like `Content-Type`, and the blob's
+/// contents as a final `file` field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresignedPost {
+  /// The URL to POST the form to.
+  pub url:    String,
+  /// The form fields to submit, carrying the key and the signed policy.
+  pub fields: BTreeMap<String, String>,
+}
+
+/// A blob returned by [`BlobStorageLike::list_page`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobEntry {
+  /// The blob's key.
+  pub key:      BlobKey,
+  /// The blob's metadata.
+  pub metadata: BlobMetadata,
+}
+
+/// A page of blobs returned by [`BlobStorageLike::list_page`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlobListPage {
+  /// The blobs on this page, ordered by key.
+  pub entries:      Vec<BlobEntry>,
+  /// The token to pass to fetch the next page, or `None` on the last page.
+  pub continuation: Option<String>,
+}
+
+/// Usage statistics for the blobs under a prefix.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageStats {
+  /// The number of blobs.
+  pub object_count: u64,
+  /// The total size of the blobs in bytes.
+  pub total_bytes:  u64,
+  /// The largest blob.
+  pub largest:      Option<BlobEntry>,
+  /// The least recently modified blob, among those with a parseable
+  /// last-modified timestamp.
+  pub oldest:       Option<BlobEntry>,
+}
+
+impl StorageStats {
+  /// Adds `entry` to the statistics.
+  pub fn record(&mut self, entry: &BlobEntry) {
+    self.object_count += 1;
+    self.total_bytes += entry.metadata.size;
+
+    if self
+      .largest
+      .as_ref()
+      .is_none_or(|largest| entry.metadata.size > largest.metadata.size)
+    {
+      self.largest = Some(entry.clone());
+    }
+
+    if let Some(modified) = last_modified(entry)
+      && self
+        .oldest
+        .as_ref()
+        .and_then(last_modified)
+        .is_none_or(|oldest| modified < oldest)
+    {
+      self.oldest = Some(entry.clone());
+    }
+  }
+}
+
+fn last_modified(entry: &BlobEntry) -> Option<DateTime<FixedOffset>> {
+  DateTime::parse_from_rfc3339(entry.metadata.last_modified.as_deref()?).ok()
+}
+
+/// Details of a failed HTTP request to a storage service, for debugging.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpErrorDetails {
+  /// The HTTP status code of the response.
+  pub status:              Option<u16>,
+  /// The service's error code, e.g. `AccessDenied`.
+  pub code:                Option<String>,
+  /// The service's ID for the request.
+  pub request_id:          Option<String>,
+  /// The service's extended ID for the request, e.g. S3's `x-amz-id-2`.
+  pub extended_request_id: Option<String>,
+}
+
 /// Error types for blob storage operations
 #[derive(Debug, thiserror::Error, Diagnostic)]
 pub enum BlobStorageError {
@@ -47,8 +404,13 @@ pub enum BlobStorageError {
   AlreadyExists(BlobKey),
 
   /// Permission denied.
-  #[error("Permission denied: {0}")]
-  PermissionDenied(miette::Report),
+  #[error("Permission denied: {report}")]
+  PermissionDenied {
+    /// The underlying error.
+    report:  miette::Report,
+    /// Details of the failed request, if the backend reported them.
+    details: Option<HttpErrorDetails>,
+  },
 
   /// Invalid config.
   #[error("Invalid configuration: {0}")]
@@ -59,8 +421,13 @@ pub enum BlobStorageError {
   InvalidInput(miette::Report),
 
   /// Network error.
-  #[error("Network error: {0}")]
-  NetworkError(miette::Report),
+  #[error("Network error: {report}")]
+  NetworkError {
+    /// The underlying error.
+    report:  miette::Report,
+    /// Details of the failed request, if the backend reported them.
+    details: Option<HttpErrorDetails>,
+  },
 
   /// IO error.
   #[error("IO error: {0}")]
@@ -70,6 +437,19 @@ pub enum BlobStorageError {
   #[error("Serialization error: {0}")]
   SerializationError(miette::Report),
 
+  /// Upload rejected by a content-scanning policy.
+  #[error("Upload rejected: {0}")]
+  Rejected(String),
+
+  /// Blob is under retention or a legal hold, so can't be deleted or
+  /// overwritten.
+  #[error("Blob is locked: {0}")]
+  Locked(BlobKey),
+
+  /// Operation not supported by this backend.
+  #[error("Unsupported operation: {0}")]
+  Unsupported(String),
+
   /// Stream error.
   #[error("Stream error: {0}")]
   StreamError(miette::Report),
@@ -92,7 +472,7 @@ impl BlobStorageError {
         io::ErrorKind::AlreadyExists,
         format!("Blob already exists: {key}"),
       ),
-      BlobStorageError::PermissionDenied(report) => {
+      BlobStorageError::PermissionDenied { report, .. } => {
         io::Error::new(io::ErrorKind::PermissionDenied, report.to_string())
       }
       BlobStorageError::InvalidConfig(report) => io::Error::new(
@@ -102,13 +482,25 @@ impl BlobStorageError {
       BlobStorageError::InvalidInput(report) => {
         io::Error::new(io::ErrorKind::InvalidInput, report.to_string())
       }
-      BlobStorageError::NetworkError(report) => {
+      BlobStorageError::NetworkError { report, .. } => {
         io::Error::new(io::ErrorKind::ConnectionAborted, report.to_string())
       }
       BlobStorageError::IoError(e) => e,
       BlobStorageError::SerializationError(report) => {
         io::Error::new(io::ErrorKind::InvalidData, report.to_string())
       }
+      BlobStorageError::Rejected(reason) => io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Upload rejected: {reason}"),
+      ),
+      BlobStorageError::Locked(key) => io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("Blob is locked: {key}"),
+      ),
+      BlobStorageError::Unsupported(operation) => io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Unsupported operation: {operation}"),
+      ),
       BlobStorageError::StreamError(report) => {
         io::Error::new(io::ErrorKind::BrokenPipe, report.to_string())
       }
@@ -117,12 +509,91 @@ impl BlobStorageError {
   }
 }
 
+impl BlobStorageError {
+  /// A stable, machine-readable code identifying the kind of error.
+  #[must_use]
+  pub const fn error_code(&self) -> &'static str {
+    match self {
+      BlobStorageError::NotFound(_) => "not_found",
+      BlobStorageError::AlreadyExists(_) => "already_exists",
+      BlobStorageError::PermissionDenied { .. } => "permission_denied",
+      BlobStorageError::InvalidConfig(_) => "invalid_config",
+      BlobStorageError::InvalidInput(_) => "invalid_input",
+      BlobStorageError::NetworkError { .. } => "network",
+      BlobStorageError::IoError(_) => "io",
+      BlobStorageError::SerializationError(_) => "serialization",
+      BlobStorageError::Rejected(_) => "rejected",
+      BlobStorageError::Locked(_) => "locked",
+      BlobStorageError::Unsupported(_) => "unsupported",
+      BlobStorageError::StreamError(_) => "stream",
+      BlobStorageError::Unknown(_) => "unknown",
+    }
+  }
+
+  /// Creates a [`BlobStorageError::PermissionDenied`] without request
+  /// details.
+  #[must_use]
+  pub const fn permission_denied(report: miette::Report) -> Self {
+    BlobStorageError::PermissionDenied {
+      report,
+      details: None,
+    }
+  }
+
+  /// Creates a [`BlobStorageError::NetworkError`] without request details.
+  #[must_use]
+  pub const fn network(report: miette::Report) -> Self {
+    BlobStorageError::NetworkError {
+      report,
+      details: None,
+    }
+  }
+
+  /// Details of the failed request, if the backend reported them.
+  #[must_use]
+  pub const fn http_details(&self) -> Option<&HttpErrorDetails> {
+    match self {
+      BlobStorageError::PermissionDenied { details, .. }
+      | BlobStorageError::NetworkError { details, .. } => details.as_ref(),
+      _ => None,
+    }
+  }
+}
+
+/// Serializes as `{ "code": "...", "message": "..." }`, where `code` is
+/// [`BlobStorageError::error_code`] and `message` is the display form of the
+/// error. Errors carrying [`HttpErrorDetails`] include them as `"http"`.
+impl Serialize for BlobStorageError {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let details = self.http_details();
+    let len = if details.is_some() { 3 } else { 2 };
+    let mut state = serializer.serialize_struct("BlobStorageError", len)?;
+    state.serialize_field("code", self.error_code())?;
+    state.serialize_field("message", &self.to_string())?;
+    if let Some(details) = details {
+      state.serialize_field("http", details)?;
+    } else {
+      state.skip_field("http")?;
+    }
+    state.end()
+  }
+}
+
 /// A type alias for [`Result`] with [`BlobStorageError`].
 pub type BlobStorageResult<T> = std::result::Result<T, BlobStorageError>;
 
+/// The number of concurrent deletes issued by the default
+/// [`BlobStorageLike::delete_many`].
+pub const DELETE_MANY_CONCURRENCY: usize = 16;
+
+/// The maximum number of blobs returned by a single
+/// [`BlobStorageLike::list_page`] call.
+pub const LIST_PAGE_SIZE: usize = 1000;
+
 /// Main trait for blob storage operations
-#[async_trait]
-pub trait BlobStorageLike: Send + Sync {
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait BlobStorageLike: MaybeSendSync {
   /// Upload data from a stream to a blob
   async fn put_stream(
     &self,
@@ -131,12 +602,45 @@ pub trait BlobStorageLike: Send + Sync {
     options: UploadOptions,
   ) -> BlobStorageResult<()>;
 
+  /// Upload the local file at `path` to a blob, given a stream of its
+  /// contents in `data`.
+  ///
+  /// Backends which can copy files directly, like the filesystem, may do so
+  /// rather than reading `data`. The default implementation uploads `data`
+  /// with [`put_stream`](Self::put_stream).
+  async fn put_file(
+    &self,
+    key: &BlobKey,
+    path: &Path,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let _ = path;
+    self.put_stream(key, data, options).await
+  }
+
   /// Download data from a blob as a stream
   async fn get_stream(
     &self,
     key: &BlobKey,
   ) -> BlobStorageResult<ResponseStream>;
 
+  /// Download the bytes in `range` of a blob as a stream.
+  ///
+  /// A range reaching past the end of the blob is cut short at its end,
+  /// though backends may reject one starting past it. The default
+  /// implementation downloads the blob with [`get_stream`](Self::get_stream)
+  /// and skips the bytes before the range, so backends which can read ranges
+  /// directly should override it.
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    let data = self.get_stream(key).await?;
+    Ok(slice_stream(data, range))
+  }
+
   /// Get metadata for a blob without downloading content
   async fn head(
     &self,
@@ -146,10 +650,194 @@ pub trait BlobStorageLike: Send + Sync {
   /// Delete a blob
   async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()>;
 
+  /// Delete many blobs, returning the result for each key in order.
+  ///
+  /// The default implementation deletes up to
+  /// [`DELETE_MANY_CONCURRENCY`] blobs at a time.
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    futures::stream::iter(keys.iter().cloned())
+      .map(|key| async move {
+        let result = self.delete(&key).await;
+        (key, result)
+      })
+      .buffered(DELETE_MANY_CONCURRENCY)
+      .collect()
+      .await
+  }
+
+  /// Copy the blob at `from` to `to`, overwriting any blob there, keeping
+  /// its content type and ACL.
+  ///
+  /// The default implementation downloads the blob and uploads it again, so
+  /// the copy is only as atomic as [`put_stream`](Self::put_stream).
+  async fn copy(&self, from: &BlobKey, to: &BlobKey) -> BlobStorageResult<()> {
+    let metadata = self
+      .head(from)
+      .await?
+      .ok_or_else(|| BlobStorageError::NotFound(from.clone()))?;
+    let data = self.get_stream(from).await?;
+    let data: RequestStream = Box::pin(
+      data.map(|chunk| chunk.map_err(BlobStorageError::into_io_error)),
+    );
+    self
+      .put_stream(to, data, UploadOptions {
+        overwrite: true,
+        content_type: metadata.content_type,
+        acl: metadata.acl,
+        ..UploadOptions::default()
+      })
+      .await
+  }
+
+  /// List a page of up to [`LIST_PAGE_SIZE`] blobs whose keys start with
+  /// `prefix`, ordered by key.
+  ///
+  /// Pass `None` to fetch the first page, and the previous page's
+  /// continuation to fetch the next one.
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    let _ = (prefix, continuation);
+    Err(BlobStorageError::Unsupported("listing".to_owned()))
+  }
+
+  /// Compute usage statistics for the blobs whose keys start with `prefix`,
+  /// by listing every page.
+  async fn stats(&self, prefix: &str) -> BlobStorageResult<StorageStats> {
+    let mut stats = StorageStats::default();
+    let mut continuation = None;
+    loop {
+      let page = self.list_page(prefix, continuation).await?;
+      for entry in &page.entries {
+        stats.record(entry);
+      }
+      continuation = page.continuation;
+      if continuation.is_none() {
+        return Ok(stats);
+      }
+    }
+  }
+
   /// Get a pre-signed URL for temporary access (if supported)
   async fn get_presigned_url(
     &self,
     key: &BlobKey,
     expiry: std::time::Duration,
   ) -> BlobStorageResult<String>;
+
+  /// Get a pre-signed form upload to a blob, valid for `expiry`, whose
+  /// uploads must meet every one of `conditions` (if supported).
+  async fn get_presigned_post(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: std::time::Duration,
+  ) -> BlobStorageResult<PresignedPost> {
+    let _ = (key, conditions, expiry);
+    Err(BlobStorageError::Unsupported("presigned POST".to_owned()))
+  }
+
+  /// Start a resumable upload to a blob.
+  async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadHandle> {
+    let _ = (key, options);
+    Err(BlobStorageError::Unsupported(
+      "resumable uploads".to_owned(),
+    ))
+  }
+
+  /// Upload a single part of a resumable upload. Uploading a part number
+  /// again replaces the earlier part.
+  async fn upload_part(
+    &self,
+    handle: &UploadHandle,
+    part_number: u32,
+    data: Bytes,
+  ) -> BlobStorageResult<UploadedPart> {
+    let _ = (handle, part_number, data);
+    Err(BlobStorageError::Unsupported(
+      "resumable uploads".to_owned(),
+    ))
+  }
+
+  /// Complete a resumable upload, assembling `parts` in order into the
+  /// blob.
+  async fn complete_upload(
+    &self,
+    handle: &UploadHandle,
+    parts: &[UploadedPart],
+  ) -> BlobStorageResult<()> {
+    let _ = (handle, parts);
+    Err(BlobStorageError::Unsupported(
+      "resumable uploads".to_owned(),
+    ))
+  }
+
+  /// Abort a resumable upload, discarding any uploaded parts.
+  async fn abort_upload(&self, handle: &UploadHandle) -> BlobStorageResult<()> {
+    let _ = handle;
+    Err(BlobStorageError::Unsupported(
+      "resumable uploads".to_owned(),
+    ))
+  }
+
+  /// Retain a blob until `until`, rejecting deletes and overwrites of it
+  /// with [`BlobStorageError::Locked`] until then. Retention can be extended
+  /// but not shortened.
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    let _ = (key, until);
+    Err(BlobStorageError::Unsupported("object lock".to_owned()))
+  }
+
+  /// Place or lift a legal hold on a blob. A held blob can't be deleted or
+  /// overwritten, regardless of its retention.
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    let _ = (key, hold);
+    Err(BlobStorageError::Unsupported("object lock".to_owned()))
+  }
+}
+
+/// Skips the bytes of `stream` before `range` and yields those within it.
+fn slice_stream(stream: ResponseStream, range: Range<u64>) -> ResponseStream {
+  let len = range.end.saturating_sub(range.start);
+  Box::pin(
+    stream
+      .scan((range.start, len), |(skip, remaining), chunk| {
+        if *remaining == 0 {
+          return ready(None);
+        }
+        let chunk = chunk.map(|mut bytes| {
+          let skipped = usize::try_from(*skip)
+            .unwrap_or(usize::MAX)
+            .min(bytes.len());
+          bytes = bytes.slice(skipped..);
+          *skip -= skipped as u64;
+
+          let taken = usize::try_from(*remaining)
+            .unwrap_or(usize::MAX)
+            .min(bytes.len());
+          bytes.truncate(taken);
+          *remaining -= taken as u64;
+          bytes
+        });
+        ready(Some(chunk))
+      })
+      .try_filter(|bytes| ready(!bytes.is_empty())),
+  )
 }
@@ -2,27 +2,69 @@
 
 use std::{
   fmt,
+  io::SeekFrom,
+  ops::Range,
   path::{Path, PathBuf},
   time::{SystemTime, UNIX_EPOCH},
 };
 
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use storage_core::{
-  BlobKey, BlobMetadata, BlobStorageError, BlobStorageLike, BlobStorageResult,
-  RequestStream, ResponseStream, UploadOptions,
+  BlobEntry, BlobKey, BlobListPage, BlobMetadata, BlobStorageError,
+  BlobStorageLike, BlobStorageResult, CannedAcl, LIST_PAGE_SIZE, ObjectLock,
+  RequestStream, ResponseStream, UploadHandle, UploadOptions, UploadedPart,
+};
+use tokio::{
+  fs,
+  io::{AsyncReadExt, AsyncSeekExt},
 };
-use tokio::fs;
 use tracing::{debug, error, info, instrument, warn};
 
+/// What [`BlobStorageFilesystem::reconcile`] found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reconciled {
+  /// The blob's metadata was already up to date, or neither it nor its
+  /// metadata exist.
+  Unchanged,
+  /// The blob had no metadata, which was generated.
+  Added,
+  /// The blob's metadata was out of date, and was regenerated.
+  Modified,
+  /// The blob was removed, so its metadata was too.
+  Removed,
+}
+
+/// Directories in the root holding state other than blobs, whose keys are
+/// reserved
+const RESERVED_DIRS: [&str; 3] = [".uploads", ".locks", ".objects"];
+
 /// Filesystem-based implementation of [`BlobStorageLike`].
 ///
 /// This implementation stores all blobs as files in a directory structure.
-/// Each blob is stored with its metadata in a sidecar file.
+/// Each blob is stored with its metadata in a sidecar file, and its object
+/// lock state under `.locks` in the root directory. Parts of
+/// resumable uploads are stored under `.uploads` in the root directory until
+/// the upload completes.
+///
+/// With [`with_dedupe`](Self::with_dedupe), identical contents are stored
+/// once under `.objects` and hard-linked to each blob's path.
+///
+/// Keys ending in `.meta` or under these directories are reserved, and
+/// rejected with [`BlobStorageError::InvalidInput`].
+///
+/// Blobs whose sidecar is missing, e.g. files placed in the root directory
+/// by other tools, have no `ETag` unless weak `ETag`s are enabled with
+/// [`with_weak_etags`](Self::with_weak_etags).
 #[derive(Debug, Clone)]
 pub struct BlobStorageFilesystem {
   /// Root directory for blob storage
-  root_path: PathBuf,
+  root_path:  PathBuf,
+  /// Whether to derive weak `ETag`s for blobs without sidecars
+  weak_etags: bool,
+  /// Whether to hard-link blobs with identical contents
+  dedupe:     bool,
 }
 
 impl BlobStorageFilesystem {
@@ -47,7 +89,37 @@ impl BlobStorageFilesystem {
       BlobStorageError::IoError(e)
     })?;
 
-    Ok(Self { root_path })
+    Ok(Self {
+      root_path,
+      weak_etags: false,
+      dedupe: false,
+    })
+  }
+
+  /// Derives a weak `ETag` from the size, modification time and inode of
+  /// blobs without a metadata sidecar, rather than returning none, so
+  /// [`head`](BlobStorageLike::head) always returns a usable `ETag` for cache
+  /// validation. The `ETag` changes whenever the file is modified or
+  /// replaced, but not necessarily when only its contents change within the
+  /// filesystem's timestamp resolution.
+  #[must_use]
+  pub const fn with_weak_etags(mut self) -> Self {
+    self.weak_etags = true;
+    self
+  }
+
+  /// Stores identical contents once, hard-linking each blob with the same
+  /// contents to a shared file under `.objects` in the root directory.
+  ///
+  /// Contents are matched by their MD5 checksum and then compared byte for
+  /// byte, so a checksum collision only stores a separate copy. Blobs are
+  /// never written in place, so overwriting or deleting one never changes
+  /// another, and shared files are removed once no blob links to them. The
+  /// root directory must be on a filesystem supporting hard links.
+  #[must_use]
+  pub const fn with_dedupe(mut self) -> Self {
+    self.dedupe = true;
+    self
   }
 
   /// Returns the file path for a given blob key
@@ -60,9 +132,446 @@ impl BlobStorageFilesystem {
     self.root_path.join(format!("{}.meta", key.as_str()))
   }
 
+  /// Returns the path of the shared file holding contents with the given
+  /// checksum
+  fn object_path(&self, etag: &str) -> PathBuf {
+    self.root_path.join(".objects").join(etag)
+  }
+
+  /// Returns the object lock file path for a given blob key
+  fn lock_path(&self, key: &BlobKey) -> PathBuf {
+    self.root_path.join(".locks").join(key.as_str())
+  }
+
+  /// Fails with [`BlobStorageError::InvalidInput`] if `key` is reserved, as
+  /// writing or deleting it would change another blob's metadata or lock.
+  /// Compared ignoring case, for case-insensitive filesystems.
+  fn check_key(key: &BlobKey) -> BlobStorageResult<()> {
+    let reserved = Path::new(key.as_str())
+      .extension()
+      .is_some_and(|ext| ext.eq_ignore_ascii_case("meta"))
+      || key.as_str().split('/').next().is_some_and(|first| {
+        RESERVED_DIRS
+          .iter()
+          .any(|dir| dir.eq_ignore_ascii_case(first))
+      });
+    if reserved {
+      warn!("Blob key is reserved");
+      return Err(BlobStorageError::InvalidInput(miette::miette!(
+        "Blob key is reserved: {}",
+        key
+      )));
+    }
+    Ok(())
+  }
+
+  /// Reads the object lock state of a blob, which is unlocked if it has no
+  /// lock file
+  async fn read_lock(&self, key: &BlobKey) -> BlobStorageResult<ObjectLock> {
+    let lock_path = self.lock_path(key);
+    let content = match fs::read_to_string(&lock_path).await {
+      Ok(content) => content,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        return Ok(ObjectLock::default());
+      }
+      Err(e) => {
+        error!(error = ?e, path = ?lock_path, "Failed to read lock file");
+        return Err(BlobStorageError::IoError(e));
+      }
+    };
+
+    serde_json::from_str(&content).map_err(|e| {
+      error!(error = ?e, "Failed to parse lock JSON");
+      BlobStorageError::InvalidInput(miette::miette!(
+        "Failed to parse object lock: {}",
+        e
+      ))
+    })
+  }
+
+  /// Writes the object lock state of a blob, after checking that it exists
+  async fn write_lock(
+    &self,
+    key: &BlobKey,
+    update: impl FnOnce(&mut ObjectLock) -> BlobStorageResult<()>,
+  ) -> BlobStorageResult<()> {
+    Self::check_key(key)?;
+    if !self.blob_path(key).exists() {
+      error!("Blob not found");
+      return Err(BlobStorageError::NotFound(key.clone()));
+    }
+
+    let mut lock = self.read_lock(key).await?;
+    update(&mut lock)?;
+
+    let content = serde_json::to_string(&lock).map_err(|e| {
+      error!(error = ?e, "Failed to serialize lock");
+      BlobStorageError::InvalidInput(miette::miette!(
+        "Failed to serialize object lock: {}",
+        e
+      ))
+    })?;
+    let lock_path = self.lock_path(key);
+    if let Some(parent) = lock_path.parent() {
+      fs::create_dir_all(parent).await?;
+    }
+    fs::write(&lock_path, content).await.map_err(|e| {
+      error!(error = ?e, path = ?lock_path, "Failed to write lock file");
+      BlobStorageError::IoError(e)
+    })
+  }
+
+  /// Fails with [`BlobStorageError::Locked`] if the blob can't be deleted or
+  /// overwritten
+  async fn check_unlocked(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    if self.read_lock(key).await?.is_locked(Utc::now()) {
+      warn!("Blob is locked");
+      return Err(BlobStorageError::Locked(key.clone()));
+    }
+    Ok(())
+  }
+
+  /// Returns the directory holding the parts of a resumable upload, after
+  /// checking that it belongs to the handle's key
+  async fn upload_dir(
+    &self,
+    handle: &UploadHandle,
+  ) -> BlobStorageResult<PathBuf> {
+    if handle.upload_id.is_empty()
+      || !handle.upload_id.chars().all(|c| c.is_ascii_hexdigit())
+    {
+      return Err(BlobStorageError::InvalidInput(miette::miette!(
+        "Invalid upload ID: {}",
+        handle.upload_id
+      )));
+    }
+
+    let upload_dir = self.root_path.join(".uploads").join(&handle.upload_id);
+    match fs::read_to_string(upload_dir.join("key")).await {
+      Ok(key) if key == handle.key.as_str() => Ok(upload_dir),
+      Ok(_) => {
+        error!("Upload belongs to a different key");
+        Err(BlobStorageError::NotFound(handle.key.clone()))
+      }
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        error!("Upload not found");
+        Err(BlobStorageError::NotFound(handle.key.clone()))
+      }
+      Err(e) => Err(BlobStorageError::IoError(e)),
+    }
+  }
+
+  /// Walks the root directory and returns the keys of all stored blobs,
+  /// skipping metadata and lock files, in-progress uploads, and shared
+  /// files
+  async fn blob_keys(&self) -> BlobStorageResult<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut dirs = vec![self.root_path.clone()];
+
+    while let Some(dir) = dirs.pop() {
+      let mut entries = fs::read_dir(&dir).await.map_err(|e| {
+        error!(error = ?e, path = ?dir, "Failed to read directory");
+        BlobStorageError::IoError(e)
+      })?;
+      while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if entry.file_type().await?.is_dir() {
+          if path != self.root_path.join(".uploads")
+            && path != self.root_path.join(".locks")
+            && path != self.root_path.join(".objects")
+          {
+            dirs.push(path);
+          }
+          continue;
+        }
+        if let Some(key) = self.key_for_path(&path) {
+          keys.push(key.as_str().to_owned());
+        }
+      }
+    }
+
+    Ok(keys)
+  }
+
+  /// Returns the key of the blob stored at `path`, or `None` if it's outside
+  /// the root directory or isn't a blob, e.g. a metadata file or part of an
+  /// in-progress upload.
+  #[must_use]
+  pub fn key_for_path(&self, path: &Path) -> Option<BlobKey> {
+    let relative = path.strip_prefix(&self.root_path).ok()?;
+    // metadata files can't be overwritten by blobs, as their keys are
+    // rejected
+    if path
+      .extension()
+      .is_some_and(|ext| ext.eq_ignore_ascii_case("meta"))
+    {
+      return None;
+    }
+    let components: Vec<_> = relative
+      .components()
+      .map(|c| c.as_os_str().to_string_lossy())
+      .collect();
+    match components.first().map(|c| &**c) {
+      None | Some(".uploads" | ".locks" | ".objects") => None,
+      Some(_) => Some(BlobKey::new(components.join("/"))),
+    }
+  }
+
+  /// Brings the metadata of the blob at `key` up to date with its file,
+  /// for files added, modified or removed by other tools.
+  ///
+  /// A file without metadata, or whose metadata no longer matches its size
+  /// and checksum, has its metadata regenerated from its contents,
+  /// keeping any content type and ACL already recorded. The metadata and
+  /// lock of a removed file are removed.
+  pub async fn reconcile(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<Reconciled> {
+    let blob_path = self.blob_path(key);
+    let previous = self.read_metadata(key).await.ok();
+
+    let data = match fs::read(&blob_path).await {
+      Ok(data) => data,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        if previous.is_none() {
+          return Ok(Reconciled::Unchanged);
+        }
+        Self::remove_if_exists(&self.metadata_path(key)).await?;
+        Self::remove_if_exists(&self.lock_path(key)).await?;
+        info!("Removed metadata of externally deleted blob");
+        return Ok(Reconciled::Removed);
+      }
+      Err(e) => return Err(BlobStorageError::IoError(e)),
+    };
+
+    let etag = Self::compute_etag(&data);
+    let size = data.len() as u64;
+    if previous
+      .as_ref()
+      .is_some_and(|m| m.size == size && m.etag.as_deref() == Some(&etag))
+    {
+      return Ok(Reconciled::Unchanged);
+    }
+
+    let last_modified = fs::metadata(&blob_path).await?.modified().map_or_else(
+      |_| Self::current_timestamp(),
+      |t| DateTime::<Utc>::from(t).to_rfc3339(),
+    );
+    let reconciled = if previous.is_some() {
+      Reconciled::Modified
+    } else {
+      Reconciled::Added
+    };
+    let (content_type, acl) =
+      previous.map_or((None, None), |m| (m.content_type, m.acl));
+    self
+      .write_metadata(key, &BlobMetadata {
+        size,
+        etag: Some(etag),
+        last_modified: Some(last_modified),
+        content_type,
+        acl,
+      })
+      .await?;
+    info!(size, ?reconciled, "Regenerated metadata of external blob");
+    Ok(reconciled)
+  }
+
+  /// Writes a blob and its metadata to disk
+  async fn write_blob(
+    &self,
+    key: &BlobKey,
+    data: &[u8],
+    content_type: Option<String>,
+    acl: Option<CannedAcl>,
+  ) -> BlobStorageResult<()> {
+    self.check_unlocked(key).await?;
+    let blob_path = self.blob_path(key);
+
+    // Create parent directories if they don't exist
+    if let Some(parent) = blob_path.parent() {
+      fs::create_dir_all(parent).await.map_err(|e| {
+        error!(error = ?e, "Failed to create parent directories");
+        BlobStorageError::IoError(e)
+      })?;
+    }
+
+    // Compute metadata
+    let etag = Self::compute_etag(data);
+    let last_modified = Self::current_timestamp();
+
+    // Remove any existing blob rather than writing through it, as it may be
+    // linked to other blobs
+    let previous = self.read_metadata(key).await.ok().and_then(|m| m.etag);
+    Self::remove_if_exists(&blob_path).await?;
+
+    // Write the blob to disk
+    if !(self.dedupe && self.link_object(&blob_path, &etag, data).await?) {
+      fs::write(&blob_path, data).await.map_err(|e| {
+        error!(error = ?e, path = ?blob_path, "Failed to write blob file");
+        BlobStorageError::IoError(e)
+      })?;
+    }
+    if let Some(previous) = previous {
+      self.release_object(&previous).await?;
+    }
+
+    // Write metadata
+    let metadata = BlobMetadata {
+      size: data.len() as u64,
+      etag: Some(etag),
+      last_modified: Some(last_modified),
+      content_type,
+      acl,
+    };
+
+    self.write_metadata(key, &metadata).await
+  }
+
+  /// Hard-links the blob at `blob_path` to the shared file for `data`,
+  /// creating it if needed. Returns `false` if a different shared file has
+  /// the same checksum, leaving the blob to be written separately.
+  ///
+  /// The shared file may be released by a concurrent delete between reading
+  /// and linking it, in which case it's recreated and linking is retried.
+  async fn link_object(
+    &self,
+    blob_path: &Path,
+    etag: &str,
+    data: &[u8],
+  ) -> BlobStorageResult<bool> {
+    const ATTEMPTS: usize = 3;
+
+    let object_path = self.object_path(etag);
+    let mut attempt = 0;
+    loop {
+      attempt += 1;
+      match fs::read(&object_path).await {
+        Ok(existing) if existing == data => {}
+        Ok(_) => {
+          warn!(etag, "Checksum collision, storing contents separately");
+          return Ok(false);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+          fs::create_dir_all(self.root_path.join(".objects")).await?;
+          // write then rename, so a partial file is never linked, to a name
+          // of our own, as other writers may be creating the same file
+          let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+          let unique = format!("{}:{nanos}", blob_path.display());
+          let temp_path = self.root_path.join(".objects").join(format!(
+            "{etag}.{}.tmp",
+            Self::compute_etag(unique.as_bytes())
+          ));
+          fs::write(&temp_path, data).await?;
+          fs::rename(&temp_path, &object_path).await?;
+        }
+        Err(e) => return Err(BlobStorageError::IoError(e)),
+      }
+
+      match fs::hard_link(&object_path, blob_path).await {
+        Ok(()) => {
+          debug!(etag, "Linked blob to shared contents");
+          return Ok(true);
+        }
+        Err(e)
+          if e.kind() == std::io::ErrorKind::NotFound && attempt < ATTEMPTS =>
+        {
+          debug!(etag, "Shared contents were released, retrying");
+        }
+        Err(e) => {
+          error!(error = ?e, path = ?blob_path, "Failed to link blob file");
+          return Err(BlobStorageError::IoError(e));
+        }
+      }
+    }
+  }
+
+  /// Removes the shared file with the given checksum once no blob links to
+  /// it
+  async fn release_object(&self, etag: &str) -> BlobStorageResult<()> {
+    let object_path = self.object_path(etag);
+    let links = match fs::metadata(&object_path).await {
+      Ok(metadata) => Self::link_count(&metadata),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+      Err(e) => return Err(BlobStorageError::IoError(e)),
+    };
+    if links <= 1 {
+      Self::remove_if_exists(&object_path).await?;
+      debug!(etag, "Removed unlinked shared contents");
+    }
+    Ok(())
+  }
+
+  /// The number of hard links to a file
+  #[cfg(unix)]
+  fn link_count(metadata: &std::fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::nlink(metadata)
+  }
+
+  /// The number of hard links to a file, which is unknown here, so shared
+  /// files are kept
+  #[cfg(not(unix))]
+  const fn link_count(_metadata: &std::fs::Metadata) -> u64 { u64::MAX }
+
+  /// Copies the file at `from` to a new file at `to`, cloning it if the
+  /// filesystem supports it, like btrfs or xfs, so the copy shares its
+  /// extents and is near-instant however large it is. Otherwise its bytes
+  /// are copied, in the kernel with `copy_file_range` where available.
+  fn clone_or_copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    match Self::reflink(from, to) {
+      Ok(()) => return Ok(()),
+      Err(e) => debug!(error = ?e, "Couldn't clone file, copying it"),
+    }
+    std::fs::copy(from, to).map(|_| ())
+  }
+
+  /// Clones the file at `from` to a new file at `to` with the `FICLONE`
+  /// ioctl, removing the new file if that fails
+  #[cfg(target_os = "linux")]
+  fn reflink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let source = std::fs::File::open(from)?;
+    let target = std::fs::File::create_new(to)?;
+    if let Err(e) = rustix::fs::ioctl_ficlone(&target, &source) {
+      drop(target);
+      std::fs::remove_file(to)?;
+      return Err(e.into());
+    }
+    Ok(())
+  }
+
+  /// Removes a file, ignoring it not existing
+  async fn remove_if_exists(path: &Path) -> BlobStorageResult<()> {
+    match fs::remove_file(path).await {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+        error!(error = ?e, path = ?path, "Failed to remove file");
+        Err(BlobStorageError::IoError(e))
+      }
+      _ => Ok(()),
+    }
+  }
+
   /// Computes the MD5 hash of data
   fn compute_etag(data: &[u8]) -> String { format!("{:x}", md5::compute(data)) }
 
+  /// Computes a weak `ETag` from a file's size, modification time and inode
+  fn weak_etag(metadata: &std::fs::Metadata) -> String {
+    let modified = metadata
+      .modified()
+      .ok()
+      .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+      .map_or(0, |d| d.as_nanos());
+    #[cfg(unix)]
+    let inode = std::os::unix::fs::MetadataExt::ino(metadata);
+    #[cfg(not(unix))]
+    let inode = 0_u64;
+    format!("W/\"{:x}-{modified:x}-{inode:x}\"", metadata.len())
+  }
+
   /// Gets the current timestamp in ISO 8601 format
   fn current_timestamp() -> String {
     let now = SystemTime::now()
@@ -74,6 +583,89 @@ impl BlobStorageFilesystem {
       .to_rfc3339()
   }
 
+  /// Copies the file at `source` to a new temporary file under `.uploads`,
+  /// or hard-links it there if `link`, returning its path. Move it into
+  /// place with [`replace_with`](Self::replace_with).
+  async fn copy_to_temp(
+    &self,
+    source: &Path,
+    key: &BlobKey,
+    link: bool,
+  ) -> BlobStorageResult<PathBuf> {
+    let nanos = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_nanos();
+    let temp_dir = self.root_path.join(".uploads");
+    let temp_path = temp_dir.join(format!(
+      "{}.copy",
+      Self::compute_etag(format!("{key}:{nanos}").as_bytes())
+    ));
+    fs::create_dir_all(&temp_dir).await?;
+
+    let copied = if link {
+      fs::hard_link(source, &temp_path).await
+    } else {
+      let (source, temp_path) = (source.to_owned(), temp_path.clone());
+      tokio::task::spawn_blocking(move || {
+        Self::clone_or_copy(&source, &temp_path)
+      })
+      .await
+      .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+    };
+    if let Err(e) = copied {
+      error!(error = ?e, path = ?source, "Failed to copy file");
+      Self::remove_if_exists(&temp_path).await?;
+      return Err(BlobStorageError::IoError(e));
+    }
+    Ok(temp_path)
+  }
+
+  /// Renames the complete file at `temp_path` over the blob at `key`, so
+  /// readers never see a partial blob, and writes its metadata
+  async fn replace_with(
+    &self,
+    key: &BlobKey,
+    temp_path: &Path,
+    metadata: &BlobMetadata,
+  ) -> BlobStorageResult<()> {
+    let blob_path = self.blob_path(key);
+    if let Some(parent) = blob_path.parent() {
+      fs::create_dir_all(parent).await.map_err(|e| {
+        error!(error = ?e, "Failed to create parent directories");
+        BlobStorageError::IoError(e)
+      })?;
+    }
+
+    let previous = self.read_metadata(key).await.ok().and_then(|m| m.etag);
+    fs::rename(temp_path, &blob_path).await.map_err(|e| {
+      error!(error = ?e, path = ?blob_path, "Failed to move blob file");
+      BlobStorageError::IoError(e)
+    })?;
+    if let Some(previous) = previous {
+      self.release_object(&previous).await?;
+    }
+
+    self.write_metadata(key, metadata).await
+  }
+
+  /// Computes the size and MD5 hash of a file, reading it in chunks
+  async fn file_etag(path: &Path) -> BlobStorageResult<(u64, String)> {
+    let mut file = fs::File::open(path).await?;
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+      let read = file.read(&mut buffer).await?;
+      if read == 0 {
+        break;
+      }
+      context.consume(&buffer[..read]);
+      size += read as u64;
+    }
+    Ok((size, format!("{:x}", context.finalize())))
+  }
+
   /// Reads metadata from a metadata file
   async fn read_metadata(
     &self,
@@ -140,6 +732,7 @@ impl BlobStorageLike for BlobStorageFilesystem {
     options: UploadOptions,
   ) -> BlobStorageResult<()> {
     debug!("Starting stream upload");
+    Self::check_key(key)?;
 
     let blob_path = self.blob_path(key);
 
@@ -153,14 +746,6 @@ impl BlobStorageLike for BlobStorageFilesystem {
       debug!("Blob does not exist, proceeding with upload");
     }
 
-    // Create parent directories if they don't exist
-    if let Some(parent) = blob_path.parent() {
-      fs::create_dir_all(parent).await.map_err(|e| {
-        error!(error = ?e, "Failed to create parent directories");
-        BlobStorageError::IoError(e)
-      })?;
-    }
-
     // Collect the stream into a single Bytes object
     debug!("Collecting stream chunks");
     let chunks: Vec<Bytes> = data.try_collect().await.map_err(|e| {
@@ -180,24 +765,9 @@ impl BlobStorageLike for BlobStorageFilesystem {
 
     debug!(total_size = total_size, "Combined chunks into single blob");
 
-    // Compute metadata
-    let etag = Self::compute_etag(&combined);
-    let last_modified = Self::current_timestamp();
-
-    // Write the blob to disk
-    fs::write(&blob_path, &combined).await.map_err(|e| {
-      error!(error = ?e, path = ?blob_path, "Failed to write blob file");
-      BlobStorageError::IoError(e)
-    })?;
-
-    // Write metadata
-    let metadata = BlobMetadata {
-      size:          total_size as u64,
-      etag:          Some(etag),
-      last_modified: Some(last_modified),
-    };
-
-    self.write_metadata(key, &metadata).await?;
+    self
+      .write_blob(key, &combined, options.content_type, options.acl)
+      .await?;
 
     info!(
       size = total_size,
@@ -208,6 +778,60 @@ impl BlobStorageLike for BlobStorageFilesystem {
     Ok(())
   }
 
+  /// Copies the file into place rather than collecting `data` in memory,
+  /// unless blobs are deduplicated, which needs their contents in memory
+  /// anyway.
+  #[instrument(
+    skip(self, data),
+    fields(
+      key = %key,
+      path = ?path,
+      overwrite = options.overwrite,
+    ),
+    err
+  )]
+  async fn put_file(
+    &self,
+    key: &BlobKey,
+    path: &Path,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    if self.dedupe {
+      return self.put_stream(key, data, options).await;
+    }
+    Self::check_key(key)?;
+
+    if !options.overwrite && self.blob_path(key).exists() {
+      warn!("Blob already exists and overwrite=false");
+      return Err(BlobStorageError::AlreadyExists(key.clone()));
+    }
+    self.check_unlocked(key).await?;
+
+    let temp_path = self.copy_to_temp(path, key, false).await?;
+    // hash the copy, as the source may change
+    let (size, etag) = match Self::file_etag(&temp_path).await {
+      Ok(hashed) => hashed,
+      Err(e) => {
+        Self::remove_if_exists(&temp_path).await?;
+        return Err(e);
+      }
+    };
+    self
+      .replace_with(key, &temp_path, &BlobMetadata {
+        size,
+        etag: Some(etag),
+        last_modified: Some(Self::current_timestamp()),
+        content_type: options.content_type,
+        acl: options.acl,
+      })
+      .await?;
+
+    info!(size, "Blob copied from file successfully");
+
+    Ok(())
+  }
+
   #[instrument(
     skip(self),
     fields(key = %key),
@@ -245,6 +869,53 @@ impl BlobStorageLike for BlobStorageFilesystem {
     Ok(Box::pin(stream))
   }
 
+  #[instrument(
+    skip(self),
+    fields(key = %key, start = range.start, end = range.end),
+    err
+  )]
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    debug!("Retrieving blob range stream");
+
+    let blob_path = self.blob_path(key);
+
+    let mut file = match fs::File::open(&blob_path).await {
+      Ok(file) => file,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        error!("Blob not found");
+        return Err(BlobStorageError::NotFound(key.clone()));
+      }
+      Err(e) => {
+        error!(error = ?e, path = ?blob_path, "Failed to open blob file");
+        return Err(BlobStorageError::IoError(e));
+      }
+    };
+
+    // read only the requested bytes into memory
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(range.start)).await?;
+    file
+      .take(range.end.saturating_sub(range.start))
+      .read_to_end(&mut data)
+      .await
+      .map_err(|e| {
+        error!(error = ?e, path = ?blob_path, "Failed to read blob file");
+        BlobStorageError::IoError(e)
+      })?;
+
+    let data_size = data.len();
+    debug!(size = data_size, "Retrieved blob range data");
+
+    let bytes = Bytes::from(data);
+    let stream = futures::stream::once(async move { Ok(bytes) });
+
+    Ok(Box::pin(stream))
+  }
+
   #[instrument(
     skip(self),
     fields(key = %key),
@@ -274,10 +945,22 @@ impl BlobStorageLike for BlobStorageFilesystem {
         BlobStorageError::IoError(e)
       })?;
 
+      let (etag, last_modified) = if self.weak_etags {
+        let last_modified = file_metadata
+          .modified()
+          .ok()
+          .map(|t| DateTime::<Utc>::from(t).to_rfc3339());
+        (Some(Self::weak_etag(&file_metadata)), last_modified)
+      } else {
+        (None, None)
+      };
+
       BlobMetadata {
-        size:          file_metadata.len(),
-        etag:          None,
-        last_modified: None,
+        size: file_metadata.len(),
+        etag,
+        last_modified,
+        content_type: None,
+        acl: None,
       }
     };
 
@@ -297,6 +980,7 @@ impl BlobStorageLike for BlobStorageFilesystem {
   )]
   async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
     debug!("Deleting blob");
+    Self::check_key(key)?;
 
     let blob_path = self.blob_path(key);
     let metadata_path = self.metadata_path(key);
@@ -306,8 +990,11 @@ impl BlobStorageLike for BlobStorageFilesystem {
       return Err(BlobStorageError::NotFound(key.clone()));
     }
 
+    self.check_unlocked(key).await?;
+
     // Get size before deletion for logging
-    let size = fs::metadata(&blob_path).await.map(|m| m.len()).unwrap_or(0);
+    let size = fs::metadata(&blob_path).await.map_or(0, |m| m.len());
+    let etag = self.read_metadata(key).await.ok().and_then(|m| m.etag);
 
     // Delete blob file
     fs::remove_file(&blob_path).await.map_err(|e| {
@@ -315,14 +1002,96 @@ impl BlobStorageLike for BlobStorageFilesystem {
       BlobStorageError::IoError(e)
     })?;
 
-    // Delete metadata file (ignore errors if it doesn't exist)
+    // Delete metadata and lock files (ignore errors if they don't exist)
     let _ = fs::remove_file(&metadata_path).await;
+    let _ = fs::remove_file(self.lock_path(key)).await;
+
+    if let Some(etag) = etag {
+      self.release_object(&etag).await?;
+    }
 
     info!(size = size, "Blob deleted successfully");
 
     Ok(())
   }
 
+  /// Copies the blob to a temporary file and renames it over the
+  /// destination, so readers never see a partial copy. The copy is a clone
+  /// sharing the blob's extents on filesystems supporting it, and with
+  /// [`with_dedupe`](Self::with_dedupe), a hard link instead.
+  #[instrument(
+    skip(self),
+    fields(from = %from, to = %to),
+    err
+  )]
+  async fn copy(&self, from: &BlobKey, to: &BlobKey) -> BlobStorageResult<()> {
+    let Some(metadata) = self.head(from).await? else {
+      error!("Blob not found");
+      return Err(BlobStorageError::NotFound(from.clone()));
+    };
+    if from == to {
+      return Ok(());
+    }
+    Self::check_key(to)?;
+    self.check_unlocked(to).await?;
+
+    let temp_path = self
+      .copy_to_temp(&self.blob_path(from), to, self.dedupe)
+      .await?;
+    let size = metadata.size;
+    self
+      .replace_with(to, &temp_path, &BlobMetadata {
+        last_modified: Some(Self::current_timestamp()),
+        ..metadata
+      })
+      .await?;
+
+    info!(size, "Blob copied successfully");
+
+    Ok(())
+  }
+
+  /// Lists blobs by walking the whole directory tree, so each page costs as
+  /// much as listing every blob.
+  #[instrument(skip(self), err)]
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    let mut keys: Vec<String> = self
+      .blob_keys()
+      .await?
+      .into_iter()
+      .filter(|key| key.starts_with(prefix))
+      .filter(|key| {
+        continuation
+          .as_deref()
+          .is_none_or(|after| key.as_str() > after)
+      })
+      .collect();
+    keys.sort_unstable();
+
+    let continuation =
+      (keys.len() > LIST_PAGE_SIZE).then(|| keys[LIST_PAGE_SIZE - 1].clone());
+    keys.truncate(LIST_PAGE_SIZE);
+
+    let mut entries = Vec::with_capacity(keys.len());
+    for key in keys {
+      let key = BlobKey::new(key);
+      // the blob may have been deleted since the walk
+      if let Some(metadata) = self.head(&key).await? {
+        entries.push(BlobEntry { key, metadata });
+      }
+    }
+
+    debug!(entry_count = entries.len(), "Listed blobs");
+    Ok(BlobListPage {
+      entries,
+      continuation,
+    })
+  }
+
   #[instrument(
     skip(self),
     fields(
@@ -374,6 +1143,231 @@ impl BlobStorageLike for BlobStorageFilesystem {
 
     Ok(url)
   }
+
+  #[instrument(
+    skip(self),
+    fields(
+      key = %key,
+      overwrite = options.overwrite,
+    ),
+    err
+  )]
+  async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadHandle> {
+    Self::check_key(key)?;
+    if !options.overwrite && self.blob_path(key).exists() {
+      warn!("Blob already exists and overwrite=false");
+      return Err(BlobStorageError::AlreadyExists(key.clone()));
+    }
+
+    let nanos = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_nanos();
+    let upload_id = Self::compute_etag(format!("{key}:{nanos}").as_bytes());
+
+    let upload_dir = self.root_path.join(".uploads").join(&upload_id);
+    fs::create_dir_all(&upload_dir).await.map_err(|e| {
+      error!(error = ?e, path = ?upload_dir, "Failed to create upload directory");
+      BlobStorageError::IoError(e)
+    })?;
+    fs::write(upload_dir.join("key"), key.as_str())
+      .await
+      .map_err(BlobStorageError::IoError)?;
+    if let Some(content_type) = &options.content_type {
+      fs::write(upload_dir.join("content-type"), content_type)
+        .await
+        .map_err(BlobStorageError::IoError)?;
+    }
+    if let Some(acl) = options.acl {
+      // stored as JSON, to be parsed back with serde
+      fs::write(upload_dir.join("acl"), format!("\"{}\"", acl.as_str()))
+        .await
+        .map_err(BlobStorageError::IoError)?;
+    }
+
+    info!(upload_id = %upload_id, "Resumable upload created");
+
+    Ok(UploadHandle {
+      key: key.clone(),
+      upload_id,
+      overwrite: options.overwrite,
+    })
+  }
+
+  #[instrument(
+    skip(self, handle, data),
+    fields(
+      key = %handle.key,
+      upload_id = %handle.upload_id,
+      size = data.len(),
+    ),
+    err
+  )]
+  async fn upload_part(
+    &self,
+    handle: &UploadHandle,
+    part_number: u32,
+    data: Bytes,
+  ) -> BlobStorageResult<UploadedPart> {
+    if part_number == 0 {
+      return Err(BlobStorageError::InvalidInput(miette::miette!(
+        "Part numbers start at 1"
+      )));
+    }
+
+    let upload_dir = self.upload_dir(handle).await?;
+
+    // Write to a temporary file first so a crash never leaves a torn part
+    let part_path = upload_dir.join(format!("{part_number}.part"));
+    let temp_path = upload_dir.join(format!("{part_number}.part.tmp"));
+    fs::write(&temp_path, &data).await.map_err(|e| {
+      error!(error = ?e, path = ?temp_path, "Failed to write part file");
+      BlobStorageError::IoError(e)
+    })?;
+    fs::rename(&temp_path, &part_path)
+      .await
+      .map_err(BlobStorageError::IoError)?;
+
+    debug!("Part uploaded successfully");
+
+    Ok(UploadedPart {
+      part_number,
+      size: data.len() as u64,
+      etag: Self::compute_etag(&data),
+    })
+  }
+
+  #[instrument(
+    skip(self, handle, parts),
+    fields(
+      key = %handle.key,
+      upload_id = %handle.upload_id,
+      part_count = parts.len(),
+    ),
+    err
+  )]
+  async fn complete_upload(
+    &self,
+    handle: &UploadHandle,
+    parts: &[UploadedPart],
+  ) -> BlobStorageResult<()> {
+    let upload_dir = self.upload_dir(handle).await?;
+
+    let mut combined = Vec::new();
+    let mut previous = 0;
+    for part in parts {
+      if part.part_number <= previous {
+        return Err(BlobStorageError::InvalidInput(miette::miette!(
+          "Parts must be in ascending order by part number"
+        )));
+      }
+      previous = part.part_number;
+
+      let part_path = upload_dir.join(format!("{}.part", part.part_number));
+      let data = fs::read(&part_path).await.map_err(|e| {
+        error!(error = ?e, path = ?part_path, "Failed to read part file");
+        BlobStorageError::IoError(e)
+      })?;
+      if Self::compute_etag(&data) != part.etag {
+        return Err(BlobStorageError::InvalidInput(miette::miette!(
+          "Part {} has changed since it was uploaded",
+          part.part_number
+        )));
+      }
+      combined.extend_from_slice(&data);
+    }
+
+    if !handle.overwrite && self.blob_path(&handle.key).exists() {
+      warn!("Blob already exists and overwrite=false");
+      return Err(BlobStorageError::AlreadyExists(handle.key.clone()));
+    }
+
+    let content_type =
+      match fs::read_to_string(upload_dir.join("content-type")).await {
+        Ok(content_type) => Some(content_type),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(BlobStorageError::IoError(e)),
+      };
+    let acl = match fs::read_to_string(upload_dir.join("acl")).await {
+      Ok(acl) => Some(serde_json::from_str(&acl).map_err(|e| {
+        BlobStorageError::InvalidInput(miette::miette!(
+          "Failed to parse upload ACL: {}",
+          e
+        ))
+      })?),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+      Err(e) => return Err(BlobStorageError::IoError(e)),
+    };
+    self
+      .write_blob(&handle.key, &combined, content_type, acl)
+      .await?;
+
+    fs::remove_dir_all(&upload_dir).await.map_err(|e| {
+      error!(error = ?e, path = ?upload_dir, "Failed to remove upload directory");
+      BlobStorageError::IoError(e)
+    })?;
+
+    info!(
+      size = combined.len(),
+      "Resumable upload completed successfully"
+    );
+
+    Ok(())
+  }
+
+  #[instrument(
+    skip(self, handle),
+    fields(
+      key = %handle.key,
+      upload_id = %handle.upload_id,
+    ),
+    err
+  )]
+  async fn abort_upload(&self, handle: &UploadHandle) -> BlobStorageResult<()> {
+    let upload_dir = self.upload_dir(handle).await?;
+
+    fs::remove_dir_all(&upload_dir).await.map_err(|e| {
+      error!(error = ?e, path = ?upload_dir, "Failed to remove upload directory");
+      BlobStorageError::IoError(e)
+    })?;
+
+    info!("Resumable upload aborted");
+
+    Ok(())
+  }
+
+  #[instrument(skip(self), fields(key = %key), err)]
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self.write_lock(key, |lock| lock.retain(key, until)).await?;
+
+    info!("Blob retention set");
+    Ok(())
+  }
+
+  #[instrument(skip(self), fields(key = %key), err)]
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self
+      .write_lock(key, |lock| {
+        lock.legal_hold = hold;
+        Ok(())
+      })
+      .await?;
+
+    info!("Blob legal hold set");
+    Ok(())
+  }
 }
 
 #[cfg(test)]
@@ -393,7 +1387,10 @@ mod tests {
     let stream = Box::pin(stream::once(async move { Ok(data) }));
 
     storage
-      .put_stream(&key, stream, UploadOptions { overwrite: true })
+      .put_stream(&key, stream, UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
       .await
       .unwrap();
 
@@ -415,7 +1412,10 @@ mod tests {
     let stream = Box::pin(stream::once(async move { Ok(data) }));
 
     storage
-      .put_stream(&key, stream, UploadOptions { overwrite: true })
+      .put_stream(&key, stream, UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
       .await
       .unwrap();
 
@@ -435,7 +1435,10 @@ mod tests {
     let stream = Box::pin(stream::once(async move { Ok(data) }));
 
     storage
-      .put_stream(&key, stream, UploadOptions { overwrite: true })
+      .put_stream(&key, stream, UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
       .await
       .unwrap();
 
@@ -444,4 +1447,287 @@ mod tests {
     let result = storage.head(&key).await.unwrap();
     assert!(result.is_none());
   }
+
+  #[tokio::test]
+  async fn test_list_page_skips_sidecars() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = BlobStorageFilesystem::new(temp_dir.path()).await.unwrap();
+
+    for key in ["a/one", "a/nested/two", "a/Cargo.lock", "b/three"] {
+      let stream = Box::pin(stream::once(async { Ok(Bytes::from("data")) }));
+      storage
+        .put_stream(&BlobKey::new(key), stream, UploadOptions::default())
+        .await
+        .unwrap();
+    }
+    storage
+      .create_upload(&BlobKey::new("a/pending"), UploadOptions::default())
+      .await
+      .unwrap();
+
+    let page = storage.list_page("a/", None).await.unwrap();
+    let keys: Vec<_> = page.entries.iter().map(|e| e.key.as_str()).collect();
+    assert_eq!(keys, ["a/Cargo.lock", "a/nested/two", "a/one"]);
+    assert!(page.continuation.is_none());
+  }
+
+  #[tokio::test]
+  async fn test_sibling_keys_cant_unlock() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = BlobStorageFilesystem::new(temp_dir.path()).await.unwrap();
+    let put = |key: &'static str| {
+      let storage = storage.clone();
+      async move {
+        let stream = Box::pin(stream::once(async { Ok(Bytes::from("data")) }));
+        storage
+          .put_stream(&BlobKey::new(key), stream, UploadOptions {
+            overwrite: true,
+            ..UploadOptions::default()
+          })
+          .await
+      }
+    };
+    let key = BlobKey::new("x");
+    put("x").await.unwrap();
+    storage.set_legal_hold(&key, true).await.unwrap();
+
+    // the lock isn't stored beside the blob, so its neighbours are just blobs
+    put("x.lock").await.unwrap();
+    storage.delete(&BlobKey::new("x.lock")).await.unwrap();
+    assert!(matches!(
+      storage.delete(&key).await,
+      Err(BlobStorageError::Locked(_))
+    ));
+
+    // and keys that would reach its metadata or lock are rejected
+    for reserved in ["x.meta", ".locks/x"] {
+      assert!(matches!(
+        put(reserved).await,
+        Err(BlobStorageError::InvalidInput(_))
+      ));
+      assert!(matches!(
+        storage.delete(&BlobKey::new(reserved)).await,
+        Err(BlobStorageError::InvalidInput(_))
+      ));
+    }
+    assert!(matches!(
+      storage.delete(&key).await,
+      Err(BlobStorageError::Locked(_))
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_put_file_copies_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = TempDir::new().unwrap();
+    let source = source_dir.path().join("upload.bin");
+    std::fs::write(&source, "file contents").unwrap();
+    let storage = BlobStorageFilesystem::new(temp_dir.path()).await.unwrap();
+
+    // the stream isn't read when the file can be copied
+    let unread = Box::pin(stream::once(async {
+      Err(std::io::Error::other("stream was read"))
+    }));
+    let key = BlobKey::new("nested/copied.bin");
+    storage
+      .put_file(&key, &source, unread, UploadOptions {
+        content_type: Some("application/octet-stream".to_owned()),
+        ..UploadOptions::default()
+      })
+      .await
+      .unwrap();
+
+    let result: Vec<Bytes> = storage
+      .get_stream(&key)
+      .await
+      .unwrap()
+      .try_collect()
+      .await
+      .unwrap();
+    assert_eq!(result.concat(), b"file contents");
+    let metadata = storage.head(&key).await.unwrap().unwrap();
+    assert_eq!(metadata.size, 13);
+    assert_eq!(
+      metadata.etag,
+      Some(BlobStorageFilesystem::compute_etag(b"file contents"))
+    );
+    assert_eq!(
+      metadata.content_type.as_deref(),
+      Some("application/octet-stream")
+    );
+    let temps = std::fs::read_dir(temp_dir.path().join(".uploads")).unwrap();
+    assert_eq!(temps.count(), 0);
+  }
+
+  #[test]
+  fn test_clone_or_copy() {
+    let temp_dir = TempDir::new().unwrap();
+    let source = temp_dir.path().join("source");
+    let target = temp_dir.path().join("target");
+    let data: Vec<u8> = (0..=255).cycle().take(1 << 20).collect();
+    std::fs::write(&source, &data).unwrap();
+
+    BlobStorageFilesystem::clone_or_copy(&source, &target).unwrap();
+    assert_eq!(std::fs::read(&target).unwrap(), data);
+
+    // clones share extents copy-on-write, so writes don't reach the source
+    std::fs::write(&target, "changed").unwrap();
+    assert_eq!(std::fs::read(&source).unwrap(), data);
+  }
+
+  #[tokio::test]
+  async fn test_weak_etag_without_sidecar() {
+    let temp_dir = TempDir::new().unwrap();
+    let key = BlobKey::new("dropped.txt");
+    std::fs::write(temp_dir.path().join("dropped.txt"), "hello").unwrap();
+
+    let storage = BlobStorageFilesystem::new(temp_dir.path()).await.unwrap();
+    let metadata = storage.head(&key).await.unwrap().unwrap();
+    assert_eq!(metadata.size, 5);
+    assert!(metadata.etag.is_none());
+
+    let storage = storage.with_weak_etags();
+    let metadata = storage.head(&key).await.unwrap().unwrap();
+    let etag = metadata.etag.unwrap();
+    assert!(etag.starts_with("W/\"5-"));
+    assert!(metadata.last_modified.is_some());
+    // stable while the file is unchanged
+    assert_eq!(storage.head(&key).await.unwrap().unwrap().etag, Some(etag));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn test_dedupe_links_identical_blobs() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = BlobStorageFilesystem::new(temp_dir.path())
+      .await
+      .unwrap()
+      .with_dedupe();
+    let put = |key: &'static str, data: &'static str| {
+      let storage = storage.clone();
+      async move {
+        let stream =
+          Box::pin(stream::once(async move { Ok(Bytes::from(data)) }));
+        storage
+          .put_stream(&BlobKey::new(key), stream, UploadOptions {
+            overwrite: true,
+            ..UploadOptions::default()
+          })
+          .await
+          .unwrap();
+      }
+    };
+    put("a", "same").await;
+    put("b", "same").await;
+
+    let a = std::fs::metadata(temp_dir.path().join("a")).unwrap();
+    let b = std::fs::metadata(temp_dir.path().join("b")).unwrap();
+    assert_eq!(a.ino(), b.ino());
+    assert_eq!(a.nlink(), 3);
+
+    // overwriting one blob leaves the other intact
+    put("a", "different").await;
+    let result: Vec<Bytes> = storage
+      .get_stream(&BlobKey::new("b"))
+      .await
+      .unwrap()
+      .try_collect()
+      .await
+      .unwrap();
+    assert_eq!(result.concat(), b"same");
+
+    // the shared file goes once its last blob does
+    storage.delete(&BlobKey::new("b")).await.unwrap();
+    storage.delete(&BlobKey::new("a")).await.unwrap();
+    let objects = std::fs::read_dir(temp_dir.path().join(".objects")).unwrap();
+    assert_eq!(objects.count(), 0);
+
+    // shared files can't be replaced through their keys
+    let etag = BlobStorageFilesystem::compute_etag(b"same");
+    let stream = Box::pin(stream::once(async { Ok(Bytes::from("other")) }));
+    let result = storage
+      .put_stream(
+        &BlobKey::new(format!(".objects/{etag}")),
+        stream,
+        UploadOptions::default(),
+      )
+      .await;
+    assert!(matches!(result, Err(BlobStorageError::InvalidInput(_))));
+  }
+
+  #[cfg(unix)]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_dedupe_concurrent_puts_and_deletes() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = BlobStorageFilesystem::new(temp_dir.path())
+      .await
+      .unwrap()
+      .with_dedupe();
+
+    let tasks: Vec<_> = (0..16)
+      .map(|i| {
+        let storage = storage.clone();
+        tokio::spawn(async move {
+          let key = BlobKey::new(format!("blob-{i}"));
+          for _ in 0..8 {
+            let stream =
+              Box::pin(stream::once(async { Ok(Bytes::from("same")) }));
+            storage
+              .put_stream(&key, stream, UploadOptions {
+                overwrite: true,
+                ..UploadOptions::default()
+              })
+              .await
+              .unwrap();
+            storage.delete(&key).await.unwrap();
+          }
+        })
+      })
+      .collect();
+    for task in tasks {
+      task.await.unwrap();
+    }
+  }
+
+  #[tokio::test]
+  async fn test_reconcile() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = BlobStorageFilesystem::new(temp_dir.path()).await.unwrap();
+    let path = temp_dir.path().join("notes/a.txt");
+    let key = storage.key_for_path(&path).unwrap();
+    assert_eq!(key.as_str(), "notes/a.txt");
+    assert!(
+      storage
+        .key_for_path(&temp_dir.path().join("a.meta"))
+        .is_none()
+    );
+    assert!(
+      storage
+        .key_for_path(&temp_dir.path().join(".uploads/1/key"))
+        .is_none()
+    );
+
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "hello").unwrap();
+    assert_eq!(storage.reconcile(&key).await.unwrap(), Reconciled::Added);
+    assert_eq!(
+      storage.reconcile(&key).await.unwrap(),
+      Reconciled::Unchanged
+    );
+    let metadata = storage.read_metadata(&key).await.unwrap();
+    assert_eq!(
+      metadata.etag,
+      Some(BlobStorageFilesystem::compute_etag(b"hello"))
+    );
+
+    std::fs::write(&path, "goodbye").unwrap();
+    assert_eq!(storage.reconcile(&key).await.unwrap(), Reconciled::Modified);
+    assert_eq!(storage.head(&key).await.unwrap().unwrap().size, 7);
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(storage.reconcile(&key).await.unwrap(), Reconciled::Removed);
+    assert!(!temp_dir.path().join("notes/a.txt.meta").exists());
+  }
 }
@@ -0,0 +1,139 @@
+//! Saving and loading blobs as tarballs, for test fixtures.
+
+use std::{
+  io::{Read, Write},
+  path::Component,
+};
+
+use bytes::Bytes;
+use chrono::DateTime;
+use storage_core::{BlobKey, BlobStorageResult};
+use tracing::{info, instrument};
+
+use crate::{BlobStorageMemory, StoredBlob, check_unlocked};
+
+/// The PAX record holding a blob's media type.
+const CONTENT_TYPE_RECORD: &str = "PALIN.content_type";
+/// The PAX record holding a blob's canned ACL.
+const ACL_RECORD: &str = "PALIN.acl";
+
+impl BlobStorageMemory {
+  /// Writes every blob to `writer` as a tarball, one file per blob named by
+  /// its key, ordered by key.
+  ///
+  /// Last-modified timestamps are kept as file modification times, and
+  /// content types and ACLs as PAX records, so
+  /// [`import_tar`](Self::import_tar) restores them. Pending resumable
+  /// uploads and object locks aren't exported.
+  #[instrument(skip_all, err)]
+  pub async fn export_tar<W: Write>(&self, writer: W) -> BlobStorageResult<W> {
+    let storage = self.storage.read().await;
+    let mut keys: Vec<_> = storage.keys().collect();
+    keys.sort_unstable();
+
+    let mut builder = tar::Builder::new(writer);
+    for key in keys {
+      let blob = &storage[key];
+      let mut records = Vec::new();
+      if let Some(content_type) = &blob.content_type {
+        records.push((CONTENT_TYPE_RECORD, content_type.as_bytes()));
+      }
+      if let Some(acl) = blob.acl {
+        records.push((ACL_RECORD, acl.as_str().as_bytes()));
+      }
+      if !records.is_empty() {
+        builder.append_pax_extensions(records)?;
+      }
+
+      let mut header = tar::Header::new_gnu();
+      header.set_size(blob.data.len() as u64);
+      header.set_mode(0o644);
+      let modified = DateTime::parse_from_rfc3339(&blob.last_modified)
+        .map_or(0, |t| t.timestamp());
+      header.set_mtime(u64::try_from(modified).unwrap_or_default());
+      builder.append_data(&mut header, key, blob.data.as_ref())?;
+    }
+
+    info!(blob_count = storage.len(), "Exported blobs to tarball");
+    Ok(builder.into_inner()?)
+  }
+
+  /// Loads every file in the tarball `reader` as a blob keyed by its path,
+  /// overwriting blobs already stored, and returns how many were loaded.
+  ///
+  /// Fails with [`Locked`](storage_core::BlobStorageError::Locked), loading
+  /// nothing, if any blob would overwrite one under retention or a legal hold.
+  ///
+  /// Tarballs from [`export_tar`](Self::export_tar) have their timestamps,
+  /// content types and ACLs restored. Tarballs from elsewhere, e.g. made
+  /// with `tar -cf fixtures.tar -C fixtures .`, work too; their blobs are
+  /// loaded without content types.
+  #[instrument(skip_all, err)]
+  pub async fn import_tar<R: Read>(
+    &self,
+    reader: R,
+  ) -> BlobStorageResult<usize> {
+    let mut archive = tar::Archive::new(reader);
+    let mut blobs = Vec::new();
+    for entry in archive.entries()? {
+      let mut entry = entry?;
+      if !entry.header().entry_type().is_file() {
+        continue;
+      }
+      // skip the `./` of tarballs made from a directory
+      let key = entry
+        .path()?
+        .components()
+        .filter_map(|c| match c {
+          Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+          _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+      let mut content_type = None;
+      let mut acl = None;
+      if let Some(records) = entry.pax_extensions()? {
+        for record in records {
+          let record = record?;
+          match record.key() {
+            Ok(CONTENT_TYPE_RECORD) => {
+              content_type = record.value().ok().map(str::to_owned);
+            }
+            Ok(ACL_RECORD) => {
+              acl = record.value().ok().and_then(|value| value.parse().ok());
+            }
+            _ => {}
+          }
+        }
+      }
+      let modified = entry
+        .header()
+        .mtime()
+        .ok()
+        .and_then(|t| i64::try_from(t).ok())
+        .and_then(|t| DateTime::from_timestamp(t, 0));
+
+      let mut data = Vec::new();
+      entry.read_to_end(&mut data)?;
+      let mut blob = StoredBlob::new(Bytes::from(data), self.clock.as_ref());
+      if let Some(modified) = modified {
+        blob.last_modified = modified.to_rfc3339();
+      }
+      blob.content_type = content_type;
+      blob.acl = acl;
+      blobs.push((key, blob));
+    }
+
+    // fail before loading anything if any blob would overwrite a locked one
+    let mut storage = self.storage.write().await;
+    let now = self.clock.now();
+    for (key, _) in &blobs {
+      check_unlocked(&storage, &BlobKey::new(key.as_str()), now)?;
+    }
+    let count = blobs.len();
+    storage.extend(blobs);
+    info!(blob_count = count, "Imported blobs from tarball");
+    Ok(count)
+  }
+}
@@ -1,16 +1,24 @@
 //! In-memory implementation of the blob storage interface.
 
+mod archive;
+
 use std::{
-  collections::HashMap,
-  sync::Arc,
-  time::{SystemTime, UNIX_EPOCH},
+  collections::{BTreeMap, HashMap},
+  ops::Range,
+  sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+  },
 };
 
 use bytes::Bytes;
+use chrono::{DateTime, SubsecRound, Utc};
+use clock::{Clock, LatencyProfile, SystemClock};
 use futures::{TryStreamExt, stream};
 use storage_core::{
-  BlobKey, BlobMetadata, BlobStorageError, BlobStorageLike, BlobStorageResult,
-  RequestStream, ResponseStream, UploadOptions,
+  BlobEntry, BlobKey, BlobListPage, BlobMetadata, BlobStorageError,
+  BlobStorageLike, BlobStorageResult, CannedAcl, LIST_PAGE_SIZE, ObjectLock,
+  RequestStream, ResponseStream, UploadHandle, UploadOptions, UploadedPart,
 };
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, instrument, warn};
@@ -24,12 +32,19 @@ struct StoredBlob {
   etag:          String,
   /// Last modified timestamp
   last_modified: String,
+  /// Emulated object lock state
+  lock:          ObjectLock,
+  /// The media type given on upload
+  content_type:  Option<String>,
+  /// The canned ACL given on upload
+  acl:           Option<CannedAcl>,
 }
 
 impl StoredBlob {
-  fn new(data: Bytes) -> Self {
+  fn new(data: Bytes, clock: &dyn Clock) -> Self {
     let etag = format!("{:x}", md5::compute(&data));
-    let last_modified = Self::current_timestamp();
+    // ISO 8601 format
+    let last_modified = clock.now().trunc_subsecs(0).to_rfc3339();
 
     debug!(
       data_size = data.len(),
@@ -41,36 +56,70 @@ impl StoredBlob {
       data,
       etag,
       last_modified,
+      lock: ObjectLock::default(),
+      content_type: None,
+      acl: None,
     }
   }
 
-  fn current_timestamp() -> String {
-    let now = SystemTime::now()
-      .duration_since(UNIX_EPOCH)
-      .unwrap()
-      .as_secs();
-    // ISO 8601 format
-    chrono::DateTime::from_timestamp(i64::try_from(now).unwrap(), 0)
-      .unwrap()
-      .to_rfc3339()
-  }
-
   fn metadata(&self) -> BlobMetadata {
     BlobMetadata {
       size:          self.data.len() as u64,
       etag:          Some(self.etag.clone()),
       last_modified: Some(self.last_modified.clone()),
+      content_type:  self.content_type.clone(),
+      acl:           self.acl,
     }
   }
 }
 
+/// Internal representation of an in-progress resumable upload
+#[derive(Debug)]
+struct PendingUpload {
+  /// The key the upload targets
+  key:          BlobKey,
+  /// The uploaded parts, by part number
+  parts:        BTreeMap<u32, StoredBlob>,
+  /// The media type of the completed blob
+  content_type: Option<String>,
+  /// The canned ACL of the completed blob
+  acl:          Option<CannedAcl>,
+}
+
 /// In-memory implementation of [`BlobStorageLike`].
 ///
 /// This implementation stores all blobs in memory and is useful for
 /// testing and development. All data is lost when the instance is dropped.
 #[derive(Debug, Clone)]
 pub struct BlobStorageMemory {
-  storage: Arc<RwLock<HashMap<String, StoredBlob>>>,
+  storage:        Arc<RwLock<HashMap<String, StoredBlob>>>,
+  uploads:        Arc<RwLock<HashMap<String, PendingUpload>>>,
+  next_upload_id: Arc<AtomicU64>,
+  clock:          Arc<dyn Clock>,
+  latency:        LatencyProfile<MemoryOperation>,
+}
+
+/// The kinds of operation a [`BlobStorageMemory`] can be given a latency
+/// for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryOperation {
+  /// Uploading a blob in one request.
+  Put,
+  /// Downloading a blob.
+  Get,
+  /// Fetching a blob's metadata.
+  Head,
+  /// Deleting one or many blobs.
+  Delete,
+  /// Listing blobs.
+  List,
+  /// Generating a pre-signed URL.
+  Presign,
+  /// Creating, uploading a part of, completing or aborting a resumable
+  /// upload.
+  Upload,
+  /// Setting a blob's retention or legal hold.
+  Lock,
 }
 
 impl BlobStorageMemory {
@@ -79,8 +128,62 @@ impl BlobStorageMemory {
   pub fn new() -> Self {
     info!("Creating new in-memory blob storage");
     Self {
-      storage: Arc::new(RwLock::new(HashMap::new())),
+      storage:        Arc::new(RwLock::new(HashMap::new())),
+      uploads:        Arc::new(RwLock::new(HashMap::new())),
+      next_upload_id: Arc::new(AtomicU64::new(1)),
+      clock:          SystemClock::shared(),
+      latency:        LatencyProfile::new(),
+    }
+  }
+
+  /// Sets the [`Clock`] used for last-modified timestamps.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  /// Sets the [`LatencyProfile`] simulating how long operations take.
+  ///
+  /// The delay comes before the operation takes effect, so an operation
+  /// abandoned by a timeout has no effect. Latency isn't simulated on
+  /// wasm32, which has no timer without a runtime.
+  #[must_use]
+  pub fn with_latency(
+    mut self,
+    latency: LatencyProfile<MemoryOperation>,
+  ) -> Self {
+    self.latency = latency;
+    self
+  }
+
+  /// Wait out the simulated latency of `operation`.
+  #[cfg_attr(target_arch = "wasm32", allow(clippy::unused_async))]
+  async fn simulate_latency(&self, operation: MemoryOperation) {
+    let latency = self.latency.sample(&operation);
+    // wasm32 has no timer without a runtime, so latency isn't simulated there
+    #[cfg(not(target_arch = "wasm32"))]
+    if !latency.is_zero() {
+      tokio::time::sleep(latency).await;
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = latency;
+  }
+}
+
+/// Fails with [`BlobStorageError::Locked`] if the blob stored at `key` can't
+/// be deleted or overwritten at `now`.
+fn check_unlocked(
+  storage: &HashMap<String, StoredBlob>,
+  key: &BlobKey,
+  now: DateTime<Utc>,
+) -> BlobStorageResult<()> {
+  match storage.get(key.as_str()) {
+    Some(blob) if blob.lock.is_locked(now) => {
+      warn!(key = %key, "Blob is locked");
+      Err(BlobStorageError::Locked(key.clone()))
     }
+    _ => Ok(()),
   }
 }
 
@@ -88,7 +191,8 @@ impl Default for BlobStorageMemory {
   fn default() -> Self { Self::new() }
 }
 
-#[async_trait::async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
 impl BlobStorageLike for BlobStorageMemory {
   #[instrument(
     skip(self, data),
@@ -104,6 +208,7 @@ impl BlobStorageLike for BlobStorageMemory {
     data: RequestStream,
     options: UploadOptions,
   ) -> BlobStorageResult<()> {
+    self.simulate_latency(MemoryOperation::Put).await;
     debug!("Starting stream upload");
 
     // Check if we should overwrite
@@ -136,14 +241,14 @@ impl BlobStorageLike for BlobStorageMemory {
 
     debug!(total_size = total_size, "Combined chunks into single blob");
 
-    let blob = StoredBlob::new(Bytes::from(combined));
+    let mut blob = StoredBlob::new(Bytes::from(combined), self.clock.as_ref());
+    blob.content_type = options.content_type;
+    blob.acl = options.acl;
 
     // Store the blob
-    self
-      .storage
-      .write()
-      .await
-      .insert(key.as_str().to_string(), blob);
+    let mut storage = self.storage.write().await;
+    check_unlocked(&storage, key, self.clock.now())?;
+    storage.insert(key.as_str().to_string(), blob);
 
     info!(
       size = total_size,
@@ -163,6 +268,7 @@ impl BlobStorageLike for BlobStorageMemory {
     &self,
     key: &BlobKey,
   ) -> BlobStorageResult<ResponseStream> {
+    self.simulate_latency(MemoryOperation::Get).await;
     debug!("Retrieving blob stream");
 
     let storage = self.storage.read().await;
@@ -185,6 +291,35 @@ impl BlobStorageLike for BlobStorageMemory {
     Ok(Box::pin(stream))
   }
 
+  #[instrument(
+    skip(self),
+    fields(key = %key, start = range.start, end = range.end),
+    err
+  )]
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    self.simulate_latency(MemoryOperation::Get).await;
+    debug!("Retrieving blob range stream");
+
+    let storage = self.storage.read().await;
+    let blob = storage.get(key.as_str()).ok_or_else(|| {
+      error!("Blob not found");
+      BlobStorageError::NotFound(key.clone())
+    })?;
+
+    let len = blob.data.len();
+    let clamp = |offset: u64| usize::try_from(offset).unwrap_or(len).min(len);
+    let start = clamp(range.start);
+    let data = blob.data.slice(start..clamp(range.end).max(start));
+
+    debug!(size = data.len(), "Retrieved blob range data");
+
+    Ok(Box::pin(stream::once(async move { Ok(data) })))
+  }
+
   #[instrument(
     skip(self),
     fields(key = %key),
@@ -194,6 +329,7 @@ impl BlobStorageLike for BlobStorageMemory {
     &self,
     key: &BlobKey,
   ) -> BlobStorageResult<Option<BlobMetadata>> {
+    self.simulate_latency(MemoryOperation::Head).await;
     debug!("Fetching blob metadata");
 
     let storage = self.storage.read().await;
@@ -218,9 +354,11 @@ impl BlobStorageLike for BlobStorageMemory {
     err
   )]
   async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    self.simulate_latency(MemoryOperation::Delete).await;
     debug!("Deleting blob");
 
     let mut storage = self.storage.write().await;
+    check_unlocked(&storage, key, self.clock.now())?;
     let blob = storage.remove(key.as_str()).ok_or_else(|| {
       error!("Blob not found");
       BlobStorageError::NotFound(key.clone())
@@ -231,6 +369,68 @@ impl BlobStorageLike for BlobStorageMemory {
     Ok(())
   }
 
+  #[instrument(skip(self, keys), fields(key_count = keys.len()))]
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    self.simulate_latency(MemoryOperation::Delete).await;
+    let mut storage = self.storage.write().await;
+    let now = self.clock.now();
+    let results: Vec<_> = keys
+      .iter()
+      .map(|key| {
+        let result = check_unlocked(&storage, key, now).and_then(|()| {
+          storage
+            .remove(key.as_str())
+            .map(|_| ())
+            .ok_or_else(|| BlobStorageError::NotFound(key.clone()))
+        });
+        (key.clone(), result)
+      })
+      .collect();
+
+    info!(
+      deleted = results.iter().filter(|(_, r)| r.is_ok()).count(),
+      "Blobs deleted"
+    );
+    results
+  }
+
+  #[instrument(skip(self), err)]
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    self.simulate_latency(MemoryOperation::List).await;
+    let storage = self.storage.read().await;
+    let mut entries: Vec<BlobEntry> = storage
+      .iter()
+      .filter(|(key, _)| key.starts_with(prefix))
+      .filter(|(key, _)| {
+        continuation
+          .as_deref()
+          .is_none_or(|after| key.as_str() > after)
+      })
+      .map(|(key, blob)| BlobEntry {
+        key:      BlobKey::new(key.clone()),
+        metadata: blob.metadata(),
+      })
+      .collect();
+    entries.sort_unstable_by(|a, b| a.key.as_str().cmp(b.key.as_str()));
+
+    let continuation = (entries.len() > LIST_PAGE_SIZE)
+      .then(|| entries[LIST_PAGE_SIZE - 1].key.as_str().to_owned());
+    entries.truncate(LIST_PAGE_SIZE);
+
+    debug!(entry_count = entries.len(), "Listed blobs");
+    Ok(BlobListPage {
+      entries,
+      continuation,
+    })
+  }
+
   #[instrument(
     skip(self),
     fields(
@@ -244,6 +444,7 @@ impl BlobStorageLike for BlobStorageMemory {
     key: &BlobKey,
     expiry: std::time::Duration,
   ) -> BlobStorageResult<String> {
+    self.simulate_latency(MemoryOperation::Presign).await;
     let expiry_secs = expiry.as_secs();
     debug!(expiry_secs = expiry_secs, "Generating presigned URL");
 
@@ -282,6 +483,220 @@ impl BlobStorageLike for BlobStorageMemory {
 
     Ok(url)
   }
+
+  #[instrument(
+    skip(self),
+    fields(
+      key = %key,
+      overwrite = options.overwrite,
+    ),
+    err
+  )]
+  async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadHandle> {
+    self.simulate_latency(MemoryOperation::Upload).await;
+    if !options.overwrite
+      && self.storage.read().await.contains_key(key.as_str())
+    {
+      warn!("Blob already exists and overwrite=false");
+      return Err(BlobStorageError::AlreadyExists(key.clone()));
+    }
+
+    let upload_id = self
+      .next_upload_id
+      .fetch_add(1, Ordering::Relaxed)
+      .to_string();
+    self
+      .uploads
+      .write()
+      .await
+      .insert(upload_id.clone(), PendingUpload {
+        key:          key.clone(),
+        parts:        BTreeMap::new(),
+        content_type: options.content_type,
+        acl:          options.acl,
+      });
+
+    info!(upload_id = %upload_id, "Resumable upload created");
+
+    Ok(UploadHandle {
+      key: key.clone(),
+      upload_id,
+      overwrite: options.overwrite,
+    })
+  }
+
+  #[instrument(
+    skip(self, handle, data),
+    fields(
+      key = %handle.key,
+      upload_id = %handle.upload_id,
+      size = data.len(),
+    ),
+    err
+  )]
+  async fn upload_part(
+    &self,
+    handle: &UploadHandle,
+    part_number: u32,
+    data: Bytes,
+  ) -> BlobStorageResult<UploadedPart> {
+    self.simulate_latency(MemoryOperation::Upload).await;
+    if part_number == 0 {
+      return Err(BlobStorageError::InvalidInput(miette::miette!(
+        "Part numbers start at 1"
+      )));
+    }
+
+    let mut uploads = self.uploads.write().await;
+    let upload = uploads
+      .get_mut(&handle.upload_id)
+      .filter(|u| u.key == handle.key)
+      .ok_or_else(|| {
+        error!("Upload not found");
+        BlobStorageError::NotFound(handle.key.clone())
+      })?;
+
+    let part = StoredBlob::new(data, self.clock.as_ref());
+    let uploaded = UploadedPart {
+      part_number,
+      size: part.data.len() as u64,
+      etag: part.etag.clone(),
+    };
+    upload.parts.insert(part_number, part);
+
+    debug!("Part uploaded successfully");
+
+    Ok(uploaded)
+  }
+
+  #[instrument(
+    skip(self, handle, parts),
+    fields(
+      key = %handle.key,
+      upload_id = %handle.upload_id,
+      part_count = parts.len(),
+    ),
+    err
+  )]
+  async fn complete_upload(
+    &self,
+    handle: &UploadHandle,
+    parts: &[UploadedPart],
+  ) -> BlobStorageResult<()> {
+    self.simulate_latency(MemoryOperation::Upload).await;
+    let mut uploads = self.uploads.write().await;
+    let upload = uploads
+      .get(&handle.upload_id)
+      .filter(|u| u.key == handle.key)
+      .ok_or_else(|| {
+        error!("Upload not found");
+        BlobStorageError::NotFound(handle.key.clone())
+      })?;
+
+    let mut combined = Vec::new();
+    let mut previous = 0;
+    for part in parts {
+      if part.part_number <= previous {
+        return Err(BlobStorageError::InvalidInput(miette::miette!(
+          "Parts must be in ascending order by part number"
+        )));
+      }
+      previous = part.part_number;
+
+      let stored = upload
+        .parts
+        .get(&part.part_number)
+        .filter(|p| p.etag == part.etag)
+        .ok_or_else(|| {
+          BlobStorageError::InvalidInput(miette::miette!(
+            "Part {} was not uploaded or has changed",
+            part.part_number
+          ))
+        })?;
+      combined.extend_from_slice(&stored.data);
+    }
+
+    let mut storage = self.storage.write().await;
+    if !handle.overwrite && storage.contains_key(handle.key.as_str()) {
+      warn!("Blob already exists and overwrite=false");
+      return Err(BlobStorageError::AlreadyExists(handle.key.clone()));
+    }
+    check_unlocked(&storage, &handle.key, self.clock.now())?;
+
+    let total_size = combined.len();
+    let mut blob = StoredBlob::new(Bytes::from(combined), self.clock.as_ref());
+    blob.content_type.clone_from(&upload.content_type);
+    blob.acl = upload.acl;
+    storage.insert(handle.key.as_str().to_string(), blob);
+    uploads.remove(&handle.upload_id);
+
+    info!(size = total_size, "Resumable upload completed successfully");
+
+    Ok(())
+  }
+
+  #[instrument(
+    skip(self, handle),
+    fields(
+      key = %handle.key,
+      upload_id = %handle.upload_id,
+    ),
+    err
+  )]
+  async fn abort_upload(&self, handle: &UploadHandle) -> BlobStorageResult<()> {
+    self.simulate_latency(MemoryOperation::Upload).await;
+    self
+      .uploads
+      .write()
+      .await
+      .remove(&handle.upload_id)
+      .ok_or_else(|| {
+        error!("Upload not found");
+        BlobStorageError::NotFound(handle.key.clone())
+      })?;
+
+    info!("Resumable upload aborted");
+
+    Ok(())
+  }
+
+  #[instrument(skip(self), fields(key = %key), err)]
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self.simulate_latency(MemoryOperation::Lock).await;
+    let mut storage = self.storage.write().await;
+    let blob = storage
+      .get_mut(key.as_str())
+      .ok_or_else(|| BlobStorageError::NotFound(key.clone()))?;
+    blob.lock.retain(key, until)?;
+
+    info!("Blob retention set");
+    Ok(())
+  }
+
+  #[instrument(skip(self), fields(key = %key), err)]
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self.simulate_latency(MemoryOperation::Lock).await;
+    let mut storage = self.storage.write().await;
+    let blob = storage
+      .get_mut(key.as_str())
+      .ok_or_else(|| BlobStorageError::NotFound(key.clone()))?;
+    blob.lock.legal_hold = hold;
+
+    info!("Blob legal hold set");
+    Ok(())
+  }
 }
 
 #[cfg(test)]
@@ -303,7 +718,10 @@ mod tests {
     }));
 
     storage
-      .put_stream(&key, stream, UploadOptions { overwrite: true })
+      .put_stream(&key, stream, UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
       .await
       .unwrap();
 
@@ -325,7 +743,10 @@ mod tests {
       async move { Ok(data.clone()) }
     }));
     storage
-      .put_stream(&key, stream, UploadOptions { overwrite: true })
+      .put_stream(&key, stream, UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
       .await
       .unwrap();
 
@@ -360,14 +781,20 @@ mod tests {
     // First upload should succeed
     let stream1 = Box::pin(stream::once(async move { Ok(data1) }));
     storage
-      .put_stream(&key, stream1, UploadOptions { overwrite: false })
+      .put_stream(&key, stream1, UploadOptions {
+        overwrite: false,
+        ..UploadOptions::default()
+      })
       .await
       .unwrap();
 
     // Second upload with overwrite=false should fail
     let stream2 = Box::pin(stream::once(async move { Ok(data2) }));
     let result = storage
-      .put_stream(&key, stream2, UploadOptions { overwrite: false })
+      .put_stream(&key, stream2, UploadOptions {
+        overwrite: false,
+        ..UploadOptions::default()
+      })
       .await;
 
     assert!(matches!(result, Err(BlobStorageError::AlreadyExists(_))));
@@ -388,7 +815,7 @@ mod tests {
 
     // Valid duration should succeed
     let result = storage
-      .get_presigned_url(&key, std::time::Duration::from_secs(3600))
+      .get_presigned_url(&key, std::time::Duration::from_hours(1))
       .await;
     assert!(result.is_ok());
 
@@ -398,4 +825,131 @@ mod tests {
       .await;
     assert!(matches!(result, Err(BlobStorageError::InvalidInput(_))));
   }
+
+  #[tokio::test]
+  async fn test_latency_delays_operations() {
+    let slow = clock::Latency::Fixed(std::time::Duration::from_mins(1));
+    let storage = BlobStorageMemory::new()
+      .with_latency(LatencyProfile::new().with(MemoryOperation::Put, slow));
+    let key = BlobKey::new("test-key");
+
+    let wait = std::time::Duration::from_millis(50);
+    let stream = Box::pin(stream::once(async { Ok(Bytes::from("test")) }));
+    let put = storage.put_stream(&key, stream, UploadOptions::default());
+    assert!(tokio::time::timeout(wait, put).await.is_err());
+
+    let head = tokio::time::timeout(wait, storage.head(&key)).await;
+    assert!(head.unwrap().unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn test_last_modified_uses_clock() {
+    let clock = clock::ManualClock::default();
+    let storage = BlobStorageMemory::new().with_clock(Arc::new(clock.clone()));
+    let key = BlobKey::new("test-key");
+
+    clock.advance(std::time::Duration::from_mins(1));
+    let stream = Box::pin(stream::once(async { Ok(Bytes::from("test")) }));
+    storage
+      .put_stream(&key, stream, UploadOptions::default())
+      .await
+      .unwrap();
+
+    let metadata = storage.head(&key).await.unwrap().unwrap();
+    assert_eq!(
+      metadata.last_modified.as_deref(),
+      Some("1970-01-01T00:01:00+00:00")
+    );
+  }
+
+  #[tokio::test]
+  async fn test_stats_oldest_uses_clock() {
+    let clock = clock::ManualClock::default();
+    let storage = BlobStorageMemory::new().with_clock(Arc::new(clock.clone()));
+
+    for key in ["b", "a", "c"] {
+      clock.advance(std::time::Duration::from_mins(1));
+      let stream =
+        Box::pin(stream::once(async move { Ok(Bytes::from(key)) }));
+      storage
+        .put_stream(&BlobKey::new(key), stream, UploadOptions::default())
+        .await
+        .unwrap();
+    }
+
+    let stats = storage.stats("").await.unwrap();
+    assert_eq!(stats.object_count, 3);
+    assert_eq!(stats.total_bytes, 3);
+    assert_eq!(stats.oldest.unwrap().key.as_str(), "b");
+  }
+
+  #[tokio::test]
+  async fn test_tar_roundtrip() {
+    let storage = BlobStorageMemory::new();
+    for (key, content_type) in
+      [("b/two.txt", None), ("a.png", Some("image/png"))]
+    {
+      storage
+        .put_stream(
+          &BlobKey::new(key),
+          Box::pin(stream::once(async move { Ok(Bytes::from(key)) })),
+          UploadOptions {
+            content_type: content_type.map(str::to_owned),
+            acl: Some(CannedAcl::PublicRead),
+            ..UploadOptions::default()
+          },
+        )
+        .await
+        .unwrap();
+    }
+    let tarball = storage.export_tar(Vec::new()).await.unwrap();
+
+    let loaded = BlobStorageMemory::new();
+    assert_eq!(loaded.import_tar(tarball.as_slice()).await.unwrap(), 2);
+    for key in ["a.png", "b/two.txt"] {
+      let key = BlobKey::new(key);
+      let original = storage.head(&key).await.unwrap().unwrap();
+      let metadata = loaded.head(&key).await.unwrap().unwrap();
+      assert_eq!(metadata.etag, original.etag);
+      assert_eq!(metadata.last_modified, original.last_modified);
+      assert_eq!(metadata.content_type, original.content_type);
+      assert_eq!(metadata.acl, Some(CannedAcl::PublicRead));
+      let mut data = loaded.get_stream(&key).await.unwrap();
+      assert_eq!(data.next().await.unwrap().unwrap(), key.as_str());
+    }
+  }
+
+  #[tokio::test]
+  async fn test_import_tar_respects_locks() {
+    let storage = BlobStorageMemory::new();
+    for key in ["held.txt", "other.txt"] {
+      storage
+        .put_stream(
+          &BlobKey::new(key),
+          Box::pin(stream::once(async { Ok(Bytes::from_static(b"old")) })),
+          UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+    }
+    let tarball = storage.export_tar(Vec::new()).await.unwrap();
+
+    let loaded = BlobStorageMemory::new();
+    let held = BlobKey::new("held.txt");
+    loaded
+      .put_stream(
+        &held,
+        Box::pin(stream::once(async { Ok(Bytes::from_static(b"kept")) })),
+        UploadOptions::default(),
+      )
+      .await
+      .unwrap();
+    loaded.set_legal_hold(&held, true).await.unwrap();
+
+    let result = loaded.import_tar(tarball.as_slice()).await;
+    assert!(matches!(result, Err(BlobStorageError::Locked(_))));
+    let mut data = loaded.get_stream(&held).await.unwrap();
+    assert_eq!(data.next().await.unwrap().unwrap(), "kept");
+    assert!(loaded.head(&"other.txt".into()).await.unwrap().is_none());
+  }
 }
@@ -0,0 +1,229 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  fmt::Write,
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::Utc;
+use miette::{IntoDiagnostic, miette};
+use reqwest::Url;
+use storage_core::{
+  BlobKey, BlobStorageError, BlobStorageResult, HttpErrorDetails,
+};
+use tracing::{debug, error};
+
+use crate::{
+  BlobStorageS3,
+  errors::{http_failure_to_blob_storage_error, xml_tag},
+  sigv4::RequestSigner,
+  telemetry::trace_context_headers,
+};
+
+/// The XML namespace of S3 request bodies.
+pub(crate) const S3_XML_NAMESPACE: &str =
+  "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// The maximum number of keys S3 deletes in one `DeleteObjects` request.
+pub(crate) const DELETE_OBJECTS_MAX_KEYS: usize = 1000;
+
+/// Escapes `text` for use as XML character data.
+fn xml_escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&apos;")
+}
+
+/// Reverses [`xml_escape`].
+fn xml_unescape(text: &str) -> String {
+  text
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+/// The body of a quiet `DeleteObjects` request for `keys`, to which S3 only
+/// reports the keys it failed to delete.
+pub(crate) fn delete_objects_body(keys: &[BlobKey]) -> String {
+  let objects = keys.iter().fold(String::new(), |mut objects, key| {
+    let _ = write!(
+      objects,
+      "<Object><Key>{}</Key></Object>",
+      xml_escape(key.as_str())
+    );
+    objects
+  });
+  format!(
+    "<Delete xmlns=\"{S3_XML_NAMESPACE}\"><Quiet>true</Quiet>{objects}</\
+     Delete>"
+  )
+}
+
+/// The keys which failed to delete in a `DeleteObjects` response, with their
+/// errors.
+///
+/// Failures look like `<Error><Key>...</Key><Code>AccessDenied</Code>
+/// <Message>...</Message></Error>`.
+pub(crate) fn delete_objects_errors(
+  body: &str,
+) -> HashMap<String, BlobStorageError> {
+  body
+    .split("<Error>")
+    .skip(1)
+    .filter_map(|error| {
+      let error = &error[..error.find("</Error>")?];
+      let key = xml_unescape(xml_tag(error, "Key")?);
+      let code = xml_tag(error, "Code").unwrap_or("InternalError");
+      let message = xml_tag(error, "Message").unwrap_or("no error message");
+      let report = miette!("S3 failed to delete {key}: {code}: {message}");
+      let details = Some(HttpErrorDetails {
+        code: Some(code.to_owned()),
+        ..HttpErrorDetails::default()
+      });
+      let error = if code == "AccessDenied" {
+        BlobStorageError::PermissionDenied { report, details }
+      } else {
+        BlobStorageError::NetworkError { report, details }
+      };
+      Some((key, error))
+    })
+    .collect()
+}
+
+/// A copy of the error failing a whole `DeleteObjects` request, for each of
+/// its keys.
+pub(crate) fn batch_error(error: &BlobStorageError) -> BlobStorageError {
+  match error {
+    BlobStorageError::PermissionDenied { report, details } => {
+      BlobStorageError::PermissionDenied {
+        report:  miette!("{report}"),
+        details: details.clone(),
+      }
+    }
+    BlobStorageError::NetworkError { report, details } => {
+      BlobStorageError::NetworkError {
+        report:  miette!("{report}"),
+        details: details.clone(),
+      }
+    }
+    BlobStorageError::InvalidConfig(report) => {
+      BlobStorageError::InvalidConfig(miette!("{report}"))
+    }
+    BlobStorageError::InvalidInput(report) => {
+      BlobStorageError::InvalidInput(miette!("{report}"))
+    }
+    error => BlobStorageError::Unknown(miette!("{error}")),
+  }
+}
+
+impl BlobStorageS3 {
+  /// Deletes up to [`DELETE_OBJECTS_MAX_KEYS`] blobs in one `DeleteObjects`
+  /// request, returning the errors of the keys which failed.
+  ///
+  /// `rust-s3` has no `DeleteObjects` API, so the request is signed with the
+  /// storage's credentials and sent directly. S3 requires these requests to
+  /// carry a `Content-MD5` header.
+  pub(crate) async fn delete_objects(
+    &self,
+    keys: &[BlobKey],
+  ) -> BlobStorageResult<HashMap<String, BlobStorageError>> {
+    let (Some(access_key), Some(secret_key)) =
+      (&self.credentials.access_key, &self.credentials.secret_key)
+    else {
+      return Err(BlobStorageError::InvalidConfig(miette!(
+        "batch deletes need an access key and a secret key"
+      )));
+    };
+    let url = Url::parse(&format!("{}/?delete", self.bucket.url()))
+      .into_diagnostic()
+      .map_err(BlobStorageError::InvalidConfig)?;
+
+    let body = delete_objects_body(keys);
+    let content_md5 = BASE64.encode(md5::compute(body.as_bytes()).0);
+    let signer = RequestSigner {
+      region: &self.region,
+      access_key,
+      secret_key,
+      security_token: self.credentials.security_token.as_deref(),
+    };
+    let headers = signer.sign(
+      "POST",
+      &url,
+      BTreeMap::from([("content-md5".to_owned(), content_md5)]),
+      body.as_bytes(),
+      Utc::now(),
+    );
+
+    let request = headers
+      .iter()
+      .fold(self.http.post(url), |request, (name, value)| {
+        request.header(name, value)
+      })
+      .headers(trace_context_headers())
+      .body(body);
+    debug!(keys = keys.len(), "Sending batch delete request");
+    let response = request
+      .send()
+      .await
+      .into_diagnostic()
+      .map_err(BlobStorageError::network)?;
+
+    let status = response.status().as_u16();
+    let body = response.text().await.unwrap_or_default();
+    if !(200..300).contains(&status) {
+      error!(status, "Batch delete request failed");
+      return Err(http_failure_to_blob_storage_error(status, &body));
+    }
+    Ok(delete_objects_errors(&body))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_delete_objects_body() {
+    let body =
+      delete_objects_body(&[BlobKey::new("a.txt"), BlobKey::new("b&<c>.txt")]);
+
+    assert!(body.contains("<Quiet>true</Quiet>"));
+    assert!(body.contains("<Object><Key>a.txt</Key></Object>"));
+    assert!(body.contains("<Object><Key>b&amp;&lt;c&gt;.txt</Key></Object>"));
+  }
+
+  #[test]
+  fn test_delete_objects_errors() {
+    let body = "<?xml version=\"1.0\" encoding=\"UTF-8\"?><DeleteResult \
+                xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Error>\
+                <Key>b&amp;c.txt</Key><Code>AccessDenied</Code><Message>Access \
+                Denied</Message></Error><Error><Key>d.txt</Key>\
+                <Code>InternalError</Code><Message>We encountered an internal \
+                error.</Message></Error></DeleteResult>";
+    let errors = delete_objects_errors(body);
+
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+      errors["b&c.txt"],
+      BlobStorageError::PermissionDenied { .. }
+    ));
+    assert!(matches!(
+      &errors["d.txt"],
+      BlobStorageError::NetworkError { details: Some(details), .. }
+        if details.code.as_deref() == Some("InternalError")
+    ));
+  }
+
+  #[test]
+  fn test_delete_objects_without_errors() {
+    let body = "<DeleteResult \
+                xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"></\
+                DeleteResult>";
+
+    assert!(delete_objects_errors(body).is_empty());
+  }
+}
@@ -1,6 +1,54 @@
 use miette::Report;
 use s3::error::S3Error;
-use storage_core::BlobStorageError;
+use storage_core::{BlobStorageError, HttpErrorDetails};
+
+/// Extracts the text of the first `<tag>` element in an XML document.
+pub(crate) fn xml_tag<'a>(body: &'a str, tag: &str) -> Option<&'a str> {
+  let open = format!("<{tag}>");
+  let close = format!("</{tag}>");
+  let start = body.find(&open)? + open.len();
+  let len = body[start..].find(&close)?;
+  Some(body[start..start + len].trim()).filter(|s| !s.is_empty())
+}
+
+/// Parses the details of an S3 error response.
+///
+/// S3 error bodies look like `<Error><Code>AccessDenied</Code>
+/// <Message>...</Message><RequestId>...</RequestId><HostId>...</HostId>
+/// </Error>`, where the host ID is the extended request ID.
+pub(crate) fn parse_error_details(status: u16, body: &str) -> HttpErrorDetails {
+  HttpErrorDetails {
+    status:              Some(status),
+    code:                xml_tag(body, "Code").map(ToOwned::to_owned),
+    request_id:          xml_tag(body, "RequestId").map(ToOwned::to_owned),
+    extended_request_id: xml_tag(body, "HostId").map(ToOwned::to_owned),
+  }
+}
+
+/// Maps a failed S3 response to an error, keeping the parsed details.
+pub(crate) fn http_failure_to_blob_storage_error(
+  status: u16,
+  body: &str,
+) -> BlobStorageError {
+  let details = parse_error_details(status, body);
+  let message = xml_tag(body, "Message").unwrap_or("no error message");
+  let report = match &details.code {
+    Some(code) => miette::miette!("S3 returned {status} {code}: {message}"),
+    None => miette::miette!("S3 returned {status}: {message}"),
+  };
+
+  if status == 403 {
+    BlobStorageError::PermissionDenied {
+      report,
+      details: Some(details),
+    }
+  } else {
+    BlobStorageError::NetworkError {
+      report,
+      details: Some(details),
+    }
+  }
+}
 
 pub(crate) fn s3_error_to_blob_storage_error(err: S3Error) -> BlobStorageError {
   match err {
@@ -14,18 +62,19 @@ pub(crate) fn s3_error_to_blob_storage_error(err: S3Error) -> BlobStorageError {
     }
 
     // potentially retryable network error
-    e @ (S3Error::HttpFailWithBody(_, _) | S3Error::HttpFail) => {
-      BlobStorageError::NetworkError(
-        Report::from_err(e).context("HTTP response code error"),
-      )
+    S3Error::HttpFailWithBody(status, body) => {
+      http_failure_to_blob_storage_error(status, &body)
     }
+    e @ S3Error::HttpFail => BlobStorageError::network(
+      Report::from_err(e).context("HTTP response code error"),
+    ),
     // probably unrecoverable network error
     e @ (S3Error::Http(_)
     | S3Error::Reqwest(_)
     | S3Error::ReqwestHeaderToStr(_)
     | S3Error::InvalidHeaderValue(_)
     | S3Error::InvalidHeaderName(_)) => {
-      BlobStorageError::NetworkError(Report::from_err(e))
+      BlobStorageError::network(Report::from_err(e))
     }
 
     // various data and serialization errors
@@ -57,3 +106,56 @@ pub(crate) fn s3_error_to_blob_storage_error(err: S3Error) -> BlobStorageError {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_http_failure_details() {
+    let body =
+      "<?xml version=\"1.0\" \
+       encoding=\"UTF-8\"?>\n<Error><Code>AccessDenied</Code><Message>Access \
+       Denied</Message><RequestId>4442587FB7D0A2F9</\
+       RequestId><HostId>vlR7PnpV2Ce81l0PRw6jlUpck7Jo5ZsQ=</HostId></Error>";
+    let error = s3_error_to_blob_storage_error(S3Error::HttpFailWithBody(
+      403,
+      body.into(),
+    ));
+
+    assert!(matches!(error, BlobStorageError::PermissionDenied { .. }));
+    assert_eq!(
+      error.http_details(),
+      Some(&HttpErrorDetails {
+        status:              Some(403),
+        code:                Some("AccessDenied".to_owned()),
+        request_id:          Some("4442587FB7D0A2F9".to_owned()),
+        extended_request_id: Some(
+          "vlR7PnpV2Ce81l0PRw6jlUpck7Jo5ZsQ=".to_owned()
+        ),
+      })
+    );
+    assert!(
+      error
+        .to_string()
+        .contains("403 AccessDenied: Access Denied")
+    );
+  }
+
+  #[test]
+  fn test_http_failure_without_body() {
+    let error = s3_error_to_blob_storage_error(S3Error::HttpFailWithBody(
+      503,
+      String::new(),
+    ));
+
+    assert!(matches!(error, BlobStorageError::NetworkError { .. }));
+    assert_eq!(
+      error.http_details(),
+      Some(&HttpErrorDetails {
+        status: Some(503),
+        ..HttpErrorDetails::default()
+      })
+    );
+  }
+}
@@ -1,23 +1,64 @@
 //! An implementation of the storage interface for S3 compatible stores.
 
+mod delete_objects;
 mod errors;
+mod object_lock;
+mod post_policy;
+mod sigv4;
+mod telemetry;
 
+use std::ops::Range;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use miette::{Context, IntoDiagnostic, miette};
-use s3::{Bucket, creds::Credentials};
+use reqwest::header::{HeaderMap, HeaderValue, RANGE};
+use s3::{Bucket, creds::Credentials, serde_types::Part};
 use storage_core::{
-  BlobKey, BlobMetadata, BlobStorageError, BlobStorageLike, BlobStorageResult,
-  RequestStream, ResponseStream, UploadOptions,
+  BlobEntry, BlobKey, BlobListPage, BlobMetadata, BlobStorageError,
+  BlobStorageLike, BlobStorageResult, Bytes, Checksum, HttpErrorDetails,
+  LIST_PAGE_SIZE, PostCondition, PresignedPost, RequestStream, ResponseStream,
+  ServerSideEncryption, UploadHandle, UploadOptions, UploadedPart,
 };
 use tokio_util::io::StreamReader;
-use tracing::{debug, error, info, instrument, warn};
+use tracing::{Span, debug, error, info, instrument, warn};
+
+use self::{
+  delete_objects::{DELETE_OBJECTS_MAX_KEYS, batch_error},
+  errors::s3_error_to_blob_storage_error,
+  object_lock::{legal_hold_body, retention_body},
+  post_policy::PolicySigner,
+  telemetry::trace_context_headers,
+};
 
-use self::errors::s3_error_to_blob_storage_error;
+/// The content type of multipart upload parts, and of uploads not given one.
+const MULTIPART_CONTENT_TYPE: &str = "application/octet-stream";
 
 /// [`BlobStorageLike`] implementer for S3-compatible backends.
+///
+/// Resumable uploads use S3 multipart uploads, so every part except the last
+/// must be at least 5 MiB. Upload checksums are only sent with
+/// [`put_stream`](BlobStorageLike::put_stream), not with resumable uploads.
+///
+/// Retention and legal holds use S3 Object Lock, so need a bucket with it
+/// enabled. Retention is set in compliance mode. As such buckets are
+/// versioned, deleting a locked blob adds a delete marker and keeps the
+/// locked version rather than failing.
+///
+/// Batch deletes are sent as `DeleteObjects` requests of up to 1000 keys, and
+/// pre-signed form uploads are signed, with the credentials the storage was
+/// created with, so both need an access key and a secret key.
+///
+/// Request spans carry the endpoint and the bytes transferred. With the `otel`
+/// feature, requests also carry the current trace context, injected by the
+/// global OpenTelemetry propagator.
 #[derive(Debug)]
 pub struct BlobStorageS3 {
-  bucket: Bucket,
+  bucket:      Bucket,
+  region:      String,
+  credentials: Credentials,
+  http:        reqwest::Client,
 }
 
 impl BlobStorageS3 {
@@ -42,6 +83,7 @@ impl BlobStorageS3 {
       "Initializing S3 blob storage"
     );
 
+    let region_name = region.to_owned();
     let region = s3::Region::Custom {
       region:   region.to_owned(),
       endpoint: endpoint.to_owned(),
@@ -56,13 +98,61 @@ impl BlobStorageS3 {
     };
 
     let storage = BlobStorageS3 {
-      bucket: *Bucket::new(bucket, region, credentials)
+      bucket: *Bucket::new(bucket, region, credentials.clone())
         .map_err(s3_error_to_blob_storage_error)?,
+      region: region_name,
+      credentials,
+      http: reqwest::Client::new(),
     };
 
     info!(bucket = bucket, "S3 blob storage initialized successfully");
     Ok(storage)
   }
+
+  /// Addresses the bucket in the request path rather than the host name, as
+  /// S3-compatible servers like `MinIO` commonly require.
+  #[must_use]
+  pub fn with_path_style(mut self) -> Self {
+    self.bucket.set_path_style();
+    self
+  }
+}
+
+/// The request header carrying a checksum, and its base64-encoded value.
+fn checksum_header(checksum: &Checksum) -> (&'static str, String) {
+  let header = match checksum {
+    Checksum::Md5(_) => "Content-MD5",
+    Checksum::Sha256(_) => "x-amz-checksum-sha256",
+  };
+  (header, BASE64.encode(checksum.digest()))
+}
+
+/// The request headers applying an upload's storage class, canned ACL and
+/// server-side encryption.
+fn object_headers(options: &UploadOptions) -> Vec<(&'static str, String)> {
+  let mut headers = Vec::new();
+  if let Some(storage_class) = options.storage_class {
+    headers.push(("x-amz-storage-class", storage_class.as_str().to_owned()));
+  }
+  if let Some(acl) = options.acl {
+    headers.push(("x-amz-acl", acl.as_str().to_owned()));
+  }
+  match &options.sse {
+    Some(ServerSideEncryption::S3) => {
+      headers.push(("x-amz-server-side-encryption", "AES256".to_owned()));
+    }
+    Some(ServerSideEncryption::Kms { key_id }) => {
+      headers.push(("x-amz-server-side-encryption", "aws:kms".to_owned()));
+      if let Some(key_id) = key_id {
+        headers.push((
+          "x-amz-server-side-encryption-aws-kms-key-id",
+          key_id.clone(),
+        ));
+      }
+    }
+    None => (),
+  }
+  headers
 }
 
 #[async_trait::async_trait]
@@ -72,7 +162,10 @@ impl BlobStorageLike for BlobStorageS3 {
     fields(
       key = %key,
       bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
       overwrite = options.overwrite,
+      bytes_sent = tracing::field::Empty,
     ),
     err
   )]
@@ -95,11 +188,31 @@ impl BlobStorageLike for BlobStorageS3 {
       debug!("Object does not exist, proceeding with upload");
     }
 
-    // adapt to AsyncReader
-    let mut stream = StreamReader::new(data);
+    // adapt to AsyncReader, counting the bytes sent
+    let mut bytes_sent = 0_u64;
+    let mut stream = StreamReader::new(
+      data.inspect_ok(|chunk| bytes_sent += chunk.len() as u64),
+    );
 
-    // build request
-    let req = self.bucket.put_object_stream_builder(key);
+    // build request, attaching the checksum for S3 to verify
+    let bucket = self.traced_bucket()?;
+    let mut req = bucket.put_object_stream_builder(key);
+    let headers = object_headers(&options)
+      .into_iter()
+      .chain(options.checksum.as_ref().map(checksum_header))
+      .chain(
+        options
+          .content_type
+          .clone()
+          .map(|content_type| ("Content-Type", content_type)),
+      );
+    for (header, value) in headers {
+      debug!(header, "Attaching upload header");
+      req = req.with_header(header, &value).map_err(|e| {
+        error!(error = ?e, header, "Failed to attach upload header");
+        s3_error_to_blob_storage_error(e)
+      })?;
+    }
 
     // send the request
     debug!("Executing upload stream");
@@ -107,6 +220,8 @@ impl BlobStorageLike for BlobStorageS3 {
       error!(error = ?e, "Failed to upload stream");
       s3_error_to_blob_storage_error(e)
     })?;
+    drop(stream);
+    Span::current().record("bytes_sent", bytes_sent);
 
     info!("Stream uploaded successfully");
     Ok(())
@@ -117,6 +232,9 @@ impl BlobStorageLike for BlobStorageS3 {
     fields(
       key = %key,
       bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+      bytes_received = tracing::field::Empty,
     ),
     err
   )]
@@ -126,13 +244,93 @@ impl BlobStorageLike for BlobStorageS3 {
   ) -> BlobStorageResult<ResponseStream> {
     debug!("Retrieving object stream");
 
-    let data = self.bucket.get_object_stream(key).await.map_err(|e| {
-      error!(error = ?e, "Failed to get object stream");
+    let data =
+      self
+        .traced_bucket()?
+        .get_object_stream(key)
+        .await
+        .map_err(|e| {
+          error!(error = ?e, "Failed to get object stream");
+          s3_error_to_blob_storage_error(e)
+        })?;
+
+    // the span stays open until the stream is dropped, so the count is
+    // recorded as the body is read
+    let span = Span::current();
+    let mut bytes_received = 0_u64;
+    let data = Box::pin(
+      data
+        .bytes
+        .inspect_ok(move |chunk| {
+          bytes_received += chunk.len() as u64;
+          span.record("bytes_received", bytes_received);
+        })
+        .map_err(s3_error_to_blob_storage_error),
+    );
+
+    info!("Object stream retrieved successfully");
+    Ok(data)
+  }
+
+  #[instrument(
+    skip(self),
+    fields(
+      key = %key,
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+      range.start = range.start,
+      range.end = range.end,
+      bytes_received = tracing::field::Empty,
+    ),
+    err
+  )]
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    debug!("Retrieving object range stream");
+
+    if range.is_empty() {
+      // S3 can't express an empty range, so only check the object exists
+      if self.head(key).await?.is_none() {
+        return Err(BlobStorageError::NotFound(key.clone()));
+      }
+      return Ok(Box::pin(futures::stream::empty()));
+    }
+
+    // ranges are inclusive in HTTP
+    let mut headers = HeaderMap::new();
+    let value = format!("bytes={}-{}", range.start, range.end - 1);
+    headers.insert(
+      RANGE,
+      HeaderValue::from_str(&value).expect("byte range header is valid"),
+    );
+    headers.extend(trace_context_headers());
+    let bucket = self
+      .bucket
+      .with_extra_headers(headers)
+      .map_err(s3_error_to_blob_storage_error)?;
+
+    let data = bucket.get_object_stream(key).await.map_err(|e| {
+      error!(error = ?e, "Failed to get object range stream");
       s3_error_to_blob_storage_error(e)
     })?;
-    let data = Box::pin(data.bytes.map_err(s3_error_to_blob_storage_error));
 
-    info!("Object stream retrieved successfully");
+    let span = Span::current();
+    let mut bytes_received = 0_u64;
+    let data = Box::pin(
+      data
+        .bytes
+        .inspect_ok(move |chunk| {
+          bytes_received += chunk.len() as u64;
+          span.record("bytes_received", bytes_received);
+        })
+        .map_err(s3_error_to_blob_storage_error),
+    );
+
+    info!("Object range stream retrieved successfully");
     Ok(data)
   }
 
@@ -141,6 +339,8 @@ impl BlobStorageLike for BlobStorageS3 {
     fields(
       key = %key,
       bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
     ),
     err
   )]
@@ -150,14 +350,19 @@ impl BlobStorageLike for BlobStorageS3 {
   ) -> BlobStorageResult<Option<BlobMetadata>> {
     debug!("Fetching object metadata");
 
-    let (head, code) = self.bucket.head_object(key).await.map_err(|e| {
-      error!(error = ?e, "Failed to fetch object metadata");
-      s3_error_to_blob_storage_error(e)
-    })?;
+    let (head, code) =
+      self.traced_bucket()?.head_object(key).await.map_err(|e| {
+        error!(error = ?e, "Failed to fetch object metadata");
+        s3_error_to_blob_storage_error(e)
+      })?;
 
     debug!(status_code = code, "Received HEAD response");
 
     let err = miette!("got {code} response from API");
+    let details = Some(HttpErrorDetails {
+      status: Some(code),
+      ..HttpErrorDetails::default()
+    });
     match code {
       200 => (),
       200..300 | 300..400 | 412 => {
@@ -169,11 +374,21 @@ impl BlobStorageLike for BlobStorageS3 {
       }
 
       400 => return Err(BlobStorageError::InvalidInput(err)),
-      403 => return Err(BlobStorageError::PermissionDenied(err)),
+      403 => {
+        return Err(BlobStorageError::PermissionDenied {
+          report: err,
+          details,
+        });
+      }
 
       404 => return Ok(None),
 
-      500..600 => return Err(BlobStorageError::NetworkError(err)),
+      500..600 => {
+        return Err(BlobStorageError::NetworkError {
+          report: err,
+          details,
+        });
+      }
       _ => return Err(BlobStorageError::Unknown(err)),
     }
 
@@ -181,7 +396,7 @@ impl BlobStorageLike for BlobStorageS3 {
       .content_length
       .ok_or_else(|| {
         error!("HEAD response missing content_length");
-        BlobStorageError::NetworkError(miette!(
+        BlobStorageError::network(miette!(
           "head response did not include content_length"
         ))
       })?
@@ -197,6 +412,8 @@ impl BlobStorageLike for BlobStorageS3 {
       size,
       etag: head.e_tag.clone(),
       last_modified: head.last_modified.clone(),
+      content_type: head.content_type.clone(),
+      acl: None,
     };
 
     info!(
@@ -213,26 +430,125 @@ impl BlobStorageLike for BlobStorageS3 {
     fields(
       key = %key,
       bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
     ),
     err
   )]
   async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
     debug!("Deleting object");
 
-    self.bucket.delete_object(key).await.map_err(|e| {
-      error!(error = ?e, "Failed to delete object");
-      s3_error_to_blob_storage_error(e)
-    })?;
+    self
+      .traced_bucket()?
+      .delete_object(key)
+      .await
+      .map_err(|e| {
+        error!(error = ?e, "Failed to delete object");
+        s3_error_to_blob_storage_error(e)
+      })?;
 
     info!("Object deleted successfully");
     Ok(())
   }
 
+  #[instrument(
+    skip(self),
+    fields(
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+    ),
+    err
+  )]
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    debug!("Listing objects");
+
+    let (result, code) = self
+      .traced_bucket()?
+      .list_page(
+        prefix.to_owned(),
+        None,
+        continuation,
+        None,
+        Some(LIST_PAGE_SIZE),
+      )
+      .await
+      .map_err(|e| {
+        error!(error = ?e, "Failed to list objects");
+        s3_error_to_blob_storage_error(e)
+      })?;
+
+    debug!(status_code = code, "Received LIST response");
+
+    let entries: Vec<BlobEntry> = result
+      .contents
+      .into_iter()
+      .map(|object| BlobEntry {
+        key:      BlobKey::new(object.key),
+        metadata: BlobMetadata {
+          size:          object.size,
+          etag:          object.e_tag,
+          last_modified: Some(object.last_modified),
+          content_type:  None,
+          acl:           None,
+        },
+      })
+      .collect();
+
+    info!(entry_count = entries.len(), "Objects listed successfully");
+    Ok(BlobListPage {
+      entries,
+      continuation: result.next_continuation_token,
+    })
+  }
+
+  #[instrument(
+    skip(self, keys),
+    fields(
+      keys = keys.len(),
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+    )
+  )]
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    let mut results = Vec::with_capacity(keys.len());
+    for batch in keys.chunks(DELETE_OBJECTS_MAX_KEYS) {
+      match self.delete_objects(batch).await {
+        Ok(mut errors) => {
+          debug!(failed = errors.len(), "Batch deleted");
+          results.extend(batch.iter().map(|key| {
+            let result = errors.remove(key.as_str()).map_or(Ok(()), Err);
+            (key.clone(), result)
+          }));
+        }
+        Err(e) => {
+          error!(error = ?e, "Failed to delete batch");
+          results.extend(
+            batch.iter().map(|key| (key.clone(), Err(batch_error(&e)))),
+          );
+        }
+      }
+    }
+
+    info!("Objects deleted");
+    results
+  }
+
   #[instrument(
     skip(self),
     fields(
       key = %key,
       bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
       expiry_secs = expiry.as_secs(),
     ),
     err
@@ -282,4 +598,272 @@ impl BlobStorageLike for BlobStorageS3 {
     );
     Ok(url)
   }
+
+  #[instrument(
+    skip(self, conditions),
+    fields(
+      key = %key,
+      bucket = %self.bucket.name,
+      expiry_secs = expiry.as_secs(),
+    ),
+    err
+  )]
+  async fn get_presigned_post(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: std::time::Duration,
+  ) -> BlobStorageResult<PresignedPost> {
+    let (Some(access_key), Some(secret_key)) =
+      (&self.credentials.access_key, &self.credentials.secret_key)
+    else {
+      return Err(BlobStorageError::InvalidConfig(miette!(
+        "pre-signed POST policies need an access key and a secret key"
+      )));
+    };
+    let expiry = chrono::Duration::from_std(expiry)
+      .into_diagnostic()
+      .map_err(BlobStorageError::InvalidInput)?;
+
+    let now = Utc::now();
+    let signer = PolicySigner {
+      bucket: &self.bucket.name,
+      url: self.bucket.url(),
+      region: &self.region,
+      access_key,
+      secret_key,
+      security_token: self.credentials.security_token.as_deref(),
+    };
+    let expiration = now.checked_add_signed(expiry).ok_or_else(|| {
+      BlobStorageError::InvalidInput(miette!("expiry {expiry} is out of range"))
+    })?;
+    let post = signer.sign(key, conditions, now, expiration);
+    info!(
+      conditions = conditions.len(),
+      "Presigned POST policy generated successfully"
+    );
+    Ok(post)
+  }
+
+  #[instrument(
+    skip(self),
+    fields(
+      key = %key,
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+      overwrite = options.overwrite,
+    ),
+    err
+  )]
+  async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadHandle> {
+    if !options.overwrite && self.head(key).await?.is_some() {
+      warn!("Object already exists and overwrite=false");
+      return Err(BlobStorageError::AlreadyExists(key.clone()));
+    }
+
+    // storage class and encryption are fixed when the upload is initiated
+    let mut headers = HeaderMap::new();
+    for (header, value) in object_headers(&options) {
+      let value = HeaderValue::from_str(&value)
+        .into_diagnostic()
+        .with_context(|| format!("invalid value for header {header}"))
+        .map_err(BlobStorageError::InvalidInput)?;
+      headers.insert(header, value);
+    }
+    headers.extend(trace_context_headers());
+    let bucket = self
+      .bucket
+      .with_extra_headers(headers)
+      .map_err(s3_error_to_blob_storage_error)?;
+
+    let response = bucket
+      .initiate_multipart_upload(
+        key.as_str(),
+        options
+          .content_type
+          .as_deref()
+          .unwrap_or(MULTIPART_CONTENT_TYPE),
+      )
+      .await
+      .map_err(|e| {
+        error!(error = ?e, "Failed to initiate multipart upload");
+        s3_error_to_blob_storage_error(e)
+      })?;
+
+    info!(upload_id = %response.upload_id, "Multipart upload created");
+
+    Ok(UploadHandle {
+      key:       key.clone(),
+      upload_id: response.upload_id,
+      overwrite: options.overwrite,
+    })
+  }
+
+  #[instrument(
+    skip(self, handle, data),
+    fields(
+      key = %handle.key,
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+      upload_id = %handle.upload_id,
+      size = data.len(),
+    ),
+    err
+  )]
+  async fn upload_part(
+    &self,
+    handle: &UploadHandle,
+    part_number: u32,
+    data: Bytes,
+  ) -> BlobStorageResult<UploadedPart> {
+    if part_number == 0 {
+      return Err(BlobStorageError::InvalidInput(miette!(
+        "Part numbers start at 1"
+      )));
+    }
+
+    let size = data.len() as u64;
+    let part = self
+      .traced_bucket()?
+      .put_multipart_chunk(
+        data.to_vec(),
+        handle.key.as_str(),
+        part_number,
+        &handle.upload_id,
+        MULTIPART_CONTENT_TYPE,
+      )
+      .await
+      .map_err(|e| {
+        error!(error = ?e, "Failed to upload part");
+        s3_error_to_blob_storage_error(e)
+      })?;
+
+    debug!("Part uploaded successfully");
+
+    Ok(UploadedPart {
+      part_number,
+      size,
+      etag: part.etag,
+    })
+  }
+
+  #[instrument(
+    skip(self, handle, parts),
+    fields(
+      key = %handle.key,
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+      upload_id = %handle.upload_id,
+      part_count = parts.len(),
+    ),
+    err
+  )]
+  async fn complete_upload(
+    &self,
+    handle: &UploadHandle,
+    parts: &[UploadedPart],
+  ) -> BlobStorageResult<()> {
+    if !handle.overwrite && self.head(&handle.key).await?.is_some() {
+      warn!("Object already exists and overwrite=false");
+      return Err(BlobStorageError::AlreadyExists(handle.key.clone()));
+    }
+
+    let parts = parts
+      .iter()
+      .map(|part| Part {
+        part_number: part.part_number,
+        etag:        part.etag.clone(),
+      })
+      .collect();
+
+    self
+      .traced_bucket()?
+      .complete_multipart_upload(handle.key.as_str(), &handle.upload_id, parts)
+      .await
+      .map_err(|e| {
+        error!(error = ?e, "Failed to complete multipart upload");
+        s3_error_to_blob_storage_error(e)
+      })?;
+
+    info!("Multipart upload completed successfully");
+    Ok(())
+  }
+
+  #[instrument(
+    skip(self, handle),
+    fields(
+      key = %handle.key,
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+      upload_id = %handle.upload_id,
+    ),
+    err
+  )]
+  async fn abort_upload(&self, handle: &UploadHandle) -> BlobStorageResult<()> {
+    self
+      .traced_bucket()?
+      .abort_upload(handle.key.as_str(), &handle.upload_id)
+      .await
+      .map_err(|e| {
+        error!(error = ?e, "Failed to abort multipart upload");
+        s3_error_to_blob_storage_error(e)
+      })?;
+
+    info!("Multipart upload aborted");
+    Ok(())
+  }
+
+  #[instrument(
+    skip(self),
+    fields(
+      key = %key,
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+    ),
+    err
+  )]
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self
+      .put_object_subresource(key, "retention", retention_body(until))
+      .await?;
+
+    info!("Object retention set");
+    Ok(())
+  }
+
+  #[instrument(
+    skip(self),
+    fields(
+      key = %key,
+      bucket = %self.bucket.name,
+      server.address = %self.bucket.host(),
+      otel.kind = "client",
+    ),
+    err
+  )]
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self
+      .put_object_subresource(key, "legal-hold", legal_hold_body(hold))
+      .await?;
+
+    info!("Object legal hold set");
+    Ok(())
+  }
 }
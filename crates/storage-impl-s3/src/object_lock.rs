@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::{DateTime, SecondsFormat, Utc};
+use miette::IntoDiagnostic;
+use reqwest::header::{HeaderMap, HeaderValue};
+use storage_core::{BlobKey, BlobStorageError, BlobStorageResult, Checksum};
+use tracing::{debug, error};
+
+use crate::{
+  BlobStorageS3,
+  delete_objects::S3_XML_NAMESPACE,
+  errors::{
+    http_failure_to_blob_storage_error, s3_error_to_blob_storage_error,
+  },
+  telemetry::trace_context_headers,
+};
+
+/// How long the pre-signed URLs for object lock requests stay valid.
+const LOCK_REQUEST_EXPIRY_SECS: u32 = 60;
+
+/// The body of a `PutObjectRetention` request, retaining an object in
+/// compliance mode until `until`.
+pub(crate) fn retention_body(until: DateTime<Utc>) -> String {
+  format!(
+    "<Retention \
+     xmlns=\"{S3_XML_NAMESPACE}\"><Mode>COMPLIANCE</Mode><RetainUntilDate>{}</\
+     RetainUntilDate></Retention>",
+    until.to_rfc3339_opts(SecondsFormat::Secs, true)
+  )
+}
+
+/// The body of a `PutObjectLegalHold` request.
+pub(crate) fn legal_hold_body(hold: bool) -> String {
+  format!(
+    "<LegalHold xmlns=\"{S3_XML_NAMESPACE}\"><Status>{}</Status></LegalHold>",
+    if hold { "ON" } else { "OFF" }
+  )
+}
+
+impl BlobStorageS3 {
+  /// Puts `body` to a subresource of an object, e.g. `?retention`.
+  ///
+  /// `rust-s3` has no object lock API, so the request is signed as a
+  /// pre-signed URL and sent directly. S3 requires these requests to carry a
+  /// `Content-MD5` header.
+  pub(crate) async fn put_object_subresource(
+    &self,
+    key: &BlobKey,
+    subresource: &str,
+    body: String,
+  ) -> BlobStorageResult<()> {
+    let content_md5 = BASE64.encode(Checksum::md5(body.as_bytes()).digest());
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      "Content-MD5",
+      HeaderValue::from_str(&content_md5)
+        .into_diagnostic()
+        .map_err(BlobStorageError::InvalidInput)?,
+    );
+    let queries = HashMap::from([(subresource.to_owned(), String::new())]);
+
+    let url = self
+      .bucket
+      .presign_put(
+        key.as_str(),
+        LOCK_REQUEST_EXPIRY_SECS,
+        Some(headers.clone()),
+        Some(queries),
+      )
+      .await
+      .map_err(|e| {
+        error!(error = ?e, "Failed to sign object lock request");
+        s3_error_to_blob_storage_error(e)
+      })?;
+
+    headers.extend(trace_context_headers());
+    debug!(subresource, "Sending object lock request");
+    let response = self
+      .http
+      .put(url)
+      .headers(headers)
+      .body(body)
+      .send()
+      .await
+      .into_diagnostic()
+      .map_err(BlobStorageError::network)?;
+
+    let status = response.status().as_u16();
+    match status {
+      200..300 => Ok(()),
+      404 => Err(BlobStorageError::NotFound(key.clone())),
+      _ => {
+        let body = response.text().await.unwrap_or_default();
+        error!(status, "Object lock request failed");
+        Err(http_failure_to_blob_storage_error(status, &body))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+
+  use super::*;
+
+  #[test]
+  fn test_retention_body() {
+    let until = Utc.with_ymd_and_hms(2030, 1, 2, 3, 4, 5).unwrap();
+    let body = retention_body(until);
+
+    assert!(body.contains("<Mode>COMPLIANCE</Mode>"));
+    assert!(
+      body.contains("<RetainUntilDate>2030-01-02T03:04:05Z</RetainUntilDate>")
+    );
+  }
+
+  #[test]
+  fn test_legal_hold_body() {
+    assert!(legal_hold_body(true).contains("<Status>ON</Status>"));
+    assert!(legal_hold_body(false).contains("<Status>OFF</Status>"));
+  }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Value, json};
+use storage_core::{BlobKey, PostCondition, PresignedPost};
+
+use crate::sigv4::{ALGORITHM, hex, hmac_sha256, signing_key};
+
+/// The credentials and location a POST policy is signed for.
+pub(crate) struct PolicySigner<'a> {
+  pub bucket:         &'a str,
+  pub url:            String,
+  pub region:         &'a str,
+  pub access_key:     &'a str,
+  pub secret_key:     &'a str,
+  pub security_token: Option<&'a str>,
+}
+
+/// The policy document condition enforcing `condition`.
+fn policy_condition(condition: &PostCondition) -> Value {
+  match condition {
+    PostCondition::ContentLengthRange { min, max } => {
+      json!(["content-length-range", min, max])
+    }
+    PostCondition::ContentType(content_type) => {
+      json!({ "Content-Type": content_type })
+    }
+    PostCondition::ContentTypePrefix(prefix) => {
+      json!(["starts-with", "$Content-Type", prefix])
+    }
+  }
+}
+
+impl PolicySigner<'_> {
+  /// Signs a POST policy for uploads to `key` meeting `conditions`, signed
+  /// at `now` and expiring at `expiration`.
+  pub(crate) fn sign(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    now: DateTime<Utc>,
+    expiration: DateTime<Utc>,
+  ) -> PresignedPost {
+    let date = now.format("%Y%m%d").to_string();
+    let mut fields = BTreeMap::from([
+      ("key".to_owned(), key.to_string()),
+      ("x-amz-algorithm".to_owned(), ALGORITHM.to_owned()),
+      (
+        "x-amz-credential".to_owned(),
+        format!("{}/{date}/{}/s3/aws4_request", self.access_key, self.region),
+      ),
+      (
+        "x-amz-date".to_owned(),
+        now.format("%Y%m%dT%H%M%SZ").to_string(),
+      ),
+    ]);
+    if let Some(token) = self.security_token {
+      fields.insert("x-amz-security-token".to_owned(), token.to_owned());
+    }
+    for condition in conditions {
+      if let PostCondition::ContentType(content_type) = condition {
+        fields.insert("Content-Type".to_owned(), content_type.clone());
+      }
+    }
+
+    let mut policy_conditions = vec![json!({ "bucket": self.bucket })];
+    policy_conditions.extend(
+      fields
+        .iter()
+        .filter(|(name, _)| name.as_str() != "Content-Type")
+        .map(|(name, value)| json!({ name: value })),
+    );
+    policy_conditions.extend(conditions.iter().map(policy_condition));
+    let policy = json!({
+      "expiration": expiration.to_rfc3339_opts(SecondsFormat::Millis, true),
+      "conditions": policy_conditions,
+    });
+    let policy = BASE64.encode(policy.to_string());
+
+    let key = signing_key(self.secret_key, &date, self.region, "s3");
+    let signature = hex(&hmac_sha256(&key, policy.as_bytes()));
+    fields.insert("policy".to_owned(), policy);
+    fields.insert("x-amz-signature".to_owned(), signature);
+
+    PresignedPost {
+      url: self.url.clone(),
+      fields,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+
+  use super::*;
+
+  #[test]
+  fn test_sign_policy() {
+    let signer = PolicySigner {
+      bucket:         "uploads",
+      url:            "https://uploads.s3.example.com".to_owned(),
+      region:         "us-east-1",
+      access_key:     "AKIDEXAMPLE",
+      secret_key:     "secret",
+      security_token: None,
+    };
+    let now = Utc.with_ymd_and_hms(2030, 1, 2, 3, 4, 5).unwrap();
+    let post = signer.sign(
+      &BlobKey::new("avatars/1.png"),
+      &[
+        PostCondition::ContentType("image/png".to_owned()),
+        PostCondition::ContentLengthRange { min: 1, max: 1024 },
+      ],
+      now,
+      now + chrono::Duration::minutes(5),
+    );
+
+    assert_eq!(post.fields["key"], "avatars/1.png");
+    assert_eq!(post.fields["Content-Type"], "image/png");
+    assert_eq!(post.fields["x-amz-date"], "20300102T030405Z");
+    assert_eq!(
+      post.fields["x-amz-credential"],
+      "AKIDEXAMPLE/20300102/us-east-1/s3/aws4_request"
+    );
+
+    let policy: Value =
+      serde_json::from_slice(&BASE64.decode(&post.fields["policy"]).unwrap())
+        .unwrap();
+    assert_eq!(policy["expiration"], "2030-01-02T03:09:05.000Z");
+    let conditions = policy["conditions"].as_array().unwrap();
+    assert!(conditions.contains(&json!({ "bucket": "uploads" })));
+    assert!(conditions.contains(&json!({ "key": "avatars/1.png" })));
+    assert!(conditions.contains(&json!({ "Content-Type": "image/png" })));
+    assert!(conditions.contains(&json!(["content-length-range", 1, 1024])));
+
+    let key = signing_key("secret", "20300102", "us-east-1", "s3");
+    assert_eq!(
+      post.fields["x-amz-signature"],
+      hex(&hmac_sha256(&key, post.fields["policy"].as_bytes()))
+    );
+  }
+}
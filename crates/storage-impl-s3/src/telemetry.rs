@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+
+use reqwest::header::HeaderMap;
+use s3::Bucket;
+use storage_core::BlobStorageResult;
+
+use crate::BlobStorageS3;
+
+impl BlobStorageS3 {
+  /// The bucket to send requests with, which carries the current trace
+  /// context with the `otel` feature.
+  #[cfg(feature = "otel")]
+  pub(crate) fn traced_bucket(&self) -> BlobStorageResult<Cow<'_, Bucket>> {
+    let bucket = self
+      .bucket
+      .with_extra_headers(trace_context_headers())
+      .map_err(crate::errors::s3_error_to_blob_storage_error)?;
+    Ok(Cow::Owned(bucket))
+  }
+
+  /// The bucket to send requests with, which carries the current trace
+  /// context with the `otel` feature.
+  #[cfg(not(feature = "otel"))]
+  #[allow(clippy::unnecessary_wraps)]
+  pub(crate) const fn traced_bucket(
+    &self,
+  ) -> BlobStorageResult<Cow<'_, Bucket>> {
+    Ok(Cow::Borrowed(&self.bucket))
+  }
+}
+
+/// Headers carrying the current span's trace context, as injected by the
+/// global OpenTelemetry propagator.
+#[cfg(feature = "otel")]
+pub(crate) fn trace_context_headers() -> HeaderMap {
+  use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+  let context = tracing::Span::current().context();
+  let mut headers = HeaderMap::new();
+  opentelemetry::global::get_text_map_propagator(|propagator| {
+    propagator.inject_context(&context, &mut HeaderInjector(&mut headers));
+  });
+  headers
+}
+
+/// Headers carrying the current span's trace context, which are empty without
+/// the `otel` feature.
+#[cfg(not(feature = "otel"))]
+pub(crate) fn trace_context_headers() -> HeaderMap { HeaderMap::new() }
+
+/// Injects propagated context into request headers, skipping any that aren't
+/// valid headers.
+#[cfg(feature = "otel")]
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+#[cfg(feature = "otel")]
+impl opentelemetry::propagation::Injector for HeaderInjector<'_> {
+  fn set(&mut self, key: &str, value: String) {
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    if let (Ok(name), Ok(value)) = (
+      HeaderName::from_bytes(key.as_bytes()),
+      HeaderValue::from_str(&value),
+    ) {
+      self.0.insert(name, value);
+    }
+  }
+}
@@ -1,6 +1,7 @@
 use std::fmt;
 
 use serde::{Deserialize, Serialize};
+use slug::LaxSlug;
 
 /// The key used for a blob.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -15,6 +16,36 @@ impl BlobKey {
   /// Convert into inner String
   #[must_use]
   pub fn into_inner(self) -> String { self.0 }
+
+  /// Create a key by joining slugs with `/`.
+  ///
+  /// Segments which are empty or only dots, like `..`, are skipped, so the
+  /// key can't traverse out of its prefix.
+  pub fn from_segments<'a>(
+    segments: impl IntoIterator<Item = &'a LaxSlug>,
+  ) -> Self {
+    let segments: Vec<&str> = segments
+      .into_iter()
+      .map(LaxSlug::as_str)
+      .filter(|segment| !segment.chars().all(|c| c == '.'))
+      .collect();
+    Self(segments.join("/"))
+  }
+
+  /// Create a storage-safe key from untrusted input, by lax-slugifying each
+  /// `/`-separated segment and joining them as in
+  /// [`from_segments`](Self::from_segments).
+  ///
+  /// ```
+  /// # use storage_types::BlobKey;
+  /// let key = BlobKey::sanitize("../uploads//my file.txt");
+  /// assert_eq!(key.as_str(), "uploads/my-file.txt");
+  /// ```
+  #[must_use]
+  pub fn sanitize(input: &str) -> Self {
+    let segments: Vec<LaxSlug> = input.split('/').map(LaxSlug::new).collect();
+    Self::from_segments(&segments)
+  }
 }
 
 impl fmt::Display for BlobKey {
@@ -34,3 +65,25 @@ impl From<&str> for BlobKey {
 impl AsRef<str> for BlobKey {
   fn as_ref(&self) -> &str { &self.0 }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_from_segments() {
+    let segments = [LaxSlug::new("org"), LaxSlug::new(".."), LaxSlug::new("")];
+    assert_eq!(BlobKey::from_segments(&segments).as_str(), "org");
+
+    let segments = [LaxSlug::new("a"), LaxSlug::new(".b")];
+    assert_eq!(BlobKey::from_segments(&segments).as_str(), "a/.b");
+  }
+
+  #[test]
+  fn test_sanitize() {
+    assert_eq!(BlobKey::sanitize("/etc/../passwd").as_str(), "etc/passwd");
+    assert_eq!(BlobKey::sanitize("a\\..\\b").as_str(), "a-..-b");
+    assert_eq!(BlobKey::sanitize("Grüße/ü.txt").as_str(), "Grusse/u.txt");
+    assert_eq!(BlobKey::sanitize("...").as_str(), "");
+  }
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+
+use crate::BlobKey;
+
+/// A key scheme such as `artifacts/{org}/{id}`, declared once and used both
+/// to build keys and to parse them back, e.g. from listings.
+///
+/// Declare templates with [`key_template!`](crate::key_template), which checks
+/// them at compile time: placeholder names must be non-empty identifiers,
+/// must be distinct, and must be separated by literal text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyTemplate {
+  template: &'static str,
+}
+
+/// A piece of a [`KeyTemplate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment {
+  Literal(&'static str),
+  Placeholder(&'static str),
+}
+
+/// An error filling a [`KeyTemplate`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum KeyTemplateError {
+  /// No value was given for a placeholder.
+  #[error("no value for placeholder `{0}`")]
+  MissingValue(&'static str),
+  /// A value was given for a placeholder the template doesn't have.
+  #[error("template has no placeholder `{0}`")]
+  UnknownPlaceholder(String),
+  /// A value is empty, or contains the literal text following its
+  /// placeholder, so the key couldn't be parsed back.
+  #[error("invalid value `{value}` for placeholder `{placeholder}`")]
+  InvalidValue {
+    /// The placeholder.
+    placeholder: &'static str,
+    /// The rejected value.
+    value:       String,
+  },
+}
+
+impl KeyTemplate {
+  /// Creates a new [`KeyTemplate`], panicking if `template` is malformed.
+  ///
+  /// Prefer [`key_template!`](crate::key_template), which evaluates this at
+  /// compile time.
+  #[must_use]
+  pub const fn new(template: &'static str) -> Self {
+    validate(template.as_bytes());
+    Self { template }
+  }
+
+  /// Returns the template string.
+  #[must_use]
+  pub const fn as_str(&self) -> &'static str { self.template }
+
+  /// Returns the literal text before the first placeholder, which every key
+  /// matching the template starts with.
+  #[must_use]
+  pub fn prefix(&self) -> &'static str {
+    match self.segments().next() {
+      Some(Segment::Literal(literal)) => literal,
+      _ => "",
+    }
+  }
+
+  /// Returns the names of the placeholders, in order.
+  pub fn placeholders(&self) -> impl Iterator<Item = &'static str> {
+    self.segments().filter_map(|segment| match segment {
+      Segment::Placeholder(name) => Some(name),
+      Segment::Literal(_) => None,
+    })
+  }
+
+  /// Builds a key by substituting `values` for the placeholders.
+  pub fn fill(
+    &self,
+    values: &[(&str, &dyn fmt::Display)],
+  ) -> Result<BlobKey, KeyTemplateError> {
+    if let Some((name, _)) = values
+      .iter()
+      .find(|(name, _)| !self.placeholders().any(|p| p == *name))
+    {
+      return Err(KeyTemplateError::UnknownPlaceholder((*name).to_owned()));
+    }
+
+    let segments: Vec<_> = self.segments().collect();
+    let mut key = String::with_capacity(self.template.len());
+    for (i, segment) in segments.iter().enumerate() {
+      match *segment {
+        Segment::Literal(literal) => key.push_str(literal),
+        Segment::Placeholder(placeholder) => {
+          let value = values
+            .iter()
+            .find(|(name, _)| *name == placeholder)
+            .ok_or(KeyTemplateError::MissingValue(placeholder))?
+            .1
+            .to_string();
+          let breaks_parse = match segments.get(i + 1) {
+            Some(Segment::Literal(next)) => value.contains(next),
+            _ => false,
+          };
+          if value.is_empty() || breaks_parse {
+            return Err(KeyTemplateError::InvalidValue { placeholder, value });
+          }
+          key.push_str(&value);
+        }
+      }
+    }
+
+    Ok(BlobKey::new(key))
+  }
+
+  /// Parses `key` against the template, returning the placeholder values,
+  /// or `None` if the key doesn't match.
+  #[must_use]
+  pub fn parse<'k>(&self, key: &'k str) -> Option<KeyParts<'k>> {
+    let segments: Vec<_> = self.segments().collect();
+    let mut rest = key;
+    let mut parts = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+      match *segment {
+        Segment::Literal(literal) => rest = rest.strip_prefix(literal)?,
+        Segment::Placeholder(name) => {
+          let end = match segments.get(i + 1) {
+            Some(Segment::Literal(next)) => rest.find(next)?,
+            _ => rest.len(),
+          };
+          if end == 0 {
+            return None;
+          }
+          parts.push((name, &rest[..end]));
+          rest = &rest[end..];
+        }
+      }
+    }
+    rest.is_empty().then_some(KeyParts { parts })
+  }
+
+  /// Splits the template into literals and placeholders. The template is
+  /// known to be well-formed.
+  fn segments(&self) -> impl Iterator<Item = Segment> {
+    let mut rest = self.template;
+    std::iter::from_fn(move || {
+      if rest.is_empty() {
+        return None;
+      }
+      if let Some(placeholder) = rest.strip_prefix('{') {
+        let (name, after) = placeholder.split_once('}')?;
+        rest = after;
+        return Some(Segment::Placeholder(name));
+      }
+      let end = rest.find('{').unwrap_or(rest.len());
+      let (literal, after) = rest.split_at(end);
+      rest = after;
+      Some(Segment::Literal(literal))
+    })
+  }
+}
+
+impl fmt::Display for KeyTemplate {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.template)
+  }
+}
+
+/// The placeholder values parsed from a key by [`KeyTemplate::parse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyParts<'k> {
+  parts: Vec<(&'static str, &'k str)>,
+}
+
+impl<'k> KeyParts<'k> {
+  /// Returns the value of the placeholder `name`.
+  #[must_use]
+  pub fn get(&self, name: &str) -> Option<&'k str> {
+    self
+      .parts
+      .iter()
+      .find(|(placeholder, _)| *placeholder == name)
+      .map(|(_, value)| *value)
+  }
+
+  /// Returns the placeholder names and values, in order.
+  pub fn pairs(&self) -> impl Iterator<Item = (&'static str, &'k str)> {
+    self.parts.iter().copied()
+  }
+}
+
+/// Checks that a template is well-formed, panicking otherwise. Evaluated in
+/// a const context, the panic is a compile error.
+const fn validate(template: &[u8]) {
+  let mut i = 0;
+  let mut after_placeholder = false;
+  while i < template.len() {
+    match template[i] {
+      b'{' => {
+        assert!(
+          !after_placeholder,
+          "key template placeholders must be separated by literal text"
+        );
+        let start = i + 1;
+        let mut end = start;
+        while end < template.len() && template[end] != b'}' {
+          assert!(
+            template[end].is_ascii_alphanumeric() || template[end] == b'_',
+            "key template placeholder names must be identifiers"
+          );
+          end += 1;
+        }
+        assert!(end < template.len(), "unclosed key template placeholder");
+        assert!(end > start, "empty key template placeholder");
+        assert!(
+          !defined_before(template, start, end),
+          "duplicate key template placeholder"
+        );
+        i = end + 1;
+        after_placeholder = true;
+      }
+      b'}' => panic!("unmatched closing brace in key template"),
+      _ => {
+        i += 1;
+        after_placeholder = false;
+      }
+    }
+  }
+}
+
+/// Whether the placeholder name at `template[start..end]` appears as a
+/// placeholder before `start`.
+const fn defined_before(template: &[u8], start: usize, end: usize) -> bool {
+  let len = end - start;
+  let mut i = 0;
+  while i + len + 1 < start {
+    if template[i] == b'{' && template[i + len + 1] == b'}' {
+      let mut j = 0;
+      while j < len && template[i + 1 + j] == template[start + j] {
+        j += 1;
+      }
+      if j == len {
+        return true;
+      }
+    }
+    i += 1;
+  }
+  false
+}
+
+/// Declares a [`KeyTemplate`], checking it at compile time.
+///
+/// ```
+/// use storage_types::{KeyTemplate, key_template};
+///
+/// const ARTIFACTS: KeyTemplate = key_template!("artifacts/{org}/{id}");
+///
+/// let key = ARTIFACTS.fill(&[("org", &"acme"), ("id", &42)]).unwrap();
+/// assert_eq!(key.as_str(), "artifacts/acme/42");
+///
+/// let parts = ARTIFACTS.parse(key.as_str()).unwrap();
+/// assert_eq!(parts.get("id"), Some("42"));
+/// ```
+///
+/// Malformed templates fail to compile:
+///
+/// ```compile_fail
+/// const BAD: storage_types::KeyTemplate =
+///   storage_types::key_template!("artifacts/{org}{id}");
+/// ```
+#[macro_export]
+macro_rules! key_template {
+  ($template:literal) => {{
+    const TEMPLATE: $crate::KeyTemplate = $crate::KeyTemplate::new($template);
+    TEMPLATE
+  }};
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ARTIFACTS: KeyTemplate = key_template!("artifacts/{org}/{id}.tar");
+
+  #[test]
+  fn test_fill_and_parse_round_trip() {
+    let key = ARTIFACTS.fill(&[("id", &7), ("org", &"acme")]).unwrap();
+    assert_eq!(key.as_str(), "artifacts/acme/7.tar");
+
+    let parts = ARTIFACTS.parse(key.as_str()).unwrap();
+    assert_eq!(parts.pairs().collect::<Vec<_>>(), [
+      ("org", "acme"),
+      ("id", "7")
+    ]);
+    assert_eq!(ARTIFACTS.prefix(), "artifacts/");
+  }
+
+  #[test]
+  fn test_fill_errors() {
+    assert_eq!(
+      ARTIFACTS.fill(&[("org", &"acme")]),
+      Err(KeyTemplateError::MissingValue("id"))
+    );
+    assert_eq!(
+      ARTIFACTS.fill(&[("org", &"acme"), ("id", &1), ("extra", &2)]),
+      Err(KeyTemplateError::UnknownPlaceholder("extra".to_owned()))
+    );
+    assert!(matches!(
+      ARTIFACTS.fill(&[("org", &"a/b"), ("id", &1)]),
+      Err(KeyTemplateError::InvalidValue {
+        placeholder: "org",
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn test_parse_rejects_mismatches() {
+    for key in [
+      "artifacts/acme/7.zip",
+      "other/acme/7.tar",
+      "artifacts//7.tar",
+      "artifacts/acme",
+    ] {
+      assert!(ARTIFACTS.parse(key).is_none(), "{key}");
+    }
+  }
+
+  #[test]
+  #[should_panic(expected = "duplicate key template placeholder")]
+  fn test_duplicate_placeholder_panics() {
+    let _ = KeyTemplate::new("{id}/{id}");
+  }
+}
@@ -1,5 +1,9 @@
 //! Types for the storage crates.
 
 mod blob_key;
+mod key_template;
 
-pub use self::blob_key::BlobKey;
+pub use self::{
+  blob_key::BlobKey,
+  key_template::{KeyParts, KeyTemplate, KeyTemplateError},
+};
@@ -0,0 +1,518 @@
+//! Auditing decorator for blob storage backends.
+
+use std::{
+  fmt,
+  ops::Range,
+  path::Path,
+  sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+  },
+  time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "audit-table")]
+use db::Database;
+use futures::{StreamExt, channel::mpsc};
+#[cfg(feature = "audit-table")]
+use model::{IndexValue, Model, RecordId};
+use serde::{Deserialize, Serialize};
+use storage_core::{
+  BlobKey, BlobListPage, BlobMetadata, BlobStorageLike, BlobStorageResult,
+  Bytes, PostCondition, PresignedPost, RequestStream, ResponseStream,
+  UploadHandle, UploadOptions, UploadedPart,
+};
+use tracing::{info, warn};
+
+/// Identifies who is performing a storage operation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationContext {
+  /// The caller performing the operation, e.g. a user or service ID.
+  pub caller:     Option<String>,
+  /// An identifier for the request the operation belongs to.
+  pub request_id: Option<String>,
+}
+
+impl OperationContext {
+  /// Creates a new [`OperationContext`] for the given caller.
+  pub fn new(caller: impl Into<String>) -> Self {
+    Self {
+      caller:     Some(caller.into()),
+      request_id: None,
+    }
+  }
+
+  /// Sets the request ID.
+  #[must_use]
+  pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+    self.request_id = Some(request_id.into());
+    self
+  }
+}
+
+/// The storage operation an [`AuditEvent`] records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+  /// A blob was uploaded.
+  Put,
+  /// A blob was downloaded.
+  Get,
+  /// A blob's metadata was read.
+  Head,
+  /// A blob was deleted.
+  Delete,
+  /// A blob was copied to the key.
+  Copy,
+  /// A pre-signed URL was issued for a blob.
+  PresignedUrl,
+  /// A pre-signed form upload was issued for a blob.
+  PresignedPost,
+  /// A blob's retention was set.
+  SetRetention,
+  /// A legal hold on a blob was placed or lifted.
+  SetLegalHold,
+}
+
+impl fmt::Display for AuditOperation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      AuditOperation::Put => "put",
+      AuditOperation::Get => "get",
+      AuditOperation::Head => "head",
+      AuditOperation::Delete => "delete",
+      AuditOperation::Copy => "copy",
+      AuditOperation::PresignedUrl => "presigned_url",
+      AuditOperation::PresignedPost => "presigned_post",
+      AuditOperation::SetRetention => "set_retention",
+      AuditOperation::SetLegalHold => "set_legal_hold",
+    })
+  }
+}
+
+/// The outcome of an audited operation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+  /// The operation succeeded.
+  Success,
+  /// The operation failed with the given error message.
+  Failure(String),
+}
+
+/// A structured record of a single storage operation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+  /// When the operation completed.
+  pub timestamp: DateTime<Utc>,
+  /// The operation performed.
+  pub operation: AuditOperation,
+  /// The blob the operation targeted.
+  pub key:       BlobKey,
+  /// The number of bytes involved, if known. For uploads this is the number
+  /// of bytes read from the request stream.
+  pub size:      Option<u64>,
+  /// The context of the caller performing the operation.
+  pub context:   OperationContext,
+  /// The outcome of the operation.
+  pub outcome:   AuditOutcome,
+}
+
+/// A destination for [`AuditEvent`]s.
+///
+/// With the `audit-table` feature, `DatabaseAuditSink` persists events into
+/// a `Model`-backed audit table. Sinks are responsible for handling their own
+/// failures; an audit failure never fails the storage operation.
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+  /// Record an audit event.
+  async fn record(&self, event: AuditEvent);
+}
+
+/// An [`AuditSink`] that emits events through `tracing`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingAuditSink;
+
+#[async_trait]
+impl AuditSink for TracingAuditSink {
+  async fn record(&self, event: AuditEvent) {
+    info!(
+      target: "storage::audit",
+      operation = %event.operation,
+      key = %event.key,
+      size = ?event.size,
+      caller = ?event.context.caller,
+      request_id = ?event.context.request_id,
+      outcome = ?event.outcome,
+      "storage operation"
+    );
+  }
+}
+
+/// An [`AuditSink`] that sends events over a channel.
+#[derive(Clone, Debug)]
+pub struct ChannelAuditSink {
+  sender: mpsc::UnboundedSender<AuditEvent>,
+}
+
+impl ChannelAuditSink {
+  /// Creates a new [`ChannelAuditSink`] along with the receiving end of its
+  /// channel.
+  #[must_use]
+  pub fn new() -> (Self, mpsc::UnboundedReceiver<AuditEvent>) {
+    let (sender, receiver) = mpsc::unbounded();
+    (Self { sender }, receiver)
+  }
+}
+
+#[async_trait]
+impl AuditSink for ChannelAuditSink {
+  async fn record(&self, event: AuditEvent) {
+    if self.sender.unbounded_send(event).is_err() {
+      warn!("audit channel closed, dropping audit event");
+    }
+  }
+}
+
+/// An [`AuditEvent`] stored by a [`DatabaseAuditSink`], indexed by the key
+/// and the caller.
+#[cfg(feature = "audit-table")]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "storage_audit_events",
+  index(name = "key", extract =
+    |m| vec![IndexValue::new_single(m.event.key.as_str())]
+  ),
+  index(name = "caller", extract =
+    |m| m.event.context.caller.iter().map(IndexValue::new_single).collect()
+  ),
+)]
+pub struct AuditRecord {
+  /// The record's ID.
+  #[model(id)]
+  pub id:    RecordId<AuditRecord>,
+  /// The recorded event.
+  pub event: AuditEvent,
+}
+
+/// An [`AuditSink`] that inserts events into a database as
+/// [`AuditRecord`]s.
+#[cfg(feature = "audit-table")]
+#[derive(Clone, Debug)]
+pub struct DatabaseAuditSink {
+  db: Database<AuditRecord>,
+}
+
+#[cfg(feature = "audit-table")]
+impl DatabaseAuditSink {
+  /// Creates a new [`DatabaseAuditSink`] inserting into `db`.
+  #[must_use]
+  pub const fn new(db: Database<AuditRecord>) -> Self { Self { db } }
+}
+
+#[cfg(feature = "audit-table")]
+#[async_trait]
+impl AuditSink for DatabaseAuditSink {
+  async fn record(&self, event: AuditEvent) {
+    let record = AuditRecord {
+      id: RecordId::new(),
+      event,
+    };
+    if let Err(e) = self.db.insert(&record).await {
+      warn!(error = ?e, "failed to insert audit record, dropping audit event");
+    }
+  }
+}
+
+impl AuditOutcome {
+  fn from_result<T>(result: &BlobStorageResult<T>) -> Self {
+    match result {
+      Ok(_) => AuditOutcome::Success,
+      Err(e) => AuditOutcome::Failure(e.to_string()),
+    }
+  }
+}
+
+/// A [`BlobStorageLike`] decorator that records an [`AuditEvent`] for every
+/// operation on the inner storage.
+///
+/// Resumable uploads are recorded once, as a [`Put`](AuditOperation::Put)
+/// when the upload is completed.
+pub struct AuditedBlobStorage<S: ?Sized> {
+  inner:   Arc<S>,
+  sink:    Arc<dyn AuditSink>,
+  context: OperationContext,
+}
+
+impl<S: ?Sized> Clone for AuditedBlobStorage<S> {
+  fn clone(&self) -> Self {
+    Self {
+      inner:   self.inner.clone(),
+      sink:    self.sink.clone(),
+      context: self.context.clone(),
+    }
+  }
+}
+
+impl<S: ?Sized> fmt::Debug for AuditedBlobStorage<S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("AuditedBlobStorage")
+      .field("context", &self.context)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<S: BlobStorageLike + ?Sized> AuditedBlobStorage<S> {
+  /// Wraps `inner`, recording its operations to `sink` with an empty
+  /// [`OperationContext`].
+  pub fn new(inner: Arc<S>, sink: Arc<dyn AuditSink>) -> Self {
+    Self {
+      inner,
+      sink,
+      context: OperationContext::default(),
+    }
+  }
+
+  /// Returns a handle sharing the same storage and sink which attributes its
+  /// operations to `context`.
+  #[must_use]
+  pub fn with_context(&self, context: OperationContext) -> Self {
+    Self {
+      inner: self.inner.clone(),
+      sink: self.sink.clone(),
+      context,
+    }
+  }
+
+  /// The context operations are attributed to.
+  #[must_use]
+  pub const fn context(&self) -> &OperationContext { &self.context }
+
+  async fn record(
+    &self,
+    operation: AuditOperation,
+    key: &BlobKey,
+    size: Option<u64>,
+    outcome: AuditOutcome,
+  ) {
+    self
+      .sink
+      .record(AuditEvent {
+        timestamp: Utc::now(),
+        operation,
+        key: key.clone(),
+        size,
+        context: self.context.clone(),
+        outcome,
+      })
+      .await;
+  }
+}
+
+#[async_trait]
+impl<S: BlobStorageLike + ?Sized> BlobStorageLike for AuditedBlobStorage<S> {
+  async fn put_stream(
+    &self,
+    key: &BlobKey,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let counter = Arc::new(AtomicU64::new(0));
+    let data = {
+      let counter = counter.clone();
+      Box::pin(data.inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+          counter.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }
+      }))
+    };
+
+    let result = self.inner.put_stream(key, data, options).await;
+    let size = counter.load(Ordering::Relaxed);
+    let outcome = AuditOutcome::from_result(&result);
+    self
+      .record(AuditOperation::Put, key, Some(size), outcome)
+      .await;
+    result
+  }
+
+  // the inner storage may copy the file without reading `data`, so the size
+  // is the file's
+  async fn put_file(
+    &self,
+    key: &BlobKey,
+    path: &Path,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let result = self.inner.put_file(key, path, data, options).await;
+    let size = tokio::fs::metadata(path).await.ok().map(|m| m.len());
+    let outcome = AuditOutcome::from_result(&result);
+    self.record(AuditOperation::Put, key, size, outcome).await;
+    result
+  }
+
+  async fn get_stream(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<ResponseStream> {
+    let result = self.inner.get_stream(key).await;
+    let outcome = AuditOutcome::from_result(&result);
+    self.record(AuditOperation::Get, key, None, outcome).await;
+    result
+  }
+
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    let result = self.inner.get_stream_range(key, range).await;
+    let outcome = AuditOutcome::from_result(&result);
+    self.record(AuditOperation::Get, key, None, outcome).await;
+    result
+  }
+
+  async fn head(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<Option<BlobMetadata>> {
+    let result = self.inner.head(key).await;
+    let size = result
+      .as_ref()
+      .ok()
+      .and_then(|m| m.as_ref().map(|m| m.size));
+    let outcome = AuditOutcome::from_result(&result);
+    self.record(AuditOperation::Head, key, size, outcome).await;
+    result
+  }
+
+  async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    let result = self.inner.delete(key).await;
+    let outcome = AuditOutcome::from_result(&result);
+    self
+      .record(AuditOperation::Delete, key, None, outcome)
+      .await;
+    result
+  }
+
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    let results = self.inner.delete_many(keys).await;
+    for (key, result) in &results {
+      let outcome = AuditOutcome::from_result(result);
+      self
+        .record(AuditOperation::Delete, key, None, outcome)
+        .await;
+    }
+    results
+  }
+
+  async fn copy(&self, from: &BlobKey, to: &BlobKey) -> BlobStorageResult<()> {
+    let result = self.inner.copy(from, to).await;
+    let outcome = AuditOutcome::from_result(&result);
+    self.record(AuditOperation::Copy, to, None, outcome).await;
+    result
+  }
+
+  // listings read no blob contents, so they aren't audited
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    self.inner.list_page(prefix, continuation).await
+  }
+
+  async fn get_presigned_url(
+    &self,
+    key: &BlobKey,
+    expiry: Duration,
+  ) -> BlobStorageResult<String> {
+    let result = self.inner.get_presigned_url(key, expiry).await;
+    let outcome = AuditOutcome::from_result(&result);
+    self
+      .record(AuditOperation::PresignedUrl, key, None, outcome)
+      .await;
+    result
+  }
+
+  async fn get_presigned_post(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: Duration,
+  ) -> BlobStorageResult<PresignedPost> {
+    let result = self.inner.get_presigned_post(key, conditions, expiry).await;
+    let outcome = AuditOutcome::from_result(&result);
+    self
+      .record(AuditOperation::PresignedPost, key, None, outcome)
+      .await;
+    result
+  }
+
+  async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadHandle> {
+    self.inner.create_upload(key, options).await
+  }
+
+  async fn upload_part(
+    &self,
+    handle: &UploadHandle,
+    part_number: u32,
+    data: Bytes,
+  ) -> BlobStorageResult<UploadedPart> {
+    self.inner.upload_part(handle, part_number, data).await
+  }
+
+  async fn complete_upload(
+    &self,
+    handle: &UploadHandle,
+    parts: &[UploadedPart],
+  ) -> BlobStorageResult<()> {
+    let result = self.inner.complete_upload(handle, parts).await;
+    let size = parts.iter().map(|p| p.size).sum();
+    let outcome = AuditOutcome::from_result(&result);
+    self
+      .record(AuditOperation::Put, &handle.key, Some(size), outcome)
+      .await;
+    result
+  }
+
+  async fn abort_upload(&self, handle: &UploadHandle) -> BlobStorageResult<()> {
+    self.inner.abort_upload(handle).await
+  }
+
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    let result = self.inner.set_retention(key, until).await;
+    let outcome = AuditOutcome::from_result(&result);
+    self
+      .record(AuditOperation::SetRetention, key, None, outcome)
+      .await;
+    result
+  }
+
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    let result = self.inner.set_legal_hold(key, hold).await;
+    let outcome = AuditOutcome::from_result(&result);
+    self
+      .record(AuditOperation::SetLegalHold, key, None, outcome)
+      .await;
+    result
+  }
+}
@@ -0,0 +1,192 @@
+//! A blocking interface to [`BlobStorage`](crate::BlobStorage), for use
+//! outside of an async runtime.
+//!
+//! Each [`BlobStorage`] drives the async interface on an internal
+//! current-thread runtime. Its methods must not be called from within an
+//! async runtime, as blocking on a runtime from inside another panics.
+//!
+//! Blobs are read and written whole rather than streamed.
+
+use std::{fmt, path::Path, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::{TryStreamExt, stream};
+use storage_core::{
+  BlobKey, BlobMetadata, BlobStorageResult, Bytes, PostCondition,
+  PresignedPost, StorageStats, UploadOptions,
+};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::StorageConfig;
+
+/// Builds the runtime a blocking wrapper drives its futures on.
+fn build_runtime() -> BlobStorageResult<Arc<Runtime>> {
+  Ok(
+    Builder::new_current_thread()
+      .enable_all()
+      .build()
+      .map(Arc::new)?,
+  )
+}
+
+/// A blocking frontend for a cloud storage interface.
+///
+/// Clones share the same underlying storage and runtime.
+#[derive(Clone)]
+pub struct BlobStorage {
+  inner:   crate::BlobStorage,
+  runtime: Arc<Runtime>,
+}
+
+impl fmt::Debug for BlobStorage {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("BlobStorage").finish_non_exhaustive()
+  }
+}
+
+impl BlobStorage {
+  /// Wraps an async [`BlobStorage`](crate::BlobStorage) in a blocking
+  /// interface.
+  pub fn from_async(inner: crate::BlobStorage) -> BlobStorageResult<Self> {
+    Ok(Self {
+      inner,
+      runtime: build_runtime()?,
+    })
+  }
+
+  /// Creates a new [`BlobStorage`] from an S3 bucket.
+  pub fn new_s3_bucket(
+    bucket: &str,
+    region: &str,
+    endpoint: &str,
+    access_key: Option<&str>,
+    secret_access_key: Option<&str>,
+  ) -> BlobStorageResult<Self> {
+    Self::from_async(crate::BlobStorage::new_s3_bucket(
+      bucket,
+      region,
+      endpoint,
+      access_key,
+      secret_access_key,
+    )?)
+  }
+
+  /// Creates a new [`BlobStorage`] from an in-memory store.
+  pub fn new_memory() -> BlobStorageResult<Self> {
+    Self::from_async(crate::BlobStorage::new_memory())
+  }
+
+  /// Creates a new [`BlobStorage`] from a filesystem path.
+  pub fn new_fs<P: AsRef<Path> + fmt::Debug>(
+    root_path: P,
+  ) -> BlobStorageResult<Self> {
+    let runtime = build_runtime()?;
+    let inner = runtime.block_on(crate::BlobStorage::new_fs(root_path))?;
+    Ok(Self { inner, runtime })
+  }
+
+  /// Creates a new [`BlobStorage`] from a [`StorageConfig`].
+  pub fn from_config(config: StorageConfig) -> BlobStorageResult<Self> {
+    let runtime = build_runtime()?;
+    let inner = runtime.block_on(crate::BlobStorage::from_config(config))?;
+    Ok(Self { inner, runtime })
+  }
+
+  /// The async [`BlobStorage`](crate::BlobStorage) this wraps.
+  #[must_use]
+  pub const fn as_async(&self) -> &crate::BlobStorage { &self.inner }
+
+  /// Upload data to a blob
+  pub fn put(
+    &self,
+    key: &BlobKey,
+    data: impl Into<Bytes>,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let data = data.into();
+    self.runtime.block_on(self.inner.put_stream(
+      key,
+      Box::pin(stream::once(async move { Ok(data) })),
+      options,
+    ))
+  }
+  /// Download the contents of a blob
+  pub fn get(&self, key: &BlobKey) -> BlobStorageResult<Bytes> {
+    self.runtime.block_on(async {
+      let chunks: Vec<Bytes> =
+        self.inner.get_stream(key).await?.try_collect().await?;
+      Ok(Bytes::from(chunks.concat()))
+    })
+  }
+  /// Get metadata for a blob without downloading content
+  pub fn head(&self, key: &BlobKey) -> BlobStorageResult<Option<BlobMetadata>> {
+    self.runtime.block_on(self.inner.head(key))
+  }
+  /// Delete a blob
+  pub fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    self.runtime.block_on(self.inner.delete(key))
+  }
+  /// Delete many blobs, returning the result for each key in order
+  #[must_use]
+  pub fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    self.runtime.block_on(self.inner.delete_many(keys))
+  }
+  /// Copy a blob to another key, overwriting any blob there
+  pub fn copy(&self, from: &BlobKey, to: &BlobKey) -> BlobStorageResult<()> {
+    self.runtime.block_on(self.inner.copy(from, to))
+  }
+  /// Compute usage statistics for the blobs whose keys start with `prefix`
+  pub fn stats(&self, prefix: &str) -> BlobStorageResult<StorageStats> {
+    self.runtime.block_on(self.inner.stats(prefix))
+  }
+  /// Check if a blob exists
+  pub fn exists(&self, key: &BlobKey) -> BlobStorageResult<bool> {
+    self.runtime.block_on(self.inner.exists(key))
+  }
+  /// Get a pre-signed URL for temporary access (if supported)
+  pub fn get_presigned_url(
+    &self,
+    key: &BlobKey,
+    expiry: Duration,
+  ) -> BlobStorageResult<String> {
+    self
+      .runtime
+      .block_on(self.inner.get_presigned_url(key, expiry))
+  }
+  /// Get a pre-signed form upload for browsers to upload a blob directly
+  /// (if supported)
+  pub fn get_presigned_post(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: Duration,
+  ) -> BlobStorageResult<PresignedPost> {
+    self
+      .runtime
+      .block_on(self.inner.get_presigned_post(key, conditions, expiry))
+  }
+  /// Get the public URL of a blob, if a public base URL is configured
+  #[must_use]
+  pub fn get_public_url(&self, key: &BlobKey) -> Option<String> {
+    self.inner.get_public_url(key)
+  }
+  /// Retain a blob until `until`, rejecting deletes and overwrites until then
+  pub fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self.runtime.block_on(self.inner.set_retention(key, until))
+  }
+  /// Place or lift a legal hold on a blob
+  pub fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self.runtime.block_on(self.inner.set_legal_hold(key, hold))
+  }
+}
@@ -0,0 +1,545 @@
+//! Content-defined chunking with deduplicated, content-addressed storage.
+//!
+//! [`ChunkedBlobStorage`] splits uploads into chunks using `FastCDC`, stores
+//! each chunk under a key derived from its SHA-256 hash, and writes a small
+//! manifest listing the chunks at the blob's own key. Near-identical uploads
+//! share most of their chunks, so only the chunks that changed are stored
+//! again. Downloads read the manifest and reassemble the original stream.
+//!
+//! Deleting a blob only deletes its manifest, as its chunks may be shared
+//! with other blobs. For the same reason, retention and legal holds only
+//! lock the manifest. Chunks no manifest refers to any more are deleted by
+//! [`ChunkedBlobStorage::collect_garbage`]. Resumable uploads and pre-signed
+//! URLs are not supported.
+
+use std::{collections::HashSet, fmt, ops::Range, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt, stream};
+use miette::miette;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use storage_core::{
+  BlobEntry, BlobKey, BlobListPage, BlobMetadata, BlobStorageError,
+  BlobStorageLike, BlobStorageResult, Bytes, RequestStream, ResponseStream,
+  UploadOptions,
+};
+use tracing::{debug, instrument};
+
+/// The current manifest format version.
+const MANIFEST_VERSION: u32 = 1;
+
+/// Options controlling how streams are split into chunks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkingOptions {
+  /// The minimum chunk size in bytes.
+  pub min_size:     usize,
+  /// The target average chunk size in bytes. Must be a power of two.
+  pub avg_size:     usize,
+  /// The maximum chunk size in bytes.
+  pub max_size:     usize,
+  /// The key prefix chunks are stored under.
+  pub chunk_prefix: String,
+}
+
+impl Default for ChunkingOptions {
+  fn default() -> Self {
+    Self {
+      min_size:     256 * 1024,
+      avg_size:     1024 * 1024,
+      max_size:     4 * 1024 * 1024,
+      chunk_prefix: "chunks".to_owned(),
+    }
+  }
+}
+
+impl ChunkingOptions {
+  fn validate(&self) -> BlobStorageResult<()> {
+    if !self.avg_size.is_power_of_two() {
+      return Err(BlobStorageError::InvalidConfig(miette!(
+        "average chunk size must be a power of two, got {}",
+        self.avg_size
+      )));
+    }
+    if self.avg_size < 4 {
+      return Err(BlobStorageError::InvalidConfig(miette!(
+        "average chunk size must be at least 4 bytes"
+      )));
+    }
+    if !(self.min_size <= self.avg_size && self.avg_size <= self.max_size) {
+      return Err(BlobStorageError::InvalidConfig(miette!(
+        "chunk sizes must satisfy min <= avg <= max, got {} / {} / {}",
+        self.min_size,
+        self.avg_size,
+        self.max_size
+      )));
+    }
+    Ok(())
+  }
+}
+
+/// Generates the gear hash table from a fixed seed with `SplitMix64`.
+///
+/// The table must never change: chunk boundaries, and therefore
+/// deduplication against previously stored chunks, depend on it.
+const fn gear_table() -> [u64; 256] {
+  let mut table = [0; 256];
+  let mut state: u64 = 0x5041_4c49_4e43_4443;
+  let mut i = 0;
+  while i < 256 {
+    state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    table[i] = z ^ (z >> 31);
+    i += 1;
+  }
+  table
+}
+
+static GEAR: [u64; 256] = gear_table();
+
+/// A `FastCDC` chunker with normalized chunking.
+#[derive(Clone, Copy, Debug)]
+struct Chunker {
+  min_size: usize,
+  avg_size: usize,
+  max_size: usize,
+  mask_s:   u64,
+  mask_l:   u64,
+}
+
+impl Chunker {
+  const fn new(options: &ChunkingOptions) -> Self {
+    let bits = options.avg_size.ilog2();
+    Self {
+      min_size: options.min_size,
+      avg_size: options.avg_size,
+      max_size: options.max_size,
+      // harder to match below the average size, easier above it
+      mask_s:   u64::MAX << (64 - (bits + 1)),
+      mask_l:   u64::MAX << (64 - (bits - 1)),
+    }
+  }
+
+  /// Finds the length of the first chunk in `data`.
+  ///
+  /// The result only depends on the first `max_size` bytes, so callers must
+  /// buffer at least that much unless the stream has ended.
+  fn cut_point(&self, data: &[u8]) -> usize {
+    if data.len() <= self.min_size {
+      return data.len();
+    }
+    let end = data.len().min(self.max_size);
+    let normal = end.min(self.avg_size);
+
+    let mut hash: u64 = 0;
+    for (i, byte) in data.iter().enumerate().take(normal).skip(self.min_size) {
+      hash = (hash << 1).wrapping_add(GEAR[usize::from(*byte)]);
+      if hash & self.mask_s == 0 {
+        return i + 1;
+      }
+    }
+    for (i, byte) in data.iter().enumerate().take(end).skip(normal) {
+      hash = (hash << 1).wrapping_add(GEAR[usize::from(*byte)]);
+      if hash & self.mask_l == 0 {
+        return i + 1;
+      }
+    }
+    end
+  }
+}
+
+/// A reference to a stored chunk.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ChunkRef {
+  hash: String,
+  size: u64,
+}
+
+/// The manifest stored at a chunked blob's key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Manifest {
+  version: u32,
+  size:    u64,
+  chunks:  Vec<ChunkRef>,
+}
+
+/// A [`BlobStorageLike`] decorator which stores blobs as deduplicated,
+/// content-defined chunks.
+pub struct ChunkedBlobStorage<S: ?Sized> {
+  inner:   Arc<S>,
+  chunker: Chunker,
+  prefix:  String,
+}
+
+impl<S: ?Sized> Clone for ChunkedBlobStorage<S> {
+  fn clone(&self) -> Self {
+    Self {
+      inner:   self.inner.clone(),
+      chunker: self.chunker,
+      prefix:  self.prefix.clone(),
+    }
+  }
+}
+
+impl<S: ?Sized> fmt::Debug for ChunkedBlobStorage<S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ChunkedBlobStorage")
+      .field("chunker", &self.chunker)
+      .field("prefix", &self.prefix)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<S: BlobStorageLike + ?Sized + 'static> ChunkedBlobStorage<S> {
+  /// Wraps `inner`, chunking uploads according to `options`.
+  pub fn new(
+    inner: Arc<S>,
+    options: &ChunkingOptions,
+  ) -> BlobStorageResult<Self> {
+    options.validate()?;
+    Ok(Self {
+      inner,
+      chunker: Chunker::new(options),
+      prefix: options.chunk_prefix.clone(),
+    })
+  }
+
+  fn chunk_key(prefix: &str, hash: &str) -> BlobKey {
+    BlobKey::new(format!("{prefix}/{hash}"))
+  }
+
+  /// Stores a chunk unless a chunk with the same contents already exists.
+  async fn store_chunk(&self, chunk: Bytes) -> BlobStorageResult<ChunkRef> {
+    let hash = format!("{:x}", Sha256::digest(&chunk));
+    let key = Self::chunk_key(&self.prefix, &hash);
+    let size = chunk.len() as u64;
+
+    if self.inner.head(&key).await?.is_some() {
+      debug!(%key, size, "chunk already stored");
+    } else {
+      debug!(%key, size, "storing new chunk");
+      self
+        .inner
+        .put_stream(
+          &key,
+          Box::pin(stream::once(async move { Ok(chunk) })),
+          UploadOptions {
+            overwrite: true,
+            ..UploadOptions::default()
+          },
+        )
+        .await?;
+    }
+
+    Ok(ChunkRef { hash, size })
+  }
+
+  async fn read_manifest(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<Option<Manifest>> {
+    let stream = match self.inner.get_stream(key).await {
+      Ok(stream) => stream,
+      Err(BlobStorageError::NotFound(_)) => return Ok(None),
+      Err(e) => return Err(e),
+    };
+    let data: Vec<Bytes> = stream.try_collect().await?;
+    let manifest: Manifest = serde_json::from_slice(&data.concat())
+      .map_err(|e| BlobStorageError::SerializationError(miette!(e)))?;
+
+    if manifest.version != MANIFEST_VERSION {
+      return Err(BlobStorageError::SerializationError(miette!(
+        "unsupported chunk manifest version {}",
+        manifest.version
+      )));
+    }
+    Ok(Some(manifest))
+  }
+
+  /// Lists every blob in the inner storage under `prefix`.
+  async fn list_all(&self, prefix: &str) -> BlobStorageResult<Vec<BlobEntry>> {
+    let mut entries = Vec::new();
+    let mut continuation = None;
+    loop {
+      let page = self.inner.list_page(prefix, continuation).await?;
+      entries.extend(page.entries);
+      continuation = page.continuation;
+      if continuation.is_none() {
+        return Ok(entries);
+      }
+    }
+  }
+
+  /// Delete chunks which no manifest refers to and which were stored at
+  /// least `grace` ago, returning how many were deleted. Chunks without a
+  /// modification time are kept.
+  ///
+  /// An upload writes its manifest after its chunks, so the grace period
+  /// must be longer than any upload takes, or the chunks of an upload in
+  /// progress may be deleted. An upload which reuses an unreferenced chunk
+  /// while it's being deleted can still lose it, so uploads should not run
+  /// at the same time as collection.
+  pub async fn collect_garbage(
+    &self,
+    grace: Duration,
+  ) -> BlobStorageResult<usize> {
+    let cutoff = clock::before(Utc::now(), grace);
+    let chunk_prefix = format!("{}/", self.prefix);
+
+    // chunks are listed first, so chunks of manifests written meanwhile are
+    // either referenced or too new to delete
+    let chunks = self.list_all(&chunk_prefix).await?;
+    let mut referenced = HashSet::new();
+    for entry in self.list_all("").await? {
+      if entry.key.as_str().starts_with(&chunk_prefix) {
+        continue;
+      }
+      // deleted since it was listed
+      let Some(manifest) = self.read_manifest(&entry.key).await? else {
+        continue;
+      };
+      referenced.extend(manifest.chunks.into_iter().map(|chunk| chunk.hash));
+    }
+
+    let garbage: Vec<BlobKey> = chunks
+      .into_iter()
+      .filter(|entry| {
+        let hash = &entry.key.as_str()[chunk_prefix.len()..];
+        !referenced.contains(hash)
+          && stored_at(entry).is_some_and(|t| t <= cutoff)
+      })
+      .map(|entry| entry.key)
+      .collect();
+
+    let mut deleted = 0;
+    for (_, result) in self.inner.delete_many(&garbage).await {
+      match result {
+        Ok(()) => deleted += 1,
+        // collected concurrently
+        Err(BlobStorageError::NotFound(_)) => {}
+        Err(e) => return Err(e),
+      }
+    }
+    debug!(deleted, "deleted unreferenced chunks");
+    Ok(deleted)
+  }
+}
+
+/// When a chunk was stored.
+fn stored_at(entry: &BlobEntry) -> Option<DateTime<Utc>> {
+  let last_modified = entry.metadata.last_modified.as_deref()?;
+  DateTime::parse_from_rfc3339(last_modified)
+    .ok()
+    .map(|t| t.to_utc())
+}
+
+#[async_trait]
+impl<S: BlobStorageLike + ?Sized + 'static> BlobStorageLike
+  for ChunkedBlobStorage<S>
+{
+  #[instrument(skip(self, data), fields(key = %key))]
+  async fn put_stream(
+    &self,
+    key: &BlobKey,
+    mut data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    if !options.overwrite && self.inner.head(key).await?.is_some() {
+      return Err(BlobStorageError::AlreadyExists(key.clone()));
+    }
+
+    let mut buffer = BytesMut::new();
+    let mut chunks = Vec::new();
+    let mut size = 0;
+    let mut ended = false;
+
+    while !ended {
+      match data.next().await {
+        Some(Ok(bytes)) => buffer.extend_from_slice(&bytes),
+        Some(Err(e)) => {
+          return Err(BlobStorageError::StreamError(miette!(e)));
+        }
+        None => ended = true,
+      }
+
+      while buffer.len() >= self.chunker.max_size
+        || (ended && !buffer.is_empty())
+      {
+        let cut = self.chunker.cut_point(&buffer);
+        let chunk = self.store_chunk(buffer.split_to(cut).freeze()).await?;
+        size += chunk.size;
+        chunks.push(chunk);
+      }
+    }
+
+    debug!(size, chunk_count = chunks.len(), "writing chunk manifest");
+    let manifest = serde_json::to_vec(&Manifest {
+      version: MANIFEST_VERSION,
+      size,
+      chunks,
+    })
+    .map_err(|e| BlobStorageError::SerializationError(miette!(e)))?;
+
+    self
+      .inner
+      .put_stream(
+        key,
+        Box::pin(stream::once(async move { Ok(Bytes::from(manifest)) })),
+        options,
+      )
+      .await
+  }
+
+  #[instrument(skip(self), fields(key = %key))]
+  async fn get_stream(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<ResponseStream> {
+    let manifest = self
+      .read_manifest(key)
+      .await?
+      .ok_or_else(|| BlobStorageError::NotFound(key.clone()))?;
+
+    let inner = self.inner.clone();
+    let prefix = self.prefix.clone();
+    let chunks = stream::iter(manifest.chunks)
+      .then(move |chunk| {
+        let inner = inner.clone();
+        let key = Self::chunk_key(&prefix, &chunk.hash);
+        async move { inner.get_stream(&key).await }
+      })
+      .try_flatten();
+
+    Ok(Box::pin(chunks))
+  }
+
+  // only the chunks overlapping the range are read
+  #[instrument(skip(self), fields(key = %key))]
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    let manifest = self
+      .read_manifest(key)
+      .await?
+      .ok_or_else(|| BlobStorageError::NotFound(key.clone()))?;
+
+    let mut offset = 0;
+    let mut parts = Vec::new();
+    for chunk in manifest.chunks {
+      let chunk_range = offset..offset + chunk.size;
+      offset += chunk.size;
+      if chunk_range.end <= range.start || chunk_range.start >= range.end {
+        continue;
+      }
+      let start = range.start.saturating_sub(chunk_range.start);
+      let end = range.end.min(chunk_range.end) - chunk_range.start;
+      parts.push((chunk, start..end));
+    }
+
+    let inner = self.inner.clone();
+    let prefix = self.prefix.clone();
+    let chunks = stream::iter(parts)
+      .then(move |(chunk, part)| {
+        let inner = inner.clone();
+        let key = Self::chunk_key(&prefix, &chunk.hash);
+        async move {
+          if part.start == 0 && part.end == chunk.size {
+            inner.get_stream(&key).await
+          } else {
+            inner.get_stream_range(&key, part).await
+          }
+        }
+      })
+      .try_flatten();
+
+    Ok(Box::pin(chunks))
+  }
+
+  async fn head(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<Option<BlobMetadata>> {
+    let Some(metadata) = self.inner.head(key).await? else {
+      return Ok(None);
+    };
+    let Some(manifest) = self.read_manifest(key).await? else {
+      return Ok(None);
+    };
+
+    Ok(Some(BlobMetadata {
+      size: manifest.size,
+      ..metadata
+    }))
+  }
+
+  async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    self.inner.delete(key).await
+  }
+
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    self.inner.delete_many(keys).await
+  }
+
+  /// Lists the blobs' manifests, skipping stored chunks and reporting each
+  /// blob's original size.
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    let page = self.inner.list_page(prefix, continuation).await?;
+    let chunk_prefix = format!("{}/", self.prefix);
+
+    let mut entries = Vec::with_capacity(page.entries.len());
+    for mut entry in page.entries {
+      if entry.key.as_str().starts_with(&chunk_prefix) {
+        continue;
+      }
+      let Some(manifest) = self.read_manifest(&entry.key).await? else {
+        continue;
+      };
+      entry.metadata.size = manifest.size;
+      entries.push(entry);
+    }
+
+    Ok(BlobListPage {
+      entries,
+      continuation: page.continuation,
+    })
+  }
+
+  async fn get_presigned_url(
+    &self,
+    _key: &BlobKey,
+    _expiry: Duration,
+  ) -> BlobStorageResult<String> {
+    Err(BlobStorageError::InvalidInput(miette!(
+      "pre-signed URLs are not supported for chunked blobs"
+    )))
+  }
+
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_retention(key, until).await
+  }
+
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_legal_hold(key, hold).await
+  }
+}
@@ -0,0 +1,262 @@
+//! Configuration for selecting and building a storage backend.
+
+use std::{fmt, path::PathBuf};
+
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+
+/// The environment variable suffixes read by [`StorageConfig::from_env`].
+mod vars {
+  pub const BACKEND: &str = "BACKEND";
+  pub const BUCKET: &str = "BUCKET";
+  pub const REGION: &str = "REGION";
+  pub const ENDPOINT: &str = "ENDPOINT";
+  pub const ACCOUNT_ID: &str = "ACCOUNT_ID";
+  pub const ACCESS_KEY: &str = "ACCESS_KEY";
+  pub const SECRET_ACCESS_KEY: &str = "SECRET_ACCESS_KEY";
+  pub const PATH: &str = "PATH";
+  pub const DEDUPE: &str = "DEDUPE";
+  pub const WEAK_ETAGS: &str = "WEAK_ETAGS";
+}
+
+/// Errors produced while loading or validating a [`StorageConfig`].
+#[derive(Debug, thiserror::Error, Diagnostic)]
+pub enum StorageConfigError {
+  /// A required environment variable is not set.
+  #[error("missing environment variable `{0}`")]
+  #[diagnostic(help("set the variable or choose a different backend"))]
+  MissingVar(String),
+
+  /// An environment variable is set but not valid unicode.
+  #[error("environment variable `{0}` is not valid unicode")]
+  NotUnicode(String),
+
+  /// A flag environment variable is set to something other than a boolean.
+  #[error("environment variable `{0}` must be `true` or `false`")]
+  NotBool(String),
+
+  /// The backend name is not recognized.
+  #[error("unknown storage backend `{0}`")]
+  #[diagnostic(help("expected one of `s3`, `r2`, `fs`, or `memory`"))]
+  UnknownBackend(String),
+
+  /// A required field is empty.
+  #[error("storage config field `{0}` must not be empty")]
+  EmptyField(&'static str),
+}
+
+/// Configuration selecting a storage backend and its settings.
+///
+/// When deserialized, the backend is selected by a `backend` field, e.g.
+/// `{ "backend": "fs", "root_path": "/var/lib/blobs" }`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+  /// An S3-compatible bucket.
+  S3 {
+    /// The bucket name.
+    bucket:            String,
+    /// The bucket region.
+    region:            String,
+    /// The S3 endpoint URL.
+    endpoint:          String,
+    /// The access key, if not using ambient credentials.
+    #[serde(default)]
+    access_key:        Option<String>,
+    /// The secret access key, if not using ambient credentials.
+    #[serde(default)]
+    secret_access_key: Option<String>,
+  },
+  /// A Cloudflare R2 bucket.
+  R2 {
+    /// The bucket name.
+    bucket:            String,
+    /// The Cloudflare account ID owning the bucket.
+    account_id:        String,
+    /// The access key.
+    access_key:        String,
+    /// The secret access key.
+    secret_access_key: String,
+  },
+  /// A directory on the local filesystem.
+  Fs {
+    /// The root directory blobs are stored under.
+    root_path:  PathBuf,
+    /// Whether to hard-link blobs with identical contents to store them
+    /// once.
+    #[serde(default)]
+    dedupe:     bool,
+    /// Whether to derive weak `ETag`s for blobs without metadata sidecars.
+    #[serde(default)]
+    weak_etags: bool,
+  },
+  /// An in-memory store, discarded when dropped.
+  Memory,
+}
+
+impl fmt::Debug for StorageConfig {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    const REDACTED: &str = "<redacted>";
+    match self {
+      StorageConfig::S3 {
+        bucket,
+        region,
+        endpoint,
+        access_key,
+        secret_access_key,
+      } => f
+        .debug_struct("S3")
+        .field("bucket", bucket)
+        .field("region", region)
+        .field("endpoint", endpoint)
+        .field("access_key", access_key)
+        .field(
+          "secret_access_key",
+          &secret_access_key.as_ref().map(|_| REDACTED),
+        )
+        .finish(),
+      StorageConfig::R2 {
+        bucket,
+        account_id,
+        access_key,
+        ..
+      } => f
+        .debug_struct("R2")
+        .field("bucket", bucket)
+        .field("account_id", account_id)
+        .field("access_key", access_key)
+        .field("secret_access_key", &REDACTED)
+        .finish(),
+      StorageConfig::Fs {
+        root_path,
+        dedupe,
+        weak_etags,
+      } => f
+        .debug_struct("Fs")
+        .field("root_path", root_path)
+        .field("dedupe", dedupe)
+        .field("weak_etags", weak_etags)
+        .finish(),
+      StorageConfig::Memory => f.write_str("Memory"),
+    }
+  }
+}
+
+impl StorageConfig {
+  /// Loads a [`StorageConfig`] from environment variables.
+  ///
+  /// Variables are named `{prefix}_{NAME}`, e.g. with the prefix `STORAGE`:
+  /// - `STORAGE_BACKEND`: one of `s3`, `r2`, `fs`, or `memory`.
+  /// - `s3`: `STORAGE_BUCKET`, `STORAGE_REGION`, `STORAGE_ENDPOINT`, and
+  ///   optionally `STORAGE_ACCESS_KEY` and `STORAGE_SECRET_ACCESS_KEY`.
+  /// - `r2`: `STORAGE_BUCKET`, `STORAGE_ACCOUNT_ID`, `STORAGE_ACCESS_KEY`, and
+  ///   `STORAGE_SECRET_ACCESS_KEY`.
+  /// - `fs`: `STORAGE_PATH`, and optionally the flags `STORAGE_DEDUPE` and
+  ///   `STORAGE_WEAK_ETAGS`, which are `true` or `false`.
+  pub fn from_env(prefix: &str) -> Result<Self, StorageConfigError> {
+    Self::from_lookup(prefix, |name| match std::env::var(name) {
+      Ok(value) => Ok(Some(value)),
+      Err(std::env::VarError::NotPresent) => Ok(None),
+      Err(std::env::VarError::NotUnicode(_)) => {
+        Err(StorageConfigError::NotUnicode(name.to_owned()))
+      }
+    })
+  }
+
+  /// Loads a [`StorageConfig`] using `lookup` to read variables by name.
+  pub(crate) fn from_lookup<F>(
+    prefix: &str,
+    lookup: F,
+  ) -> Result<Self, StorageConfigError>
+  where
+    F: Fn(&str) -> Result<Option<String>, StorageConfigError>,
+  {
+    let name = |suffix: &str| {
+      if prefix.is_empty() {
+        suffix.to_owned()
+      } else {
+        format!("{prefix}_{suffix}")
+      }
+    };
+    let optional = |suffix: &str| lookup(&name(suffix));
+    let required = |suffix: &str| {
+      optional(suffix)?
+        .ok_or_else(|| StorageConfigError::MissingVar(name(suffix)))
+    };
+    let flag = |suffix: &str| match optional(suffix)? {
+      None => Ok(false),
+      Some(value) => match value.to_lowercase().as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(StorageConfigError::NotBool(name(suffix))),
+      },
+    };
+
+    let config = match required(vars::BACKEND)?.to_lowercase().as_str() {
+      "s3" => StorageConfig::S3 {
+        bucket:            required(vars::BUCKET)?,
+        region:            required(vars::REGION)?,
+        endpoint:          required(vars::ENDPOINT)?,
+        access_key:        optional(vars::ACCESS_KEY)?,
+        secret_access_key: optional(vars::SECRET_ACCESS_KEY)?,
+      },
+      "r2" => StorageConfig::R2 {
+        bucket:            required(vars::BUCKET)?,
+        account_id:        required(vars::ACCOUNT_ID)?,
+        access_key:        required(vars::ACCESS_KEY)?,
+        secret_access_key: required(vars::SECRET_ACCESS_KEY)?,
+      },
+      "fs" => StorageConfig::Fs {
+        root_path:  required(vars::PATH)?.into(),
+        dedupe:     flag(vars::DEDUPE)?,
+        weak_etags: flag(vars::WEAK_ETAGS)?,
+      },
+      "memory" => StorageConfig::Memory,
+      other => {
+        return Err(StorageConfigError::UnknownBackend(other.to_owned()));
+      }
+    };
+
+    config.validate()?;
+    Ok(config)
+  }
+
+  /// Checks that all required fields are set.
+  pub fn validate(&self) -> Result<(), StorageConfigError> {
+    let non_empty = |field: &'static str, value: &str| {
+      if value.trim().is_empty() {
+        Err(StorageConfigError::EmptyField(field))
+      } else {
+        Ok(())
+      }
+    };
+
+    match self {
+      StorageConfig::S3 {
+        bucket,
+        region,
+        endpoint,
+        ..
+      } => {
+        non_empty("bucket", bucket)?;
+        non_empty("region", region)?;
+        non_empty("endpoint", endpoint)
+      }
+      StorageConfig::R2 {
+        bucket,
+        account_id,
+        access_key,
+        secret_access_key,
+      } => {
+        non_empty("bucket", bucket)?;
+        non_empty("account_id", account_id)?;
+        non_empty("access_key", access_key)?;
+        non_empty("secret_access_key", secret_access_key)
+      }
+      StorageConfig::Fs { root_path, .. } => {
+        non_empty("root_path", &root_path.to_string_lossy())
+      }
+      StorageConfig::Memory => Ok(()),
+    }
+  }
+}
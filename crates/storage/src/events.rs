@@ -0,0 +1,320 @@
+//! Event notifications for changes to blobs.
+
+use std::{
+  fmt,
+  ops::Range,
+  path::Path,
+  sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+  },
+  time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, channel::mpsc};
+use serde::{Deserialize, Serialize};
+use storage_core::{
+  BlobKey, BlobListPage, BlobMetadata, BlobStorageLike, BlobStorageResult,
+  Bytes, PostCondition, PresignedPost, RequestStream, ResponseStream,
+  UploadHandle, UploadOptions, UploadedPart,
+};
+use tracing::warn;
+
+/// What happened to the blob a [`BlobEvent`] is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobEventKind {
+  /// A blob was uploaded where none existed.
+  Created,
+  /// A blob was uploaded over an existing one.
+  Overwritten,
+  /// A blob was deleted.
+  Deleted,
+}
+
+impl fmt::Display for BlobEventKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      BlobEventKind::Created => "created",
+      BlobEventKind::Overwritten => "overwritten",
+      BlobEventKind::Deleted => "deleted",
+    })
+  }
+}
+
+/// A notification that a blob changed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobEvent {
+  /// When the change completed.
+  pub timestamp: DateTime<Utc>,
+  /// What happened to the blob.
+  pub kind:      BlobEventKind,
+  /// The blob that changed.
+  pub key:       BlobKey,
+  /// The blob's new size in bytes, for uploads.
+  pub size:      Option<u64>,
+}
+
+/// A destination for [`BlobEvent`]s.
+///
+/// Sinks are responsible for handling their own failures; a notification
+/// failure never fails the storage operation.
+#[async_trait]
+pub trait BlobEventSink: Send + Sync {
+  /// Deliver a blob event.
+  async fn notify(&self, event: BlobEvent);
+}
+
+/// A [`BlobEventSink`] that sends events over a channel.
+#[derive(Clone, Debug)]
+pub struct ChannelEventSink {
+  sender: mpsc::UnboundedSender<BlobEvent>,
+}
+
+impl ChannelEventSink {
+  /// Creates a new [`ChannelEventSink`] along with the receiving end of its
+  /// channel.
+  #[must_use]
+  pub fn new() -> (Self, mpsc::UnboundedReceiver<BlobEvent>) {
+    let (sender, receiver) = mpsc::unbounded();
+    (Self { sender }, receiver)
+  }
+}
+
+#[async_trait]
+impl BlobEventSink for ChannelEventSink {
+  async fn notify(&self, event: BlobEvent) {
+    if self.sender.unbounded_send(event).is_err() {
+      warn!("blob event channel closed, dropping blob event");
+    }
+  }
+}
+
+/// A [`BlobStorageLike`] decorator that sends a [`BlobEvent`] to a sink for
+/// every successful upload and delete on the inner storage.
+///
+/// Whether an upload created or overwrote a blob is decided by checking for
+/// the blob before uploading, so concurrent uploads to a new key may both be
+/// reported as [`Created`](BlobEventKind::Created). Deletes are reported
+/// whether or not the blob existed. Resumable uploads are reported once they
+/// are completed. Pre-signed form uploads are not supported, as they would go
+/// unreported.
+pub struct NotifyingBlobStorage<S: ?Sized> {
+  inner: Arc<S>,
+  sink:  Arc<dyn BlobEventSink>,
+}
+
+impl<S: ?Sized> Clone for NotifyingBlobStorage<S> {
+  fn clone(&self) -> Self {
+    Self {
+      inner: self.inner.clone(),
+      sink:  self.sink.clone(),
+    }
+  }
+}
+
+impl<S: ?Sized> fmt::Debug for NotifyingBlobStorage<S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("NotifyingBlobStorage")
+      .finish_non_exhaustive()
+  }
+}
+
+impl<S: BlobStorageLike + ?Sized> NotifyingBlobStorage<S> {
+  /// Wraps `inner`, sending its changes to `sink`.
+  pub fn new(inner: Arc<S>, sink: Arc<dyn BlobEventSink>) -> Self {
+    Self { inner, sink }
+  }
+
+  /// The kind of event an upload to `key` will be, if it succeeds.
+  async fn upload_kind(&self, key: &BlobKey) -> BlobEventKind {
+    match self.inner.head(key).await {
+      Ok(Some(_)) => BlobEventKind::Overwritten,
+      _ => BlobEventKind::Created,
+    }
+  }
+
+  async fn notify(
+    &self,
+    kind: BlobEventKind,
+    key: &BlobKey,
+    size: Option<u64>,
+  ) {
+    self
+      .sink
+      .notify(BlobEvent {
+        timestamp: Utc::now(),
+        kind,
+        key: key.clone(),
+        size,
+      })
+      .await;
+  }
+}
+
+#[async_trait]
+impl<S: BlobStorageLike + ?Sized> BlobStorageLike for NotifyingBlobStorage<S> {
+  async fn put_stream(
+    &self,
+    key: &BlobKey,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let kind = self.upload_kind(key).await;
+    let counter = Arc::new(AtomicU64::new(0));
+    let data = {
+      let counter = counter.clone();
+      Box::pin(data.inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+          counter.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }
+      }))
+    };
+
+    self.inner.put_stream(key, data, options).await?;
+    let size = counter.load(Ordering::Relaxed);
+    self.notify(kind, key, Some(size)).await;
+    Ok(())
+  }
+
+  // the inner storage may copy the file without reading `data`, so the size
+  // is the file's
+  async fn put_file(
+    &self,
+    key: &BlobKey,
+    path: &Path,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let kind = self.upload_kind(key).await;
+    self.inner.put_file(key, path, data, options).await?;
+    let size = tokio::fs::metadata(path).await.ok().map(|m| m.len());
+    self.notify(kind, key, size).await;
+    Ok(())
+  }
+
+  async fn get_stream(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<ResponseStream> {
+    self.inner.get_stream(key).await
+  }
+
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    self.inner.get_stream_range(key, range).await
+  }
+
+  async fn head(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<Option<BlobMetadata>> {
+    self.inner.head(key).await
+  }
+
+  async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    self.inner.delete(key).await?;
+    self.notify(BlobEventKind::Deleted, key, None).await;
+    Ok(())
+  }
+
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    let results = self.inner.delete_many(keys).await;
+    for (key, _) in results.iter().filter(|(_, result)| result.is_ok()) {
+      self.notify(BlobEventKind::Deleted, key, None).await;
+    }
+    results
+  }
+
+  async fn copy(&self, from: &BlobKey, to: &BlobKey) -> BlobStorageResult<()> {
+    let kind = self.upload_kind(to).await;
+    self.inner.copy(from, to).await?;
+    let size = self.inner.head(to).await.ok().flatten().map(|m| m.size);
+    self.notify(kind, to, size).await;
+    Ok(())
+  }
+
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    self.inner.list_page(prefix, continuation).await
+  }
+
+  async fn get_presigned_url(
+    &self,
+    key: &BlobKey,
+    expiry: Duration,
+  ) -> BlobStorageResult<String> {
+    self.inner.get_presigned_url(key, expiry).await
+  }
+
+  // uploads made with the post go straight to the backend, so they aren't
+  // reported
+  async fn get_presigned_post(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: Duration,
+  ) -> BlobStorageResult<PresignedPost> {
+    self.inner.get_presigned_post(key, conditions, expiry).await
+  }
+
+  async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadHandle> {
+    self.inner.create_upload(key, options).await
+  }
+
+  async fn upload_part(
+    &self,
+    handle: &UploadHandle,
+    part_number: u32,
+    data: Bytes,
+  ) -> BlobStorageResult<UploadedPart> {
+    self.inner.upload_part(handle, part_number, data).await
+  }
+
+  async fn complete_upload(
+    &self,
+    handle: &UploadHandle,
+    parts: &[UploadedPart],
+  ) -> BlobStorageResult<()> {
+    let kind = self.upload_kind(&handle.key).await;
+    self.inner.complete_upload(handle, parts).await?;
+    let size = parts.iter().map(|p| p.size).sum();
+    self.notify(kind, &handle.key, Some(size)).await;
+    Ok(())
+  }
+
+  async fn abort_upload(&self, handle: &UploadHandle) -> BlobStorageResult<()> {
+    self.inner.abort_upload(handle).await
+  }
+
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_retention(key, until).await
+  }
+
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_legal_hold(key, hold).await
+  }
+}
@@ -1,22 +1,62 @@
 //! Frontend for a cloud storage interface.
 
+pub mod audit;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod chunked;
+mod config;
+pub mod events;
+pub mod limit;
+pub mod scan;
+pub mod sniff;
+pub mod staging;
 #[cfg(test)]
 mod tests;
+mod upload;
+pub mod urls;
+#[cfg(feature = "watch")]
+pub mod watch;
 
-use std::{fmt, path::Path, sync::Arc};
+use std::{fmt, ops::Range, path::Path, sync::Arc};
 
-use storage_core::RequestStream;
+use belt::Belt;
+use chrono::{DateTime, Utc};
+pub use clock::{Latency, LatencyProfile};
+use storage_core::BlobStorageLike;
 pub use storage_core::{
-  BlobKey, BlobMetadata, BlobStorageError, BlobStorageResult, Bytes,
-  ResponseStream, UploadOptions,
+  BlobEntry, BlobKey, BlobListPage, BlobMetadata, BlobStorageError,
+  BlobStorageResult, Bytes, CannedAcl, Checksum, KeyParts, KeyTemplate,
+  KeyTemplateError, PostCondition, PresignedPost, RequestStream,
+  ResponseStream, ServerSideEncryption, StorageClass, StorageStats,
+  UploadHandle, UploadOptions, UploadedPart, key_template,
+};
+pub use storage_impl_fs::BlobStorageFilesystem;
+pub use storage_impl_memory::{BlobStorageMemory, MemoryOperation};
+pub use storage_impl_s3::BlobStorageS3;
+
+use self::{
+  audit::{AuditSink, AuditedBlobStorage, OperationContext},
+  chunked::{ChunkedBlobStorage, ChunkingOptions},
+  events::{BlobEventSink, NotifyingBlobStorage},
+  limit::{LimitedBlobStorage, StorageLimits},
+  scan::{ScanPolicy, ScannedBlobStorage},
+  sniff::SniffingBlobStorage,
+  staging::Staging,
+  urls::{UrlConfig, UrlRewrite, UrlSigner},
+};
+pub use self::{
+  config::{StorageConfig, StorageConfigError},
+  upload::{UploadSession, UploadSessionState},
 };
-use storage_impl_fs::BlobStorageFilesystem;
-use storage_impl_memory::BlobStorageMemory;
-use storage_impl_s3::BlobStorageS3;
 
 /// Frontend for a cloud storage interface.
+///
+/// Clones share the same underlying storage.
+#[derive(Clone)]
 pub struct BlobStorage {
-  inner: Arc<dyn storage_core::BlobStorageLike>,
+  inner:   Arc<dyn BlobStorageLike>,
+  urls:    UrlConfig,
+  staging: Arc<Staging>,
 }
 
 impl BlobStorage {
@@ -29,13 +69,15 @@ impl BlobStorage {
     secret_access_key: Option<&str>,
   ) -> BlobStorageResult<Self> {
     Ok(BlobStorage {
-      inner: Arc::new(BlobStorageS3::new(
+      inner:   Arc::new(BlobStorageS3::new(
         bucket,
         region,
         endpoint,
         access_key,
         secret_access_key,
       )?),
+      urls:    UrlConfig::default(),
+      staging: Arc::default(),
     })
   }
 
@@ -43,7 +85,22 @@ impl BlobStorage {
   #[must_use]
   pub fn new_memory() -> Self {
     BlobStorage {
-      inner: Arc::new(BlobStorageMemory::new()),
+      inner:   Arc::new(BlobStorageMemory::new()),
+      urls:    UrlConfig::default(),
+      staging: Arc::default(),
+    }
+  }
+
+  /// Creates a new [`BlobStorage`] from an in-memory store, simulating the
+  /// latency of each operation with the given [`LatencyProfile`].
+  #[must_use]
+  pub fn new_memory_with_latency(
+    latency: LatencyProfile<MemoryOperation>,
+  ) -> Self {
+    BlobStorage {
+      inner:   Arc::new(BlobStorageMemory::new().with_latency(latency)),
+      urls:    UrlConfig::default(),
+      staging: Arc::default(),
     }
   }
 
@@ -52,12 +109,195 @@ impl BlobStorage {
     root_path: P,
   ) -> BlobStorageResult<Self> {
     Ok(BlobStorage {
-      inner: Arc::new(BlobStorageFilesystem::new(root_path).await?),
+      inner:   Arc::new(BlobStorageFilesystem::new(root_path).await?),
+      urls:    UrlConfig::default(),
+      staging: Arc::default(),
     })
   }
+
+  /// Creates a new [`BlobStorage`] from the given backend, for backends
+  /// configured with their builder methods, e.g.
+  /// [`BlobStorageFilesystem::with_dedupe`].
+  #[must_use]
+  pub fn from_backend(backend: impl BlobStorageLike + 'static) -> Self {
+    BlobStorage {
+      inner:   Arc::new(backend),
+      urls:    UrlConfig::default(),
+      staging: Arc::default(),
+    }
+  }
+
+  /// Creates a new [`BlobStorage`] from a [`StorageConfig`].
+  pub async fn from_config(config: StorageConfig) -> BlobStorageResult<Self> {
+    config
+      .validate()
+      .map_err(|e| BlobStorageError::InvalidConfig(e.into()))?;
+
+    match config {
+      StorageConfig::S3 {
+        bucket,
+        region,
+        endpoint,
+        access_key,
+        secret_access_key,
+      } => Self::new_s3_bucket(
+        &bucket,
+        &region,
+        &endpoint,
+        access_key.as_deref(),
+        secret_access_key.as_deref(),
+      ),
+      StorageConfig::R2 {
+        bucket,
+        account_id,
+        access_key,
+        secret_access_key,
+      } => Self::new_s3_bucket(
+        &bucket,
+        "auto",
+        &format!("https://{account_id}.r2.cloudflarestorage.com"),
+        Some(&access_key),
+        Some(&secret_access_key),
+      ),
+      StorageConfig::Fs {
+        root_path,
+        dedupe,
+        weak_etags,
+      } => {
+        let mut backend = BlobStorageFilesystem::new(root_path).await?;
+        if dedupe {
+          backend = backend.with_dedupe();
+        }
+        if weak_etags {
+          backend = backend.with_weak_etags();
+        }
+        Ok(Self::from_backend(backend))
+      }
+      StorageConfig::Memory => Ok(Self::new_memory()),
+    }
+  }
+
+  /// Wraps this [`BlobStorage`] so that every operation is recorded to
+  /// `sink`, attributed to `context`.
+  #[must_use]
+  pub fn audited(
+    self,
+    sink: Arc<dyn AuditSink>,
+    context: OperationContext,
+  ) -> Self {
+    BlobStorage {
+      inner: Arc::new(
+        AuditedBlobStorage::new(self.inner, sink).with_context(context),
+      ),
+      ..self
+    }
+  }
+
+  /// Wraps this [`BlobStorage`] so that blobs are stored as deduplicated,
+  /// content-defined chunks. See [`chunked`] for details.
+  pub fn chunked(self, options: &ChunkingOptions) -> BlobStorageResult<Self> {
+    Ok(BlobStorage {
+      inner: Arc::new(ChunkedBlobStorage::new(self.inner, options)?),
+      ..self
+    })
+  }
+
+  /// Wraps this [`BlobStorage`] so that every upload is scanned by `policy`
+  /// before it becomes visible.
+  #[must_use]
+  pub fn with_scan_policy(self, policy: Arc<dyn ScanPolicy>) -> Self {
+    BlobStorage {
+      inner: Arc::new(ScannedBlobStorage::new(self.inner, policy)),
+      ..self
+    }
+  }
+
+  /// Wraps this [`BlobStorage`] so that every blob created, overwritten or
+  /// deleted through it is reported to `sink`. See [`events`] for details.
+  #[must_use]
+  pub fn with_events(self, sink: Arc<dyn BlobEventSink>) -> Self {
+    BlobStorage {
+      inner: Arc::new(NotifyingBlobStorage::new(self.inner, sink)),
+      ..self
+    }
+  }
+
+  /// Wraps this [`BlobStorage`] so that uploads made without a content type
+  /// have theirs detected from their leading bytes. See [`sniff`] for
+  /// details.
+  #[must_use]
+  pub fn with_content_sniffing(self) -> Self {
+    BlobStorage {
+      inner: Arc::new(SniffingBlobStorage::new(self.inner)),
+      ..self
+    }
+  }
+
+  /// Wraps this [`BlobStorage`] so that its operations are held to `limits`.
+  /// Clones share the limits. See [`limit`] for details.
+  #[must_use]
+  pub fn with_limits(self, limits: StorageLimits) -> Self {
+    BlobStorage {
+      inner: Arc::new(LimitedBlobStorage::new(self.inner, limits)),
+      ..self
+    }
+  }
+
+  /// Sets how long a blob may stay staged before it's considered abandoned
+  /// and deleted. See [`staging`] for details.
+  #[must_use]
+  pub fn with_staging_max_age(mut self, max_age: std::time::Duration) -> Self {
+    self.staging = Arc::new(Staging::new(max_age));
+    self
+  }
+
+  /// Sets the base URL public blobs are served from, e.g. a bucket's public
+  /// endpoint, enabling [`get_public_url`](Self::get_public_url).
+  #[must_use]
+  pub fn with_public_base_url(mut self, url: impl Into<String>) -> Self {
+    self.urls.public_base_url = Some(url.into());
+    self
+  }
+
+  /// Rewrites the URLs this [`BlobStorage`] returns with `rewrite`, e.g. to
+  /// point them at a CDN in front of the bucket. See [`urls`] for details.
+  #[must_use]
+  pub fn with_url_rewrite(mut self, rewrite: UrlRewrite) -> Self {
+    self.urls.rewrite = Some(rewrite);
+    self
+  }
+
+  /// Signs the URLs [`get_presigned_url`](Self::get_presigned_url) returns
+  /// with `signer` rather than the backend, e.g. for a private CDN. See
+  /// [`urls`] for details.
+  #[must_use]
+  pub fn with_url_signer(mut self, signer: Arc<dyn UrlSigner>) -> Self {
+    self.urls.signer = Some(signer);
+    self
+  }
 }
 
 impl BlobStorage {
+  /// Upload a [`Belt`] to a blob. A [`Belt`] made with [`Belt::from_file`]
+  /// is copied straight from its file by backends which can, like the
+  /// filesystem.
+  pub async fn put(
+    &self,
+    key: &BlobKey,
+    data: Belt,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    match data.path_hint().map(Path::to_path_buf) {
+      Some(path) => {
+        self
+          .inner
+          .put_file(key, &path, Box::pin(data), options)
+          .await
+      }
+      None => self.inner.put_stream(key, Box::pin(data), options).await,
+    }
+  }
+
   /// Upload data from a stream to a blob
   pub async fn put_stream(
     &self,
@@ -74,6 +314,15 @@ impl BlobStorage {
   ) -> BlobStorageResult<ResponseStream> {
     self.inner.get_stream(key).await
   }
+  /// Download the bytes in `range` of a blob as a stream, cut short at the
+  /// end of the blob
+  pub async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    self.inner.get_stream_range(key, range).await
+  }
   /// Get metadata for a blob without downloading content
   pub async fn head(
     &self,
@@ -85,16 +334,107 @@ impl BlobStorage {
   pub async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
     self.inner.delete(key).await
   }
+  /// Delete many blobs, returning the result for each key in order
+  pub async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    self.inner.delete_many(keys).await
+  }
+  /// Copy a blob to another key, overwriting any blob there
+  pub async fn copy(
+    &self,
+    from: &BlobKey,
+    to: &BlobKey,
+  ) -> BlobStorageResult<()> {
+    self.inner.copy(from, to).await
+  }
+  /// List a page of blobs whose keys start with `prefix`, ordered by key
+  pub async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    self.inner.list_page(prefix, continuation).await
+  }
+  /// Compute usage statistics for the blobs whose keys start with `prefix`
+  pub async fn stats(&self, prefix: &str) -> BlobStorageResult<StorageStats> {
+    self.inner.stats(prefix).await
+  }
   /// Check if a blob exists
   pub async fn exists(&self, key: &BlobKey) -> BlobStorageResult<bool> {
     Ok(self.inner.head(key).await?.is_some())
   }
-  /// Get a pre-signed URL for temporary access (if supported)
+  /// Get a pre-signed URL for temporary access (if supported). With a
+  /// [`UrlSigner`], the URL is signed by it instead, without checking the
+  /// blob exists or rewriting the URL.
   pub async fn get_presigned_url(
     &self,
     key: &BlobKey,
     expiry: std::time::Duration,
   ) -> BlobStorageResult<String> {
-    self.inner.get_presigned_url(key, expiry).await
+    if let Some(signer) = &self.urls.signer {
+      let expires_at = chrono::Duration::from_std(expiry)
+        .ok()
+        .and_then(|expiry| Utc::now().checked_add_signed(expiry))
+        .ok_or_else(|| {
+          BlobStorageError::InvalidInput(miette::miette!(
+            "expiry {expiry:?} is out of range"
+          ))
+        })?;
+      return signer.signed_url(key, expires_at);
+    }
+    let url = self.inner.get_presigned_url(key, expiry).await?;
+    Ok(self.urls.finish(url))
+  }
+  /// Get a pre-signed form upload for browsers to upload a blob directly
+  /// (if supported)
+  pub async fn get_presigned_post(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: std::time::Duration,
+  ) -> BlobStorageResult<PresignedPost> {
+    self.inner.get_presigned_post(key, conditions, expiry).await
+  }
+  /// Get the public URL of a blob, if a public base URL is configured. The
+  /// URL only works for blobs uploaded with a public [`CannedAcl`], or
+  /// behind a public bucket or CDN.
+  #[must_use]
+  pub fn get_public_url(&self, key: &BlobKey) -> Option<String> {
+    self.urls.public_url(key)
+  }
+  /// Retain a blob until `until`, rejecting deletes and overwrites until then
+  pub async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_retention(key, until).await
+  }
+  /// Place or lift a legal hold on a blob
+  pub async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_legal_hold(key, hold).await
+  }
+  /// Start a resumable upload to a blob
+  pub async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadSession> {
+    let handle = self.inner.create_upload(key, options).await?;
+    Ok(UploadSession::new(self.inner.clone(), UploadSessionState {
+      handle,
+      parts: Vec::new(),
+    }))
+  }
+  /// Resume a resumable upload from its saved state
+  #[must_use]
+  pub fn resume_upload(&self, state: UploadSessionState) -> UploadSession {
+    UploadSession::new(self.inner.clone(), state)
   }
 }
@@ -0,0 +1,342 @@
+//! Client-side concurrency and bandwidth limits for blob storage.
+//!
+//! [`LimitedBlobStorage`] caps how many operations run at once with a
+//! semaphore, and how many bytes per second are uploaded and downloaded in
+//! aggregate with a token bucket. The limits are shared by everything using
+//! the same [`BlobStorage`](crate::BlobStorage), so a background job can be
+//! given its own limited storage without starving interactive traffic on
+//! another.
+
+use std::{
+  fmt,
+  ops::Range,
+  path::Path,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt, stream};
+use storage_core::{
+  BlobKey, BlobListPage, BlobMetadata, BlobStorageLike, BlobStorageResult,
+  Bytes, PostCondition, PresignedPost, RequestStream, ResponseStream,
+  UploadHandle, UploadOptions, UploadedPart,
+};
+use tokio::{
+  sync::{OwnedSemaphorePermit, Semaphore},
+  time::Instant,
+};
+
+/// Limits on the operations of a [`BlobStorage`](crate::BlobStorage).
+///
+/// Unset limits are not enforced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StorageLimits {
+  /// The maximum number of operations in flight at once. Downloads hold
+  /// their slot until their stream is dropped.
+  pub max_concurrent:    Option<usize>,
+  /// The maximum number of bytes uploaded and downloaded per second, in
+  /// aggregate. Up to a second's worth may be transferred in a burst.
+  pub max_bytes_per_sec: Option<u64>,
+}
+
+impl StorageLimits {
+  /// No limits.
+  #[must_use]
+  pub const fn new() -> Self {
+    Self {
+      max_concurrent:    None,
+      max_bytes_per_sec: None,
+    }
+  }
+
+  /// Limit the number of operations in flight at once.
+  #[must_use]
+  pub const fn with_max_concurrent(mut self, max: usize) -> Self {
+    self.max_concurrent = Some(max);
+    self
+  }
+
+  /// Limit the aggregate bytes transferred per second.
+  #[must_use]
+  pub const fn with_max_bytes_per_sec(mut self, max: u64) -> Self {
+    self.max_bytes_per_sec = Some(max);
+    self
+  }
+}
+
+/// A token bucket metering bytes transferred.
+///
+/// Transfers reserve their bytes up front and may drive the bucket into
+/// debt, so chunks larger than the bucket still pass; later transfers wait
+/// until the debt is repaid.
+struct TokenBucket {
+  /// Tokens added per second.
+  rate:     f64,
+  /// The most tokens the bucket holds.
+  capacity: f64,
+  /// The current tokens, and when they were last refilled.
+  state:    Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+  #[allow(clippy::cast_precision_loss)]
+  fn new(bytes_per_sec: u64) -> Self {
+    let rate = bytes_per_sec.max(1) as f64;
+    Self {
+      rate,
+      capacity: rate,
+      state: Mutex::new((rate, Instant::now())),
+    }
+  }
+
+  /// Reserve `bytes`, returning how long to wait before transferring them.
+  #[allow(clippy::cast_precision_loss)]
+  fn reserve(&self, bytes: usize) -> Duration {
+    let mut state = self.state.lock().unwrap();
+    let (tokens, last) = &mut *state;
+    let now = Instant::now();
+    *tokens =
+      (*tokens + (now - *last).as_secs_f64() * self.rate).min(self.capacity);
+    *last = now;
+    *tokens -= bytes as f64;
+
+    if *tokens >= 0.0 {
+      Duration::ZERO
+    } else {
+      Duration::from_secs_f64(-*tokens / self.rate)
+    }
+  }
+
+  /// Wait until `bytes` may be transferred.
+  async fn acquire(&self, bytes: usize) {
+    let wait = self.reserve(bytes);
+    if !wait.is_zero() {
+      tokio::time::sleep(wait).await;
+    }
+  }
+}
+
+/// A [`BlobStorageLike`] decorator enforcing [`StorageLimits`].
+///
+/// Pre-signed URLs are generated without limits, as the transfers they
+/// authorize bypass this client.
+pub struct LimitedBlobStorage<S: ?Sized> {
+  inner:     Arc<S>,
+  semaphore: Option<Arc<Semaphore>>,
+  bandwidth: Option<Arc<TokenBucket>>,
+}
+
+impl<S: ?Sized> Clone for LimitedBlobStorage<S> {
+  fn clone(&self) -> Self {
+    Self {
+      inner:     self.inner.clone(),
+      semaphore: self.semaphore.clone(),
+      bandwidth: self.bandwidth.clone(),
+    }
+  }
+}
+
+impl<S: ?Sized> fmt::Debug for LimitedBlobStorage<S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("LimitedBlobStorage").finish_non_exhaustive()
+  }
+}
+
+impl<S: BlobStorageLike + ?Sized> LimitedBlobStorage<S> {
+  /// Wraps `inner`, enforcing `limits` across all of its operations.
+  pub fn new(inner: Arc<S>, limits: StorageLimits) -> Self {
+    Self {
+      inner,
+      semaphore: limits
+        .max_concurrent
+        .map(|max| Arc::new(Semaphore::new(max.max(1)))),
+      bandwidth: limits
+        .max_bytes_per_sec
+        .map(|max| Arc::new(TokenBucket::new(max))),
+    }
+  }
+
+  /// Wait for a slot to run an operation in.
+  async fn permit(&self) -> Option<OwnedSemaphorePermit> {
+    let semaphore = self.semaphore.clone()?;
+    // the semaphore is never closed
+    Some(semaphore.acquire_owned().await.unwrap())
+  }
+}
+
+/// Meters the chunks of `data` through `bandwidth`, holding `permit` until
+/// the stream is dropped.
+fn throttle<St, E>(
+  data: St,
+  bandwidth: Option<Arc<TokenBucket>>,
+  permit: Option<OwnedSemaphorePermit>,
+) -> impl Stream<Item = Result<Bytes, E>> + Send + 'static
+where
+  St: Stream<Item = Result<Bytes, E>> + Send + Unpin + 'static,
+  E: Send + 'static,
+{
+  stream::unfold(
+    (data, bandwidth, permit),
+    |(mut data, bandwidth, permit)| async move {
+      let chunk = data.next().await?;
+      if let (Some(bandwidth), Ok(chunk)) = (&bandwidth, &chunk) {
+        bandwidth.acquire(chunk.len()).await;
+      }
+      Some((chunk, (data, bandwidth, permit)))
+    },
+  )
+}
+
+#[async_trait]
+impl<S: BlobStorageLike + ?Sized> BlobStorageLike for LimitedBlobStorage<S> {
+  async fn put_stream(
+    &self,
+    key: &BlobKey,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let _permit = self.permit().await;
+    let data = Box::pin(throttle(data, self.bandwidth.clone(), None));
+    self.inner.put_stream(key, data, options).await
+  }
+
+  // files the inner storage copies without reading `data` aren't throttled
+  async fn put_file(
+    &self,
+    key: &BlobKey,
+    path: &Path,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let _permit = self.permit().await;
+    let data = Box::pin(throttle(data, self.bandwidth.clone(), None));
+    self.inner.put_file(key, path, data, options).await
+  }
+
+  async fn get_stream(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<ResponseStream> {
+    let permit = self.permit().await;
+    let data = self.inner.get_stream(key).await?;
+    Ok(Box::pin(throttle(data, self.bandwidth.clone(), permit)))
+  }
+
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    let permit = self.permit().await;
+    let data = self.inner.get_stream_range(key, range).await?;
+    Ok(Box::pin(throttle(data, self.bandwidth.clone(), permit)))
+  }
+
+  async fn head(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<Option<BlobMetadata>> {
+    let _permit = self.permit().await;
+    self.inner.head(key).await
+  }
+
+  async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    let _permit = self.permit().await;
+    self.inner.delete(key).await
+  }
+
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    let _permit = self.permit().await;
+    self.inner.delete_many(keys).await
+  }
+
+  async fn copy(&self, from: &BlobKey, to: &BlobKey) -> BlobStorageResult<()> {
+    let _permit = self.permit().await;
+    self.inner.copy(from, to).await
+  }
+
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    let _permit = self.permit().await;
+    self.inner.list_page(prefix, continuation).await
+  }
+
+  async fn get_presigned_url(
+    &self,
+    key: &BlobKey,
+    expiry: Duration,
+  ) -> BlobStorageResult<String> {
+    self.inner.get_presigned_url(key, expiry).await
+  }
+
+  async fn get_presigned_post(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: Duration,
+  ) -> BlobStorageResult<PresignedPost> {
+    self.inner.get_presigned_post(key, conditions, expiry).await
+  }
+
+  async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadHandle> {
+    let _permit = self.permit().await;
+    self.inner.create_upload(key, options).await
+  }
+
+  async fn upload_part(
+    &self,
+    handle: &UploadHandle,
+    part_number: u32,
+    data: Bytes,
+  ) -> BlobStorageResult<UploadedPart> {
+    let _permit = self.permit().await;
+    if let Some(bandwidth) = &self.bandwidth {
+      bandwidth.acquire(data.len()).await;
+    }
+    self.inner.upload_part(handle, part_number, data).await
+  }
+
+  async fn complete_upload(
+    &self,
+    handle: &UploadHandle,
+    parts: &[UploadedPart],
+  ) -> BlobStorageResult<()> {
+    let _permit = self.permit().await;
+    self.inner.complete_upload(handle, parts).await
+  }
+
+  async fn abort_upload(&self, handle: &UploadHandle) -> BlobStorageResult<()> {
+    let _permit = self.permit().await;
+    self.inner.abort_upload(handle).await
+  }
+
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    let _permit = self.permit().await;
+    self.inner.set_retention(key, until).await
+  }
+
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    let _permit = self.permit().await;
+    self.inner.set_legal_hold(key, hold).await
+  }
+}
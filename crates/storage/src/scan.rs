@@ -0,0 +1,265 @@
+//! Content-scanning decorator for blob storage uploads.
+
+use std::{
+  fmt, io,
+  ops::Range,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  time::Duration,
+};
+
+use async_trait::async_trait;
+use belt::Belt;
+use chrono::{DateTime, Utc};
+use futures::{
+  SinkExt, StreamExt,
+  channel::{mpsc, oneshot},
+  stream,
+};
+use storage_core::{
+  BlobKey, BlobListPage, BlobMetadata, BlobStorageError, BlobStorageLike,
+  BlobStorageResult, Bytes, RequestStream, ResponseStream, UploadOptions,
+};
+use tracing::warn;
+
+/// How many chunks may be buffered for the scanner before the upload waits
+/// for it to catch up.
+const SCAN_BUFFER_CHUNKS: usize = 16;
+
+/// The result of scanning an upload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanVerdict {
+  /// The upload may be stored.
+  Accept,
+  /// The upload must be rejected for the given reason.
+  Reject(String),
+}
+
+/// A policy which inspects the contents of every upload.
+///
+/// The scanner receives a copy of the upload's bytes as they stream to the
+/// inner storage. The upload is held open until the scan completes, so a
+/// rejected upload never becomes visible.
+#[async_trait]
+pub trait ScanPolicy: Send + Sync {
+  /// Scan the contents of an upload to `key`.
+  ///
+  /// The scanner may stop reading `data` early once it has reached a
+  /// verdict. Returning an error aborts the upload with that error.
+  async fn scan(
+    &self,
+    key: &BlobKey,
+    data: Belt,
+  ) -> BlobStorageResult<ScanVerdict>;
+}
+
+/// A [`BlobStorageLike`] decorator which runs every upload through a
+/// [`ScanPolicy`] and fails it with [`BlobStorageError::Rejected`] if the
+/// policy rejects it.
+///
+/// Resumable uploads are not supported, as their parts are stored before the
+/// whole blob can be scanned, and neither are pre-signed form uploads, which
+/// bypass the decorator.
+pub struct ScannedBlobStorage<S: ?Sized> {
+  inner:  Arc<S>,
+  policy: Arc<dyn ScanPolicy>,
+}
+
+impl<S: ?Sized> Clone for ScannedBlobStorage<S> {
+  fn clone(&self) -> Self {
+    Self {
+      inner:  self.inner.clone(),
+      policy: self.policy.clone(),
+    }
+  }
+}
+
+impl<S: ?Sized> fmt::Debug for ScannedBlobStorage<S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ScannedBlobStorage").finish_non_exhaustive()
+  }
+}
+
+impl<S: BlobStorageLike + ?Sized> ScannedBlobStorage<S> {
+  /// Wraps `inner`, scanning its uploads with `policy`.
+  pub fn new(inner: Arc<S>, policy: Arc<dyn ScanPolicy>) -> Self {
+    Self { inner, policy }
+  }
+}
+
+/// State for the upload stream handed to the inner storage.
+struct Gate {
+  data:    stream::Fuse<RequestStream>,
+  scanner: Option<mpsc::Sender<Bytes>>,
+  verdict: Option<oneshot::Receiver<Option<String>>>,
+  fired:   Arc<AtomicBool>,
+}
+
+/// Forwards `data` to the inner storage while teeing it to the scanner, and
+/// withholds the end of the stream until the scanner's verdict is in. A
+/// rejection is surfaced as a stream error so the backend abandons the
+/// upload.
+fn gate(
+  data: RequestStream,
+  scanner: mpsc::Sender<Bytes>,
+  verdict: oneshot::Receiver<Option<String>>,
+  fired: Arc<AtomicBool>,
+) -> RequestStream {
+  let gate = Gate {
+    data: data.fuse(),
+    scanner: Some(scanner),
+    verdict: Some(verdict),
+    fired,
+  };
+
+  Box::pin(stream::unfold(gate, |mut gate| async move {
+    match gate.data.next().await {
+      Some(Ok(chunk)) => {
+        if let Some(scanner) = &mut gate.scanner
+          && scanner.send(chunk.clone()).await.is_err()
+        {
+          // the scanner stopped reading early
+          gate.scanner = None;
+        }
+        Some((Ok(chunk), gate))
+      }
+      Some(Err(e)) => {
+        gate.scanner = None;
+        Some((Err(e), gate))
+      }
+      None => {
+        // close the scanner's stream and wait for its verdict
+        gate.scanner = None;
+        let rejection = match gate.verdict.take()?.await {
+          Ok(None) => return None,
+          Ok(Some(reason)) => reason,
+          Err(oneshot::Canceled) => "scan did not complete".to_owned(),
+        };
+        gate.fired.store(true, Ordering::Release);
+        Some((Err(io::Error::other(rejection)), gate))
+      }
+    }
+  }))
+}
+
+#[async_trait]
+impl<S: BlobStorageLike + ?Sized> BlobStorageLike for ScannedBlobStorage<S> {
+  async fn put_stream(
+    &self,
+    key: &BlobKey,
+    data: RequestStream,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    let (scanner_tx, scanner_rx) = mpsc::channel(SCAN_BUFFER_CHUNKS);
+    let (verdict_tx, verdict_rx) = oneshot::channel();
+    let fired = Arc::new(AtomicBool::new(false));
+
+    let scan = async {
+      let verdict = self
+        .policy
+        .scan(key, Belt::new(scanner_rx.map(Ok::<_, io::Error>)))
+        .await;
+      let rejection = match &verdict {
+        Ok(ScanVerdict::Accept) => None,
+        Ok(ScanVerdict::Reject(reason)) => Some(reason.clone()),
+        Err(e) => Some(e.to_string()),
+      };
+      // the upload may already have failed and dropped the gate
+      let _ = verdict_tx.send(rejection);
+      verdict
+    };
+    let upload = self.inner.put_stream(
+      key,
+      gate(data, scanner_tx, verdict_rx, fired.clone()),
+      options,
+    );
+
+    let (verdict, result) = futures::join!(scan, upload);
+
+    if result.is_err() && fired.load(Ordering::Acquire) {
+      return match verdict {
+        Ok(ScanVerdict::Reject(reason)) => {
+          warn!(%key, %reason, "upload rejected by scan policy");
+          Err(BlobStorageError::Rejected(reason))
+        }
+        Err(e) => Err(e),
+        Ok(ScanVerdict::Accept) => result,
+      };
+    }
+
+    result
+  }
+
+  async fn get_stream(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<ResponseStream> {
+    self.inner.get_stream(key).await
+  }
+
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    self.inner.get_stream_range(key, range).await
+  }
+
+  async fn head(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<Option<BlobMetadata>> {
+    self.inner.head(key).await
+  }
+
+  async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    self.inner.delete(key).await
+  }
+
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    self.inner.delete_many(keys).await
+  }
+
+  // copies only duplicate contents already in storage, which were scanned
+  // when they were uploaded
+  async fn copy(&self, from: &BlobKey, to: &BlobKey) -> BlobStorageResult<()> {
+    self.inner.copy(from, to).await
+  }
+
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    self.inner.list_page(prefix, continuation).await
+  }
+
+  async fn get_presigned_url(
+    &self,
+    key: &BlobKey,
+    expiry: Duration,
+  ) -> BlobStorageResult<String> {
+    self.inner.get_presigned_url(key, expiry).await
+  }
+
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_retention(key, until).await
+  }
+
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_legal_hold(key, hold).await
+  }
+}
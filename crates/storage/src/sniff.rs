@@ -0,0 +1,225 @@
+//! Content-type sniffing decorator for blob storage uploads.
+
+use std::{fmt, ops::Range, path::Path, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use storage_core::{
+  BlobKey, BlobListPage, BlobMetadata, BlobStorageLike, BlobStorageResult,
+  Bytes, PostCondition, PresignedPost, RequestStream, ResponseStream,
+  UploadHandle, UploadOptions, UploadedPart,
+};
+use tracing::debug;
+
+/// How many leading bytes of an upload are inspected. Every signature
+/// `infer` knows of lies within this prefix.
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// Detects the media type of `data` from its magic bytes.
+#[must_use]
+pub fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+  infer::get(data).map(|kind| kind.mime_type())
+}
+
+/// A [`BlobStorageLike`] decorator which detects the content type of
+/// uploads made without one, from their leading bytes, so it's stored with
+/// the blob and served with downloads.
+///
+/// Uploads whose type isn't recognised are stored without one. Resumable
+/// uploads are not sniffed, as their type is fixed when they're created.
+pub struct SniffingBlobStorage<S: ?Sized> {
+  inner: Arc<S>,
+}
+
+impl<S: ?Sized> Clone for SniffingBlobStorage<S> {
+  fn clone(&self) -> Self {
+    Self {
+      inner: self.inner.clone(),
+    }
+  }
+}
+
+impl<S: ?Sized> fmt::Debug for SniffingBlobStorage<S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("SniffingBlobStorage")
+      .finish_non_exhaustive()
+  }
+}
+
+impl<S: BlobStorageLike + ?Sized> SniffingBlobStorage<S> {
+  /// Wraps `inner`, sniffing the content type of its uploads.
+  pub const fn new(inner: Arc<S>) -> Self { Self { inner } }
+}
+
+/// Reads chunks of `data` until [`SNIFF_LEN`] bytes are buffered or it ends,
+/// returning the detected type and the stream with the buffered chunks put
+/// back in front.
+async fn sniff(
+  mut data: RequestStream,
+) -> (Option<&'static str>, RequestStream) {
+  let mut buffered = Vec::new();
+  let mut prefix = Vec::with_capacity(SNIFF_LEN);
+  while prefix.len() < SNIFF_LEN {
+    let Some(chunk) = data.next().await else {
+      break;
+    };
+    let failed = chunk.is_err();
+    if let Ok(chunk) = &chunk {
+      let wanted = (SNIFF_LEN - prefix.len()).min(chunk.len());
+      prefix.extend_from_slice(&chunk[..wanted]);
+    }
+    buffered.push(chunk);
+    // leave the error for the inner storage to fail the upload with
+    if failed {
+      break;
+    }
+  }
+
+  let content_type = sniff_content_type(&prefix);
+  (content_type, Box::pin(stream::iter(buffered).chain(data)))
+}
+
+#[async_trait]
+impl<S: BlobStorageLike + ?Sized> BlobStorageLike for SniffingBlobStorage<S> {
+  async fn put_stream(
+    &self,
+    key: &BlobKey,
+    data: RequestStream,
+    mut options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    if options.content_type.is_some() {
+      return self.inner.put_stream(key, data, options).await;
+    }
+
+    let (content_type, data) = sniff(data).await;
+    debug!(%key, ?content_type, "sniffed upload content type");
+    options.content_type = content_type.map(str::to_owned);
+    self.inner.put_stream(key, data, options).await
+  }
+
+  async fn put_file(
+    &self,
+    key: &BlobKey,
+    path: &Path,
+    data: RequestStream,
+    mut options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    if options.content_type.is_some() {
+      return self.inner.put_file(key, path, data, options).await;
+    }
+
+    let (content_type, data) = sniff(data).await;
+    debug!(%key, ?content_type, "sniffed upload content type");
+    options.content_type = content_type.map(str::to_owned);
+    self.inner.put_file(key, path, data, options).await
+  }
+
+  async fn get_stream(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<ResponseStream> {
+    self.inner.get_stream(key).await
+  }
+
+  async fn get_stream_range(
+    &self,
+    key: &BlobKey,
+    range: Range<u64>,
+  ) -> BlobStorageResult<ResponseStream> {
+    self.inner.get_stream_range(key, range).await
+  }
+
+  async fn head(
+    &self,
+    key: &BlobKey,
+  ) -> BlobStorageResult<Option<BlobMetadata>> {
+    self.inner.head(key).await
+  }
+
+  async fn delete(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    self.inner.delete(key).await
+  }
+
+  async fn delete_many(
+    &self,
+    keys: &[BlobKey],
+  ) -> Vec<(BlobKey, BlobStorageResult<()>)> {
+    self.inner.delete_many(keys).await
+  }
+
+  // copies keep their source's content type
+  async fn copy(&self, from: &BlobKey, to: &BlobKey) -> BlobStorageResult<()> {
+    self.inner.copy(from, to).await
+  }
+
+  async fn list_page(
+    &self,
+    prefix: &str,
+    continuation: Option<String>,
+  ) -> BlobStorageResult<BlobListPage> {
+    self.inner.list_page(prefix, continuation).await
+  }
+
+  async fn get_presigned_url(
+    &self,
+    key: &BlobKey,
+    expiry: Duration,
+  ) -> BlobStorageResult<String> {
+    self.inner.get_presigned_url(key, expiry).await
+  }
+
+  async fn get_presigned_post(
+    &self,
+    key: &BlobKey,
+    conditions: &[PostCondition],
+    expiry: Duration,
+  ) -> BlobStorageResult<PresignedPost> {
+    self.inner.get_presigned_post(key, conditions, expiry).await
+  }
+
+  async fn create_upload(
+    &self,
+    key: &BlobKey,
+    options: UploadOptions,
+  ) -> BlobStorageResult<UploadHandle> {
+    self.inner.create_upload(key, options).await
+  }
+
+  async fn upload_part(
+    &self,
+    handle: &UploadHandle,
+    part_number: u32,
+    data: Bytes,
+  ) -> BlobStorageResult<UploadedPart> {
+    self.inner.upload_part(handle, part_number, data).await
+  }
+
+  async fn complete_upload(
+    &self,
+    handle: &UploadHandle,
+    parts: &[UploadedPart],
+  ) -> BlobStorageResult<()> {
+    self.inner.complete_upload(handle, parts).await
+  }
+
+  async fn abort_upload(&self, handle: &UploadHandle) -> BlobStorageResult<()> {
+    self.inner.abort_upload(handle).await
+  }
+
+  async fn set_retention(
+    &self,
+    key: &BlobKey,
+    until: DateTime<Utc>,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_retention(key, until).await
+  }
+
+  async fn set_legal_hold(
+    &self,
+    key: &BlobKey,
+    hold: bool,
+  ) -> BlobStorageResult<()> {
+    self.inner.set_legal_hold(key, hold).await
+  }
+}
@@ -0,0 +1,151 @@
+//! Two-phase blob writes.
+//!
+//! Uploads to some backends, like the filesystem, aren't atomic, so readers
+//! may see a blob while it's still being written.
+//! [`BlobStorage::put_staged`] uploads a blob under [`STAGING_PREFIX`]
+//! instead, where readers don't look for it, and [`BlobStorage::promote`]
+//! then [copies](BlobStorage::copy) it to its key in one step and deletes the
+//! staged blob. Backends with their own `copy` make promotion atomic; the
+//! filesystem renames a finished copy into place.
+//!
+//! Staged blobs which are never promoted, e.g. because their writer crashed,
+//! are abandoned. Each `put_staged` first deletes abandoned blobs, those
+//! staged longer ago than the staging max age, at most once per
+//! [`SWEEP_INTERVAL`]. They can also be deleted on demand with
+//! [`BlobStorage::purge_abandoned_staged`].
+
+use std::{sync::Mutex, time::Duration};
+
+use belt::Belt;
+use chrono::{DateTime, Utc};
+use storage_core::{
+  BlobEntry, BlobKey, BlobStorageError, BlobStorageResult, UploadOptions,
+};
+use tracing::{debug, warn};
+
+use crate::BlobStorage;
+
+/// The prefix staged blobs are stored under, followed by their key.
+pub const STAGING_PREFIX: &str = ".staging/";
+
+/// How long a blob may stay staged by default before it's considered
+/// abandoned.
+pub const DEFAULT_STAGING_MAX_AGE: Duration = Duration::from_hours(24);
+
+/// How often [`BlobStorage::put_staged`] deletes abandoned staged blobs.
+pub const SWEEP_INTERVAL: Duration = Duration::from_mins(15);
+
+/// The staging settings of a [`BlobStorage`], shared by its clones.
+#[derive(Debug)]
+pub(crate) struct Staging {
+  /// How long a blob may stay staged before it's considered abandoned.
+  pub(crate) max_age: Duration,
+  /// When abandoned staged blobs were last deleted.
+  last_sweep:         Mutex<Option<DateTime<Utc>>>,
+}
+
+impl Staging {
+  pub(crate) const fn new(max_age: Duration) -> Self {
+    Self {
+      max_age,
+      last_sweep: Mutex::new(None),
+    }
+  }
+
+  /// Returns whether a sweep is due at `now`, recording that one starts if
+  /// so.
+  fn start_sweep(&self, now: DateTime<Utc>) -> bool {
+    let mut last_sweep = self.last_sweep.lock().unwrap();
+    let due =
+      last_sweep.is_none_or(|last| now >= clock::after(last, SWEEP_INTERVAL));
+    if due {
+      *last_sweep = Some(now);
+    }
+    due
+  }
+}
+
+impl Default for Staging {
+  fn default() -> Self { Self::new(DEFAULT_STAGING_MAX_AGE) }
+}
+
+/// The key `key` is staged under.
+#[must_use]
+pub fn staging_key(key: &BlobKey) -> BlobKey {
+  BlobKey::new(format!("{STAGING_PREFIX}{key}"))
+}
+
+impl BlobStorage {
+  /// Upload a blob to the staging key for `key`, replacing any blob staged
+  /// there, without touching `key` until it's [`promote`](Self::promote)d.
+  /// `options` apply to the staged blob, and so to the promoted one.
+  pub async fn put_staged(
+    &self,
+    key: &BlobKey,
+    data: Belt,
+    options: UploadOptions,
+  ) -> BlobStorageResult<()> {
+    // before uploading, so a sweep never deletes the blob it's staging
+    if self.staging.start_sweep(Utc::now())
+      && let Err(error) = self.purge_abandoned_staged().await
+    {
+      warn!(%error, "failed to delete abandoned staged blobs");
+    }
+
+    let options = UploadOptions {
+      overwrite: true,
+      ..options
+    };
+    self.put(&staging_key(key), data, options).await
+  }
+
+  /// Move the blob staged for `key` to `key`, overwriting any blob there.
+  pub async fn promote(&self, key: &BlobKey) -> BlobStorageResult<()> {
+    let staged = staging_key(key);
+    self.inner.copy(&staged, key).await?;
+    self.inner.delete(&staged).await
+  }
+
+  /// Delete staged blobs which are at least as old as the staging max age,
+  /// returning how many were deleted. Staged blobs without a modification
+  /// time are kept.
+  pub async fn purge_abandoned_staged(&self) -> BlobStorageResult<usize> {
+    let cutoff = clock::before(Utc::now(), self.staging.max_age);
+    let mut abandoned = Vec::new();
+    let mut continuation = None;
+    loop {
+      let page = self.inner.list_page(STAGING_PREFIX, continuation).await?;
+      abandoned.extend(
+        page
+          .entries
+          .into_iter()
+          .filter(|entry| staged_at(entry).is_some_and(|t| t <= cutoff))
+          .map(|entry| entry.key),
+      );
+      continuation = page.continuation;
+      if continuation.is_none() {
+        break;
+      }
+    }
+
+    let mut purged = 0;
+    for (_, result) in self.inner.delete_many(&abandoned).await {
+      match result {
+        Ok(()) => purged += 1,
+        // promoted or purged concurrently
+        Err(BlobStorageError::NotFound(_)) => {}
+        Err(e) => return Err(e),
+      }
+    }
+    debug!(purged, "deleted abandoned staged blobs");
+    Ok(purged)
+  }
+}
+
+/// When a staged blob was uploaded.
+fn staged_at(entry: &BlobEntry) -> Option<DateTime<Utc>> {
+  let last_modified = entry.metadata.last_modified.as_deref()?;
+  DateTime::parse_from_rfc3339(last_modified)
+    .ok()
+    .map(|t| t.to_utc())
+}
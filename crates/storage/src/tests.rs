@@ -5,11 +5,25 @@ use storage_impl_memory::BlobStorageMemory;
 
 trait StorageInstantiator {
   type Guard;
+  /// Whether object locks apply to keys, rather than to versions as in S3,
+  /// where overwrites and deletes of locked blobs add versions instead.
+  const KEY_LOCKS: bool = true;
+  /// Whether blob metadata includes the canned ACL given on upload.
+  const RECORDS_ACL: bool = true;
+  /// Whether any storage class and encryption are accepted.
+  const ANY_STORAGE_OPTIONS: bool = true;
+  /// Whether parts of resumable uploads other than the last may be smaller
+  /// than S3's minimum of 5 MiB.
+  const SMALL_PARTS: bool = true;
+  /// Whether upload checksums are verified, rejecting mismatched uploads.
+  const VERIFIES_CHECKSUMS: bool = false;
   async fn init() -> (Arc<dyn BlobStorageLike>, Self::Guard);
 }
 
 struct MemoryInstatiator;
 struct FileSystemInstatiator;
+#[cfg(feature = "integration")]
+struct MinioInstatiator;
 
 impl StorageInstantiator for MemoryInstatiator {
   type Guard = ();
@@ -31,6 +45,63 @@ impl StorageInstantiator for FileSystemInstatiator {
     )
   }
 }
+#[cfg(feature = "integration")]
+impl StorageInstantiator for MinioInstatiator {
+  type Guard = testcontainers_modules::testcontainers::ContainerAsync<
+    testcontainers_modules::minio::MinIO,
+  >;
+  const KEY_LOCKS: bool = false;
+  const RECORDS_ACL: bool = false;
+  const ANY_STORAGE_OPTIONS: bool = false;
+  const SMALL_PARTS: bool = false;
+  const VERIFIES_CHECKSUMS: bool = true;
+  async fn init() -> (Arc<dyn BlobStorageLike>, Self::Guard) {
+    use testcontainers_modules::testcontainers::runners::AsyncRunner;
+
+    const BUCKET: &str = "blobs";
+    const REGION: &str = "us-east-1";
+    const CREDENTIAL: &str = "minioadmin";
+
+    let container = testcontainers_modules::minio::MinIO::default()
+      .start()
+      .await
+      .unwrap();
+    let endpoint = format!(
+      "http://{}:{}",
+      container.get_host().await.unwrap(),
+      container.get_host_port_ipv4(9000).await.unwrap()
+    );
+    s3::Bucket::create_with_path_style(
+      BUCKET,
+      s3::Region::Custom {
+        region:   REGION.to_owned(),
+        endpoint: endpoint.clone(),
+      },
+      s3::creds::Credentials::new(
+        Some(CREDENTIAL),
+        Some(CREDENTIAL),
+        None,
+        None,
+        None,
+      )
+      .unwrap(),
+      s3::BucketConfiguration::default(),
+    )
+    .await
+    .unwrap();
+
+    let storage = storage_impl_s3::BlobStorageS3::new(
+      BUCKET,
+      REGION,
+      &endpoint,
+      Some(CREDENTIAL),
+      Some(CREDENTIAL),
+    )
+    .unwrap()
+    .with_path_style();
+    (Arc::new(storage), container)
+  }
+}
 
 #[generic_tests::define(attrs(tokio::test))]
 mod generic_testing {
@@ -41,6 +112,8 @@ mod generic_testing {
   use storage_core::BlobStorageLike;
 
   use super::{super::*, StorageInstantiator};
+  #[cfg(feature = "integration")]
+  use crate::tests::MinioInstatiator;
   use crate::tests::{FileSystemInstatiator, MemoryInstatiator};
 
   // Helper function to create a stream from bytes
@@ -72,7 +145,10 @@ mod generic_testing {
     let data = b"Hello, World!".to_vec();
 
     // Upload
-    let options = UploadOptions { overwrite: true };
+    let options = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data.clone()), options)
       .await
@@ -85,6 +161,143 @@ mod generic_testing {
     assert_eq!(data, retrieved);
   }
 
+  #[tokio::test]
+  async fn test_get_stream_range<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let key = BlobKey::new("ranged-blob");
+    storage
+      .put_stream(
+        &key,
+        bytes_stream(b"0123456789".to_vec()),
+        UploadOptions::default(),
+      )
+      .await
+      .unwrap();
+
+    let stream = storage.get_stream_range(&key, 2..5).await.unwrap();
+    assert_eq!(collect_stream(stream).await.unwrap(), b"234");
+    // ranges past the end are cut short
+    let stream = storage.get_stream_range(&key, 7..100).await.unwrap();
+    assert_eq!(collect_stream(stream).await.unwrap(), b"789");
+
+    let missing = BlobKey::new("missing-blob");
+    let result = storage.get_stream_range(&missing, 0..1).await;
+    assert!(matches!(result, Err(BlobStorageError::NotFound(_))));
+  }
+
+  #[tokio::test]
+  async fn test_put_with_checksum<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let key = BlobKey::new("checksum-test");
+    let data = b"checksummed".to_vec();
+
+    // backends which can't verify checksums accept them regardless
+    for checksum in [Checksum::md5(&data), Checksum::sha256(&data)] {
+      let options = UploadOptions {
+        overwrite: true,
+        checksum: Some(checksum),
+        ..UploadOptions::default()
+      };
+      storage
+        .put_stream(&key, bytes_stream(data.clone()), options)
+        .await
+        .unwrap();
+    }
+
+    let stream = storage.get_stream(&key).await.unwrap();
+    assert_eq!(data, collect_stream(stream).await.unwrap());
+  }
+
+  #[tokio::test]
+  async fn test_put_with_storage_options<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let key = BlobKey::new("archived");
+    let data = b"archived".to_vec();
+
+    let options = UploadOptions {
+      overwrite: true,
+      storage_class: Some(StorageClass::Glacier),
+      sse: Some(ServerSideEncryption::Kms {
+        key_id: Some("key".to_owned()),
+      }),
+      ..UploadOptions::default()
+    };
+    let result = storage
+      .put_stream(&key, bytes_stream(data.clone()), options)
+      .await;
+    if !I::ANY_STORAGE_OPTIONS {
+      // S3 rejects storage classes and keys it doesn't have
+      assert!(result.is_err());
+      assert!(storage.head(&key).await.unwrap().is_none());
+      return;
+    }
+    // backends without storage classes or encryption ignore them
+    result.unwrap();
+
+    let stream = storage.get_stream(&key).await.unwrap();
+    assert_eq!(data, collect_stream(stream).await.unwrap());
+  }
+
+  #[tokio::test]
+  async fn test_put_with_acl<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let key = BlobKey::new("public");
+
+    let options = UploadOptions {
+      overwrite: true,
+      acl: Some(CannedAcl::PublicRead),
+      ..UploadOptions::default()
+    };
+    storage
+      .put_stream(&key, bytes_stream(b"public".to_vec()), options.clone())
+      .await
+      .unwrap();
+    let metadata = storage.head(&key).await.unwrap().unwrap();
+    if !I::RECORDS_ACL {
+      // S3 applies the ACL, but doesn't return it with the object's metadata
+      assert_eq!(metadata.acl, None);
+      return;
+    }
+    // backends without ACLs record them in the blob's metadata
+    assert_eq!(metadata.acl, Some(CannedAcl::PublicRead));
+
+    // and keep them through resumable uploads
+    let handle = storage.create_upload(&key, options).await.unwrap();
+    let part = storage
+      .upload_part(&handle, 1, Bytes::from_static(b"public"))
+      .await
+      .unwrap();
+    storage.complete_upload(&handle, &[part]).await.unwrap();
+    let metadata = storage.head(&key).await.unwrap().unwrap();
+    assert!(metadata.acl.is_some_and(CannedAcl::is_public));
+  }
+
+  #[tokio::test]
+  async fn test_put_with_mismatched_checksum<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let key = BlobKey::new("bad-checksum-test");
+    let data = b"checksummed".to_vec();
+
+    for checksum in [Checksum::md5(b"other"), Checksum::sha256(b"other")] {
+      let options = UploadOptions {
+        overwrite: true,
+        checksum: Some(checksum),
+        ..UploadOptions::default()
+      };
+      let result = storage
+        .put_stream(&key, bytes_stream(data.clone()), options)
+        .await;
+      if I::VERIFIES_CHECKSUMS {
+        assert!(matches!(result, Err(BlobStorageError::InvalidInput(_))));
+        assert!(storage.head(&key).await.unwrap().is_none());
+      } else {
+        // backends which can't verify checksums ignore them
+        result.unwrap();
+        storage.delete(&key).await.unwrap();
+      }
+    }
+  }
+
   #[tokio::test]
   async fn test_put_overwrite_flag<I: StorageInstantiator>() {
     let (storage, _guard) = setup::<I>().await;
@@ -93,7 +306,10 @@ mod generic_testing {
     let data2 = b"second".to_vec();
 
     // First upload
-    let options = UploadOptions { overwrite: false };
+    let options = UploadOptions {
+      overwrite: false,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data1.clone()), options.clone())
       .await
@@ -106,7 +322,10 @@ mod generic_testing {
     assert!(matches!(result, Err(BlobStorageError::AlreadyExists(_))));
 
     // Third upload with overwrite should succeed
-    let options_overwrite = UploadOptions { overwrite: true };
+    let options_overwrite = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data2.clone()), options_overwrite)
       .await
@@ -133,7 +352,10 @@ mod generic_testing {
     let key = BlobKey::new("metadata-test");
     let data = b"test data for metadata".to_vec();
 
-    let options = UploadOptions { overwrite: true };
+    let options = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data.clone()), options)
       .await
@@ -161,7 +383,10 @@ mod generic_testing {
     let data = b"to be deleted".to_vec();
 
     // Upload
-    let options = UploadOptions { overwrite: true };
+    let options = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data), options)
       .await
@@ -190,6 +415,168 @@ mod generic_testing {
     );
   }
 
+  #[tokio::test]
+  async fn test_legal_hold<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let key = BlobKey::new("held");
+    let options = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
+    storage
+      .put_stream(&key, bytes_stream(b"held".to_vec()), options.clone())
+      .await
+      .unwrap();
+
+    if !I::KEY_LOCKS {
+      // S3 only locks objects in buckets with Object Lock enabled, which the
+      // test bucket isn't
+      assert!(storage.set_legal_hold(&key, true).await.is_err());
+      storage.delete(&key).await.unwrap();
+      return;
+    }
+    storage.set_legal_hold(&key, true).await.unwrap();
+    let result = storage.delete(&key).await;
+    assert!(matches!(result, Err(BlobStorageError::Locked(_))));
+    let result = storage
+      .put_stream(&key, bytes_stream(b"other".to_vec()), options)
+      .await;
+    assert!(matches!(result, Err(BlobStorageError::Locked(_))));
+
+    storage.set_legal_hold(&key, false).await.unwrap();
+    storage.delete(&key).await.unwrap();
+
+    let result = storage.set_legal_hold(&key, true).await;
+    assert!(matches!(result, Err(BlobStorageError::NotFound(_))));
+  }
+
+  #[tokio::test]
+  async fn test_retention<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let retained = BlobKey::new("retained");
+    let expired = BlobKey::new("expired");
+    for key in [&retained, &expired] {
+      storage
+        .put_stream(
+          key,
+          bytes_stream(b"retained".to_vec()),
+          UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let now = chrono::Utc::now();
+    let until = now + chrono::TimeDelta::days(1);
+    if !I::KEY_LOCKS {
+      // S3 only locks objects in buckets with Object Lock enabled, which the
+      // test bucket isn't
+      assert!(storage.set_retention(&retained, until).await.is_err());
+      return;
+    }
+    storage.set_retention(&retained, until).await.unwrap();
+    storage
+      .set_retention(&expired, now - chrono::TimeDelta::days(1))
+      .await
+      .unwrap();
+
+    let results = storage
+      .delete_many(&[retained.clone(), expired.clone()])
+      .await;
+    assert!(matches!(results[0].1, Err(BlobStorageError::Locked(_))));
+    assert!(results[1].1.is_ok());
+
+    // retention can be extended but not shortened
+    let result = storage.set_retention(&retained, now).await;
+    assert!(matches!(result, Err(BlobStorageError::Locked(_))));
+    storage
+      .set_retention(&retained, until + chrono::TimeDelta::days(1))
+      .await
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_delete_many<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let keys: Vec<_> = (0..40)
+      .map(|i| BlobKey::new(format!("batch/{i}")))
+      .collect();
+
+    for key in &keys {
+      storage
+        .put_stream(key, bytes_stream(b"batch".to_vec()), UploadOptions {
+          overwrite: true,
+          ..UploadOptions::default()
+        })
+        .await
+        .unwrap();
+    }
+
+    let mut to_delete = keys.clone();
+    to_delete.push(BlobKey::new("batch/missing"));
+    let results = storage.delete_many(&to_delete).await;
+
+    assert_eq!(results.len(), to_delete.len());
+    for ((key, result), expected) in results.iter().zip(&keys) {
+      assert_eq!(key, expected);
+      assert!(result.is_ok());
+      assert!(storage.head(key).await.unwrap().is_none());
+    }
+    let (key, result) = results.last().unwrap();
+    assert_eq!(key.as_str(), "batch/missing");
+    assert!(
+      result.is_ok() || matches!(result, Err(BlobStorageError::NotFound(_)))
+    );
+  }
+
+  #[tokio::test]
+  async fn test_list_and_stats<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    // enough blobs to span two pages
+    let count = storage_core::LIST_PAGE_SIZE + 1;
+    for i in 0..count {
+      storage
+        .put_stream(
+          &BlobKey::new(format!("tenant-a/{i:05}")),
+          bytes_stream(vec![0; i % 7 + 1]),
+          UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+    }
+    for key in ["tenant-a/big", "tenant-b/other"] {
+      storage
+        .put_stream(
+          &BlobKey::new(key),
+          bytes_stream(vec![0; 100]),
+          UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let first = storage.list_page("tenant-a/", None).await.unwrap();
+    assert_eq!(first.entries.len(), storage_core::LIST_PAGE_SIZE);
+    assert_eq!(first.entries[0].key.as_str(), "tenant-a/00000");
+    let second = storage
+      .list_page("tenant-a/", first.continuation)
+      .await
+      .unwrap();
+    assert_eq!(second.entries.len(), 2);
+    assert!(second.continuation.is_none());
+
+    let stats = storage.stats("tenant-a/").await.unwrap();
+    let expected_bytes: usize = (0..count).map(|i| i % 7 + 1).sum::<usize>();
+    assert_eq!(stats.object_count, count as u64 + 1);
+    assert_eq!(stats.total_bytes, expected_bytes as u64 + 100);
+    assert_eq!(stats.largest.unwrap().key.as_str(), "tenant-a/big");
+    assert!(stats.oldest.is_some());
+
+    let empty = storage.stats("tenant-c/").await.unwrap();
+    assert_eq!(empty.object_count, 0);
+    assert!(empty.largest.is_none());
+  }
+
   #[tokio::test]
   async fn test_presigned_url<I: StorageInstantiator>() {
     let (storage, _guard) = setup::<I>().await;
@@ -197,14 +584,17 @@ mod generic_testing {
     let data = b"presigned url test".to_vec();
 
     // Upload
-    let options = UploadOptions { overwrite: true };
+    let options = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data), options)
       .await
       .unwrap();
 
     // Get presigned URL
-    let expiry = Duration::from_secs(3600);
+    let expiry = Duration::from_hours(1);
     let url = storage.get_presigned_url(&key, expiry).await.unwrap();
 
     assert!(!url.is_empty());
@@ -221,7 +611,7 @@ mod generic_testing {
     let (storage, _guard) = setup::<I>().await;
     let key = BlobKey::new("nonexistent-presigned");
 
-    let expiry = Duration::from_secs(3600);
+    let expiry = Duration::from_hours(1);
     let result = storage.get_presigned_url(&key, expiry).await;
 
     // Implementation may return NotFound or succeed (some backends generate
@@ -252,7 +642,10 @@ mod generic_testing {
     let size = 10 * 1024 * 1024;
     let data = vec![42u8; size];
 
-    let options = UploadOptions { overwrite: true };
+    let options = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data.clone()), options)
       .await
@@ -274,7 +667,10 @@ mod generic_testing {
     let key = BlobKey::new("empty-blob");
     let data = Vec::new();
 
-    let options = UploadOptions { overwrite: true };
+    let options = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data.clone()), options)
       .await
@@ -296,7 +692,10 @@ mod generic_testing {
     let key = BlobKey::new("test/with/slashes-and_underscores");
     let data = b"special key test".to_vec();
 
-    let options = UploadOptions { overwrite: true };
+    let options = UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    };
     storage
       .put_stream(&key, bytes_stream(data.clone()), options)
       .await
@@ -322,7 +721,10 @@ mod generic_testing {
       let data = format!("data-{i}").into_bytes();
 
       let handle = tokio::spawn(async move {
-        let options = UploadOptions { overwrite: true };
+        let options = UploadOptions {
+          overwrite: true,
+          ..UploadOptions::default()
+        };
         storage
           .put_stream(&key, bytes_stream(data), options)
           .await
@@ -341,8 +743,1249 @@ mod generic_testing {
     }
   }
 
+  #[tokio::test]
+  async fn test_resumable_upload<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let storage = BlobStorage {
+      inner:   storage,
+      urls:    crate::urls::UrlConfig::default(),
+      staging: Arc::default(),
+    };
+    let key = BlobKey::new("resumable");
+
+    let mut session = storage
+      .create_upload(&key, UploadOptions {
+        overwrite: false,
+        ..UploadOptions::default()
+      })
+      .await
+      .unwrap();
+    session.append(Bytes::from_static(b"hello ")).await.unwrap();
+    session
+      .append(Bytes::from_static(b"resumable "))
+      .await
+      .unwrap();
+
+    if !I::SMALL_PARTS {
+      // S3 rejects uploads with parts other than the last under 5 MiB
+      assert!(session.finalize().await.is_err());
+      assert!(!storage.exists(&key).await.unwrap());
+      return;
+    }
+
+    // not visible until finalized
+    assert!(!storage.exists(&key).await.unwrap());
+
+    // simulate a crash by persisting the state and dropping the session
+    let state = serde_json::to_string(session.state()).unwrap();
+    drop(session);
+
+    let mut session =
+      storage.resume_upload(serde_json::from_str(&state).unwrap());
+    assert_eq!(session.uploaded_size(), 16);
+    let part = session.append(Bytes::from_static(b"world")).await.unwrap();
+    assert_eq!(part.part_number, 3);
+    session.finalize().await.unwrap();
+
+    let stream = storage.get_stream(&key).await.unwrap();
+    let retrieved = collect_stream(stream).await.unwrap();
+    assert_eq!(retrieved, b"hello resumable world");
+  }
+
+  #[tokio::test]
+  async fn test_abort_upload<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let storage = BlobStorage {
+      inner:   storage,
+      urls:    crate::urls::UrlConfig::default(),
+      staging: Arc::default(),
+    };
+    let key = BlobKey::new("aborted");
+
+    let mut session = storage
+      .create_upload(&key, UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
+      .await
+      .unwrap();
+    session
+      .append(Bytes::from_static(b"partial"))
+      .await
+      .unwrap();
+    let state = session.state().clone();
+    session.abort().await.unwrap();
+
+    assert!(!storage.exists(&key).await.unwrap());
+    let result = storage.resume_upload(state).finalize().await;
+    assert!(matches!(result, Err(BlobStorageError::NotFound(_))));
+  }
+
+  #[tokio::test]
+  async fn test_copy<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let source = BlobKey::new("copy/source.txt");
+    let target = BlobKey::new("copy/nested/target.txt");
+
+    let result = storage.copy(&source, &target).await;
+    assert!(matches!(result, Err(BlobStorageError::NotFound(_))));
+
+    let options = UploadOptions {
+      content_type: Some("text/plain".to_owned()),
+      ..UploadOptions::default()
+    };
+    storage
+      .put_stream(&source, bytes_stream(b"original".to_vec()), options)
+      .await
+      .unwrap();
+    storage
+      .put_stream(
+        &target,
+        bytes_stream(b"replaced".to_vec()),
+        UploadOptions::default(),
+      )
+      .await
+      .unwrap();
+    storage.copy(&source, &target).await.unwrap();
+
+    let stream = storage.get_stream(&target).await.unwrap();
+    assert_eq!(collect_stream(stream).await.unwrap(), b"original");
+    let metadata = storage.head(&target).await.unwrap().unwrap();
+    assert_eq!(metadata.size, 8);
+    assert_eq!(metadata.content_type.as_deref(), Some("text/plain"));
+
+    // the source is untouched
+    let stream = storage.get_stream(&source).await.unwrap();
+    assert_eq!(collect_stream(stream).await.unwrap(), b"original");
+  }
+
+  #[tokio::test]
+  async fn test_put_from_file<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let storage = BlobStorage {
+      inner:   storage,
+      urls:    crate::urls::UrlConfig::default(),
+      staging: Arc::default(),
+    };
+    let source_dir = tempfile::tempdir().unwrap();
+    let source = source_dir.path().join("source.txt");
+    std::fs::write(&source, "from a file").unwrap();
+    let key = BlobKey::new("from-file.txt");
+
+    storage
+      .put(
+        &key,
+        belt::Belt::from_file(&source),
+        UploadOptions::default(),
+      )
+      .await
+      .unwrap();
+    let stream = storage.get_stream(&key).await.unwrap();
+    assert_eq!(collect_stream(stream).await.unwrap(), b"from a file");
+    assert_eq!(storage.head(&key).await.unwrap().unwrap().size, 11);
+  }
+
+  #[tokio::test]
+  async fn test_staged_put_and_promote<I: StorageInstantiator>() {
+    let (storage, _guard) = setup::<I>().await;
+    let storage = BlobStorage {
+      inner:   storage,
+      urls:    crate::urls::UrlConfig::default(),
+      staging: Arc::default(),
+    };
+    let key = BlobKey::new("staged.txt");
+
+    storage
+      .put_staged(&key, belt::Belt::from("staged"), UploadOptions::default())
+      .await
+      .unwrap();
+    assert!(!storage.exists(&key).await.unwrap());
+
+    storage.promote(&key).await.unwrap();
+    let stream = storage.get_stream(&key).await.unwrap();
+    assert_eq!(collect_stream(stream).await.unwrap(), b"staged");
+    let staged = crate::staging::staging_key(&key);
+    assert!(!storage.exists(&staged).await.unwrap());
+
+    let result = storage.promote(&key).await;
+    assert!(matches!(result, Err(BlobStorageError::NotFound(_))));
+  }
+
   #[instantiate_tests(<MemoryInstatiator>)]
   mod test_memory {}
   #[instantiate_tests(<FileSystemInstatiator>)]
   mod test_fs {}
+  #[cfg(feature = "integration")]
+  #[instantiate_tests(<MinioInstatiator>)]
+  mod test_minio {}
+}
+
+mod shared_tests {
+  use std::sync::Arc;
+
+  use bytes::Bytes;
+  use futures::stream;
+  use storage_core::BlobStorageLike;
+  use storage_impl_memory::BlobStorageMemory;
+
+  use crate::{
+    BlobKey, BlobStorage, UploadOptions,
+    audit::{AuditedBlobStorage, TracingAuditSink},
+    chunked::{ChunkedBlobStorage, ChunkingOptions},
+  };
+
+  async fn put(storage: &impl BlobStorageLike, key: &BlobKey) {
+    storage
+      .put_stream(
+        key,
+        Box::pin(stream::once(async { Ok(Bytes::from_static(b"shared")) })),
+        UploadOptions::default(),
+      )
+      .await
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_decorator_stacks_share_storage() {
+    let memory = Arc::new(BlobStorageMemory::new());
+    let chunked =
+      ChunkedBlobStorage::new(memory.clone(), &ChunkingOptions::default())
+        .unwrap();
+    let audited = AuditedBlobStorage::new(
+      Arc::new(chunked.clone()),
+      Arc::new(TracingAuditSink),
+    );
+
+    put(&memory, &BlobKey::new("plain")).await;
+    put(&audited, &BlobKey::new("chunked")).await;
+
+    let metadata = audited.clone().head(&"chunked".into()).await.unwrap();
+    assert_eq!(metadata.unwrap().size, 6);
+    assert!(chunked.head(&"chunked".into()).await.unwrap().is_some());
+    assert!(memory.head(&"plain".into()).await.unwrap().is_some());
+  }
+
+  #[tokio::test]
+  async fn test_boxes_and_references_are_storage() {
+    let memory = BlobStorageMemory::new();
+    put(&&memory, &BlobKey::new("borrowed")).await;
+
+    let boxed: Box<dyn BlobStorageLike> = Box::new(memory.clone());
+    put(&boxed, &BlobKey::new("boxed")).await;
+
+    let stats = memory.stats("").await.unwrap();
+    assert_eq!(stats.object_count, 2);
+  }
+
+  #[tokio::test]
+  async fn test_frontend_clones_share_storage() {
+    let storage = BlobStorage::new_memory();
+    let clone = storage.clone();
+
+    let key = BlobKey::new("shared");
+    put(&storage.inner, &key).await;
+    assert!(clone.exists(&key).await.unwrap());
+  }
+}
+
+mod audit_tests {
+  use std::sync::Arc;
+
+  use bytes::Bytes;
+  use futures::{StreamExt, stream};
+
+  use crate::{
+    BlobKey, BlobStorage, BlobStorageError, UploadOptions,
+    audit::{AuditOperation, AuditOutcome, ChannelAuditSink, OperationContext},
+  };
+
+  #[tokio::test]
+  async fn test_audit_records_operations() {
+    let (sink, mut events) = ChannelAuditSink::new();
+    let storage = BlobStorage::new_memory()
+      .audited(Arc::new(sink), OperationContext::new("alice"));
+    let key = BlobKey::new("audited");
+
+    let data =
+      Box::pin(stream::once(async { Ok(Bytes::from_static(b"hello")) }));
+    storage
+      .put_stream(&key, data, UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
+      .await
+      .unwrap();
+    storage.head(&key).await.unwrap();
+    storage.delete(&key).await.unwrap();
+    let result = storage.get_stream(&key).await;
+    assert!(matches!(result, Err(BlobStorageError::NotFound(_))));
+
+    let put = events.next().await.unwrap();
+    assert_eq!(put.operation, AuditOperation::Put);
+    assert_eq!(put.key, key);
+    assert_eq!(put.size, Some(5));
+    assert_eq!(put.context.caller.as_deref(), Some("alice"));
+    assert_eq!(put.outcome, AuditOutcome::Success);
+
+    let head = events.next().await.unwrap();
+    assert_eq!(head.operation, AuditOperation::Head);
+    assert_eq!(head.size, Some(5));
+
+    let delete = events.next().await.unwrap();
+    assert_eq!(delete.operation, AuditOperation::Delete);
+
+    let get = events.next().await.unwrap();
+    assert_eq!(get.operation, AuditOperation::Get);
+    assert!(matches!(get.outcome, AuditOutcome::Failure(_)));
+  }
+
+  #[tokio::test]
+  async fn test_audit_records_file_uploads() {
+    let root = tempfile::tempdir().unwrap();
+    let (sink, mut events) = ChannelAuditSink::new();
+    let storage = BlobStorage::new_fs(root.path())
+      .await
+      .unwrap()
+      .audited(Arc::new(sink), OperationContext::new("alice"));
+    let source_dir = tempfile::tempdir().unwrap();
+    let source = source_dir.path().join("source.txt");
+    std::fs::write(&source, "from a file").unwrap();
+
+    storage
+      .put(
+        &BlobKey::new("from-file.txt"),
+        belt::Belt::from_file(&source),
+        UploadOptions::default(),
+      )
+      .await
+      .unwrap();
+
+    let put = events.next().await.unwrap();
+    assert_eq!(put.operation, AuditOperation::Put);
+    assert_eq!(put.size, Some(11));
+    assert_eq!(put.outcome, AuditOutcome::Success);
+  }
+
+  #[cfg(feature = "audit-table")]
+  #[tokio::test]
+  async fn test_audit_records_to_database() {
+    use db::Database;
+    use model::IndexValue;
+
+    use crate::audit::{
+      AuditRecord, AuditRecordIndexSelector, DatabaseAuditSink,
+    };
+
+    let db = Database::<AuditRecord>::new_mock();
+    let storage = BlobStorage::new_memory().audited(
+      Arc::new(DatabaseAuditSink::new(db.clone())),
+      OperationContext::new("alice"),
+    );
+    let key = BlobKey::new("audited");
+
+    let data =
+      Box::pin(stream::once(async { Ok(Bytes::from_static(b"hello")) }));
+    storage
+      .put_stream(&key, data, UploadOptions::default())
+      .await
+      .unwrap();
+    storage.delete(&key).await.unwrap();
+
+    let records = db
+      .find_by_index(
+        AuditRecordIndexSelector::Caller,
+        &IndexValue::new_single("alice"),
+      )
+      .await
+      .unwrap();
+    let mut operations = records
+      .iter()
+      .map(|r| r.event.operation.to_string())
+      .collect::<Vec<_>>();
+    operations.sort();
+    assert_eq!(operations, ["delete", "put"]);
+
+    let records = db
+      .find_by_index(
+        AuditRecordIndexSelector::Key,
+        &IndexValue::new_single("audited"),
+      )
+      .await
+      .unwrap();
+    assert_eq!(records.len(), 2);
+  }
+}
+
+mod events_tests {
+  use std::sync::Arc;
+
+  use bytes::Bytes;
+  use futures::{StreamExt, stream};
+
+  use crate::{
+    BlobKey, BlobStorage, UploadOptions,
+    events::{BlobEventKind, ChannelEventSink},
+  };
+
+  async fn put(storage: &BlobStorage, key: &BlobKey, data: &'static [u8]) {
+    let data = Box::pin(stream::once(async { Ok(Bytes::from_static(data)) }));
+    storage
+      .put_stream(key, data, UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
+      .await
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_events_report_changes() {
+    let (sink, mut events) = ChannelEventSink::new();
+    let storage = BlobStorage::new_memory().with_events(Arc::new(sink));
+    let key = BlobKey::new("photos/cat.png");
+
+    put(&storage, &key, b"meow").await;
+    put(&storage, &key, b"purr!").await;
+    // reads aren't reported
+    storage.head(&key).await.unwrap();
+    storage.delete(&key).await.unwrap();
+
+    let created = events.next().await.unwrap();
+    assert_eq!(created.kind, BlobEventKind::Created);
+    assert_eq!(created.key, key);
+    assert_eq!(created.size, Some(4));
+
+    let overwritten = events.next().await.unwrap();
+    assert_eq!(overwritten.kind, BlobEventKind::Overwritten);
+    assert_eq!(overwritten.size, Some(5));
+
+    let deleted = events.next().await.unwrap();
+    assert_eq!(deleted.kind, BlobEventKind::Deleted);
+    assert_eq!(deleted.size, None);
+
+    drop(storage);
+    assert!(events.next().await.is_none());
+  }
+
+  #[tokio::test]
+  async fn test_failed_upload_is_not_reported() {
+    let (sink, events) = ChannelEventSink::new();
+    let storage = BlobStorage::new_memory().with_events(Arc::new(sink));
+    let key = BlobKey::new("existing");
+    put(&storage, &key, b"first").await;
+
+    let data = Box::pin(stream::once(async { Ok(Bytes::from_static(b"x")) }));
+    let result = storage
+      .put_stream(&key, data, UploadOptions {
+        overwrite: false,
+        ..UploadOptions::default()
+      })
+      .await;
+    assert!(result.is_err());
+
+    drop(storage);
+    let kinds: Vec<_> = events.map(|e| e.kind).collect().await;
+    assert_eq!(kinds, [BlobEventKind::Created]);
+  }
+}
+
+mod sniff_tests {
+  use bytes::Bytes;
+  use futures::{TryStreamExt, stream};
+
+  use crate::{BlobKey, BlobStorage, UploadOptions};
+
+  const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+  async fn upload(
+    storage: &BlobStorage,
+    key: &BlobKey,
+    chunks: &[&'static [u8]],
+    content_type: Option<&str>,
+  ) {
+    let chunks = chunks
+      .iter()
+      .map(|c| Ok(Bytes::from_static(c)))
+      .collect::<Vec<_>>();
+    storage
+      .put_stream(key, Box::pin(stream::iter(chunks)), UploadOptions {
+        overwrite: true,
+        content_type: content_type.map(str::to_owned),
+        ..UploadOptions::default()
+      })
+      .await
+      .unwrap();
+  }
+
+  async fn content_type(
+    storage: &BlobStorage,
+    key: &BlobKey,
+  ) -> Option<String> {
+    storage.head(key).await.unwrap().unwrap().content_type
+  }
+
+  #[tokio::test]
+  async fn test_sniffs_missing_content_type() {
+    let storage = BlobStorage::new_memory().with_content_sniffing();
+    let key = BlobKey::new("upload");
+
+    // the signature is split across chunks
+    upload(&storage, &key, &[&PNG[..3], &PNG[3..], b"rest"], None).await;
+    assert_eq!(
+      content_type(&storage, &key).await.as_deref(),
+      Some("image/png")
+    );
+    let chunks: Vec<Bytes> = storage
+      .get_stream(&key)
+      .await
+      .unwrap()
+      .try_collect()
+      .await
+      .unwrap();
+    let data = chunks.concat();
+    assert_eq!(&data[..PNG.len()], PNG);
+    assert_eq!(&data[PNG.len()..], b"rest");
+
+    // unrecognised content is stored without a type
+    upload(&storage, &key, &[b"plain words"], None).await;
+    assert_eq!(content_type(&storage, &key).await, None);
+  }
+
+  #[tokio::test]
+  async fn test_given_content_type_is_kept() {
+    let storage = BlobStorage::new_memory().with_content_sniffing();
+    let key = BlobKey::new("upload");
+
+    upload(&storage, &key, &[PNG], Some("application/x-custom")).await;
+    assert_eq!(
+      content_type(&storage, &key).await.as_deref(),
+      Some("application/x-custom")
+    );
+  }
+}
+
+mod scan_tests {
+  use std::sync::Arc;
+
+  use belt::Belt;
+  use bytes::Bytes;
+  use futures::stream;
+
+  use crate::{
+    BlobKey, BlobStorage, BlobStorageError, BlobStorageResult, UploadOptions,
+    scan::{ScanPolicy, ScanVerdict},
+  };
+
+  struct RejectSignature;
+
+  #[async_trait::async_trait]
+  impl ScanPolicy for RejectSignature {
+    async fn scan(
+      &self,
+      _key: &BlobKey,
+      data: Belt,
+    ) -> BlobStorageResult<ScanVerdict> {
+      let data = data.collect_bytes().await?;
+      if data.windows(5).any(|w| w == b"VIRUS") {
+        Ok(ScanVerdict::Reject("signature matched".to_owned()))
+      } else {
+        Ok(ScanVerdict::Accept)
+      }
+    }
+  }
+
+  async fn upload(
+    storage: &BlobStorage,
+    key: &BlobKey,
+    chunks: &[&'static [u8]],
+  ) -> BlobStorageResult<()> {
+    let chunks = chunks
+      .iter()
+      .map(|c| Ok(Bytes::from_static(c)))
+      .collect::<Vec<_>>();
+    storage
+      .put_stream(key, Box::pin(stream::iter(chunks)), UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
+      .await
+  }
+
+  #[tokio::test]
+  async fn test_scan_accepts_clean_upload() {
+    let storage =
+      BlobStorage::new_memory().with_scan_policy(Arc::new(RejectSignature));
+    let key = BlobKey::new("clean");
+
+    upload(&storage, &key, &[b"hello ", b"world"])
+      .await
+      .unwrap();
+
+    assert_eq!(storage.head(&key).await.unwrap().unwrap().size, 11);
+  }
+
+  #[tokio::test]
+  async fn test_scan_rejects_upload() {
+    let storage =
+      BlobStorage::new_memory().with_scan_policy(Arc::new(RejectSignature));
+    let key = BlobKey::new("infected");
+
+    let result = upload(&storage, &key, &[b"harmless ", b"VIRUS"]).await;
+
+    assert!(matches!(result, Err(BlobStorageError::Rejected(_))));
+    assert!(!storage.exists(&key).await.unwrap());
+  }
+}
+
+mod limit_tests {
+  use std::time::{Duration, Instant};
+
+  use bytes::Bytes;
+  use futures::stream;
+
+  use crate::{BlobKey, BlobStorage, UploadOptions, limit::StorageLimits};
+
+  async fn put(storage: &BlobStorage, key: &BlobKey, data: &'static [u8]) {
+    storage
+      .put_stream(
+        key,
+        Box::pin(stream::once(async { Ok(Bytes::from_static(data)) })),
+        UploadOptions::default(),
+      )
+      .await
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_limits_concurrent_operations() {
+    let storage = BlobStorage::new_memory()
+      .with_limits(StorageLimits::new().with_max_concurrent(1));
+    let key = BlobKey::new("limited");
+    put(&storage, &key, b"data").await;
+
+    // an open download holds the only slot
+    let download = storage.get_stream(&key).await.unwrap();
+    let blocked =
+      tokio::time::timeout(Duration::from_millis(50), storage.head(&key)).await;
+    assert!(blocked.is_err());
+
+    drop(download);
+    assert!(storage.head(&key).await.unwrap().is_some());
+  }
+
+  #[tokio::test]
+  async fn test_limits_bandwidth() {
+    let storage = BlobStorage::new_memory()
+      .with_limits(StorageLimits::new().with_max_bytes_per_sec(10_000));
+
+    // a second's worth passes as a burst, the rest waits for the bucket
+    let start = Instant::now();
+    put(&storage, &BlobKey::new("burst"), &[0; 10_000]).await;
+    put(&storage, &BlobKey::new("throttled"), &[0; 4_000]).await;
+    assert!(start.elapsed() >= Duration::from_millis(300));
+  }
+}
+
+mod chunked_tests {
+  use std::{sync::Arc, time::Duration};
+
+  use bytes::Bytes;
+  use clock::ManualClock;
+  use futures::{TryStreamExt, stream};
+  use storage_core::BlobStorageLike;
+  use storage_impl_memory::BlobStorageMemory;
+
+  use crate::{
+    BlobKey, UploadOptions,
+    audit::{AuditEvent, AuditOperation, AuditedBlobStorage, ChannelAuditSink},
+    chunked::{ChunkedBlobStorage, ChunkingOptions},
+  };
+
+  fn options() -> ChunkingOptions {
+    ChunkingOptions {
+      min_size: 1024,
+      avg_size: 4096,
+      max_size: 16 * 1024,
+      ..Default::default()
+    }
+  }
+
+  // Deterministic pseudo-random data, so chunk boundaries are realistic
+  fn test_data(len: usize) -> Vec<u8> {
+    let mut state: u32 = 0x1234_5678;
+    (0..len)
+      .map(|_| {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        state.to_be_bytes()[0]
+      })
+      .collect()
+  }
+
+  // Upload in uneven network-sized pieces to exercise buffering
+  async fn upload(storage: &impl BlobStorageLike, key: &BlobKey, data: &[u8]) {
+    let pieces = data
+      .chunks(3000)
+      .map(|c| Ok(Bytes::copy_from_slice(c)))
+      .collect::<Vec<_>>();
+    storage
+      .put_stream(key, Box::pin(stream::iter(pieces)), UploadOptions {
+        overwrite: true,
+        ..UploadOptions::default()
+      })
+      .await
+      .unwrap();
+  }
+
+  async fn download(storage: &impl BlobStorageLike, key: &BlobKey) -> Vec<u8> {
+    let chunks: Vec<Bytes> = storage
+      .get_stream(key)
+      .await
+      .unwrap()
+      .try_collect()
+      .await
+      .unwrap();
+    chunks.concat()
+  }
+
+  #[tokio::test]
+  async fn test_stats_report_original_sizes() {
+    let inner = Arc::new(BlobStorageMemory::new());
+    let storage = ChunkedBlobStorage::new(inner.clone(), &options()).unwrap();
+
+    let data = test_data(64 * 1024);
+    upload(&storage, &BlobKey::new("a"), &data).await;
+    upload(&storage, &BlobKey::new("b"), &data[..1000]).await;
+
+    let stats = storage.stats("").await.unwrap();
+    assert_eq!(stats.object_count, 2);
+    assert_eq!(stats.total_bytes, 64 * 1024 + 1000);
+    assert_eq!(stats.largest.unwrap().key.as_str(), "a");
+    assert!(inner.stats("").await.unwrap().object_count > 2);
+  }
+
+  fn drain_puts(
+    events: &mut futures::channel::mpsc::UnboundedReceiver<AuditEvent>,
+  ) -> usize {
+    let mut puts = 0;
+    while let Ok(Some(event)) = events.try_next() {
+      if event.operation == AuditOperation::Put {
+        puts += 1;
+      }
+    }
+    puts
+  }
+
+  #[tokio::test]
+  async fn test_chunked_round_trip() {
+    let storage =
+      ChunkedBlobStorage::new(Arc::new(BlobStorageMemory::new()), &options())
+        .unwrap();
+    let key = BlobKey::new("artifact");
+    let data = test_data(200 * 1024);
+
+    upload(&storage, &key, &data).await;
+
+    assert_eq!(download(&storage, &key).await, data);
+    let metadata = storage.head(&key).await.unwrap().unwrap();
+    assert_eq!(metadata.size, data.len() as u64);
+  }
+
+  #[tokio::test]
+  async fn test_chunked_range_reads_overlapping_chunks() {
+    let inner = Arc::new(BlobStorageMemory::new());
+    let storage = ChunkedBlobStorage::new(inner.clone(), &options()).unwrap();
+    let key = BlobKey::new("artifact");
+    let data = test_data(200 * 1024);
+    upload(&storage, &key, &data).await;
+
+    for range in [0..1, 1000..70_000, 150_000..300_000] {
+      let chunks: Vec<Bytes> = storage
+        .get_stream_range(&key, range.start as u64..range.end as u64)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+      let end = range.end.min(data.len());
+      assert_eq!(chunks.concat(), data[range.start..end]);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_chunked_empty_blob() {
+    let storage =
+      ChunkedBlobStorage::new(Arc::new(BlobStorageMemory::new()), &options())
+        .unwrap();
+    let key = BlobKey::new("empty");
+
+    upload(&storage, &key, &[]).await;
+
+    assert!(download(&storage, &key).await.is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_chunked_deduplicates() {
+    let (sink, mut events) = ChannelAuditSink::new();
+    let inner = Arc::new(AuditedBlobStorage::new(
+      Arc::new(BlobStorageMemory::new()),
+      Arc::new(sink),
+    ));
+    let storage = ChunkedBlobStorage::new(inner, &options()).unwrap();
+    let data = test_data(200 * 1024);
+
+    upload(&storage, &BlobKey::new("first"), &data).await;
+    let first_puts = drain_puts(&mut events);
+
+    // identical contents only store a new manifest
+    upload(&storage, &BlobKey::new("second"), &data).await;
+    assert_eq!(drain_puts(&mut events), 1);
+
+    // a small edit only stores the chunks around it
+    let mut edited = data.clone();
+    edited[100 * 1024] ^= 0xff;
+    upload(&storage, &BlobKey::new("third"), &edited).await;
+    let edited_puts = drain_puts(&mut events);
+    assert!(
+      edited_puts <= 3,
+      "stored {edited_puts} of {first_puts} puts"
+    );
+
+    assert_eq!(download(&storage, &BlobKey::new("third")).await, edited);
+  }
+
+  #[tokio::test]
+  async fn test_collect_garbage_deletes_unreferenced_chunks() {
+    // chunks are stamped long ago, so they're past any grace period
+    let inner = Arc::new(
+      BlobStorageMemory::new().with_clock(Arc::new(ManualClock::default())),
+    );
+    let storage = ChunkedBlobStorage::new(inner.clone(), &options()).unwrap();
+    let data = test_data(200 * 1024);
+    let mut edited = data.clone();
+    edited[100 * 1024] ^= 0xff;
+    upload(&storage, &BlobKey::new("first"), &data).await;
+    upload(&storage, &BlobKey::new("second"), &edited).await;
+    let stored = inner.stats("chunks/").await.unwrap().object_count;
+
+    // chunks shared with the second blob are kept
+    storage.delete(&BlobKey::new("first")).await.unwrap();
+    let deleted = storage
+      .collect_garbage(Duration::from_hours(1))
+      .await
+      .unwrap();
+    assert!(deleted > 0);
+    assert_eq!(
+      inner.stats("chunks/").await.unwrap().object_count,
+      stored - deleted as u64
+    );
+    assert_eq!(download(&storage, &BlobKey::new("second")).await, edited);
+    assert_eq!(storage.collect_garbage(Duration::ZERO).await.unwrap(), 0);
+
+    storage.delete(&BlobKey::new("second")).await.unwrap();
+    storage.collect_garbage(Duration::ZERO).await.unwrap();
+    assert_eq!(inner.stats("").await.unwrap().object_count, 0);
+  }
+
+  #[tokio::test]
+  async fn test_collect_garbage_keeps_recent_chunks() {
+    let inner = Arc::new(BlobStorageMemory::new());
+    let storage = ChunkedBlobStorage::new(inner.clone(), &options()).unwrap();
+    let key = BlobKey::new("artifact");
+    upload(&storage, &key, &test_data(64 * 1024)).await;
+    storage.delete(&key).await.unwrap();
+
+    // e.g. the chunks of an upload which hasn't written its manifest yet
+    let deleted = storage.collect_garbage(Duration::from_hours(1)).await;
+    assert_eq!(deleted.unwrap(), 0);
+    assert!(inner.stats("chunks/").await.unwrap().object_count > 0);
+  }
+}
+
+mod config_tests {
+  use std::collections::HashMap;
+
+  use crate::{BlobStorage, StorageConfig, StorageConfigError};
+
+  fn from_vars(
+    vars: &[(&str, &str)],
+  ) -> Result<StorageConfig, StorageConfigError> {
+    let vars: HashMap<_, _> = vars.iter().copied().collect();
+    StorageConfig::from_lookup("STORAGE", |name| {
+      Ok(vars.get(name).map(|v| (*v).to_owned()))
+    })
+  }
+
+  #[test]
+  fn test_config_from_env_r2() {
+    let config = from_vars(&[
+      ("STORAGE_BACKEND", "r2"),
+      ("STORAGE_BUCKET", "artifacts"),
+      ("STORAGE_ACCOUNT_ID", "abc123"),
+      ("STORAGE_ACCESS_KEY", "key"),
+      ("STORAGE_SECRET_ACCESS_KEY", "secret"),
+    ])
+    .unwrap();
+
+    assert_eq!(config, StorageConfig::R2 {
+      bucket:            "artifacts".to_owned(),
+      account_id:        "abc123".to_owned(),
+      access_key:        "key".to_owned(),
+      secret_access_key: "secret".to_owned(),
+    });
+    assert!(!format!("{config:?}").contains("secret\""));
+  }
+
+  #[test]
+  fn test_config_from_env_errors() {
+    assert!(matches!(
+      from_vars(&[]),
+      Err(StorageConfigError::MissingVar(var)) if var == "STORAGE_BACKEND"
+    ));
+    assert!(matches!(
+      from_vars(&[("STORAGE_BACKEND", "ftp")]),
+      Err(StorageConfigError::UnknownBackend(_))
+    ));
+    assert!(matches!(
+      from_vars(&[("STORAGE_BACKEND", "fs")]),
+      Err(StorageConfigError::MissingVar(var)) if var == "STORAGE_PATH"
+    ));
+    assert!(matches!(
+      from_vars(&[("STORAGE_BACKEND", "fs"), ("STORAGE_PATH", " ")]),
+      Err(StorageConfigError::EmptyField("root_path"))
+    ));
+  }
+
+  #[test]
+  fn test_config_deserialize_backend_field() {
+    let config: StorageConfig =
+      serde_json::from_str(r#"{ "backend": "fs", "root_path": "/tmp/blobs" }"#)
+        .unwrap();
+    assert_eq!(config, StorageConfig::Fs {
+      root_path:  "/tmp/blobs".into(),
+      dedupe:     false,
+      weak_etags: false,
+    });
+  }
+
+  #[test]
+  fn test_config_from_env_fs_flags() {
+    let config = from_vars(&[
+      ("STORAGE_BACKEND", "fs"),
+      ("STORAGE_PATH", "/tmp/blobs"),
+      ("STORAGE_DEDUPE", "true"),
+    ])
+    .unwrap();
+    assert_eq!(config, StorageConfig::Fs {
+      root_path:  "/tmp/blobs".into(),
+      dedupe:     true,
+      weak_etags: false,
+    });
+
+    assert!(matches!(
+      from_vars(&[
+        ("STORAGE_BACKEND", "fs"),
+        ("STORAGE_PATH", "/tmp/blobs"),
+        ("STORAGE_WEAK_ETAGS", "yes"),
+      ]),
+      Err(StorageConfigError::NotBool(var)) if var == "STORAGE_WEAK_ETAGS"
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_from_config_memory() {
+    let storage = BlobStorage::from_config(StorageConfig::Memory)
+      .await
+      .unwrap();
+    assert!(!storage.exists(&"missing".into()).await.unwrap());
+  }
+
+  #[tokio::test]
+  async fn test_from_config_fs_weak_etags() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("placed.txt"), "placed").unwrap();
+    let storage = BlobStorage::from_config(StorageConfig::Fs {
+      root_path:  dir.path().to_owned(),
+      dedupe:     false,
+      weak_etags: true,
+    })
+    .await
+    .unwrap();
+
+    let metadata = storage.head(&"placed.txt".into()).await.unwrap().unwrap();
+    assert!(metadata.etag.is_some());
+  }
+}
+
+mod serialization_tests {
+  use crate::{BlobKey, BlobStorageError};
+
+  #[test]
+  fn test_error_serialization() {
+    let error = BlobStorageError::NotFound(BlobKey::new("missing"));
+    assert_eq!(
+      serde_json::to_value(&error).unwrap(),
+      serde_json::json!({
+        "code": "not_found",
+        "message": "Blob not found: missing",
+      })
+    );
+  }
+}
+
+#[cfg(feature = "blocking")]
+mod blocking_tests {
+  use crate::{BlobKey, UploadOptions, blocking::BlobStorage};
+
+  #[test]
+  fn test_blocking_round_trip() {
+    let storage = BlobStorage::new_memory().unwrap();
+    let key = BlobKey::new("blocking.txt");
+
+    storage
+      .put(&key, &b"blocking data"[..], UploadOptions::default())
+      .unwrap();
+    assert_eq!(storage.get(&key).unwrap(), &b"blocking data"[..]);
+    assert_eq!(storage.head(&key).unwrap().unwrap().size, 13);
+
+    storage.delete(&key).unwrap();
+    assert!(!storage.exists(&key).unwrap());
+  }
+
+  #[test]
+  fn test_blocking_fs() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = BlobStorage::new_fs(dir.path()).unwrap();
+    let key = BlobKey::new("nested/blob");
+
+    storage
+      .put(&key, vec![1, 2, 3], UploadOptions::default())
+      .unwrap();
+    assert_eq!(storage.get(&key).unwrap(), vec![1, 2, 3]);
+  }
+
+  #[test]
+  fn test_blocking_clone_shares_storage() {
+    let storage = BlobStorage::new_memory().unwrap();
+    let clone = storage.clone();
+    let key = BlobKey::new("shared.txt");
+
+    storage
+      .put(&key, &b"shared"[..], UploadOptions::default())
+      .unwrap();
+    drop(storage);
+    assert_eq!(clone.get(&key).unwrap(), &b"shared"[..]);
+  }
+}
+
+mod url_tests {
+  use std::{sync::Arc, time::Duration};
+
+  use bytes::Bytes;
+  use chrono::{DateTime, Utc};
+  use futures::stream;
+
+  use crate::{
+    BlobKey, BlobStorage, BlobStorageResult, UploadOptions,
+    urls::{UrlRewrite, UrlSigner},
+  };
+
+  /// Signs URLs with their expiry alone.
+  #[derive(Debug)]
+  struct ExpirySigner;
+
+  impl UrlSigner for ExpirySigner {
+    fn signed_url(
+      &self,
+      key: &BlobKey,
+      expires_at: DateTime<Utc>,
+    ) -> BlobStorageResult<String> {
+      Ok(format!(
+        "https://private.test/{key}?expires={}",
+        expires_at.timestamp()
+      ))
+    }
+  }
+
+  #[test]
+  fn test_rewrite() {
+    let rewrite = UrlRewrite::new(
+      "https://bucket.s3.amazonaws.com/",
+      "https://files.example.com",
+    );
+    assert_eq!(
+      rewrite
+        .rewrite("https://bucket.s3.amazonaws.com/a/b.png?X-Amz-Signature=1"),
+      "https://files.example.com/a/b.png?X-Amz-Signature=1"
+    );
+    // only URLs under the origin are rewritten
+    for url in [
+      "https://other.example.com/a.png",
+      "https://bucket.s3.amazonaws.com.evil.com/a.png",
+    ] {
+      assert_eq!(rewrite.rewrite(url), url);
+    }
+
+    let rewrite = rewrite.without_query();
+    assert_eq!(
+      rewrite
+        .rewrite("https://bucket.s3.amazonaws.com/a/b.png?X-Amz-Signature=1"),
+      "https://files.example.com/a/b.png"
+    );
+  }
+
+  #[test]
+  fn test_public_url() {
+    let storage = BlobStorage::new_memory();
+    assert_eq!(storage.get_public_url(&"avatars/1.png".into()), None);
+
+    let storage = storage
+      .with_public_base_url("https://bucket.s3.amazonaws.com/")
+      .with_content_sniffing();
+    assert_eq!(
+      storage.get_public_url(&"avatars/1.png".into()).as_deref(),
+      Some("https://bucket.s3.amazonaws.com/avatars/1.png")
+    );
+
+    let storage = storage.with_url_rewrite(UrlRewrite::new(
+      "https://bucket.s3.amazonaws.com",
+      "https://files.example.com",
+    ));
+    assert_eq!(
+      storage.get_public_url(&"avatars/1.png".into()).as_deref(),
+      Some("https://files.example.com/avatars/1.png")
+    );
+
+    assert_eq!(
+      storage
+        .get_public_url(&"avatars/my photo#1.png".into())
+        .as_deref(),
+      Some("https://files.example.com/avatars/my%20photo%231.png")
+    );
+  }
+
+  #[tokio::test]
+  async fn test_presigned_url_rewritten() {
+    let storage = BlobStorage::new_memory()
+      .with_url_rewrite(UrlRewrite::new("memory://blob", "https://cdn.test"));
+    let key = BlobKey::new("a.txt");
+    storage
+      .put_stream(
+        &key,
+        Box::pin(stream::once(async { Ok(Bytes::from_static(b"a")) })),
+        UploadOptions::default(),
+      )
+      .await
+      .unwrap();
+
+    let url = storage
+      .get_presigned_url(&key, Duration::from_mins(1))
+      .await
+      .unwrap();
+    assert_eq!(url, "https://cdn.test/a.txt");
+  }
+
+  #[tokio::test]
+  async fn test_presigned_url_signed() {
+    let storage = BlobStorage::new_memory()
+      .with_url_rewrite(UrlRewrite::new(
+        "https://private.test",
+        "https://cdn.test",
+      ))
+      .with_url_signer(Arc::new(ExpirySigner));
+
+    // signed without asking the backend, so the blob needn't exist
+    let before = Utc::now().timestamp();
+    let url = storage
+      .get_presigned_url(&"a.txt".into(), Duration::from_mins(1))
+      .await
+      .unwrap();
+    let expires: i64 = url
+      .strip_prefix("https://private.test/a.txt?expires=")
+      .unwrap()
+      .parse()
+      .unwrap();
+    assert!((before + 60..=Utc::now().timestamp() + 60).contains(&expires));
+  }
+}
+
+mod staging_tests {
+  use std::time::Duration;
+
+  use belt::Belt;
+
+  use crate::{
+    BlobKey, BlobStorage, UploadOptions,
+    staging::{STAGING_PREFIX, staging_key},
+  };
+
+  #[tokio::test]
+  async fn test_abandoned_staged_blobs_are_purged() {
+    let storage =
+      BlobStorage::new_memory().with_staging_max_age(Duration::ZERO);
+    let (a, b) = (BlobKey::new("a"), BlobKey::new("b"));
+
+    // the first staged put sweeps before uploading, and the next isn't due
+    // to sweep yet
+    storage
+      .put_staged(&a, Belt::from("a"), UploadOptions::default())
+      .await
+      .unwrap();
+    storage
+      .put_staged(&b, Belt::from("b"), UploadOptions::default())
+      .await
+      .unwrap();
+    assert!(storage.exists(&staging_key(&a)).await.unwrap());
+
+    storage.promote(&b).await.unwrap();
+    assert_eq!(storage.purge_abandoned_staged().await.unwrap(), 1);
+    assert_eq!(storage.stats(STAGING_PREFIX).await.unwrap().object_count, 0);
+    assert!(storage.exists(&b).await.unwrap());
+  }
+}
+
+#[cfg(feature = "watch")]
+mod watch_tests {
+  use std::{sync::Arc, time::Duration};
+
+  use futures::StreamExt;
+
+  use crate::{
+    BlobKey, BlobStorage,
+    events::{BlobEventKind, ChannelEventSink},
+    watch::FsWatcher,
+  };
+
+  #[tokio::test]
+  async fn test_external_files_become_blobs() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (sink, mut events) = ChannelEventSink::new();
+    let _watcher = FsWatcher::spawn_with_settle(
+      temp_dir.path(),
+      Arc::new(sink),
+      Duration::from_millis(50),
+    )
+    .await
+    .unwrap();
+    let storage = BlobStorage::new_fs(temp_dir.path()).await.unwrap();
+    let mut next = async || {
+      tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .unwrap()
+        .unwrap()
+    };
+
+    std::fs::create_dir(temp_dir.path().join("drop")).unwrap();
+    std::fs::write(temp_dir.path().join("drop/a.txt"), "hello").unwrap();
+    let event = next().await;
+    assert_eq!(event.kind, BlobEventKind::Created);
+    assert_eq!(event.key.as_str(), "drop/a.txt");
+    assert_eq!(event.size, Some(5));
+    let metadata = storage.head(&event.key).await.unwrap().unwrap();
+    assert!(metadata.etag.is_some());
+
+    std::fs::remove_file(temp_dir.path().join("drop/a.txt")).unwrap();
+    let event = next().await;
+    assert_eq!(event.kind, BlobEventKind::Deleted);
+    assert!(!storage.exists(&BlobKey::new("drop/a.txt")).await.unwrap());
+  }
 }
@@ -0,0 +1,89 @@
+//! Resumable upload sessions.
+
+use std::{fmt, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use storage_core::{
+  BlobKey, BlobStorageLike, BlobStorageResult, Bytes, UploadHandle,
+  UploadedPart,
+};
+
+/// The persistent state of an [`UploadSession`].
+///
+/// Save this after every [`append`](UploadSession::append) and pass it to
+/// [`BlobStorage::resume_upload`](crate::BlobStorage::resume_upload) to
+/// continue the upload after a crash or restart.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadSessionState {
+  /// The backend's handle for the upload.
+  pub handle: UploadHandle,
+  /// The parts uploaded so far, in order.
+  pub parts:  Vec<UploadedPart>,
+}
+
+/// An in-progress resumable upload.
+///
+/// The blob is not visible until the session is
+/// [`finalize`](Self::finalize)d.
+pub struct UploadSession {
+  inner: Arc<dyn BlobStorageLike>,
+  state: UploadSessionState,
+}
+
+impl fmt::Debug for UploadSession {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("UploadSession")
+      .field("state", &self.state)
+      .finish_non_exhaustive()
+  }
+}
+
+impl UploadSession {
+  pub(crate) const fn new(
+    inner: Arc<dyn BlobStorageLike>,
+    state: UploadSessionState,
+  ) -> Self {
+    Self { inner, state }
+  }
+
+  /// The key the blob will be stored at.
+  #[must_use]
+  pub const fn key(&self) -> &BlobKey { &self.state.handle.key }
+
+  /// The persistent state of the session.
+  #[must_use]
+  pub const fn state(&self) -> &UploadSessionState { &self.state }
+
+  /// The number of bytes uploaded so far.
+  #[must_use]
+  pub fn uploaded_size(&self) -> u64 {
+    self.state.parts.iter().map(|p| p.size).sum()
+  }
+
+  /// Upload the next part of the blob.
+  pub async fn append(
+    &mut self,
+    part: Bytes,
+  ) -> BlobStorageResult<&UploadedPart> {
+    let part_number = self.state.parts.last().map_or(1, |p| p.part_number + 1);
+    let part = self
+      .inner
+      .upload_part(&self.state.handle, part_number, part)
+      .await?;
+    self.state.parts.push(part);
+    Ok(&self.state.parts[self.state.parts.len() - 1])
+  }
+
+  /// Complete the upload, making the blob visible.
+  pub async fn finalize(self) -> BlobStorageResult<()> {
+    self
+      .inner
+      .complete_upload(&self.state.handle, &self.state.parts)
+      .await
+  }
+
+  /// Abort the upload, discarding any uploaded parts.
+  pub async fn abort(self) -> BlobStorageResult<()> {
+    self.inner.abort_upload(&self.state.handle).await
+  }
+}
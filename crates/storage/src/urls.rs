@@ -0,0 +1,134 @@
+//! Mapping the URLs blob storage returns onto a custom domain or CDN.
+//!
+//! A [`UrlRewrite`] substitutes a CDN's base URL for the origin endpoint's in
+//! the URLs returned by
+//! [`get_presigned_url`](crate::BlobStorage::get_presigned_url)
+//! and [`get_public_url`](crate::BlobStorage::get_public_url). By default the
+//! query string is kept, so pre-signed URLs still carry their signature for a
+//! CDN which forwards it to the origin. For a CDN which authorizes requests
+//! itself, e.g. with signed cookies, the query string can be dropped.
+//!
+//! For a private CDN which authorizes requests with its own signatures, a
+//! [`UrlSigner`] replaces pre-signing by the backend altogether. With the
+//! `cloudfront` feature, [`CloudFrontSigner`] signs URLs for `CloudFront`
+//! distributions.
+
+#[cfg(feature = "cloudfront")]
+mod cloudfront;
+
+use std::{
+  fmt::{self, Write},
+  sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use storage_core::{BlobKey, BlobStorageResult};
+
+#[cfg(feature = "cloudfront")]
+pub use self::cloudfront::CloudFrontSigner;
+
+/// The URL path of the blob at `key`, with each segment percent-encoded.
+pub(crate) fn key_path(key: &BlobKey) -> String {
+  key
+    .as_str()
+    .split('/')
+    .map(|segment| {
+      segment.bytes().fold(String::new(), |mut encoded, b| {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+          encoded.push(char::from(b));
+        } else {
+          let _ = write!(encoded, "%{b:02X}");
+        }
+        encoded
+      })
+    })
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+/// Signs URLs granting temporary access to blobs, in place of the backend's
+/// pre-signed URLs.
+pub trait UrlSigner: fmt::Debug + Send + Sync {
+  /// Returns a URL granting access to the blob at `key` until `expires_at`.
+  fn signed_url(
+    &self,
+    key: &BlobKey,
+    expires_at: DateTime<Utc>,
+  ) -> BlobStorageResult<String>;
+}
+
+/// Substitutes a CDN's base URL for the origin's in returned URLs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UrlRewrite {
+  origin:     String,
+  cdn:        String,
+  keep_query: bool,
+}
+
+impl UrlRewrite {
+  /// Creates a new [`UrlRewrite`] replacing the base URL `origin`, e.g.
+  /// `https://bucket.s3.us-east-1.amazonaws.com`, with `cdn`, e.g.
+  /// `https://files.example.com`.
+  #[must_use]
+  pub fn new(origin: impl Into<String>, cdn: impl Into<String>) -> Self {
+    Self {
+      origin:     origin.into().trim_end_matches('/').to_owned(),
+      cdn:        cdn.into().trim_end_matches('/').to_owned(),
+      keep_query: true,
+    }
+  }
+
+  /// Drops the query string from rewritten URLs, for CDNs which authorize
+  /// requests themselves rather than forwarding the origin's signature.
+  #[must_use]
+  pub const fn without_query(mut self) -> Self {
+    self.keep_query = false;
+    self
+  }
+
+  /// Rewrites `url` if it lies under the origin, returning it unchanged
+  /// otherwise.
+  #[must_use]
+  pub fn rewrite(&self, url: &str) -> String {
+    let Some(rest) = url
+      .strip_prefix(&self.origin)
+      .filter(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+    else {
+      return url.to_owned();
+    };
+    let rest = if self.keep_query {
+      rest
+    } else {
+      rest.split_once('?').map_or(rest, |(path, _)| path)
+    };
+    format!("{}{rest}", self.cdn)
+  }
+}
+
+/// How a [`BlobStorage`](crate::BlobStorage) builds the URLs it returns.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct UrlConfig {
+  /// The base URL public blobs are served from.
+  pub(crate) public_base_url: Option<String>,
+  /// The rewrite applied to returned URLs.
+  pub(crate) rewrite:         Option<UrlRewrite>,
+  /// The signer of pre-signed URLs, replacing the backend's.
+  pub(crate) signer:          Option<Arc<dyn UrlSigner>>,
+}
+
+impl UrlConfig {
+  /// Applies the rewrite, if any, to `url`.
+  pub(crate) fn finish(&self, url: String) -> String {
+    match &self.rewrite {
+      Some(rewrite) => rewrite.rewrite(&url),
+      None => url,
+    }
+  }
+
+  /// The public URL of the blob at `key`, if a public base URL is set.
+  pub(crate) fn public_url(&self, key: &BlobKey) -> Option<String> {
+    let base = self.public_base_url.as_deref()?;
+    let base = base.trim_end_matches('/');
+    Some(self.finish(format!("{base}/{}", key_path(key))))
+  }
+}
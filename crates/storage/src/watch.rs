@@ -0,0 +1,187 @@
+//! Picking up files added to filesystem storage by other tools.
+//!
+//! For local development, [`FsWatcher`] watches a filesystem storage root
+//! so that files dropped into it, e.g. by a file manager or a build step,
+//! become blobs with metadata like any uploaded through the storage. Each
+//! changed file is [reconciled](BlobStorageFilesystem::reconcile), creating
+//! or repairing its metadata sidecar, and a [`BlobEvent`] is sent for each
+//! external change. Changes made through the storage itself are already up
+//! to date once they settle, so they're not reported again.
+
+use std::{
+  collections::BTreeSet,
+  fmt,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::Duration,
+};
+
+use chrono::Utc;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use storage_core::{BlobStorageError, BlobStorageLike, BlobStorageResult};
+use storage_impl_fs::{BlobStorageFilesystem, Reconciled};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{debug, warn};
+
+use crate::events::{BlobEvent, BlobEventKind, BlobEventSink};
+
+/// How long a path must be quiet before it's reconciled, so files still
+/// being written, or being written through the storage, settle first.
+const DEFAULT_SETTLE: Duration = Duration::from_millis(250);
+
+/// Watches a filesystem storage root, repairing the metadata of files
+/// changed by other tools and reporting them as [`BlobEvent`]s.
+///
+/// Watching stops when the [`FsWatcher`] is dropped.
+pub struct FsWatcher {
+  root_path: PathBuf,
+  _watcher:  RecommendedWatcher,
+  task:      JoinHandle<()>,
+}
+
+impl fmt::Debug for FsWatcher {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("FsWatcher")
+      .field("root_path", &self.root_path)
+      .finish_non_exhaustive()
+  }
+}
+
+impl FsWatcher {
+  /// Starts watching the filesystem storage rooted at `root_path`, sending
+  /// events for external changes to `sink`. Must be called within a Tokio
+  /// runtime.
+  ///
+  /// Only changes made after the watcher starts are picked up.
+  pub async fn spawn(
+    root_path: impl AsRef<Path>,
+    sink: Arc<dyn BlobEventSink>,
+  ) -> BlobStorageResult<Self> {
+    Self::spawn_with_settle(root_path, sink, DEFAULT_SETTLE).await
+  }
+
+  /// Like [`spawn`](Self::spawn), but waiting `settle` for a burst of
+  /// changes to end before reconciling them.
+  pub async fn spawn_with_settle(
+    root_path: impl AsRef<Path>,
+    sink: Arc<dyn BlobEventSink>,
+    settle: Duration,
+  ) -> BlobStorageResult<Self> {
+    // notifications carry canonical paths, so the root must be too
+    tokio::fs::create_dir_all(root_path.as_ref()).await?;
+    let root_path = tokio::fs::canonicalize(root_path.as_ref()).await?;
+    let storage = BlobStorageFilesystem::new(&root_path).await?;
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(
+      move |result: notify::Result<notify::Event>| match result {
+        Ok(event) => {
+          for path in event.paths {
+            let _ = sender.send(path);
+          }
+        }
+        Err(error) => warn!(%error, "filesystem watch error"),
+      },
+    )
+    .map_err(watch_error)?;
+    watcher
+      .watch(&root_path, RecursiveMode::Recursive)
+      .map_err(watch_error)?;
+
+    let task = tokio::spawn(run(storage, sink, receiver, settle));
+    debug!(path = ?root_path, "watching filesystem storage");
+    Ok(Self {
+      root_path,
+      _watcher: watcher,
+      task,
+    })
+  }
+}
+
+impl Drop for FsWatcher {
+  fn drop(&mut self) { self.task.abort(); }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn watch_error(error: notify::Error) -> BlobStorageError {
+  BlobStorageError::InvalidConfig(miette::miette!(
+    "failed to watch filesystem storage: {error}"
+  ))
+}
+
+/// Reconciles changed paths once each burst of changes settles.
+async fn run(
+  storage: BlobStorageFilesystem,
+  sink: Arc<dyn BlobEventSink>,
+  mut receiver: mpsc::UnboundedReceiver<PathBuf>,
+  settle: Duration,
+) {
+  while let Some(path) = receiver.recv().await {
+    let mut paths = BTreeSet::from([path]);
+    loop {
+      match tokio::time::timeout(settle, receiver.recv()).await {
+        Ok(Some(path)) => {
+          paths.insert(path);
+        }
+        Ok(None) => return,
+        Err(_) => break,
+      }
+    }
+
+    // files written into a new directory before it's watched have no
+    // events of their own, so directories are walked for them
+    let mut files = BTreeSet::new();
+    for path in paths {
+      if path.is_dir() {
+        files.extend(files_under(path).await);
+      } else {
+        files.insert(path);
+      }
+    }
+
+    let keys = files.iter().filter_map(|p| storage.key_for_path(p));
+    for key in keys {
+      let kind = match storage.reconcile(&key).await {
+        Ok(Reconciled::Unchanged) => continue,
+        Ok(Reconciled::Added) => BlobEventKind::Created,
+        Ok(Reconciled::Modified) => BlobEventKind::Overwritten,
+        Ok(Reconciled::Removed) => BlobEventKind::Deleted,
+        Err(error) => {
+          warn!(%key, %error, "failed to reconcile external change");
+          continue;
+        }
+      };
+      let size = match kind {
+        BlobEventKind::Deleted => None,
+        _ => storage.head(&key).await.ok().flatten().map(|m| m.size),
+      };
+      sink
+        .notify(BlobEvent {
+          timestamp: Utc::now(),
+          kind,
+          key,
+          size,
+        })
+        .await;
+    }
+  }
+}
+
+/// The paths of all files under the directory `dir`.
+async fn files_under(dir: PathBuf) -> Vec<PathBuf> {
+  let mut files = Vec::new();
+  let mut dirs = vec![dir];
+  while let Some(dir) = dirs.pop() {
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+      continue;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+      match entry.file_type().await {
+        Ok(file_type) if file_type.is_dir() => dirs.push(entry.path()),
+        Ok(_) => files.push(entry.path()),
+        Err(_) => {}
+      }
+    }
+  }
+  files
+}
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use model::{IndexValue, Model, RecordId};
+use serde::{Deserialize, Serialize};
+
+/// A webhook which ran out of delivery attempts, kept so it can be inspected
+/// and [redelivered](crate::WebhookEmitter::redeliver).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "webhook_dead_letters",
+  index(name = "endpoint", extract =
+    |m| vec![IndexValue::new_single(&m.endpoint)]
+  ),
+)]
+pub struct DeadLetter {
+  /// The dead letter's ID.
+  #[model(id)]
+  pub id:         RecordId<DeadLetter>,
+  /// The name of the endpoint the webhook was for.
+  pub endpoint:   String,
+  /// The webhook's ID, shared by its deliveries to every endpoint.
+  pub webhook_id: String,
+  /// What happened to the record: `inserted`, `updated` or `deleted`.
+  pub action:     String,
+  /// The ID of the changed record, which the payload is built from when the
+  /// webhook is sent.
+  pub record_id:  String,
+  /// When the change was emitted.
+  pub timestamp:  DateTime<Utc>,
+  /// How many times delivery was attempted.
+  pub attempts:   u32,
+  /// Why the last attempt failed.
+  pub last_error: String,
+  /// When the webhook was dead-lettered.
+  pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,22 @@
+use db::DatabaseError;
+use jobs::JobError;
+use miette::Diagnostic;
+use thiserror::Error;
+
+/// Errors that can occur emitting or delivering webhooks.
+#[derive(Debug, Error, Diagnostic)]
+pub enum WebhookError {
+  /// The database failed, or the dead letter doesn't exist.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Database(#[from] DatabaseError),
+
+  /// The delivery queue failed.
+  #[error(transparent)]
+  #[diagnostic(transparent)]
+  Queue(#[from] JobError),
+
+  /// The HTTP client couldn't be built.
+  #[error("Failed to build HTTP client")]
+  Client(#[source] reqwest::Error),
+}
@@ -0,0 +1,467 @@
+//! Webhooks for changes to a model's records.
+//!
+//! [`WebhookEmitter::emit`] turns a [`ChangeEvent`] into a signed JSON
+//! webhook for each configured [`Endpoint`], queued as a [`Job`] so delivery
+//! survives restarts. [`WebhookEmitter::deliver`] POSTs queued webhooks,
+//! retrying failed deliveries with the queue's backoff until they run out of
+//! attempts, at which point they are moved into the [`DeadLetter`] table for
+//! inspection and [redelivery](WebhookEmitter::redeliver). Deliveries the
+//! queue dead-letters itself, e.g. when a worker crashes during the final
+//! attempt, are moved there too.
+//!
+//! Payloads and signatures follow the Standard Webhooks specification: the
+//! body is `{"type", "timestamp", "data"}`, and each request carries
+//! `webhook-id`, `webhook-timestamp` and `webhook-signature` headers; see
+//! [`sign`]. Delivery is at least once, so receivers should deduplicate on
+//! `webhook-id`.
+//!
+//! Only the changed record's ID is queued and dead-lettered, so records
+//! never leave their table, encrypted fields and all. The body is built when
+//! the webhook is sent, from the record as it is then.
+//!
+//! With the `cdc` feature, [`WebhookEmitter::forward`] emits the changes read
+//! from a Postgres change data capture stream.
+
+mod dead_letter;
+mod error;
+mod signature;
+#[cfg(test)]
+mod tests;
+
+use std::{fmt, marker::PhantomData, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use db::{ChangeEvent, Clock, Database, DatabaseError, SystemClock};
+use jobs::{Job, JobError, JobQueue, Lease, QueueOptions};
+use model::{IndexValue, Model, RecordId, Ulid};
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+pub use self::{
+  dead_letter::{DeadLetter, DeadLetterIndexSelector},
+  error::WebhookError,
+  signature::sign,
+};
+
+/// How long an endpoint has to respond before a delivery fails.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A URL webhooks are posted to, with the secret they're signed with.
+#[derive(Clone)]
+pub struct Endpoint {
+  /// The endpoint's name, which identifies it in the delivery queue and in
+  /// dead letters.
+  pub name:   String,
+  /// The URL webhooks are posted to.
+  pub url:    String,
+  /// The secret webhooks are signed with.
+  pub secret: Vec<u8>,
+}
+
+impl fmt::Debug for Endpoint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Endpoint")
+      .field("name", &self.name)
+      .field("url", &self.url)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Endpoint {
+  /// Creates a new [`Endpoint`].
+  #[must_use]
+  pub fn new(
+    name: impl Into<String>,
+    url: impl Into<String>,
+    secret: impl Into<Vec<u8>>,
+  ) -> Self {
+    Self {
+      name:   name.into(),
+      url:    url.into(),
+      secret: secret.into(),
+    }
+  }
+}
+
+/// The outcome of [`WebhookEmitter::deliver`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeliveryReport {
+  /// How many webhooks were delivered.
+  pub delivered:     usize,
+  /// How many deliveries failed and will be retried.
+  pub retried:       usize,
+  /// How many deliveries failed for the last time, or were dead-lettered by
+  /// the queue, and were moved into the dead letter table.
+  pub dead_lettered: usize,
+}
+
+/// A queued delivery of a webhook to one endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Delivery {
+  endpoint:   String,
+  webhook_id: String,
+  /// What happened to the record, e.g. `updated`
+  action:     String,
+  record_id:  String,
+  timestamp:  DateTime<Utc>,
+}
+
+/// Emits webhooks for changes to records of `M`.
+pub struct WebhookEmitter<M: Model> {
+  records:      Database<M>,
+  queue:        JobQueue<Delivery>,
+  dead_letters: Database<DeadLetter>,
+  endpoints:    Arc<[Endpoint]>,
+  client:       reqwest::Client,
+  clock:        Arc<dyn Clock>,
+  options:      QueueOptions,
+  _model:       PhantomData<fn() -> M>,
+}
+
+impl<M: Model> Clone for WebhookEmitter<M> {
+  fn clone(&self) -> Self {
+    Self {
+      records:      self.records.clone(),
+      queue:        self.queue.clone(),
+      dead_letters: self.dead_letters.clone(),
+      endpoints:    self.endpoints.clone(),
+      client:       self.client.clone(),
+      clock:        self.clock.clone(),
+      options:      self.options,
+      _model:       PhantomData,
+    }
+  }
+}
+
+impl<M: Model> fmt::Debug for WebhookEmitter<M> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("WebhookEmitter")
+      .field("model", &M::TABLE_NAME)
+      .field("endpoints", &self.endpoints)
+      .field("options", &self.options)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<M: Model> WebhookEmitter<M> {
+  /// Creates a new [`WebhookEmitter`] for changes to `records`, sending to
+  /// `endpoints`, queueing deliveries in `jobs` and dead-lettering them into
+  /// `dead_letters`.
+  ///
+  /// Fails if the HTTP client can't be built, e.g. when no TLS backend is
+  /// available.
+  pub fn new(
+    records: Database<M>,
+    jobs: Database<Job>,
+    dead_letters: Database<DeadLetter>,
+    endpoints: Vec<Endpoint>,
+  ) -> Result<Self, WebhookError> {
+    let options = QueueOptions::default();
+    let client = reqwest::Client::builder()
+      .timeout(REQUEST_TIMEOUT)
+      .build()
+      .map_err(WebhookError::Client)?;
+    Ok(Self {
+      records,
+      queue: JobQueue::new(jobs, format!("webhooks/{}", M::TABLE_NAME))
+        .with_options(options),
+      dead_letters,
+      endpoints: endpoints.into(),
+      client,
+      clock: SystemClock::shared(),
+      options,
+      _model: PhantomData,
+    })
+  }
+
+  /// Sets the clock used to timestamp webhooks and schedule retries.
+  #[must_use]
+  pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.queue = self.queue.with_clock(clock.clone());
+    self.clock = clock;
+    self
+  }
+
+  /// Sets the delivery queue's options: how many times delivery is
+  /// attempted, and the backoff between attempts.
+  #[must_use]
+  pub fn with_options(mut self, options: QueueOptions) -> Self {
+    self.queue = self.queue.with_options(options);
+    self.options = options;
+    self
+  }
+
+  /// Sets the HTTP client webhooks are sent with, e.g. to change the request
+  /// timeout.
+  #[must_use]
+  pub fn with_client(mut self, client: reqwest::Client) -> Self {
+    self.client = client;
+    self
+  }
+
+  /// Queues a webhook for `event` to every endpoint, returning its
+  /// `webhook-id`.
+  pub async fn emit(
+    &self,
+    event: &ChangeEvent<M>,
+  ) -> Result<String, WebhookError> {
+    let action = event.action();
+    let webhook_id = Ulid::new().to_string();
+    let timestamp = self.clock.now();
+    for endpoint in self.endpoints.iter() {
+      self
+        .queue
+        .enqueue(&Delivery {
+          endpoint: endpoint.name.clone(),
+          webhook_id: webhook_id.clone(),
+          action: action.to_owned(),
+          record_id: event.id().to_string(),
+          timestamp,
+        })
+        .await?;
+    }
+    debug!(%webhook_id, id = %event.id(), action, "emitted webhook");
+    Ok(webhook_id)
+  }
+
+  /// Sends up to `limit` queued webhooks which are due, oldest first.
+  ///
+  /// A delivery succeeds when the endpoint responds with a 2xx status.
+  /// Deliveries to endpoints which are no longer configured are
+  /// dead-lettered without being attempted, and deliveries the queue
+  /// dead-lettered are moved into the dead letter table.
+  pub async fn deliver(
+    &self,
+    limit: u32,
+  ) -> Result<DeliveryReport, WebhookError> {
+    let mut report = DeliveryReport::default();
+    for lease in self.queue.lease(limit).await? {
+      let delivery = &lease.payload;
+      let Some(endpoint) =
+        self.endpoints.iter().find(|e| e.name == delivery.endpoint)
+      else {
+        self
+          .dead_letter(&lease, "endpoint is no longer configured")
+          .await?;
+        report.dead_lettered += 1;
+        continue;
+      };
+
+      match self.send(endpoint, delivery).await {
+        Ok(()) => {
+          self.queue.complete(&lease).await?;
+          report.delivered += 1;
+        }
+        Err(error) if lease.attempt >= self.options.max_attempts => {
+          self.dead_letter(&lease, &error).await?;
+          report.dead_lettered += 1;
+        }
+        Err(error) => {
+          debug!(
+            webhook_id = %delivery.webhook_id,
+            endpoint = %endpoint.name,
+            %error,
+            "webhook delivery failed, retrying"
+          );
+          self.queue.fail(&lease, &error).await?;
+          report.retried += 1;
+        }
+      }
+    }
+    // leasing dead-letters deliveries whose final lease expired
+    report.dead_lettered += self.collect_dead_jobs().await?;
+    Ok(report)
+  }
+
+  /// Returns the dead-lettered webhooks for the endpoint named `endpoint`,
+  /// first moving any deliveries the queue dead-lettered into the dead letter
+  /// table.
+  pub async fn dead_letters(
+    &self,
+    endpoint: &str,
+  ) -> Result<Vec<DeadLetter>, WebhookError> {
+    self.collect_dead_jobs().await?;
+    Ok(
+      self
+        .dead_letters
+        .find_by_index(
+          DeadLetterIndexSelector::Endpoint,
+          &IndexValue::new_single(endpoint),
+        )
+        .await?,
+    )
+  }
+
+  /// Queues a dead-lettered webhook for delivery again, with its attempts
+  /// reset, and removes the dead letter.
+  pub async fn redeliver(
+    &self,
+    id: RecordId<DeadLetter>,
+  ) -> Result<(), WebhookError> {
+    let dead_letter = self.dead_letters.get_or_error(id).await?;
+    self
+      .queue
+      .enqueue(&Delivery {
+        endpoint:   dead_letter.endpoint,
+        webhook_id: dead_letter.webhook_id,
+        action:     dead_letter.action,
+        record_id:  dead_letter.record_id,
+        timestamp:  dead_letter.timestamp,
+      })
+      .await?;
+    self.dead_letters.delete(id).await?;
+    debug!(%id, "requeued dead-lettered webhook");
+    Ok(())
+  }
+
+  /// Emits a webhook for every change read from `stream`, acknowledging each
+  /// change once its webhook is queued. Only returns on error.
+  #[cfg(feature = "cdc")]
+  pub async fn forward(
+    &self,
+    stream: &mut db::ChangeStream<M>,
+  ) -> Result<(), WebhookError> {
+    loop {
+      let event = stream.recv().await?;
+      self.emit(&event.change).await?;
+      stream.ack(event.position).await?;
+    }
+  }
+
+  /// Builds the JSON payload of a delivery from the changed record as it
+  /// is now, which is `null` once the record is deleted.
+  async fn body(&self, delivery: &Delivery) -> Result<String, String> {
+    let id: RecordId<M> = delivery
+      .record_id
+      .parse()
+      .map_err(|e| format!("invalid record id: {e}"))?;
+    let record = if delivery.action == "deleted" {
+      None
+    } else {
+      self
+        .records
+        .get(id)
+        .await
+        .map_err(|e| format!("failed to read record: {e}"))?
+    };
+    serde_json::to_string(&serde_json::json!({
+      "type": format!("{}.{}", M::TABLE_NAME, delivery.action),
+      "timestamp": delivery.timestamp,
+      "data": {
+        "id": id,
+        "label": record.as_ref().map(Model::display_label),
+        "record": record,
+      },
+    }))
+    .map_err(|e| format!("failed to serialize payload: {e}"))
+  }
+
+  /// POSTs a webhook to `endpoint`, returning why it failed if it did.
+  async fn send(
+    &self,
+    endpoint: &Endpoint,
+    delivery: &Delivery,
+  ) -> Result<(), String> {
+    let body = self.body(delivery).await?;
+    let timestamp = self.clock.now().timestamp();
+    let signature =
+      sign(&endpoint.secret, &delivery.webhook_id, timestamp, &body);
+    let response = self
+      .client
+      .post(&endpoint.url)
+      .header(CONTENT_TYPE, "application/json")
+      .header("webhook-id", &delivery.webhook_id)
+      .header("webhook-timestamp", timestamp.to_string())
+      .header("webhook-signature", signature)
+      .body(body)
+      .send()
+      .await
+      .map_err(|e| format!("request failed: {e}"))?;
+
+    let status = response.status();
+    if status.is_success() {
+      Ok(())
+    } else {
+      Err(format!("endpoint responded with {status}"))
+    }
+  }
+
+  /// Moves the deliveries the queue dead-lettered into the dead letter
+  /// table, returning how many were moved.
+  ///
+  /// Each dead letter takes its job's ID, so moving the same job twice
+  /// leaves one dead letter. Jobs whose payload isn't a delivery stay in the
+  /// queue.
+  async fn collect_dead_jobs(&self) -> Result<usize, WebhookError> {
+    let mut moved = 0;
+    for job in self.queue.dead_letters().await? {
+      let delivery: Delivery = match serde_json::from_value(job.payload) {
+        Ok(delivery) => delivery,
+        Err(e) => {
+          warn!(id = %job.id, error = %e, "dead job is not a webhook");
+          continue;
+        }
+      };
+      warn!(
+        webhook_id = %delivery.webhook_id,
+        endpoint = %delivery.endpoint,
+        attempts = job.attempts,
+        "moving webhook dead-lettered by the queue"
+      );
+      self
+        .dead_letters
+        .upsert(&DeadLetter {
+          id:         RecordId::from_ulid(job.id.inner()),
+          endpoint:   delivery.endpoint,
+          webhook_id: delivery.webhook_id,
+          action:     delivery.action,
+          record_id:  delivery.record_id,
+          timestamp:  delivery.timestamp,
+          attempts:   job.attempts,
+          last_error: job.last_error.unwrap_or_default(),
+          created_at: self.clock.now(),
+        })
+        .await?;
+      match self.queue.discard(job.id).await {
+        Ok(()) => moved += 1,
+        // another emitter moved it first
+        Err(JobError::Database(DatabaseError::NotFound(_))) => {}
+        Err(e) => return Err(e.into()),
+      }
+    }
+    Ok(moved)
+  }
+
+  /// Moves a leased delivery into the dead letter table.
+  async fn dead_letter(
+    &self,
+    lease: &Lease<Delivery>,
+    error: &str,
+  ) -> Result<(), WebhookError> {
+    let delivery = &lease.payload;
+    warn!(
+      webhook_id = %delivery.webhook_id,
+      endpoint = %delivery.endpoint,
+      attempts = lease.attempt,
+      %error,
+      "dead-lettering webhook"
+    );
+    self
+      .dead_letters
+      .insert(&DeadLetter {
+        id:         RecordId::new(),
+        endpoint:   delivery.endpoint.clone(),
+        webhook_id: delivery.webhook_id.clone(),
+        action:     delivery.action.clone(),
+        record_id:  delivery.record_id.clone(),
+        timestamp:  delivery.timestamp,
+        attempts:   lease.attempt,
+        last_error: error.to_owned(),
+        created_at: self.clock.now(),
+      })
+      .await?;
+    self.queue.complete(lease).await?;
+    Ok(())
+  }
+}
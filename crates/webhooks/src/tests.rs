@@ -0,0 +1,315 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use db::{ChangeEvent, Database, ManualClock, MockDatabase};
+use jobs::{Backoff, JobQueue, QueueOptions};
+use model::{Model, RecordId};
+use serde::{Deserialize, Serialize};
+use tokio::{
+  io::{AsyncReadExt, AsyncWriteExt},
+  net::TcpListener,
+};
+
+use crate::{DeliveryReport, Endpoint, WebhookEmitter, sign};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Model)]
+#[model(table = "orders")]
+struct Order {
+  #[model(id)]
+  id:    RecordId<Order>,
+  total: u32,
+}
+
+const SECRET: &[u8] = b"shh";
+
+const OPTIONS: QueueOptions = QueueOptions {
+  visibility_timeout: Duration::from_secs(30),
+  max_attempts:       2,
+  backoff:            Backoff {
+    base: Duration::from_secs(10),
+    max:  Duration::from_secs(10),
+  },
+};
+
+/// A request received by a [`Receiver`].
+#[derive(Clone)]
+struct Received {
+  headers: HashMap<String, String>,
+  body:    String,
+}
+
+/// An HTTP server which records the requests it receives and responds with
+/// the given statuses in turn, then 200.
+#[derive(Clone)]
+struct Receiver {
+  url:      String,
+  received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl Receiver {
+  async fn start(statuses: &[u16]) -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let mut statuses: VecDeque<u16> = statuses.iter().copied().collect();
+
+    let log = received.clone();
+    tokio::spawn(async move {
+      loop {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let request = read_request(&mut socket).await;
+        log.lock().unwrap().push(request);
+        let status = statuses.pop_front().unwrap_or(200);
+        let response = format!(
+          "HTTP/1.1 {status} Status\r\ncontent-length: 0\r\nconnection: \
+           close\r\n\r\n"
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+      }
+    });
+    Self { url, received }
+  }
+
+  fn count(&self) -> usize { self.received.lock().unwrap().len() }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Received {
+  let mut buf = Vec::new();
+  let header_end = loop {
+    let mut chunk = [0; 1024];
+    let n = socket.read(&mut chunk).await.unwrap();
+    buf.extend_from_slice(&chunk[..n]);
+    if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+      break end + 4;
+    }
+  };
+
+  let head = String::from_utf8(buf[..header_end].to_vec()).unwrap();
+  let headers: HashMap<String, String> = head
+    .lines()
+    .skip(1)
+    .filter_map(|line| line.split_once(": "))
+    .map(|(name, value)| (name.to_lowercase(), value.to_owned()))
+    .collect();
+  let length: usize = headers["content-length"].parse().unwrap();
+  while buf.len() < header_end + length {
+    let mut chunk = [0; 1024];
+    let n = socket.read(&mut chunk).await.unwrap();
+    buf.extend_from_slice(&chunk[..n]);
+  }
+
+  Received {
+    headers,
+    body: String::from_utf8(buf[header_end..].to_vec()).unwrap(),
+  }
+}
+
+fn emitter(
+  receiver: &Receiver,
+  clock: &ManualClock,
+  orders: &Database<Order>,
+) -> WebhookEmitter<Order> {
+  let jobs = Database::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  WebhookEmitter::new(orders.clone(), jobs, Database::new_mock(), vec![
+    Endpoint::new("billing", receiver.url.clone(), SECRET),
+  ])
+  .unwrap()
+  .with_clock(Arc::new(clock.clone()))
+  .with_options(OPTIONS)
+}
+
+fn order() -> Order {
+  Order {
+    id:    RecordId::from_ulid_u128(1),
+    total: 42,
+  }
+}
+
+#[tokio::test]
+async fn test_deliver_signed_webhook() {
+  let receiver = Receiver::start(&[]).await;
+  let clock = ManualClock::default();
+  let orders = Database::new_mock();
+  let emitter = emitter(&receiver, &clock, &orders);
+
+  orders.insert(&order()).await.unwrap();
+  let webhook_id = emitter.emit(&ChangeEvent::Inserted(order())).await.unwrap();
+  let report = emitter.deliver(10).await.unwrap();
+  assert_eq!(report, DeliveryReport {
+    delivered: 1,
+    ..DeliveryReport::default()
+  });
+
+  let request = receiver.received.lock().unwrap()[0].clone();
+  assert_eq!(request.headers["webhook-id"], webhook_id);
+  assert_eq!(request.headers["content-type"], "application/json");
+  let timestamp: i64 = request.headers["webhook-timestamp"].parse().unwrap();
+  assert_eq!(
+    request.headers["webhook-signature"],
+    sign(SECRET, &webhook_id, timestamp, &request.body)
+  );
+
+  let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+  assert_eq!(body["type"], "orders.inserted");
+  assert_eq!(body["data"]["id"], order().id.to_string());
+  assert_eq!(body["data"]["label"], order().id.to_string());
+  assert_eq!(body["data"]["record"]["total"], 42);
+
+  // delivered webhooks leave the queue
+  assert_eq!(
+    emitter.deliver(10).await.unwrap(),
+    DeliveryReport::default()
+  );
+}
+
+#[tokio::test]
+async fn test_failed_delivery_retries_then_dead_letters() {
+  let receiver = Receiver::start(&[500, 503, 500]).await;
+  let clock = ManualClock::default();
+  let emitter = emitter(&receiver, &clock, &Database::new_mock());
+  emitter
+    .emit(&ChangeEvent::Deleted(order().id))
+    .await
+    .unwrap();
+
+  assert_eq!(emitter.deliver(10).await.unwrap().retried, 1);
+  // not retried until the backoff passes
+  assert_eq!(
+    emitter.deliver(10).await.unwrap(),
+    DeliveryReport::default()
+  );
+  clock.advance(OPTIONS.backoff.base);
+  assert_eq!(emitter.deliver(10).await.unwrap().dead_lettered, 1);
+  assert_eq!(receiver.count(), 2);
+
+  let dead = emitter.dead_letters("billing").await.unwrap();
+  assert_eq!(dead.len(), 1);
+  assert_eq!(dead[0].attempts, 2);
+  assert!(dead[0].last_error.contains("503"));
+  assert_eq!(dead[0].action, "deleted");
+  assert_eq!(dead[0].record_id, order().id.to_string());
+
+  // redelivered with its attempts reset
+  emitter.redeliver(dead[0].id).await.unwrap();
+  assert!(emitter.dead_letters("billing").await.unwrap().is_empty());
+  assert_eq!(emitter.deliver(10).await.unwrap().retried, 1);
+  clock.advance(OPTIONS.backoff.base);
+  assert_eq!(emitter.deliver(10).await.unwrap().delivered, 1);
+  assert_eq!(receiver.count(), 4);
+}
+
+#[tokio::test]
+async fn test_queue_dead_letters_reach_the_dead_letter_table() {
+  let receiver = Receiver::start(&[]).await;
+  let clock = ManualClock::default();
+  let jobs = Database::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let emitter = WebhookEmitter::new(
+    Database::new_mock(),
+    jobs.clone(),
+    Database::new_mock(),
+    vec![Endpoint::new("billing", receiver.url.clone(), SECRET)],
+  )
+  .unwrap()
+  .with_clock(Arc::new(clock.clone()))
+  .with_options(OPTIONS);
+  emitter
+    .emit(&ChangeEvent::Deleted(order().id))
+    .await
+    .unwrap();
+
+  // a worker leases the delivery and crashes, on every attempt
+  let crashing: JobQueue<serde_json::Value> =
+    JobQueue::new(jobs, "webhooks/orders")
+      .with_clock(Arc::new(clock.clone()))
+      .with_options(OPTIONS);
+  for _ in 0..OPTIONS.max_attempts {
+    assert_eq!(crashing.lease(1).await.unwrap().len(), 1);
+    clock.advance(OPTIONS.visibility_timeout);
+  }
+
+  assert_eq!(emitter.deliver(10).await.unwrap(), DeliveryReport {
+    dead_lettered: 1,
+    ..DeliveryReport::default()
+  });
+  assert!(crashing.dead_letters().await.unwrap().is_empty());
+  let dead = emitter.dead_letters("billing").await.unwrap();
+  assert_eq!(dead.len(), 1);
+  assert_eq!(dead[0].attempts, 2);
+  assert_eq!(dead[0].last_error, "lease expired on final attempt");
+
+  emitter.redeliver(dead[0].id).await.unwrap();
+  assert_eq!(emitter.deliver(10).await.unwrap().delivered, 1);
+  assert_eq!(receiver.count(), 1);
+}
+
+#[tokio::test]
+async fn test_unknown_endpoint_dead_letters_immediately() {
+  let receiver = Receiver::start(&[]).await;
+  let clock = ManualClock::default();
+  let jobs = Database::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let dead_letters = Database::new_mock();
+
+  let orders = Database::new_mock();
+
+  let old = WebhookEmitter::new(
+    orders.clone(),
+    jobs.clone(),
+    dead_letters.clone(),
+    vec![Endpoint::new("legacy", receiver.url.clone(), SECRET)],
+  )
+  .unwrap()
+  .with_clock(Arc::new(clock.clone()));
+  old.emit(&ChangeEvent::Updated(order())).await.unwrap();
+
+  let new = WebhookEmitter::new(orders, jobs, dead_letters, Vec::new())
+    .unwrap()
+    .with_clock(Arc::new(clock.clone()));
+  assert_eq!(new.deliver(10).await.unwrap().dead_lettered, 1);
+  assert_eq!(receiver.count(), 0);
+  assert_eq!(new.dead_letters("legacy").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_queued_webhooks_hold_only_the_record_id() {
+  let receiver = Receiver::start(&[]).await;
+  let clock = ManualClock::default();
+  let orders = Database::new_mock();
+  let jobs = Database::from_backend(
+    MockDatabase::new().with_clock(Arc::new(clock.clone())),
+  );
+  let emitter = WebhookEmitter::new(
+    orders.clone(),
+    jobs.clone(),
+    Database::new_mock(),
+    vec![Endpoint::new("billing", receiver.url.clone(), SECRET)],
+  )
+  .unwrap()
+  .with_clock(Arc::new(clock.clone()));
+
+  orders.insert(&order()).await.unwrap();
+  emitter.emit(&ChangeEvent::Updated(order())).await.unwrap();
+  let queued = serde_json::to_string(&jobs.list_all().await.unwrap()).unwrap();
+  assert!(queued.contains(&order().id.to_string()));
+  assert!(!queued.contains("total"));
+
+  // the body is built from the record as it is when sent
+  let updated = Order {
+    total: 43,
+    ..order()
+  };
+  orders.update(&updated).await.unwrap();
+  assert_eq!(emitter.deliver(10).await.unwrap().delivered, 1);
+  let request = receiver.received.lock().unwrap()[0].clone();
+  let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+  assert_eq!(body["type"], "orders.updated");
+  assert_eq!(body["data"]["record"]["total"], 43);
+}
@@ -0,0 +1,62 @@
+use axum::{
+  Json,
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use db::DatabaseError;
+use storage::BlobStorageError;
+use storage_axum::BlobError;
+use tracing::error;
+
+/// An error from handling a request, convertible into a response.
+#[derive(Debug)]
+pub(crate) enum ApiError {
+  /// The database failed.
+  Database(DatabaseError),
+  /// Blob storage failed.
+  Blob(BlobError),
+  /// An artifact with the given name already exists.
+  NameTaken(String),
+}
+
+impl From<DatabaseError> for ApiError {
+  fn from(error: DatabaseError) -> Self { ApiError::Database(error) }
+}
+
+impl From<BlobError> for ApiError {
+  fn from(error: BlobError) -> Self { ApiError::Blob(error) }
+}
+
+impl From<BlobStorageError> for ApiError {
+  fn from(error: BlobStorageError) -> Self {
+    ApiError::Blob(BlobError::Storage(error))
+  }
+}
+
+impl IntoResponse for ApiError {
+  fn into_response(self) -> Response {
+    match self {
+      ApiError::Database(error) => {
+        let status = match error {
+          DatabaseError::NotFound(_) => StatusCode::NOT_FOUND,
+          DatabaseError::UniqueViolation { .. } | DatabaseError::Conflict(_) => {
+            StatusCode::CONFLICT
+          }
+          _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        // don't leak backend details to the client
+        if status.is_server_error() {
+          error!(%error, "database operation failed");
+          return status.into_response();
+        }
+        (status, Json(error)).into_response()
+      }
+      ApiError::Blob(error) => error.into_response(),
+      ApiError::NameTaken(name) => (
+        StatusCode::CONFLICT,
+        format!("an artifact named `{name}` already exists"),
+      )
+        .into_response(),
+    }
+  }
+}
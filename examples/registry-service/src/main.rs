@@ -0,0 +1,233 @@
+//! An object registry service, composing a [`Database`] of artifact metadata
+//! with [`BlobStorage`] for their contents behind an HTTP API.
+//!
+//! - `POST /artifacts?name=..` uploads the request body as a new artifact.
+//! - `GET /artifacts?limit=..&offset=..` lists artifacts a page at a time.
+//! - `GET /artifacts/{id}` returns an artifact's metadata.
+//! - `GET /artifacts/{id}/content` redirects to a pre-signed URL for the
+//!   artifact's contents, or streams them if the backend can't sign URLs.
+//!
+//! The database and storage backends are configured from `REGISTRY_DB_*` and
+//! `REGISTRY_STORAGE_*` variables; set `REGISTRY_DB_BACKEND=mock` and
+//! `REGISTRY_STORAGE_BACKEND=memory` to run without any infrastructure.
+
+mod error;
+#[cfg(test)]
+mod tests;
+
+use std::time::Duration;
+
+use axum::{
+  Json, Router,
+  extract::{Path, Query, State},
+  http::{HeaderMap, HeaderValue, StatusCode, header},
+  response::{IntoResponse, Redirect, Response},
+  routing::get,
+};
+use chrono::{DateTime, Utc};
+use db::{Database, DbConfig, Page};
+use miette::{Context, IntoDiagnostic, Result};
+use model::{IndexValue, Model, RecordId};
+use serde::{Deserialize, Serialize};
+use storage::{
+  BlobKey, BlobStorage, BlobStorageError, StorageConfig, UploadOptions,
+};
+use storage_axum::{BlobBody, blob_response, put_body};
+use tracing::warn;
+
+use self::error::ApiError;
+
+/// The default number of artifacts in a listed page.
+const DEFAULT_PAGE_SIZE: u32 = 50;
+/// The maximum number of artifacts in a listed page.
+const MAX_PAGE_SIZE: u32 = 500;
+/// How long pre-signed download URLs stay valid.
+const DOWNLOAD_URL_EXPIRY: Duration = Duration::from_mins(5);
+
+/// An uploaded artifact, whose contents are stored at its
+/// [`blob_key`](Artifact::blob_key).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Model)]
+#[model(
+  table = "artifacts",
+  index(name = "name", unique, extract =
+    |m| vec![IndexValue::new_single(&m.name)]
+  ),
+)]
+struct Artifact {
+  #[model(id)]
+  id:           RecordId<Artifact>,
+  name:         String,
+  content_type: String,
+  size:         u64,
+  etag:         Option<String>,
+  uploaded_at:  DateTime<Utc>,
+}
+
+impl Artifact {
+  /// The key the artifact's contents are stored at.
+  fn blob_key(&self) -> BlobKey { blob_key(self.id) }
+}
+
+/// The key the contents of the artifact with the given ID are stored at.
+fn blob_key(id: RecordId<Artifact>) -> BlobKey {
+  BlobKey::new(format!("artifacts/{id}"))
+}
+
+/// The shared state of the service.
+#[derive(Clone)]
+struct AppState {
+  db:      Database<Artifact>,
+  storage: BlobStorage,
+}
+
+/// Builds the service's router.
+fn app(state: AppState) -> Router {
+  Router::new()
+    .route("/artifacts", get(list_artifacts).post(upload_artifact))
+    .route("/artifacts/{id}", get(get_artifact))
+    .route("/artifacts/{id}/content", get(download_artifact))
+    .with_state(state)
+}
+
+/// Parameters for uploading an artifact.
+#[derive(Debug, Deserialize)]
+struct UploadParams {
+  name: String,
+}
+
+/// Uploads the request body as a new artifact.
+// axum extractors are taken by value
+#[allow(clippy::needless_pass_by_value)]
+async fn upload_artifact(
+  State(state): State<AppState>,
+  Query(UploadParams { name }): Query<UploadParams>,
+  headers: HeaderMap,
+  body: BlobBody,
+) -> Result<(StatusCode, Json<Artifact>), ApiError> {
+  // check before uploading, so a taken name doesn't cost an upload
+  let name_key = IndexValue::new_single(&name);
+  if state
+    .db
+    .exists_by_unique_index(ArtifactIndexSelector::Name, &name_key)
+    .await?
+  {
+    return Err(ApiError::NameTaken(name));
+  }
+
+  let id = RecordId::new();
+  let key = blob_key(id);
+  put_body(&state.storage, &key, body, UploadOptions::default()).await?;
+
+  let metadata = state
+    .storage
+    .head(&key)
+    .await?
+    .ok_or_else(|| BlobStorageError::NotFound(key.clone()))?;
+  let content_type = headers
+    .get(header::CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .map_or_else(|| storage_axum::content_type_for(&key), ToOwned::to_owned);
+  let artifact = Artifact {
+    id,
+    name,
+    content_type,
+    size: metadata.size,
+    etag: metadata.etag,
+    uploaded_at: Utc::now(),
+  };
+
+  // the name may have been taken while uploading
+  if let Err(error) = state.db.insert(&artifact).await {
+    if let Err(cleanup_error) = state.storage.delete(&key).await {
+      warn!(%key, error = %cleanup_error, "Failed to delete orphaned blob");
+    }
+    return Err(error.into());
+  }
+
+  Ok((StatusCode::CREATED, Json(artifact)))
+}
+
+/// Pagination parameters for listing artifacts.
+#[derive(Debug, Deserialize)]
+struct ListParams {
+  limit:  Option<u32>,
+  offset: Option<u32>,
+}
+
+/// Lists a page of artifacts.
+async fn list_artifacts(
+  State(state): State<AppState>,
+  Query(params): Query<ListParams>,
+) -> Result<Json<Page<Artifact>>, ApiError> {
+  let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+  let offset = params.offset.unwrap_or(0);
+  Ok(Json(state.db.list_page(limit, offset).await?))
+}
+
+/// Returns the metadata of an artifact.
+async fn get_artifact(
+  State(state): State<AppState>,
+  Path(id): Path<RecordId<Artifact>>,
+) -> Result<Json<Artifact>, ApiError> {
+  Ok(Json(state.db.get_or_error(id).await?))
+}
+
+/// Redirects to a pre-signed URL for an artifact's contents, falling back to
+/// streaming them for backends without HTTP pre-signed URLs.
+#[allow(clippy::needless_pass_by_value)]
+async fn download_artifact(
+  State(state): State<AppState>,
+  Path(id): Path<RecordId<Artifact>>,
+  headers: HeaderMap,
+) -> Result<Response, ApiError> {
+  let artifact = state.db.get_or_error(id).await?;
+  let key = artifact.blob_key();
+
+  match state
+    .storage
+    .get_presigned_url(&key, DOWNLOAD_URL_EXPIRY)
+    .await
+  {
+    Ok(url) if url.starts_with("https://") || url.starts_with("http://") => {
+      return Ok(Redirect::temporary(&url).into_response());
+    }
+    Ok(_) | Err(BlobStorageError::Unsupported(_)) => {}
+    Err(error) => return Err(error.into()),
+  }
+
+  let mut response = blob_response(&state.storage, &key, &headers).await?;
+  if let Ok(content_type) = HeaderValue::from_str(&artifact.content_type) {
+    response
+      .headers_mut()
+      .insert(header::CONTENT_TYPE, content_type);
+  }
+  Ok(response)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  let db_config = DbConfig::from_env("REGISTRY_DB")
+    .context("failed to read database config")?;
+  let db = Database::from_config(db_config)
+    .await
+    .context("failed to initialize database")?;
+
+  let storage_config = StorageConfig::from_env("REGISTRY_STORAGE")
+    .context("failed to read storage config")?;
+  let storage = BlobStorage::from_config(storage_config)
+    .await
+    .context("failed to initialize storage")?;
+
+  let addr = std::env::var("REGISTRY_ADDR")
+    .unwrap_or_else(|_| "127.0.0.1:3000".to_owned());
+  let listener = tokio::net::TcpListener::bind(&addr)
+    .await
+    .into_diagnostic()
+    .with_context(|| format!("failed to bind to `{addr}`"))?;
+  println!("listening on {addr}");
+
+  axum::serve(listener, app(AppState { db, storage }))
+    .await
+    .into_diagnostic()
+    .context("server failed")
+}
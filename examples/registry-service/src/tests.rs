@@ -0,0 +1,104 @@
+use axum::{
+  body::{Body, to_bytes},
+  http::Request,
+};
+use tower::ServiceExt;
+
+use super::*;
+
+fn test_app() -> Router {
+  app(AppState {
+    db:      Database::new_mock(),
+    storage: BlobStorage::new_memory(),
+  })
+}
+
+async fn send(app: &Router, request: Request<Body>) -> Response {
+  app.clone().oneshot(request).await.unwrap()
+}
+
+async fn body_json(response: Response) -> serde_json::Value {
+  let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+  serde_json::from_slice(&bytes).unwrap()
+}
+
+fn upload_request(name: &str, data: &'static str) -> Request<Body> {
+  Request::post(format!("/artifacts?name={name}"))
+    .header(header::CONTENT_TYPE, "text/plain")
+    .body(Body::from(data))
+    .unwrap()
+}
+
+fn get_request(uri: &str) -> Request<Body> {
+  Request::get(uri).body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_upload_and_download() {
+  let app = test_app();
+
+  let response = send(&app, upload_request("hello.txt", "hello")).await;
+  assert_eq!(response.status(), StatusCode::CREATED);
+  let artifact = body_json(response).await;
+  assert_eq!(artifact["name"], "hello.txt");
+  assert_eq!(artifact["size"], 5);
+  let id = artifact["id"].as_str().unwrap();
+
+  let response = send(&app, get_request(&format!("/artifacts/{id}"))).await;
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(body_json(response).await, artifact);
+
+  // the memory backend can't sign HTTP URLs, so the contents are streamed
+  let response =
+    send(&app, get_request(&format!("/artifacts/{id}/content"))).await;
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain");
+  let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+  assert_eq!(&bytes[..], b"hello");
+}
+
+#[tokio::test]
+async fn test_duplicate_name_conflicts() {
+  let app = test_app();
+
+  let response = send(&app, upload_request("dup", "one")).await;
+  assert_eq!(response.status(), StatusCode::CREATED);
+  let response = send(&app, upload_request("dup", "two")).await;
+  assert_eq!(response.status(), StatusCode::CONFLICT);
+
+  let page = body_json(send(&app, get_request("/artifacts")).await).await;
+  assert_eq!(page["total"], 1);
+}
+
+#[tokio::test]
+async fn test_list_pagination() {
+  let app = test_app();
+  for name in ["a", "b", "c"] {
+    send(&app, upload_request(name, "data")).await;
+  }
+
+  let response = send(&app, get_request("/artifacts?limit=2")).await;
+  assert_eq!(response.status(), StatusCode::OK);
+  let page = body_json(response).await;
+  assert_eq!(page["items"].as_array().unwrap().len(), 2);
+  assert_eq!(page["total"], 3);
+  assert_eq!(page["has_more"], true);
+
+  let page =
+    body_json(send(&app, get_request("/artifacts?limit=2&offset=2")).await)
+      .await;
+  assert_eq!(page["items"].as_array().unwrap().len(), 1);
+  assert_eq!(page["has_more"], false);
+}
+
+#[tokio::test]
+async fn test_missing_artifact() {
+  let app = test_app();
+  let id = RecordId::<Artifact>::new();
+
+  let response = send(&app, get_request(&format!("/artifacts/{id}"))).await;
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  let response =
+    send(&app, get_request(&format!("/artifacts/{id}/content"))).await;
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
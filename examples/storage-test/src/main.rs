@@ -88,7 +88,10 @@ async fn main() -> Result<()> {
   let stream = Belt::new(ReaderStream::new(file));
   let counter = stream.counter();
   bucket
-    .put_stream(&key, Box::pin(stream), UploadOptions { overwrite: true })
+    .put_stream(&key, Box::pin(stream), UploadOptions {
+      overwrite: true,
+      ..UploadOptions::default()
+    })
     .await
     .with_context(|| format!("failed to put object at key `{key}`"))?;
   println!(